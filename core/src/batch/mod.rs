@@ -17,6 +17,41 @@
 */
 
 //! Combinators that can be applied to batches of packets within a pipeline.
+//!
+//! A pipeline starts with a [`Poll`] batch that receives packets from a
+//! [`PacketRx`], chains the per-packet processing steps as combinators,
+//! and ends with [`send`] to a [`PacketTx`]. Each step decides what
+//! happens to the packet through its [`Disposition`]. An `Act` packet
+//! moves on to the next step. A `Drop` or `Abort` packet short-circuits
+//! the rest of the steps, and so does an `Emit` packet, which was already
+//! redirected to a different `PacketTx` with [`emit`].
+//!
+//! # Example
+//!
+//! ```
+//! fn install(q: PortQueue) -> impl Pipeline {
+//!     Poll::new(q.clone())
+//!         .map(|packet| packet.parse::<Ethernet>())
+//!         .filter(|packet| packet.ether_type() == EtherTypes::Ipv4)
+//!         .map(|packet| packet.parse::<Ipv4>())
+//!         .for_each(|packet| {
+//!             debug!(?packet);
+//!             Ok(())
+//!         })
+//!         .send(q)
+//! }
+//! ```
+//!
+//! Each pipeline runs on a single core. The [`Runtime`] drives all the
+//! installed pipelines and stops them together on shutdown.
+//!
+//! [`Poll`]: crate::batch::Poll
+//! [`PacketRx`]: crate::batch::PacketRx
+//! [`PacketTx`]: crate::batch::PacketTx
+//! [`send`]: crate::batch::Batch::send
+//! [`emit`]: crate::batch::Batch::emit
+//! [`Disposition`]: crate::batch::Disposition
+//! [`Runtime`]: crate::Runtime
 
 mod emit;
 mod filter;
@@ -25,6 +60,8 @@ mod for_each;
 mod group_by;
 mod inspect;
 mod map;
+mod packet_batch;
+mod packet_filter;
 mod poll;
 mod replace;
 mod rxtx;
@@ -37,6 +74,8 @@ pub use self::for_each::*;
 pub use self::group_by::*;
 pub use self::inspect::*;
 pub use self::map::*;
+pub use self::packet_batch::*;
+pub use self::packet_filter::*;
 pub use self::poll::*;
 pub use self::replace::*;
 pub use self::rxtx::*;
@@ -733,4 +772,53 @@ mod tests {
         pipeline.run_once();
         assert!(rx2.try_recv().is_ok());
     }
+
+    #[capsule::test]
+    fn packet_batch_stage() {
+        let (mut tx, mut rx) = mpsc::channel();
+        let packets = [&IPV4_UDP_PACKET[..], &IPV4_TCP_PACKET, &ICMPV4_PACKET]
+            .iter()
+            .map(|bytes| Mbuf::from_bytes(bytes).unwrap())
+            .collect::<Vec<_>>();
+        tx.transmit(packets);
+
+        let mut batch = PacketBatch::new();
+        assert_eq!(DEFAULT_BATCH_CAPACITY, batch.capacity());
+        assert_eq!(3, batch.fill_from_rx(&mut rx));
+
+        // only udp is kept
+        let mut batch = batch.apply(|packet| {
+            let v4 = packet.parse::<Ipv4>().ok()?;
+            if v4.protocol() == ProtocolNumbers::Udp {
+                Some(v4.deparse())
+            } else {
+                None
+            }
+        });
+        assert_eq!(1, batch.len());
+
+        let (mut tx2, rx2) = mpsc::channel();
+        assert_eq!(1, batch.flush_to_tx(&mut tx2));
+        assert!(batch.is_empty());
+        assert_eq!(1, rx2.try_iter().count());
+    }
+
+    #[capsule::test]
+    fn packet_batch_filter() {
+        let mut batch = PacketBatch::new();
+        batch.extend(
+            [&IPV4_UDP_PACKET[..], &IPV4_TCP_PACKET, &ICMPV4_PACKET]
+                .iter()
+                .map(|bytes| {
+                    Mbuf::from_bytes(bytes)
+                        .unwrap()
+                        .parse::<Ethernet>()
+                        .unwrap()
+                }),
+        );
+
+        let filter = FilterBuilder::new().tcp_dst_port(23).build();
+        let batch = batch.filter(filter.as_ref());
+        assert_eq!(1, batch.len());
+    }
 }
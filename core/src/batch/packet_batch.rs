@@ -0,0 +1,187 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{PacketFilter, PacketRx, PacketTx};
+use crate::packets::{Ethernet, Packet};
+use crate::Mbuf;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// The default number of packets a `PacketBatch` holds.
+pub const DEFAULT_BATCH_CAPACITY: usize = 32;
+
+/// An owned burst of Ethernet packets processed together as one stage.
+///
+/// Unlike the lazy [`Batch`] combinators, a `PacketBatch` holds all the
+/// packets of the burst at once, so each stage runs over the whole burst
+/// before the next stage starts. This keeps the code and data of a stage
+/// hot in the cache.
+///
+/// # Example
+///
+/// ```
+/// let mut batch = PacketBatch::new();
+/// batch.fill_from_rx(&mut q);
+/// let mut batch = batch.apply(|packet| {
+///     if packet.ether_type() == EtherTypes::Ipv4 {
+///         Some(packet)
+///     } else {
+///         None
+///     }
+/// });
+/// batch.flush_to_tx(&mut q);
+/// ```
+///
+/// [`Batch`]: crate::batch::Batch
+pub struct PacketBatch {
+    packets: VecDeque<Ethernet>,
+    capacity: usize,
+}
+
+impl PacketBatch {
+    /// Creates a new empty batch with the default capacity of 32.
+    pub fn new() -> Self {
+        PacketBatch::with_capacity(DEFAULT_BATCH_CAPACITY)
+    }
+
+    /// Creates a new empty batch with room for `capacity` packets.
+    pub fn with_capacity(capacity: usize) -> Self {
+        PacketBatch {
+            packets: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Returns the number of packets the batch is sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of packets in the batch.
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    /// Returns whether the batch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Appends a packet to the end of the batch.
+    pub fn push(&mut self, packet: Ethernet) {
+        self.packets.push_back(packet);
+    }
+
+    /// Returns an iterator over the packets.
+    pub fn iter(&self) -> impl Iterator<Item = &Ethernet> + '_ {
+        self.packets.iter()
+    }
+
+    /// Returns an iterator that allows modifying the packets.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Ethernet> + '_ {
+        self.packets.iter_mut()
+    }
+
+    /// Receives a burst of packets and appends them to the batch. Returns
+    /// the number of packets appended.
+    ///
+    /// Packets that are not valid Ethernet frames are dropped.
+    pub fn fill_from_rx<Rx: PacketRx>(&mut self, rx: &mut Rx) -> usize {
+        let before = self.packets.len();
+        self.packets.extend(
+            rx.receive()
+                .into_iter()
+                .filter_map(|mbuf| mbuf.parse::<Ethernet>().ok()),
+        );
+        self.packets.len() - before
+    }
+
+    /// Applies the transform to every packet in the batch. Packets the
+    /// transform returns `None` for are dropped.
+    pub fn apply<F>(self, f: F) -> PacketBatch
+    where
+        F: Fn(Ethernet) -> Option<Ethernet>,
+    {
+        let capacity = self.capacity;
+        let packets = self.filter_map(f).collect::<VecDeque<_>>();
+        PacketBatch { packets, capacity }
+    }
+
+    /// Keeps only the packets the filter matches. The other packets are
+    /// dropped.
+    pub fn filter(self, f: &dyn PacketFilter) -> PacketBatch {
+        let capacity = self.capacity;
+        let packets = self
+            .packets
+            .into_iter()
+            .filter(|packet| f.matches(packet))
+            .collect::<VecDeque<_>>();
+        PacketBatch { packets, capacity }
+    }
+
+    /// Transmits all the packets in the batch, leaving the batch empty.
+    /// Returns the number of packets handed to the transmit queue.
+    ///
+    /// Packets the queue can't accept are freed by the queue.
+    pub fn flush_to_tx<Tx: PacketTx>(&mut self, tx: &mut Tx) -> usize {
+        let mbufs = self
+            .packets
+            .drain(..)
+            .map(|packet| packet.reset())
+            .collect::<Vec<Mbuf>>();
+
+        let len = mbufs.len();
+        if len > 0 {
+            tx.transmit(mbufs);
+        }
+        len
+    }
+}
+
+impl Default for PacketBatch {
+    fn default() -> Self {
+        PacketBatch::new()
+    }
+}
+
+impl Iterator for PacketBatch {
+    type Item = Ethernet;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.packets.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.packets.len(), Some(self.packets.len()))
+    }
+}
+
+impl Extend<Ethernet> for PacketBatch {
+    fn extend<I: IntoIterator<Item = Ethernet>>(&mut self, iter: I) {
+        self.packets.extend(iter);
+    }
+}
+
+impl fmt::Debug for PacketBatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketBatch")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
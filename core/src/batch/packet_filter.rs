@@ -0,0 +1,373 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::net::{Cidr, Ipv4Cidr};
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::v6::{Ipv6, Ipv6Packet};
+use crate::packets::ip::ProtocolNumbers;
+use crate::packets::{EtherType, EtherTypes, Ethernet, Packet, Tcp4, Tcp6};
+use std::fmt;
+
+/// A predicate that selects packets.
+///
+/// Filters are combined with [`AndFilter`], [`OrFilter`] and
+/// [`NotFilter`], or built with [`FilterBuilder`].
+///
+/// [`AndFilter`]: AndFilter
+/// [`OrFilter`]: OrFilter
+/// [`NotFilter`]: NotFilter
+/// [`FilterBuilder`]: FilterBuilder
+pub trait PacketFilter {
+    /// Returns whether the packet is selected.
+    fn matches(&self, packet: &Ethernet) -> bool;
+}
+
+/// Matches all packets.
+#[derive(Debug)]
+struct AnyFilter;
+
+impl PacketFilter for AnyFilter {
+    fn matches(&self, _packet: &Ethernet) -> bool {
+        true
+    }
+}
+
+/// Matches packets with the ether type.
+#[derive(Debug)]
+pub struct EtherTypeFilter(pub EtherType);
+
+impl PacketFilter for EtherTypeFilter {
+    fn matches(&self, packet: &Ethernet) -> bool {
+        packet.ether_type() == self.0
+    }
+}
+
+/// Matches IPv4 packets with a source address in the range.
+#[derive(Debug)]
+pub struct Ipv4SrcFilter(pub Ipv4Cidr);
+
+impl PacketFilter for Ipv4SrcFilter {
+    fn matches(&self, packet: &Ethernet) -> bool {
+        packet.ether_type() == EtherTypes::Ipv4
+            && packet
+                .peek::<Ipv4>()
+                .map(|ipv4| self.0.contains(ipv4.src()))
+                .unwrap_or(false)
+    }
+}
+
+/// Matches IPv4 packets with a destination address in the range.
+#[derive(Debug)]
+pub struct Ipv4DstFilter(pub Ipv4Cidr);
+
+impl PacketFilter for Ipv4DstFilter {
+    fn matches(&self, packet: &Ethernet) -> bool {
+        packet.ether_type() == EtherTypes::Ipv4
+            && packet
+                .peek::<Ipv4>()
+                .map(|ipv4| self.0.contains(ipv4.dst()))
+                .unwrap_or(false)
+    }
+}
+
+/// Matches TCP packets over IPv4 or IPv6 with the ports. A `None` port
+/// matches any port.
+#[derive(Debug)]
+pub struct TcpPortFilter {
+    /// The source port to match.
+    pub src: Option<u16>,
+    /// The destination port to match.
+    pub dst: Option<u16>,
+}
+
+impl TcpPortFilter {
+    fn matches_ports(&self, src: u16, dst: u16) -> bool {
+        self.src.map_or(true, |port| port == src) && self.dst.map_or(true, |port| port == dst)
+    }
+}
+
+impl PacketFilter for TcpPortFilter {
+    fn matches(&self, packet: &Ethernet) -> bool {
+        match packet.ether_type() {
+            EtherTypes::Ipv4 => packet
+                .peek::<Ipv4>()
+                .ok()
+                .filter(|ipv4| ipv4.protocol() == ProtocolNumbers::Tcp)
+                .and_then(|ipv4| {
+                    ipv4.peek::<Tcp4>()
+                        .map(|tcp| self.matches_ports(tcp.src_port(), tcp.dst_port()))
+                        .ok()
+                })
+                .unwrap_or(false),
+            EtherTypes::Ipv6 => packet
+                .peek::<Ipv6>()
+                .ok()
+                .filter(|ipv6| ipv6.next_header() == ProtocolNumbers::Tcp)
+                .and_then(|ipv6| {
+                    ipv6.peek::<Tcp6>()
+                        .map(|tcp| self.matches_ports(tcp.src_port(), tcp.dst_port()))
+                        .ok()
+                })
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+/// Matches packets both filters match.
+pub struct AndFilter(pub Box<dyn PacketFilter>, pub Box<dyn PacketFilter>);
+
+impl PacketFilter for AndFilter {
+    fn matches(&self, packet: &Ethernet) -> bool {
+        self.0.matches(packet) && self.1.matches(packet)
+    }
+}
+
+impl fmt::Debug for AndFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AndFilter").finish()
+    }
+}
+
+/// Matches packets either filter matches.
+pub struct OrFilter(pub Box<dyn PacketFilter>, pub Box<dyn PacketFilter>);
+
+impl PacketFilter for OrFilter {
+    fn matches(&self, packet: &Ethernet) -> bool {
+        self.0.matches(packet) || self.1.matches(packet)
+    }
+}
+
+impl fmt::Debug for OrFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OrFilter").finish()
+    }
+}
+
+/// Matches packets the filter does not match.
+pub struct NotFilter(pub Box<dyn PacketFilter>);
+
+impl PacketFilter for NotFilter {
+    fn matches(&self, packet: &Ethernet) -> bool {
+        !self.0.matches(packet)
+    }
+}
+
+impl fmt::Debug for NotFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NotFilter").finish()
+    }
+}
+
+/// How the builder joins the next filter.
+#[derive(Clone, Copy, Debug)]
+enum Join {
+    And,
+    Or,
+}
+
+/// A fluent builder for composing filters.
+///
+/// Filters are joined left to right, without precedence. Two filters
+/// added without `and` or `or` in between are joined with `and`.
+/// `negate` applies only to the next filter added.
+///
+/// # Example
+///
+/// ```
+/// let filter = FilterBuilder::new()
+///     .ether_type(EtherTypes::Ipv4)
+///     .and()
+///     .tcp_dst_port(80)
+///     .build();
+///
+/// let batch = batch.filter(filter.as_ref());
+/// ```
+pub struct FilterBuilder {
+    filter: Option<Box<dyn PacketFilter>>,
+    join: Join,
+    negate: bool,
+}
+
+impl FilterBuilder {
+    /// Creates a new builder. The filter built without adding any filter
+    /// matches all packets.
+    pub fn new() -> Self {
+        FilterBuilder {
+            filter: None,
+            join: Join::And,
+            negate: false,
+        }
+    }
+
+    /// Joins the next filter with `and`.
+    pub fn and(mut self) -> Self {
+        self.join = Join::And;
+        self
+    }
+
+    /// Joins the next filter with `or`.
+    pub fn or(mut self) -> Self {
+        self.join = Join::Or;
+        self
+    }
+
+    /// Negates the next filter.
+    pub fn negate(mut self) -> Self {
+        self.negate = !self.negate;
+        self
+    }
+
+    /// Adds a filter.
+    pub fn filter(mut self, filter: Box<dyn PacketFilter>) -> Self {
+        let filter = if self.negate {
+            Box::new(NotFilter(filter))
+        } else {
+            filter
+        };
+
+        self.filter = Some(match self.filter.take() {
+            None => filter,
+            Some(current) => match self.join {
+                Join::And => Box::new(AndFilter(current, filter)),
+                Join::Or => Box::new(OrFilter(current, filter)),
+            },
+        });
+        self.join = Join::And;
+        self.negate = false;
+        self
+    }
+
+    /// Adds an ether type filter.
+    pub fn ether_type(self, ether_type: EtherType) -> Self {
+        self.filter(Box::new(EtherTypeFilter(ether_type)))
+    }
+
+    /// Adds an IPv4 source address filter.
+    pub fn ipv4_src(self, cidr: Ipv4Cidr) -> Self {
+        self.filter(Box::new(Ipv4SrcFilter(cidr)))
+    }
+
+    /// Adds an IPv4 destination address filter.
+    pub fn ipv4_dst(self, cidr: Ipv4Cidr) -> Self {
+        self.filter(Box::new(Ipv4DstFilter(cidr)))
+    }
+
+    /// Adds a TCP source port filter.
+    pub fn tcp_src_port(self, port: u16) -> Self {
+        self.filter(Box::new(TcpPortFilter {
+            src: Some(port),
+            dst: None,
+        }))
+    }
+
+    /// Adds a TCP destination port filter.
+    pub fn tcp_dst_port(self, port: u16) -> Self {
+        self.filter(Box::new(TcpPortFilter {
+            src: None,
+            dst: Some(port),
+        }))
+    }
+
+    /// Builds the filter.
+    pub fn build(self) -> Box<dyn PacketFilter> {
+        self.filter.unwrap_or_else(|| Box::new(AnyFilter))
+    }
+}
+
+impl Default for FilterBuilder {
+    fn default() -> Self {
+        FilterBuilder::new()
+    }
+}
+
+impl fmt::Debug for FilterBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FilterBuilder")
+            .field("join", &self.join)
+            .field("negate", &self.negate)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::{IPV4_TCP_PACKET, IPV4_UDP_PACKET, IPV6_TCP_PACKET};
+    use crate::Mbuf;
+    use std::net::Ipv4Addr;
+
+    fn ethernet(bytes: &[u8]) -> Ethernet {
+        Mbuf::from_bytes(bytes)
+            .unwrap()
+            .parse::<Ethernet>()
+            .unwrap()
+    }
+
+    #[capsule::test]
+    fn match_basic_filters() {
+        let tcp4 = ethernet(&IPV4_TCP_PACKET);
+        let udp4 = ethernet(&IPV4_UDP_PACKET);
+        let tcp6 = ethernet(&IPV6_TCP_PACKET);
+
+        let filter = EtherTypeFilter(EtherTypes::Ipv4);
+        assert!(filter.matches(&tcp4));
+        assert!(!filter.matches(&tcp6));
+
+        let cidr = Ipv4Cidr::new(Ipv4Addr::new(139, 133, 217, 0), 24).unwrap();
+        assert!(Ipv4SrcFilter(cidr).matches(&tcp4));
+        assert!(!Ipv4DstFilter(cidr).matches(&tcp4));
+        assert!(!Ipv4SrcFilter(cidr).matches(&tcp6));
+
+        let filter = TcpPortFilter {
+            src: Some(36869),
+            dst: None,
+        };
+        assert!(filter.matches(&tcp4));
+        assert!(filter.matches(&tcp6));
+        assert!(!filter.matches(&udp4));
+    }
+
+    #[capsule::test]
+    fn build_composite_filters() {
+        let tcp4 = ethernet(&IPV4_TCP_PACKET);
+        let udp4 = ethernet(&IPV4_UDP_PACKET);
+        let tcp6 = ethernet(&IPV6_TCP_PACKET);
+
+        let filter = FilterBuilder::new()
+            .ether_type(EtherTypes::Ipv4)
+            .and()
+            .tcp_dst_port(23)
+            .build();
+        assert!(filter.matches(&tcp4));
+        assert!(!filter.matches(&udp4));
+        assert!(!filter.matches(&tcp6));
+
+        let filter = FilterBuilder::new()
+            .negate()
+            .ether_type(EtherTypes::Ipv4)
+            .or()
+            .tcp_src_port(36869)
+            .build();
+        assert!(filter.matches(&tcp4));
+        assert!(!filter.matches(&udp4));
+        assert!(filter.matches(&tcp6));
+
+        assert!(FilterBuilder::new().build().matches(&udp4));
+    }
+}
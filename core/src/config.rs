@@ -45,7 +45,7 @@
 //!
 //! [`pktdump`]: https://github.com/capsule-rs/capsule/tree/master/examples/pktdump
 
-use crate::dpdk::CoreId;
+use crate::dpdk::{CoreId, RxOffloadFlags, TxOffloadFlags, ETHER_OVERHEAD};
 use crate::net::{Ipv4Cidr, Ipv6Cidr, MacAddr};
 use anyhow::Result;
 use clap::{clap_app, crate_version};
@@ -359,6 +359,35 @@ pub struct PortConfig {
     #[serde(default = "default_port_txd")]
     pub txd: usize,
 
+    /// The maximum number of packets to receive from the receive queue in
+    /// one burst. Must not exceed `rxd`. Defaults to `32`.
+    #[serde(default = "default_port_rx_burst")]
+    pub rx_burst: usize,
+
+    /// The maximum transmission unit of the port. The value is checked
+    /// against the limits of the Ethernet device. Defaults to the device's
+    /// own MTU, which is typically `1500`.
+    #[serde(default)]
+    pub mtu: Option<usize>,
+
+    /// The receive offloads to enable on the port. The offloads are
+    /// checked against the capabilities of the Ethernet device. Defaults
+    /// to none. This setting can only be set programmatically.
+    #[serde(skip)]
+    pub rx_offloads: RxOffloadFlags,
+
+    /// The transmit offloads to enable on the port. The offloads are
+    /// checked against the capabilities of the Ethernet device. Defaults
+    /// to none. This setting can only be set programmatically.
+    #[serde(skip)]
+    pub tx_offloads: TxOffloadFlags,
+
+    /// Whether receive side scaling uses a symmetric hash key, so that both
+    /// directions of a flow are received by the same core. Only applies
+    /// to ports with more than one core. Defaults to `false`.
+    #[serde(default)]
+    pub symmetric_rss: bool,
+
     /// Whether promiscuous mode is enabled for this port. Defaults to `false`.
     #[serde(default)]
     pub promiscuous: bool,
@@ -375,6 +404,40 @@ pub struct PortConfig {
     pub kni: bool,
 }
 
+impl PortConfig {
+    /// Enables the receive offloads on the port.
+    pub fn enable_rx_offloads(&mut self, flags: RxOffloadFlags) -> &mut Self {
+        self.rx_offloads |= flags;
+        self
+    }
+
+    /// Enables the transmit offloads on the port.
+    pub fn enable_tx_offloads(&mut self, flags: TxOffloadFlags) -> &mut Self {
+        self.tx_offloads |= flags;
+        self
+    }
+
+    /// Keeps the Ethernet frame check sequence at the end of the received
+    /// frames. See `EthernetFrameCheck` for validating and stripping it.
+    pub fn keep_fcs(&mut self) -> &mut Self {
+        self.rx_offloads |= RxOffloadFlags::KEEP_CRC;
+        self
+    }
+
+    /// Uses a generated symmetric hash key for receive side scaling.
+    pub fn use_symmetric_rss(&mut self) -> &mut Self {
+        self.symmetric_rss = true;
+        self
+    }
+
+    /// Enables jumbo frames of up to `max_pkt_size` bytes, including the
+    /// Ethernet header and CRC. The MTU is set accordingly.
+    pub fn enable_jumbo_frames(&mut self, max_pkt_size: u16) -> &mut Self {
+        self.mtu = Some((max_pkt_size as usize).saturating_sub(ETHER_OVERHEAD));
+        self
+    }
+}
+
 fn default_port_rxd() -> usize {
     128
 }
@@ -383,6 +446,10 @@ fn default_port_txd() -> usize {
     128
 }
 
+fn default_port_rx_burst() -> usize {
+    32
+}
+
 fn default_multicast_mode() -> bool {
     true
 }
@@ -398,7 +465,20 @@ impl fmt::Debug for PortConfig {
         d.field("cores", &self.cores)
             .field("rxd", &self.rxd)
             .field("txd", &self.txd)
-            .field("promiscuous", &self.promiscuous)
+            .field("rx_burst", &self.rx_burst);
+        if let Some(mtu) = &self.mtu {
+            d.field("mtu", mtu);
+        }
+        if !self.rx_offloads.is_empty() {
+            d.field("rx_offloads", &self.rx_offloads);
+        }
+        if !self.tx_offloads.is_empty() {
+            d.field("tx_offloads", &self.tx_offloads);
+        }
+        if self.symmetric_rss {
+            d.field("symmetric_rss", &self.symmetric_rss);
+        }
+        d.field("promiscuous", &self.promiscuous)
             .field("multicast", &self.multicast)
             .field("kni", &self.kni)
             .finish()
@@ -451,11 +531,33 @@ mod tests {
         assert_eq!(None, config.ports[0].args);
         assert_eq!(default_port_rxd(), config.ports[0].rxd);
         assert_eq!(default_port_txd(), config.ports[0].txd);
+        assert_eq!(default_port_rx_burst(), config.ports[0].rx_burst);
+        assert_eq!(None, config.ports[0].mtu);
+        assert!(config.ports[0].rx_offloads.is_empty());
+        assert!(config.ports[0].tx_offloads.is_empty());
+        assert!(!config.ports[0].symmetric_rss);
         assert_eq!(false, config.ports[0].promiscuous);
         assert_eq!(default_multicast_mode(), config.ports[0].multicast);
         assert_eq!(false, config.ports[0].kni);
     }
 
+    #[test]
+    fn enable_jumbo_frames() {
+        const CONFIG: &str = r#"
+            app_name = "myapp"
+            master_core = 0
+
+            [[ports]]
+                name = "eth0"
+                device = "0000:00:01.0"
+                cores = [2, 3]
+        "#;
+
+        let mut config: RuntimeConfig = toml::from_str(CONFIG).unwrap();
+        config.ports[0].enable_jumbo_frames(9018);
+        assert_eq!(Some(9000), config.ports[0].mtu);
+    }
+
     #[test]
     fn config_to_eal_args() {
         const CONFIG: &str = r#"
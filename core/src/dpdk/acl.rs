@@ -0,0 +1,518 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, SocketId};
+use crate::ffi::{self, ToCString, ToResult};
+use crate::net::{Cidr, Ipv4Cidr};
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::Packet;
+use crate::{debug, ensure};
+use anyhow::Result;
+use std::fmt;
+use std::mem;
+use std::ptr::NonNull;
+use thiserror::Error;
+
+/// The number of fields in an IPv4 5-tuple rule.
+const NUM_FIELDS: usize = 5;
+
+/// The number of bytes classification reads from the IPv4 header, up to
+/// and including the transport ports.
+const INPUT_LEN: usize = 24;
+
+/// The layout of the IPv4 5-tuple relative to the start of the IPv4
+/// header, assuming the header has no options.
+const FIELD_DEFS: [ffi::rte_acl_field_def; NUM_FIELDS] = [
+    // protocol
+    ffi::rte_acl_field_def {
+        type_: ffi::RTE_ACL_FIELD_TYPE_BITMASK as u8,
+        size: 1,
+        field_index: 0,
+        input_index: 0,
+        offset: 9,
+    },
+    // source address
+    ffi::rte_acl_field_def {
+        type_: ffi::RTE_ACL_FIELD_TYPE_MASK as u8,
+        size: 4,
+        field_index: 1,
+        input_index: 1,
+        offset: 12,
+    },
+    // destination address
+    ffi::rte_acl_field_def {
+        type_: ffi::RTE_ACL_FIELD_TYPE_MASK as u8,
+        size: 4,
+        field_index: 2,
+        input_index: 2,
+        offset: 16,
+    },
+    // source port, shares the 4-byte input with the destination port
+    ffi::rte_acl_field_def {
+        type_: ffi::RTE_ACL_FIELD_TYPE_RANGE as u8,
+        size: 2,
+        field_index: 3,
+        input_index: 3,
+        offset: 20,
+    },
+    // destination port
+    ffi::rte_acl_field_def {
+        type_: ffi::RTE_ACL_FIELD_TYPE_RANGE as u8,
+        size: 2,
+        field_index: 4,
+        input_index: 3,
+        offset: 22,
+    },
+];
+
+/// ACL errors.
+#[derive(Debug, Error)]
+pub(crate) enum AclError {
+    /// The match type is not supported by the field, or the value is out
+    /// of range for the field.
+    #[error("Field {0} does not support {1:?}.")]
+    InvalidField(usize, AclField),
+
+    /// The result 0 is reserved for no match.
+    #[error("Rule result must not be 0.")]
+    ZeroResult,
+
+    /// The priority is larger than `RTE_ACL_MAX_PRIORITY`.
+    #[error("Rule priority {0} is out of range.")]
+    InvalidPriority(u32),
+
+    /// The number of categories must be 1 or a multiple of 4, up to 16.
+    #[error("Invalid number of categories {0}.")]
+    InvalidCategories(u32),
+
+    /// The packet is too short to read all the fields from.
+    #[error("Packet is too short to classify.")]
+    PacketTooShort,
+
+    /// The results buffer is too short.
+    #[error("Results buffer holds {0} values, expected at least {1}.")]
+    ResultsTooShort(usize, usize),
+}
+
+/// A match on one field of an ACL rule.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AclField {
+    /// Matches any value.
+    Any,
+    /// Matches exactly the value.
+    Exact(u32),
+    /// Matches any value in the inclusive range. Supported by the ports.
+    Range(u32, u32),
+    /// Matches the value in the bits set in the mask. Supported by the
+    /// protocol.
+    Bitmask(u32, u32),
+    /// Matches the value's prefix of the length in bits. Supported by the
+    /// addresses.
+    Prefix(u32, u8),
+}
+
+impl From<Ipv4Cidr> for AclField {
+    fn from(cidr: Ipv4Cidr) -> Self {
+        AclField::Prefix(cidr.network().into(), cidr.length() as u8)
+    }
+}
+
+impl AclField {
+    /// Converts the match into the raw field for the field definition.
+    fn to_raw(self, index: usize) -> Result<ffi::rte_acl_field> {
+        let def = FIELD_DEFS[index];
+        let max = (1u64 << (def.size * 8)) - 1;
+        let invalid = || AclError::InvalidField(index, self);
+
+        let (value, mask_range) = match (def.type_ as u32, self) {
+            (ffi::RTE_ACL_FIELD_TYPE_BITMASK, AclField::Any) => (0, 0),
+            (ffi::RTE_ACL_FIELD_TYPE_BITMASK, AclField::Exact(v)) => (v, max as u32),
+            (ffi::RTE_ACL_FIELD_TYPE_BITMASK, AclField::Bitmask(v, m)) => (v, m),
+            (ffi::RTE_ACL_FIELD_TYPE_MASK, AclField::Any) => (0, 0),
+            (ffi::RTE_ACL_FIELD_TYPE_MASK, AclField::Exact(v)) => (v, 32),
+            (ffi::RTE_ACL_FIELD_TYPE_MASK, AclField::Prefix(v, len)) if len <= 32 => {
+                (v, len as u32)
+            }
+            (ffi::RTE_ACL_FIELD_TYPE_RANGE, AclField::Any) => (0, max as u32),
+            (ffi::RTE_ACL_FIELD_TYPE_RANGE, AclField::Exact(v)) => (v, v),
+            (ffi::RTE_ACL_FIELD_TYPE_RANGE, AclField::Range(lo, hi)) if lo <= hi => (lo, hi),
+            _ => return Err(invalid().into()),
+        };
+
+        if def.type_ as u32 != ffi::RTE_ACL_FIELD_TYPE_MASK {
+            ensure!(value as u64 <= max && mask_range as u64 <= max, invalid());
+        }
+
+        let mut field = ffi::rte_acl_field::default();
+        match def.size {
+            1 => {
+                field.value.u8_ = value as u8;
+                field.mask_range.u8_ = mask_range as u8;
+            }
+            2 => {
+                field.value.u16_ = value as u16;
+                field.mask_range.u16_ = mask_range as u16;
+            }
+            _ => {
+                field.value.u32_ = value;
+                field.mask_range.u32_ = mask_range;
+            }
+        }
+
+        Ok(field)
+    }
+}
+
+/// An IPv4 5-tuple rule in the layout `rte_acl` expects.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct AclRule {
+    data: ffi::rte_acl_rule_data,
+    fields: [ffi::rte_acl_field; NUM_FIELDS],
+}
+
+/// Builds an `AclContext`.
+pub struct AclContextBuilder {
+    name: String,
+    socket_id: SocketId,
+    categories: u32,
+    rules: Vec<AclRule>,
+}
+
+impl AclContextBuilder {
+    /// Sets the number of categories. Each rule can belong to several
+    /// categories, and classification returns the best match for each
+    /// category. The default is 1.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number is neither 1 or a multiple of 4, up
+    /// to 16.
+    pub fn categories(&mut self, categories: u32) -> Result<&mut Self> {
+        ensure!(
+            is_valid_categories(categories),
+            AclError::InvalidCategories(categories)
+        );
+        self.categories = categories;
+        Ok(self)
+    }
+
+    /// Adds a rule. The fields are in the order of protocol, source
+    /// address, destination address, source port and destination port.
+    /// When multiple rules match, the one with the highest priority wins.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a field's match is not supported by the field,
+    /// the priority is out of range, or the result is 0.
+    pub fn add_rule(
+        &mut self,
+        priority: u32,
+        fields: [AclField; NUM_FIELDS],
+        category_mask: u32,
+        result: u32,
+    ) -> Result<&mut Self> {
+        ensure!(
+            priority <= ffi::RTE_ACL_MAX_PRIORITY,
+            AclError::InvalidPriority(priority)
+        );
+        ensure!(result != 0, AclError::ZeroResult);
+
+        let mut rule = AclRule {
+            data: ffi::rte_acl_rule_data {
+                category_mask,
+                priority: priority as i32,
+                userdata: result,
+            },
+            fields: [ffi::rte_acl_field::default(); NUM_FIELDS],
+        };
+
+        for (index, field) in fields.iter().enumerate() {
+            rule.fields[index] = field.to_raw(index)?;
+        }
+
+        self.rules.push(rule);
+        Ok(self)
+    }
+
+    /// Creates the context and compiles the rules.
+    ///
+    /// # Errors
+    ///
+    /// If the context cannot be created or the rules cannot be compiled,
+    /// `DpdkError` is returned.
+    pub fn build(&self) -> Result<AclContext> {
+        let name = self.name.clone().into_cstring();
+        let param = ffi::rte_acl_param {
+            name: name.as_ptr(),
+            socket_id: self.socket_id.raw(),
+            rule_size: mem::size_of::<AclRule>() as u32,
+            max_rule_num: self.rules.len().max(1) as u32,
+        };
+
+        let raw = unsafe { ffi::rte_acl_create(&param).into_result(|_| DpdkError::new())? };
+
+        // the context is freed on error from here on.
+        let ctx = AclContext {
+            raw,
+            name: self.name.clone(),
+            categories: self.categories,
+        };
+
+        let mut cfg = ffi::rte_acl_config {
+            num_categories: self.categories,
+            num_fields: NUM_FIELDS as u32,
+            ..Default::default()
+        };
+        cfg.defs[..NUM_FIELDS].copy_from_slice(&FIELD_DEFS);
+
+        unsafe {
+            ffi::rte_acl_add_rules(
+                raw.as_ptr(),
+                self.rules.as_ptr() as *const ffi::rte_acl_rule,
+                self.rules.len() as u32,
+            )
+            .into_result(DpdkError::from_errno)?;
+            ffi::rte_acl_build(raw.as_ptr(), &cfg).into_result(DpdkError::from_errno)?;
+        }
+
+        debug!(
+            "built ACL context {} with {} rules.",
+            self.name,
+            self.rules.len()
+        );
+        Ok(ctx)
+    }
+}
+
+impl fmt::Debug for AclContextBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AclContextBuilder")
+            .field("name", &self.name)
+            .field("socket_id", &self.socket_id)
+            .field("categories", &self.categories)
+            .field("rules", &self.rules.len())
+            .finish()
+    }
+}
+
+fn is_valid_categories(categories: u32) -> bool {
+    categories == 1
+        || (categories > 0
+            && categories <= ffi::RTE_ACL_MAX_CATEGORIES
+            && categories % ffi::RTE_ACL_RESULTS_MULTIPLIER == 0)
+}
+
+/// A compiled set of IPv4 5-tuple classification rules backed by `rte_acl`.
+///
+/// The rules are immutable once built. Classification is read-only and
+/// can happen concurrently from multiple cores.
+///
+/// # Example
+///
+/// ```
+/// let mut builder = AclContext::builder("firewall", SocketId::current());
+/// builder.add_rule(
+///     1,
+///     [
+///         AclField::Exact(ProtocolNumbers::Tcp.0 as u32),
+///         AclField::Any,
+///         "10.0.0.0/8".parse::<Ipv4Cidr>()?.into(),
+///         AclField::Any,
+///         AclField::Exact(22),
+///     ],
+///     1,
+///     DENY,
+/// )?;
+/// let acl = builder.build()?;
+/// acl.classify(&packets, &mut results, 1)?;
+/// ```
+pub struct AclContext {
+    raw: NonNull<ffi::rte_acl_ctx>,
+    name: String,
+    categories: u32,
+}
+
+impl AclContext {
+    /// Returns a builder for a new context. `name` must be unique.
+    pub fn builder(name: &str, socket_id: SocketId) -> AclContextBuilder {
+        AclContextBuilder {
+            name: name.to_owned(),
+            socket_id,
+            categories: 1,
+            rules: vec![],
+        }
+    }
+
+    /// Returns the name of the context.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Classifies the packets. For each packet, the results of the best
+    /// matching rules in the first `n_categories` categories are written to
+    /// `results`, 0 if no rule matches. Packets with IPv4 options are
+    /// classified as if the options are the transport header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `n_categories` is invalid, a packet is too
+    /// short, or `results` holds less than `packets.len() * n_categories`
+    /// values.
+    pub fn classify(&self, packets: &[Ipv4], results: &mut [u32], n_categories: u32) -> Result<()> {
+        ensure!(
+            is_valid_categories(n_categories) && n_categories <= self.categories,
+            AclError::InvalidCategories(n_categories)
+        );
+
+        let expected = packets.len() * n_categories as usize;
+        ensure!(
+            results.len() >= expected,
+            AclError::ResultsTooShort(results.len(), expected)
+        );
+
+        let mut data = Vec::with_capacity(packets.len());
+        for packet in packets.iter() {
+            ensure!(packet.len() >= INPUT_LEN, AclError::PacketTooShort);
+            data.push(unsafe { packet.mbuf().data_address(packet.offset()) as *const u8 });
+        }
+
+        unsafe {
+            ffi::rte_acl_classify(
+                self.raw.as_ptr(),
+                data.as_mut_ptr(),
+                results.as_mut_ptr(),
+                data.len() as u32,
+                n_categories,
+            )
+            .into_result(DpdkError::from_errno)?;
+        }
+
+        Ok(())
+    }
+
+    /// Dumps the context to stdout for debugging.
+    pub fn dump(&self) {
+        unsafe {
+            ffi::rte_acl_dump(self.raw.as_ptr());
+        }
+    }
+}
+
+impl fmt::Debug for AclContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AclContext")
+            .field("name", &self.name())
+            .field("categories", &self.categories)
+            .finish()
+    }
+}
+
+impl Drop for AclContext {
+    fn drop(&mut self) {
+        debug!("freeing ACL context {}.", self.name);
+
+        unsafe {
+            ffi::rte_acl_free(self.raw.as_ptr());
+        }
+    }
+}
+
+/// The context is read-only once built.
+unsafe impl Send for AclContext {}
+unsafe impl Sync for AclContext {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::Ethernet;
+    use crate::testils::byte_arrays::{IPV4_TCP_PACKET, IPV4_UDP_PACKET};
+    use crate::Mbuf;
+
+    #[test]
+    fn acl_field_to_raw() {
+        assert!(AclField::Exact(6).to_raw(0).is_ok());
+        assert!(AclField::Exact(256).to_raw(0).is_err());
+        assert!(AclField::Range(1, 2).to_raw(0).is_err());
+        assert!(AclField::Prefix(0x0a00_0000, 8).to_raw(1).is_ok());
+        assert!(AclField::Prefix(0x0a00_0000, 33).to_raw(1).is_err());
+        assert!(AclField::Range(1024, 65535).to_raw(3).is_ok());
+        assert!(AclField::Range(2, 1).to_raw(3).is_err());
+        assert!(AclField::Exact(65536).to_raw(4).is_err());
+    }
+
+    #[capsule::test]
+    fn acl_classify() {
+        let mut builder = AclContext::builder("acl_classify", SocketId::ANY);
+        builder
+            .add_rule(
+                1,
+                [
+                    AclField::Any,
+                    AclField::Any,
+                    AclField::Any,
+                    AclField::Any,
+                    AclField::Any,
+                ],
+                1,
+                1,
+            )
+            .unwrap()
+            .add_rule(
+                2,
+                [
+                    AclField::Exact(0x06),
+                    AclField::Any,
+                    AclField::Any,
+                    AclField::Any,
+                    AclField::Any,
+                ],
+                1,
+                2,
+            )
+            .unwrap();
+        let acl = builder.build().unwrap();
+
+        let packets = [&IPV4_TCP_PACKET[..], &IPV4_UDP_PACKET[..]]
+            .iter()
+            .map(|bytes| {
+                let packet = Mbuf::from_bytes(bytes).unwrap();
+                packet.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = [0; 2];
+        acl.classify(&packets, &mut results, 1).unwrap();
+        assert_eq!([2, 1], results);
+
+        let mut short = [0; 1];
+        assert!(acl.classify(&packets, &mut short, 1).is_err());
+    }
+
+    #[test]
+    fn acl_rule_validation() {
+        let mut builder = AclContext::builder("acl_validation", SocketId::ANY);
+        let any = [AclField::Any; NUM_FIELDS];
+        assert!(builder.add_rule(1, any, 1, 0).is_err());
+        assert!(builder
+            .add_rule(ffi::RTE_ACL_MAX_PRIORITY + 1, any, 1, 1)
+            .is_err());
+        assert!(builder.categories(3).is_err());
+        assert!(builder.categories(8).is_ok());
+    }
+}
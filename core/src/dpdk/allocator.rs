@@ -0,0 +1,185 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, Mbuf, MempoolError, SocketId, MEMPOOL};
+use crate::ffi::{self, AsStr, ToCString, ToResult};
+use anyhow::Result;
+use std::fmt;
+use std::os::raw;
+use std::ptr::NonNull;
+
+/// An allocator of `Mbuf`s with a fixed amount of headroom.
+///
+/// The default headroom of an `Mbuf` is `RTE_PKTMBUF_HEADROOM`, or 128
+/// bytes. Packets that are encapsulated in tunnel headers after allocation
+/// may need more room in front of the data to avoid moving the payload.
+///
+/// # Example
+///
+/// ```
+/// let allocator = PacketAllocator::new(256)?;
+/// let packet = allocator.alloc_with_data(&payload)?;
+/// ```
+pub struct PacketAllocator {
+    raw: NonNull<ffi::rte_mempool>,
+    headroom: u16,
+}
+
+impl PacketAllocator {
+    /// Creates a new allocator that allocates from the `Mempool` assigned
+    /// to the current executing thread by the `Runtime`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MempoolError::NotFound` if invoked from a thread not
+    /// managed by the `Runtime`.
+    pub fn new(headroom: u16) -> Result<Self> {
+        let raw = NonNull::new(MEMPOOL.with(|tls| tls.get()))
+            .ok_or_else(|| MempoolError::NotFound(SocketId::current()))?;
+        Ok(PacketAllocator { raw, headroom })
+    }
+
+    /// Creates a new allocator that allocates from an existing mempool
+    /// looked up by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DpdkError` if the mempool is not found.
+    ///
+    /// # Safety
+    ///
+    /// The allocator does not own the mempool or keep it alive. The caller
+    /// must ensure the mempool is not freed while the allocator exists,
+    /// for example the `Runtime`'s mempools are freed on shutdown.
+    pub unsafe fn from_mempool_name(name: &str, headroom: u16) -> Result<Self> {
+        let raw = ffi::rte_mempool_lookup(name.into_cstring().as_ptr())
+            .into_result(|_| DpdkError::new())?;
+        Ok(PacketAllocator { raw, headroom })
+    }
+
+    /// Returns the headroom reserved in the allocated `Mbuf`s.
+    pub fn headroom(&self) -> u16 {
+        self.headroom
+    }
+
+    /// Allocates a new `Mbuf`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MempoolError::Exhausted` if the allocation of mbuf fails.
+    /// Returns `BufferError::BadOffset` if the headroom exceeds the mbuf
+    /// size.
+    pub fn alloc(&self) -> Result<Mbuf> {
+        let mut mbuf = unsafe {
+            let raw = ffi::_rte_pktmbuf_alloc(self.raw.as_ptr())
+                .into_result(|_| MempoolError::Exhausted)?;
+            Mbuf::from_ptr(raw.as_ptr())
+        };
+        mbuf.reserve_headroom(self.headroom as usize)?;
+        Ok(mbuf)
+    }
+
+    /// Allocates `n` `Mbuf`s in bulk.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DpdkError` if the allocation of mbuf fails.
+    /// Returns `BufferError::BadOffset` if the headroom exceeds the mbuf
+    /// size.
+    pub fn alloc_bulk(&self, n: usize) -> Result<Vec<Mbuf>> {
+        let mut ptrs = Vec::with_capacity(n);
+
+        let mbufs = unsafe {
+            ffi::_rte_pktmbuf_alloc_bulk(self.raw.as_ptr(), ptrs.as_mut_ptr(), n as raw::c_uint)
+                .into_result(DpdkError::from_errno)?;
+
+            ptrs.set_len(n);
+            ptrs.into_iter()
+                .map(|ptr| Mbuf::from_ptr(ptr))
+                .collect::<Vec<_>>()
+        };
+
+        mbufs
+            .into_iter()
+            .map(|mut mbuf| {
+                mbuf.reserve_headroom(self.headroom as usize)?;
+                Ok(mbuf)
+            })
+            .collect()
+    }
+
+    /// Allocates a new `Mbuf` and copies `data` into it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MempoolError::Exhausted` if the allocation of mbuf fails.
+    /// Returns `BufferError::NotResized` if the data and the headroom do
+    /// not fit in the mbuf.
+    pub fn alloc_with_data(&self, data: &[u8]) -> Result<Mbuf> {
+        let mut mbuf = self.alloc()?;
+        if !data.is_empty() {
+            mbuf.extend(0, data.len())?;
+            mbuf.write_data_slice(0, data)?;
+        }
+        Ok(mbuf)
+    }
+}
+
+impl fmt::Debug for PacketAllocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = unsafe { self.raw.as_ref() };
+        f.debug_struct("packet_allocator")
+            .field("mempool", &raw.name[..].as_str())
+            .field("headroom", &self.headroom)
+            .finish()
+    }
+}
+
+// same as `Mempool`, the allocator can be moved to another thread but is
+// not shareable across threads.
+unsafe impl Send for PacketAllocator {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn alloc_with_headroom() {
+        let allocator = PacketAllocator::new(256).unwrap();
+
+        let mbuf = allocator.alloc().unwrap();
+        assert_eq!(256, mbuf.headroom());
+        assert_eq!(0, mbuf.data_len());
+
+        let mbuf = allocator.alloc_with_data(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(256, mbuf.headroom());
+        assert_eq!(4, mbuf.data_len());
+
+        let mbuf = allocator.alloc_with_data(&[]).unwrap();
+        assert_eq!(0, mbuf.data_len());
+
+        let mbufs = allocator.alloc_bulk(4).unwrap();
+        assert!(mbufs.iter().all(|mbuf| mbuf.headroom() == 256));
+    }
+
+    #[capsule::test]
+    fn alloc_with_excessive_headroom() {
+        let allocator = PacketAllocator::new(u16::max_value()).unwrap();
+        assert!(allocator.alloc().is_err());
+    }
+}
@@ -0,0 +1,250 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::{DpdkError, SocketId};
+use crate::ffi::{self, ToCString, ToResult};
+use crate::info;
+use anyhow::Result;
+
+/// The link aggregation modes of a bonded port. The mode values are
+/// macros in `rte_eth_bond.h`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BondingMode {
+    /// Transmits packets through the slaves in turn.
+    RoundRobin,
+    /// Only the primary slave is active. Another slave becomes active if
+    /// the primary fails.
+    ActiveBackup,
+    /// Transmits packets through the slave selected by the transmit hash
+    /// policy.
+    Balance,
+    /// Transmits every packet through all the slaves.
+    Broadcast,
+    /// IEEE 802.3ad dynamic link aggregation.
+    Lacp,
+    /// Adaptive transmit load balancing.
+    Tlb,
+    /// Adaptive load balancing of both the transmitted and the received
+    /// packets.
+    Alb,
+}
+
+impl BondingMode {
+    fn raw(self) -> u8 {
+        match self {
+            BondingMode::RoundRobin => 0,
+            BondingMode::ActiveBackup => 1,
+            BondingMode::Balance => 2,
+            BondingMode::Broadcast => 3,
+            BondingMode::Lacp => 4,
+            BondingMode::Tlb => 5,
+            BondingMode::Alb => 6,
+        }
+    }
+}
+
+/// The transmit hash policies for the `Balance` and `Lacp` modes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum XmitPolicy {
+    /// Hashes the Ethernet source and destination addresses.
+    Layer2,
+    /// Hashes the Ethernet and the IP source and destination addresses.
+    Layer23,
+    /// Hashes the IP addresses and the TCP or UDP ports.
+    Layer34,
+}
+
+impl XmitPolicy {
+    fn raw(self) -> u8 {
+        match self {
+            XmitPolicy::Layer2 => 0,
+            XmitPolicy::Layer23 => 1,
+            XmitPolicy::Layer34 => 2,
+        }
+    }
+}
+
+/// Configures bonded ports with the DPDK bonding PMD.
+///
+/// A bonded port aggregates several slave ports into one logical port.
+/// The slaves must be stopped when added to or removed from the bond.
+///
+/// # Example
+///
+/// ```
+/// let bond_id = BondingPort::create("net_bonding0", BondingMode::Lacp, SocketId::current())?;
+/// BondingPort::add_slave(bond_id, 0)?;
+/// BondingPort::add_slave(bond_id, 1)?;
+/// BondingPort::set_balance_xmit_policy(bond_id, XmitPolicy::Layer34)?;
+/// ```
+#[derive(Debug)]
+pub struct BondingPort;
+
+impl BondingPort {
+    /// Creates a new bonded port and returns its port id. The name must
+    /// start with `net_bonding`. `SocketId::ANY` creates the port on the
+    /// socket of the current core.
+    ///
+    /// # Errors
+    ///
+    /// If the name is already used or invalid, `DpdkError` is returned.
+    pub fn create(name: &str, mode: BondingMode, socket_id: SocketId) -> Result<u16> {
+        let socket_id = if socket_id == SocketId::ANY {
+            SocketId::current()
+        } else {
+            socket_id
+        };
+
+        let port_id = unsafe {
+            ffi::rte_eth_bond_create(
+                name.into_cstring().as_ptr(),
+                mode.raw(),
+                socket_id.raw() as u8,
+            )
+            .into_result(DpdkError::from_errno)?
+        };
+
+        info!(?mode, "created bonded port {}.", name);
+        Ok(port_id as u16)
+    }
+
+    /// Frees a bonded port by name.
+    ///
+    /// # Errors
+    ///
+    /// If the bonded port does not exist, `DpdkError` is returned.
+    pub fn free(name: &str) -> Result<()> {
+        unsafe {
+            ffi::rte_eth_bond_free(name.into_cstring().as_ptr())
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        info!("freed bonded port {}.", name);
+        Ok(())
+    }
+
+    /// Adds a slave port to the bonded port.
+    ///
+    /// # Errors
+    ///
+    /// If either port is invalid or the slave is already added,
+    /// `DpdkError` is returned.
+    pub fn add_slave(bond_port_id: u16, slave_port_id: u16) -> Result<()> {
+        unsafe {
+            ffi::rte_eth_bond_slave_add(bond_port_id, slave_port_id)
+                .into_result(DpdkError::from_errno)?;
+        }
+        Ok(())
+    }
+
+    /// Removes a slave port from the bonded port.
+    ///
+    /// # Errors
+    ///
+    /// If either port is invalid or the port is not a slave of the bond,
+    /// `DpdkError` is returned.
+    pub fn remove_slave(bond_port_id: u16, slave_port_id: u16) -> Result<()> {
+        unsafe {
+            ffi::rte_eth_bond_slave_remove(bond_port_id, slave_port_id)
+                .into_result(DpdkError::from_errno)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the primary slave, the active slave in `ActiveBackup` mode.
+    ///
+    /// # Errors
+    ///
+    /// If either port is invalid or the port is not a slave of the bond,
+    /// `DpdkError` is returned.
+    pub fn set_active_slave(bond_port_id: u16, slave_port_id: u16) -> Result<()> {
+        unsafe {
+            ffi::rte_eth_bond_primary_set(bond_port_id, slave_port_id)
+                .into_result(DpdkError::from_errno)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the transmit hash policy for the `Balance` and `Lacp` modes.
+    ///
+    /// # Errors
+    ///
+    /// If the port is not a bonded port, `DpdkError` is returned.
+    pub fn set_balance_xmit_policy(bond_port_id: u16, policy: XmitPolicy) -> Result<()> {
+        unsafe {
+            ffi::rte_eth_bond_xmit_policy_set(bond_port_id, policy.raw())
+                .into_result(DpdkError::from_errno)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the slaves of the bonded port.
+    ///
+    /// # Errors
+    ///
+    /// If the port is not a bonded port, `DpdkError` is returned.
+    pub fn slaves(bond_port_id: u16) -> Result<Vec<u16>> {
+        let mut slaves = vec![0u16; ffi::RTE_MAX_ETHPORTS as usize];
+        let count = unsafe {
+            ffi::rte_eth_bond_slaves_get(bond_port_id, slaves.as_mut_ptr(), slaves.len() as u16)
+                .into_result(DpdkError::from_errno)?
+        };
+        slaves.truncate(count as usize);
+        Ok(slaves)
+    }
+
+    /// Returns the slaves of the bonded port that are currently active.
+    ///
+    /// # Errors
+    ///
+    /// If the port is not a bonded port, `DpdkError` is returned.
+    pub fn active_slaves(bond_port_id: u16) -> Result<Vec<u16>> {
+        let mut slaves = vec![0u16; ffi::RTE_MAX_ETHPORTS as usize];
+        let count = unsafe {
+            ffi::rte_eth_bond_active_slaves_get(
+                bond_port_id,
+                slaves.as_mut_ptr(),
+                slaves.len() as u16,
+            )
+            .into_result(DpdkError::from_errno)?
+        };
+        slaves.truncate(count as usize);
+        Ok(slaves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn create_and_free_bonded_port() {
+        let port_id = BondingPort::create(
+            "net_bonding_test0",
+            BondingMode::ActiveBackup,
+            SocketId::ANY,
+        )
+        .unwrap();
+
+        assert!(BondingPort::slaves(port_id).unwrap().is_empty());
+        assert!(BondingPort::active_slaves(port_id).unwrap().is_empty());
+        assert!(BondingPort::set_balance_xmit_policy(port_id, XmitPolicy::Layer23).is_ok());
+        assert!(BondingPort::add_slave(port_id, u16::max_value()).is_err());
+
+        assert!(BondingPort::free("net_bonding_test0").is_ok());
+    }
+}
@@ -0,0 +1,330 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::DpdkError;
+use crate::error;
+use crate::ffi::{self, ToResult};
+use anyhow::Result;
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::os::raw;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::NonNull;
+use std::slice;
+
+type BurstFn = Box<dyn FnMut(&[*mut ffi::rte_mbuf]) + Send + 'static>;
+
+/// Boxes the closure again to get a thin pointer that can go through the
+/// C callback as the user parameter.
+fn into_user_param<F>(cb: F) -> *mut raw::c_void
+where
+    F: FnMut(&[*mut ffi::rte_mbuf]) + Send + 'static,
+{
+    Box::into_raw(Box::new(Box::new(cb) as BurstFn)) as *mut raw::c_void
+}
+
+/// Invokes the closure with the burst of packets.
+///
+/// # Safety
+///
+/// `pkts` must point to `nb_pkts` mbuf pointers, and `user_param` must be
+/// a pointer returned by `into_user_param` that is not yet reclaimed.
+unsafe fn invoke(pkts: *mut *mut ffi::rte_mbuf, nb_pkts: u16, user_param: *mut raw::c_void) {
+    let cb = &mut *(user_param as *mut BurstFn);
+    let pkts = if nb_pkts > 0 {
+        slice::from_raw_parts(pkts, nb_pkts as usize)
+    } else {
+        &[]
+    };
+
+    // must not unwind across the FFI boundary.
+    if panic::catch_unwind(AssertUnwindSafe(|| cb(pkts))).is_err() {
+        error!("queue callback panicked.");
+    }
+}
+
+/// The callback registered with all the RX queues. The packets are passed
+/// through unchanged.
+unsafe extern "C" fn rx_callback_main(
+    _port_id: u16,
+    _queue_id: u16,
+    pkts: *mut *mut ffi::rte_mbuf,
+    nb_pkts: u16,
+    _max_pkts: u16,
+    user_param: *mut raw::c_void,
+) -> u16 {
+    invoke(pkts, nb_pkts, user_param);
+    nb_pkts
+}
+
+/// The callback registered with all the TX queues. The packets are passed
+/// through unchanged.
+unsafe extern "C" fn tx_callback_main(
+    _port_id: u16,
+    _queue_id: u16,
+    pkts: *mut *mut ffi::rte_mbuf,
+    nb_pkts: u16,
+    user_param: *mut raw::c_void,
+) -> u16 {
+    invoke(pkts, nb_pkts, user_param);
+    nb_pkts
+}
+
+/// A closure installed on a port RX queue, invoked with every burst of
+/// received packets before they are returned to the pipeline.
+///
+/// The closure runs on the core polling the queue. The callback is
+/// removed when the handle is dropped. Because `rte_eth_remove_rx_callback`
+/// does not wait for a burst already in progress, dropping the handle
+/// leaks the closure. Use `remove` to also free it once the queue is no
+/// longer polled.
+///
+/// # Example
+///
+/// ```
+/// let mut count = 0;
+/// let handle = RxCallbackHandle::install(0, 0, move |pkts| count += pkts.len())?;
+/// ```
+pub struct RxCallbackHandle {
+    port_id: u16,
+    queue_id: u16,
+    raw: NonNull<ffi::rte_eth_rxtx_callback>,
+    user_param: *mut raw::c_void,
+}
+
+impl RxCallbackHandle {
+    /// Installs the closure on the RX queue of the port.
+    ///
+    /// # Errors
+    ///
+    /// If the port or the queue is invalid, `DpdkError` is returned.
+    pub fn install<F>(port_id: u16, queue_id: u16, cb: F) -> Result<RxCallbackHandle>
+    where
+        F: FnMut(&[*mut ffi::rte_mbuf]) + Send + 'static,
+    {
+        let user_param = into_user_param(cb);
+
+        let res = unsafe {
+            (ffi::rte_eth_add_rx_callback(port_id, queue_id, Some(rx_callback_main), user_param)
+                as *mut ffi::rte_eth_rxtx_callback)
+                .into_result(|_| DpdkError::new())
+        };
+
+        match res {
+            Ok(raw) => Ok(RxCallbackHandle {
+                port_id,
+                queue_id,
+                raw,
+                user_param,
+            }),
+            Err(err) => {
+                // the callback is not installed, reclaims it to avoid the leak.
+                unsafe {
+                    drop(Box::from_raw(user_param as *mut BurstFn));
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Removes the callback from the RX queue and frees the closure.
+    ///
+    /// # Errors
+    ///
+    /// If the callback cannot be removed, `DpdkError` is returned and the
+    /// closure is leaked.
+    ///
+    /// # Safety
+    ///
+    /// No core may be polling the queue during the call, for example
+    /// because the port is stopped or the pipeline cores are parked.
+    /// Otherwise a burst in progress may still run the freed closure.
+    pub unsafe fn remove(self) -> Result<()> {
+        let this = ManuallyDrop::new(self);
+        ffi::rte_eth_remove_rx_callback(this.port_id, this.queue_id, this.raw.as_ptr())
+            .into_result(DpdkError::from_errno)?;
+        drop(Box::from_raw(this.user_param as *mut BurstFn));
+        Ok(())
+    }
+}
+
+impl fmt::Debug for RxCallbackHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RxCallbackHandle")
+            .field("port_id", &self.port_id)
+            .field("queue_id", &self.queue_id)
+            .finish()
+    }
+}
+
+impl Drop for RxCallbackHandle {
+    fn drop(&mut self) {
+        // the closure is leaked, a burst on another core may still be
+        // running it.
+        unsafe {
+            let _ = ffi::rte_eth_remove_rx_callback(self.port_id, self.queue_id, self.raw.as_ptr());
+        }
+    }
+}
+
+// the closure is `Send`, and the callback pointer is only used to remove
+// the callback.
+unsafe impl Send for RxCallbackHandle {}
+
+/// A closure installed on a port TX queue, invoked with every burst of
+/// packets before they are handed to the device for transmission.
+///
+/// The closure runs on the core transmitting through the queue. The
+/// callback is removed when the handle is dropped. Because
+/// `rte_eth_remove_tx_callback` does not wait for a burst already in
+/// progress, dropping the handle leaks the closure. Use `remove` to also
+/// free it once there are no more transmits on the queue.
+///
+/// # Example
+///
+/// ```
+/// let handle = TxCallbackHandle::install(0, 0, |pkts| {
+///     debug!("transmitting {} packets.", pkts.len());
+/// })?;
+/// ```
+pub struct TxCallbackHandle {
+    port_id: u16,
+    queue_id: u16,
+    raw: NonNull<ffi::rte_eth_rxtx_callback>,
+    user_param: *mut raw::c_void,
+}
+
+impl TxCallbackHandle {
+    /// Installs the closure on the TX queue of the port.
+    ///
+    /// # Errors
+    ///
+    /// If the port or the queue is invalid, `DpdkError` is returned.
+    pub fn install<F>(port_id: u16, queue_id: u16, cb: F) -> Result<TxCallbackHandle>
+    where
+        F: FnMut(&[*mut ffi::rte_mbuf]) + Send + 'static,
+    {
+        let user_param = into_user_param(cb);
+
+        let res = unsafe {
+            (ffi::rte_eth_add_tx_callback(port_id, queue_id, Some(tx_callback_main), user_param)
+                as *mut ffi::rte_eth_rxtx_callback)
+                .into_result(|_| DpdkError::new())
+        };
+
+        match res {
+            Ok(raw) => Ok(TxCallbackHandle {
+                port_id,
+                queue_id,
+                raw,
+                user_param,
+            }),
+            Err(err) => {
+                // the callback is not installed, reclaims it to avoid the leak.
+                unsafe {
+                    drop(Box::from_raw(user_param as *mut BurstFn));
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Removes the callback from the TX queue and frees the closure.
+    ///
+    /// # Errors
+    ///
+    /// If the callback cannot be removed, `DpdkError` is returned and the
+    /// closure is leaked.
+    ///
+    /// # Safety
+    ///
+    /// No core may be transmitting through the queue during the call.
+    /// Otherwise a burst in progress may still run the freed closure.
+    pub unsafe fn remove(self) -> Result<()> {
+        let this = ManuallyDrop::new(self);
+        ffi::rte_eth_remove_tx_callback(this.port_id, this.queue_id, this.raw.as_ptr())
+            .into_result(DpdkError::from_errno)?;
+        drop(Box::from_raw(this.user_param as *mut BurstFn));
+        Ok(())
+    }
+}
+
+impl fmt::Debug for TxCallbackHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TxCallbackHandle")
+            .field("port_id", &self.port_id)
+            .field("queue_id", &self.queue_id)
+            .finish()
+    }
+}
+
+impl Drop for TxCallbackHandle {
+    fn drop(&mut self) {
+        // the closure is leaked, a burst on another core may still be
+        // running it.
+        unsafe {
+            let _ = ffi::rte_eth_remove_tx_callback(self.port_id, self.queue_id, self.raw.as_ptr());
+        }
+    }
+}
+
+// the closure is `Send`, and the callback pointer is only used to remove
+// the callback.
+unsafe impl Send for TxCallbackHandle {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpdk::Mbuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[capsule::test]
+    fn install_on_invalid_port() {
+        assert!(RxCallbackHandle::install(u16::max_value(), 0, |_| ()).is_err());
+        assert!(TxCallbackHandle::install(u16::max_value(), 0, |_| ()).is_err());
+    }
+
+    #[capsule::test]
+    fn callback_sees_burst() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        let user_param = into_user_param(move |pkts| {
+            counter.fetch_add(pkts.len(), Ordering::Relaxed);
+        });
+
+        let mut pkts = vec![
+            Mbuf::new().unwrap().into_ptr(),
+            Mbuf::new().unwrap().into_ptr(),
+        ];
+
+        unsafe {
+            assert_eq!(
+                2,
+                rx_callback_main(0, 0, pkts.as_mut_ptr(), 2, 32, user_param)
+            );
+            assert_eq!(0, tx_callback_main(0, 0, pkts.as_mut_ptr(), 0, user_param));
+            drop(Box::from_raw(user_param as *mut BurstFn));
+
+            for ptr in pkts {
+                drop(Mbuf::from_ptr(ptr));
+            }
+        }
+
+        assert_eq!(2, count.load(Ordering::Relaxed));
+    }
+}
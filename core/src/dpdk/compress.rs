@@ -0,0 +1,502 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::{tsc_cycles, DpdkError, Mbuf, SocketId};
+use crate::ffi::{self, ToCString, ToResult};
+use crate::{debug, ensure, info};
+use anyhow::Result;
+use std::fmt;
+use std::os::raw;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use thiserror::Error;
+
+/// The number of operations in the pool of a `CompressSession`. Only one
+/// operation is in flight at a time.
+const OP_POOL_SIZE: u32 = 63;
+
+/// The maximum number of private transforms per device. Each session
+/// creates one for compression and one for decompression.
+const MAX_PRIV_XFORMS: u16 = 64;
+
+/// The maximum number of operations in flight on the queue pair.
+const MAX_INFLIGHT_OPS: u32 = 512;
+
+/// The base two log of the deflate window size.
+const DEFLATE_WINDOW_SIZE: u8 = 15;
+
+/// The maximum compression level.
+const MAX_LEVEL: u8 = 9;
+
+/// Compress device errors.
+#[derive(Debug, Error)]
+pub(crate) enum CompressError {
+    /// The device id is not valid.
+    #[error("Compress device {0} is not found.")]
+    NotFound(u8),
+
+    /// The compression level is not valid.
+    #[error("Compression level {0} is not within 0..=9.")]
+    InvalidLevel(u8),
+
+    /// The input does not fit in a single mbuf.
+    #[error("Input of {0} bytes does not fit in a single mbuf.")]
+    InputTooLarge(usize),
+
+    /// No operation can be allocated from the pool.
+    #[error("Cannot allocate a new compress operation.")]
+    Exhausted,
+
+    /// The queue pair is full.
+    #[error("Compress queue pair is full.")]
+    QueueFull,
+
+    /// The output does not fit in a single mbuf.
+    #[error("Output does not fit in a single mbuf.")]
+    OutOfSpace,
+
+    /// The operation failed with the status.
+    #[error("Compress operation failed with status {0}.")]
+    Failed(u8),
+
+    /// The operation did not complete in time.
+    #[error("Compress operation timed out.")]
+    Timeout,
+}
+
+/// A compress device configured with a single queue pair.
+///
+/// All the sessions created on the device share the queue pair. Sessions
+/// must be dropped before the device is.
+pub struct CompressDevice {
+    dev_id: u8,
+    socket_id: SocketId,
+}
+
+impl CompressDevice {
+    /// Returns the number of compress devices available.
+    pub fn count() -> usize {
+        unsafe { ffi::rte_compressdev_count() as usize }
+    }
+
+    /// Configures and starts a compress device with one queue pair.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CompressError::NotFound` if the device id is invalid. If
+    /// the device fails to start, `DpdkError` is returned.
+    pub fn open(dev_id: u8) -> Result<Self> {
+        ensure!(
+            (dev_id as usize) < CompressDevice::count(),
+            CompressError::NotFound(dev_id)
+        );
+
+        let socket_id = match unsafe { ffi::rte_compressdev_socket_id(dev_id) } {
+            id if id < 0 => SocketId::ANY,
+            id => SocketId(id),
+        };
+
+        // only stateless operations are used, so no stream is needed.
+        let mut config = ffi::rte_compressdev_config {
+            socket_id: socket_id.raw(),
+            nb_queue_pairs: 1,
+            max_nb_priv_xforms: MAX_PRIV_XFORMS,
+            max_nb_streams: 0,
+        };
+
+        unsafe {
+            ffi::rte_compressdev_configure(dev_id, &mut config)
+                .into_result(DpdkError::from_errno)?;
+            ffi::rte_compressdev_queue_pair_setup(dev_id, 0, MAX_INFLIGHT_OPS, socket_id.raw())
+                .into_result(DpdkError::from_errno)?;
+            ffi::rte_compressdev_start(dev_id).into_result(DpdkError::from_errno)?;
+        }
+
+        info!(dev_id, "compress device started.");
+        Ok(CompressDevice { dev_id, socket_id })
+    }
+
+    /// Returns the device id.
+    pub fn dev_id(&self) -> u8 {
+        self.dev_id
+    }
+
+    /// Returns the socket the device is on.
+    pub fn socket_id(&self) -> SocketId {
+        self.socket_id
+    }
+}
+
+impl fmt::Debug for CompressDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressDevice")
+            .field("dev_id", &self.dev_id)
+            .field("socket_id", &self.socket_id)
+            .finish()
+    }
+}
+
+impl Drop for CompressDevice {
+    fn drop(&mut self) {
+        debug!(dev_id = self.dev_id, "stopping compress device.");
+        unsafe {
+            ffi::rte_compressdev_stop(self.dev_id);
+            ffi::rte_compressdev_close(self.dev_id);
+        }
+    }
+}
+
+/// The private transforms and the operation pool of a device session.
+/// The resources are freed when dropped, including the ones created
+/// before a failure.
+struct DeviceSession {
+    dev_id: u8,
+    compress: *mut raw::c_void,
+    decompress: *mut raw::c_void,
+    op_pool: *mut ffi::rte_mempool,
+}
+
+impl DeviceSession {
+    fn new(dev_id: u8, level: u8) -> Result<Self> {
+        static SESSION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let n = SESSION_COUNT.fetch_add(1, Ordering::Relaxed);
+        let socket_id = unsafe { ffi::rte_compressdev_socket_id(dev_id) }.max(SocketId::ANY.raw());
+
+        let mut session = DeviceSession {
+            dev_id,
+            compress: ptr::null_mut(),
+            decompress: ptr::null_mut(),
+            op_pool: ptr::null_mut(),
+        };
+
+        let mut xform = ffi::rte_comp_xform {
+            type_: ffi::rte_comp_xform_type::RTE_COMP_COMPRESS,
+            ..Default::default()
+        };
+        unsafe {
+            let compress = &mut xform.__bindgen_anon_1.compress;
+            compress.algo = ffi::rte_comp_algorithm::RTE_COMP_ALGO_DEFLATE;
+            compress.__bindgen_anon_1.deflate.huffman =
+                ffi::rte_comp_huffman::RTE_COMP_HUFFMAN_DEFAULT;
+            compress.level = level as raw::c_int;
+            compress.window_size = DEFLATE_WINDOW_SIZE;
+            compress.chksum = ffi::rte_comp_checksum_type::RTE_COMP_CHECKSUM_NONE;
+            compress.hash_algo = ffi::rte_comp_hash_algorithm::RTE_COMP_HASH_ALGO_NONE;
+
+            ffi::rte_compressdev_private_xform_create(dev_id, &xform, &mut session.compress)
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        let mut xform = ffi::rte_comp_xform {
+            type_: ffi::rte_comp_xform_type::RTE_COMP_DECOMPRESS,
+            ..Default::default()
+        };
+        unsafe {
+            let decompress = &mut xform.__bindgen_anon_1.decompress;
+            decompress.algo = ffi::rte_comp_algorithm::RTE_COMP_ALGO_DEFLATE;
+            decompress.window_size = DEFLATE_WINDOW_SIZE;
+            decompress.chksum = ffi::rte_comp_checksum_type::RTE_COMP_CHECKSUM_NONE;
+            decompress.hash_algo = ffi::rte_comp_hash_algorithm::RTE_COMP_HASH_ALGO_NONE;
+
+            ffi::rte_compressdev_private_xform_create(dev_id, &xform, &mut session.decompress)
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        unsafe {
+            session.op_pool = ffi::rte_comp_op_pool_create(
+                format!("comp_op{}", n).into_cstring().as_ptr(),
+                OP_POOL_SIZE,
+                0,
+                0,
+                socket_id,
+            )
+            .into_result(|_| DpdkError::new())?
+            .as_ptr();
+        }
+
+        Ok(session)
+    }
+
+    /// Runs a stateless operation through the device and appends the
+    /// produced data to `output`.
+    fn process(
+        &self,
+        xform: *mut raw::c_void,
+        input: &[u8],
+        output: &mut Vec<u8>,
+    ) -> Result<usize> {
+        let src = if input.is_empty() {
+            Mbuf::new()?
+        } else {
+            Mbuf::from_bytes(input).map_err(|_| CompressError::InputTooLarge(input.len()))?
+        };
+
+        // the whole buffer of the destination is made available to the
+        // device. `extend` needs to leave at least one byte of tailroom.
+        let mut dst = Mbuf::new()?;
+        let capacity = {
+            let raw = dst.raw();
+            (raw.buf_len - raw.data_off) as usize - 1
+        };
+        dst.extend(0, capacity)?;
+
+        let op = unsafe {
+            ffi::rte_comp_op_alloc(self.op_pool).into_result(|_| CompressError::Exhausted)?
+        };
+
+        unsafe {
+            let raw = &mut *op.as_ptr();
+            raw.op_type = ffi::rte_comp_op_type::RTE_COMP_OP_STATELESS;
+            raw.__bindgen_anon_1.private_xform = xform;
+            raw.m_src = src.into_ptr();
+            raw.m_dst = dst.into_ptr();
+            raw.src.offset = 0;
+            raw.src.length = input.len() as u32;
+            raw.dst.offset = 0;
+            raw.flush_flag = ffi::rte_comp_flush_flag::RTE_COMP_FLUSH_FINAL;
+            raw.input_chksum = 0;
+
+            let mut ops = [op.as_ptr()];
+            if ffi::rte_compressdev_enqueue_burst(self.dev_id, 0, ops.as_mut_ptr(), 1) == 0 {
+                let _ = Mbuf::from_ptr(raw.m_src);
+                let _ = Mbuf::from_ptr(raw.m_dst);
+                ffi::rte_comp_op_free(op.as_ptr());
+                return Err(CompressError::QueueFull.into());
+            }
+        }
+
+        // the operation is polled for at most a second. on timeout, the
+        // operation and its mbufs are still owned by the device and are
+        // leaked.
+        let deadline = tsc_cycles() + unsafe { ffi::rte_get_tsc_hz() };
+        let mut ops = [ptr::null_mut()];
+        while unsafe { ffi::rte_compressdev_dequeue_burst(self.dev_id, 0, ops.as_mut_ptr(), 1) }
+            == 0
+        {
+            ensure!(tsc_cycles() < deadline, CompressError::Timeout);
+        }
+
+        unsafe {
+            let done = &*ops[0];
+            let status = done.status;
+            let produced = done.produced as usize;
+            let src = Mbuf::from_ptr(done.m_src);
+            let dst = Mbuf::from_ptr(done.m_dst);
+            ffi::rte_comp_op_free(ops[0]);
+            drop(src);
+
+            match status as raw::c_uint {
+                ffi::rte_comp_op_status::RTE_COMP_OP_STATUS_SUCCESS => (),
+                ffi::rte_comp_op_status::RTE_COMP_OP_STATUS_OUT_OF_SPACE_TERMINATED
+                | ffi::rte_comp_op_status::RTE_COMP_OP_STATUS_OUT_OF_SPACE_RECOVERABLE => {
+                    return Err(CompressError::OutOfSpace.into())
+                }
+                _ => return Err(CompressError::Failed(status).into()),
+            }
+
+            if produced > 0 {
+                let data = dst.read_data_slice::<u8>(0, produced)?;
+                output.extend_from_slice(data.as_ref());
+            }
+            Ok(produced)
+        }
+    }
+}
+
+impl Drop for DeviceSession {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.compress.is_null() {
+                ffi::rte_compressdev_private_xform_free(self.dev_id, self.compress);
+            }
+            if !self.decompress.is_null() {
+                ffi::rte_compressdev_private_xform_free(self.dev_id, self.decompress);
+            }
+            // `rte_mempool_free` is a no-op on null pointers.
+            ffi::rte_mempool_free(self.op_pool);
+        }
+    }
+}
+
+/// The backend a `CompressSession` runs its operations on.
+enum Backend {
+    Device(DeviceSession),
+    #[cfg(feature = "compress-sw")]
+    Software(flate2::Compression),
+}
+
+/// A stateless deflate session.
+///
+/// Operations on a device session are enqueued to the first queue pair of
+/// the device, one at a time, and polled until they complete. Both the
+/// input and the output must fit in a single mbuf.
+///
+/// With the `compress-sw` feature, a session can also run in software
+/// using `flate2`, which has no size limit. Both backends produce raw
+/// deflate streams without a zlib header or checksum, so data compressed
+/// by one can be decompressed by the other.
+pub struct CompressSession {
+    backend: Backend,
+}
+
+impl CompressSession {
+    /// Creates a new deflate session on the device with the compression
+    /// `level` between 0 and 9.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CompressError::NotFound` if the device id is invalid, or
+    /// `CompressError::InvalidLevel` if the level is greater than 9. If
+    /// the session cannot be created, `DpdkError` is returned.
+    pub fn new_deflate(level: u8, dev_id: u8) -> Result<Self> {
+        ensure!(level <= MAX_LEVEL, CompressError::InvalidLevel(level));
+        ensure!(
+            (dev_id as usize) < CompressDevice::count(),
+            CompressError::NotFound(dev_id)
+        );
+
+        let session = DeviceSession::new(dev_id, level)?;
+        info!(dev_id, level, "created deflate compress session.");
+        Ok(CompressSession {
+            backend: Backend::Device(session),
+        })
+    }
+
+    /// Creates a new deflate session in software with the compression
+    /// `level` between 0 and 9.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CompressError::InvalidLevel` if the level is greater
+    /// than 9.
+    #[cfg(feature = "compress-sw")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "compress-sw")))]
+    pub fn new_deflate_sw(level: u8) -> Result<Self> {
+        ensure!(level <= MAX_LEVEL, CompressError::InvalidLevel(level));
+        Ok(CompressSession {
+            backend: Backend::Software(flate2::Compression::new(level as u32)),
+        })
+    }
+
+    /// Returns the id of the device the session is on, or `None` if the
+    /// session runs in software.
+    pub fn dev_id(&self) -> Option<u8> {
+        match self.backend {
+            Backend::Device(ref session) => Some(session.dev_id),
+            #[cfg(feature = "compress-sw")]
+            Backend::Software(_) => None,
+        }
+    }
+
+    /// Compresses `input` and appends the compressed data to `output`.
+    /// Returns the compressed length.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CompressError::InputTooLarge` or `CompressError::OutOfSpace`
+    /// if the input or the output does not fit in an mbuf,
+    /// `CompressError::Timeout` if the device does not complete the
+    /// operation within a second, or `CompressError::Failed` if the
+    /// device fails the operation.
+    pub fn compress(&self, input: &[u8], output: &mut Vec<u8>) -> Result<usize> {
+        match self.backend {
+            Backend::Device(ref session) => session.process(session.compress, input, output),
+            #[cfg(feature = "compress-sw")]
+            Backend::Software(level) => {
+                use std::io::Write;
+
+                let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), level);
+                encoder.write_all(input)?;
+                let data = encoder.finish()?;
+                output.extend_from_slice(&data);
+                Ok(data.len())
+            }
+        }
+    }
+
+    /// Decompresses `input` and appends the decompressed data to `output`.
+    /// Returns the decompressed length.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `compress`. In software, returns an
+    /// I/O error if the input is not a valid deflate stream.
+    pub fn decompress(&self, input: &[u8], output: &mut Vec<u8>) -> Result<usize> {
+        match self.backend {
+            Backend::Device(ref session) => session.process(session.decompress, input, output),
+            #[cfg(feature = "compress-sw")]
+            Backend::Software(_) => {
+                use std::io::Read;
+
+                let mut decoder = flate2::read::DeflateDecoder::new(input);
+                let mut data = Vec::new();
+                decoder.read_to_end(&mut data)?;
+                output.extend_from_slice(&data);
+                Ok(data.len())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for CompressSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompressSession")
+            .field("dev_id", &self.dev_id())
+            .finish()
+    }
+}
+
+unsafe impl Send for CompressSession {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn open_invalid_device() {
+        assert!(CompressDevice::open(u8::MAX).is_err());
+    }
+
+    #[capsule::test]
+    fn session_invalid_device() {
+        assert!(CompressSession::new_deflate(6, u8::MAX).is_err());
+    }
+
+    #[cfg(feature = "compress-sw")]
+    #[test]
+    fn software_round_trip() {
+        let session = CompressSession::new_deflate_sw(6).unwrap();
+        let input = b"capsule capsule capsule capsule capsule capsule".to_vec();
+
+        let mut compressed = vec![];
+        let len = session.compress(&input, &mut compressed).unwrap();
+        assert_eq!(len, compressed.len());
+        assert!(len < input.len());
+
+        let mut decompressed = vec![];
+        let len = session.decompress(&compressed, &mut decompressed).unwrap();
+        assert_eq!(input.len(), len);
+        assert_eq!(input, decompressed);
+    }
+
+    #[cfg(feature = "compress-sw")]
+    #[test]
+    fn invalid_level() {
+        assert!(CompressSession::new_deflate_sw(10).is_err());
+    }
+}
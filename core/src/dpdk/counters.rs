@@ -0,0 +1,229 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::ffi;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Pads and aligns a value to the length of a cache line.
+///
+/// Values updated by different cores are kept on separate cache lines,
+/// so concurrent writes do not invalidate each other's cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(align(64))]
+pub struct CachePadded<T> {
+    value: T,
+}
+
+impl<T> CachePadded<T> {
+    /// Pads and aligns a value.
+    pub const fn new(value: T) -> Self {
+        CachePadded { value }
+    }
+
+    /// Returns the inner value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// A counter with one slot per lcore.
+///
+/// Each lcore only updates its own slot, so increments are never
+/// contended. Reading the total sums all the slots, which is not an
+/// atomic snapshot of the counter while it's being updated.
+#[derive(Debug)]
+pub struct PerLcoreCounters {
+    counters: Vec<CachePadded<AtomicU64>>,
+}
+
+impl PerLcoreCounters {
+    /// Creates a new counter with a slot for every possible lcore.
+    pub fn new() -> Self {
+        PerLcoreCounters {
+            counters: (0..ffi::RTE_MAX_LCORE)
+                .map(|_| CachePadded::new(AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+
+    /// Adds the value to the slot of the lcore.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lcore_id` is not less than `RTE_MAX_LCORE`.
+    #[inline]
+    pub fn increment(&self, lcore_id: u32, value: u64) {
+        self.counters[lcore_id as usize].fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Returns the value of the slot of the lcore.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lcore_id` is not less than `RTE_MAX_LCORE`.
+    #[inline]
+    pub fn read(&self, lcore_id: u32) -> u64 {
+        self.counters[lcore_id as usize].load(Ordering::Relaxed)
+    }
+
+    /// Returns the sum of all the slots.
+    pub fn total(&self) -> u64 {
+        self.counters
+            .iter()
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Resets all the slots to 0.
+    pub fn reset(&self) {
+        self.counters
+            .iter()
+            .for_each(|counter| counter.store(0, Ordering::Relaxed));
+    }
+}
+
+impl Default for PerLcoreCounters {
+    fn default() -> Self {
+        PerLcoreCounters::new()
+    }
+}
+
+/// The common packet counters of a pipeline.
+#[derive(Debug, Default)]
+pub struct CounterSet {
+    /// Packets received.
+    pub rx_packets: PerLcoreCounters,
+    /// Packets transmitted.
+    pub tx_packets: PerLcoreCounters,
+    /// Packets dropped.
+    pub dropped_packets: PerLcoreCounters,
+    /// Bytes received.
+    pub rx_bytes: PerLcoreCounters,
+    /// Bytes transmitted.
+    pub tx_bytes: PerLcoreCounters,
+}
+
+impl CounterSet {
+    /// Creates a new set of counters.
+    pub fn new() -> Self {
+        CounterSet::default()
+    }
+
+    /// Returns the totals of all the counters.
+    ///
+    /// The counters are read one after another, so a snapshot taken while
+    /// the counters are being updated is only consistent within each
+    /// counter.
+    pub fn snapshot(&self) -> CounterSnapshot {
+        CounterSnapshot {
+            rx_packets: self.rx_packets.total(),
+            tx_packets: self.tx_packets.total(),
+            dropped_packets: self.dropped_packets.total(),
+            rx_bytes: self.rx_bytes.total(),
+            tx_bytes: self.tx_bytes.total(),
+        }
+    }
+
+    /// Resets all the counters to 0.
+    pub fn reset(&self) {
+        self.rx_packets.reset();
+        self.tx_packets.reset();
+        self.dropped_packets.reset();
+        self.rx_bytes.reset();
+        self.tx_bytes.reset();
+    }
+}
+
+/// The totals of a `CounterSet` at a point in time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CounterSnapshot {
+    /// Packets received.
+    pub rx_packets: u64,
+    /// Packets transmitted.
+    pub tx_packets: u64,
+    /// Packets dropped.
+    pub dropped_packets: u64,
+    /// Bytes received.
+    pub rx_bytes: u64,
+    /// Bytes transmitted.
+    pub tx_bytes: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn cache_padded_alignment() {
+        assert_eq!(64, mem::align_of::<CachePadded<AtomicU64>>());
+        assert_eq!(64, mem::size_of::<CachePadded<AtomicU64>>());
+    }
+
+    #[test]
+    fn per_lcore_increment_and_total() {
+        let counters = Arc::new(PerLcoreCounters::new());
+
+        let handles = (0..4)
+            .map(|lcore_id| {
+                let counters = counters.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        counters.increment(lcore_id, 2);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+
+        assert_eq!(2000, counters.read(0));
+        assert_eq!(2000, counters.read(3));
+        assert_eq!(0, counters.read(4));
+        assert_eq!(8000, counters.total());
+
+        counters.reset();
+        assert_eq!(0, counters.total());
+    }
+
+    #[test]
+    fn counter_set_snapshot() {
+        let set = CounterSet::new();
+        set.rx_packets.increment(0, 10);
+        set.rx_packets.increment(1, 5);
+        set.rx_bytes.increment(1, 1500);
+        set.dropped_packets.increment(2, 1);
+
+        let snapshot = set.snapshot();
+        assert_eq!(15, snapshot.rx_packets);
+        assert_eq!(1500, snapshot.rx_bytes);
+        assert_eq!(1, snapshot.dropped_packets);
+        assert_eq!(0, snapshot.tx_packets);
+        assert_eq!(0, snapshot.tx_bytes);
+    }
+}
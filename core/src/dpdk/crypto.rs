@@ -0,0 +1,778 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, Mbuf, SocketId};
+use crate::ffi::{self, AsStr, ToCString, ToResult};
+use crate::{debug, ensure, info};
+use anyhow::Result;
+use std::fmt;
+use std::mem;
+use std::os::raw;
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use thiserror::Error;
+
+/// The number of operations in the pool of a `CryptoSession`.
+const OP_POOL_SIZE: u32 = 1023;
+
+/// The per core cache size of the operation pool.
+const OP_POOL_CACHE_SIZE: u32 = 32;
+
+/// The length of the AES-GCM initialization vector.
+pub(crate) const AES_GCM_IV_LEN: usize = 12;
+
+/// The length of the AES-GCM authentication tag.
+pub(crate) const AES_GCM_DIGEST_LEN: usize = 16;
+
+/// The maximum length of the additional authenticated data.
+const MAX_AAD_LEN: usize = 64;
+
+/// The offset of the initialization vector from the start of the operation.
+/// The IV, the AAD and the digest are stored in the private data area
+/// following the symmetric operation, which has a physical address the
+/// device can read from and write to.
+pub(crate) const IV_OFFSET: usize =
+    mem::size_of::<ffi::rte_crypto_op>() + mem::size_of::<ffi::rte_crypto_sym_op>();
+
+/// The offset of the additional authenticated data.
+const AAD_OFFSET: usize = IV_OFFSET + 16;
+
+/// The offset of the digest.
+const DIGEST_OFFSET: usize = AAD_OFFSET + MAX_AAD_LEN;
+
+/// The size of the private data area.
+const PRIV_SIZE: usize = DIGEST_OFFSET + AES_GCM_DIGEST_LEN - IV_OFFSET;
+
+/// Crypto device errors.
+#[derive(Debug, Error)]
+pub(crate) enum CryptoError {
+    /// The device id is not valid.
+    #[error("Crypto device {0} is not found.")]
+    NotFound(u8),
+
+    /// The operation type is not supported.
+    #[error("Crypto operation type {0:?} is not supported.")]
+    Unsupported(CryptoOpType),
+
+    /// The length of a parameter is not what the session expects.
+    #[error("Invalid {0} length {1}, expected {2}.")]
+    InvalidLength(&'static str, usize, usize),
+
+    /// The data range is not within the packet.
+    #[error("Data range {0}..{1} is out of the packet bounds.")]
+    OutOfBounds(usize, usize),
+
+    /// No operation can be allocated from the pool.
+    #[error("Cannot allocate a new crypto operation.")]
+    Exhausted,
+
+    /// The queue pair is full.
+    #[error("Crypto queue pair is full.")]
+    QueueFull,
+}
+
+/// A capability of a crypto device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CryptoCapability {
+    /// A symmetric cipher algorithm.
+    Cipher(String),
+    /// A symmetric authentication algorithm.
+    Auth(String),
+    /// A symmetric authenticated encryption algorithm.
+    Aead(String),
+    /// An asymmetric transform.
+    Asymmetric,
+}
+
+impl CryptoCapability {
+    /// Creates a capability from the raw capability.
+    ///
+    /// # Safety
+    ///
+    /// The algorithm index must be valid for the corresponding string table.
+    unsafe fn from_raw(raw: &ffi::rte_cryptodev_capabilities) -> Option<Self> {
+        unsafe fn algo_name(table: *const *const raw::c_char, algo: raw::c_uint) -> String {
+            let name = *table.add(algo as usize);
+            if name.is_null() {
+                format!("unknown({})", algo)
+            } else {
+                name.as_str().to_owned()
+            }
+        }
+
+        match raw.op {
+            ffi::rte_crypto_op_type::RTE_CRYPTO_OP_TYPE_SYMMETRIC => {
+                let sym = &raw.__bindgen_anon_1.sym;
+                match sym.xform_type {
+                    ffi::rte_crypto_sym_xform_type::RTE_CRYPTO_SYM_XFORM_CIPHER => {
+                        Some(CryptoCapability::Cipher(algo_name(
+                            ptr::addr_of!(ffi::rte_crypto_cipher_algorithm_strings).cast(),
+                            sym.__bindgen_anon_1.cipher.algo,
+                        )))
+                    }
+                    ffi::rte_crypto_sym_xform_type::RTE_CRYPTO_SYM_XFORM_AUTH => {
+                        Some(CryptoCapability::Auth(algo_name(
+                            ptr::addr_of!(ffi::rte_crypto_auth_algorithm_strings).cast(),
+                            sym.__bindgen_anon_1.auth.algo,
+                        )))
+                    }
+                    ffi::rte_crypto_sym_xform_type::RTE_CRYPTO_SYM_XFORM_AEAD => {
+                        Some(CryptoCapability::Aead(algo_name(
+                            ptr::addr_of!(ffi::rte_crypto_aead_algorithm_strings).cast(),
+                            sym.__bindgen_anon_1.aead.algo,
+                        )))
+                    }
+                    _ => None,
+                }
+            }
+            ffi::rte_crypto_op_type::RTE_CRYPTO_OP_TYPE_ASYMMETRIC => {
+                Some(CryptoCapability::Asymmetric)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The contextual information of a crypto device.
+#[derive(Clone, Debug)]
+pub struct CryptoDevInfo {
+    driver_name: String,
+    feature_flags: u64,
+    max_queue_pairs: u32,
+    max_sessions: u32,
+    capabilities: Vec<CryptoCapability>,
+}
+
+impl CryptoDevInfo {
+    /// Returns the name of the driver.
+    pub fn driver_name(&self) -> &str {
+        &self.driver_name
+    }
+
+    /// Returns the `RTE_CRYPTODEV_FF_*` feature flags.
+    pub fn feature_flags(&self) -> u64 {
+        self.feature_flags
+    }
+
+    /// Returns whether the device is hardware accelerated.
+    pub fn is_hw_accelerated(&self) -> bool {
+        self.feature_flags & ffi::RTE_CRYPTODEV_FF_HW_ACCELERATED as u64 != 0
+    }
+
+    /// Returns the maximum number of queue pairs.
+    pub fn max_queue_pairs(&self) -> u32 {
+        self.max_queue_pairs
+    }
+
+    /// Returns the maximum number of symmetric sessions. 0 means there's
+    /// no limit.
+    pub fn max_sessions(&self) -> u32 {
+        self.max_sessions
+    }
+
+    /// Returns the capabilities of the device.
+    pub fn capabilities(&self) -> &[CryptoCapability] {
+        &self.capabilities
+    }
+
+    /// Returns whether the device supports AES-GCM.
+    pub fn supports_aes_gcm(&self) -> bool {
+        self.capabilities
+            .iter()
+            .any(|capa| *capa == CryptoCapability::Aead("aes-gcm".to_owned()))
+    }
+}
+
+/// A crypto device configured with a single queue pair.
+///
+/// All the sessions created on the device share the queue pair. Sessions
+/// must be dropped before the device is.
+pub struct CryptoDevice {
+    dev_id: u8,
+    socket_id: SocketId,
+}
+
+impl CryptoDevice {
+    /// Returns the number of crypto devices available.
+    pub fn count() -> usize {
+        unsafe { ffi::rte_cryptodev_count() as usize }
+    }
+
+    /// Retrieves the contextual information of a crypto device, including
+    /// the list of its capabilities.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CryptoError::NotFound` if the device id is invalid.
+    pub fn info(dev_id: u8) -> Result<CryptoDevInfo> {
+        ensure!(
+            (dev_id as usize) < CryptoDevice::count(),
+            CryptoError::NotFound(dev_id)
+        );
+
+        let mut raw = ffi::rte_cryptodev_info::default();
+        unsafe {
+            ffi::rte_cryptodev_info_get(dev_id, &mut raw);
+        }
+
+        let mut capabilities = vec![];
+        let mut capa = raw.capabilities;
+        unsafe {
+            // the list is terminated by an entry with an undefined op type.
+            while !capa.is_null()
+                && (*capa).op != ffi::rte_crypto_op_type::RTE_CRYPTO_OP_TYPE_UNDEFINED
+            {
+                capabilities.extend(CryptoCapability::from_raw(&*capa));
+                capa = capa.add(1);
+            }
+        }
+
+        let driver_name = if raw.driver_name.is_null() {
+            String::new()
+        } else {
+            raw.driver_name.as_str().to_owned()
+        };
+
+        Ok(CryptoDevInfo {
+            driver_name,
+            feature_flags: raw.feature_flags,
+            max_queue_pairs: raw.max_nb_queue_pairs,
+            max_sessions: raw.sym.max_nb_sessions,
+            capabilities,
+        })
+    }
+
+    /// Configures and starts a crypto device with one queue pair of
+    /// `nb_descriptors`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CryptoError::NotFound` if the device id is invalid. If the
+    /// device fails to start, `DpdkError` is returned.
+    pub fn start(dev_id: u8, nb_descriptors: u32) -> Result<Self> {
+        ensure!(
+            (dev_id as usize) < CryptoDevice::count(),
+            CryptoError::NotFound(dev_id)
+        );
+
+        let socket_id = match unsafe { ffi::rte_cryptodev_socket_id(dev_id) } {
+            id if id < 0 => SocketId::ANY,
+            id => SocketId(id),
+        };
+
+        let mut config = ffi::rte_cryptodev_config {
+            socket_id: socket_id.raw(),
+            nb_queue_pairs: 1,
+            ff_disable: ffi::RTE_CRYPTODEV_FF_ASYMMETRIC_CRYPTO as u64,
+        };
+
+        // sessionless operations are not used, so the queue pair does not
+        // need the session pools.
+        let qp_conf = ffi::rte_cryptodev_qp_conf {
+            nb_descriptors,
+            ..Default::default()
+        };
+
+        unsafe {
+            ffi::rte_cryptodev_configure(dev_id, &mut config).into_result(DpdkError::from_errno)?;
+            ffi::rte_cryptodev_queue_pair_setup(dev_id, 0, &qp_conf, socket_id.raw())
+                .into_result(DpdkError::from_errno)?;
+            ffi::rte_cryptodev_start(dev_id).into_result(DpdkError::from_errno)?;
+        }
+
+        info!(dev_id, "crypto device started.");
+        Ok(CryptoDevice { dev_id, socket_id })
+    }
+
+    /// Returns the device id.
+    pub fn dev_id(&self) -> u8 {
+        self.dev_id
+    }
+
+    /// Returns the socket the device is on.
+    pub fn socket_id(&self) -> SocketId {
+        self.socket_id
+    }
+}
+
+impl fmt::Debug for CryptoDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CryptoDevice")
+            .field("dev_id", &self.dev_id)
+            .field("socket_id", &self.socket_id)
+            .finish()
+    }
+}
+
+impl Drop for CryptoDevice {
+    fn drop(&mut self) {
+        debug!(dev_id = self.dev_id, "stopping crypto device.");
+        unsafe {
+            ffi::rte_cryptodev_stop(self.dev_id);
+            ffi::rte_cryptodev_close(self.dev_id);
+        }
+    }
+}
+
+/// The direction of a cipher operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CryptoDirection {
+    /// Encrypts the data and generates the digest.
+    Encrypt,
+    /// Decrypts the data and verifies the digest.
+    Decrypt,
+}
+
+/// The type of a crypto operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CryptoOpType {
+    /// A symmetric operation on a packet.
+    Symmetric,
+    /// An asymmetric operation.
+    Asymmetric,
+}
+
+/// The status of a crypto operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CryptoOpStatus {
+    /// The operation completed successfully.
+    Success,
+    /// The operation has not yet been processed by the device.
+    NotProcessed,
+    /// The digest verification failed.
+    AuthFailed,
+    /// The session is not valid.
+    InvalidSession,
+    /// The operation failed due to invalid arguments.
+    InvalidArgs,
+    /// The operation failed.
+    Error,
+}
+
+impl From<u8> for CryptoOpStatus {
+    fn from(status: u8) -> Self {
+        match status as raw::c_uint {
+            ffi::rte_crypto_op_status::RTE_CRYPTO_OP_STATUS_SUCCESS => CryptoOpStatus::Success,
+            ffi::rte_crypto_op_status::RTE_CRYPTO_OP_STATUS_NOT_PROCESSED => {
+                CryptoOpStatus::NotProcessed
+            }
+            ffi::rte_crypto_op_status::RTE_CRYPTO_OP_STATUS_AUTH_FAILED => {
+                CryptoOpStatus::AuthFailed
+            }
+            ffi::rte_crypto_op_status::RTE_CRYPTO_OP_STATUS_INVALID_SESSION => {
+                CryptoOpStatus::InvalidSession
+            }
+            ffi::rte_crypto_op_status::RTE_CRYPTO_OP_STATUS_INVALID_ARGS => {
+                CryptoOpStatus::InvalidArgs
+            }
+            _ => CryptoOpStatus::Error,
+        }
+    }
+}
+
+/// A crypto operation on a packet.
+///
+/// The data range is ciphered in place. For encryption, the digest is
+/// generated by the device; for decryption, the digest must be set to
+/// the expected authentication tag.
+#[derive(Debug)]
+pub struct CryptoOp {
+    /// The operation type.
+    pub op_type: CryptoOpType,
+    /// The packet to cipher.
+    pub mbuf: Mbuf,
+    /// The offset of the data to cipher in the packet.
+    pub data_offset: usize,
+    /// The length of the data to cipher.
+    pub data_len: usize,
+    /// The initialization vector.
+    pub iv: Vec<u8>,
+    /// The additional authenticated data.
+    pub aad: Vec<u8>,
+    /// The digest.
+    pub digest: Vec<u8>,
+    /// The status of the operation.
+    pub status: CryptoOpStatus,
+}
+
+impl CryptoOp {
+    /// Creates a new symmetric operation that ciphers the entire packet.
+    pub fn new(mbuf: Mbuf) -> Self {
+        let data_len = mbuf.data_len();
+        CryptoOp {
+            op_type: CryptoOpType::Symmetric,
+            mbuf,
+            data_offset: 0,
+            data_len,
+            iv: vec![],
+            aad: vec![],
+            digest: vec![0; AES_GCM_DIGEST_LEN],
+            status: CryptoOpStatus::NotProcessed,
+        }
+    }
+}
+
+/// The mempools backing a `CryptoSession`. The pools are freed when
+/// dropped, including the ones created before a failure.
+struct SessionPools {
+    sess: *mut ffi::rte_mempool,
+    sess_priv: *mut ffi::rte_mempool,
+    op: *mut ffi::rte_mempool,
+}
+
+impl SessionPools {
+    fn new(n: usize, dev_id: u8, socket_id: raw::c_int) -> Result<Self> {
+        let mut pools = SessionPools {
+            sess: ptr::null_mut(),
+            sess_priv: ptr::null_mut(),
+            op: ptr::null_mut(),
+        };
+
+        unsafe {
+            pools.sess = ffi::rte_cryptodev_sym_session_pool_create(
+                format!("crypto_sess{}", n).into_cstring().as_ptr(),
+                1,
+                0,
+                0,
+                0,
+                socket_id,
+            )
+            .into_result(|_| DpdkError::new())?
+            .as_ptr();
+
+            pools.sess_priv = ffi::rte_mempool_create(
+                format!("crypto_priv{}", n).into_cstring().as_ptr(),
+                1,
+                ffi::rte_cryptodev_sym_get_private_session_size(dev_id),
+                0,
+                0,
+                None,
+                ptr::null_mut(),
+                None,
+                ptr::null_mut(),
+                socket_id,
+                0,
+            )
+            .into_result(|_| DpdkError::new())?
+            .as_ptr();
+
+            pools.op = ffi::rte_crypto_op_pool_create(
+                format!("crypto_op{}", n).into_cstring().as_ptr(),
+                ffi::rte_crypto_op_type::RTE_CRYPTO_OP_TYPE_SYMMETRIC,
+                OP_POOL_SIZE,
+                OP_POOL_CACHE_SIZE,
+                PRIV_SIZE as u16,
+                socket_id,
+            )
+            .into_result(|_| DpdkError::new())?
+            .as_ptr();
+        }
+
+        Ok(pools)
+    }
+}
+
+impl Drop for SessionPools {
+    fn drop(&mut self) {
+        // `rte_mempool_free` is a no-op on null pointers.
+        unsafe {
+            ffi::rte_mempool_free(self.op);
+            ffi::rte_mempool_free(self.sess_priv);
+            ffi::rte_mempool_free(self.sess);
+        }
+    }
+}
+
+/// A symmetric crypto session.
+///
+/// Operations are enqueued to and dequeued from the first queue pair of
+/// the device. Each session allocates its own session and operation pools.
+pub struct CryptoSession {
+    raw: NonNull<ffi::rte_cryptodev_sym_session>,
+    dev_id: u8,
+    direction: CryptoDirection,
+    aad_len: usize,
+    pools: SessionPools,
+}
+
+impl CryptoSession {
+    /// Creates a new AES-128-GCM session without additional authenticated
+    /// data.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CryptoError::NotFound` if the device id is invalid. If the
+    /// session cannot be created, `DpdkError` is returned.
+    pub fn new_aes_gcm(key: &[u8; 16], direction: CryptoDirection, dev_id: u8) -> Result<Self> {
+        CryptoSession::new_aes_gcm_with_aad(key, direction, dev_id, 0)
+    }
+
+    /// Creates a new AES-128-GCM session with `aad_len` bytes of additional
+    /// authenticated data per operation.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CryptoError::NotFound` if the device id is invalid. If the
+    /// session cannot be created, `DpdkError` is returned.
+    pub fn new_aes_gcm_with_aad(
+        key: &[u8; 16],
+        direction: CryptoDirection,
+        dev_id: u8,
+        aad_len: usize,
+    ) -> Result<Self> {
+        static SESSION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        ensure!(
+            (dev_id as usize) < CryptoDevice::count(),
+            CryptoError::NotFound(dev_id)
+        );
+        ensure!(
+            aad_len <= MAX_AAD_LEN,
+            CryptoError::InvalidLength("aad", aad_len, MAX_AAD_LEN)
+        );
+
+        let n = SESSION_COUNT.fetch_add(1, Ordering::Relaxed);
+        let socket_id = unsafe { ffi::rte_cryptodev_socket_id(dev_id) }.max(SocketId::ANY.raw());
+        let pools = SessionPools::new(n, dev_id, socket_id)?;
+
+        let raw = unsafe {
+            ffi::rte_cryptodev_sym_session_create(pools.sess).into_result(|_| DpdkError::new())?
+        };
+
+        // from here on, drop frees the session and the pools.
+        let session = CryptoSession {
+            raw,
+            dev_id,
+            direction,
+            aad_len,
+            pools,
+        };
+
+        let op = match direction {
+            CryptoDirection::Encrypt => ffi::rte_crypto_aead_operation::RTE_CRYPTO_AEAD_OP_ENCRYPT,
+            CryptoDirection::Decrypt => ffi::rte_crypto_aead_operation::RTE_CRYPTO_AEAD_OP_DECRYPT,
+        };
+
+        let mut xform = ffi::rte_crypto_sym_xform {
+            type_: ffi::rte_crypto_sym_xform_type::RTE_CRYPTO_SYM_XFORM_AEAD,
+            ..Default::default()
+        };
+        unsafe {
+            let aead = &mut xform.__bindgen_anon_1.aead;
+            aead.op = op;
+            aead.algo = ffi::rte_crypto_aead_algorithm::RTE_CRYPTO_AEAD_AES_GCM;
+            aead.key.data = key.as_ptr();
+            aead.key.length = key.len() as u16;
+            aead.iv.offset = IV_OFFSET as u16;
+            aead.iv.length = AES_GCM_IV_LEN as u16;
+            aead.digest_length = AES_GCM_DIGEST_LEN as u16;
+            aead.aad_length = aad_len as u16;
+
+            // the key is copied into the session private data.
+            ffi::rte_cryptodev_sym_session_init(
+                dev_id,
+                session.raw.as_ptr(),
+                &mut xform,
+                session.pools.sess_priv,
+            )
+            .into_result(DpdkError::from_errno)?;
+        }
+
+        info!(dev_id, ?direction, "created AES-GCM crypto session.");
+        Ok(session)
+    }
+
+    /// Returns the id of the device the session is on.
+    pub fn dev_id(&self) -> u8 {
+        self.dev_id
+    }
+
+    /// Returns the direction of the session.
+    pub fn direction(&self) -> CryptoDirection {
+        self.direction
+    }
+
+    /// Returns the length of the additional authenticated data.
+    pub fn aad_len(&self) -> usize {
+        self.aad_len
+    }
+
+    /// Returns the raw session.
+    pub(crate) fn raw(&self) -> NonNull<ffi::rte_cryptodev_sym_session> {
+        self.raw
+    }
+
+    /// Returns the pool the operations of the session are allocated from.
+    pub(crate) fn op_pool(&self) -> *mut ffi::rte_mempool {
+        self.pools.op
+    }
+
+    /// Enqueues an operation for processing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the initialization vector, the additional
+    /// authenticated data or the digest has a length the session does
+    /// not expect, or if the data range is outside of the packet. Returns
+    /// `CryptoError::QueueFull` if the queue pair is full.
+    pub fn enqueue_op(&self, op: CryptoOp) -> Result<()> {
+        ensure!(
+            op.op_type == CryptoOpType::Symmetric,
+            CryptoError::Unsupported(op.op_type)
+        );
+        ensure!(
+            op.iv.len() == AES_GCM_IV_LEN,
+            CryptoError::InvalidLength("iv", op.iv.len(), AES_GCM_IV_LEN)
+        );
+        ensure!(
+            op.aad.len() == self.aad_len,
+            CryptoError::InvalidLength("aad", op.aad.len(), self.aad_len)
+        );
+        ensure!(
+            op.digest.len() == AES_GCM_DIGEST_LEN,
+            CryptoError::InvalidLength("digest", op.digest.len(), AES_GCM_DIGEST_LEN)
+        );
+        ensure!(
+            op.data_offset + op.data_len <= op.mbuf.data_len(),
+            CryptoError::OutOfBounds(op.data_offset, op.data_offset + op.data_len)
+        );
+
+        let raw = unsafe {
+            ffi::_rte_crypto_op_alloc(
+                self.pools.op,
+                ffi::rte_crypto_op_type::RTE_CRYPTO_OP_TYPE_SYMMETRIC,
+            )
+            .into_result(|_| CryptoError::Exhausted)?
+        };
+
+        unsafe {
+            let base = raw.as_ptr() as *mut u8;
+            let phys_addr = raw.as_ref().phys_addr;
+            ptr::copy_nonoverlapping(op.iv.as_ptr(), base.add(IV_OFFSET), AES_GCM_IV_LEN);
+            ptr::copy_nonoverlapping(op.aad.as_ptr(), base.add(AAD_OFFSET), self.aad_len);
+            ptr::copy_nonoverlapping(
+                op.digest.as_ptr(),
+                base.add(DIGEST_OFFSET),
+                AES_GCM_DIGEST_LEN,
+            );
+
+            let sym = &mut *sym_op(raw.as_ptr());
+            sym.m_src = op.mbuf.into_ptr();
+            sym.m_dst = ptr::null_mut();
+            let aead = &mut sym.__bindgen_anon_2.aead;
+            aead.data.offset = op.data_offset as u32;
+            aead.data.length = op.data_len as u32;
+            aead.aad.data = base.add(AAD_OFFSET);
+            aead.aad.phys_addr = phys_addr + AAD_OFFSET as u64;
+            aead.digest.data = base.add(DIGEST_OFFSET);
+            aead.digest.phys_addr = phys_addr + DIGEST_OFFSET as u64;
+
+            ffi::_rte_crypto_op_attach_sym_session(raw.as_ptr(), self.raw.as_ptr());
+
+            let mut ops = [raw.as_ptr()];
+            if ffi::_rte_cryptodev_enqueue_burst(self.dev_id, 0, ops.as_mut_ptr(), 1) == 0 {
+                let _ = Mbuf::from_ptr(sym.m_src);
+                ffi::_rte_crypto_op_free(raw.as_ptr());
+                return Err(CryptoError::QueueFull.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Dequeues a processed operation. Returns `None` if no operation
+    /// has completed.
+    ///
+    /// Because the queue pair is shared, the operation may belong to any
+    /// session on the same device.
+    pub fn dequeue_op(&self) -> Option<CryptoOp> {
+        let mut ops = [ptr::null_mut()];
+
+        unsafe {
+            if ffi::_rte_cryptodev_dequeue_burst(self.dev_id, 0, ops.as_mut_ptr(), 1) == 0 {
+                return None;
+            }
+
+            let raw = &mut *ops[0];
+            let base = ops[0] as *const u8;
+            let status = raw.__bindgen_anon_1.__bindgen_anon_1.status;
+            let sym = &*sym_op(raw);
+            let aead = &sym.__bindgen_anon_2.aead;
+
+            let op = CryptoOp {
+                op_type: CryptoOpType::Symmetric,
+                mbuf: Mbuf::from_ptr(sym.m_src),
+                data_offset: aead.data.offset as usize,
+                data_len: aead.data.length as usize,
+                iv: std::slice::from_raw_parts(base.add(IV_OFFSET), AES_GCM_IV_LEN).to_vec(),
+                aad: std::slice::from_raw_parts(base.add(AAD_OFFSET), self.aad_len).to_vec(),
+                digest: std::slice::from_raw_parts(base.add(DIGEST_OFFSET), AES_GCM_DIGEST_LEN)
+                    .to_vec(),
+                status: status.into(),
+            };
+
+            ffi::_rte_crypto_op_free(ops[0]);
+            Some(op)
+        }
+    }
+}
+
+/// Returns the symmetric operation following the crypto operation.
+#[inline]
+pub(crate) unsafe fn sym_op(op: *mut ffi::rte_crypto_op) -> *mut ffi::rte_crypto_sym_op {
+    (op as *mut u8).add(mem::size_of::<ffi::rte_crypto_op>()) as *mut ffi::rte_crypto_sym_op
+}
+
+impl fmt::Debug for CryptoSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CryptoSession")
+            .field("raw", &self.raw)
+            .field("dev_id", &self.dev_id)
+            .field("direction", &self.direction)
+            .field("aad_len", &self.aad_len)
+            .finish()
+    }
+}
+
+impl Drop for CryptoSession {
+    fn drop(&mut self) {
+        debug!(dev_id = self.dev_id, "freeing crypto session.");
+        unsafe {
+            ffi::rte_cryptodev_sym_session_clear(self.dev_id, self.raw.as_ptr());
+            ffi::rte_cryptodev_sym_session_free(self.raw.as_ptr());
+        }
+    }
+}
+
+unsafe impl Send for CryptoSession {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn info_invalid_device() {
+        assert!(CryptoDevice::info(u8::MAX).is_err());
+    }
+
+    #[capsule::test]
+    fn session_invalid_device() {
+        let key = [0u8; 16];
+        assert!(CryptoSession::new_aes_gcm(&key, CryptoDirection::Encrypt, u8::MAX).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, RxOffloadFlags, TxOffloadFlags, ETHER_MTU};
+use crate::ffi::{self, AsStr, ToResult};
+use anyhow::Result;
+use bitflags::bitflags;
+use std::fmt;
+
+bitflags! {
+    /// The link speeds supported by an Ethernet device.
+    #[derive(Default)]
+    pub struct SpeedCapa: u32 {
+        /// Fixed speed, no auto-negotiation.
+        const FIXED = ffi::ETH_LINK_SPEED_FIXED;
+        /// 10 Mbps half-duplex.
+        const SPEED_10M_HD = ffi::ETH_LINK_SPEED_10M_HD;
+        /// 10 Mbps full-duplex.
+        const SPEED_10M = ffi::ETH_LINK_SPEED_10M;
+        /// 100 Mbps half-duplex.
+        const SPEED_100M_HD = ffi::ETH_LINK_SPEED_100M_HD;
+        /// 100 Mbps full-duplex.
+        const SPEED_100M = ffi::ETH_LINK_SPEED_100M;
+        /// 1 Gbps.
+        const SPEED_1G = ffi::ETH_LINK_SPEED_1G;
+        /// 2.5 Gbps.
+        const SPEED_2_5G = ffi::ETH_LINK_SPEED_2_5G;
+        /// 5 Gbps.
+        const SPEED_5G = ffi::ETH_LINK_SPEED_5G;
+        /// 10 Gbps.
+        const SPEED_10G = ffi::ETH_LINK_SPEED_10G;
+        /// 20 Gbps.
+        const SPEED_20G = ffi::ETH_LINK_SPEED_20G;
+        /// 25 Gbps.
+        const SPEED_25G = ffi::ETH_LINK_SPEED_25G;
+        /// 40 Gbps.
+        const SPEED_40G = ffi::ETH_LINK_SPEED_40G;
+        /// 50 Gbps.
+        const SPEED_50G = ffi::ETH_LINK_SPEED_50G;
+        /// 56 Gbps.
+        const SPEED_56G = ffi::ETH_LINK_SPEED_56G;
+        /// 100 Gbps.
+        const SPEED_100G = ffi::ETH_LINK_SPEED_100G;
+    }
+}
+
+/// The contextual information of an Ethernet device.
+#[derive(Clone, Copy)]
+pub struct DeviceInfo {
+    raw: ffi::rte_eth_dev_info,
+}
+
+impl DeviceInfo {
+    /// Retrieves the contextual information of an Ethernet device.
+    ///
+    /// # Errors
+    ///
+    /// If the port id is invalid, `DpdkError` is returned.
+    pub fn query(port_id: u16) -> Result<Self> {
+        let mut raw = ffi::rte_eth_dev_info::default();
+        unsafe {
+            ffi::rte_eth_dev_info_get(port_id, &mut raw).into_result(DpdkError::from_errno)?;
+        }
+        Ok(DeviceInfo { raw })
+    }
+
+    /// Returns the name of the driver.
+    pub fn driver_name(&self) -> &str {
+        if self.raw.driver_name.is_null() {
+            ""
+        } else {
+            self.raw.driver_name.as_str()
+        }
+    }
+
+    /// Returns the minimum MTU allowed.
+    pub fn min_mtu(&self) -> u16 {
+        self.raw.min_mtu
+    }
+
+    /// Returns the maximum MTU allowed.
+    pub fn max_mtu(&self) -> u16 {
+        self.raw.max_mtu
+    }
+
+    /// Returns the maximum length of a received frame.
+    pub fn max_rx_pktlen(&self) -> u32 {
+        self.raw.max_rx_pktlen
+    }
+
+    /// Returns whether the device supports frames larger than the standard
+    /// Ethernet MTU.
+    pub fn supports_jumbo_frames(&self) -> bool {
+        self.raw.max_mtu as usize > ETHER_MTU
+    }
+
+    /// Returns the maximum number of receive queues.
+    pub fn max_rx_queues(&self) -> u16 {
+        self.raw.max_rx_queues
+    }
+
+    /// Returns the maximum number of transmit queues.
+    pub fn max_tx_queues(&self) -> u16 {
+        self.raw.max_tx_queues
+    }
+
+    /// Returns the maximum number of MAC addresses.
+    pub fn max_mac_addrs(&self) -> u32 {
+        self.raw.max_mac_addrs
+    }
+
+    /// Returns the supported link speeds.
+    pub fn speed_capa(&self) -> SpeedCapa {
+        SpeedCapa::from_bits_truncate(self.raw.speed_capa)
+    }
+
+    /// Returns the receive offload capabilities.
+    pub fn rx_offload_capa(&self) -> RxOffloadFlags {
+        RxOffloadFlags::from_bits_truncate(self.raw.rx_offload_capa)
+    }
+
+    /// Returns the transmit offload capabilities.
+    pub fn tx_offload_capa(&self) -> TxOffloadFlags {
+        TxOffloadFlags::from_bits_truncate(self.raw.tx_offload_capa)
+    }
+}
+
+impl fmt::Debug for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceInfo")
+            .field("driver_name", &self.driver_name())
+            .field("min_mtu", &self.min_mtu())
+            .field("max_mtu", &self.max_mtu())
+            .field("max_rx_queues", &self.max_rx_queues())
+            .field("max_tx_queues", &self.max_tx_queues())
+            .field("max_mac_addrs", &self.max_mac_addrs())
+            .field("speed_capa", &self.speed_capa())
+            .field("rx_offload_capa", &self.rx_offload_capa())
+            .field("tx_offload_capa", &self.tx_offload_capa())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn device_info_fields() {
+        let name = CStr::from_bytes_with_nul(b"net_ring\0").unwrap();
+        let raw = ffi::rte_eth_dev_info {
+            driver_name: name.as_ptr(),
+            max_mtu: 9000,
+            speed_capa: ffi::ETH_LINK_SPEED_10G | ffi::ETH_LINK_SPEED_25G,
+            tx_offload_capa: ffi::DEV_TX_OFFLOAD_MBUF_FAST_FREE as u64,
+            ..Default::default()
+        };
+
+        let info = DeviceInfo { raw };
+        assert_eq!("net_ring", info.driver_name());
+        assert_eq!(9000, info.max_mtu());
+        assert!(info.supports_jumbo_frames());
+        assert_eq!(
+            SpeedCapa::SPEED_10G | SpeedCapa::SPEED_25G,
+            info.speed_capa()
+        );
+        assert_eq!(TxOffloadFlags::MBUF_FAST_FREE, info.tx_offload_capa());
+        assert!(DeviceInfo {
+            raw: Default::default()
+        }
+        .driver_name()
+        .is_empty());
+    }
+}
@@ -0,0 +1,398 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::ffi;
+use crate::{error, info};
+use anyhow::Result;
+use std::path::Path;
+use thiserror::Error;
+
+/// The log levels of `libdpdk`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum LogLevel {
+    /// System is unusable.
+    Emergency = ffi::RTE_LOG_EMERG,
+    /// Action must be taken immediately.
+    Alert = ffi::RTE_LOG_ALERT,
+    /// Critical conditions.
+    Critical = ffi::RTE_LOG_CRIT,
+    /// Error conditions.
+    Error = ffi::RTE_LOG_ERR,
+    /// Warning conditions.
+    Warning = ffi::RTE_LOG_WARNING,
+    /// Normal but significant condition.
+    Notice = ffi::RTE_LOG_NOTICE,
+    /// Informational.
+    Info = ffi::RTE_LOG_INFO,
+    /// Debug-level messages.
+    Debug = ffi::RTE_LOG_DEBUG,
+}
+
+/// EAL errors.
+#[derive(Debug, Error)]
+pub(crate) enum EalError {
+    /// The EAL can only be initialized once per process.
+    #[error("EAL is already initialized.")]
+    AlreadyInitialized,
+}
+
+/// Programmatic configuration of the Environment Abstraction Layer (EAL).
+///
+/// The configuration is serialized into the command line arguments
+/// `rte_eal_init` expects. Use this when running without the `Runtime`,
+/// which initializes the EAL from its own `RuntimeConfig`.
+///
+/// # Example
+///
+/// ```
+/// let _eal = EalConfig::new("myapp")
+///     .master_core(0)
+///     .cores(&[0, 1, 2])
+///     .memory_channels(4)
+///     .file_prefix("myapp")
+///     .allow_device("0000:00:01.0")
+///     .log_level(LogLevel::Warning)
+///     .init()?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct EalConfig {
+    app_name: String,
+    memory_channels: Option<u8>,
+    huge_dir: Option<String>,
+    no_shconf: bool,
+    log_level: Option<LogLevel>,
+    socket_mem: Vec<u32>,
+    file_prefix: Option<String>,
+    network_namespace: Option<String>,
+    master_core: Option<usize>,
+    cores: Vec<usize>,
+    allowed: Vec<String>,
+    blocked: Vec<String>,
+    vdevs: Vec<String>,
+}
+
+impl EalConfig {
+    /// Creates a new configuration. The application name is passed to
+    /// the EAL as the program name.
+    pub fn new(app_name: &str) -> Self {
+        EalConfig {
+            app_name: app_name.to_owned(),
+            memory_channels: None,
+            huge_dir: None,
+            no_shconf: false,
+            log_level: None,
+            socket_mem: vec![],
+            file_prefix: None,
+            network_namespace: None,
+            master_core: None,
+            cores: vec![],
+            allowed: vec![],
+            blocked: vec![],
+            vdevs: vec![],
+        }
+    }
+
+    /// Sets the number of memory channels to use.
+    pub fn memory_channels(&mut self, n: u8) -> &mut Self {
+        self.memory_channels = Some(n);
+        self
+    }
+
+    /// Sets the directory where the hugetlbfs is mounted.
+    pub fn huge_dir<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.huge_dir = Some(path.as_ref().to_string_lossy().into_owned());
+        self
+    }
+
+    /// Disables the shared configuration, so no hugepage backed files
+    /// are created.
+    pub fn no_shconf(&mut self) -> &mut Self {
+        self.no_shconf = true;
+        self
+    }
+
+    /// Sets the global log level.
+    pub fn log_level(&mut self, level: LogLevel) -> &mut Self {
+        self.log_level = Some(level);
+        self
+    }
+
+    /// Sets the megabytes of memory to preallocate on each socket, indexed
+    /// by socket id.
+    pub fn socket_mem(&mut self, mb_per_socket: &[u32]) -> &mut Self {
+        self.socket_mem = mb_per_socket.to_vec();
+        self
+    }
+
+    /// Sets the prefix of the hugepage files. Processes with different
+    /// prefixes do not share memory.
+    pub fn file_prefix(&mut self, prefix: &str) -> &mut Self {
+        self.file_prefix = Some(prefix.to_owned());
+        self
+    }
+
+    /// Sets the name of the network namespace the application runs in.
+    ///
+    /// The namespace is appended to the file prefix, which defaults to the
+    /// application name, so instances in different namespaces on the same
+    /// host do not share memory. The namespace is not entered, use
+    /// `NetworkNamespace::enter` before initializing the EAL.
+    pub fn network_namespace(&mut self, ns: &str) -> &mut Self {
+        self.network_namespace = Some(ns.to_owned());
+        self
+    }
+
+    /// Sets the core the EAL runs its master lcore on.
+    ///
+    /// The master core is always part of the core list, even if it's not
+    /// in the cores set with `cores`.
+    pub fn master_core(&mut self, core: usize) -> &mut Self {
+        self.master_core = Some(core);
+        self
+    }
+
+    /// Sets the cores the EAL creates lcores for. If neither the cores nor
+    /// the master core are set, the EAL uses all the cores available.
+    pub fn cores(&mut self, cores: &[usize]) -> &mut Self {
+        self.cores = cores.to_vec();
+        self
+    }
+
+    /// Adds a virtual device, with its driver arguments separated by
+    /// commas, for example `net_ring0` or `net_pcap0,iface=eth0`.
+    pub fn vdev(&mut self, vdev: &str) -> &mut Self {
+        self.vdevs.push(vdev.to_owned());
+        self
+    }
+
+    /// Adds a PCI device to the whitelist. When there is at least one
+    /// allowed device, only the allowed devices are probed.
+    pub fn allow_device(&mut self, pci_addr: &str) -> &mut Self {
+        self.allowed.push(pci_addr.to_owned());
+        self
+    }
+
+    /// Adds a PCI device to the blacklist, so it's not probed.
+    pub fn block_device(&mut self, pci_addr: &str) -> &mut Self {
+        self.blocked.push(pci_addr.to_owned());
+        self
+    }
+
+    /// Serializes the configuration into EAL arguments.
+    pub(crate) fn to_eal_args(&self) -> Vec<String> {
+        let mut args = vec![self.app_name.clone()];
+
+        if let Some(n) = self.memory_channels {
+            args.push("-n".to_owned());
+            args.push(n.to_string());
+        }
+
+        if let Some(dir) = &self.huge_dir {
+            args.push("--huge-dir".to_owned());
+            args.push(dir.clone());
+        }
+
+        if self.no_shconf {
+            args.push("--no-shconf".to_owned());
+        }
+
+        if let Some(level) = self.log_level {
+            args.push("--log-level".to_owned());
+            args.push((level as u32).to_string());
+        }
+
+        if !self.socket_mem.is_empty() {
+            let mem = self
+                .socket_mem
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            args.push("--socket-mem".to_owned());
+            args.push(mem);
+        }
+
+        let prefix = match (&self.file_prefix, &self.network_namespace) {
+            (Some(prefix), Some(ns)) => Some(format!("{}_{}", prefix, ns)),
+            (None, Some(ns)) => Some(format!("{}_{}", self.app_name, ns)),
+            (prefix, None) => prefix.clone(),
+        };
+
+        if let Some(prefix) = prefix {
+            args.push("--file-prefix".to_owned());
+            args.push(prefix);
+        }
+
+        for addr in self.allowed.iter() {
+            args.push("--pci-whitelist".to_owned());
+            args.push(addr.clone());
+        }
+
+        for addr in self.blocked.iter() {
+            args.push("--pci-blacklist".to_owned());
+            args.push(addr.clone());
+        }
+
+        for vdev in self.vdevs.iter() {
+            args.push("--vdev".to_owned());
+            args.push(vdev.clone());
+        }
+
+        if let Some(master) = self.master_core {
+            args.push("--master-lcore".to_owned());
+            args.push(master.to_string());
+        }
+
+        let mut cores = self.cores.clone();
+        if let Some(master) = self.master_core {
+            if !cores.contains(&master) {
+                cores.insert(0, master);
+            }
+        }
+
+        if !cores.is_empty() {
+            let list = cores
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            args.push("-l".to_owned());
+            args.push(list);
+        }
+
+        args
+    }
+
+    /// Initializes the EAL.
+    ///
+    /// # Errors
+    ///
+    /// If the EAL is already initialized, either by a previous call or by
+    /// the `Runtime`, an error is returned. If the EAL fails to parse the
+    /// arguments or initialize, `DpdkError` is returned. The EAL cannot be
+    /// initialized again afterwards.
+    pub fn init(&self) -> Result<Eal> {
+        info!("initializing EAL...");
+        super::eal_init(self.to_eal_args())?;
+        Ok(Eal { _private: () })
+    }
+}
+
+/// A handle to the initialized Environment Abstraction Layer.
+///
+/// There is at most one `Eal` per process. When the handle is dropped, the
+/// resources held by the EAL are released.
+#[derive(Debug)]
+pub struct Eal {
+    _private: (),
+}
+
+impl Drop for Eal {
+    fn drop(&mut self) {
+        if let Err(err) = super::eal_cleanup() {
+            error!(message = "failed to clean up EAL.", ?err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eal_config_to_eal_args() {
+        let config = EalConfig::new("myapp")
+            .memory_channels(4)
+            .huge_dir("/mnt/huge")
+            .no_shconf()
+            .log_level(LogLevel::Warning)
+            .socket_mem(&[1024, 512])
+            .file_prefix("mygroup")
+            .allow_device("0000:00:01.0")
+            .block_device("0000:00:02.0")
+            .vdev("net_ring0")
+            .master_core(0)
+            .cores(&[0, 2, 3])
+            .clone();
+
+        assert_eq!(
+            &[
+                "myapp",
+                "-n",
+                "4",
+                "--huge-dir",
+                "/mnt/huge",
+                "--no-shconf",
+                "--log-level",
+                "5",
+                "--socket-mem",
+                "1024,512",
+                "--file-prefix",
+                "mygroup",
+                "--pci-whitelist",
+                "0000:00:01.0",
+                "--pci-blacklist",
+                "0000:00:02.0",
+                "--vdev",
+                "net_ring0",
+                "--master-lcore",
+                "0",
+                "-l",
+                "0,2,3",
+            ],
+            config.to_eal_args().as_slice(),
+        )
+    }
+
+    #[test]
+    fn master_core_in_core_list() {
+        let config = EalConfig::new("myapp").master_core(1).clone();
+        assert_eq!(
+            &["myapp", "--master-lcore", "1", "-l", "1"],
+            config.to_eal_args().as_slice(),
+        );
+
+        let config = EalConfig::new("myapp")
+            .master_core(1)
+            .cores(&[2, 3])
+            .clone();
+        assert_eq!(
+            &["myapp", "--master-lcore", "1", "-l", "1,2,3"],
+            config.to_eal_args().as_slice(),
+        );
+    }
+
+    #[test]
+    fn network_namespace_file_prefix() {
+        let config = EalConfig::new("myapp").network_namespace("blue").clone();
+        assert_eq!(
+            &["myapp", "--file-prefix", "myapp_blue"],
+            config.to_eal_args().as_slice(),
+        );
+
+        let config = EalConfig::new("myapp")
+            .file_prefix("mygroup")
+            .network_namespace("blue")
+            .clone();
+        assert_eq!(
+            &["myapp", "--file-prefix", "mygroup_blue"],
+            config.to_eal_args().as_slice(),
+        );
+    }
+}
@@ -0,0 +1,540 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, Mbuf, SocketId};
+use crate::ffi::{self, ToResult};
+use crate::{debug, ensure, info};
+use anyhow::Result;
+use std::fmt;
+use std::ptr;
+use thiserror::Error;
+
+/// Event device errors.
+#[derive(Debug, Error)]
+pub(crate) enum EventDevError {
+    /// The device id is not valid.
+    #[error("Event device {0} is not found.")]
+    NotFound(u8),
+
+    /// The configuration exceeds the device limits.
+    #[error("{0} {1} exceeds the device maximum {2}.")]
+    ExceedsLimit(&'static str, u32, u32),
+}
+
+/// The scheduling type of an event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventSchedType {
+    /// Events of a flow are processed in parallel, but their original
+    /// order is restored when forwarded.
+    Ordered,
+    /// Events of a flow are processed by one port at a time.
+    Atomic,
+    /// Events are processed in parallel without ordering.
+    Parallel,
+}
+
+impl EventSchedType {
+    fn raw(self) -> u32 {
+        match self {
+            EventSchedType::Ordered => ffi::RTE_SCHED_TYPE_ORDERED,
+            EventSchedType::Atomic => ffi::RTE_SCHED_TYPE_ATOMIC,
+            EventSchedType::Parallel => ffi::RTE_SCHED_TYPE_PARALLEL,
+        }
+    }
+
+    fn from_raw(raw: u32) -> Self {
+        match raw {
+            ffi::RTE_SCHED_TYPE_ORDERED => EventSchedType::Ordered,
+            ffi::RTE_SCHED_TYPE_ATOMIC => EventSchedType::Atomic,
+            _ => EventSchedType::Parallel,
+        }
+    }
+}
+
+/// The operation of an enqueued event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventOp {
+    /// A new event injected into the device.
+    New,
+    /// A dequeued event forwarded to another queue.
+    Forward,
+    /// A dequeued event released without forwarding.
+    Release,
+}
+
+impl EventOp {
+    fn raw(self) -> u32 {
+        match self {
+            EventOp::New => ffi::RTE_EVENT_OP_NEW,
+            EventOp::Forward => ffi::RTE_EVENT_OP_FORWARD,
+            EventOp::Release => ffi::RTE_EVENT_OP_RELEASE,
+        }
+    }
+
+    fn from_raw(raw: u32) -> Self {
+        match raw {
+            ffi::RTE_EVENT_OP_NEW => EventOp::New,
+            ffi::RTE_EVENT_OP_FORWARD => EventOp::Forward,
+            _ => EventOp::Release,
+        }
+    }
+}
+
+// bit positions of the fields in the event word.
+const FLOW_ID_MASK: u64 = 0xf_ffff;
+const EVENT_TYPE_SHIFT: u64 = 28;
+const OP_SHIFT: u64 = 32;
+const SCHED_TYPE_SHIFT: u64 = 38;
+const QUEUE_ID_SHIFT: u64 = 40;
+const PRIORITY_SHIFT: u64 = 48;
+
+/// An event carrying a packet through an event device.
+///
+/// The event does not own the packet. A packet moved into an event must
+/// either be enqueued, in which case the device takes ownership, or be
+/// taken back with `into_mbuf`. Otherwise the packet is leaked.
+#[derive(Clone, Copy, Default)]
+#[repr(transparent)]
+pub struct RteEvent {
+    raw: ffi::rte_event,
+}
+
+impl RteEvent {
+    /// Creates a new event for the packet, with normal priority.
+    pub fn new(mbuf: Mbuf, queue_id: u8, sched_type: EventSchedType, flow_id: u32) -> Self {
+        let mut event = RteEvent::default();
+        event.set_word(
+            (flow_id as u64 & FLOW_ID_MASK)
+                | (ffi::RTE_EVENT_TYPE_CPU as u64) << EVENT_TYPE_SHIFT
+                | (ffi::RTE_EVENT_DEV_PRIORITY_NORMAL as u64) << PRIORITY_SHIFT,
+        );
+        event.set_queue_id(queue_id);
+        event.set_sched_type(sched_type);
+        event.raw.__bindgen_anon_2.mbuf = mbuf.into_ptr();
+        event
+    }
+
+    #[inline]
+    fn word(&self) -> u64 {
+        unsafe { self.raw.__bindgen_anon_1.event }
+    }
+
+    #[inline]
+    fn set_word(&mut self, word: u64) {
+        self.raw.__bindgen_anon_1.event = word;
+    }
+
+    #[inline]
+    fn set_field(&mut self, shift: u64, mask: u64, value: u64) {
+        let word = (self.word() & !(mask << shift)) | (value & mask) << shift;
+        self.set_word(word);
+    }
+
+    /// Returns the flow of the event. Only the lower 20 bits are used.
+    pub fn flow_id(&self) -> u32 {
+        (self.word() & FLOW_ID_MASK) as u32
+    }
+
+    /// Sets the flow of the event.
+    pub fn set_flow_id(&mut self, flow_id: u32) {
+        self.set_field(0, FLOW_ID_MASK, flow_id as u64);
+    }
+
+    /// Returns the operation.
+    pub fn op(&self) -> EventOp {
+        EventOp::from_raw(((self.word() >> OP_SHIFT) & 0x3) as u32)
+    }
+
+    /// Sets the operation. A dequeued event must be forwarded or
+    /// released.
+    pub fn set_op(&mut self, op: EventOp) {
+        self.set_field(OP_SHIFT, 0x3, op.raw() as u64);
+    }
+
+    /// Returns the scheduling type.
+    pub fn sched_type(&self) -> EventSchedType {
+        EventSchedType::from_raw(((self.word() >> SCHED_TYPE_SHIFT) & 0x3) as u32)
+    }
+
+    /// Sets the scheduling type.
+    pub fn set_sched_type(&mut self, sched_type: EventSchedType) {
+        self.set_field(SCHED_TYPE_SHIFT, 0x3, sched_type.raw() as u64);
+    }
+
+    /// Returns the queue the event is enqueued to.
+    pub fn queue_id(&self) -> u8 {
+        (self.word() >> QUEUE_ID_SHIFT) as u8
+    }
+
+    /// Sets the queue the event is enqueued to.
+    pub fn set_queue_id(&mut self, queue_id: u8) {
+        self.set_field(QUEUE_ID_SHIFT, 0xff, queue_id as u64);
+    }
+
+    /// Returns the priority. 0 is the highest and 255 is the lowest.
+    pub fn priority(&self) -> u8 {
+        (self.word() >> PRIORITY_SHIFT) as u8
+    }
+
+    /// Sets the priority.
+    pub fn set_priority(&mut self, priority: u8) {
+        self.set_field(PRIORITY_SHIFT, 0xff, priority as u64);
+    }
+
+    /// Takes the packet out of the event.
+    pub fn into_mbuf(self) -> Option<Mbuf> {
+        unsafe {
+            let ptr = self.raw.__bindgen_anon_2.mbuf;
+            if ptr.is_null() {
+                None
+            } else {
+                Some(Mbuf::from_ptr(ptr))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for RteEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RteEvent")
+            .field("queue_id", &self.queue_id())
+            .field("sched_type", &self.sched_type())
+            .field("priority", &self.priority())
+            .field("flow_id", &self.flow_id())
+            .field("op", &self.op())
+            .finish()
+    }
+}
+
+/// The configuration of an event device.
+///
+/// Limits left at 0 are set to the device maximum.
+#[derive(Clone, Debug, Default)]
+pub struct EventDevConfig {
+    nb_queues: u8,
+    nb_ports: u8,
+    nb_events_limit: u32,
+    nb_queue_flows: u32,
+    dequeue_depth: u32,
+    enqueue_depth: u32,
+    dequeue_timeout_ns: u32,
+}
+
+impl EventDevConfig {
+    /// Creates a new configuration with the number of event queues and
+    /// event ports.
+    pub fn new(nb_queues: u8, nb_ports: u8) -> Self {
+        EventDevConfig {
+            nb_queues,
+            nb_ports,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the maximum number of events in flight.
+    pub fn nb_events_limit(&mut self, limit: u32) -> &mut Self {
+        self.nb_events_limit = limit;
+        self
+    }
+
+    /// Sets the number of flows per queue.
+    pub fn nb_queue_flows(&mut self, flows: u32) -> &mut Self {
+        self.nb_queue_flows = flows;
+        self
+    }
+
+    /// Sets the maximum number of events dequeued in a burst.
+    pub fn dequeue_depth(&mut self, depth: u32) -> &mut Self {
+        self.dequeue_depth = depth;
+        self
+    }
+
+    /// Sets the maximum number of events enqueued in a burst.
+    pub fn enqueue_depth(&mut self, depth: u32) -> &mut Self {
+        self.enqueue_depth = depth;
+        self
+    }
+
+    /// Sets the global dequeue timeout. 0 uses the device default.
+    pub fn dequeue_timeout_ns(&mut self, timeout: u32) -> &mut Self {
+        self.dequeue_timeout_ns = timeout;
+        self
+    }
+}
+
+/// An event device that schedules packets from queues to ports.
+///
+/// By default, every port is linked to every queue. Use `link` for
+/// other topologies before starting the device.
+pub struct EventDevice {
+    dev_id: u8,
+    nb_queues: u8,
+    nb_ports: u8,
+    started: bool,
+}
+
+impl EventDevice {
+    /// Returns the number of event devices available.
+    pub fn count() -> usize {
+        unsafe { ffi::rte_event_dev_count() as usize }
+    }
+
+    /// Configures an event device, and sets up its queues and ports.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EventDevError::NotFound` if the device id is invalid, or
+    /// an error if the configuration exceeds the device limits. If the
+    /// configuration fails, `DpdkError` is returned.
+    pub fn configure(dev_id: u8, config: EventDevConfig) -> Result<Self> {
+        ensure!(
+            (dev_id as usize) < EventDevice::count(),
+            EventDevError::NotFound(dev_id)
+        );
+
+        let mut info = ffi::rte_event_dev_info::default();
+        unsafe {
+            ffi::rte_event_dev_info_get(dev_id, &mut info).into_result(DpdkError::from_errno)?;
+        }
+
+        ensure!(
+            config.nb_queues <= info.max_event_queues,
+            EventDevError::ExceedsLimit(
+                "queues",
+                config.nb_queues as u32,
+                info.max_event_queues as u32
+            )
+        );
+        ensure!(
+            config.nb_ports <= info.max_event_ports,
+            EventDevError::ExceedsLimit(
+                "ports",
+                config.nb_ports as u32,
+                info.max_event_ports as u32
+            )
+        );
+
+        let or_max = |value: u32, max: u32| if value == 0 { max } else { value };
+        let dev_conf = ffi::rte_event_dev_config {
+            dequeue_timeout_ns: config.dequeue_timeout_ns,
+            nb_events_limit: or_max(config.nb_events_limit, info.max_num_events as u32) as i32,
+            nb_event_queues: config.nb_queues,
+            nb_event_ports: config.nb_ports,
+            nb_event_queue_flows: or_max(config.nb_queue_flows, info.max_event_queue_flows),
+            nb_event_port_dequeue_depth: or_max(
+                config.dequeue_depth,
+                info.max_event_port_dequeue_depth as u32,
+            ),
+            nb_event_port_enqueue_depth: or_max(
+                config.enqueue_depth,
+                info.max_event_port_enqueue_depth,
+            ),
+            event_dev_cfg: 0,
+        };
+
+        unsafe {
+            ffi::rte_event_dev_configure(dev_id, &dev_conf).into_result(DpdkError::from_errno)?;
+
+            for queue_id in 0..config.nb_queues {
+                let mut conf = ffi::rte_event_queue_conf::default();
+                ffi::rte_event_queue_default_conf_get(dev_id, queue_id, &mut conf)
+                    .into_result(DpdkError::from_errno)?;
+                // lets each event choose its own scheduling type.
+                if info.event_dev_cap & ffi::RTE_EVENT_DEV_CAP_QUEUE_ALL_TYPES != 0 {
+                    conf.event_queue_cfg |= ffi::RTE_EVENT_QUEUE_CFG_ALL_TYPES;
+                }
+                ffi::rte_event_queue_setup(dev_id, queue_id, &conf)
+                    .into_result(DpdkError::from_errno)?;
+            }
+
+            for port_id in 0..config.nb_ports {
+                let mut conf = ffi::rte_event_port_conf::default();
+                ffi::rte_event_port_default_conf_get(dev_id, port_id, &mut conf)
+                    .into_result(DpdkError::from_errno)?;
+                ffi::rte_event_port_setup(dev_id, port_id, &conf)
+                    .into_result(DpdkError::from_errno)?;
+                // null links the port to all the queues.
+                ffi::rte_event_port_link(dev_id, port_id, ptr::null(), ptr::null(), 0)
+                    .into_result(DpdkError::from_errno)?;
+            }
+        }
+
+        info!(
+            dev_id,
+            nb_queues = config.nb_queues,
+            nb_ports = config.nb_ports,
+            "event device configured."
+        );
+
+        Ok(EventDevice {
+            dev_id,
+            nb_queues: config.nb_queues,
+            nb_ports: config.nb_ports,
+            started: false,
+        })
+    }
+
+    /// Returns the device id.
+    pub fn dev_id(&self) -> u8 {
+        self.dev_id
+    }
+
+    /// Returns the socket the device is on.
+    pub fn socket_id(&self) -> SocketId {
+        match unsafe { ffi::rte_event_dev_socket_id(self.dev_id) } {
+            id if id < 0 => SocketId::ANY,
+            id => SocketId(id),
+        }
+    }
+
+    /// Links a port to only the given queues, replacing the existing
+    /// links.
+    ///
+    /// # Errors
+    ///
+    /// If the link fails, `DpdkError` is returned.
+    pub fn link(&mut self, port_id: u8, queues: &[u8]) -> Result<()> {
+        unsafe {
+            ffi::rte_event_port_unlink(self.dev_id, port_id, ptr::null_mut(), 0)
+                .into_result(DpdkError::from_errno)?;
+            let linked = ffi::rte_event_port_link(
+                self.dev_id,
+                port_id,
+                queues.as_ptr(),
+                ptr::null(),
+                queues.len() as u16,
+            )
+            .into_result(DpdkError::from_errno)?;
+            ensure!(linked as usize == queues.len(), DpdkError::new());
+        }
+
+        Ok(())
+    }
+
+    /// Starts the device.
+    ///
+    /// # Errors
+    ///
+    /// If the device fails to start, `DpdkError` is returned.
+    pub fn start(&mut self) -> Result<()> {
+        unsafe {
+            ffi::rte_event_dev_start(self.dev_id).into_result(DpdkError::from_errno)?;
+        }
+
+        self.started = true;
+        info!(dev_id = self.dev_id, "event device started.");
+        Ok(())
+    }
+
+    /// Stops the device. Events still in the device are dropped by the
+    /// driver.
+    pub fn stop(&mut self) {
+        if self.started {
+            unsafe {
+                ffi::rte_event_dev_stop(self.dev_id);
+            }
+            self.started = false;
+            info!(dev_id = self.dev_id, "event device stopped.");
+        }
+    }
+
+    /// Enqueues a burst of events on a port. Returns the number of events
+    /// enqueued. The packets of the enqueued events are owned by the
+    /// device and the caller must not take them back.
+    pub fn enqueue_burst(&self, port_id: u8, events: &[RteEvent]) -> u16 {
+        unsafe {
+            ffi::_rte_event_enqueue_burst(
+                self.dev_id,
+                port_id,
+                events.as_ptr() as *const ffi::rte_event,
+                events.len() as u16,
+            )
+        }
+    }
+
+    /// Dequeues a burst of events from a port, waiting up to
+    /// `timeout_ticks` if none is available. Returns the number of
+    /// events dequeued into the front of `events`.
+    pub fn dequeue_burst(&self, port_id: u8, events: &mut [RteEvent], timeout_ticks: u64) -> u16 {
+        unsafe {
+            ffi::_rte_event_dequeue_burst(
+                self.dev_id,
+                port_id,
+                events.as_mut_ptr() as *mut ffi::rte_event,
+                events.len() as u16,
+                timeout_ticks,
+            )
+        }
+    }
+}
+
+impl fmt::Debug for EventDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventDevice")
+            .field("dev_id", &self.dev_id)
+            .field("nb_queues", &self.nb_queues)
+            .field("nb_ports", &self.nb_ports)
+            .field("started", &self.started)
+            .finish()
+    }
+}
+
+impl Drop for EventDevice {
+    fn drop(&mut self) {
+        self.stop();
+        debug!(dev_id = self.dev_id, "closing event device.");
+        unsafe {
+            ffi::rte_event_dev_close(self.dev_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn event_fields() {
+        let mbuf = Mbuf::new().unwrap();
+        let mut event = RteEvent::new(mbuf, 3, EventSchedType::Atomic, 0x12345);
+
+        assert_eq!(3, event.queue_id());
+        assert_eq!(EventSchedType::Atomic, event.sched_type());
+        assert_eq!(0x12345, event.flow_id());
+        assert_eq!(EventOp::New, event.op());
+        assert_eq!(ffi::RTE_EVENT_DEV_PRIORITY_NORMAL as u8, event.priority());
+
+        event.set_op(EventOp::Forward);
+        event.set_queue_id(7);
+        event.set_priority(0);
+        event.set_flow_id(0xfff_ffff);
+        assert_eq!(EventOp::Forward, event.op());
+        assert_eq!(7, event.queue_id());
+        assert_eq!(0, event.priority());
+        assert_eq!(0xf_ffff, event.flow_id());
+        assert_eq!(EventSchedType::Atomic, event.sched_type());
+
+        assert!(event.into_mbuf().is_some());
+        assert!(RteEvent::default().into_mbuf().is_none());
+    }
+
+    #[capsule::test]
+    fn configure_invalid_device() {
+        assert!(EventDevice::configure(u8::MAX, EventDevConfig::new(1, 1)).is_err());
+    }
+}
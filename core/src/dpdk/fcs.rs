@@ -0,0 +1,176 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::{DeviceInfo, RxOffloadFlags};
+use crate::ensure;
+use crate::ffi::{self, ToResult};
+use crate::packets::{Ethernet, Packet};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use thiserror::Error;
+
+/// The length of the Ethernet frame check sequence.
+const FCS_LEN: usize = 4;
+
+/// The reflected polynomial of the IEEE 802.3 CRC32.
+const CRC32_POLY: u32 = 0xedb8_8320;
+
+/// The lookup table for the byte-at-a-time CRC32.
+static CRC32_TABLE: Lazy<[u32; 256]> = Lazy::new(|| {
+    let mut table = [0u32; 256];
+    for (i, entry) in table.iter_mut().enumerate() {
+        let mut crc = i as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ CRC32_POLY
+            } else {
+                crc >> 1
+            };
+        }
+        *entry = crc;
+    }
+    table
+});
+
+/// Computes the IEEE 802.3 CRC32 of the data.
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Error indicating the frame check sequence cannot be stripped.
+#[derive(Debug, Error)]
+pub(crate) enum FcsError {
+    /// The frame is not long enough to hold the FCS.
+    #[error("Frame of {0} bytes is too short to have an FCS.")]
+    TooShort(usize),
+}
+
+/// Handles the Ethernet frame check sequence (FCS) of received frames.
+///
+/// By default, the PMDs strip the FCS before handing the frames to the
+/// application. With `PortConfig::keep_fcs`, ports that support it keep
+/// the 4 byte FCS at the end of the frames instead, so monitoring tools
+/// can detect corrupted frames. The FCS must be stripped before the frame
+/// is processed further or forwarded.
+///
+/// # Example
+///
+/// ```
+/// if EthernetFrameCheck::has_fcs(port_id) {
+///     if !EthernetFrameCheck::validate_fcs(&ethernet) {
+///         corrupted += 1;
+///     }
+///     EthernetFrameCheck::strip_fcs(&mut ethernet)?;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct EthernetFrameCheck;
+
+impl EthernetFrameCheck {
+    /// Returns whether the port can keep the FCS of the received frames.
+    /// Returns `false` if the port id is invalid.
+    pub fn has_fcs(port_id: u16) -> bool {
+        DeviceInfo::query(port_id)
+            .map(|info| info.rx_offload_capa().contains(RxOffloadFlags::KEEP_CRC))
+            .unwrap_or(false)
+    }
+
+    /// Removes the FCS from the end of the frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FcsError::TooShort` if the frame is not longer than the
+    /// Ethernet header and the FCS.
+    pub fn strip_fcs(pkt: &mut Ethernet) -> Result<()> {
+        let len = pkt.mbuf().data_len();
+        ensure!(len >= pkt.header_len() + FCS_LEN, FcsError::TooShort(len));
+
+        unsafe {
+            ffi::_rte_pktmbuf_trim(pkt.mbuf_mut().raw_mut(), FCS_LEN as u16)
+                .into_result(|_| FcsError::TooShort(len))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether the FCS at the end of the frame matches the CRC32
+    /// of the rest of the frame. Returns `false` if the frame is too short
+    /// to have an FCS.
+    pub fn validate_fcs(pkt: &Ethernet) -> bool {
+        let len = pkt.mbuf().data_len();
+        if len < pkt.header_len() + FCS_LEN {
+            return false;
+        }
+
+        match pkt.mbuf().read_data_slice::<u8>(0, len) {
+            Ok(data) => {
+                let data = unsafe { data.as_ref() };
+                let (frame, fcs) = data.split_at(len - FCS_LEN);
+                // the FCS is sent least significant byte first.
+                crc32(frame).to_le_bytes() == fcs
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+    use crate::Mbuf;
+
+    fn with_fcs(frame: &[u8]) -> Vec<u8> {
+        let mut bytes = frame.to_vec();
+        bytes.extend_from_slice(&crc32(frame).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    }
+
+    #[capsule::test]
+    fn validate_and_strip_fcs() {
+        let packet = Mbuf::from_bytes(&with_fcs(&IPV4_UDP_PACKET)).unwrap();
+        let mut ethernet = packet.parse::<Ethernet>().unwrap();
+        assert!(EthernetFrameCheck::validate_fcs(&ethernet));
+
+        EthernetFrameCheck::strip_fcs(&mut ethernet).unwrap();
+        assert_eq!(IPV4_UDP_PACKET.len(), ethernet.mbuf().data_len());
+        assert!(!EthernetFrameCheck::validate_fcs(&ethernet));
+    }
+
+    #[capsule::test]
+    fn detect_corrupted_frame() {
+        let mut bytes = with_fcs(&IPV4_UDP_PACKET);
+        bytes[20] ^= 0xff;
+        let packet = Mbuf::from_bytes(&bytes).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        assert!(!EthernetFrameCheck::validate_fcs(&ethernet));
+    }
+
+    #[capsule::test]
+    fn strip_fcs_too_short() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET[..16]).unwrap();
+        let mut ethernet = packet.parse::<Ethernet>().unwrap();
+        assert!(EthernetFrameCheck::strip_fcs(&mut ethernet).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{PortId, RssConfig};
+use crate::dpdk::DpdkError;
+use crate::error;
+use crate::ffi::{self, AsStr, ToResult};
+use crate::net::MacAddr;
+use anyhow::Result;
+use std::fmt;
+use std::mem;
+use std::net::Ipv4Addr;
+use std::os::raw;
+use std::ptr::{self, NonNull};
+use thiserror::Error;
+
+/// Error indicating the flow rule is rejected by the Ethernet device.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub(crate) struct FlowError(String);
+
+impl FlowError {
+    /// Returns the `FlowError` using the message set by the driver, or
+    /// the `errno` if the driver did not provide one.
+    fn new(errno: raw::c_int, error: &ffi::rte_flow_error) -> Self {
+        if error.message.is_null() {
+            FlowError(DpdkError::from_errno(errno).to_string())
+        } else {
+            FlowError(error.message.as_str().into())
+        }
+    }
+}
+
+/// An action applied to the packets matching a flow rule.
+enum FlowAction {
+    Queue(ffi::rte_flow_action_queue),
+    Drop,
+    Rss {
+        conf: ffi::rte_flow_action_rss,
+        // owns the memory `conf` points to.
+        _key: Vec<u8>,
+        _queues: Vec<u16>,
+    },
+}
+
+/// A hardware-offloaded packet steering rule.
+///
+/// The flow rule matches ingress packets by their headers and applies the
+/// actions to the matching packets in the Ethernet device, before they
+/// reach the receive queues. Not all the devices support flow rules, and
+/// the supported patterns and actions vary by device.
+///
+/// # Example
+///
+/// ```
+/// let rule = FlowRule::new()
+///     .match_ipv4_dst(Ipv4Addr::new(10, 0, 0, 1))
+///     .match_tcp_dst_port(80)
+///     .action_queue(1);
+/// let installed = q.install_flow_rule(&rule)?;
+/// ```
+pub struct FlowRule {
+    priority: u32,
+    eth: Option<(ffi::rte_flow_item_eth, ffi::rte_flow_item_eth)>,
+    ipv4: Option<(ffi::rte_flow_item_ipv4, ffi::rte_flow_item_ipv4)>,
+    tcp: Option<(ffi::rte_flow_item_tcp, ffi::rte_flow_item_tcp)>,
+    actions: Vec<FlowAction>,
+}
+
+impl FlowRule {
+    /// Creates a new flow rule that matches all ingress packets and has
+    /// no actions.
+    pub fn new() -> Self {
+        FlowRule {
+            priority: 0,
+            eth: None,
+            ipv4: None,
+            tcp: None,
+            actions: vec![],
+        }
+    }
+
+    /// Sets the priority of the rule. Lower value means higher priority.
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Matches packets with the source MAC address.
+    pub fn match_eth_src(mut self, mac: MacAddr) -> Self {
+        let (mut spec, mut mask) = self.eth.unwrap_or_default();
+        spec.src.addr_bytes = mac.octets();
+        mask.src.addr_bytes = [0xff; 6];
+        self.eth = Some((spec, mask));
+        self
+    }
+
+    /// Matches IPv4 packets with the destination address.
+    pub fn match_ipv4_dst(mut self, ip: Ipv4Addr) -> Self {
+        let (mut spec, mut mask) = self.ipv4.unwrap_or_default();
+        spec.hdr.dst_addr = u32::from(ip).to_be();
+        mask.hdr.dst_addr = u32::max_value();
+        self.ipv4 = Some((spec, mask));
+        self
+    }
+
+    /// Matches TCP packets with the destination port.
+    pub fn match_tcp_dst_port(mut self, port: u16) -> Self {
+        let (mut spec, mut mask) = self.tcp.unwrap_or_default();
+        spec.hdr.dst_port = port.to_be();
+        mask.hdr.dst_port = u16::max_value();
+        self.tcp = Some((spec, mask));
+        self
+    }
+
+    /// Steers the matching packets to the receive queue.
+    pub fn action_queue(mut self, queue_id: u16) -> Self {
+        self.actions
+            .push(FlowAction::Queue(ffi::rte_flow_action_queue {
+                index: queue_id,
+            }));
+        self
+    }
+
+    /// Drops the matching packets.
+    pub fn action_drop(mut self) -> Self {
+        self.actions.push(FlowAction::Drop);
+        self
+    }
+
+    /// Spreads the matching packets across the receive queues using the
+    /// receive side scaling configuration.
+    pub fn action_rss(mut self, rss_conf: &RssConfig, queues: &[u16]) -> Self {
+        let key = rss_conf.key().map(|k| k.to_vec()).unwrap_or_default();
+        let queues = queues.to_vec();
+        let conf = ffi::rte_flow_action_rss {
+            func: ffi::rte_eth_hash_function::RTE_ETH_HASH_FUNCTION_DEFAULT,
+            level: 0,
+            types: rss_conf.hash_functions().bits(),
+            key_len: key.len() as u32,
+            queue_num: queues.len() as u32,
+            key: if key.is_empty() {
+                ptr::null()
+            } else {
+                key.as_ptr()
+            },
+            queue: queues.as_ptr(),
+        };
+
+        self.actions.push(FlowAction::Rss {
+            conf,
+            _key: key,
+            _queues: queues,
+        });
+        self
+    }
+
+    /// Invokes `f` with the raw attributes, pattern and actions of the rule.
+    ///
+    /// The pattern always starts with the Ethernet item. The IPv4 item is
+    /// added without a spec if only the TCP port is matched.
+    fn with_raw<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&ffi::rte_flow_attr, &[ffi::rte_flow_item], &[ffi::rte_flow_action]) -> T,
+    {
+        fn item<S>(
+            type_: ffi::rte_flow_item_type::Type,
+            spec_mask: &Option<(S, S)>,
+        ) -> ffi::rte_flow_item {
+            let (spec, mask) = spec_mask
+                .as_ref()
+                .map(|(spec, mask)| {
+                    (
+                        spec as *const S as *const raw::c_void,
+                        mask as *const S as *const raw::c_void,
+                    )
+                })
+                .unwrap_or((ptr::null(), ptr::null()));
+
+            ffi::rte_flow_item {
+                type_,
+                spec,
+                last: ptr::null(),
+                mask,
+            }
+        }
+
+        let mut attr = ffi::rte_flow_attr {
+            priority: self.priority,
+            ..Default::default()
+        };
+        attr.set_ingress(1);
+
+        let mut pattern = vec![item(
+            ffi::rte_flow_item_type::RTE_FLOW_ITEM_TYPE_ETH,
+            &self.eth,
+        )];
+        if self.ipv4.is_some() || self.tcp.is_some() {
+            pattern.push(item(
+                ffi::rte_flow_item_type::RTE_FLOW_ITEM_TYPE_IPV4,
+                &self.ipv4,
+            ));
+        }
+        if self.tcp.is_some() {
+            pattern.push(item(
+                ffi::rte_flow_item_type::RTE_FLOW_ITEM_TYPE_TCP,
+                &self.tcp,
+            ));
+        }
+        pattern.push(ffi::rte_flow_item {
+            type_: ffi::rte_flow_item_type::RTE_FLOW_ITEM_TYPE_END,
+            ..Default::default()
+        });
+
+        let mut actions = self
+            .actions
+            .iter()
+            .map(|action| match action {
+                FlowAction::Queue(conf) => ffi::rte_flow_action {
+                    type_: ffi::rte_flow_action_type::RTE_FLOW_ACTION_TYPE_QUEUE,
+                    conf: conf as *const _ as *const raw::c_void,
+                },
+                FlowAction::Drop => ffi::rte_flow_action {
+                    type_: ffi::rte_flow_action_type::RTE_FLOW_ACTION_TYPE_DROP,
+                    conf: ptr::null(),
+                },
+                FlowAction::Rss { conf, .. } => ffi::rte_flow_action {
+                    type_: ffi::rte_flow_action_type::RTE_FLOW_ACTION_TYPE_RSS,
+                    conf: conf as *const _ as *const raw::c_void,
+                },
+            })
+            .collect::<Vec<_>>();
+        actions.push(ffi::rte_flow_action {
+            type_: ffi::rte_flow_action_type::RTE_FLOW_ACTION_TYPE_END,
+            ..Default::default()
+        });
+
+        f(&attr, &pattern, &actions)
+    }
+
+    /// Checks whether the rule is valid and can be installed on the port.
+    pub(crate) fn validate(&self, port_id: PortId) -> Result<()> {
+        self.with_raw(|attr, pattern, actions| {
+            let mut error = ffi::rte_flow_error::default();
+            unsafe {
+                ffi::rte_flow_validate(
+                    port_id.raw(),
+                    attr,
+                    pattern.as_ptr(),
+                    actions.as_ptr(),
+                    &mut error,
+                )
+                .into_result(|errno| FlowError::new(errno, &error))
+                .map(|_| ())
+            }
+        })
+    }
+
+    /// Installs the rule on the port.
+    pub(crate) fn install(&self, port_id: PortId) -> Result<InstalledFlowRule> {
+        self.with_raw(|attr, pattern, actions| {
+            let mut error = ffi::rte_flow_error::default();
+            let raw = unsafe {
+                ffi::rte_flow_create(
+                    port_id.raw(),
+                    attr,
+                    pattern.as_ptr(),
+                    actions.as_ptr(),
+                    &mut error,
+                )
+                .into_result(|_| FlowError::new(-1, &error))?
+            };
+
+            Ok(InstalledFlowRule { port_id, raw })
+        })
+    }
+}
+
+impl Default for FlowRule {
+    fn default() -> Self {
+        FlowRule::new()
+    }
+}
+
+impl fmt::Debug for FlowRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("flow_rule");
+        d.field("priority", &self.priority);
+        if let Some((spec, _)) = &self.eth {
+            d.field("eth_src", &MacAddr::from(spec.src.addr_bytes));
+        }
+        if let Some((spec, _)) = &self.ipv4 {
+            d.field("ipv4_dst", &Ipv4Addr::from(u32::from_be(spec.hdr.dst_addr)));
+        }
+        if let Some((spec, _)) = &self.tcp {
+            d.field("tcp_dst_port", &u16::from_be(spec.hdr.dst_port));
+        }
+        d.field("actions", &self.actions.len()).finish()
+    }
+}
+
+/// A flow rule installed on an Ethernet device.
+///
+/// The rule is removed from the device when dropped.
+pub struct InstalledFlowRule {
+    port_id: PortId,
+    raw: NonNull<ffi::rte_flow>,
+}
+
+impl InstalledFlowRule {
+    fn destroy(&mut self) -> Result<()> {
+        let mut error = ffi::rte_flow_error::default();
+        unsafe {
+            ffi::rte_flow_destroy(self.port_id.raw(), self.raw.as_ptr(), &mut error)
+                .into_result(|errno| FlowError::new(errno, &error))
+                .map(|_| ())
+        }
+    }
+
+    /// Removes the rule from the Ethernet device.
+    pub fn remove(mut self) -> Result<()> {
+        let res = self.destroy();
+        mem::forget(self);
+        res
+    }
+}
+
+impl fmt::Debug for InstalledFlowRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("installed_flow_rule")
+            .field("port", &self.port_id.raw())
+            .field("raw", &self.raw)
+            .finish()
+    }
+}
+
+impl Drop for InstalledFlowRule {
+    fn drop(&mut self) {
+        if let Err(err) = self.destroy() {
+            error!(message = "failed to destroy flow rule.", ?err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flow_rule_pattern_and_actions() {
+        let rule = FlowRule::new().match_tcp_dst_port(80).action_drop();
+
+        rule.with_raw(|attr, pattern, actions| {
+            assert_eq!(1, attr.ingress());
+            assert_eq!(4, pattern.len());
+            assert!(pattern[1].spec.is_null());
+            assert!(!pattern[2].spec.is_null());
+            assert_eq!(
+                ffi::rte_flow_item_type::RTE_FLOW_ITEM_TYPE_END,
+                pattern[3].type_
+            );
+            assert_eq!(2, actions.len());
+            assert_eq!(
+                ffi::rte_flow_action_type::RTE_FLOW_ACTION_TYPE_DROP,
+                actions[0].type_
+            );
+        });
+    }
+}
@@ -0,0 +1,281 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::DpdkError;
+use crate::debug;
+use crate::ffi::{self, ToResult};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use thiserror::Error;
+
+/// The number of 802.1p priorities.
+const NUM_PRIORITIES: u8 = 8;
+
+/// The priority flow control settings applied to each port, since DPDK
+/// has no way to read them back.
+static PFC_CONFIGS: Lazy<Mutex<HashMap<u16, PfcConfig>>> = Lazy::new(Default::default);
+
+/// Flow control errors.
+#[derive(Debug, Error)]
+pub(crate) enum FlowControlError {
+    /// Priority flow control was never applied to the port.
+    #[error("Priority flow control is not configured on port {0}.")]
+    PfcNotConfigured(u16),
+}
+
+/// The direction of 802.3x pause frames a port handles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlowControlMode {
+    /// Flow control is disabled.
+    None_,
+    /// The port stops transmitting when it receives pause frames.
+    Rx,
+    /// The port sends pause frames when its receive buffer fills up.
+    Tx,
+    /// The port both sends and honors pause frames.
+    Full,
+}
+
+impl FlowControlMode {
+    fn from_pause(rx: bool, tx: bool) -> Self {
+        match (rx, tx) {
+            (false, false) => FlowControlMode::None_,
+            (true, false) => FlowControlMode::Rx,
+            (false, true) => FlowControlMode::Tx,
+            (true, true) => FlowControlMode::Full,
+        }
+    }
+}
+
+impl From<ffi::rte_eth_fc_mode::Type> for FlowControlMode {
+    fn from(mode: ffi::rte_eth_fc_mode::Type) -> Self {
+        match mode {
+            ffi::rte_eth_fc_mode::RTE_FC_RX_PAUSE => FlowControlMode::Rx,
+            ffi::rte_eth_fc_mode::RTE_FC_TX_PAUSE => FlowControlMode::Tx,
+            ffi::rte_eth_fc_mode::RTE_FC_FULL => FlowControlMode::Full,
+            _ => FlowControlMode::None_,
+        }
+    }
+}
+
+impl From<FlowControlMode> for ffi::rte_eth_fc_mode::Type {
+    fn from(mode: FlowControlMode) -> Self {
+        match mode {
+            FlowControlMode::None_ => ffi::rte_eth_fc_mode::RTE_FC_NONE,
+            FlowControlMode::Rx => ffi::rte_eth_fc_mode::RTE_FC_RX_PAUSE,
+            FlowControlMode::Tx => ffi::rte_eth_fc_mode::RTE_FC_TX_PAUSE,
+            FlowControlMode::Full => ffi::rte_eth_fc_mode::RTE_FC_FULL,
+        }
+    }
+}
+
+/// The link level flow control settings of a port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlowControlConfig {
+    /// The pause frames the port handles.
+    pub mode: FlowControlMode,
+    /// The pause quanta sent in pause frames.
+    pub pause_time: u16,
+    /// The receive buffer level that triggers a pause frame.
+    pub high_water: u32,
+    /// The receive buffer level that triggers a resume frame.
+    pub low_water: u32,
+    /// Whether a resume frame is sent when the buffer drains below the
+    /// low water mark.
+    pub send_xon: bool,
+    /// Whether MAC control frames are forwarded to the host.
+    pub mac_ctrl_frame_fwd: bool,
+    /// Whether flow control is negotiated with the link partner.
+    pub autoneg: bool,
+}
+
+impl From<ffi::rte_eth_fc_conf> for FlowControlConfig {
+    fn from(raw: ffi::rte_eth_fc_conf) -> Self {
+        FlowControlConfig {
+            mode: raw.mode.into(),
+            pause_time: raw.pause_time,
+            high_water: raw.high_water,
+            low_water: raw.low_water,
+            send_xon: raw.send_xon != 0,
+            mac_ctrl_frame_fwd: raw.mac_ctrl_frame_fwd != 0,
+            autoneg: raw.autoneg != 0,
+        }
+    }
+}
+
+/// Link level flow control, as defined in IEEE 802.3x.
+#[derive(Debug)]
+pub struct FlowControl;
+
+impl FlowControl {
+    /// Sets the flow control mode and the pause time of a port. The other
+    /// settings are kept as they are.
+    ///
+    /// # Errors
+    ///
+    /// If the port id is invalid or the device does not support flow
+    /// control, `DpdkError` is returned.
+    pub fn set(port_id: u16, mode: FlowControlMode, pause_time: u16) -> Result<()> {
+        let mut raw = fc_conf_get(port_id)?;
+        raw.mode = mode.into();
+        raw.pause_time = pause_time;
+
+        unsafe {
+            ffi::rte_eth_dev_flow_ctrl_set(port_id, &mut raw).into_result(DpdkError::from_errno)?;
+        }
+
+        debug!(port_id, ?mode, pause_time, "set flow control.");
+        Ok(())
+    }
+
+    /// Retrieves the flow control settings of a port.
+    ///
+    /// # Errors
+    ///
+    /// If the port id is invalid or the device does not support flow
+    /// control, `DpdkError` is returned.
+    pub fn get(port_id: u16) -> Result<FlowControlConfig> {
+        fc_conf_get(port_id).map(Into::into)
+    }
+}
+
+/// Retrieves the raw flow control settings of a port.
+fn fc_conf_get(port_id: u16) -> Result<ffi::rte_eth_fc_conf> {
+    let mut raw = ffi::rte_eth_fc_conf {
+        high_water: 0,
+        low_water: 0,
+        pause_time: 0,
+        send_xon: 0,
+        mode: ffi::rte_eth_fc_mode::RTE_FC_NONE,
+        mac_ctrl_frame_fwd: 0,
+        autoneg: 0,
+    };
+
+    unsafe {
+        ffi::rte_eth_dev_flow_ctrl_get(port_id, &mut raw).into_result(DpdkError::from_errno)?;
+    }
+
+    Ok(raw)
+}
+
+/// Priority flow control, as defined in IEEE 802.1Qbb.
+///
+/// Pause frames are sent and honored per 802.1p priority, so lossless
+/// traffic, such as RoCE, can be paused without stopping the other
+/// priorities on the link.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PfcConfig {
+    /// The priorities flow control is enabled for, one bit per priority.
+    pub priority_mask: u8,
+    /// The pause time honored for received pause frames. The device
+    /// pauses for the quanta in the received frame; a non-zero value
+    /// enables honoring them.
+    pub rx_pause_time: u16,
+    /// The pause quanta sent in pause frames. A non-zero value enables
+    /// sending them.
+    pub tx_pause_time: u16,
+}
+
+impl PfcConfig {
+    /// Applies the settings to all the priorities of a port. The
+    /// priorities not in the mask have flow control disabled. The buffer
+    /// water marks of the link level flow control are reused.
+    ///
+    /// # Errors
+    ///
+    /// If the port id is invalid or the device does not support priority
+    /// flow control, `DpdkError` is returned.
+    pub fn apply(&self, port_id: u16) -> Result<()> {
+        let mode = FlowControlMode::from_pause(self.rx_pause_time > 0, self.tx_pause_time > 0);
+        let mut fc = fc_conf_get(port_id)?;
+        fc.pause_time = self.tx_pause_time;
+
+        for priority in 0..NUM_PRIORITIES {
+            fc.mode = if self.priority_mask & (1 << priority) != 0 {
+                mode.into()
+            } else {
+                ffi::rte_eth_fc_mode::RTE_FC_NONE
+            };
+
+            let mut raw = ffi::rte_eth_pfc_conf { fc, priority };
+            unsafe {
+                ffi::rte_eth_dev_priority_flow_ctrl_set(port_id, &mut raw)
+                    .into_result(DpdkError::from_errno)?;
+            }
+        }
+
+        PFC_CONFIGS.lock().unwrap().insert(port_id, *self);
+        debug!(port_id, config = ?self, "applied priority flow control.");
+        Ok(())
+    }
+
+    /// Returns the settings last applied to a port.
+    ///
+    /// DPDK cannot read priority flow control settings from the device,
+    /// so only the ones applied with `apply` are known.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FlowControlError::PfcNotConfigured` if no settings were
+    /// applied to the port.
+    pub fn get(port_id: u16) -> Result<PfcConfig> {
+        PFC_CONFIGS
+            .lock()
+            .unwrap()
+            .get(&port_id)
+            .copied()
+            .ok_or_else(|| FlowControlError::PfcNotConfigured(port_id).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_from_pause() {
+        assert_eq!(
+            FlowControlMode::None_,
+            FlowControlMode::from_pause(false, false)
+        );
+        assert_eq!(
+            FlowControlMode::Rx,
+            FlowControlMode::from_pause(true, false)
+        );
+        assert_eq!(
+            FlowControlMode::Tx,
+            FlowControlMode::from_pause(false, true)
+        );
+        assert_eq!(
+            FlowControlMode::Full,
+            FlowControlMode::from_pause(true, true)
+        );
+
+        let raw: ffi::rte_eth_fc_mode::Type = FlowControlMode::Full.into();
+        assert_eq!(FlowControlMode::Full, raw.into());
+    }
+
+    #[capsule::test]
+    fn invalid_port() {
+        assert!(FlowControl::get(u16::MAX).is_err());
+        assert!(FlowControl::set(u16::MAX, FlowControlMode::Full, 0xffff).is_err());
+        assert!(PfcConfig::default().apply(u16::MAX).is_err());
+        assert!(PfcConfig::get(u16::MAX).is_err());
+    }
+}
@@ -0,0 +1,461 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{mbuf_free_bulk, tsc_cycles, CoreId, LcoreHandle, LcoreManager, PacketAllocator};
+use crate::ffi;
+use crate::{ensure, error, info};
+use anyhow::Result;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// The maximum number of packets of a stream sent in one burst when the
+/// generator falls behind the schedule.
+const MAX_BURST: u64 = 32;
+
+/// Error indicating failed traffic generator operations.
+#[derive(Debug, Error)]
+pub(crate) enum GeneratorError {
+    /// The generator is already started.
+    #[error("Traffic generator is already started.")]
+    AlreadyStarted,
+
+    /// The rate of the stream is not a positive number.
+    #[error("Invalid rate {1} pps for stream {0:?}.")]
+    BadRate(StreamId, f64),
+}
+
+/// Mutates the packet template of a stream before each packet is sent.
+///
+/// The engine is given a fresh copy of the template each time, so it must
+/// keep its own state to vary the fields across packets.
+pub trait FieldEngine: Send {
+    /// Mutates the packet data.
+    fn mutate(&mut self, pkt: &mut [u8]);
+}
+
+/// A field engine that increments a big-endian field of the template.
+///
+/// The field cycles through `count` values, starting from the value in
+/// the template. Checksums are not updated, so either offload them or set
+/// them to 0 if the protocol allows it.
+///
+/// # Example
+///
+/// ```
+/// // cycles the IPv4 source address through 256 hosts.
+/// let engine = FieldIncrement::new(26, 4, 256);
+/// ```
+#[derive(Clone, Debug)]
+pub struct FieldIncrement {
+    offset: usize,
+    width: usize,
+    count: u32,
+    next: u32,
+}
+
+impl FieldIncrement {
+    /// Creates a new engine for the field at `offset` that is `width`
+    /// bytes long, cycling through `count` values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is not 1, 2 or 4, or if `count` is 0.
+    pub fn new(offset: usize, width: usize, count: u32) -> Self {
+        assert!(
+            width == 1 || width == 2 || width == 4,
+            "field width must be 1, 2 or 4 bytes."
+        );
+        assert!(count > 0, "field must have at least one value.");
+
+        FieldIncrement {
+            offset,
+            width,
+            count,
+            next: 0,
+        }
+    }
+}
+
+impl FieldEngine for FieldIncrement {
+    fn mutate(&mut self, pkt: &mut [u8]) {
+        let field = match pkt.get_mut(self.offset..self.offset + self.width) {
+            Some(field) => field,
+            None => return,
+        };
+
+        let mut bytes = [0; 4];
+        bytes[4 - self.width..].copy_from_slice(field);
+        let value = u32::from_be_bytes(bytes).wrapping_add(self.next);
+        field.copy_from_slice(&value.to_be_bytes()[4 - self.width..]);
+
+        self.next = (self.next + 1) % self.count;
+    }
+}
+
+/// A stream of packets sent at a constant rate to a transmit queue.
+pub struct TrafficStream {
+    /// The bytes of the packets, starting from the Ethernet header.
+    pub packet_template: Vec<u8>,
+    /// The rate to send the packets at, in packets per second.
+    pub rate_pps: f64,
+    /// The port to send the packets to.
+    pub port_id: u16,
+    /// The transmit queue of the port.
+    pub queue_id: u16,
+    /// The optional engine that mutates each packet.
+    pub field_engine: Option<Box<dyn FieldEngine>>,
+}
+
+impl fmt::Debug for TrafficStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrafficStream")
+            .field("len", &self.packet_template.len())
+            .field("rate_pps", &self.rate_pps)
+            .field("port_id", &self.port_id)
+            .field("queue_id", &self.queue_id)
+            .field("field_engine", &self.field_engine.is_some())
+            .finish()
+    }
+}
+
+/// The identifier of a stream added to a `TrafficGenerator`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StreamId(usize);
+
+/// The statistics of a stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GeneratorStats {
+    /// The number of packets sent.
+    pub sent_packets: u64,
+    /// The number of bytes sent.
+    pub sent_bytes: u64,
+    /// The number of packets dropped, because either the allocation
+    /// failed or the transmit queue was full.
+    pub drop_count: u64,
+}
+
+/// The counters of a stream, shared with the generator lcore.
+#[derive(Default)]
+struct StreamCounters {
+    sent_packets: AtomicU64,
+    sent_bytes: AtomicU64,
+    drop_count: AtomicU64,
+}
+
+/// The state of a stream on the generator lcore.
+struct StreamState {
+    stream: TrafficStream,
+    counters: Arc<StreamCounters>,
+    interval: f64,
+    next_send: f64,
+    scratch: Vec<u8>,
+}
+
+impl StreamState {
+    /// Sends the packets that are due by `now`.
+    fn send_due(&mut self, allocator: &PacketAllocator, now: u64) {
+        let now = now as f64;
+        if now < self.next_send {
+            return;
+        }
+
+        let due = (((now - self.next_send) / self.interval) as u64 + 1).min(MAX_BURST);
+        self.next_send += due as f64 * self.interval;
+        // the schedule is not caught up beyond one burst, so a stalled
+        // generator does not send a flood of packets afterwards.
+        if self.next_send < now {
+            self.next_send = now;
+        }
+
+        let mut ptrs = Vec::with_capacity(due as usize);
+        let mut dropped = 0;
+
+        for _ in 0..due {
+            self.scratch.copy_from_slice(&self.stream.packet_template);
+            if let Some(engine) = self.stream.field_engine.as_mut() {
+                engine.mutate(&mut self.scratch);
+            }
+
+            match allocator.alloc_with_data(&self.scratch) {
+                Ok(mbuf) => ptrs.push(mbuf.into_ptr()),
+                Err(_) => dropped += 1,
+            }
+        }
+
+        let sent = unsafe {
+            ffi::_rte_eth_tx_burst(
+                self.stream.port_id,
+                self.stream.queue_id,
+                ptrs.as_mut_ptr(),
+                ptrs.len() as u16,
+            )
+        } as usize;
+
+        if sent < ptrs.len() {
+            dropped += ptrs.len() - sent;
+            mbuf_free_bulk(ptrs.split_off(sent));
+        }
+
+        let len = self.scratch.len() as u64;
+        self.counters
+            .sent_packets
+            .fetch_add(sent as u64, Ordering::Relaxed);
+        self.counters
+            .sent_bytes
+            .fetch_add(sent as u64 * len, Ordering::Relaxed);
+        self.counters
+            .drop_count
+            .fetch_add(dropped as u64, Ordering::Relaxed);
+    }
+}
+
+/// A generator of synthetic traffic at precise rates.
+///
+/// The packets are sent from a dedicated EAL worker lcore, paced with the
+/// TSC. The lcore busy polls until the generator is stopped, so it should
+/// not be shared with anything else.
+///
+/// # Example
+///
+/// ```
+/// let mut generator = unsafe { TrafficGenerator::new(core_id, "mempool0") };
+/// let id = generator.add_stream(TrafficStream {
+///     packet_template: template,
+///     rate_pps: 1_000_000.0,
+///     port_id: 0,
+///     queue_id: 0,
+///     field_engine: Some(Box::new(FieldIncrement::new(26, 4, 256))),
+/// });
+///
+/// generator.start(Some(10))?;
+/// generator.join()?;
+/// println!("{:?}", generator.stats(id));
+/// ```
+pub struct TrafficGenerator {
+    core_id: CoreId,
+    mempool: String,
+    streams: Vec<TrafficStream>,
+    counters: Vec<Arc<StreamCounters>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<LcoreHandle>,
+}
+
+impl TrafficGenerator {
+    /// Creates a new generator that runs on the worker lcore and allocates
+    /// the packets from the named mempool.
+    ///
+    /// # Safety
+    ///
+    /// The mempool is looked up by name and not kept alive by the
+    /// generator. The caller must ensure it is not freed while the
+    /// generator is running.
+    pub unsafe fn new(core_id: CoreId, mempool: &str) -> Self {
+        TrafficGenerator {
+            core_id,
+            mempool: mempool.to_owned(),
+            streams: vec![],
+            counters: vec![],
+            stop: Arc::new(AtomicBool::new(false)),
+            handle: None,
+        }
+    }
+
+    /// Adds a stream to the generator. The stream is sent from the next
+    /// `start`, and the statistics are kept across restarts.
+    pub fn add_stream(&mut self, stream: TrafficStream) -> StreamId {
+        self.streams.push(stream);
+        self.counters.push(Default::default());
+        StreamId(self.counters.len() - 1)
+    }
+
+    /// Returns whether the generator is running.
+    pub fn is_running(&self) -> bool {
+        self.handle.is_some()
+    }
+
+    /// Starts sending the streams added since the last start on the lcore.
+    /// If `duration_secs` is set, the generator stops on its own after that many seconds.
+    /// Otherwise it runs until `stop` is called.
+    ///
+    /// # Errors
+    ///
+    /// Returns `GeneratorError::AlreadyStarted` if the generator is running,
+    /// `GeneratorError::BadRate` if a stream has no positive rate, or
+    /// `DpdkError` if the lcore cannot be launched.
+    pub fn start(&mut self, duration_secs: Option<u64>) -> Result<()> {
+        ensure!(self.handle.is_none(), GeneratorError::AlreadyStarted);
+
+        for (idx, stream) in self.streams.iter().enumerate() {
+            ensure!(
+                stream.rate_pps.is_finite() && stream.rate_pps > 0.0,
+                GeneratorError::BadRate(StreamId(idx), stream.rate_pps)
+            );
+        }
+
+        let hz = unsafe { ffi::rte_get_tsc_hz() };
+        let deadline = duration_secs.map(|secs| tsc_cycles() + secs * hz);
+        let start = tsc_cycles() as f64;
+
+        let mut states = self
+            .streams
+            .drain(..)
+            .zip(self.counters.iter())
+            .map(|(stream, counters)| StreamState {
+                interval: hz as f64 / stream.rate_pps,
+                next_send: start,
+                scratch: stream.packet_template.clone(),
+                counters: counters.clone(),
+                stream,
+            })
+            .collect::<Vec<_>>();
+
+        let mempool = self.mempool.clone();
+        let stop = self.stop.clone();
+        stop.store(false, Ordering::Release);
+
+        let handle = LcoreManager::launch(self.core_id, move || {
+            // the caller of `new` guarantees the mempool outlives the run.
+            let allocator = match unsafe { PacketAllocator::from_mempool_name(&mempool, 0) } {
+                Ok(allocator) => allocator,
+                Err(err) => {
+                    error!(message = "failed to find mempool.", %mempool, ?err);
+                    return;
+                }
+            };
+
+            while !stop.load(Ordering::Acquire) {
+                let now = tsc_cycles();
+                if deadline.map_or(false, |deadline| now >= deadline) {
+                    break;
+                }
+
+                for state in states.iter_mut() {
+                    state.send_due(&allocator, now);
+                }
+            }
+        })?;
+
+        info!(core = ?self.core_id, "started traffic generator.");
+        self.handle = Some(handle);
+        Ok(())
+    }
+
+    /// Signals the generator to stop and waits for the lcore to finish.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LcoreError::Panicked` if the generator panicked.
+    pub fn stop(&mut self) -> Result<()> {
+        self.stop.store(true, Ordering::Release);
+        self.join()
+    }
+
+    /// Waits for the generator to finish, either because the duration
+    /// elapsed or because it's stopped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LcoreError::Panicked` if the generator panicked.
+    pub fn join(&mut self) -> Result<()> {
+        match self.handle.take() {
+            Some(handle) => handle.join(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the statistics of the stream.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stream is not added to this generator.
+    pub fn stats(&self, stream_id: StreamId) -> GeneratorStats {
+        let counters = &self.counters[stream_id.0];
+        GeneratorStats {
+            sent_packets: counters.sent_packets.load(Ordering::Relaxed),
+            sent_bytes: counters.sent_bytes.load(Ordering::Relaxed),
+            drop_count: counters.drop_count.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl fmt::Debug for TrafficGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrafficGenerator")
+            .field("core_id", &self.core_id)
+            .field("mempool", &self.mempool)
+            .field("streams", &self.counters.len())
+            .field("running", &self.is_running())
+            .finish()
+    }
+}
+
+impl Drop for TrafficGenerator {
+    fn drop(&mut self) {
+        if let Err(err) = self.stop() {
+            error!(message = "traffic generator failed.", ?err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn increment_field() {
+        let mut engine = FieldIncrement::new(1, 2, 3);
+        let template = [0xaa, 0x00, 0xfe, 0xbb];
+
+        let values = (0..4)
+            .map(|_| {
+                let mut pkt = template;
+                engine.mutate(&mut pkt);
+                assert_eq!(0xaa, pkt[0]);
+                assert_eq!(0xbb, pkt[3]);
+                u16::from_be_bytes([pkt[1], pkt[2]])
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![0xfe, 0xff, 0x100, 0xfe], values);
+    }
+
+    #[test]
+    fn increment_field_out_of_bounds() {
+        let mut engine = FieldIncrement::new(3, 4, 10);
+        let mut pkt = [1, 2, 3, 4];
+        engine.mutate(&mut pkt);
+        assert_eq!([1, 2, 3, 4], pkt);
+    }
+
+    #[test]
+    fn stats_of_new_stream() {
+        let mut generator = unsafe { TrafficGenerator::new(CoreId::new(1), "mempool0") };
+        let id = generator.add_stream(TrafficStream {
+            packet_template: vec![0; 64],
+            rate_pps: 1000.0,
+            port_id: 0,
+            queue_id: 0,
+            field_engine: None,
+        });
+
+        assert!(!generator.is_running());
+        assert_eq!(GeneratorStats::default(), generator.stats(id));
+    }
+}
@@ -0,0 +1,301 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::{DpdkError, Mbuf, PacketType, SocketId};
+use crate::ffi::{self, ToResult};
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::ProtocolNumbers;
+use crate::packets::{EtherTypes, Ethernet, Packet, Tcp4, Udp4};
+use crate::{debug, ensure};
+use anyhow::{anyhow, Result};
+use bitflags::bitflags;
+use std::fmt;
+use std::os::raw;
+use std::ptr::{self, NonNull};
+
+bitflags! {
+    /// The packet types a `GroContext` coalesces.
+    pub struct GroTypes: u64 {
+        /// TCP over IPv4.
+        const TCP_IPV4 = ffi::RTE_GRO_TCP_IPV4 as u64;
+        /// TCP over IPv4 inside VxLAN over IPv4.
+        const VXLAN_TCP_IPV4 = ffi::RTE_GRO_IPV4_VXLAN_TCP_IPV4 as u64;
+        /// UDP over IPv4, reassembled from IP fragments.
+        const UDP_IPV4 = ffi::RTE_GRO_UDP_IPV4 as u64;
+        /// UDP over IPv4 inside VxLAN over IPv4.
+        const VXLAN_UDP_IPV4 = ffi::RTE_GRO_IPV4_VXLAN_UDP_IPV4 as u64;
+    }
+}
+
+/// The parameters used to create a `GroContext`.
+#[derive(Clone, Copy, Debug)]
+pub struct GroParams {
+    /// The packet types to coalesce.
+    pub gro_types: GroTypes,
+    /// The maximum number of flows the context tracks.
+    pub max_flow_num: u16,
+    /// The maximum number of packets held per flow.
+    pub max_item_per_flow: u16,
+}
+
+impl Default for GroParams {
+    fn default() -> Self {
+        GroParams {
+            gro_types: GroTypes::TCP_IPV4,
+            max_flow_num: 64,
+            max_item_per_flow: 32,
+        }
+    }
+}
+
+/// A software generic receive offload backed by `rte_gro`.
+///
+/// Consecutive TCP segments of the same flow are merged into one larger
+/// packet, so the pipeline processes fewer packets. The merged packets
+/// are held in the context until they are flushed.
+///
+/// # Example
+///
+/// ```
+/// let mut gro = GroContext::new(GroParams::default())?;
+/// gro.reassemble(&mut packets);
+/// gro.timeout_flush(timeout_cycles, 32, &mut packets);
+/// ```
+pub struct GroContext {
+    raw: NonNull<raw::c_void>,
+    gro_types: GroTypes,
+}
+
+impl GroContext {
+    /// Creates a new context on the socket of the current core.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no packet type is selected. If the context
+    /// cannot be allocated, `DpdkError` is returned.
+    pub fn new(params: GroParams) -> Result<Self> {
+        ensure!(
+            !params.gro_types.is_empty(),
+            anyhow!("no packet type is selected for coalescing.")
+        );
+
+        let socket_id = SocketId::current();
+        let raw_params = ffi::rte_gro_param {
+            gro_types: params.gro_types.bits(),
+            max_flow_num: params.max_flow_num,
+            max_item_per_flow: params.max_item_per_flow,
+            socket_id: socket_id.raw().max(0) as u16,
+        };
+
+        let raw =
+            unsafe { ffi::rte_gro_ctx_create(&raw_params).into_result(|_| DpdkError::new())? };
+
+        debug!(?params, "created gro context.");
+        Ok(GroContext {
+            raw,
+            gro_types: params.gro_types,
+        })
+    }
+
+    /// Returns the packet types coalesced.
+    pub fn gro_types(&self) -> GroTypes {
+        self.gro_types
+    }
+
+    /// Returns the number of packets held in the context.
+    pub fn pkt_count(&self) -> u64 {
+        unsafe { ffi::rte_gro_get_pkt_count(self.raw.as_ptr()) }
+    }
+
+    /// Sets the metadata `rte_gro` needs for a TCP or UDP over IPv4
+    /// packet. Tunnel packets must have the metadata set by the caller.
+    fn prepare(ethernet: &mut Ethernet) {
+        if ethernet.ether_type() != EtherTypes::Ipv4 {
+            return;
+        }
+
+        let l2_len = ethernet.header_len();
+        let (l3_len, l4_len, l4_type) = match ethernet.peek::<Ipv4>() {
+            Ok(ipv4) => match ipv4.protocol() {
+                ProtocolNumbers::Tcp => match ipv4.peek::<Tcp4>() {
+                    Ok(tcp) => (ipv4.header_len(), tcp.header_len(), ffi::RTE_PTYPE_L4_TCP),
+                    Err(_) => return,
+                },
+                ProtocolNumbers::Udp => match ipv4.peek::<Udp4>() {
+                    Ok(udp) => (ipv4.header_len(), udp.header_len(), ffi::RTE_PTYPE_L4_UDP),
+                    Err(_) => return,
+                },
+                _ => return,
+            },
+            Err(_) => return,
+        };
+
+        let mbuf = ethernet.mbuf_mut();
+        mbuf.meta_mut().set_packet_type(PacketType::from(
+            ffi::RTE_PTYPE_L2_ETHER | ffi::RTE_PTYPE_L3_IPV4 | l4_type,
+        ));
+        mbuf.raw_mut().__bindgen_anon_6.tx_offload =
+            l2_len as u64 | (l3_len as u64) << 7 | (l4_len as u64) << 16;
+    }
+
+    /// Coalesces the packets with the ones held in the context. The
+    /// packets merged or held are removed from `pkts`, and the packets
+    /// that cannot be coalesced are left in order. Returns the number of
+    /// packets left in `pkts`.
+    pub fn reassemble(&mut self, pkts: &mut Vec<Ethernet>) -> usize {
+        if pkts.is_empty() {
+            return 0;
+        }
+
+        let mut ptrs = pkts
+            .drain(..)
+            .map(|mut pkt| {
+                GroContext::prepare(&mut pkt);
+                pkt.reset().into_ptr()
+            })
+            .collect::<Vec<_>>();
+
+        let left = unsafe {
+            ffi::rte_gro_reassemble(ptrs.as_mut_ptr(), ptrs.len() as u16, self.raw.as_ptr())
+        };
+
+        ptrs.truncate(left as usize);
+        pkts.extend(
+            ptrs.into_iter()
+                .filter_map(|ptr| unsafe { Mbuf::from_ptr(ptr) }.parse::<Ethernet>().ok()),
+        );
+        pkts.len()
+    }
+
+    /// Flushes up to `max_pkts` packets held in the context for longer
+    /// than `timeout_cycles`, and appends them to `output`. A timeout of
+    /// `0` flushes all the packets. Returns the number of packets flushed.
+    pub fn timeout_flush(
+        &mut self,
+        timeout_cycles: u64,
+        max_pkts: u16,
+        output: &mut Vec<Ethernet>,
+    ) -> usize {
+        let mut ptrs = vec![ptr::null_mut(); max_pkts as usize];
+
+        let len = unsafe {
+            ffi::rte_gro_timeout_flush(
+                self.raw.as_ptr(),
+                timeout_cycles,
+                self.gro_types.bits(),
+                ptrs.as_mut_ptr(),
+                max_pkts,
+            )
+        };
+
+        ptrs.truncate(len as usize);
+        output.extend(
+            ptrs.into_iter()
+                .filter_map(|ptr| unsafe { Mbuf::from_ptr(ptr) }.parse::<Ethernet>().ok()),
+        );
+        len as usize
+    }
+}
+
+impl fmt::Debug for GroContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroContext")
+            .field("gro_types", &self.gro_types)
+            .field("pkt_count", &self.pkt_count())
+            .finish()
+    }
+}
+
+impl Drop for GroContext {
+    fn drop(&mut self) {
+        // `rte_gro_ctx_destroy` does not free the packets still held.
+        let mut held = vec![];
+        while self.timeout_flush(0, 32, &mut held) > 0 {
+            held.clear();
+        }
+
+        unsafe {
+            ffi::rte_gro_ctx_destroy(self.raw.as_ptr());
+        }
+    }
+}
+
+// the context is only used by the core that owns it.
+unsafe impl Send for GroContext {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::{IPV4_TCP_PACKET, IPV4_UDP_PACKET};
+
+    /// Builds the `idx`th TCP ACK segment of a flow, with `len` bytes of
+    /// payload each.
+    fn tcp_segment(idx: u16, len: usize) -> Ethernet {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let mut ipv4 = ethernet.parse::<Ipv4>().unwrap();
+        let identification = ipv4.identification();
+        ipv4.set_identification(identification.wrapping_add(idx));
+
+        let mut tcp = ipv4.parse::<Tcp4>().unwrap();
+
+        tcp.unset_syn();
+        tcp.set_ack();
+        let seq_no = tcp.seq_no();
+        tcp.set_seq_no(seq_no.wrapping_add(idx as u32 * len as u32));
+
+        let offset = tcp.payload_offset();
+        tcp.mbuf_mut().extend(offset, len).unwrap();
+        tcp.mbuf_mut()
+            .write_data_slice(offset, &vec![0xaau8; len])
+            .unwrap();
+        tcp.reconcile_all();
+
+        tcp.deparse().deparse()
+    }
+
+    #[capsule::test]
+    fn coalesce_tcp_segments() {
+        let mut gro = GroContext::new(GroParams::default()).unwrap();
+
+        let udp = Mbuf::from_bytes(&IPV4_UDP_PACKET)
+            .unwrap()
+            .parse::<Ethernet>()
+            .unwrap();
+        let mut pkts = vec![tcp_segment(0, 100), tcp_segment(1, 100), udp];
+
+        // the udp packet is not coalesced.
+        assert_eq!(1, gro.reassemble(&mut pkts));
+        assert_eq!(1, gro.pkt_count());
+
+        let mut output = vec![];
+        assert_eq!(1, gro.timeout_flush(0, 32, &mut output));
+        assert_eq!(0, gro.pkt_count());
+
+        // the payload of the second segment is chained to the first.
+        assert_eq!(IPV4_TCP_PACKET.len() + 200, output[0].mbuf().pkt_len());
+    }
+
+    #[capsule::test]
+    fn empty_gro_types() {
+        let params = GroParams {
+            gro_types: GroTypes::empty(),
+            ..Default::default()
+        };
+        assert!(GroContext::new(params).is_err());
+    }
+}
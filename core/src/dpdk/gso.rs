@@ -0,0 +1,233 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, Mbuf, MempoolError, SocketId, MEMPOOL};
+use crate::ensure;
+use crate::ffi;
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::ProtocolNumbers;
+use crate::packets::{EtherTypes, Ethernet, Packet, Tcp4, Udp4};
+use anyhow::{anyhow, Result};
+use bitflags::bitflags;
+use std::fmt;
+use std::ptr::{self, NonNull};
+
+bitflags! {
+    /// The packet types a `GsoContext` segments.
+    ///
+    /// TCP over IPv6 is not supported by `rte_gso`.
+    pub struct GsoTypes: u32 {
+        /// TCP over IPv4.
+        const TCP_IPV4 = ffi::DEV_TX_OFFLOAD_TCP_TSO;
+        /// UDP over IPv4, segmented into IP fragments.
+        const UDP_IPV4 = ffi::DEV_TX_OFFLOAD_UDP_TSO;
+        /// TCP over IPv4 inside VxLAN over IPv4.
+        const VXLAN = ffi::DEV_TX_OFFLOAD_VXLAN_TNL_TSO;
+        /// TCP over IPv4 inside GRE over IPv4.
+        const GRE = ffi::DEV_TX_OFFLOAD_GRE_TNL_TSO;
+    }
+}
+
+/// A software generic segmentation offload backed by `rte_gso`.
+///
+/// Packets larger than the segment size are split into segments that
+/// share the payload with the original packet through indirect mbufs,
+/// so the payload is not copied.
+///
+/// # Example
+///
+/// ```
+/// let gso = GsoContext::new(GsoTypes::TCP_IPV4, 1514)?;
+/// let segments = gso.segment(ethernet)?;
+/// ```
+pub struct GsoContext {
+    raw: ffi::rte_gso_ctx,
+    pool: NonNull<ffi::rte_mempool>,
+}
+
+impl GsoContext {
+    /// Creates a new context that segments packets into `max_pkt_size`
+    /// bytes, including the Ethernet header. The segments are allocated
+    /// from the `Mempool` assigned to the current executing thread by the
+    /// `Runtime`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MempoolError::NotFound` if invoked from a thread not
+    /// managed by the `Runtime`. Returns an error if `max_pkt_size` is less
+    /// than `RTE_GSO_SEG_SIZE_MIN`.
+    pub fn new(gso_types: GsoTypes, max_pkt_size: u16) -> Result<Self> {
+        ensure!(
+            max_pkt_size as u32 >= ffi::RTE_GSO_SEG_SIZE_MIN,
+            anyhow!("segment size {} is too small.", max_pkt_size)
+        );
+
+        let pool = NonNull::new(MEMPOOL.with(|tls| tls.get()))
+            .ok_or_else(|| MempoolError::NotFound(SocketId::current()))?;
+
+        let raw = ffi::rte_gso_ctx {
+            direct_pool: pool.as_ptr(),
+            indirect_pool: pool.as_ptr(),
+            flag: 0,
+            gso_types: gso_types.bits(),
+            gso_size: max_pkt_size,
+        };
+
+        Ok(GsoContext { raw, pool })
+    }
+
+    /// Returns the packet types segmented.
+    pub fn gso_types(&self) -> GsoTypes {
+        GsoTypes::from_bits_truncate(self.raw.gso_types)
+    }
+
+    /// Returns the maximum size of the segments.
+    pub fn max_pkt_size(&self) -> u16 {
+        self.raw.gso_size
+    }
+
+    /// Returns the socket the segments are allocated from.
+    pub fn socket_id(&self) -> SocketId {
+        SocketId(unsafe { self.pool.as_ref().socket_id })
+    }
+
+    /// Sets the transmit offload metadata `rte_gso` needs for a TCP or UDP
+    /// over IPv4 packet. Returns the length of the headers.
+    fn prepare(ethernet: &mut Ethernet) -> Result<usize> {
+        let l2_len = ethernet.header_len();
+        let raw = ethernet.mbuf().raw();
+
+        if raw.ol_flags & ffi::PKT_TX_TUNNEL_MASK != 0 {
+            // the caller already set the metadata for the tunnel packet.
+            let lens = unsafe { raw.__bindgen_anon_6.tx_offload };
+            let inner = (lens & 0x7f) + ((lens >> 7) & 0x1ff) + ((lens >> 16) & 0xff);
+            let outer = ((lens >> 40) & 0x1ff) + ((lens >> 49) & 0x7f);
+            return Ok((inner + outer) as usize);
+        }
+
+        ensure!(
+            ethernet.ether_type() == EtherTypes::Ipv4,
+            anyhow!("only IPv4 packets are segmented.")
+        );
+
+        let ipv4 = ethernet.peek::<Ipv4>()?;
+        let l3_len = ipv4.header_len();
+        let (l4_len, seg_flag) = match ipv4.protocol() {
+            ProtocolNumbers::Tcp => (ipv4.peek::<Tcp4>()?.header_len(), ffi::PKT_TX_TCP_SEG),
+            ProtocolNumbers::Udp => (ipv4.peek::<Udp4>()?.header_len(), ffi::PKT_TX_UDP_SEG),
+            protocol => return Err(anyhow!("protocol {} is not segmented.", protocol)),
+        };
+
+        let raw = ethernet.mbuf_mut().raw_mut();
+        raw.__bindgen_anon_6.tx_offload =
+            l2_len as u64 | (l3_len as u64) << 7 | (l4_len as u64) << 16;
+        raw.ol_flags |= ffi::PKT_TX_IPV4 | seg_flag;
+
+        Ok(l2_len + l3_len + l4_len)
+    }
+
+    /// Segments the packet. If the packet is not larger than the maximum
+    /// segment size, it's returned as the only segment. The original packet
+    /// is consumed, and its buffer is freed when all the segments are.
+    ///
+    /// Tunnel packets must have the transmit offload metadata set by the
+    /// caller. For TCP and UDP over IPv4, the metadata is set from the
+    /// headers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the packet type is not supported. Returns
+    /// `DpdkError` if the segmentation fails.
+    pub fn segment(&self, ethernet: Ethernet) -> Result<Vec<Ethernet>> {
+        let mut ethernet = ethernet;
+        let header_len = GsoContext::prepare(&mut ethernet)?;
+
+        let seg_payload = (self.raw.gso_size as usize).saturating_sub(header_len);
+        ensure!(
+            seg_payload > 0,
+            anyhow!("headers do not fit in segment size {}.", self.raw.gso_size)
+        );
+
+        let payload = ethernet.mbuf().pkt_len().saturating_sub(header_len);
+        let capacity = (payload + seg_payload - 1) / seg_payload + 1;
+        let mut pkts_out = vec![ptr::null_mut(); capacity];
+
+        let raw = ethernet.reset().into_ptr();
+        let res =
+            unsafe { ffi::rte_gso_segment(raw, &self.raw, pkts_out.as_mut_ptr(), capacity as u16) };
+
+        if res < 0 {
+            // the original buffer is still owned on failure.
+            let _ = unsafe { Mbuf::from_ptr(raw) };
+            return Err(DpdkError::from_code(res).into());
+        }
+
+        pkts_out.truncate(res as usize);
+        pkts_out
+            .into_iter()
+            .map(|ptr| unsafe { Mbuf::from_ptr(ptr) }.parse::<Ethernet>())
+            .collect::<Result<Vec<_>>>()
+    }
+}
+
+impl fmt::Debug for GsoContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GsoContext")
+            .field("gso_types", &self.gso_types())
+            .field("max_pkt_size", &self.max_pkt_size())
+            .field("socket_id", &self.socket_id())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_TCP_PACKET;
+
+    #[capsule::test]
+    fn segment_tcp_packet() {
+        let gso = GsoContext::new(GsoTypes::TCP_IPV4, 256).unwrap();
+
+        let mut packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let len = packet.data_len();
+        packet.extend(len, 1000).unwrap();
+        let mut ipv4 = packet.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap();
+        ipv4.reconcile();
+        let header_len = ipv4.envelope().header_len()
+            + ipv4.header_len()
+            + ipv4.peek::<Tcp4>().unwrap().header_len();
+        let payload = ipv4.mbuf().data_len() - header_len;
+
+        let segments = gso.segment(ipv4.deparse()).unwrap();
+        let seg_payload = 256 - header_len;
+        assert_eq!((payload + seg_payload - 1) / seg_payload, segments.len());
+        assert!(segments.iter().all(|s| s.mbuf().pkt_len() <= 256));
+    }
+
+    #[capsule::test]
+    fn segment_small_packet() {
+        let gso = GsoContext::new(GsoTypes::TCP_IPV4, 1514).unwrap();
+
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let segments = gso.segment(ethernet).unwrap();
+        assert_eq!(1, segments.len());
+        assert_eq!(IPV4_TCP_PACKET.len(), segments[0].mbuf().data_len());
+    }
+}
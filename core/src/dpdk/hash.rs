@@ -0,0 +1,425 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{Allocate, DpdkError, NeedsSocket, SocketId};
+use crate::ffi::{self, ToCString, ToResult};
+use crate::{debug, ensure};
+use anyhow::Result;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw;
+use std::ptr::{self, NonNull};
+use thiserror::Error;
+
+/// A fixed size key of a `HashTable`.
+///
+/// The key is hashed and compared by its raw bytes. It is implemented for
+/// byte arrays of the common key sizes.
+pub trait HashKey: Copy {
+    /// Returns the raw bytes of the key.
+    fn as_bytes(&self) -> &[u8];
+
+    /// Creates a key from the raw bytes.
+    ///
+    /// # Safety
+    ///
+    /// `bytes` must point to at least `size_of::<Self>()` readable bytes.
+    unsafe fn from_ptr(bytes: *const u8) -> Self {
+        ptr::read_unaligned(bytes as *const Self)
+    }
+}
+
+macro_rules! impl_hash_key {
+    ($($len:expr),*) => {
+        $(
+            impl HashKey for [u8; $len] {
+                fn as_bytes(&self) -> &[u8] {
+                    &self[..]
+                }
+            }
+        )*
+    };
+}
+
+impl_hash_key!(
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 24, 28, 32, 36, 37, 40,
+    48, 64
+);
+
+/// The hash functions for `HashTable` keys.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HashFunc {
+    /// Jenkins hash.
+    Jhash,
+    /// CRC32 hash, accelerated with the SSE4.2 instructions if available.
+    Crc,
+}
+
+impl HashFunc {
+    fn raw(self) -> ffi::rte_hash_function {
+        match self {
+            HashFunc::Jhash => Some(ffi::_rte_jhash),
+            HashFunc::Crc => Some(ffi::_rte_hash_crc),
+        }
+    }
+}
+
+/// Hash table errors.
+#[derive(Debug, Error)]
+pub(crate) enum HashTableError {
+    /// The value type is larger than a pointer.
+    #[error("Value of {0} bytes is larger than a pointer.")]
+    ValueTooLarge(usize),
+}
+
+/// The parameters used to create a `HashTable`.
+///
+/// The key size is determined by the key type of the table.
+#[derive(Clone, Debug)]
+pub struct HashTableParams {
+    name: String,
+    capacity: u32,
+    socket_id: SocketId,
+    hash_func: HashFunc,
+}
+
+impl HashTableParams {
+    /// Creates the parameters for a table that holds up to `capacity`
+    /// entries. By default, the table is allocated from any socket and
+    /// uses the Jenkins hash.
+    pub fn new(name: &str, capacity: u32) -> Self {
+        HashTableParams {
+            name: name.to_owned(),
+            capacity,
+            socket_id: SocketId::ANY,
+            hash_func: HashFunc::Jhash,
+        }
+    }
+
+    /// Sets the number of entries the table holds.
+    pub fn capacity(&mut self, capacity: u32) -> &mut Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets the socket to allocate the table from.
+    pub fn socket_id(&mut self, socket_id: SocketId) -> &mut Self {
+        self.socket_id = socket_id;
+        self
+    }
+
+    /// Sets the hash function.
+    pub fn hash_func(&mut self, hash_func: HashFunc) -> &mut Self {
+        self.hash_func = hash_func;
+        self
+    }
+
+    /// Returns the extra flags of the table.
+    fn extra_flag() -> u8 {
+        if cfg!(feature = "hash-multi-writer") {
+            (ffi::RTE_HASH_EXTRA_FLAGS_MULTI_WRITER_ADD | ffi::RTE_HASH_EXTRA_FLAGS_RW_CONCURRENCY)
+                as u8
+        } else {
+            0
+        }
+    }
+}
+
+/// An exact match hash table backed by `rte_hash`.
+///
+/// The values are stored inline in the table, in place of the data pointer
+/// `rte_hash` associates with each key, so they must be `Copy` and no larger
+/// than a pointer. Typical values are indices into user managed arrays,
+/// port ids, or small flags.
+///
+/// Without the `hash-multi-writer` feature, the table is not `Sync` and
+/// can only be used from one core at a time. With the feature, concurrent
+/// reads and writes from multiple cores are supported.
+///
+/// # Example
+///
+/// ```
+/// let table = HashTable::<[u8; 6], u16>::new(&HashTableParams::new("macs", 1024))?;
+/// table.insert(&mac.octets(), port_id)?;
+/// assert_eq!(Some(port_id), table.lookup(&mac.octets()));
+/// ```
+pub struct HashTable<K: HashKey, V: Copy> {
+    raw: NonNull<ffi::rte_hash>,
+    name: String,
+    socket_id: SocketId,
+    _phantom: PhantomData<(K, V)>,
+}
+
+impl<K: HashKey, V: Copy> HashTable<K, V> {
+    /// Creates a new table.
+    ///
+    /// # Errors
+    ///
+    /// If `V` is larger than a pointer, an error is returned. If the name
+    /// is already used or the allocation fails, `DpdkError` is returned.
+    pub fn new(params: &HashTableParams) -> Result<Self> {
+        ensure!(
+            mem::size_of::<V>() <= mem::size_of::<*mut raw::c_void>(),
+            HashTableError::ValueTooLarge(mem::size_of::<V>())
+        );
+
+        let name = params.name.clone().into_cstring();
+        let raw_params = ffi::rte_hash_parameters {
+            name: name.as_ptr(),
+            entries: params.capacity,
+            key_len: mem::size_of::<K>() as u32,
+            hash_func: params.hash_func.raw(),
+            hash_func_init_val: 0,
+            socket_id: params.socket_id.raw(),
+            extra_flag: HashTableParams::extra_flag(),
+            ..Default::default()
+        };
+
+        let raw = unsafe { ffi::rte_hash_create(&raw_params).into_result(|_| DpdkError::new())? };
+
+        debug!("created hash table {}.", params.name);
+        Ok(HashTable {
+            raw,
+            name: params.name.clone(),
+            socket_id: params.socket_id,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns the name of the table.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Packs the value into a data pointer.
+    #[inline]
+    fn to_data(value: V) -> *mut raw::c_void {
+        let mut data: *mut raw::c_void = ptr::null_mut();
+        unsafe {
+            ptr::copy_nonoverlapping(
+                &value as *const V as *const u8,
+                &mut data as *mut *mut raw::c_void as *mut u8,
+                mem::size_of::<V>(),
+            );
+        }
+        data
+    }
+
+    /// Unpacks the value from a data pointer.
+    #[inline]
+    fn from_data(data: *mut raw::c_void) -> V {
+        unsafe { ptr::read_unaligned(&data as *const *mut raw::c_void as *const V) }
+    }
+
+    #[inline]
+    fn key_ptr(key: &K) -> *const raw::c_void {
+        key.as_bytes().as_ptr() as *const raw::c_void
+    }
+
+    /// Inserts a key-value pair. If the key is already present, its value
+    /// is updated.
+    ///
+    /// # Errors
+    ///
+    /// If the table is full, `DpdkError` is returned.
+    pub fn insert(&self, key: &K, value: V) -> Result<()> {
+        unsafe {
+            ffi::rte_hash_add_key_data(
+                self.raw.as_ptr(),
+                HashTable::<K, V>::key_ptr(key),
+                HashTable::<K, V>::to_data(value),
+            )
+            .into_result(DpdkError::from_errno)
+            .map(|_| ())
+        }
+    }
+
+    /// Returns the value of the key.
+    pub fn lookup(&self, key: &K) -> Option<V> {
+        let mut data = ptr::null_mut();
+        let res = unsafe {
+            ffi::rte_hash_lookup_data(
+                self.raw.as_ptr(),
+                HashTable::<K, V>::key_ptr(key),
+                &mut data,
+            )
+        };
+
+        if res >= 0 {
+            Some(HashTable::<K, V>::from_data(data))
+        } else {
+            None
+        }
+    }
+
+    /// Deletes the key. Returns whether the key was present.
+    pub fn delete(&self, key: &K) -> bool {
+        unsafe { ffi::rte_hash_del_key(self.raw.as_ptr(), HashTable::<K, V>::key_ptr(key)) >= 0 }
+    }
+
+    /// Returns the number of entries in the table.
+    pub fn len(&self) -> usize {
+        unsafe { ffi::rte_hash_count(self.raw.as_ptr()).max(0) as usize }
+    }
+
+    /// Returns whether the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Deletes all the entries.
+    pub fn clear(&mut self) {
+        unsafe {
+            ffi::rte_hash_reset(self.raw.as_ptr());
+        }
+    }
+
+    /// Returns an iterator over the entries, in no particular order.
+    pub fn iter(&self) -> HashTableIter<'_, K, V> {
+        HashTableIter {
+            table: self,
+            next: 0,
+        }
+    }
+}
+
+impl<K: HashKey, V: Copy> NeedsSocket for HashTable<K, V> {
+    fn socket_id(&self) -> SocketId {
+        self.socket_id
+    }
+}
+
+impl<K: HashKey, V: Copy> Allocate for HashTable<K, V> {
+    type Params = HashTableParams;
+
+    fn allocate(socket_id: SocketId, mut params: Self::Params) -> Result<Self> {
+        params.socket_id(socket_id);
+        HashTable::new(&params)
+    }
+}
+
+impl<K: HashKey, V: Copy> fmt::Debug for HashTable<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashTable")
+            .field("name", &self.name())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<K: HashKey, V: Copy> Drop for HashTable<K, V> {
+    fn drop(&mut self) {
+        debug!("freeing hash table {}.", self.name);
+
+        unsafe {
+            ffi::rte_hash_free(self.raw.as_ptr());
+        }
+    }
+}
+
+unsafe impl<K: HashKey + Send, V: Copy + Send> Send for HashTable<K, V> {}
+
+/// With concurrency enabled, `rte_hash` synchronizes the readers and writers.
+#[cfg(feature = "hash-multi-writer")]
+unsafe impl<K: HashKey + Sync, V: Copy + Sync> Sync for HashTable<K, V> {}
+
+/// An iterator over the entries of a `HashTable`.
+pub struct HashTableIter<'a, K: HashKey, V: Copy> {
+    table: &'a HashTable<K, V>,
+    next: u32,
+}
+
+impl<K: HashKey, V: Copy> Iterator for HashTableIter<'_, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut key = ptr::null();
+        let mut data = ptr::null_mut();
+
+        let res = unsafe {
+            ffi::rte_hash_iterate(self.table.raw.as_ptr(), &mut key, &mut data, &mut self.next)
+        };
+
+        if res >= 0 {
+            let key = unsafe { K::from_ptr(key as *const u8) };
+            Some((key, HashTable::<K, V>::from_data(data)))
+        } else {
+            None
+        }
+    }
+}
+
+impl<K: HashKey, V: Copy> fmt::Debug for HashTableIter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HashTableIter")
+            .field("table", &self.table.name())
+            .field("next", &self.next)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn hash_table_insert_lookup_delete() {
+        let table =
+            HashTable::<[u8; 6], u16>::new(&HashTableParams::new("hash_basic", 64)).unwrap();
+
+        table.insert(&[0, 0, 0, 0, 0, 1], 1).unwrap();
+        table.insert(&[0, 0, 0, 0, 0, 2], 2).unwrap();
+        assert_eq!(2, table.len());
+        assert_eq!(Some(1), table.lookup(&[0, 0, 0, 0, 0, 1]));
+        assert_eq!(None, table.lookup(&[0, 0, 0, 0, 0, 3]));
+
+        // updates the existing value.
+        table.insert(&[0, 0, 0, 0, 0, 1], 10).unwrap();
+        assert_eq!(Some(10), table.lookup(&[0, 0, 0, 0, 0, 1]));
+
+        assert!(table.delete(&[0, 0, 0, 0, 0, 1]));
+        assert!(!table.delete(&[0, 0, 0, 0, 0, 1]));
+        assert_eq!(1, table.len());
+    }
+
+    #[capsule::test]
+    fn hash_table_iter() {
+        let mut params = HashTableParams::new("hash_iter", 64);
+        params.hash_func(HashFunc::Crc);
+        let mut table = HashTable::<[u8; 4], u32>::new(&params).unwrap();
+
+        for i in 0..10u32 {
+            table.insert(&i.to_be_bytes(), i * 2).unwrap();
+        }
+
+        let mut entries = table.iter().collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(10, entries.len());
+        assert_eq!(([0, 0, 0, 9], 18), entries[9]);
+
+        table.clear();
+        assert!(table.is_empty());
+    }
+
+    #[capsule::test]
+    fn hash_table_value_too_large() {
+        let params = HashTableParams::new("hash_large", 64);
+        assert!(HashTable::<[u8; 4], [u64; 2]>::new(&params).is_err());
+    }
+}
@@ -0,0 +1,428 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use crate::ensure;
+use crate::packets::{Ethernet, Packet};
+use anyhow::Result;
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+use thiserror::Error;
+
+/// The default bucket boundaries of `SizeHistogram`, in bytes.
+const SIZE_BOUNDS: [u64; 7] = [64, 128, 256, 512, 1024, 1500, 9000];
+
+/// The default bucket boundaries of `LatencyHistogram`, in nanoseconds.
+const LATENCY_BOUNDS: [u64; 12] = [
+    1_000,
+    2_000,
+    5_000,
+    10_000,
+    20_000,
+    50_000,
+    100_000,
+    200_000,
+    500_000,
+    1_000_000,
+    10_000_000,
+    100_000_000,
+];
+
+/// Error indicating the histogram operation failed.
+#[derive(Debug, Error)]
+pub(crate) enum HistogramError {
+    /// The bucket boundaries are empty or not strictly increasing.
+    #[error("Bucket boundaries must be strictly increasing.")]
+    InvalidBounds,
+
+    /// The histograms merged have different bucket boundaries.
+    #[error("Histograms have different bucket boundaries.")]
+    MismatchedBounds,
+}
+
+/// A histogram of values over fixed buckets.
+///
+/// Each boundary is the inclusive upper bound of a bucket. Values above
+/// the last boundary are counted in an overflow bucket. The histogram is
+/// not thread-safe. Each core should record into its own histogram, and
+/// the control plane merges them for reporting.
+///
+/// # Example
+///
+/// ```
+/// let mut total = Histogram::new(&[10, 100, 1000])?;
+/// for per_core in histograms {
+///     total.merge(&per_core)?;
+/// }
+/// println!("p99 = {}", total.percentile(0.99));
+/// total.print_table(&mut io::stdout())?;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    bounds: Vec<u64>,
+    // one more than the bounds, the last one is the overflow bucket.
+    counts: Vec<u64>,
+    total: u64,
+    max: u64,
+}
+
+impl Histogram {
+    /// Creates a histogram with the bucket boundaries.
+    ///
+    /// # Errors
+    ///
+    /// Returns `HistogramError::InvalidBounds` if the boundaries are empty
+    /// or not strictly increasing.
+    pub fn new(bounds: &[u64]) -> Result<Self> {
+        ensure!(
+            !bounds.is_empty() && bounds.windows(2).all(|w| w[0] < w[1]),
+            HistogramError::InvalidBounds
+        );
+
+        Ok(Histogram {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+            total: 0,
+            max: 0,
+        })
+    }
+
+    /// Returns the bucket boundaries.
+    pub fn bounds(&self) -> &[u64] {
+        &self.bounds
+    }
+
+    /// Returns the counts of the buckets. The last count is the overflow
+    /// bucket.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns the number of values recorded.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns the largest value recorded.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Records the value.
+    #[inline]
+    pub fn record(&mut self, value: u64) {
+        let idx = match self.bounds.binary_search(&value) {
+            Ok(idx) | Err(idx) => idx,
+        };
+        self.counts[idx] += 1;
+        self.total += 1;
+        self.max = self.max.max(value);
+    }
+
+    /// Returns the value at the percentile `p`, between `0.0` and `1.0`.
+    /// The value is interpolated linearly within the bucket it falls in.
+    /// The overflow bucket's upper bound is the largest value recorded.
+    /// Returns `0` if the histogram is empty.
+    pub fn percentile(&self, p: f64) -> u64 {
+        if self.total == 0 {
+            return 0;
+        }
+
+        let rank = p.max(0.0).min(1.0) * self.total as f64;
+        let mut below = 0u64;
+
+        for (idx, &count) in self.counts.iter().enumerate() {
+            if count > 0 && (below + count) as f64 >= rank {
+                let lower = if idx == 0 { 0 } else { self.bounds[idx - 1] };
+                let upper = self
+                    .bounds
+                    .get(idx)
+                    .copied()
+                    .unwrap_or(self.max)
+                    .min(self.max);
+                let fraction = (rank - below as f64) / count as f64;
+                return lower + ((upper.saturating_sub(lower)) as f64 * fraction) as u64;
+            }
+            below += count;
+        }
+
+        self.max
+    }
+
+    /// Adds the counts of the other histogram.
+    ///
+    /// # Errors
+    ///
+    /// Returns `HistogramError::MismatchedBounds` if the histograms have
+    /// different bucket boundaries.
+    pub fn merge(&mut self, other: &Histogram) -> Result<()> {
+        ensure!(
+            self.bounds == other.bounds,
+            HistogramError::MismatchedBounds
+        );
+
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+        Ok(())
+    }
+
+    /// Clears the counts.
+    pub fn reset(&mut self) {
+        self.counts.iter_mut().for_each(|count| *count = 0);
+        self.total = 0;
+        self.max = 0;
+    }
+
+    /// Writes the buckets as an ASCII table.
+    ///
+    /// ```
+    /// +--------------+------------+---------+
+    /// | bucket       |      count |       % |
+    /// +--------------+------------+---------+
+    /// | <= 64        |         10 |   50.00 |
+    /// | > 9000       |         10 |   50.00 |
+    /// +--------------+------------+---------+
+    /// ```
+    pub fn print_table<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let border = "+--------------+------------+---------+";
+        writeln!(writer, "{}", border)?;
+        writeln!(
+            writer,
+            "| {:<12} | {:>10} | {:>7} |",
+            "bucket", "count", "%"
+        )?;
+        writeln!(writer, "{}", border)?;
+
+        for (idx, &count) in self.counts.iter().enumerate() {
+            let bucket = match self.bounds.get(idx) {
+                Some(bound) => format!("<= {}", bound),
+                None => format!("> {}", self.bounds[idx - 1]),
+            };
+            let percent = if self.total == 0 {
+                0.0
+            } else {
+                count as f64 * 100.0 / self.total as f64
+            };
+            writeln!(
+                writer,
+                "| {:<12} | {:>10} | {:>7.2} |",
+                bucket, count, percent
+            )?;
+        }
+
+        writeln!(writer, "{}", border)
+    }
+}
+
+/// A histogram of Ethernet frame sizes in bytes.
+///
+/// Derefs to the underlying `Histogram`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SizeHistogram(Histogram);
+
+impl SizeHistogram {
+    /// Creates a histogram with the bucket boundaries in bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `HistogramError::InvalidBounds` if the boundaries are empty
+    /// or not strictly increasing.
+    pub fn new(bounds: &[u64]) -> Result<Self> {
+        Histogram::new(bounds).map(SizeHistogram)
+    }
+
+    /// Records the size of the frame.
+    #[inline]
+    pub fn record(&mut self, pkt: &Ethernet) {
+        self.0.record(pkt.len() as u64);
+    }
+}
+
+impl Default for SizeHistogram {
+    /// Creates a histogram with the buckets 64, 128, 256, 512, 1024, 1500
+    /// and 9000 bytes.
+    fn default() -> Self {
+        SizeHistogram::new(&SIZE_BOUNDS).unwrap()
+    }
+}
+
+impl Deref for SizeHistogram {
+    type Target = Histogram;
+
+    fn deref(&self) -> &Histogram {
+        &self.0
+    }
+}
+
+impl DerefMut for SizeHistogram {
+    fn deref_mut(&mut self) -> &mut Histogram {
+        &mut self.0
+    }
+}
+
+/// A histogram of latencies in nanoseconds.
+///
+/// Derefs to the underlying `Histogram`.
+///
+/// # Example
+///
+/// ```
+/// let mut latency = LatencyHistogram::default();
+/// let hz = CycleTimer::hz();
+///
+/// for packet in packets {
+///     latency.record_latency(packet.mbuf().meta().timestamp(), CycleTimer::now(), hz);
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatencyHistogram(Histogram);
+
+impl LatencyHistogram {
+    /// Creates a histogram with the bucket boundaries in nanoseconds.
+    ///
+    /// # Errors
+    ///
+    /// Returns `HistogramError::InvalidBounds` if the boundaries are empty
+    /// or not strictly increasing.
+    pub fn new(bounds_ns: &[u64]) -> Result<Self> {
+        Histogram::new(bounds_ns).map(LatencyHistogram)
+    }
+
+    /// Records the latency between the receive timestamp and now, both in
+    /// cycles of a `hz` clock. A timestamp after now counts as 0.
+    #[inline]
+    pub fn record_latency(&mut self, rx_ts: u64, now_cycles: u64, hz: u64) {
+        let cycles = now_cycles.saturating_sub(rx_ts) as u128;
+        let ns = cycles * 1_000_000_000 / hz.max(1) as u128;
+        self.0.record(ns as u64);
+    }
+}
+
+impl Default for LatencyHistogram {
+    /// Creates a histogram with buckets from 1 µs to 100 ms.
+    fn default() -> Self {
+        LatencyHistogram::new(&LATENCY_BOUNDS).unwrap()
+    }
+}
+
+impl Deref for LatencyHistogram {
+    type Target = Histogram;
+
+    fn deref(&self) -> &Histogram {
+        &self.0
+    }
+}
+
+impl DerefMut for LatencyHistogram {
+    fn deref_mut(&mut self) -> &mut Histogram {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+    use crate::Mbuf;
+
+    #[test]
+    fn invalid_bounds() {
+        assert!(Histogram::new(&[]).is_err());
+        assert!(Histogram::new(&[10, 10]).is_err());
+        assert!(Histogram::new(&[20, 10]).is_err());
+    }
+
+    #[test]
+    fn record_into_buckets() {
+        let mut histogram = Histogram::new(&[10, 100]).unwrap();
+        histogram.record(0);
+        histogram.record(10);
+        histogram.record(11);
+        histogram.record(1000);
+
+        assert_eq!(&[2, 1, 1], histogram.counts());
+        assert_eq!(4, histogram.total());
+        assert_eq!(1000, histogram.max());
+    }
+
+    #[test]
+    fn interpolate_percentile() {
+        let mut histogram = Histogram::new(&[100, 200]).unwrap();
+        assert_eq!(0, histogram.percentile(0.5));
+
+        for _ in 0..10 {
+            histogram.record(50);
+            histogram.record(150);
+        }
+
+        assert_eq!(50, histogram.percentile(0.25));
+        assert_eq!(100, histogram.percentile(0.5));
+        assert_eq!(125, histogram.percentile(0.75));
+        assert_eq!(150, histogram.percentile(1.0));
+    }
+
+    #[test]
+    fn merge_histograms() {
+        let mut a = Histogram::new(&[10, 100]).unwrap();
+        let mut b = a.clone();
+        a.record(5);
+        b.record(50);
+        b.record(500);
+
+        a.merge(&b).unwrap();
+        assert_eq!(&[1, 1, 1], a.counts());
+        assert_eq!(500, a.max());
+
+        let c = Histogram::new(&[10]).unwrap();
+        assert!(a.merge(&c).is_err());
+    }
+
+    #[test]
+    fn print_ascii_table() {
+        let mut histogram = Histogram::new(&[64]).unwrap();
+        histogram.record(64);
+        histogram.record(65);
+
+        let mut out = Vec::new();
+        histogram.print_table(&mut out).unwrap();
+        let table = String::from_utf8(out).unwrap();
+        assert!(table.contains("| <= 64        |          1 |   50.00 |"));
+        assert!(table.contains("| > 64         |          1 |   50.00 |"));
+    }
+
+    #[test]
+    fn record_latency_in_ns() {
+        let mut latency = LatencyHistogram::default();
+        latency.record_latency(1_000, 4_000, 1_000_000_000);
+        latency.record_latency(5_000, 4_000, 1_000_000_000);
+        assert_eq!(3_000, latency.max());
+        assert_eq!(1, latency.counts()[0]);
+        assert_eq!(1, latency.counts()[2]);
+    }
+
+    #[capsule::test]
+    fn record_frame_size() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+
+        let mut sizes = SizeHistogram::default();
+        sizes.record(&ethernet);
+        assert_eq!(1, sizes.counts()[0]);
+    }
+}
@@ -0,0 +1,249 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, SocketId};
+use crate::ensure;
+use crate::ffi::{self, ToResult};
+use anyhow::Result;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::os::raw;
+use std::ptr::{self, NonNull};
+use std::slice;
+use thiserror::Error;
+
+/// Huge page allocation errors.
+#[derive(Debug, Error)]
+pub(crate) enum HugePageError {
+    /// Zero-sized allocations are not supported by `rte_malloc`.
+    #[error("Cannot allocate zero bytes from huge pages.")]
+    ZeroSized,
+
+    /// The socket does not have enough free huge page memory.
+    #[error("Failed to allocate {0} bytes from huge pages on {1:?}.")]
+    OutOfMemory(usize, SocketId),
+}
+
+/// Allocates memory from the huge pages reserved by the EAL.
+///
+/// Huge page memory is not swapped out and has far fewer TLB misses than
+/// regular heap memory. It is well suited for large data structures, like
+/// routing tables, that are shared across cores on the same socket.
+///
+/// # Example
+///
+/// ```
+/// let table = HugePageAllocator::alloc_slice::<Route>(1 << 20, SocketId::current())?;
+/// ```
+#[derive(Debug)]
+pub struct HugePageAllocator;
+
+impl HugePageAllocator {
+    /// Allocates the raw memory for `count` values of `T`.
+    fn alloc_raw<T>(count: usize, socket_id: SocketId, zeroed: bool) -> Result<NonNull<T>> {
+        let size = mem::size_of::<T>() * count;
+        ensure!(size > 0, HugePageError::ZeroSized);
+
+        let align = mem::align_of::<T>() as raw::c_uint;
+        let ptr = unsafe {
+            if zeroed {
+                ffi::rte_zmalloc_socket(ptr::null(), size as ffi::size_t, align, socket_id.raw())
+            } else {
+                ffi::rte_malloc_socket(ptr::null(), size as ffi::size_t, align, socket_id.raw())
+            }
+        };
+
+        NonNull::new(ptr as *mut T)
+            .ok_or_else(|| HugePageError::OutOfMemory(size, socket_id).into())
+    }
+
+    /// Moves a value into huge page memory on a socket.
+    ///
+    /// # Errors
+    ///
+    /// If `T` is zero-sized or the socket doesn't have enough free memory,
+    /// an error is returned.
+    pub fn alloc<T>(value: T, socket_id: SocketId) -> Result<HugePageBox<T>> {
+        let ptr = HugePageAllocator::alloc_raw::<T>(1, socket_id, false)?;
+        unsafe {
+            ptr::write(ptr.as_ptr(), value);
+        }
+
+        Ok(HugePageBox {
+            ptr,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Allocates zeroed huge page memory on a socket for `count` values,
+    /// then initializes each to the default value.
+    ///
+    /// # Errors
+    ///
+    /// If the slice is zero-sized or the socket doesn't have enough free
+    /// memory, an error is returned.
+    pub fn alloc_slice<T: Default>(count: usize, socket_id: SocketId) -> Result<HugePageSlice<T>> {
+        let ptr = HugePageAllocator::alloc_raw::<T>(count, socket_id, true)?;
+        for i in 0..count {
+            unsafe {
+                ptr::write(ptr.as_ptr().add(i), T::default());
+            }
+        }
+
+        Ok(HugePageSlice {
+            ptr,
+            len: count,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns the number of free bytes in the huge page heap of a socket.
+    ///
+    /// # Errors
+    ///
+    /// If the socket id is invalid, `DpdkError` is returned.
+    pub fn available(socket_id: SocketId) -> Result<u64> {
+        let mut stats = ffi::rte_malloc_socket_stats::default();
+        unsafe {
+            ffi::rte_malloc_get_socket_stats(socket_id.raw(), &mut stats)
+                .into_result(|_| DpdkError::new())?;
+        }
+        Ok(stats.heap_freesz_bytes as u64)
+    }
+
+    /// Dumps the statistics of all the huge page heaps to stdout.
+    pub fn dump_stats() {
+        unsafe {
+            ffi::_rte_malloc_dump_stats(ptr::null());
+        }
+    }
+}
+
+/// A pointer type for a value allocated in huge page memory.
+///
+/// Like `Box<T>`, the value is dropped and the memory freed when the
+/// `HugePageBox` goes out of scope.
+pub struct HugePageBox<T> {
+    ptr: NonNull<T>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> Deref for HugePageBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for HugePageBox<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for HugePageBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for HugePageBox<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.ptr.as_ptr());
+            ffi::rte_free(self.ptr.as_ptr() as *mut raw::c_void);
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for HugePageBox<T> {}
+unsafe impl<T: Sync> Sync for HugePageBox<T> {}
+
+/// A fixed length slice allocated in huge page memory.
+///
+/// All the values are dropped and the memory freed when the
+/// `HugePageSlice` goes out of scope.
+pub struct HugePageSlice<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> Deref for HugePageSlice<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for HugePageSlice<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for HugePageSlice<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for HugePageSlice<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(&mut **self as *mut [T]);
+            ffi::rte_free(self.ptr.as_ptr() as *mut raw::c_void);
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for HugePageSlice<T> {}
+unsafe impl<T: Sync> Sync for HugePageSlice<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn alloc_hugepage_box() {
+        let mut boxed = HugePageAllocator::alloc([1u64; 16], SocketId::ANY).unwrap();
+        assert_eq!(1, boxed[15]);
+        boxed[15] = 2;
+        assert_eq!(2, boxed[15]);
+        assert!(boxed.ptr.as_ptr() as usize % mem::align_of::<[u64; 16]>() == 0);
+    }
+
+    #[capsule::test]
+    fn alloc_hugepage_slice() {
+        let mut slice = HugePageAllocator::alloc_slice::<u32>(1024, SocketId::ANY).unwrap();
+        assert_eq!(1024, slice.len());
+        assert!(slice.iter().all(|&v| v == 0));
+        slice[1023] = 7;
+        assert_eq!(7, slice[1023]);
+    }
+
+    #[capsule::test]
+    fn alloc_zero_sized() {
+        assert!(HugePageAllocator::alloc((), SocketId::ANY).is_err());
+        assert!(HugePageAllocator::alloc_slice::<u32>(0, SocketId::ANY).is_err());
+    }
+}
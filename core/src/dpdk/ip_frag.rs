@@ -0,0 +1,827 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{tsc_cycles, DpdkError, Mbuf, MempoolError, SocketId, MEMPOOL};
+use crate::ffi::{self, ToResult};
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::v6::{Ipv6, Ipv6Packet};
+use crate::packets::ip::ProtocolNumbers;
+use crate::packets::{Ethernet, Packet};
+use crate::{debug, ensure, warn};
+use anyhow::Result;
+use std::fmt;
+use std::ptr::{self, NonNull};
+use thiserror::Error;
+
+/// The number of mbufs a single reassembly may add to the death row.
+const MAX_FREED_PER_PACKET: u32 = ffi::RTE_LIBRTE_IP_FRAG_MAX_FRAG + 1;
+
+/// The length of the IPv6 header.
+const IPV6_HDR_LEN: usize = 40;
+
+/// The length of the IPv6 fragment extension header.
+const IPV6_FRAG_HDR_LEN: usize = 8;
+
+/// IP fragmentation errors.
+#[derive(Debug, Error)]
+pub(crate) enum IpFragError {
+    /// The packet has the don't fragment flag set.
+    #[error("Packet has the don't fragment flag set.")]
+    FragmentationNotAllowed,
+
+    /// The MTU can't fit the headers and the minimum fragment payload.
+    #[error("MTU {0} is too small to fragment.")]
+    MtuTooSmall(u16),
+}
+
+/// A holding area for the mbufs the reassembly no longer needs.
+///
+/// Fragments of expired or invalid datagrams are not freed right away
+/// during reassembly. Instead they are put on the death row, so they can
+/// be freed in bulk at a convenient time, typically once per batch of
+/// received packets.
+pub struct DeathRow {
+    raw: Box<ffi::rte_ip_frag_death_row>,
+}
+
+impl DeathRow {
+    /// Creates a new empty death row.
+    pub fn new() -> Self {
+        DeathRow {
+            raw: Box::new(ffi::rte_ip_frag_death_row::default()),
+        }
+    }
+
+    /// Returns the number of mbufs waiting to be freed.
+    pub fn len(&self) -> usize {
+        self.raw.cnt as usize
+    }
+
+    /// Returns whether the death row is empty.
+    pub fn is_empty(&self) -> bool {
+        self.raw.cnt == 0
+    }
+
+    /// Frees all the mbufs on the death row.
+    pub fn free_all(&mut self) {
+        if !self.is_empty() {
+            unsafe {
+                ffi::rte_ip_frag_free_death_row(self.raw.as_mut(), 0);
+            }
+        }
+    }
+
+    /// Makes sure there's room for one more reassembly.
+    fn reserve(&mut self) {
+        if self.raw.cnt + MAX_FREED_PER_PACKET > ffi::IP_FRAG_DEATH_ROW_MBUF_LEN {
+            self.free_all();
+        }
+    }
+}
+
+impl Default for DeathRow {
+    fn default() -> Self {
+        DeathRow::new()
+    }
+}
+
+impl fmt::Debug for DeathRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeathRow")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl Drop for DeathRow {
+    fn drop(&mut self) {
+        self.free_all();
+    }
+}
+
+/// Sets the L2 and L3 header lengths `rte_ip_frag` reads from the mbuf,
+/// and returns a pointer to the IP header.
+fn prepare<T: Packet<Envelope = Ethernet>, H>(packet: &mut T, l3_len: usize) -> *mut H {
+    let l2_len = packet.envelope().header_len() as u64;
+    let offset = packet.offset();
+
+    let raw = packet.mbuf_mut().raw_mut();
+    unsafe {
+        let lens = &mut raw.__bindgen_anon_6.tx_offload;
+        *lens = (*lens & !0xffff) | l2_len | (l3_len as u64) << 7;
+        (raw.buf_addr as *mut u8).add(raw.data_off as usize + offset) as *mut H
+    }
+}
+
+/// A fragment table shared by the IPv4 and IPv6 reassembly.
+struct FragTable {
+    raw: NonNull<ffi::rte_ip_frag_tbl>,
+    max_flows: u32,
+}
+
+impl FragTable {
+    fn new(
+        bucket_entries: u32,
+        max_flows: u32,
+        max_flow_ttl_cycles: u64,
+        socket_id: SocketId,
+    ) -> Result<Self> {
+        // over provisions the buckets to reduce the chance of collisions.
+        let bucket_num = max_flows + max_flows / 4;
+
+        let raw = unsafe {
+            ffi::rte_ip_frag_table_create(
+                bucket_num,
+                bucket_entries,
+                max_flows,
+                max_flow_ttl_cycles,
+                socket_id.raw(),
+            )
+            .into_result(|_| DpdkError::new())?
+        };
+
+        Ok(FragTable { raw, max_flows })
+    }
+
+    /// Parses the reassembled datagram.
+    fn parse<T: Packet<Envelope = Ethernet>>(raw: *mut ffi::rte_mbuf) -> Option<T> {
+        let mbuf = unsafe { Mbuf::from_ptr(NonNull::new(raw)?.as_ptr()) };
+
+        match mbuf.parse::<Ethernet>().and_then(|eth| eth.parse::<T>()) {
+            Ok(packet) => Some(packet),
+            Err(err) => {
+                warn!(message = "failed to parse reassembled datagram.", ?err);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for FragTable {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rte_ip_frag_table_destroy(self.raw.as_ptr());
+        }
+    }
+}
+
+/// Reassembles fragmented IPv4 datagrams, backed by `rte_ip_frag`.
+///
+/// Fragments are kept in a table keyed by the source, destination and
+/// identification fields until all the fragments of the datagram have
+/// arrived. The reassembled datagram is a chain of the fragment mbufs.
+/// Datagrams not completed within the time to live are dropped.
+///
+/// # Example
+///
+/// ```
+/// let mut defrag = Ipv4Defrag::new(16, 1024, tsc_hz, SocketId::current())?;
+/// let mut death_row = DeathRow::new();
+///
+/// if let Some(ipv4) = defrag.process_fragment(ipv4, &mut death_row) {
+///     // the complete datagram.
+/// }
+///
+/// death_row.free_all();
+/// ```
+pub struct Ipv4Defrag {
+    table: FragTable,
+}
+
+impl Ipv4Defrag {
+    /// Creates a new reassembly table that tracks up to `max_flows`
+    /// datagrams. `bucket_entries` is the number of datagrams per hash
+    /// bucket, and must be a power of 2. A datagram not completed within
+    /// `max_flow_ttl_cycles` TSC cycles is dropped.
+    ///
+    /// # Errors
+    ///
+    /// If the parameters are invalid or the allocation fails, `DpdkError`
+    /// is returned.
+    pub fn new(
+        bucket_entries: u32,
+        max_flows: u32,
+        max_flow_ttl_cycles: u64,
+        socket_id: SocketId,
+    ) -> Result<Self> {
+        let table = FragTable::new(bucket_entries, max_flows, max_flow_ttl_cycles, socket_id)?;
+        debug!(max_flows, "created IPv4 reassembly table.");
+        Ok(Ipv4Defrag { table })
+    }
+
+    /// Returns the maximum number of datagrams tracked.
+    pub fn max_flows(&self) -> u32 {
+        self.table.max_flows
+    }
+
+    /// Processes a packet. If the packet is not a fragment, it's returned
+    /// as is. Otherwise the fragment is added to the table, and the
+    /// reassembled datagram is returned once all the fragments arrived.
+    ///
+    /// Returns `None` if more fragments are needed, or if the fragment was
+    /// dropped. Dropped fragments are put on the death row.
+    pub fn process_fragment(&mut self, pkt: Ipv4, death_row: &mut DeathRow) -> Option<Ipv4> {
+        if !pkt.more_fragments() && pkt.fragment_offset() == 0 {
+            return Some(pkt);
+        }
+
+        let mut pkt = pkt;
+        let l3_len = pkt.header_len();
+        let hdr = prepare::<_, ffi::rte_ipv4_hdr>(&mut pkt, l3_len);
+        death_row.reserve();
+
+        let raw = unsafe {
+            ffi::rte_ipv4_frag_reassemble_packet(
+                self.table.raw.as_ptr(),
+                death_row.raw.as_mut(),
+                pkt.reset().into_ptr(),
+                tsc_cycles(),
+                hdr,
+            )
+        };
+
+        FragTable::parse::<Ipv4>(raw).map(|mut ipv4| {
+            // the reassembly leaves the header checksum stale.
+            ipv4.compute_checksum();
+            ipv4
+        })
+    }
+}
+
+impl fmt::Debug for Ipv4Defrag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ipv4Defrag")
+            .field("max_flows", &self.max_flows())
+            .finish()
+    }
+}
+
+impl Drop for Ipv4Defrag {
+    fn drop(&mut self) {
+        debug!("freeing IPv4 reassembly table.");
+    }
+}
+
+/// Reassembles fragmented IPv6 packets, backed by `rte_ip_frag`.
+///
+/// Works the same way as [`Ipv4Defrag`]. Only fragment headers directly
+/// following the IPv6 header are recognized. The fragment header is
+/// removed from the reassembled packet.
+///
+/// [`Ipv4Defrag`]: Ipv4Defrag
+pub struct Ipv6Defrag {
+    table: FragTable,
+}
+
+impl Ipv6Defrag {
+    /// Creates a new reassembly table that tracks up to `max_flows`
+    /// packets. `bucket_entries` is the number of packets per hash
+    /// bucket, and must be a power of 2. A packet not completed within
+    /// `max_flow_ttl_cycles` TSC cycles is dropped.
+    ///
+    /// # Errors
+    ///
+    /// If the parameters are invalid or the allocation fails, `DpdkError`
+    /// is returned.
+    pub fn new(
+        bucket_entries: u32,
+        max_flows: u32,
+        max_flow_ttl_cycles: u64,
+        socket_id: SocketId,
+    ) -> Result<Self> {
+        let table = FragTable::new(bucket_entries, max_flows, max_flow_ttl_cycles, socket_id)?;
+        debug!(max_flows, "created IPv6 reassembly table.");
+        Ok(Ipv6Defrag { table })
+    }
+
+    /// Returns the maximum number of packets tracked.
+    pub fn max_flows(&self) -> u32 {
+        self.table.max_flows
+    }
+
+    /// Processes a packet. If the packet is not a fragment, it's returned
+    /// as is. Otherwise the fragment is added to the table, and the
+    /// reassembled packet is returned once all the fragments arrived.
+    ///
+    /// Returns `None` if more fragments are needed, or if the fragment was
+    /// dropped. Dropped fragments are put on the death row.
+    pub fn process_fragment(&mut self, pkt: Ipv6, death_row: &mut DeathRow) -> Option<Ipv6> {
+        if pkt.next_header() != ProtocolNumbers::Ipv6Frag {
+            return Some(pkt);
+        }
+
+        let mut pkt = pkt;
+        let hdr = prepare::<_, u8>(&mut pkt, IPV6_HDR_LEN + IPV6_FRAG_HDR_LEN);
+        death_row.reserve();
+
+        let raw = unsafe {
+            ffi::rte_ipv6_frag_reassemble_packet(
+                self.table.raw.as_ptr(),
+                death_row.raw.as_mut(),
+                pkt.reset().into_ptr(),
+                tsc_cycles(),
+                hdr as *mut ffi::rte_ipv6_hdr,
+                hdr.add(IPV6_HDR_LEN) as *mut ffi::ipv6_extension_fragment,
+            )
+        };
+
+        FragTable::parse::<Ipv6>(raw)
+    }
+}
+
+impl fmt::Debug for Ipv6Defrag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ipv6Defrag")
+            .field("max_flows", &self.max_flows())
+            .finish()
+    }
+}
+
+impl Drop for Ipv6Defrag {
+    fn drop(&mut self) {
+        debug!("freeing IPv6 reassembly table.");
+    }
+}
+
+/// The function signature shared by `rte_ipv4_fragment_packet` and
+/// `rte_ipv6_fragment_packet`.
+type FragmentFn = unsafe extern "C" fn(
+    *mut ffi::rte_mbuf,
+    *mut *mut ffi::rte_mbuf,
+    u16,
+    u16,
+    *mut ffi::rte_mempool,
+    *mut ffi::rte_mempool,
+) -> i32;
+
+/// Fragments the packet with `rte_ip_frag`, and prepends a copy of the
+/// Ethernet header to each fragment.
+///
+/// `frag_mtu` is the already aligned fragment size, including the L3
+/// headers `rte_ip_frag` adds to each fragment.
+fn fragment_packet<T: Packet<Envelope = Ethernet>>(
+    pkt: T,
+    pool: NonNull<ffi::rte_mempool>,
+    frag_fn: FragmentFn,
+    frag_mtu: u16,
+    capacity: usize,
+) -> Result<Vec<T>> {
+    let l2_len = pkt.envelope().header_len();
+    let l2_hdr = unsafe { pkt.mbuf().read_data_slice::<u8>(0, l2_len)?.as_ref() }.to_vec();
+    let mut pkts_out = vec![ptr::null_mut(); capacity];
+
+    let mut mbuf = pkt.reset();
+    let res = unsafe {
+        // `rte_ip_frag` expects the packet to start at the IP header.
+        let raw = mbuf.raw_mut();
+        raw.data_off += l2_len as u16;
+        raw.data_len -= l2_len as u16;
+        raw.pkt_len -= l2_len as u32;
+
+        frag_fn(
+            raw,
+            pkts_out.as_mut_ptr(),
+            capacity as u16,
+            frag_mtu,
+            pool.as_ptr(),
+            pool.as_ptr(),
+        )
+    };
+
+    // the fragments hold their own references to the original buffer.
+    drop(mbuf);
+    ensure!(res >= 0, DpdkError::from_code(res));
+
+    pkts_out.truncate(res as usize);
+    let mbufs = pkts_out
+        .into_iter()
+        .map(|ptr| unsafe { Mbuf::from_ptr(ptr) })
+        .collect::<Vec<_>>();
+
+    mbufs
+        .into_iter()
+        .map(|mut mbuf| {
+            mbuf.extend(0, l2_len)?;
+            mbuf.write_data_slice(0, &l2_hdr)?;
+            mbuf.parse::<Ethernet>()?.parse::<T>()
+        })
+        .collect::<Result<Vec<_>>>()
+}
+
+/// Returns the `Mempool` assigned to the current executing thread.
+fn thread_mempool() -> Result<NonNull<ffi::rte_mempool>> {
+    let pool = NonNull::new(MEMPOOL.with(|tls| tls.get()))
+        .ok_or_else(|| MempoolError::NotFound(SocketId::current()))?;
+    Ok(pool)
+}
+
+/// Breaks large IPv4 packets into fragments, backed by `rte_ip_frag`.
+///
+/// The fragments share the payload with the original packet through
+/// indirect mbufs, so the payload is not copied.
+///
+/// # Example
+///
+/// ```
+/// let fragmenter = IpFragmenter::new()?;
+/// if IpFragmenter::needs_fragmentation(&ipv4, 1500) {
+///     let fragments = fragmenter.fragment(ipv4, 1500)?;
+/// }
+/// ```
+pub struct IpFragmenter {
+    pool: NonNull<ffi::rte_mempool>,
+}
+
+impl IpFragmenter {
+    /// Creates a new fragmenter. The fragments are allocated from the
+    /// `Mempool` assigned to the current executing thread by the `Runtime`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MempoolError::NotFound` if invoked from a thread not
+    /// managed by the `Runtime`.
+    pub fn new() -> Result<Self> {
+        Ok(IpFragmenter {
+            pool: thread_mempool()?,
+        })
+    }
+
+    /// Returns whether the packet is larger than `mtu`.
+    pub fn needs_fragmentation(pkt: &Ipv4, mtu: u16) -> bool {
+        pkt.total_length() > mtu
+    }
+
+    /// Fragments the packet so each fragment, starting from the IPv4
+    /// header, is no larger than `mtu`. If the packet is not larger than
+    /// `mtu`, it's returned as the only fragment. The original packet is
+    /// consumed, and its buffer is freed when all the fragments are.
+    ///
+    /// The fragments keep the identification of the original packet, and
+    /// have the fragment offset, the more fragments flag and the checksum
+    /// set.
+    ///
+    /// # Errors
+    ///
+    /// Returns `IpFragError::FragmentationNotAllowed` if the packet has the
+    /// don't fragment flag set. Returns `IpFragError::MtuTooSmall` if `mtu`
+    /// can't fit the header and 8 bytes of payload. Returns `DpdkError` if
+    /// the fragmentation fails.
+    pub fn fragment(&self, pkt: Ipv4, mtu: u16) -> Result<Vec<Ipv4>> {
+        if !IpFragmenter::needs_fragmentation(&pkt, mtu) {
+            return Ok(vec![pkt]);
+        }
+
+        ensure!(!pkt.dont_fragment(), IpFragError::FragmentationNotAllowed);
+
+        // the fragment payload must be a multiple of 8 bytes.
+        let l3_len = pkt.header_len();
+        let frag_size = (mtu as usize).saturating_sub(l3_len) & !7;
+        ensure!(frag_size > 0, IpFragError::MtuTooSmall(mtu));
+
+        let payload = pkt.total_length() as usize - l3_len;
+        let capacity = (payload + frag_size - 1) / frag_size;
+
+        let mut fragments = fragment_packet(
+            pkt,
+            self.pool,
+            ffi::rte_ipv4_fragment_packet,
+            (l3_len + frag_size) as u16,
+            capacity,
+        )?;
+
+        // `rte_ip_frag` zeroes the checksums of the fragments.
+        fragments.iter_mut().for_each(Ipv4::compute_checksum);
+        Ok(fragments)
+    }
+}
+
+impl fmt::Debug for IpFragmenter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IpFragmenter")
+            .field(
+                "socket_id",
+                &SocketId(unsafe { self.pool.as_ref().socket_id }),
+            )
+            .finish()
+    }
+}
+
+/// Breaks large IPv6 packets into fragments, backed by `rte_ip_frag`.
+///
+/// The fragments share the payload with the original packet through
+/// indirect mbufs, so the payload is not copied.
+///
+/// # Example
+///
+/// ```
+/// let fragmenter = Ipv6Fragmenter::new()?;
+/// let fragments = fragmenter.fragment(ipv6, 1280)?;
+/// ```
+pub struct Ipv6Fragmenter {
+    pool: NonNull<ffi::rte_mempool>,
+}
+
+impl Ipv6Fragmenter {
+    /// Creates a new fragmenter. The fragments are allocated from the
+    /// `Mempool` assigned to the current executing thread by the `Runtime`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MempoolError::NotFound` if invoked from a thread not
+    /// managed by the `Runtime`.
+    pub fn new() -> Result<Self> {
+        Ok(Ipv6Fragmenter {
+            pool: thread_mempool()?,
+        })
+    }
+
+    /// Fragments the packet so each fragment, starting from the IPv6
+    /// header, is no larger than `mtu`. If the packet is not larger than
+    /// `mtu`, it's returned as the only fragment. The original packet is
+    /// consumed, and its buffer is freed when all the fragments are.
+    ///
+    /// Extension headers are not replicated into the fragments, only the
+    /// IPv6 header is.
+    ///
+    /// # Errors
+    ///
+    /// Returns `IpFragError::MtuTooSmall` if `mtu` can't fit the headers
+    /// and 8 bytes of payload. Returns `DpdkError` if the fragmentation
+    /// fails.
+    pub fn fragment(&self, pkt: Ipv6, mtu: u16) -> Result<Vec<Ipv6>> {
+        let len = pkt.mbuf().pkt_len() - pkt.offset();
+        if len <= mtu as usize {
+            return Ok(vec![pkt]);
+        }
+
+        // the fragment payload must be a multiple of 8 bytes.
+        let frag_size = (mtu as usize).saturating_sub(IPV6_HDR_LEN + IPV6_FRAG_HDR_LEN) & !7;
+        ensure!(frag_size > 0, IpFragError::MtuTooSmall(mtu));
+
+        let capacity = (len - IPV6_HDR_LEN + frag_size - 1) / frag_size;
+
+        fragment_packet(
+            pkt,
+            self.pool,
+            ffi::rte_ipv6_fragment_packet,
+            (frag_size + IPV6_HDR_LEN + IPV6_FRAG_HDR_LEN) as u16,
+            capacity,
+        )
+    }
+}
+
+impl fmt::Debug for Ipv6Fragmenter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ipv6Fragmenter")
+            .field(
+                "socket_id",
+                &SocketId(unsafe { self.pool.as_ref().socket_id }),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpdk::SegmentedPacket;
+    use crate::packets::ip::v6::Fragment;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const PAYLOAD_LEN: usize = 4096;
+    const FRAGMENT_LEN: usize = 1024;
+
+    fn payload(offset: usize, len: usize) -> Vec<u8> {
+        (offset..offset + len).map(|i| i as u8).collect()
+    }
+
+    fn ipv4_fragment(offset: usize, more_fragments: bool) -> Ipv4 {
+        let ethernet = Mbuf::new().unwrap().push::<Ethernet>().unwrap();
+        let mut ipv4 = ethernet.push::<Ipv4>().unwrap();
+        ipv4.set_src(Ipv4Addr::new(10, 0, 0, 1));
+        ipv4.set_dst(Ipv4Addr::new(10, 0, 0, 2));
+        ipv4.set_protocol(ProtocolNumbers::Udp);
+        ipv4.set_identification(42);
+        ipv4.set_fragment_offset((offset / 8) as u16);
+        if more_fragments {
+            ipv4.set_more_fragments();
+        }
+
+        let payload_offset = ipv4.payload_offset();
+        ipv4.mbuf_mut()
+            .extend(payload_offset, FRAGMENT_LEN)
+            .unwrap();
+        ipv4.mbuf_mut()
+            .write_data_slice(payload_offset, &payload(offset, FRAGMENT_LEN))
+            .unwrap();
+        ipv4.reconcile();
+        ipv4
+    }
+
+    fn ipv6_packet(len: usize) -> Ipv6 {
+        let ethernet = Mbuf::new().unwrap().push::<Ethernet>().unwrap();
+        let mut ipv6 = ethernet.push::<Ipv6>().unwrap();
+        ipv6.set_src(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1));
+        ipv6.set_dst(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2));
+        ipv6.set_next_header(ProtocolNumbers::Udp);
+
+        let payload_offset = ipv6.payload_offset();
+        ipv6.mbuf_mut().extend(payload_offset, len).unwrap();
+        ipv6.mbuf_mut()
+            .write_data_slice(payload_offset, &payload(0, len))
+            .unwrap();
+        ipv6.reconcile();
+        ipv6
+    }
+
+    /// Builds an IPv4 packet with the payload spread across segments of
+    /// 1000 bytes.
+    fn large_ipv4_packet(len: usize) -> Ipv4 {
+        const SEGMENT_LEN: usize = 1000;
+
+        let ethernet = Mbuf::new().unwrap().push::<Ethernet>().unwrap();
+        let mut ipv4 = ethernet.push::<Ipv4>().unwrap();
+        ipv4.set_src(Ipv4Addr::new(10, 0, 0, 1));
+        ipv4.set_dst(Ipv4Addr::new(10, 0, 0, 2));
+        ipv4.set_protocol(ProtocolNumbers::Udp);
+        ipv4.set_identification(7);
+
+        let offset = ipv4.offset();
+        let payload_offset = ipv4.payload_offset();
+        ipv4.mbuf_mut().extend(payload_offset, SEGMENT_LEN).unwrap();
+        ipv4.mbuf_mut()
+            .write_data_slice(payload_offset, &payload(0, SEGMENT_LEN))
+            .unwrap();
+
+        let mut packet = SegmentedPacket::new(ipv4.reset());
+        for start in (SEGMENT_LEN..len).step_by(SEGMENT_LEN) {
+            let bytes = payload(start, SEGMENT_LEN.min(len - start));
+            packet
+                .append_segment(Mbuf::from_bytes(&bytes).unwrap())
+                .unwrap();
+        }
+
+        // reconcile only sees the first segment, sets the length directly.
+        let mut mbuf = packet.into_mbuf();
+        let total_length = (20 + len) as u16;
+        mbuf.write_data_slice(offset + 2, &total_length.to_be_bytes())
+            .unwrap();
+
+        let mut ipv4 = mbuf.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap();
+        ipv4.compute_checksum();
+        ipv4
+    }
+
+    /// Collects the payload of a reassembled packet across the segments.
+    fn collect_payload<T: Packet>(packet: &T) -> Vec<u8> {
+        let offset = packet.payload_offset();
+        packet
+            .mbuf()
+            .segments()
+            .enumerate()
+            .flat_map(|(i, seg)| if i == 0 { &seg[offset..] } else { seg })
+            .copied()
+            .collect()
+    }
+
+    #[capsule::test]
+    fn reassemble_four_ipv4_fragments() {
+        let mut defrag = Ipv4Defrag::new(16, 64, u64::MAX, SocketId::ANY).unwrap();
+        let mut death_row = DeathRow::new();
+
+        let offsets = (0..PAYLOAD_LEN).step_by(FRAGMENT_LEN).collect::<Vec<_>>();
+        assert_eq!(4, offsets.len());
+
+        let mut reassembled = None;
+        for (i, &offset) in offsets.iter().enumerate() {
+            let more = i < offsets.len() - 1;
+            reassembled = defrag.process_fragment(ipv4_fragment(offset, more), &mut death_row);
+            assert_eq!(more, reassembled.is_none());
+        }
+
+        let ipv4 = reassembled.unwrap();
+        assert!(!ipv4.more_fragments());
+        assert_eq!(0, ipv4.fragment_offset());
+        assert_eq!(
+            (ipv4.header_len() + PAYLOAD_LEN) as u16,
+            ipv4.total_length()
+        );
+        assert!(ipv4.validate_checksum());
+        assert_eq!(4, ipv4.mbuf().nb_segs());
+        assert_eq!(payload(0, PAYLOAD_LEN), collect_payload(&ipv4));
+
+        death_row.free_all();
+        assert!(death_row.is_empty());
+    }
+
+    #[capsule::test]
+    fn pass_through_unfragmented_ipv4_packet() {
+        let mut defrag = Ipv4Defrag::new(16, 64, u64::MAX, SocketId::ANY).unwrap();
+        let mut death_row = DeathRow::new();
+
+        let ipv4 = ipv4_fragment(0, false);
+        let len = ipv4.mbuf().data_len();
+        let ipv4 = defrag.process_fragment(ipv4, &mut death_row).unwrap();
+        assert_eq!(1, ipv4.mbuf().nb_segs());
+        assert_eq!(len, ipv4.mbuf().data_len());
+    }
+
+    #[capsule::test]
+    fn fragment_and_reassemble_ipv6_packet() {
+        let fragmenter = Ipv6Fragmenter::new().unwrap();
+        let fragments = fragmenter.fragment(ipv6_packet(1800), 600).unwrap();
+        assert_eq!(4, fragments.len());
+
+        for fragment in fragments.iter() {
+            assert_eq!(ProtocolNumbers::Ipv6Frag, fragment.next_header());
+            assert!(fragment.mbuf().pkt_len() - fragment.offset() <= 600);
+            let header = fragment.peek::<Fragment<Ipv6>>().unwrap();
+            assert_eq!(ProtocolNumbers::Udp, header.next_header());
+        }
+
+        let mut defrag = Ipv6Defrag::new(16, 64, u64::MAX, SocketId::ANY).unwrap();
+        let mut death_row = DeathRow::new();
+
+        let mut reassembled = None;
+        let count = fragments.len();
+        for (i, fragment) in fragments.into_iter().enumerate() {
+            reassembled = defrag.process_fragment(fragment, &mut death_row);
+            assert_eq!(i < count - 1, reassembled.is_none());
+        }
+
+        let ipv6 = reassembled.unwrap();
+        assert_eq!(ProtocolNumbers::Udp, ipv6.next_header());
+        assert_eq!(1800, ipv6.payload_length());
+        assert_eq!(payload(0, 1800), collect_payload(&ipv6));
+    }
+
+    #[capsule::test]
+    fn fragment_small_ipv6_packet() {
+        let fragmenter = Ipv6Fragmenter::new().unwrap();
+        let fragments = fragmenter.fragment(ipv6_packet(100), 1280).unwrap();
+        assert_eq!(1, fragments.len());
+        assert_eq!(ProtocolNumbers::Udp, fragments[0].next_header());
+    }
+
+    #[capsule::test]
+    fn fragment_ipv4_packet() {
+        let ipv4 = large_ipv4_packet(5000);
+        assert!(IpFragmenter::needs_fragmentation(&ipv4, 1500));
+
+        let fragmenter = IpFragmenter::new().unwrap();
+        let fragments = fragmenter.fragment(ipv4, 1500).unwrap();
+        assert_eq!(4, fragments.len());
+
+        let last = fragments.len() - 1;
+        for (i, fragment) in fragments.iter().enumerate() {
+            assert!(fragment.total_length() <= 1500);
+            assert_eq!(7, fragment.identification());
+            assert_eq!((i * 1480 / 8) as u16, fragment.fragment_offset());
+            assert_eq!(i < last, fragment.more_fragments());
+            assert!(fragment.validate_checksum());
+        }
+
+        // the fragments reassemble back to the original payload.
+        let mut defrag = Ipv4Defrag::new(16, 64, u64::MAX, SocketId::ANY).unwrap();
+        let mut death_row = DeathRow::new();
+        let reassembled = fragments
+            .into_iter()
+            .filter_map(|fragment| defrag.process_fragment(fragment, &mut death_row))
+            .collect::<Vec<_>>();
+        assert_eq!(1, reassembled.len());
+        assert_eq!(payload(0, 5000), collect_payload(&reassembled[0]));
+    }
+
+    #[capsule::test]
+    fn cannot_fragment_dont_fragment_packet() {
+        let mut ipv4 = large_ipv4_packet(5000);
+        ipv4.set_dont_fragment();
+
+        let fragmenter = IpFragmenter::new().unwrap();
+        assert!(fragmenter.fragment(ipv4, 1500).is_err());
+    }
+
+    #[capsule::test]
+    fn fragment_small_ipv4_packet() {
+        let ipv4 = ipv4_fragment(0, false);
+        assert!(!IpFragmenter::needs_fragmentation(&ipv4, 1500));
+
+        let fragmenter = IpFragmenter::new().unwrap();
+        assert_eq!(1, fragmenter.fragment(ipv4, 1500).unwrap().len());
+    }
+}
@@ -0,0 +1,646 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::{
+    sym_op, tsc_cycles, CryptoDirection, CryptoSession, DpdkError, Mbuf, SocketId,
+    AES_GCM_DIGEST_LEN, AES_GCM_IV_LEN, IV_OFFSET,
+};
+use crate::ffi::{self, ToResult};
+use crate::net::MacAddr;
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::{EtherTypes, Ethernet, Packet};
+use crate::{debug, ensure, info};
+use anyhow::Result;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::ptr::{self, NonNull};
+use thiserror::Error;
+
+/// The maximum number of packets sent to the crypto device at once.
+const MAX_BURST: usize = 32;
+
+/// The length of the AES-128-GCM key.
+const AES_GCM_KEY_LEN: usize = 16;
+
+/// The length of the key material of an ESP AES-GCM SA, which is the key
+/// followed by a 4-byte salt, as defined in RFC 4106.
+const AES_GCM_KEYMAT_LEN: usize = AES_GCM_KEY_LEN + 4;
+
+/// The length of the additional authenticated data of ESP without extended
+/// sequence numbers, which is the SPI and the sequence number.
+const ESP_AAD_LEN: usize = 8;
+
+/// The length of the Ethernet header.
+const ETH_HDR_LEN: usize = 14;
+
+/// The length of the outer header prepended to outbound tunnel packets,
+/// which is an Ethernet header followed by an IPv4 header.
+const TUNNEL_HDR_LEN: usize = ETH_HDR_LEN + 20;
+
+/// The IP protocol number of IPv4 encapsulation.
+const IPPROTO_IPIP: u8 = 4;
+
+/// The IP protocol number of ESP.
+const IPPROTO_ESP: u8 = 50;
+
+/// IPsec errors.
+#[derive(Clone, Copy, Debug, Error)]
+pub(crate) enum IpsecError {
+    /// The key material is not the expected length.
+    #[error("Invalid key material length {0}, expected {1}.")]
+    InvalidKeyLength(usize, usize),
+
+    /// The crypto session does not match the SA.
+    #[error("Crypto session does not match the SA: {0}.")]
+    SessionMismatch(&'static str),
+
+    /// The packet is not an IPv4 packet.
+    #[error("IPsec SA only supports IPv4 packets.")]
+    NotIpv4,
+
+    /// No crypto operation can be allocated for the packet.
+    #[error("Cannot allocate a new crypto operation.")]
+    Exhausted,
+
+    /// The crypto queue pair is full.
+    #[error("Crypto queue pair is full.")]
+    QueueFull,
+
+    /// The crypto device did not complete the operation in time.
+    #[error("Crypto operation timed out.")]
+    Timeout,
+
+    /// The packet failed the IPsec processing, for example the integrity
+    /// check or the replay window check.
+    #[error("IPsec processing failed.")]
+    Failed,
+}
+
+/// The mode of an IPsec SA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpsecMode {
+    /// The ESP header is inserted between the IP header and the payload.
+    Transport,
+    /// The whole IP packet is encapsulated in a new IPv4 header with the
+    /// tunnel end point addresses.
+    Tunnel {
+        /// The source address of the outer header.
+        src: Ipv4Addr,
+        /// The destination address of the outer header.
+        dst: Ipv4Addr,
+    },
+}
+
+/// The direction of an IPsec SA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpsecDirection {
+    /// Decapsulates and decrypts received ESP packets.
+    Inbound,
+    /// Encrypts and encapsulates packets into ESP.
+    Outbound,
+}
+
+/// The cipher algorithm of an IPsec SA.
+///
+/// Only the algorithms a `CryptoSession` can be created with are listed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpsecCipher {
+    /// AES-128 in Galois/Counter mode.
+    AesGcm128,
+}
+
+/// The authentication algorithm of an IPsec SA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpsecAuth {
+    /// The integrity is provided by the AEAD cipher.
+    Aead,
+}
+
+/// The specification of an ESP security association.
+#[derive(Clone, Debug)]
+pub struct SaParams {
+    /// The security parameter index.
+    pub spi: u32,
+    /// The mode of the SA.
+    pub mode: IpsecMode,
+    /// The direction of the SA.
+    pub direction: IpsecDirection,
+    /// The cipher algorithm.
+    pub cipher: IpsecCipher,
+    /// The authentication algorithm.
+    pub auth: IpsecAuth,
+    /// The key material. For AES-GCM, the 16-byte key followed by the
+    /// 4-byte salt.
+    pub key: Vec<u8>,
+    /// The size of the anti-replay window of an inbound SA in packets. 0
+    /// disables the replay check.
+    pub replay_window_size: u32,
+}
+
+/// A snapshot of the state of an IPsec SA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SaInfo {
+    /// The security parameter index.
+    pub spi: u32,
+    /// The mode of the SA.
+    pub mode: IpsecMode,
+    /// The direction of the SA.
+    pub direction: IpsecDirection,
+    /// The size of the anti-replay window in packets.
+    pub replay_window_size: u32,
+    /// The `RTE_IPSEC_SATP_*` type flags of the SA.
+    pub sa_type: u64,
+    /// The number of packets processed successfully.
+    pub processed: u64,
+    /// The number of packets that failed processing. For inbound SAs, this
+    /// includes the packets rejected by the replay window.
+    pub failed: u64,
+}
+
+/// An ESP session that uses a crypto device as a lookaside accelerator.
+///
+/// Packets are prepared by the IPsec library, enqueued to the first queue
+/// pair of the crypto device, polled until they complete, then finalized.
+/// Because the queue pair is shared, no other session on the same crypto
+/// device can have operations in flight while a burst is processed.
+///
+/// In tunnel mode, the Ethernet addresses of the packets are preserved.
+/// Only IPv4 packets and IPv4 tunnels are supported.
+pub struct IpsecSession {
+    ss: ffi::rte_ipsec_session,
+    sa: NonNull<ffi::rte_ipsec_sa>,
+    params: SaParams,
+    crypto: CryptoSession,
+    processed: u64,
+    failed: u64,
+}
+
+impl IpsecSession {
+    /// Creates a new IPsec session on the SA and the crypto session.
+    ///
+    /// The crypto session must be an AES-GCM session created with the key
+    /// of the SA, the same direction, and 8 bytes of additional
+    /// authenticated data.
+    ///
+    /// # Errors
+    ///
+    /// Returns `IpsecError::InvalidKeyLength` if the key material is not
+    /// 20 bytes, or `IpsecError::SessionMismatch` if the crypto session
+    /// does not match the SA. If the SA cannot be initialized, `DpdkError`
+    /// is returned.
+    pub fn new(sa_params: SaParams, crypto_session: CryptoSession) -> Result<Self> {
+        ensure!(
+            sa_params.key.len() == AES_GCM_KEYMAT_LEN,
+            IpsecError::InvalidKeyLength(sa_params.key.len(), AES_GCM_KEYMAT_LEN)
+        );
+
+        let direction = match sa_params.direction {
+            IpsecDirection::Inbound => CryptoDirection::Decrypt,
+            IpsecDirection::Outbound => CryptoDirection::Encrypt,
+        };
+        ensure!(
+            crypto_session.direction() == direction,
+            IpsecError::SessionMismatch("direction")
+        );
+        ensure!(
+            crypto_session.aad_len() == ESP_AAD_LEN,
+            IpsecError::SessionMismatch("aad length")
+        );
+
+        let mut xform = ffi::rte_crypto_sym_xform {
+            type_: ffi::rte_crypto_sym_xform_type::RTE_CRYPTO_SYM_XFORM_AEAD,
+            ..Default::default()
+        };
+        unsafe {
+            let aead = &mut xform.__bindgen_anon_1.aead;
+            aead.op = match direction {
+                CryptoDirection::Encrypt => {
+                    ffi::rte_crypto_aead_operation::RTE_CRYPTO_AEAD_OP_ENCRYPT
+                }
+                CryptoDirection::Decrypt => {
+                    ffi::rte_crypto_aead_operation::RTE_CRYPTO_AEAD_OP_DECRYPT
+                }
+            };
+            aead.algo = ffi::rte_crypto_aead_algorithm::RTE_CRYPTO_AEAD_AES_GCM;
+            aead.key.data = sa_params.key.as_ptr();
+            aead.key.length = AES_GCM_KEY_LEN as u16;
+            aead.iv.offset = IV_OFFSET as u16;
+            aead.iv.length = AES_GCM_IV_LEN as u16;
+            aead.digest_length = AES_GCM_DIGEST_LEN as u16;
+            aead.aad_length = ESP_AAD_LEN as u16;
+        }
+
+        // the salt is copied into the IV as is, so it keeps the byte order
+        // of the key material.
+        let mut salt = [0u8; 4];
+        salt.copy_from_slice(&sa_params.key[AES_GCM_KEY_LEN..]);
+
+        let mut prm = ffi::rte_ipsec_sa_prm {
+            crypto_xform: &mut xform,
+            replay_win_sz: sa_params.replay_window_size,
+            ..Default::default()
+        };
+
+        let ipsec = &mut prm.ipsec_xform;
+        ipsec.spi = sa_params.spi;
+        ipsec.salt = u32::from_ne_bytes(salt);
+        ipsec.proto = ffi::rte_security_ipsec_sa_protocol::RTE_SECURITY_IPSEC_SA_PROTO_ESP;
+        ipsec.direction = match sa_params.direction {
+            IpsecDirection::Inbound => {
+                ffi::rte_security_ipsec_sa_direction::RTE_SECURITY_IPSEC_SA_DIR_INGRESS
+            }
+            IpsecDirection::Outbound => {
+                ffi::rte_security_ipsec_sa_direction::RTE_SECURITY_IPSEC_SA_DIR_EGRESS
+            }
+        };
+        ipsec.tunnel.type_ = ffi::rte_security_ipsec_tunnel_type::RTE_SECURITY_IPSEC_TUNNEL_IPV4;
+
+        // the header template is copied into the SA on init.
+        let header;
+        match sa_params.mode {
+            IpsecMode::Transport => {
+                ipsec.mode = ffi::rte_security_ipsec_sa_mode::RTE_SECURITY_IPSEC_SA_MODE_TRANSPORT;
+                prm.__bindgen_anon_1.trs.proto = IPPROTO_IPIP;
+            }
+            IpsecMode::Tunnel { src, dst } => {
+                ipsec.mode = ffi::rte_security_ipsec_sa_mode::RTE_SECURITY_IPSEC_SA_MODE_TUNNEL;
+                unsafe {
+                    let ipv4 = &mut ipsec.tunnel.__bindgen_anon_1.ipv4;
+                    ipv4.src_ip.s_addr = u32::from(src).to_be();
+                    ipv4.dst_ip.s_addr = u32::from(dst).to_be();
+                    ipv4.ttl = 64;
+                }
+
+                header = tunnel_header(src, dst);
+                prm.__bindgen_anon_1.tun = ffi::rte_ipsec_sa_prm__bindgen_ty_1__bindgen_ty_1 {
+                    hdr_len: TUNNEL_HDR_LEN as u8,
+                    hdr_l3_off: ETH_HDR_LEN as u8,
+                    next_proto: IPPROTO_IPIP,
+                    hdr: header.as_ptr() as *const _,
+                };
+            }
+        }
+
+        let size = unsafe { ffi::rte_ipsec_sa_size(&prm) }.into_result(DpdkError::from_errno)?;
+        let socket_id = crypto_session_socket(&crypto_session);
+        let sa = unsafe {
+            ffi::rte_zmalloc_socket(
+                ptr::null(),
+                size as ffi::size_t,
+                ffi::RTE_CACHE_LINE_SIZE,
+                socket_id.raw(),
+            )
+            .into_result(|_| DpdkError::new())?
+            .cast::<ffi::rte_ipsec_sa>()
+        };
+
+        unsafe {
+            if let Err(err) =
+                ffi::rte_ipsec_sa_init(sa.as_ptr(), &prm, size).into_result(DpdkError::from_errno)
+            {
+                ffi::rte_free(sa.as_ptr() as *mut _);
+                return Err(err);
+            }
+        }
+
+        let ss = ffi::rte_ipsec_session {
+            sa: sa.as_ptr(),
+            type_: ffi::rte_security_session_action_type::RTE_SECURITY_ACTION_TYPE_NONE,
+            __bindgen_anon_1: ffi::rte_ipsec_session__bindgen_ty_1 {
+                crypto: ffi::rte_ipsec_session__bindgen_ty_1__bindgen_ty_1 {
+                    ses: crypto_session.raw().as_ptr(),
+                    dev_id: crypto_session.dev_id(),
+                },
+            },
+            ..Default::default()
+        };
+
+        // from here on, drop frees the SA.
+        let mut session = IpsecSession {
+            ss,
+            sa,
+            params: sa_params,
+            crypto: crypto_session,
+            processed: 0,
+            failed: 0,
+        };
+
+        unsafe {
+            ffi::rte_ipsec_session_prepare(&mut session.ss).into_result(DpdkError::from_errno)?;
+        }
+
+        info!(
+            spi = session.params.spi,
+            mode = ?session.params.mode,
+            direction = ?session.params.direction,
+            "created IPsec session."
+        );
+        Ok(session)
+    }
+
+    /// Returns a snapshot of the state of the SA.
+    pub fn sa_info(&self) -> SaInfo {
+        SaInfo {
+            spi: self.params.spi,
+            mode: self.params.mode,
+            direction: self.params.direction,
+            replay_window_size: self.params.replay_window_size,
+            sa_type: unsafe { ffi::rte_ipsec_sa_type(self.sa.as_ptr()) },
+            processed: self.processed,
+            failed: self.failed,
+        }
+    }
+
+    /// Processes the packets through the SA. Outbound packets are
+    /// encrypted and encapsulated; inbound packets are checked against the
+    /// replay window, decrypted and decapsulated.
+    ///
+    /// The packets processed successfully are returned first, followed by
+    /// the errors of the ones that failed. The packets that failed are
+    /// dropped.
+    pub fn process(&mut self, pkts: Vec<Ethernet>) -> Vec<Result<Ethernet>> {
+        let mut results = Vec::with_capacity(pkts.len());
+        let mut failures = vec![];
+        let mut mbufs = Vec::with_capacity(MAX_BURST);
+        let mut addrs = Vec::with_capacity(MAX_BURST);
+
+        for mut pkt in pkts {
+            if let Err(err) = prepare(&mut pkt) {
+                failures.push(Err(err));
+                continue;
+            }
+
+            let mbuf = pkt.reset().into_ptr();
+            addrs.push((mbuf, unsafe { eth_addrs(mbuf) }));
+            mbufs.push(mbuf);
+
+            if mbufs.len() == MAX_BURST {
+                self.process_burst(&mut mbufs, &addrs, &mut results, &mut failures);
+                addrs.clear();
+            }
+        }
+
+        if !mbufs.is_empty() {
+            self.process_burst(&mut mbufs, &addrs, &mut results, &mut failures);
+        }
+
+        self.processed += results.len() as u64;
+        self.failed += failures.len() as u64;
+        results.extend(failures);
+        results
+    }
+
+    /// Processes a burst of packets through the crypto device. The mbufs
+    /// are drained.
+    fn process_burst(
+        &mut self,
+        mbufs: &mut Vec<*mut ffi::rte_mbuf>,
+        addrs: &[(*mut ffi::rte_mbuf, (MacAddr, MacAddr))],
+        results: &mut Vec<Result<Ethernet>>,
+        failures: &mut Vec<Result<Ethernet>>,
+    ) {
+        let dev_id = self.crypto.dev_id();
+        let pool = self.crypto.op_pool();
+
+        unsafe {
+            let mut cops = mbufs
+                .iter()
+                .map(|_| {
+                    ffi::_rte_crypto_op_alloc(
+                        pool,
+                        ffi::rte_crypto_op_type::RTE_CRYPTO_OP_TYPE_SYMMETRIC,
+                    )
+                })
+                .take_while(|op| !op.is_null())
+                .collect::<Vec<_>>();
+            drop_mbufs(mbufs.drain(cops.len()..), IpsecError::Exhausted, failures);
+
+            // the packets that fail the preparation are moved to the end.
+            let prepared = ffi::_rte_ipsec_pkt_crypto_prepare(
+                &self.ss,
+                mbufs.as_mut_ptr(),
+                cops.as_mut_ptr(),
+                mbufs.len() as u16,
+            ) as usize;
+            drop_mbufs(mbufs.drain(prepared..), IpsecError::Failed, failures);
+            cops.drain(prepared..)
+                .for_each(|op| ffi::_rte_crypto_op_free(op));
+
+            // the operations own the mbufs from here on.
+            mbufs.clear();
+            let enqueued =
+                ffi::_rte_cryptodev_enqueue_burst(dev_id, 0, cops.as_mut_ptr(), cops.len() as u16)
+                    as usize;
+            for op in cops.drain(enqueued..) {
+                drop_mbufs(
+                    std::iter::once((*sym_op(op)).m_src),
+                    IpsecError::QueueFull,
+                    failures,
+                );
+                ffi::_rte_crypto_op_free(op);
+            }
+
+            // the operations are polled for at most a second. on timeout,
+            // the operations and their mbufs are still owned by the device
+            // and are leaked.
+            let deadline = tsc_cycles() + ffi::rte_get_tsc_hz();
+            let mut done = Vec::with_capacity(enqueued);
+            while done.len() < enqueued {
+                let mut ops = [ptr::null_mut(); MAX_BURST];
+                let count = ffi::_rte_cryptodev_dequeue_burst(
+                    dev_id,
+                    0,
+                    ops.as_mut_ptr(),
+                    (enqueued - done.len()) as u16,
+                ) as usize;
+
+                for &op in &ops[..count] {
+                    // marks the failed operations the same way
+                    // `rte_ipsec_pkt_crypto_group` does.
+                    let mbuf = (*sym_op(op)).m_src;
+                    if (*op).__bindgen_anon_1.__bindgen_anon_1.status
+                        != ffi::rte_crypto_op_status::RTE_CRYPTO_OP_STATUS_SUCCESS as u8
+                    {
+                        (*mbuf).ol_flags |= ffi::PKT_RX_SEC_OFFLOAD_FAILED as u64;
+                    }
+                    ffi::_rte_crypto_op_free(op);
+                    done.push(mbuf);
+                }
+
+                if count == 0 && tsc_cycles() >= deadline {
+                    for _ in done.len()..enqueued {
+                        failures.push(Err(IpsecError::Timeout.into()));
+                    }
+                    break;
+                }
+            }
+
+            // the packets that fail the processing are moved to the end.
+            let processed =
+                ffi::_rte_ipsec_pkt_process(&self.ss, done.as_mut_ptr(), done.len() as u16)
+                    as usize;
+            drop_mbufs(done.drain(processed..), IpsecError::Failed, failures);
+
+            for mbuf in done {
+                let (src, dst) = addrs
+                    .iter()
+                    .find(|(ptr, _)| *ptr == mbuf)
+                    .map(|&(_, addrs)| addrs)
+                    .unwrap_or_default();
+
+                match self.finish(Mbuf::from_ptr(mbuf), src, dst) {
+                    Ok(pkt) => results.push(Ok(pkt)),
+                    Err(err) => failures.push(Err(err)),
+                }
+            }
+        }
+    }
+
+    /// Restores the Ethernet header of a processed packet.
+    fn finish(&self, mut mbuf: Mbuf, src: MacAddr, dst: MacAddr) -> Result<Ethernet> {
+        match (self.params.mode, self.params.direction) {
+            (IpsecMode::Transport, _) => mbuf.parse::<Ethernet>(),
+            (IpsecMode::Tunnel { .. }, IpsecDirection::Outbound) => {
+                // the outer header is copied from the template, which
+                // has no addresses and no checksum.
+                let mut ethernet = mbuf.parse::<Ethernet>()?;
+                ethernet.set_src(src);
+                ethernet.set_dst(dst);
+                let mut ipv4 = ethernet.parse::<Ipv4>()?;
+                ipv4.reconcile();
+                Ok(ipv4.deparse())
+            }
+            (IpsecMode::Tunnel { .. }, IpsecDirection::Inbound) => {
+                // both the outer Ethernet and IP headers are removed.
+                mbuf.extend(0, ETH_HDR_LEN)?;
+                let mut ethernet = mbuf.parse::<Ethernet>()?;
+                ethernet.set_src(src);
+                ethernet.set_dst(dst);
+                ethernet.set_ether_type(EtherTypes::Ipv4);
+                Ok(ethernet)
+            }
+        }
+    }
+}
+
+/// Returns the socket of the crypto device of the session.
+fn crypto_session_socket(session: &CryptoSession) -> SocketId {
+    match unsafe { ffi::rte_cryptodev_socket_id(session.dev_id()) } {
+        id if id < 0 => SocketId::ANY,
+        id => SocketId(id),
+    }
+}
+
+/// Sets the header lengths the IPsec library expects on the packet.
+fn prepare(ethernet: &mut Ethernet) -> Result<()> {
+    ensure!(
+        ethernet.ether_type() == EtherTypes::Ipv4,
+        IpsecError::NotIpv4
+    );
+
+    let l2_len = ethernet.header_len();
+    let l3_len = ethernet.peek::<Ipv4>()?.header_len();
+    ethernet.mbuf_mut().raw_mut().__bindgen_anon_6.tx_offload =
+        l2_len as u64 | (l3_len as u64) << 7;
+    Ok(())
+}
+
+/// Returns the source and destination addresses of the Ethernet header of
+/// the mbuf.
+unsafe fn eth_addrs(mbuf: *mut ffi::rte_mbuf) -> (MacAddr, MacAddr) {
+    let data = ((*mbuf).buf_addr as *const u8).add((*mbuf).data_off as usize);
+    let mut dst = [0u8; 6];
+    let mut src = [0u8; 6];
+    ptr::copy_nonoverlapping(data, dst.as_mut_ptr(), 6);
+    ptr::copy_nonoverlapping(data.add(6), src.as_mut_ptr(), 6);
+    (src.into(), dst.into())
+}
+
+/// Frees the mbufs that failed and records the error of each.
+unsafe fn drop_mbufs(
+    mbufs: impl Iterator<Item = *mut ffi::rte_mbuf>,
+    err: IpsecError,
+    failures: &mut Vec<Result<Ethernet>>,
+) {
+    for mbuf in mbufs {
+        let _ = Mbuf::from_ptr(mbuf);
+        failures.push(Err(err.into()));
+    }
+}
+
+/// Returns the outer header template of an outbound tunnel. The Ethernet
+/// addresses, the total length and the checksum are filled in per packet.
+fn tunnel_header(src: Ipv4Addr, dst: Ipv4Addr) -> [u8; TUNNEL_HDR_LEN] {
+    let mut header = [0u8; TUNNEL_HDR_LEN];
+    header[12] = 0x08;
+
+    let ip = &mut header[ETH_HDR_LEN..];
+    // version 4, header length of 5 words.
+    ip[0] = 0x45;
+    ip[8] = 64;
+    ip[9] = IPPROTO_ESP;
+    ip[12..16].copy_from_slice(&src.octets());
+    ip[16..20].copy_from_slice(&dst.octets());
+    header
+}
+
+impl fmt::Debug for IpsecSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IpsecSession")
+            .field("spi", &self.params.spi)
+            .field("mode", &self.params.mode)
+            .field("direction", &self.params.direction)
+            .field("crypto", &self.crypto)
+            .finish()
+    }
+}
+
+impl Drop for IpsecSession {
+    fn drop(&mut self) {
+        debug!(spi = self.params.spi, "freeing IPsec session.");
+        unsafe {
+            ffi::rte_ipsec_sa_fini(self.sa.as_ptr());
+            ffi::rte_free(self.sa.as_ptr() as *mut _);
+        }
+    }
+}
+
+unsafe impl Send for IpsecSession {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV6_TCP_PACKET;
+
+    #[test]
+    fn tunnel_header_template() {
+        let header = tunnel_header(Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2));
+
+        assert_eq!([0x08, 0x00], header[12..14]);
+        assert_eq!(0x45, header[14]);
+        assert_eq!(IPPROTO_ESP, header[23]);
+        assert_eq!([10, 0, 0, 1], header[26..30]);
+        assert_eq!([10, 0, 0, 2], header[30..34]);
+    }
+
+    #[capsule::test]
+    fn prepare_rejects_ipv6() {
+        let packet = Mbuf::from_bytes(&IPV6_TCP_PACKET).unwrap();
+        let mut ethernet = packet.parse::<Ethernet>().unwrap();
+        assert!(prepare(&mut ethernet).is_err());
+    }
+}
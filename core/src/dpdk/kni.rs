@@ -16,17 +16,19 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
-use super::{Mbuf, PortId};
+use super::{Mbuf, MempoolError, PortId, SocketId, MEMPOOL};
 use crate::dpdk::DpdkError;
 use crate::ffi::{self, AsStr, ToResult};
 
 #[cfg(feature = "metrics")]
 use crate::metrics::{labels, Counter, SINK};
 use crate::net::MacAddr;
-use crate::{debug, error, warn};
+use crate::packets::{Ethernet, Packet};
+use crate::{debug, error, info, warn};
 use anyhow::Result;
 use futures::{future, Future, StreamExt};
 use std::cmp;
+use std::fmt;
 use std::mem;
 use std::os::raw;
 use std::ptr::{self, NonNull};
@@ -394,6 +396,136 @@ impl<'a> KniBuilder<'a> {
     }
 }
 
+/// A standalone kernel NIC interface for a port.
+///
+/// Unlike the interface the runtime creates for a port with `kni`
+/// enabled, the caller drives both directions directly. The DPDK
+/// implementation is single-threaded, so only one core should send and
+/// one core should receive at a time. `handle_requests` must be called
+/// periodically, typically from the master lcore, to answer the link
+/// change requests from the kernel. The interface is released when
+/// dropped.
+///
+/// # Example
+///
+/// ```
+/// let kni = KernelNic::create(0, "vEth0")?;
+/// let mut burst = vec![];
+/// kni.receive_from_kernel(&mut burst)?;
+/// kni.handle_requests();
+/// ```
+pub struct KernelNic {
+    name: String,
+    kni: Kni,
+}
+
+impl KernelNic {
+    /// Creates a kernel interface named `name` for the port, with the
+    /// MAC address of the port. The packets from the kernel are allocated
+    /// from the `Mempool` assigned to the current executing thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MempoolError::NotFound` if invoked from a thread not
+    /// managed by the `Runtime`. If the KNI module is not loaded or the
+    /// interface cannot be allocated, `DpdkError` is returned.
+    pub fn create(port_id: u16, name: &str) -> Result<KernelNic> {
+        let mut mempool = NonNull::new(MEMPOOL.with(|tls| tls.get()))
+            .ok_or_else(|| MempoolError::NotFound(SocketId::current()))?;
+
+        // the subsystem is only initialized once, repeated calls are no-op.
+        kni_init(1)?;
+
+        let mut builder = KniBuilder::new(unsafe { mempool.as_mut() });
+        builder.name(name).mac_addr(super::eth_macaddr_get(port_id));
+        builder.conf.group_id = port_id;
+        builder.ops.port_id = port_id;
+        let kni = builder.finish()?;
+
+        info!(port_id, "created kernel interface {}.", name);
+        Ok(KernelNic {
+            name: name.to_owned(),
+            kni,
+        })
+    }
+
+    /// Returns the name of the kernel interface.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Sends the packets to the kernel. Returns the number of packets
+    /// sent. The packets the interface has no room for are dropped.
+    pub fn send_to_kernel(&self, pkts: Vec<Ethernet>) -> usize {
+        let mut ptrs = pkts
+            .into_iter()
+            .map(|pkt| pkt.reset().into_ptr())
+            .collect::<Vec<_>>();
+
+        let sent = unsafe {
+            ffi::rte_kni_tx_burst(
+                self.kni.raw.as_ptr(),
+                ptrs.as_mut_ptr(),
+                ptrs.len() as raw::c_uint,
+            ) as usize
+        };
+
+        super::mbuf_free_bulk(ptrs.split_off(sent));
+        sent
+    }
+
+    /// Receives a burst of packets from the kernel, up to a maximum of
+    /// **32** packets, and appends them to `burst`. Returns the number of
+    /// packets received. Packets that are not valid Ethernet frames are
+    /// dropped.
+    pub fn receive_from_kernel(&self, burst: &mut Vec<Ethernet>) -> usize {
+        const RX_BURST_MAX: usize = 32;
+        let mut ptrs = Vec::with_capacity(RX_BURST_MAX);
+
+        let len = unsafe {
+            ffi::rte_kni_rx_burst(
+                self.kni.raw.as_ptr(),
+                ptrs.as_mut_ptr(),
+                RX_BURST_MAX as raw::c_uint,
+            )
+        };
+
+        let before = burst.len();
+        unsafe {
+            ptrs.set_len(len as usize);
+            burst.extend(
+                ptrs.into_iter()
+                    .filter_map(|ptr| Mbuf::from_ptr(ptr).parse::<Ethernet>().ok()),
+            );
+        }
+
+        burst.len() - before
+    }
+
+    /// Handles the pending link change requests from the kernel.
+    pub fn handle_requests(&self) {
+        unsafe {
+            if let Err(err) =
+                ffi::rte_kni_handle_request(self.kni.raw.as_ptr()).into_result(|_| DpdkError::new())
+            {
+                warn!(message = "failed to handle change link requests.", ?err);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for KernelNic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KernelNic")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+// the interface is single-threaded per direction, but it can be moved to
+// the core that drives it.
+unsafe impl Send for KernelNic {}
+
 /// Initializes and preallocates the KNI subsystem.
 pub(crate) fn kni_init(max: usize) -> Result<()> {
     unsafe {
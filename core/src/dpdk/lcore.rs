@@ -0,0 +1,147 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{CoreId, DpdkError, SocketId};
+use crate::ffi::{self, ToResult};
+use crate::{ensure, error};
+use anyhow::Result;
+use std::os::raw;
+use std::panic::{self, AssertUnwindSafe};
+use thiserror::Error;
+
+/// Error indicating the function launched on the lcore failed.
+#[derive(Debug, Error)]
+pub(crate) enum LcoreError {
+    #[error("Function launched on {0:?} panicked.")]
+    Panicked(CoreId),
+}
+
+type LcoreFn = Box<dyn FnOnce() + Send + 'static>;
+
+/// Launches functions on the EAL worker lcores.
+///
+/// The runtime manages its own core threads and initializes the EAL with
+/// only the master lcore. Worker lcores are only available if additional
+/// lcores are given to the EAL through `dpdk_args`.
+///
+/// # Example
+///
+/// ```
+/// for core_id in LcoreManager::available_lcores() {
+///     let handle = LcoreManager::launch(core_id, move || {
+///         println!("running on {:?}", core_id);
+///     })?;
+///     handle.join()?;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LcoreManager;
+
+impl LcoreManager {
+    /// Launches `f` on the worker lcore.
+    ///
+    /// # Errors
+    ///
+    /// If the lcore is not a worker lcore, or it is still running a
+    /// previously launched function that has not been joined, `DpdkError`
+    /// is returned.
+    pub fn launch<F>(core_id: CoreId, f: F) -> Result<LcoreHandle>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        // the trait object is boxed again to get a thin pointer that can
+        // go through the C callback.
+        let arg = Box::into_raw(Box::new(Box::new(f) as LcoreFn)) as *mut raw::c_void;
+
+        let res = unsafe {
+            ffi::rte_eal_remote_launch(Some(lcore_main), arg, core_id.raw() as raw::c_uint)
+                .into_result(DpdkError::from_errno)
+        };
+
+        if let Err(err) = res {
+            // the function is not launched, reclaims it to avoid the leak.
+            unsafe {
+                drop(Box::from_raw(arg as *mut LcoreFn));
+            }
+            return Err(err);
+        }
+
+        Ok(LcoreHandle { core_id })
+    }
+
+    /// Returns all the worker lcores enabled in the EAL, not including the
+    /// master lcore.
+    pub fn available_lcores() -> Vec<CoreId> {
+        let mut lcores = vec![];
+        unsafe {
+            let mut i = ffi::rte_get_next_lcore(u32::max_value(), 1, 0);
+            while i < ffi::RTE_MAX_LCORE {
+                lcores.push(CoreId::new(i as usize));
+                i = ffi::rte_get_next_lcore(i, 1, 0);
+            }
+        }
+        lcores
+    }
+
+    /// Returns the ID of the socket the lcore is on.
+    pub fn socket_of(core_id: CoreId) -> SocketId {
+        unsafe { SocketId(ffi::rte_lcore_to_socket_id(core_id.raw() as raw::c_uint) as raw::c_int) }
+    }
+}
+
+/// The entry point for the launched functions. Returns `-1` if the
+/// function panicked.
+extern "C" fn lcore_main(arg: *mut raw::c_void) -> raw::c_int {
+    let f = unsafe { Box::from_raw(arg as *mut LcoreFn) };
+
+    // must not unwind across the FFI boundary.
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(()) => 0,
+        Err(_) => {
+            error!("function launched on lcore panicked.");
+            -1
+        }
+    }
+}
+
+/// A handle to a function launched on a worker lcore.
+///
+/// The handle must be joined before another function can be launched on
+/// the same lcore.
+#[derive(Debug)]
+pub struct LcoreHandle {
+    core_id: CoreId,
+}
+
+impl LcoreHandle {
+    /// Returns the lcore the function is running on.
+    pub fn core_id(&self) -> CoreId {
+        self.core_id
+    }
+
+    /// Waits for the function to finish.
+    ///
+    /// # Errors
+    ///
+    /// If the function panicked, `LcoreError::Panicked` is returned.
+    pub fn join(self) -> Result<()> {
+        let res = unsafe { ffi::rte_eal_wait_lcore(self.core_id.raw() as raw::c_uint) };
+        ensure!(res == 0, LcoreError::Panicked(self.core_id));
+        Ok(())
+    }
+}
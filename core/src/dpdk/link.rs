@@ -0,0 +1,332 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{CoreId, DpdkError, LcoreHandle, LcoreManager};
+use crate::ffi::{self, ToResult};
+use crate::{debug, info};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The negotiated speed of a link.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LinkSpeed {
+    /// No speed, the link is down.
+    None,
+    /// 10 Mbps.
+    Speed10M,
+    /// 100 Mbps.
+    Speed100M,
+    /// 1 Gbps.
+    Speed1G,
+    /// 10 Gbps.
+    Speed10G,
+    /// 25 Gbps.
+    Speed25G,
+    /// 40 Gbps.
+    Speed40G,
+    /// 100 Gbps.
+    Speed100G,
+    /// Any other speed, in Mbps.
+    Other(u32),
+}
+
+impl LinkSpeed {
+    /// Returns the speed in Mbps.
+    pub fn mbps(self) -> u32 {
+        match self {
+            LinkSpeed::None => ffi::ETH_SPEED_NUM_NONE,
+            LinkSpeed::Speed10M => ffi::ETH_SPEED_NUM_10M,
+            LinkSpeed::Speed100M => ffi::ETH_SPEED_NUM_100M,
+            LinkSpeed::Speed1G => ffi::ETH_SPEED_NUM_1G,
+            LinkSpeed::Speed10G => ffi::ETH_SPEED_NUM_10G,
+            LinkSpeed::Speed25G => ffi::ETH_SPEED_NUM_25G,
+            LinkSpeed::Speed40G => ffi::ETH_SPEED_NUM_40G,
+            LinkSpeed::Speed100G => ffi::ETH_SPEED_NUM_100G,
+            LinkSpeed::Other(mbps) => mbps,
+        }
+    }
+}
+
+impl From<u32> for LinkSpeed {
+    fn from(mbps: u32) -> Self {
+        match mbps {
+            ffi::ETH_SPEED_NUM_NONE => LinkSpeed::None,
+            ffi::ETH_SPEED_NUM_10M => LinkSpeed::Speed10M,
+            ffi::ETH_SPEED_NUM_100M => LinkSpeed::Speed100M,
+            ffi::ETH_SPEED_NUM_1G => LinkSpeed::Speed1G,
+            ffi::ETH_SPEED_NUM_10G => LinkSpeed::Speed10G,
+            ffi::ETH_SPEED_NUM_25G => LinkSpeed::Speed25G,
+            ffi::ETH_SPEED_NUM_40G => LinkSpeed::Speed40G,
+            ffi::ETH_SPEED_NUM_100G => LinkSpeed::Speed100G,
+            _ => LinkSpeed::Other(mbps),
+        }
+    }
+}
+
+impl fmt::Display for LinkSpeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mbps() {
+            0 => write!(f, "none"),
+            mbps if mbps % 1000 == 0 => write!(f, "{}Gbps", mbps / 1000),
+            mbps => write!(f, "{}Mbps", mbps),
+        }
+    }
+}
+
+/// The duplex mode of a link.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Duplex {
+    /// Half-duplex.
+    Half,
+    /// Full-duplex.
+    Full,
+}
+
+/// The status of a port's link.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LinkStatus {
+    /// The negotiated speed.
+    pub speed: LinkSpeed,
+    /// The duplex mode.
+    pub duplex: Duplex,
+    /// Whether the speed was auto-negotiated.
+    pub autoneg: bool,
+    /// Whether the link is up.
+    pub up: bool,
+}
+
+impl LinkStatus {
+    /// Retrieves the link status of the port without waiting.
+    ///
+    /// # Errors
+    ///
+    /// If the port id is invalid, `DpdkError` is returned.
+    pub fn get(port_id: u16) -> Result<Self> {
+        let mut raw = ffi::rte_eth_link::default();
+        unsafe {
+            ffi::rte_eth_link_get_nowait(port_id, &mut raw).into_result(DpdkError::from_errno)?;
+        }
+        Ok(raw.into())
+    }
+
+    /// Waits for the link of the port to come up.
+    ///
+    /// `rte_eth_link_get` blocks for a driver defined duration that can't
+    /// be shortened. Instead, the status is polled every 10 milliseconds so
+    /// the wait does not exceed `timeout_ms`.
+    ///
+    /// # Errors
+    ///
+    /// If the link is still down after `timeout_ms` milliseconds, an error
+    /// is returned. If the port id is invalid, `DpdkError` is returned.
+    pub fn wait_up(port_id: u16, timeout_ms: u32) -> Result<Self> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+
+        loop {
+            let status = LinkStatus::get(port_id)?;
+            if status.up {
+                return Ok(status);
+            }
+
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "port {} link is still down after {}ms.",
+                    port_id,
+                    timeout_ms
+                ));
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl From<ffi::rte_eth_link> for LinkStatus {
+    fn from(raw: ffi::rte_eth_link) -> Self {
+        LinkStatus {
+            speed: raw.link_speed.into(),
+            duplex: if raw.link_duplex() as u32 == ffi::ETH_LINK_FULL_DUPLEX {
+                Duplex::Full
+            } else {
+                Duplex::Half
+            },
+            autoneg: raw.link_autoneg() as u32 == ffi::ETH_LINK_AUTONEG,
+            up: raw.link_status() as u32 == ffi::ETH_LINK_UP,
+        }
+    }
+}
+
+/// Monitors the link status of a set of ports from a worker lcore.
+///
+/// The lcore polls the ports at a fixed interval, and invokes the callback
+/// with the port id and the new status whenever the status of a port
+/// changes.
+///
+/// # Example
+///
+/// ```
+/// let mut monitor = LinkMonitor::new(Duration::from_millis(100));
+/// monitor.register(0).register(1);
+/// let handle = monitor.start(core_id, |port_id, status| {
+///     println!("port {} is up: {}.", port_id, status.up);
+/// })?;
+/// ```
+#[derive(Debug)]
+pub struct LinkMonitor {
+    ports: Vec<u16>,
+    interval: Duration,
+}
+
+impl LinkMonitor {
+    /// Creates a new monitor that polls the ports every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        LinkMonitor {
+            ports: vec![],
+            interval,
+        }
+    }
+
+    /// Registers a port to monitor.
+    pub fn register(&mut self, port_id: u16) -> &mut Self {
+        if !self.ports.contains(&port_id) {
+            self.ports.push(port_id);
+        }
+        self
+    }
+
+    /// Returns the registered ports.
+    pub fn ports(&self) -> &[u16] {
+        &self.ports
+    }
+
+    /// Starts polling the registered ports on the worker lcore.
+    ///
+    /// The initial status of each port is retrieved before the lcore is
+    /// launched, and the callback is only invoked on changes.
+    ///
+    /// # Errors
+    ///
+    /// If any of the port ids is invalid, or the lcore can't be launched,
+    /// `DpdkError` is returned.
+    pub fn start<F>(&self, core_id: CoreId, callback: F) -> Result<LinkMonitorHandle>
+    where
+        F: FnMut(u16, LinkStatus) + Send + 'static,
+    {
+        let mut statuses = self
+            .ports
+            .iter()
+            .map(|&port_id| LinkStatus::get(port_id).map(|status| (port_id, status)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+        let interval = self.interval;
+        let mut callback = callback;
+
+        let handle = LcoreManager::launch(core_id, move || {
+            while flag.load(Ordering::Relaxed) {
+                for (port_id, last) in statuses.iter_mut() {
+                    if let Ok(status) = LinkStatus::get(*port_id) {
+                        if status != *last {
+                            info!(
+                                port = *port_id,
+                                up = status.up,
+                                speed = %status.speed,
+                                "link status changed."
+                            );
+                            *last = status;
+                            callback(*port_id, status);
+                        }
+                    }
+                }
+
+                thread::sleep(interval);
+            }
+        })?;
+
+        debug!(?core_id, "started link monitor.");
+        Ok(LinkMonitorHandle {
+            running,
+            handle: Some(handle),
+        })
+    }
+}
+
+/// A handle to a running `LinkMonitor`. The monitor is stopped when the
+/// handle is dropped.
+#[derive(Debug)]
+pub struct LinkMonitorHandle {
+    running: Arc<AtomicBool>,
+    handle: Option<LcoreHandle>,
+}
+
+impl LinkMonitorHandle {
+    /// Stops the monitor and waits for the lcore to finish.
+    ///
+    /// # Errors
+    ///
+    /// If the callback panicked, `LcoreError::Panicked` is returned.
+    pub fn stop(mut self) -> Result<()> {
+        self.running.store(false, Ordering::Relaxed);
+        match self.handle.take() {
+            Some(handle) => handle.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for LinkMonitorHandle {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+            debug!("stopped link monitor.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_link_speed() {
+        assert_eq!(LinkSpeed::None, 0.into());
+        assert_eq!(LinkSpeed::Speed10G, 10_000.into());
+        assert_eq!(LinkSpeed::Other(2_500), 2_500.into());
+        assert_eq!(100_000, LinkSpeed::Speed100G.mbps());
+        assert_eq!("25Gbps", LinkSpeed::Speed25G.to_string());
+        assert_eq!("100Mbps", LinkSpeed::Speed100M.to_string());
+    }
+
+    #[test]
+    fn register_ports_once() {
+        let mut monitor = LinkMonitor::new(Duration::from_millis(100));
+        monitor.register(0).register(1).register(0);
+        assert_eq!(&[0, 1], monitor.ports());
+    }
+
+    #[capsule::test]
+    fn get_invalid_port_link_status() {
+        assert!(LinkStatus::get(u16::max_value()).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, LogLevel};
+use crate::ffi::{self, ToCString, ToResult};
+use crate::{ensure, info};
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use std::ffi::CString;
+use std::os::raw;
+use thiserror::Error;
+
+/// Logger errors.
+#[derive(Debug, Error)]
+pub(crate) enum LoggerError {
+    /// A logger is already installed in the `log` crate.
+    #[error("A logger is already installed.")]
+    AlreadyInstalled,
+}
+
+impl From<log::Level> for LogLevel {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => LogLevel::Error,
+            log::Level::Warn => LogLevel::Warning,
+            log::Level::Info => LogLevel::Info,
+            // `libdpdk` has no level below debug.
+            log::Level::Debug | log::Level::Trace => LogLevel::Debug,
+        }
+    }
+}
+
+static LOGGER: OnceCell<DpdkLogger> = OnceCell::new();
+
+/// A `log` crate logger that writes to the `libdpdk` log.
+///
+/// Records are logged with `rte_log` under a registered log type, and are
+/// prefixed with the Rust module path of the call site. The log type's
+/// level, set with `--log-level` or `rte_log_set_level`, filters the
+/// records.
+///
+/// The framework logs through `tracing`. When no `tracing` subscriber is
+/// installed, its events are forwarded to the `log` crate, so they end up
+/// in the `libdpdk` log as well once the logger is installed.
+///
+/// # Example
+///
+/// ```
+/// DpdkLogger::install("user.myapp")?;
+/// log::warn!("routed through rte_log.");
+/// ```
+#[derive(Debug)]
+pub struct DpdkLogger {
+    log_type: u32,
+}
+
+impl DpdkLogger {
+    /// Registers the log type `name` with `rte_log_register`, and installs
+    /// the logger for the `log` crate. Registering a name already
+    /// registered returns the existing log type.
+    ///
+    /// Must be called after the EAL is initialized.
+    ///
+    /// # Errors
+    ///
+    /// If the log type cannot be registered, `DpdkError` is returned. If a
+    /// logger is already installed, `LoggerError::AlreadyInstalled` is
+    /// returned.
+    pub fn install(name: &str) -> Result<()> {
+        ensure!(LOGGER.get().is_none(), LoggerError::AlreadyInstalled);
+
+        let log_type = unsafe {
+            ffi::rte_log_register(name.into_cstring().as_ptr())
+                .into_result(DpdkError::from_errno)?
+        };
+
+        let logger = LOGGER.get_or_init(|| DpdkLogger { log_type });
+        log::set_logger(logger).map_err(|_| LoggerError::AlreadyInstalled)?;
+        log::set_max_level(log::LevelFilter::Trace);
+
+        info!("installed dpdk logger {} with log type {}.", name, log_type);
+        Ok(())
+    }
+
+    /// Returns the registered log type.
+    pub fn log_type(&self) -> u32 {
+        self.log_type
+    }
+}
+
+impl log::Log for DpdkLogger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        let level = LogLevel::from(metadata.level()) as raw::c_int;
+        unsafe { ffi::rte_log_get_level(self.log_type) >= level }
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let unit = record.module_path().unwrap_or_else(|| record.target());
+        // interior nul bytes would truncate the message, so they are
+        // dropped instead.
+        let unit = CString::new(unit.replace('\0', "")).unwrap_or_default();
+        let message = CString::new(record.args().to_string().replace('\0', "")).unwrap_or_default();
+
+        unsafe {
+            ffi::rte_log(
+                LogLevel::from(record.level()) as u32,
+                self.log_type,
+                b"%s: %s\n\0".as_ptr() as *const raw::c_char,
+                unit.as_ptr(),
+                message.as_ptr(),
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_log_levels() {
+        assert_eq!(LogLevel::Error, log::Level::Error.into());
+        assert_eq!(LogLevel::Warning, log::Level::Warn.into());
+        assert_eq!(LogLevel::Info, log::Level::Info.into());
+        assert_eq!(LogLevel::Debug, log::Level::Debug.into());
+        assert_eq!(LogLevel::Debug, log::Level::Trace.into());
+    }
+
+    #[capsule::test]
+    fn install_dpdk_logger() {
+        DpdkLogger::install("user.capsule.test").unwrap();
+        assert!(LOGGER.get().unwrap().log_type() >= ffi::RTE_LOGTYPE_USER1);
+        assert!(DpdkLogger::install("user.capsule.test").is_err());
+
+        log::info!("logged through rte_log.");
+    }
+}
@@ -0,0 +1,274 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{Allocate, DpdkError, NeedsSocket, SocketId};
+use crate::ffi::{self, ToCString, ToResult};
+use crate::net::{Cidr, Ipv4Cidr};
+use crate::{debug, ensure};
+use anyhow::Result;
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::os::raw;
+use std::ptr::NonNull;
+use thiserror::Error;
+
+/// The number of tbl8 groups, each holds the routes for one /24 prefix
+/// with routes longer than /24.
+const NUMBER_TBL8S: u32 = 256;
+
+/// The largest next hop value `rte_lpm` can store.
+const MAX_NEXT_HOP: u32 = ffi::RTE_LPM_LOOKUP_SUCCESS - 1;
+
+/// LPM errors.
+#[derive(Debug, Error)]
+pub(crate) enum LpmError {
+    /// The next hop is larger than 24 bits.
+    #[error("Next hop {0} is larger than the maximum {}.", MAX_NEXT_HOP)]
+    InvalidNextHop(u32),
+
+    /// The lookup results buffer is shorter than the addresses.
+    #[error("Results buffer holds {0} values, expected at least {1}.")]
+    ResultsTooShort(usize, usize),
+}
+
+/// A longest prefix match table for IPv4 routes.
+///
+/// Each route maps a prefix to a 24-bit next hop value, typically an index
+/// into an user defined table of next hops. Lookups are lock-free and can
+/// happen concurrently from multiple cores, while changes to the routes
+/// require exclusive access.
+///
+/// # Example
+///
+/// ```
+/// let mut lpm = LpmTable::new("routes", 1024, SocketId::current())?;
+/// lpm.add_route("10.0.0.0/8".parse()?, 1)?;
+/// assert_eq!(Some(1), lpm.lookup(Ipv4Addr::new(10, 1, 2, 3)));
+/// ```
+pub struct LpmTable {
+    raw: NonNull<ffi::rte_lpm>,
+    name: String,
+    socket_id: SocketId,
+}
+
+impl LpmTable {
+    /// Creates a new table that holds up to `max_rules` routes.
+    ///
+    /// # Errors
+    ///
+    /// If the name is already used or the allocation fails, `DpdkError` is
+    /// returned.
+    pub fn new(name: &str, max_rules: u32, socket_id: SocketId) -> Result<Self> {
+        let config = ffi::rte_lpm_config {
+            max_rules,
+            number_tbl8s: NUMBER_TBL8S,
+            flags: 0,
+        };
+
+        let raw = unsafe {
+            ffi::rte_lpm_create(name.into_cstring().as_ptr(), socket_id.raw(), &config)
+                .into_result(|_| DpdkError::new())?
+        };
+
+        debug!("created LPM table {}.", name);
+        Ok(LpmTable {
+            raw,
+            name: name.to_owned(),
+            socket_id,
+        })
+    }
+
+    /// Returns the name of the table.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Adds a route. If there's already a route for the prefix, its next
+    /// hop is updated.
+    ///
+    /// # Errors
+    ///
+    /// If the next hop does not fit in 24 bits, an error is returned. If
+    /// the table is full, `DpdkError` is returned.
+    pub fn add_route(&mut self, prefix: Ipv4Cidr, next_hop: u32) -> Result<()> {
+        ensure!(next_hop <= MAX_NEXT_HOP, LpmError::InvalidNextHop(next_hop));
+
+        unsafe {
+            ffi::rte_lpm_add(
+                self.raw.as_ptr(),
+                prefix.network().into(),
+                prefix.length() as u8,
+                next_hop,
+            )
+            .into_result(DpdkError::from_errno)
+            .map(|_| ())
+        }
+    }
+
+    /// Deletes a route.
+    ///
+    /// # Errors
+    ///
+    /// If the route does not exist, `DpdkError` is returned.
+    pub fn delete_route(&mut self, prefix: Ipv4Cidr) -> Result<()> {
+        unsafe {
+            ffi::rte_lpm_delete(
+                self.raw.as_ptr(),
+                prefix.network().into(),
+                prefix.length() as u8,
+            )
+            .into_result(DpdkError::from_errno)
+            .map(|_| ())
+        }
+    }
+
+    /// Deletes all the routes.
+    pub fn clear(&mut self) {
+        unsafe {
+            ffi::rte_lpm_delete_all(self.raw.as_ptr());
+        }
+    }
+
+    /// Returns the next hop of the longest prefix matching the address.
+    pub fn lookup(&self, dst: Ipv4Addr) -> Option<u32> {
+        let mut next_hop = 0;
+        let res = unsafe { ffi::_rte_lpm_lookup(self.raw.as_ptr(), dst.into(), &mut next_hop) };
+
+        if res == 0 {
+            Some(next_hop)
+        } else {
+            None
+        }
+    }
+
+    /// Looks up multiple addresses at once. The next hop of `ips[i]` is
+    /// written to `results[i]`. Returns the number of addresses that
+    /// matched a route.
+    ///
+    /// # Errors
+    ///
+    /// If `results` is shorter than `ips`, an error is returned.
+    pub fn lookup_bulk(&self, ips: &[Ipv4Addr], results: &mut [Option<u32>]) -> Result<usize> {
+        ensure!(
+            results.len() >= ips.len(),
+            LpmError::ResultsTooShort(results.len(), ips.len())
+        );
+
+        let ips = ips.iter().map(|&ip| ip.into()).collect::<Vec<u32>>();
+        let mut next_hops = vec![0u32; ips.len()];
+
+        unsafe {
+            ffi::_rte_lpm_lookup_bulk(
+                self.raw.as_ptr(),
+                ips.as_ptr(),
+                next_hops.as_mut_ptr(),
+                ips.len() as raw::c_uint,
+            )
+            .into_result(DpdkError::from_errno)?;
+        }
+
+        let mut found = 0;
+        for (result, next_hop) in results.iter_mut().zip(next_hops) {
+            *result = if next_hop & ffi::RTE_LPM_LOOKUP_SUCCESS != 0 {
+                found += 1;
+                Some(next_hop & MAX_NEXT_HOP)
+            } else {
+                None
+            };
+        }
+
+        Ok(found)
+    }
+}
+
+impl NeedsSocket for LpmTable {
+    fn socket_id(&self) -> SocketId {
+        self.socket_id
+    }
+}
+
+impl Allocate for LpmTable {
+    /// The name and the maximum number of routes of the table.
+    type Params = (String, u32);
+
+    fn allocate(socket_id: SocketId, (name, max_rules): Self::Params) -> Result<Self> {
+        LpmTable::new(&name, max_rules, socket_id)
+    }
+}
+
+impl fmt::Debug for LpmTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LpmTable")
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+impl Drop for LpmTable {
+    fn drop(&mut self) {
+        debug!("freeing LPM table {}.", self.name);
+
+        unsafe {
+            ffi::rte_lpm_free(self.raw.as_ptr());
+        }
+    }
+}
+
+/// Lookups only read the table, so the table can be shared across cores.
+unsafe impl Send for LpmTable {}
+unsafe impl Sync for LpmTable {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn lpm_longest_prefix_match() {
+        let mut lpm = LpmTable::new("lpm_match", 16, SocketId::ANY).unwrap();
+        lpm.add_route("10.0.0.0/8".parse().unwrap(), 1).unwrap();
+        lpm.add_route("10.1.0.0/16".parse().unwrap(), 2).unwrap();
+        lpm.add_route("10.1.1.128/25".parse().unwrap(), 3).unwrap();
+
+        assert_eq!(Some(1), lpm.lookup(Ipv4Addr::new(10, 2, 0, 1)));
+        assert_eq!(Some(2), lpm.lookup(Ipv4Addr::new(10, 1, 0, 1)));
+        assert_eq!(Some(3), lpm.lookup(Ipv4Addr::new(10, 1, 1, 200)));
+        assert_eq!(None, lpm.lookup(Ipv4Addr::new(192, 168, 0, 1)));
+
+        lpm.delete_route("10.1.0.0/16".parse().unwrap()).unwrap();
+        assert_eq!(Some(1), lpm.lookup(Ipv4Addr::new(10, 1, 0, 1)));
+        assert!(lpm.delete_route("10.1.0.0/16".parse().unwrap()).is_err());
+
+        assert!(lpm
+            .add_route("10.0.0.0/8".parse().unwrap(), MAX_NEXT_HOP + 1)
+            .is_err());
+    }
+
+    #[capsule::test]
+    fn lpm_lookup_bulk() {
+        let mut lpm = LpmTable::new("lpm_bulk", 16, SocketId::ANY).unwrap();
+        lpm.add_route("10.0.0.0/8".parse().unwrap(), 1).unwrap();
+
+        let ips = [Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(11, 0, 0, 1)];
+        let mut results = [None; 2];
+        assert_eq!(1, lpm.lookup_bulk(&ips, &mut results).unwrap());
+        assert_eq!([Some(1), None], results);
+
+        let mut short = [None; 1];
+        assert!(lpm.lookup_bulk(&ips, &mut short).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, SocketId};
+use crate::ffi::{self, ToCString, ToResult};
+use crate::net::{Cidr, Ipv6Cidr};
+use crate::{debug, ensure, info};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::Ipv6Addr;
+use std::ptr::NonNull;
+use thiserror::Error;
+
+/// The number of tbl8 groups. Each route longer than /24 needs a group for
+/// every 8 bits of prefix beyond the first 24.
+const NUMBER_TBL8S: u32 = 1 << 16;
+
+/// The largest next hop value `rte_lpm6` can store.
+const MAX_NEXT_HOP: u32 = (1 << 21) - 1;
+
+/// LPM6 errors.
+#[derive(Debug, Error)]
+pub(crate) enum Lpm6Error {
+    /// The next hop is larger than 21 bits.
+    #[error("Next hop {0} is larger than the maximum {}.", MAX_NEXT_HOP)]
+    InvalidNextHop(u32),
+}
+
+/// A longest prefix match table for IPv6 routes.
+///
+/// The IPv6 counterpart of `LpmTable`. Each route maps a prefix to a 21-bit
+/// next hop value.
+///
+/// # Example
+///
+/// ```
+/// let mut lpm = Lpm6Table::new("routes6", 1024, SocketId::current())?;
+/// lpm.add_route("2001:db8::/32".parse()?, 1)?;
+/// assert_eq!(Some(1), lpm.lookup("2001:db8::1".parse()?));
+/// ```
+pub struct Lpm6Table {
+    raw: NonNull<ffi::rte_lpm6>,
+    name: String,
+    // `rte_lpm6` doesn't expose its rules, they are tracked here instead.
+    routes: BTreeMap<Ipv6Cidr, u32>,
+}
+
+impl Lpm6Table {
+    /// Creates a new table that holds up to `max_rules` routes.
+    ///
+    /// # Errors
+    ///
+    /// If the name is already used or the allocation fails, `DpdkError` is
+    /// returned.
+    pub fn new(name: &str, max_rules: u32, socket_id: SocketId) -> Result<Self> {
+        let config = ffi::rte_lpm6_config {
+            max_rules,
+            number_tbl8s: NUMBER_TBL8S,
+            flags: 0,
+        };
+
+        let raw = unsafe {
+            ffi::rte_lpm6_create(name.into_cstring().as_ptr(), socket_id.raw(), &config)
+                .into_result(|_| DpdkError::new())?
+        };
+
+        debug!("created LPM6 table {}.", name);
+        Ok(Lpm6Table {
+            raw,
+            name: name.to_owned(),
+            routes: BTreeMap::new(),
+        })
+    }
+
+    /// Returns the name of the table.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    /// Normalizes the prefix so routes to the same network are the same
+    /// key regardless of the host bits.
+    fn network(prefix: Ipv6Cidr) -> Ipv6Cidr {
+        Ipv6Cidr::new(prefix.network(), prefix.length()).unwrap_or(prefix)
+    }
+
+    /// Adds a route. If there's already a route for the prefix, its next
+    /// hop is updated.
+    ///
+    /// # Errors
+    ///
+    /// If the next hop does not fit in 21 bits, an error is returned. If
+    /// the table is full, `DpdkError` is returned.
+    pub fn add_route(&mut self, prefix: Ipv6Cidr, next_hop: u32) -> Result<()> {
+        ensure!(
+            next_hop <= MAX_NEXT_HOP,
+            Lpm6Error::InvalidNextHop(next_hop)
+        );
+
+        let prefix = Lpm6Table::network(prefix);
+        let mut ip = prefix.address().octets();
+        unsafe {
+            ffi::rte_lpm6_add(
+                self.raw.as_ptr(),
+                ip.as_mut_ptr(),
+                prefix.length() as u8,
+                next_hop,
+            )
+            .into_result(DpdkError::from_errno)?;
+        }
+
+        self.routes.insert(prefix, next_hop);
+        Ok(())
+    }
+
+    /// Deletes a route.
+    ///
+    /// # Errors
+    ///
+    /// If the route does not exist, `DpdkError` is returned.
+    pub fn delete_route(&mut self, prefix: Ipv6Cidr) -> Result<()> {
+        let prefix = Lpm6Table::network(prefix);
+        let mut ip = prefix.address().octets();
+        unsafe {
+            ffi::rte_lpm6_delete(self.raw.as_ptr(), ip.as_mut_ptr(), prefix.length() as u8)
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        self.routes.remove(&prefix);
+        Ok(())
+    }
+
+    /// Deletes all the routes.
+    pub fn clear(&mut self) {
+        unsafe {
+            ffi::rte_lpm6_delete_all(self.raw.as_ptr());
+        }
+        self.routes.clear();
+    }
+
+    /// Returns the next hop of the longest prefix matching the address.
+    pub fn lookup(&self, dst: Ipv6Addr) -> Option<u32> {
+        let mut ip = dst.octets();
+        let mut next_hop = 0;
+        let res =
+            unsafe { ffi::rte_lpm6_lookup(self.raw.as_ptr(), ip.as_mut_ptr(), &mut next_hop) };
+
+        if res == 0 {
+            Some(next_hop)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of routes in the table.
+    pub fn rules_count(&self) -> u32 {
+        self.routes.len() as u32
+    }
+
+    /// Logs all the routes in the table, ordered by prefix.
+    pub fn dump(&self) {
+        info!("LPM6 table {} has {} routes.", self.name, self.routes.len());
+        for (prefix, next_hop) in self.routes.iter() {
+            info!("  {} -> {}", prefix, next_hop);
+        }
+    }
+}
+
+impl fmt::Debug for Lpm6Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lpm6Table")
+            .field("name", &self.name())
+            .field("rules_count", &self.rules_count())
+            .finish()
+    }
+}
+
+impl Drop for Lpm6Table {
+    fn drop(&mut self) {
+        debug!("freeing LPM6 table {}.", self.name);
+
+        unsafe {
+            ffi::rte_lpm6_free(self.raw.as_ptr());
+        }
+    }
+}
+
+/// Lookups only read the table, so the table can be shared across cores.
+unsafe impl Send for Lpm6Table {}
+unsafe impl Sync for Lpm6Table {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn lpm6_longest_prefix_match() {
+        let mut lpm = Lpm6Table::new("lpm6_match", 16, SocketId::ANY).unwrap();
+        lpm.add_route("2001:db8::/32".parse().unwrap(), 1).unwrap();
+        lpm.add_route("2001:db8:1::/48".parse().unwrap(), 2)
+            .unwrap();
+        assert_eq!(2, lpm.rules_count());
+
+        assert_eq!(Some(1), lpm.lookup("2001:db8:2::1".parse().unwrap()));
+        assert_eq!(Some(2), lpm.lookup("2001:db8:1::1".parse().unwrap()));
+        assert_eq!(None, lpm.lookup("fe80::1".parse().unwrap()));
+
+        // updates the next hop instead of adding a new route.
+        lpm.add_route("2001:db8:1::/48".parse().unwrap(), 3)
+            .unwrap();
+        assert_eq!(2, lpm.rules_count());
+        assert_eq!(Some(3), lpm.lookup("2001:db8:1::1".parse().unwrap()));
+
+        lpm.delete_route("2001:db8:1::/48".parse().unwrap())
+            .unwrap();
+        assert_eq!(1, lpm.rules_count());
+        assert_eq!(Some(1), lpm.lookup("2001:db8:1::1".parse().unwrap()));
+
+        assert!(lpm
+            .add_route("2001:db8::/32".parse().unwrap(), MAX_NEXT_HOP + 1)
+            .is_err());
+    }
+}
@@ -0,0 +1,312 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DeviceInfo, DpdkError, PortId};
+use crate::ffi::{self, ToResult};
+use crate::net::MacAddr;
+use crate::{debug, ensure};
+use anyhow::Result;
+use std::fmt;
+use std::ptr;
+use std::slice;
+use thiserror::Error;
+
+/// MAC address filter errors.
+#[derive(Debug, Error)]
+pub(crate) enum MacFilterError {
+    /// The address is not a multicast address.
+    #[error("{0} is not a multicast address.")]
+    NotMulticast(MacAddr),
+
+    /// The address is not in the multicast filter.
+    #[error("{0} is not a joined multicast group.")]
+    NotJoined(MacAddr),
+
+    /// The address is not a unicast address.
+    #[error("{0} is not a unicast address.")]
+    NotUnicast(MacAddr),
+
+    /// The port already has the maximum number of MAC addresses.
+    #[error("Port already has the maximum of {0} MAC addresses.")]
+    TooManyAddresses(u32),
+}
+
+/// The unicast MAC addresses a port receives packets for.
+///
+/// Besides its default address, a NIC can accept packets for additional
+/// addresses, typically to provide aliases or to steer traffic to virtual
+/// functions through the VMDq pools. The number of addresses is limited
+/// by the device.
+///
+/// # Example
+///
+/// ```
+/// let filter = q.mac_filter()?;
+/// filter.add("02:00:00:00:00:01".parse()?, 0)?;
+/// ```
+pub struct MacFilter {
+    port_id: PortId,
+    max_mac_addrs: u32,
+}
+
+impl MacFilter {
+    /// Creates a new filter for the port.
+    pub(crate) fn new(port_id: PortId) -> Result<Self> {
+        let max_mac_addrs = DeviceInfo::query(port_id.raw())?.max_mac_addrs();
+        Ok(MacFilter {
+            port_id,
+            max_mac_addrs,
+        })
+    }
+
+    /// Returns the maximum number of MAC addresses the port accepts,
+    /// including the default address.
+    pub fn max_mac_addrs(&self) -> u32 {
+        self.max_mac_addrs
+    }
+
+    /// Adds an address to the VMDq pool. Adding an address the port
+    /// already has only adds the pool.
+    ///
+    /// # Errors
+    ///
+    /// If the address is not unicast, or the port already has the maximum
+    /// number of addresses, `MacFilterError` is returned. If the device
+    /// does not support the operation, `DpdkError` is returned.
+    pub fn add(&self, mac: MacAddr, pool_idx: u32) -> Result<()> {
+        ensure!(!mac.is_multicast(), MacFilterError::NotUnicast(mac));
+
+        let addrs = MacFilter::list(self.port_id.raw())?;
+        ensure!(
+            addrs.contains(&mac) || (addrs.len() as u32) < self.max_mac_addrs,
+            MacFilterError::TooManyAddresses(self.max_mac_addrs)
+        );
+
+        let mut addr: ffi::rte_ether_addr = mac.into();
+        unsafe {
+            ffi::rte_eth_dev_mac_addr_add(self.port_id.raw(), &mut addr, pool_idx)
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        debug!(port = ?self.port_id, %mac, pool_idx, "added MAC address.");
+        Ok(())
+    }
+
+    /// Removes an address. The default address can't be removed.
+    ///
+    /// # Errors
+    ///
+    /// If the address is the default address, or the device does not
+    /// support the operation, `DpdkError` is returned.
+    pub fn remove(&self, mac: MacAddr) -> Result<()> {
+        let mut addr: ffi::rte_ether_addr = mac.into();
+        unsafe {
+            ffi::rte_eth_dev_mac_addr_remove(self.port_id.raw(), &mut addr)
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        debug!(port = ?self.port_id, %mac, "removed MAC address.");
+        Ok(())
+    }
+
+    /// Replaces the default address of the port.
+    ///
+    /// # Errors
+    ///
+    /// If the address is not unicast, `MacFilterError` is returned. If the
+    /// device does not support the operation, `DpdkError` is returned.
+    pub fn set_default(&self, mac: MacAddr) -> Result<()> {
+        ensure!(!mac.is_multicast(), MacFilterError::NotUnicast(mac));
+
+        let mut addr: ffi::rte_ether_addr = mac.into();
+        unsafe {
+            ffi::rte_eth_dev_default_mac_addr_set(self.port_id.raw(), &mut addr)
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        debug!(port = ?self.port_id, %mac, "set default MAC address.");
+        Ok(())
+    }
+
+    /// Returns all the addresses of the port, starting with the default
+    /// address.
+    ///
+    /// DPDK 19.11 does not have `rte_eth_macaddrs_get`, so the addresses
+    /// are read from the device data the same way the function does.
+    ///
+    /// # Errors
+    ///
+    /// If the port id is invalid, `DpdkError` is returned.
+    pub fn list(port_id: u16) -> Result<Vec<MacAddr>> {
+        let max = DeviceInfo::query(port_id)?.max_mac_addrs() as usize;
+
+        let addrs = unsafe {
+            let dev = ptr::addr_of!(ffi::rte_eth_devices)
+                .cast::<ffi::rte_eth_dev>()
+                .add(port_id as usize);
+            let mac_addrs = (*(*dev).data).mac_addrs;
+            if mac_addrs.is_null() {
+                return Ok(vec![]);
+            }
+            slice::from_raw_parts(mac_addrs, max)
+        };
+
+        // unused slots are zeroed.
+        Ok(addrs
+            .iter()
+            .map(|&addr| MacAddr::from(addr))
+            .filter(|&mac| mac != MacAddr::UNSPECIFIED)
+            .collect())
+    }
+}
+
+impl fmt::Debug for MacFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MacFilter")
+            .field("port_id", &self.port_id)
+            .field("max_mac_addrs", &self.max_mac_addrs)
+            .finish()
+    }
+}
+
+/// The multicast groups a port receives packets for.
+///
+/// The NIC filters incoming multicast packets against a list of group
+/// addresses. Every change to the membership replaces the list on the
+/// device, so all the changes for a port should go through the same
+/// filter.
+///
+/// # Example
+///
+/// ```
+/// let mut filter = q.multicast_filter();
+/// filter.join("01:00:5e:00:00:fb".parse()?)?;
+/// ```
+pub struct MulticastFilter {
+    port_id: PortId,
+    joined: Vec<MacAddr>,
+}
+
+impl MulticastFilter {
+    /// Creates a new empty filter for the port.
+    pub(crate) fn new(port_id: PortId) -> Self {
+        MulticastFilter {
+            port_id,
+            joined: vec![],
+        }
+    }
+
+    /// Returns the multicast groups joined.
+    pub fn joined(&self) -> &[MacAddr] {
+        &self.joined
+    }
+
+    /// Joins a multicast group. Joining a group already joined has no
+    /// effect.
+    ///
+    /// # Errors
+    ///
+    /// If the address is not a multicast address, `MacFilterError` is
+    /// returned. If the device does not support multicast filtering, or
+    /// the list is full, `DpdkError` is returned.
+    pub fn join(&mut self, mac: MacAddr) -> Result<()> {
+        ensure!(mac.is_multicast(), MacFilterError::NotMulticast(mac));
+
+        if self.joined.contains(&mac) {
+            return Ok(());
+        }
+
+        self.joined.push(mac);
+        if let Err(err) = self.apply() {
+            self.joined.pop();
+            return Err(err);
+        }
+
+        debug!(port = ?self.port_id, %mac, "joined multicast group.");
+        Ok(())
+    }
+
+    /// Leaves a multicast group.
+    ///
+    /// # Errors
+    ///
+    /// If the group is not joined, `MacFilterError` is returned. If the
+    /// device fails to update the list, `DpdkError` is returned.
+    pub fn leave(&mut self, mac: MacAddr) -> Result<()> {
+        let idx = self
+            .joined
+            .iter()
+            .position(|&joined| joined == mac)
+            .ok_or(MacFilterError::NotJoined(mac))?;
+
+        self.joined.remove(idx);
+        if let Err(err) = self.apply() {
+            self.joined.insert(idx, mac);
+            return Err(err);
+        }
+
+        debug!(port = ?self.port_id, %mac, "left multicast group.");
+        Ok(())
+    }
+
+    /// Enables the port to receive all multicast packets, regardless of
+    /// the joined groups.
+    ///
+    /// # Errors
+    ///
+    /// If the port id is invalid or the device does not support the mode,
+    /// `DpdkError` is returned.
+    pub fn all_multicast_enable(port_id: u16) -> Result<()> {
+        unsafe {
+            ffi::rte_eth_allmulticast_enable(port_id)
+                .into_result(DpdkError::from_errno)
+                .map(|_| ())
+        }
+    }
+
+    /// Replaces the list on the device with the joined groups.
+    fn apply(&self) -> Result<()> {
+        let mut addrs = self
+            .joined
+            .iter()
+            .map(|&mac| mac.into())
+            .collect::<Vec<ffi::rte_ether_addr>>();
+
+        // an empty list clears the filter.
+        let ptr = if addrs.is_empty() {
+            ptr::null_mut()
+        } else {
+            addrs.as_mut_ptr()
+        };
+
+        unsafe {
+            ffi::rte_eth_dev_set_mc_addr_list(self.port_id.raw(), ptr, addrs.len() as u32)
+                .into_result(DpdkError::from_errno)
+                .map(|_| ())
+        }
+    }
+}
+
+impl fmt::Debug for MulticastFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MulticastFilter")
+            .field("port_id", &self.port_id)
+            .field("joined", &self.joined)
+            .finish()
+    }
+}
@@ -16,13 +16,14 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
-use super::MEMPOOL;
+use super::{PacketMeta, PacketMetaMut, MEMPOOL};
 use crate::dpdk::{DpdkError, MempoolError};
 use crate::ffi::{self, ToResult};
 use crate::packets::{Internal, Packet};
 use crate::{ensure, trace};
 use anyhow::Result;
 use std::fmt;
+use std::iter;
 use std::mem;
 use std::os::raw;
 use std::ptr::{self, NonNull};
@@ -101,9 +102,12 @@ pub(crate) enum BufferError {
 ///
 /// # Remarks
 ///
-/// Multi-segment Mbuf is not supported. It's the application's responsibilty
-/// to ensure that the ethernet device's MTU is less than the default size
-/// of a single Mbuf segment (`RTE_MBUF_DEFAULT_DATAROOM` = 2048).
+/// Multi-segment Mbuf is only partially supported. The data access and
+/// resizing functions all operate on the first segment. The chained
+/// segments can be read through `Mbuf::segments`. It's the application's
+/// responsibilty to ensure that the ethernet device's MTU is less than the
+/// default size of a single Mbuf segment (`RTE_MBUF_DEFAULT_DATAROOM` = 2048)
+/// if the packets need to be modified.
 pub struct Mbuf {
     inner: MbufInner,
 }
@@ -181,22 +185,109 @@ impl Mbuf {
 
     /// Returns the raw struct needed for FFI calls.
     #[inline]
-    fn raw(&self) -> &ffi::rte_mbuf {
+    pub(crate) fn raw(&self) -> &ffi::rte_mbuf {
         unsafe { self.inner.ptr().as_ref() }
     }
 
     /// Returns the raw struct needed for FFI calls.
     #[inline]
-    fn raw_mut(&mut self) -> &mut ffi::rte_mbuf {
+    pub(crate) fn raw_mut(&mut self) -> &mut ffi::rte_mbuf {
         unsafe { self.inner.ptr_mut().as_mut() }
     }
 
+    /// Returns the metadata of the buffer.
+    #[inline]
+    pub fn meta(&self) -> PacketMeta<'_> {
+        PacketMeta::new(self)
+    }
+
+    /// Returns the writable metadata of the buffer.
+    #[inline]
+    pub fn meta_mut(&mut self) -> PacketMetaMut<'_> {
+        PacketMetaMut::new(self)
+    }
+
     /// Returns amount of data stored in the buffer.
     #[inline]
     pub fn data_len(&self) -> usize {
         self.raw().data_len as usize
     }
 
+    /// Returns the total amount of data stored in all the segments of
+    /// the buffer.
+    #[inline]
+    pub fn pkt_len(&self) -> usize {
+        self.raw().pkt_len as usize
+    }
+
+    /// Returns the amount of space reserved in front of the data.
+    #[inline]
+    pub fn headroom(&self) -> usize {
+        self.raw().data_off as usize
+    }
+
+    /// Sets the amount of space reserved in front of the data. The buffer
+    /// must be empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `BufferError::NotResized` if the buffer is not empty.
+    /// Returns `BufferError::BadOffset` if the headroom exceeds the buffer
+    /// length.
+    pub(crate) fn reserve_headroom(&mut self, headroom: usize) -> Result<()> {
+        ensure!(self.data_len() == 0, BufferError::NotResized);
+
+        let buf_len = self.raw().buf_len as usize;
+        ensure!(
+            headroom <= buf_len,
+            BufferError::BadOffset(headroom, buf_len)
+        );
+
+        self.raw_mut().data_off = headroom as u16;
+        Ok(())
+    }
+
+    /// Returns the number of segments chained together in the buffer.
+    #[inline]
+    pub fn nb_segs(&self) -> usize {
+        self.raw().nb_segs as usize
+    }
+
+    /// Returns an iterator over the data of each segment in the buffer.
+    ///
+    /// The segments are visited by following the `next` pointers, starting
+    /// with the first segment.
+    #[inline]
+    pub fn segments(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        iter::successors(Some(self.raw()), |seg| unsafe { seg.next.as_ref() }).map(|seg| unsafe {
+            let data = (seg.buf_addr as *const u8).offset(seg.data_off as isize);
+            slice::from_raw_parts(data, seg.data_len as usize)
+        })
+    }
+
+    /// Creates a deep copy of the message buffer.
+    ///
+    /// Unlike the internal clone used by the packet types, the copy has its
+    /// own data buffer allocated from the same `Mempool` as the original.
+    /// All the segments are copied and the copy is a single segment Mbuf
+    /// if the data fits.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MempoolError::Exhausted` if the allocation of mbuf fails.
+    #[inline]
+    pub fn try_clone(&self) -> Result<Self> {
+        let raw = self.raw();
+        let ptr = unsafe {
+            ffi::rte_pktmbuf_copy(raw, raw.pool, 0, u32::max_value())
+                .into_result(|_| MempoolError::Exhausted)?
+        };
+
+        Ok(Mbuf {
+            inner: MbufInner::Original(ptr),
+        })
+    }
+
     /// Returns the raw pointer from the offset
     #[inline]
     pub(crate) unsafe fn data_address(&self, offset: usize) -> *mut u8 {
@@ -725,6 +816,32 @@ mod tests {
         assert!(mbuf.read_data_slice::<u8>(10, 16).is_err());
     }
 
+    #[capsule::test]
+    fn iterate_segments() {
+        let mbuf = Mbuf::from_bytes(&BUFFER).unwrap();
+
+        assert_eq!(1, mbuf.nb_segs());
+        assert_eq!(16, mbuf.pkt_len());
+
+        let segments = mbuf.segments().collect::<Vec<_>>();
+        assert_eq!(1, segments.len());
+        assert_eq!(BUFFER, segments[0]);
+    }
+
+    #[capsule::test]
+    fn try_clone_mbuf() {
+        let mut mbuf = Mbuf::from_bytes(&BUFFER).unwrap();
+        let copy = mbuf.try_clone().unwrap();
+
+        // changes to the original should not touch the copy
+        assert!(mbuf.write_data(0, &[0u8; 16]).is_ok());
+        assert_eq!(16, copy.data_len());
+
+        let slice = copy.read_data_slice::<u8>(0, 16).unwrap();
+        let slice = unsafe { slice.as_ref() };
+        assert_eq!(BUFFER, slice);
+    }
+
     #[capsule::test]
     fn alloc_bulk() {
         let mbufs = Mbuf::alloc_bulk(8).unwrap();
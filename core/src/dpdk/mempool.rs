@@ -16,7 +16,7 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
-use super::SocketId;
+use super::{Allocate, NeedsSocket, SocketId};
 use crate::dpdk::DpdkError;
 use crate::ffi::{self, AsStr, ToCString, ToResult};
 use crate::{debug, info};
@@ -31,6 +31,11 @@ use thiserror::Error;
 
 /// A memory pool is an allocator of message buffers, or `Mbuf`. For best
 /// performance, each socket should have a dedicated `Mempool`.
+///
+/// A `Mempool` can be moved to another thread but is not shareable across
+/// threads. The cores on the same socket allocate from the same pool through
+/// the thread local `MEMPOOL` pointer instead, which goes through the per
+/// core object cache.
 pub(crate) struct Mempool {
     raw: NonNull<ffi::rte_mempool>,
 }
@@ -69,6 +74,9 @@ impl Mempool {
             .into_result(|_| DpdkError::new())?
         };
 
+        #[cfg(feature = "telemetry")]
+        crate::telemetry::register_mempool_info();
+
         info!("created {}.", name);
         Ok(Self { raw })
     }
@@ -91,17 +99,58 @@ impl Mempool {
         self.raw().name[..].as_str()
     }
 
+    /// Returns the maximum number of `Mbuf` the `Mempool` can hold.
+    #[inline]
+    pub(crate) fn capacity(&self) -> usize {
+        self.raw().size as usize
+    }
+
+    /// Returns the number of `Mbuf` available for allocation.
+    ///
+    /// The count includes the `Mbuf` held in the per core caches. Should
+    /// only be used for debugging and stats collection because it's slow.
+    #[inline]
+    pub(crate) fn available(&self) -> usize {
+        unsafe { ffi::rte_mempool_avail_count(self.raw()) as usize }
+    }
+
+    /// Returns the number of `Mbuf` allocated from the `Mempool`.
+    ///
+    /// Should only be used for debugging and stats collection because it's
+    /// slow.
+    #[inline]
+    pub(crate) fn in_use(&self) -> usize {
+        unsafe { ffi::rte_mempool_in_use_count(self.raw()) as usize }
+    }
+
     #[cfg(feature = "metrics")]
     pub(crate) fn stats(&self) -> super::MempoolStats {
         super::MempoolStats::build(self)
     }
 }
 
+impl NeedsSocket for Mempool {
+    fn socket_id(&self) -> SocketId {
+        SocketId(self.raw().socket_id)
+    }
+}
+
+impl Allocate for Mempool {
+    /// The capacity and the per core cache size of the mempool.
+    type Params = (usize, usize);
+
+    fn allocate(socket_id: SocketId, (capacity, cache_size): Self::Params) -> Result<Self> {
+        Mempool::new(capacity, cache_size, socket_id)
+    }
+}
+
 impl fmt::Debug for Mempool {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let raw = self.raw();
         f.debug_struct(self.name())
-            .field("capacity", &raw.size)
+            .field("capacity", &self.capacity())
+            .field("available", &self.available())
+            .field("in_use", &self.in_use())
             .field("cache_size", &raw.cache_size)
             .field("flags", &format_args!("{:#x}", raw.flags))
             .field("socket", &raw.socket_id)
@@ -109,6 +158,11 @@ impl fmt::Debug for Mempool {
     }
 }
 
+// the `Mempool` owns the raw pointer and DPDK allows the pool to be used
+// and freed from any thread. explicitly implement the `Send` trait so the
+// `Runtime` can move it. `Sync` is intentionally not implemented.
+unsafe impl Send for Mempool {}
+
 impl Drop for Mempool {
     fn drop(&mut self) {
         debug!("freeing {}.", self.name());
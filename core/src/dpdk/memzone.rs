@@ -0,0 +1,302 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::{DpdkError, SocketId};
+use crate::ffi::{self, AsStr, ToCString, ToResult};
+use crate::{debug, ensure};
+use anyhow::Result;
+use bitflags::bitflags;
+use std::fmt;
+use std::mem;
+use std::os::raw;
+use std::ptr::{self, NonNull};
+use std::slice;
+use thiserror::Error;
+
+bitflags! {
+    /// Flags used when reserving a `MemoryZone`.
+    pub struct MemzoneFlags: u32 {
+        /// Reserves the zone from 256KB pages.
+        const PAGE_256KB = ffi::RTE_MEMZONE_256KB;
+        /// Reserves the zone from 2MB pages.
+        const PAGE_2MB = ffi::RTE_MEMZONE_2MB;
+        /// Reserves the zone from 16MB pages.
+        const PAGE_16MB = ffi::RTE_MEMZONE_16MB;
+        /// Reserves the zone from 256MB pages.
+        const PAGE_256MB = ffi::RTE_MEMZONE_256MB;
+        /// Reserves the zone from 512MB pages.
+        const PAGE_512MB = ffi::RTE_MEMZONE_512MB;
+        /// Reserves the zone from 1GB pages.
+        const PAGE_1GB = ffi::RTE_MEMZONE_1GB;
+        /// Reserves the zone from 4GB pages.
+        const PAGE_4GB = ffi::RTE_MEMZONE_4GB;
+        /// Reserves the zone from 16GB pages.
+        const PAGE_16GB = ffi::RTE_MEMZONE_16GB;
+        /// Reserves the zone from either 2MB or 1GB huge pages.
+        const HUGE_PAGES = Self::PAGE_2MB.bits | Self::PAGE_1GB.bits;
+        /// The page size flags are a preference only. If no pages of the
+        /// requested size are available, other sizes are used instead.
+        const SIZE_HINT = ffi::RTE_MEMZONE_SIZE_HINT_ONLY;
+        /// The zone must be IOVA-contiguous.
+        const IOVA_CONTIG = ffi::RTE_MEMZONE_IOVA_CONTIG;
+    }
+}
+
+/// Memory zone errors.
+#[derive(Debug, Error)]
+pub(crate) enum MemzoneError {
+    /// The name does not fit in `RTE_MEMZONE_NAMESIZE`.
+    #[error("Memzone name '{0}' is longer than {1} bytes.")]
+    NameTooLong(String, usize),
+
+    /// The zone length is not a multiple of the item size.
+    #[error("Memzone of {0} bytes cannot be viewed as a slice of {1}-byte items.")]
+    SizeMismatch(usize, usize),
+
+    /// The zone address is not aligned for the item type.
+    #[error("Memzone address is not aligned to {0} bytes.")]
+    Misaligned(usize),
+}
+
+/// A named, contiguous block of huge page memory.
+///
+/// Memory zones are registered with the EAL by name and can be found by
+/// any core, or by a secondary process, with `MemoryZone::lookup`. They
+/// are typically used to share state, like counters or configuration
+/// tables, between processes.
+///
+/// A zone is not freed when the handle is dropped, because other handles
+/// to the same zone may exist. Use `MemoryZone::free` to release it.
+///
+/// # Example
+///
+/// ```
+/// let mut zone = MemoryZone::reserve("counters", 4096, SocketId::ANY, MemzoneFlags::empty())?;
+/// let counters = unsafe { zone.as_slice_mut::<u64>()? };
+/// counters[0] += 1;
+/// ```
+pub struct MemoryZone {
+    raw: NonNull<ffi::rte_memzone>,
+}
+
+impl MemoryZone {
+    /// Reserves a new zeroed memory zone of `len` bytes on a socket.
+    ///
+    /// # Errors
+    ///
+    /// If the name is too long or already used, or the socket doesn't have
+    /// enough free memory, an error is returned.
+    pub fn reserve(
+        name: &str,
+        len: usize,
+        socket_id: SocketId,
+        flags: MemzoneFlags,
+    ) -> Result<Self> {
+        let max = ffi::RTE_MEMZONE_NAMESIZE as usize - 1;
+        ensure!(
+            name.len() <= max,
+            MemzoneError::NameTooLong(name.to_owned(), max)
+        );
+
+        let raw = unsafe {
+            ffi::rte_memzone_reserve(
+                name.into_cstring().as_ptr(),
+                len as ffi::size_t,
+                socket_id.raw(),
+                flags.bits() as raw::c_uint,
+            ) as *mut ffi::rte_memzone
+        }
+        .into_result(|_| DpdkError::new())?;
+
+        let zone = MemoryZone { raw };
+        unsafe {
+            ptr::write_bytes(zone.addr(), 0, zone.len());
+        }
+
+        debug!("reserved memzone {}.", name);
+        Ok(zone)
+    }
+
+    /// Looks up a memory zone by name. Returns `None` if no zone with the
+    /// name is reserved.
+    pub fn lookup(name: &str) -> Option<Self> {
+        let raw = unsafe {
+            ffi::rte_memzone_lookup(name.into_cstring().as_ptr()) as *mut ffi::rte_memzone
+        };
+        NonNull::new(raw).map(|raw| MemoryZone { raw })
+    }
+
+    #[inline]
+    fn raw(&self) -> &ffi::rte_memzone {
+        unsafe { self.raw.as_ref() }
+    }
+
+    /// Returns the name of the zone.
+    pub fn name(&self) -> &str {
+        self.raw().name[..].as_str()
+    }
+
+    /// Returns the length of the zone in bytes.
+    pub fn len(&self) -> usize {
+        self.raw().len as usize
+    }
+
+    /// Returns whether the zone is zero-sized.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the socket the zone is reserved on.
+    pub fn socket_id(&self) -> SocketId {
+        SocketId(self.raw().socket_id)
+    }
+
+    /// Returns the virtual address of the start of the zone.
+    pub fn addr(&self) -> *mut u8 {
+        unsafe { self.raw().__bindgen_anon_2.addr as *mut u8 }
+    }
+
+    /// Checks that the zone can be viewed as a slice of `T` and returns
+    /// the number of items.
+    fn slice_len<T: Copy>(&self) -> Result<usize> {
+        let size = mem::size_of::<T>();
+        ensure!(
+            size > 0 && self.len() % size == 0,
+            MemzoneError::SizeMismatch(self.len(), size)
+        );
+
+        let align = mem::align_of::<T>();
+        ensure!(
+            self.addr() as usize % align == 0,
+            MemzoneError::Misaligned(align)
+        );
+
+        Ok(self.len() / size)
+    }
+
+    /// Returns the zone as a slice of `T`.
+    ///
+    /// # Errors
+    ///
+    /// If the zone length is not a multiple of the size of `T`, or the
+    /// zone is not aligned for `T`, an error is returned.
+    ///
+    /// # Safety
+    ///
+    /// The zone must hold valid values of `T`, and no other core or
+    /// process may write to the zone while the slice is in use.
+    pub unsafe fn as_slice<T: Copy>(&self) -> Result<&[T]> {
+        let len = self.slice_len::<T>()?;
+        Ok(slice::from_raw_parts(self.addr() as *const T, len))
+    }
+
+    /// Returns the zone as a mutable slice of `T`.
+    ///
+    /// # Errors
+    ///
+    /// If the zone length is not a multiple of the size of `T`, or the
+    /// zone is not aligned for `T`, an error is returned.
+    ///
+    /// # Safety
+    ///
+    /// The zone must hold valid values of `T`, and no other core or
+    /// process may access the zone while the slice is in use.
+    pub unsafe fn as_slice_mut<T: Copy>(&mut self) -> Result<&mut [T]> {
+        let len = self.slice_len::<T>()?;
+        Ok(slice::from_raw_parts_mut(self.addr() as *mut T, len))
+    }
+
+    /// Frees the memory zone.
+    ///
+    /// Any other handle to the same zone, including ones in secondary
+    /// processes, becomes dangling and must not be used afterwards.
+    ///
+    /// # Errors
+    ///
+    /// If the zone is already freed, `DpdkError` is returned.
+    pub fn free(self) -> Result<()> {
+        let name = self.name().to_owned();
+        unsafe {
+            ffi::rte_memzone_free(self.raw.as_ptr()).into_result(DpdkError::from_errno)?;
+        }
+
+        debug!("freed memzone {}.", name);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for MemoryZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = self.raw();
+        f.debug_struct(self.name())
+            .field("addr", &format!("{:p}", self.addr()))
+            .field("len", &self.len())
+            .field("hugepage_sz", &{ raw.hugepage_sz })
+            .field("socket_id", &self.socket_id())
+            .field("flags", &MemzoneFlags::from_bits_truncate(raw.flags))
+            .finish()
+    }
+}
+
+// the zone is plain shared memory, so the handle can move across threads.
+// concurrent access to the contents is guarded by the unsafe slice views.
+unsafe impl Send for MemoryZone {}
+unsafe impl Sync for MemoryZone {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn reserve_lookup_and_free() {
+        let mut zone =
+            MemoryZone::reserve("test_mz0", 64, SocketId::ANY, MemzoneFlags::empty()).unwrap();
+        assert_eq!("test_mz0", zone.name());
+        assert_eq!(64, zone.len());
+
+        unsafe {
+            let counters = zone.as_slice_mut::<u64>().unwrap();
+            assert_eq!(8, counters.len());
+            assert_eq!(0, counters[0]);
+            counters[0] = 42;
+        }
+
+        let found = MemoryZone::lookup("test_mz0").unwrap();
+        assert_eq!(zone.addr(), found.addr());
+        assert_eq!(42, unsafe { found.as_slice::<u64>().unwrap()[0] });
+
+        assert!(zone.free().is_ok());
+        assert!(MemoryZone::lookup("test_mz0").is_none());
+    }
+
+    #[capsule::test]
+    fn slice_size_mismatch() {
+        let zone =
+            MemoryZone::reserve("test_mz1", 10, SocketId::ANY, MemzoneFlags::empty()).unwrap();
+        assert!(unsafe { zone.as_slice::<u64>() }.is_err());
+        assert!(unsafe { zone.as_slice::<u8>() }.is_ok());
+        assert!(zone.free().is_ok());
+    }
+
+    #[capsule::test]
+    fn reserve_duplicate_name() {
+        let zone =
+            MemoryZone::reserve("test_mz2", 64, SocketId::ANY, MemzoneFlags::empty()).unwrap();
+        assert!(MemoryZone::reserve("test_mz2", 64, SocketId::ANY, MemzoneFlags::empty()).is_err());
+        assert!(zone.free().is_ok());
+    }
+}
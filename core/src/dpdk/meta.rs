@@ -0,0 +1,362 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::Mbuf;
+use crate::ffi;
+use std::fmt;
+
+/// The data link layer type of a packet.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum L2Type {
+    Unknown,
+    Ether,
+    EtherTimesync,
+    EtherArp,
+    EtherLldp,
+    EtherNsh,
+    EtherVlan,
+    EtherQinq,
+    EtherPppoe,
+    EtherFcoe,
+    EtherMpls,
+}
+
+/// The network layer type of a packet.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum L3Type {
+    /// Unknown or not recognized.
+    Unknown,
+    /// IPv4 without options.
+    Ipv4,
+    /// IPv4 with options.
+    Ipv4Ext,
+    /// IPv4, may or may not have options.
+    Ipv4ExtUnknown,
+    /// IPv6 without extension headers.
+    Ipv6,
+    /// IPv6 with extension headers.
+    Ipv6Ext,
+    /// IPv6, may or may not have extension headers.
+    Ipv6ExtUnknown,
+}
+
+/// The transport layer type of a packet.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum L4Type {
+    /// Unknown or not recognized.
+    Unknown,
+    /// TCP.
+    Tcp,
+    /// UDP.
+    Udp,
+    /// An IP fragment.
+    Frag,
+    /// SCTP.
+    Sctp,
+    /// ICMP.
+    Icmp,
+    /// Not fragmented, but the transport protocol is not recognized.
+    NonFrag,
+    /// IGMP.
+    Igmp,
+}
+
+/// The tunnel type of a packet.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TunnelType {
+    None,
+    Ip,
+    Gre,
+    Vxlan,
+    Nvgre,
+    Geneve,
+    Grenat,
+    Gtpc,
+    Gtpu,
+    Esp,
+    L2tp,
+    VxlanGpe,
+    MplsInGre,
+    MplsInUdp,
+    Unknown,
+}
+
+/// The packet type recognized by the device or software, decomposed from the
+/// `RTE_PTYPE_*` bits.
+#[derive(Clone, Copy, Default, Eq, PartialEq)]
+pub struct PacketType(u32);
+
+impl PacketType {
+    /// Returns the raw `RTE_PTYPE_*` bits.
+    pub fn raw(self) -> u32 {
+        self.0
+    }
+
+    /// Returns the data link layer type.
+    pub fn l2(self) -> L2Type {
+        match self.0 & ffi::RTE_PTYPE_L2_MASK {
+            ffi::RTE_PTYPE_L2_ETHER => L2Type::Ether,
+            ffi::RTE_PTYPE_L2_ETHER_TIMESYNC => L2Type::EtherTimesync,
+            ffi::RTE_PTYPE_L2_ETHER_ARP => L2Type::EtherArp,
+            ffi::RTE_PTYPE_L2_ETHER_LLDP => L2Type::EtherLldp,
+            ffi::RTE_PTYPE_L2_ETHER_NSH => L2Type::EtherNsh,
+            ffi::RTE_PTYPE_L2_ETHER_VLAN => L2Type::EtherVlan,
+            ffi::RTE_PTYPE_L2_ETHER_QINQ => L2Type::EtherQinq,
+            ffi::RTE_PTYPE_L2_ETHER_PPPOE => L2Type::EtherPppoe,
+            ffi::RTE_PTYPE_L2_ETHER_FCOE => L2Type::EtherFcoe,
+            ffi::RTE_PTYPE_L2_ETHER_MPLS => L2Type::EtherMpls,
+            _ => L2Type::Unknown,
+        }
+    }
+
+    /// Returns the network layer type.
+    pub fn l3(self) -> L3Type {
+        match self.0 & ffi::RTE_PTYPE_L3_MASK {
+            ffi::RTE_PTYPE_L3_IPV4 => L3Type::Ipv4,
+            ffi::RTE_PTYPE_L3_IPV4_EXT => L3Type::Ipv4Ext,
+            ffi::RTE_PTYPE_L3_IPV4_EXT_UNKNOWN => L3Type::Ipv4ExtUnknown,
+            ffi::RTE_PTYPE_L3_IPV6 => L3Type::Ipv6,
+            ffi::RTE_PTYPE_L3_IPV6_EXT => L3Type::Ipv6Ext,
+            ffi::RTE_PTYPE_L3_IPV6_EXT_UNKNOWN => L3Type::Ipv6ExtUnknown,
+            _ => L3Type::Unknown,
+        }
+    }
+
+    /// Returns the transport layer type.
+    pub fn l4(self) -> L4Type {
+        match self.0 & ffi::RTE_PTYPE_L4_MASK {
+            ffi::RTE_PTYPE_L4_TCP => L4Type::Tcp,
+            ffi::RTE_PTYPE_L4_UDP => L4Type::Udp,
+            ffi::RTE_PTYPE_L4_FRAG => L4Type::Frag,
+            ffi::RTE_PTYPE_L4_SCTP => L4Type::Sctp,
+            ffi::RTE_PTYPE_L4_ICMP => L4Type::Icmp,
+            ffi::RTE_PTYPE_L4_NONFRAG => L4Type::NonFrag,
+            ffi::RTE_PTYPE_L4_IGMP => L4Type::Igmp,
+            _ => L4Type::Unknown,
+        }
+    }
+
+    /// Returns the tunnel type.
+    pub fn tunnel(self) -> TunnelType {
+        match self.0 & ffi::RTE_PTYPE_TUNNEL_MASK {
+            0 => TunnelType::None,
+            ffi::RTE_PTYPE_TUNNEL_IP => TunnelType::Ip,
+            ffi::RTE_PTYPE_TUNNEL_GRE => TunnelType::Gre,
+            ffi::RTE_PTYPE_TUNNEL_VXLAN => TunnelType::Vxlan,
+            ffi::RTE_PTYPE_TUNNEL_NVGRE => TunnelType::Nvgre,
+            ffi::RTE_PTYPE_TUNNEL_GENEVE => TunnelType::Geneve,
+            ffi::RTE_PTYPE_TUNNEL_GRENAT => TunnelType::Grenat,
+            ffi::RTE_PTYPE_TUNNEL_GTPC => TunnelType::Gtpc,
+            ffi::RTE_PTYPE_TUNNEL_GTPU => TunnelType::Gtpu,
+            ffi::RTE_PTYPE_TUNNEL_ESP => TunnelType::Esp,
+            ffi::RTE_PTYPE_TUNNEL_L2TP => TunnelType::L2tp,
+            ffi::RTE_PTYPE_TUNNEL_VXLAN_GPE => TunnelType::VxlanGpe,
+            ffi::RTE_PTYPE_TUNNEL_MPLS_IN_GRE => TunnelType::MplsInGre,
+            ffi::RTE_PTYPE_TUNNEL_MPLS_IN_UDP => TunnelType::MplsInUdp,
+            _ => TunnelType::Unknown,
+        }
+    }
+}
+
+impl From<u32> for PacketType {
+    fn from(raw: u32) -> Self {
+        PacketType(raw)
+    }
+}
+
+impl fmt::Debug for PacketType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketType")
+            .field("l2", &self.l2())
+            .field("l3", &self.l3())
+            .field("l4", &self.l4())
+            .field("tunnel", &self.tunnel())
+            .finish()
+    }
+}
+
+/// Read access to the metadata the device and the software path attach to
+/// a message buffer.
+///
+/// # Example
+///
+/// ```
+/// let meta = packet.mbuf().meta();
+/// if meta.packet_type().l4() == L4Type::Tcp {
+///     let core = meta.rss_hash() as usize % cores.len();
+/// }
+/// ```
+pub struct PacketMeta<'a> {
+    raw: &'a ffi::rte_mbuf,
+}
+
+impl<'a> PacketMeta<'a> {
+    pub(crate) fn new(mbuf: &'a Mbuf) -> Self {
+        PacketMeta { raw: mbuf.raw() }
+    }
+
+    /// Returns the RSS hash computed by the device. It's only valid if
+    /// the device has RSS enabled.
+    pub fn rss_hash(&self) -> u32 {
+        unsafe { self.raw.__bindgen_anon_4.hash.rss }
+    }
+
+    /// Returns the packet type.
+    pub fn packet_type(&self) -> PacketType {
+        unsafe { self.raw.__bindgen_anon_3.packet_type.into() }
+    }
+
+    /// Returns the VLAN tag control information. It's only valid if the
+    /// device stripped the VLAN tag.
+    pub fn vlan_tci(&self) -> u16 {
+        self.raw.vlan_tci
+    }
+
+    /// Returns the outer VLAN tag control information. It's only valid if
+    /// the device stripped the QinQ tags.
+    pub fn vlan_tci_outer(&self) -> u16 {
+        self.raw.vlan_tci_outer
+    }
+
+    /// Returns the time the packet is received, in device specific units.
+    /// Returns `None` if the device did not timestamp the packet.
+    pub fn timestamp(&self) -> Option<u64> {
+        if self.raw.ol_flags & ffi::PKT_RX_TIMESTAMP as u64 != 0 {
+            Some(self.raw.timestamp)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the application defined data.
+    pub fn userdata(&self) -> u64 {
+        unsafe { self.raw.__bindgen_anon_5.udata64 }
+    }
+
+    /// Returns the id of the port the packet was received on.
+    pub fn port(&self) -> u16 {
+        self.raw.port
+    }
+
+    /// Returns the sequence number used by `ReorderBuffer`.
+    pub fn seqn(&self) -> u32 {
+        self.raw.seqn
+    }
+}
+
+impl fmt::Debug for PacketMeta<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketMeta")
+            .field("rss_hash", &self.rss_hash())
+            .field("packet_type", &self.packet_type())
+            .field("vlan_tci", &self.vlan_tci())
+            .field("vlan_tci_outer", &self.vlan_tci_outer())
+            .field("timestamp", &self.timestamp())
+            .field("userdata", &self.userdata())
+            .field("port", &self.port())
+            .field("seqn", &self.seqn())
+            .finish()
+    }
+}
+
+/// Write access to the metadata of a message buffer, for the fields a
+/// network function can legitimately change.
+pub struct PacketMetaMut<'a> {
+    raw: &'a mut ffi::rte_mbuf,
+}
+
+impl<'a> PacketMetaMut<'a> {
+    pub(crate) fn new(mbuf: &'a mut Mbuf) -> Self {
+        PacketMetaMut {
+            raw: mbuf.raw_mut(),
+        }
+    }
+
+    /// Sets the application defined data.
+    pub fn set_userdata(&mut self, userdata: u64) {
+        self.raw.__bindgen_anon_5.udata64 = userdata;
+    }
+
+    /// Sets the VLAN tag control information the device inserts on
+    /// transmit, if VLAN insertion is enabled.
+    pub fn set_vlan_tci(&mut self, tci: u16) {
+        self.raw.vlan_tci = tci;
+    }
+
+    /// Sets the outer VLAN tag control information the device inserts on
+    /// transmit, if QinQ insertion is enabled.
+    pub fn set_vlan_tci_outer(&mut self, tci: u16) {
+        self.raw.vlan_tci_outer = tci;
+    }
+
+    /// Sets the packet type, for software that classifies the packet.
+    pub fn set_packet_type(&mut self, packet_type: PacketType) {
+        self.raw.__bindgen_anon_3.packet_type = packet_type.raw();
+    }
+
+    /// Sets the sequence number used by `ReorderBuffer`, typically when
+    /// the packet is received and before it is processed in parallel.
+    pub fn set_seqn(&mut self, seqn: u32) {
+        self.raw.seqn = seqn;
+    }
+}
+
+impl fmt::Debug for PacketMetaMut<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketMetaMut").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompose_packet_type() {
+        let ptype = PacketType::from(
+            ffi::RTE_PTYPE_L2_ETHER | ffi::RTE_PTYPE_L3_IPV4 | ffi::RTE_PTYPE_L4_UDP,
+        );
+        assert_eq!(L2Type::Ether, ptype.l2());
+        assert_eq!(L3Type::Ipv4, ptype.l3());
+        assert_eq!(L4Type::Udp, ptype.l4());
+        assert_eq!(TunnelType::None, ptype.tunnel());
+
+        let ptype = PacketType::from(ffi::RTE_PTYPE_TUNNEL_VXLAN | ffi::RTE_PTYPE_L3_IPV6_EXT);
+        assert_eq!(L2Type::Unknown, ptype.l2());
+        assert_eq!(L3Type::Ipv6Ext, ptype.l3());
+        assert_eq!(TunnelType::Vxlan, ptype.tunnel());
+    }
+
+    #[capsule::test]
+    fn read_and_write_meta() {
+        let mut mbuf = Mbuf::new().unwrap();
+        assert_eq!(None, mbuf.meta().timestamp());
+
+        let mut meta = mbuf.meta_mut();
+        meta.set_userdata(42);
+        meta.set_vlan_tci(100);
+        meta.set_packet_type(PacketType::from(ffi::RTE_PTYPE_L4_TCP));
+
+        let meta = mbuf.meta();
+        assert_eq!(42, meta.userdata());
+        assert_eq!(100, meta.vlan_tci());
+        assert_eq!(L4Type::Tcp, meta.packet_type().l4());
+    }
+}
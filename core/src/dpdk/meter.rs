@@ -0,0 +1,285 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{tsc_cycles, DpdkError, PortQueue};
+use crate::ffi::{self, ToResult};
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::{EtherTypes, Ethernet, Packet};
+use anyhow::Result;
+use std::fmt;
+
+/// The color a meter marks a packet with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeterColor {
+    /// The packet conforms to the committed rate.
+    Green,
+    /// The packet exceeds the committed rate, but is within the burst or
+    /// peak allowance.
+    Yellow,
+    /// The packet violates the allowed rate.
+    Red,
+}
+
+impl From<ffi::rte_color::Type> for MeterColor {
+    fn from(color: ffi::rte_color::Type) -> Self {
+        match color {
+            ffi::rte_color::RTE_COLOR_GREEN => MeterColor::Green,
+            ffi::rte_color::RTE_COLOR_YELLOW => MeterColor::Yellow,
+            _ => MeterColor::Red,
+        }
+    }
+}
+
+/// A single-rate three-color marker as defined in RFC 2697.
+///
+/// The rate is in bytes per second and the burst sizes are in bytes.
+/// Packets are metered in color blind mode.
+#[derive(Clone, Copy)]
+pub struct SrTcmMeter {
+    raw: ffi::rte_meter_srtcm,
+    profile: ffi::rte_meter_srtcm_profile,
+    params: ffi::rte_meter_srtcm_params,
+}
+
+impl SrTcmMeter {
+    /// Creates a new meter with the committed information rate, the
+    /// committed burst size and the excess burst size.
+    ///
+    /// # Errors
+    ///
+    /// If the rate is 0 or both burst sizes are 0, `DpdkError` is returned.
+    pub fn new(cir_bps: u64, cbs_bytes: u64, ebs_bytes: u64) -> Result<Self> {
+        let mut params = ffi::rte_meter_srtcm_params {
+            cir: cir_bps,
+            cbs: cbs_bytes,
+            ebs: ebs_bytes,
+        };
+        let mut profile = ffi::rte_meter_srtcm_profile::default();
+        let mut raw = ffi::rte_meter_srtcm::default();
+
+        unsafe {
+            ffi::rte_meter_srtcm_profile_config(&mut profile, &mut params)
+                .into_result(DpdkError::from_errno)?;
+            ffi::rte_meter_srtcm_config(&mut raw, &mut profile)
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        Ok(SrTcmMeter {
+            raw,
+            profile,
+            params,
+        })
+    }
+
+    /// Meters a packet of `n_bytes` arriving at `time_cycles`, in TSC
+    /// cycles, and consumes the tokens from the buckets.
+    #[inline]
+    pub fn check_and_update(&mut self, n_bytes: u32, time_cycles: u64) -> MeterColor {
+        unsafe {
+            ffi::_rte_meter_srtcm_color_blind_check(
+                &mut self.raw,
+                &mut self.profile,
+                time_cycles,
+                n_bytes,
+            )
+            .into()
+        }
+    }
+}
+
+impl fmt::Debug for SrTcmMeter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SrTcmMeter")
+            .field("cir", &self.params.cir)
+            .field("cbs", &self.params.cbs)
+            .field("ebs", &self.params.ebs)
+            .finish()
+    }
+}
+
+/// A two-rate three-color marker as defined in RFC 2698.
+///
+/// The rates are in bytes per second and the burst sizes are in bytes.
+/// Packets are metered in color blind mode.
+#[derive(Clone, Copy)]
+pub struct TrTcmMeter {
+    raw: ffi::rte_meter_trtcm,
+    profile: ffi::rte_meter_trtcm_profile,
+    params: ffi::rte_meter_trtcm_params,
+}
+
+impl TrTcmMeter {
+    /// Creates a new meter with the committed and peak information rates,
+    /// and the committed and peak burst sizes.
+    ///
+    /// # Errors
+    ///
+    /// If either rate is 0, the committed rate exceeds the peak rate, or
+    /// either burst size is 0, `DpdkError` is returned.
+    pub fn new(cir_bps: u64, pir_bps: u64, cbs_bytes: u64, pbs_bytes: u64) -> Result<Self> {
+        let mut params = ffi::rte_meter_trtcm_params {
+            cir: cir_bps,
+            pir: pir_bps,
+            cbs: cbs_bytes,
+            pbs: pbs_bytes,
+        };
+        let mut profile = ffi::rte_meter_trtcm_profile::default();
+        let mut raw = ffi::rte_meter_trtcm::default();
+
+        unsafe {
+            ffi::rte_meter_trtcm_profile_config(&mut profile, &mut params)
+                .into_result(DpdkError::from_errno)?;
+            ffi::rte_meter_trtcm_config(&mut raw, &mut profile)
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        Ok(TrTcmMeter {
+            raw,
+            profile,
+            params,
+        })
+    }
+
+    /// Meters a packet of `n_bytes` arriving at `time_cycles`, in TSC
+    /// cycles, and consumes the tokens from the buckets.
+    #[inline]
+    pub fn check_and_update(&mut self, n_bytes: u32, time_cycles: u64) -> MeterColor {
+        unsafe {
+            ffi::_rte_meter_trtcm_color_blind_check(
+                &mut self.raw,
+                &mut self.profile,
+                time_cycles,
+                n_bytes,
+            )
+            .into()
+        }
+    }
+}
+
+impl fmt::Debug for TrTcmMeter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrTcmMeter")
+            .field("cir", &self.params.cir)
+            .field("pir", &self.params.pir)
+            .field("cbs", &self.params.cbs)
+            .field("pbs", &self.params.pbs)
+            .finish()
+    }
+}
+
+/// A traffic policer in front of a transmit queue.
+///
+/// Red packets are dropped. Yellow packets are forwarded, and if a DSCP
+/// is set for them, their IPv4 DSCP is remarked. Green packets are
+/// forwarded unchanged.
+#[allow(missing_debug_implementations)]
+pub struct TrafficShaper {
+    queue: PortQueue,
+    meter: TrTcmMeter,
+    yellow_dscp: Option<u8>,
+    dropped: u64,
+}
+
+impl TrafficShaper {
+    /// Creates a new shaper for the transmit queue.
+    pub fn new(queue: PortQueue, meter: TrTcmMeter) -> Self {
+        TrafficShaper {
+            queue,
+            meter,
+            yellow_dscp: None,
+            dropped: 0,
+        }
+    }
+
+    /// Sets the DSCP yellow IPv4 packets are remarked with. `None`
+    /// forwards them unchanged.
+    pub fn set_yellow_dscp(&mut self, dscp: Option<u8>) -> &mut Self {
+        self.yellow_dscp = dscp;
+        self
+    }
+
+    /// Returns the number of packets dropped as red.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Meters the packets, dropping the red ones and remarking the yellow
+    /// ones. Returns the packets that are allowed through.
+    pub fn shape(&mut self, pkts: Vec<Ethernet>) -> Vec<Ethernet> {
+        let now = tsc_cycles();
+        let mut allowed = Vec::with_capacity(pkts.len());
+
+        for ethernet in pkts {
+            let len = ethernet.mbuf().pkt_len() as u32;
+            match self.meter.check_and_update(len, now) {
+                MeterColor::Green => allowed.push(ethernet),
+                MeterColor::Yellow => match self.yellow_dscp {
+                    Some(dscp) if ethernet.ether_type() == EtherTypes::Ipv4 => {
+                        if let Ok(mut ipv4) = ethernet.parse::<Ipv4>() {
+                            ipv4.set_dscp(dscp);
+                            ipv4.reconcile();
+                            allowed.push(ipv4.deparse());
+                        }
+                    }
+                    _ => allowed.push(ethernet),
+                },
+                MeterColor::Red => self.dropped += 1,
+            }
+        }
+
+        allowed
+    }
+
+    /// Shapes the packets and sends the allowed ones to the transmit queue.
+    pub fn transmit(&mut self, pkts: Vec<Ethernet>) {
+        let allowed = self.shape(pkts);
+        self.queue
+            .transmit(allowed.into_iter().map(Packet::reset).collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn srtcm_colors() {
+        let mut meter = SrTcmMeter::new(1000, 1500, 1500).unwrap();
+        let now = tsc_cycles();
+
+        assert_eq!(MeterColor::Green, meter.check_and_update(1000, now));
+        assert_eq!(MeterColor::Yellow, meter.check_and_update(1000, now));
+        assert_eq!(MeterColor::Red, meter.check_and_update(1000, now));
+    }
+
+    #[capsule::test]
+    fn trtcm_colors() {
+        let mut meter = TrTcmMeter::new(1000, 2000, 1000, 2000).unwrap();
+        let now = tsc_cycles();
+
+        assert_eq!(MeterColor::Green, meter.check_and_update(1000, now));
+        assert_eq!(MeterColor::Yellow, meter.check_and_update(1000, now));
+        assert_eq!(MeterColor::Red, meter.check_and_update(1000, now));
+    }
+
+    #[capsule::test]
+    fn invalid_meter_params() {
+        assert!(SrTcmMeter::new(0, 1500, 1500).is_err());
+        assert!(TrTcmMeter::new(2000, 1000, 1000, 1000).is_err());
+    }
+}
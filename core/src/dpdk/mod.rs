@@ -16,57 +16,237 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
+//! Safe wrappers around the DPDK libraries, devices and memory.
+
+mod acl;
+mod allocator;
+mod bonding;
+mod callback;
+mod compress;
+mod counters;
+mod crypto;
+mod device;
+mod eal;
+mod eventdev;
+mod fcs;
+mod flow;
+mod flow_ctrl;
+mod generator;
+mod gro;
+mod gso;
+mod hash;
+mod histogram;
+mod hugepage;
+mod ip_frag;
+mod ipsec;
 mod kni;
+mod lcore;
+mod link;
+mod logger;
+mod lpm;
+mod lpm6;
+mod mac_filter;
 mod mbuf;
 mod mempool;
+mod memzone;
+mod meta;
+mod meter;
+mod netns;
+mod numa;
+mod offload;
+mod pcap_file;
+mod pcapng;
+mod pci;
+mod pdump;
 mod port;
+mod port_stats;
+mod rcu;
+mod reorder;
+mod ring;
+mod rss;
+mod sched;
+mod segmented;
+mod shared;
 #[cfg(feature = "metrics")]
 mod stats;
+mod timer;
+mod timestamp;
+mod version;
+mod vfio;
+mod vhost;
+mod virtio;
 
 #[allow(unreachable_pub)] // https://github.com/rust-lang/rust/issues/57411
+pub use self::acl::*;
+#[allow(unreachable_pub)]
+pub use self::allocator::*;
+#[allow(unreachable_pub)]
+pub use self::bonding::*;
+#[allow(unreachable_pub)]
+pub use self::callback::*;
+#[allow(unreachable_pub)]
+pub use self::compress::*;
+#[allow(unreachable_pub)]
+pub use self::counters::*;
+#[allow(unreachable_pub)]
+pub use self::crypto::*;
+#[allow(unreachable_pub)]
+pub use self::device::*;
+#[allow(unreachable_pub)]
+pub use self::eal::*;
+#[allow(unreachable_pub)]
+pub use self::eventdev::*;
+#[allow(unreachable_pub)]
+pub use self::fcs::*;
+#[allow(unreachable_pub)]
+pub use self::flow::*;
+#[allow(unreachable_pub)]
+pub use self::flow_ctrl::*;
+#[allow(unreachable_pub)]
+pub use self::generator::*;
+#[allow(unreachable_pub)]
+pub use self::gro::*;
+#[allow(unreachable_pub)]
+pub use self::gso::*;
+#[allow(unreachable_pub)]
+pub use self::hash::*;
+#[allow(unreachable_pub)]
+pub use self::histogram::*;
+#[allow(unreachable_pub)]
+pub use self::hugepage::*;
+#[allow(unreachable_pub)]
+pub use self::ip_frag::*;
+#[allow(unreachable_pub)]
+pub use self::ipsec::*;
+#[allow(unreachable_pub)]
 pub use self::kni::*;
 #[allow(unreachable_pub)]
+pub use self::lcore::*;
+#[allow(unreachable_pub)]
+pub use self::link::*;
+#[allow(unreachable_pub)]
+pub use self::logger::*;
+#[allow(unreachable_pub)]
+pub use self::lpm::*;
+#[allow(unreachable_pub)]
+pub use self::lpm6::*;
+#[allow(unreachable_pub)]
+pub use self::mac_filter::*;
+#[allow(unreachable_pub)]
 pub use self::mbuf::*;
 pub(crate) use self::mempool::*;
 #[allow(unreachable_pub)]
+pub use self::memzone::*;
+#[allow(unreachable_pub)]
+pub use self::meta::*;
+#[allow(unreachable_pub)]
+pub use self::meter::*;
+#[allow(unreachable_pub)]
+pub use self::netns::*;
+#[allow(unreachable_pub)]
+pub use self::numa::*;
+#[allow(unreachable_pub)]
+pub use self::offload::*;
+#[allow(unreachable_pub)]
+pub use self::pcap_file::*;
+#[allow(unreachable_pub)]
+pub use self::pcapng::*;
+#[allow(unreachable_pub)]
+pub use self::pci::*;
+#[allow(unreachable_pub)]
+pub use self::pdump::*;
+#[allow(unreachable_pub)]
 pub use self::port::*;
+#[allow(unreachable_pub)]
+pub use self::port_stats::*;
+#[allow(unreachable_pub)]
+pub use self::rcu::*;
+#[allow(unreachable_pub)]
+pub use self::reorder::*;
+#[allow(unreachable_pub)]
+pub use self::ring::*;
+#[allow(unreachable_pub)]
+pub use self::rss::*;
+#[allow(unreachable_pub)]
+pub use self::sched::*;
+#[allow(unreachable_pub)]
+pub use self::segmented::*;
+#[allow(unreachable_pub)]
+pub use self::shared::*;
 #[cfg(feature = "metrics")]
 pub(crate) use self::stats::*;
+#[allow(unreachable_pub)]
+pub use self::timer::*;
+#[allow(unreachable_pub)]
+pub use self::timestamp::*;
+#[allow(unreachable_pub)]
+pub use self::version::*;
+#[allow(unreachable_pub)]
+pub use self::vfio::*;
+#[allow(unreachable_pub)]
+pub use self::vhost::*;
+#[allow(unreachable_pub)]
+pub use self::virtio::*;
 
-use crate::debug;
 use crate::ffi::{self, AsStr, ToCString, ToResult};
 use crate::net::MacAddr;
+use crate::{debug, ensure};
 use anyhow::Result;
 use std::cell::Cell;
 use std::fmt;
 use std::mem;
 use std::os::raw;
+use std::sync::atomic::{AtomicBool, Ordering};
 use thiserror::Error;
 
 /// An error generated in `libdpdk`.
 ///
 /// When an FFI call fails, the `errno` is translated into `DpdkError`.
 #[derive(Debug, Error)]
-#[error("{0}")]
-pub(crate) struct DpdkError(String);
+#[error("DPDK error {code}: {message}")]
+pub struct DpdkError {
+    code: i32,
+    message: String,
+}
 
 impl DpdkError {
     /// Returns the `DpdkError` for the most recent failure on the current
-    /// thread.
+    /// thread, read from the per-thread `rte_errno`.
     #[inline]
     pub(crate) fn new() -> Self {
-        DpdkError::from_errno(-1)
+        DpdkError::from_code(unsafe { ffi::_rte_errno() })
+    }
+
+    /// Returns the `DpdkError` for an error code. Functions that return
+    /// the error code directly return it negated, so the sign is ignored.
+    #[inline]
+    pub(crate) fn from_code(code: i32) -> Self {
+        let code = code.abs();
+        DpdkError {
+            code,
+            message: unsafe { ffi::rte_strerror(code).as_str().into() },
+        }
     }
 
-    /// Returns the `DpdkError` for a specific `errno`.
+    /// Returns the `DpdkError` for the return value of a failed function.
+    /// `-1` means the error code is in `rte_errno`.
     #[inline]
     fn from_errno(errno: raw::c_int) -> Self {
-        let errno = if errno == -1 {
-            unsafe { ffi::_rte_errno() }
+        if errno == -1 {
+            DpdkError::new()
         } else {
-            -errno
-        };
-        DpdkError(unsafe { ffi::rte_strerror(errno).as_str().into() })
+            DpdkError::from_code(errno)
+        }
+    }
+
+    /// Returns the error code.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// Returns the human-readable description of the error.
+    pub fn message(&self) -> &str {
+        &self.message
     }
 }
 
@@ -177,8 +357,17 @@ thread_local! {
     static CURRENT_CORE_ID: Cell<CoreId> = Cell::new(CoreId::ANY);
 }
 
+/// Whether the EAL is initialized. `rte_eal_init` can only be called once
+/// per process, even if it failed or the EAL is cleaned up.
+static EAL_INITIALIZED: AtomicBool = AtomicBool::new(false);
+
 /// Initializes the Environment Abstraction Layer (EAL).
 pub(crate) fn eal_init(args: Vec<String>) -> Result<()> {
+    ensure!(
+        !EAL_INITIALIZED.swap(true, Ordering::SeqCst),
+        EalError::AlreadyInitialized
+    );
+
     debug!(arguments=?args);
 
     let len = args.len() as raw::c_int;
@@ -207,12 +396,27 @@ pub(crate) fn eal_cleanup() -> Result<()> {
 }
 
 /// Returns the `MacAddr` of a port.
-fn eth_macaddr_get(port_id: u16) -> MacAddr {
+pub(crate) fn eth_macaddr_get(port_id: u16) -> MacAddr {
     let mut addr = ffi::rte_ether_addr::default();
     unsafe {
         ffi::rte_eth_macaddr_get(port_id, &mut addr);
     }
-    addr.addr_bytes.into()
+    addr.into()
+}
+
+/// Returns the number of TSC cycles since boot.
+#[inline]
+pub(crate) fn tsc_cycles() -> u64 {
+    unsafe { ffi::_rte_get_tsc_cycles() }
+}
+
+/// Returns the device info of a port.
+fn eth_dev_info_get(port_id: u16) -> ffi::rte_eth_dev_info {
+    let mut dev_info = ffi::rte_eth_dev_info::default();
+    unsafe {
+        ffi::rte_eth_dev_info_get(port_id, &mut dev_info);
+    }
+    dev_info
 }
 
 /// Frees the `rte_mbuf` in bulk.
@@ -242,3 +446,19 @@ pub(crate) fn mbuf_free_bulk(mbufs: Vec<*mut ffi::rte_mbuf>) {
         to_free.set_len(0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dpdk_error_from_code() {
+        let err = DpdkError::from_code(-libc::EINVAL);
+        assert_eq!(libc::EINVAL, err.code());
+        assert!(!err.message().is_empty());
+        assert_eq!(
+            format!("DPDK error {}: {}", libc::EINVAL, err.message()),
+            err.to_string()
+        );
+    }
+}
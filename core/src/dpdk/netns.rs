@@ -0,0 +1,121 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::{debug, error};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+
+/// The network namespace of the calling thread. `setns` only moves the
+/// calling thread, so `/proc/self`, which is the main thread, may point to
+/// a different namespace.
+const THREAD_NET_NS: &str = "/proc/thread-self/ns/net";
+
+/// Moves the calling thread into the namespace referred to by the file.
+fn setns(file: &File) -> io::Result<()> {
+    match unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Helper for running in Linux network namespaces.
+///
+/// Namespaces are per thread. Threads spawned after entering a namespace,
+/// including the EAL lcore threads spawned by `rte_eal_init`, inherit it.
+/// Requires `CAP_SYS_ADMIN`.
+///
+/// # Example
+///
+/// ```
+/// let _guard = NetworkNamespace::enter("/var/run/netns/blue")?;
+/// let _eal = EalConfig::new("myapp").network_namespace("blue").init()?;
+/// ```
+#[derive(Debug)]
+pub struct NetworkNamespace;
+
+impl NetworkNamespace {
+    /// Moves the calling thread into the network namespace at `path`, for
+    /// example `/var/run/netns/blue` or `/proc/{pid}/ns/net`.
+    ///
+    /// The returned guard moves the thread back to its original namespace
+    /// when dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an IO error if either namespace file cannot be opened, or if
+    /// the switch fails.
+    pub fn enter(path: &str) -> Result<NsGuard> {
+        let original = File::open(THREAD_NET_NS)
+            .with_context(|| format!("failed to open '{}'.", THREAD_NET_NS))?;
+        let target = File::open(path).with_context(|| format!("failed to open '{}'.", path))?;
+
+        setns(&target).with_context(|| format!("failed to enter network namespace '{}'.", path))?;
+
+        debug!(path, "entered network namespace.");
+        Ok(NsGuard { original })
+    }
+
+    /// Returns the inode number of the calling thread's network namespace.
+    /// Two threads are in the same namespace if the inode numbers match.
+    ///
+    /// # Errors
+    ///
+    /// Returns an IO error if the namespace file cannot be read.
+    pub fn current_ns_inode() -> Result<u64> {
+        let metadata = std::fs::metadata(THREAD_NET_NS)
+            .with_context(|| format!("failed to stat '{}'.", THREAD_NET_NS))?;
+        Ok(metadata.ino())
+    }
+}
+
+/// A guard that moves the thread back to its original network namespace
+/// when dropped.
+#[derive(Debug)]
+pub struct NsGuard {
+    original: File,
+}
+
+impl Drop for NsGuard {
+    fn drop(&mut self) {
+        if let Err(err) = setns(&self.original) {
+            error!(message = "failed to restore network namespace.", ?err);
+        } else {
+            debug!("restored network namespace.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_ns_inode() {
+        let inode = NetworkNamespace::current_ns_inode().unwrap();
+        let metadata = std::fs::metadata("/proc/self/ns/net").unwrap();
+        assert_eq!(metadata.ino(), inode);
+    }
+
+    #[test]
+    fn enter_missing_namespace() {
+        assert!(NetworkNamespace::enter("/var/run/netns/does-not-exist").is_err());
+    }
+}
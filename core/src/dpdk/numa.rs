@@ -0,0 +1,123 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::SocketId;
+use anyhow::Result;
+
+/// A resource allocated from the huge page memory of a NUMA socket.
+pub trait NeedsSocket {
+    /// Returns the socket the resource is allocated on. `SocketId::ANY`
+    /// means the socket is not known.
+    fn socket_id(&self) -> SocketId;
+}
+
+/// A resource that can be allocated on a chosen NUMA socket.
+pub trait Allocate: Sized {
+    /// The parameters other than the socket needed to create the resource.
+    type Params;
+
+    /// Creates the resource on the socket.
+    fn allocate(socket_id: SocketId, params: Self::Params) -> Result<Self>;
+}
+
+/// Helpers to keep the data structures on the same NUMA socket as the
+/// cores using them.
+///
+/// Accessing memory on a remote socket goes through the interconnect and
+/// is noticeably slower than accessing local memory.
+///
+/// # Example
+///
+/// ```
+/// let table = SocketMemory::allocate_on_socket::<LpmTable>(
+///     SocketMemory::current_lcore_socket(),
+///     ("routes".to_owned(), 1024),
+/// )?;
+/// SocketMemory::assert_local(&table);
+/// ```
+#[derive(Debug)]
+pub struct SocketMemory;
+
+impl SocketMemory {
+    /// Returns the socket of the core the current thread is running on.
+    #[inline]
+    pub fn current_lcore_socket() -> SocketId {
+        SocketId::current()
+    }
+
+    /// Asserts that the resource is allocated on the socket of the current
+    /// core. The check is skipped if either socket is unknown, and in
+    /// release builds.
+    ///
+    /// # Panics
+    ///
+    /// Panics in debug builds if the resource is on a remote socket.
+    #[inline]
+    pub fn assert_local<T: NeedsSocket>(val: &T) {
+        if cfg!(debug_assertions) {
+            let current = SocketMemory::current_lcore_socket();
+            let socket_id = val.socket_id();
+            assert!(
+                socket_id == SocketId::ANY || current == SocketId::ANY || socket_id == current,
+                "resource on {:?} is used from a core on {:?}.",
+                socket_id,
+                current
+            );
+        }
+    }
+
+    /// Creates the resource on the socket.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the resource's constructor.
+    pub fn allocate_on_socket<T: Allocate>(socket_id: SocketId, params: T::Params) -> Result<T> {
+        T::allocate(socket_id, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpdk::{HashTable, HashTableParams, LpmTable, Ring, RingFlags};
+
+    #[capsule::test]
+    fn allocate_on_current_socket() {
+        let socket_id = SocketMemory::current_lcore_socket();
+
+        let ring = SocketMemory::allocate_on_socket::<Ring<u32>>(
+            socket_id,
+            ("numa_ring0".to_owned(), 8, RingFlags::empty()),
+        )
+        .unwrap();
+        assert_eq!(socket_id, ring.socket_id());
+        SocketMemory::assert_local(&ring);
+
+        let lpm =
+            SocketMemory::allocate_on_socket::<LpmTable>(socket_id, ("numa_lpm0".to_owned(), 16))
+                .unwrap();
+        assert_eq!(socket_id, NeedsSocket::socket_id(&lpm));
+        SocketMemory::assert_local(&lpm);
+
+        let table = SocketMemory::allocate_on_socket::<HashTable<[u8; 4], u32>>(
+            socket_id,
+            HashTableParams::new("numa_hash0", 64),
+        )
+        .unwrap();
+        SocketMemory::assert_local(&table);
+    }
+}
@@ -0,0 +1,139 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::ffi;
+use bitflags::bitflags;
+
+bitflags! {
+    /// The receive offload capabilities of an Ethernet device.
+    #[derive(Default)]
+    pub struct RxOffloadFlags: u64 {
+        /// Strips the VLAN tag.
+        const VLAN_STRIP = ffi::DEV_RX_OFFLOAD_VLAN_STRIP as u64;
+        /// Validates the IPv4 header checksum.
+        const IPV4_CKSUM = ffi::DEV_RX_OFFLOAD_IPV4_CKSUM as u64;
+        /// Validates the UDP checksum.
+        const UDP_CKSUM = ffi::DEV_RX_OFFLOAD_UDP_CKSUM as u64;
+        /// Validates the TCP checksum.
+        const TCP_CKSUM = ffi::DEV_RX_OFFLOAD_TCP_CKSUM as u64;
+        /// Aggregates TCP segments with large receive offload.
+        const TCP_LRO = ffi::DEV_RX_OFFLOAD_TCP_LRO as u64;
+        /// Strips the outer and inner VLAN tags of QinQ packets.
+        const QINQ_STRIP = ffi::DEV_RX_OFFLOAD_QINQ_STRIP as u64;
+        /// Validates the outer IPv4 header checksum of tunneled packets.
+        const OUTER_IPV4_CKSUM = ffi::DEV_RX_OFFLOAD_OUTER_IPV4_CKSUM as u64;
+        /// Strips the MACsec header.
+        const MACSEC_STRIP = ffi::DEV_RX_OFFLOAD_MACSEC_STRIP as u64;
+        /// Splits the header and payload into separate buffers.
+        const HEADER_SPLIT = ffi::DEV_RX_OFFLOAD_HEADER_SPLIT as u64;
+        /// Filters packets by VLAN ID.
+        const VLAN_FILTER = ffi::DEV_RX_OFFLOAD_VLAN_FILTER as u64;
+        /// Enables extended VLAN, or QinQ.
+        const VLAN_EXTEND = ffi::DEV_RX_OFFLOAD_VLAN_EXTEND as u64;
+        /// Receives frames larger than the standard Ethernet MTU.
+        const JUMBO_FRAME = ffi::DEV_RX_OFFLOAD_JUMBO_FRAME as u64;
+        /// Receives packets into multi-segment mbufs.
+        const SCATTER = ffi::DEV_RX_OFFLOAD_SCATTER as u64;
+        /// Timestamps the received packets.
+        const TIMESTAMP = ffi::DEV_RX_OFFLOAD_TIMESTAMP as u64;
+        /// Processes inline IPsec.
+        const SECURITY = ffi::DEV_RX_OFFLOAD_SECURITY as u64;
+        /// Keeps the Ethernet CRC.
+        const KEEP_CRC = ffi::DEV_RX_OFFLOAD_KEEP_CRC as u64;
+        /// Validates the SCTP checksum.
+        const SCTP_CKSUM = ffi::DEV_RX_OFFLOAD_SCTP_CKSUM as u64;
+        /// Validates the outer UDP checksum of tunneled packets.
+        const OUTER_UDP_CKSUM = ffi::DEV_RX_OFFLOAD_OUTER_UDP_CKSUM as u64;
+        /// Delivers the RSS hash of the received packets.
+        const RSS_HASH = ffi::DEV_RX_OFFLOAD_RSS_HASH as u64;
+
+        /// Validates the IPv4, UDP and TCP checksums.
+        const CHECKSUM = Self::IPV4_CKSUM.bits | Self::UDP_CKSUM.bits | Self::TCP_CKSUM.bits;
+        /// All the VLAN offloads.
+        const VLAN = Self::VLAN_STRIP.bits
+            | Self::VLAN_FILTER.bits
+            | Self::VLAN_EXTEND.bits
+            | Self::QINQ_STRIP.bits;
+    }
+}
+
+bitflags! {
+    /// The transmit offload capabilities of an Ethernet device.
+    #[derive(Default)]
+    pub struct TxOffloadFlags: u64 {
+        /// Inserts the VLAN tag.
+        const VLAN_INSERT = ffi::DEV_TX_OFFLOAD_VLAN_INSERT as u64;
+        /// Computes the IPv4 header checksum.
+        const IPV4_CKSUM = ffi::DEV_TX_OFFLOAD_IPV4_CKSUM as u64;
+        /// Computes the UDP checksum.
+        const UDP_CKSUM = ffi::DEV_TX_OFFLOAD_UDP_CKSUM as u64;
+        /// Computes the TCP checksum.
+        const TCP_CKSUM = ffi::DEV_TX_OFFLOAD_TCP_CKSUM as u64;
+        /// Computes the SCTP checksum.
+        const SCTP_CKSUM = ffi::DEV_TX_OFFLOAD_SCTP_CKSUM as u64;
+        /// Segments large TCP packets.
+        const TCP_TSO = ffi::DEV_TX_OFFLOAD_TCP_TSO as u64;
+        /// Segments large UDP packets.
+        const UDP_TSO = ffi::DEV_TX_OFFLOAD_UDP_TSO as u64;
+        /// Computes the outer IPv4 header checksum of tunneled packets.
+        const OUTER_IPV4_CKSUM = ffi::DEV_TX_OFFLOAD_OUTER_IPV4_CKSUM as u64;
+        /// Inserts the outer and inner VLAN tags of QinQ packets.
+        const QINQ_INSERT = ffi::DEV_TX_OFFLOAD_QINQ_INSERT as u64;
+        /// Segments large VXLAN tunneled packets.
+        const VXLAN_TNL_TSO = ffi::DEV_TX_OFFLOAD_VXLAN_TNL_TSO as u64;
+        /// Segments large GRE tunneled packets.
+        const GRE_TNL_TSO = ffi::DEV_TX_OFFLOAD_GRE_TNL_TSO as u64;
+        /// Segments large IP-in-IP tunneled packets.
+        const IPIP_TNL_TSO = ffi::DEV_TX_OFFLOAD_IPIP_TNL_TSO as u64;
+        /// Segments large Geneve tunneled packets.
+        const GENEVE_TNL_TSO = ffi::DEV_TX_OFFLOAD_GENEVE_TNL_TSO as u64;
+        /// Inserts the MACsec header.
+        const MACSEC_INSERT = ffi::DEV_TX_OFFLOAD_MACSEC_INSERT as u64;
+        /// Transmits on the same queue from multiple threads without locks.
+        const MT_LOCKFREE = ffi::DEV_TX_OFFLOAD_MT_LOCKFREE as u64;
+        /// Transmits multi-segment mbufs.
+        const MULTI_SEGS = ffi::DEV_TX_OFFLOAD_MULTI_SEGS as u64;
+        /// Releases mbufs without reference counting. All mbufs on the same
+        /// queue must come from the same mempool.
+        const MBUF_FAST_FREE = ffi::DEV_TX_OFFLOAD_MBUF_FAST_FREE as u64;
+        /// Processes inline IPsec.
+        const SECURITY = ffi::DEV_TX_OFFLOAD_SECURITY as u64;
+        /// Segments large packets of generic UDP tunnels.
+        const UDP_TNL_TSO = ffi::DEV_TX_OFFLOAD_UDP_TNL_TSO as u64;
+        /// Segments large packets of generic IP tunnels.
+        const IP_TNL_TSO = ffi::DEV_TX_OFFLOAD_IP_TNL_TSO as u64;
+        /// Computes the outer UDP checksum of tunneled packets.
+        const OUTER_UDP_CKSUM = ffi::DEV_TX_OFFLOAD_OUTER_UDP_CKSUM as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offload_flags_from_capa() {
+        let capa = (ffi::DEV_TX_OFFLOAD_IPV4_CKSUM | ffi::DEV_TX_OFFLOAD_TCP_CKSUM) as u64;
+        let flags = TxOffloadFlags::from_bits_truncate(capa);
+        assert!(flags.contains(TxOffloadFlags::IPV4_CKSUM | TxOffloadFlags::TCP_CKSUM));
+        assert!(!flags.contains(TxOffloadFlags::UDP_CKSUM));
+
+        assert!(RxOffloadFlags::CHECKSUM.contains(RxOffloadFlags::UDP_CKSUM));
+        assert_eq!(TxOffloadFlags::empty(), TxOffloadFlags::default());
+    }
+}
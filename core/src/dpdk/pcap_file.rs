@@ -0,0 +1,468 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{mbuf_free_bulk, tsc_cycles, Mbuf};
+use crate::ffi;
+use crate::packets::{Ethernet, Packet};
+use crate::{ensure, error, info};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// The magic number of a PCAP file with microsecond timestamps.
+pub(crate) const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+
+/// The magic number of a PCAP file with nanosecond timestamps.
+const PCAP_MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+
+/// The link type for Ethernet.
+pub(crate) const DLT_EN10MB: u32 = 1;
+
+/// The maximum captured length of a packet.
+pub(crate) const PCAP_SNAPSHOT_LEN: u32 = 65535;
+
+/// The number of packets sent in a burst when replaying without a rate.
+const REPLAY_BURST: usize = 32;
+
+/// PCAP file errors.
+#[derive(Debug, Error)]
+pub(crate) enum PcapFileError {
+    /// The file does not start with a PCAP magic number.
+    #[error("Invalid PCAP magic number {0:#010x}.")]
+    InvalidMagic(u32),
+
+    /// The link type is not Ethernet.
+    #[error("Unsupported PCAP link type {0}.")]
+    UnsupportedLinkType(u32),
+
+    /// The record is longer than the snapshot length.
+    #[error("PCAP record length {0} exceeds the snapshot length {1}.")]
+    RecordTooLong(u32, u32),
+}
+
+/// A reader of PCAP files, for replaying recorded traffic.
+///
+/// Both byte orders are supported, detected from the magic number. Only
+/// files with the Ethernet link type can be read. Each packet is copied
+/// into an `Mbuf` allocated from the `Mempool` assigned to the current
+/// executing thread.
+///
+/// # Example
+///
+/// ```
+/// for packet in PcapReader::open("trace.pcap")? {
+///     let ethernet = packet?;
+/// }
+/// ```
+pub struct PcapReader {
+    path: String,
+    reader: BufReader<File>,
+    big_endian: bool,
+    snaplen: u32,
+}
+
+impl PcapReader {
+    /// Opens a PCAP file and reads its global header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, if the magic number
+    /// is invalid, or if the link type is not Ethernet.
+    pub fn open(path: &str) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut header = [0; 24];
+        reader.read_exact(&mut header)?;
+
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let big_endian = match magic {
+            PCAP_MAGIC | PCAP_MAGIC_NANOS => false,
+            _ if magic.swap_bytes() == PCAP_MAGIC || magic.swap_bytes() == PCAP_MAGIC_NANOS => true,
+            _ => return Err(PcapFileError::InvalidMagic(magic).into()),
+        };
+
+        let read_u32 = |bytes: &[u8]| {
+            let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            }
+        };
+
+        let snaplen = read_u32(&header[16..20]);
+        let link_type = read_u32(&header[20..24]);
+        ensure!(
+            link_type == DLT_EN10MB,
+            PcapFileError::UnsupportedLinkType(link_type)
+        );
+
+        info!(path, big_endian, snaplen, "opened PCAP file.");
+        Ok(PcapReader {
+            path: path.to_owned(),
+            reader,
+            big_endian,
+            snaplen,
+        })
+    }
+
+    #[inline]
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// Reads the next record. Returns `None` at the end of the file.
+    fn read_record(&mut self) -> Result<Option<Ethernet>> {
+        // a clean end of file can only happen at a record boundary.
+        let _ts_sec = match self.read_u32() {
+            Ok(ts_sec) => ts_sec,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let _ts_frac = self.read_u32()?;
+        let incl_len = self.read_u32()?;
+        let _orig_len = self.read_u32()?;
+
+        let max_len = self.snaplen.max(PCAP_SNAPSHOT_LEN);
+        ensure!(
+            incl_len <= max_len,
+            PcapFileError::RecordTooLong(incl_len, max_len)
+        );
+
+        let mut data = vec![0; incl_len as usize];
+        self.reader.read_exact(&mut data)?;
+
+        let mbuf = Mbuf::from_bytes(&data)?;
+        mbuf.parse::<Ethernet>().map(Some)
+    }
+
+    /// Sends the remaining packets to a transmit queue of a port. Returns
+    /// the number of packets sent.
+    ///
+    /// If `pps` is set, the packets are paced at that rate. Otherwise they
+    /// are sent in bursts as fast as the queue accepts them. Packets the
+    /// queue cannot accept are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read.
+    pub fn replay_to_port(
+        &mut self,
+        port_id: u16,
+        queue_id: u16,
+        pps: Option<u64>,
+    ) -> Result<usize> {
+        ensure!(pps != Some(0), anyhow!("replay rate must be positive."));
+
+        let interval = pps.map(|pps| unsafe { ffi::rte_get_tsc_hz() } / pps);
+        let burst = if interval.is_some() { 1 } else { REPLAY_BURST };
+        let mut next_send = tsc_cycles();
+        let mut sent = 0;
+
+        loop {
+            let mut ptrs = Vec::with_capacity(burst);
+            while ptrs.len() < burst {
+                match self.read_record()? {
+                    Some(ethernet) => ptrs.push(ethernet.reset().into_ptr()),
+                    None => break,
+                }
+            }
+
+            if ptrs.is_empty() {
+                break;
+            }
+
+            if let Some(interval) = interval {
+                while tsc_cycles() < next_send {}
+                next_send += interval;
+            }
+
+            let count = unsafe {
+                ffi::_rte_eth_tx_burst(port_id, queue_id, ptrs.as_mut_ptr(), ptrs.len() as u16)
+            } as usize;
+            sent += count;
+
+            if count < ptrs.len() {
+                mbuf_free_bulk(ptrs.split_off(count));
+            }
+        }
+
+        info!(path = ?self.path, sent, "replayed PCAP file.");
+        Ok(sent)
+    }
+}
+
+impl Iterator for PcapReader {
+    type Item = Result<Ethernet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+impl fmt::Debug for PcapReader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PcapReader")
+            .field("path", &self.path)
+            .field("big_endian", &self.big_endian)
+            .field("snaplen", &self.snaplen)
+            .finish()
+    }
+}
+
+/// A writer of PCAP files, for saving processed packets.
+///
+/// The file is written in the native byte order with microsecond
+/// timestamps and the Ethernet link type. The writes are buffered, and flushed
+/// when the writer is dropped.
+///
+/// # Example
+///
+/// ```
+/// let mut writer = PcapWriter::create("out.pcap")?;
+/// writer.write_packet(&ethernet)?;
+/// ```
+pub struct PcapWriter {
+    path: String,
+    writer: BufWriter<File>,
+}
+
+impl PcapWriter {
+    /// Creates a PCAP file, truncating it if it exists, and writes the
+    /// global header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or written to.
+    pub fn create(path: &str) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(&PCAP_MAGIC.to_ne_bytes())?;
+        writer.write_all(&2u16.to_ne_bytes())?;
+        writer.write_all(&4u16.to_ne_bytes())?;
+        // timezone offset and timestamp accuracy, always 0.
+        writer.write_all(&0i32.to_ne_bytes())?;
+        writer.write_all(&0u32.to_ne_bytes())?;
+        writer.write_all(&PCAP_SNAPSHOT_LEN.to_ne_bytes())?;
+        writer.write_all(&DLT_EN10MB.to_ne_bytes())?;
+
+        info!(path, "created PCAP file.");
+        Ok(PcapWriter {
+            path: path.to_owned(),
+            writer,
+        })
+    }
+
+    /// Writes the record header and the data of a message buffer. The
+    /// record is timestamped with the current time.
+    pub(crate) fn write_mbuf(&mut self, mbuf: &Mbuf) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let orig_len = mbuf.pkt_len() as u32;
+        let incl_len = orig_len.min(PCAP_SNAPSHOT_LEN);
+
+        self.writer
+            .write_all(&(now.as_secs() as u32).to_ne_bytes())?;
+        self.writer.write_all(&now.subsec_micros().to_ne_bytes())?;
+        self.writer.write_all(&incl_len.to_ne_bytes())?;
+        self.writer.write_all(&orig_len.to_ne_bytes())?;
+
+        let mut remaining = incl_len as usize;
+        for segment in mbuf.segments() {
+            let len = segment.len().min(remaining);
+            self.writer.write_all(&segment[..len])?;
+            remaining -= len;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a packet record, timestamped with the current time. All the
+    /// segments of the packet are written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written to.
+    pub fn write_packet(&mut self, pkt: &Ethernet) -> Result<()> {
+        self.write_mbuf(pkt.mbuf())
+    }
+
+    /// Writes a batch of packet records.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written to.
+    pub fn write_packets(&mut self, pkts: &[Ethernet]) -> Result<()> {
+        pkts.iter().try_for_each(|pkt| self.write_packet(pkt))
+    }
+
+    /// Flushes the buffered records to the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written to.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PcapWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PcapWriter")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl Drop for PcapWriter {
+    fn drop(&mut self) {
+        if let Err(err) = self.writer.flush() {
+            error!(message = "failed to flush PCAP file.", path = ?self.path, ?err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::{IPV4_TCP_PACKET, IPV4_UDP_PACKET};
+    use std::env;
+    use std::fs;
+
+    fn write_pcap(name: &str, big_endian: bool, link_type: u32, packets: &[&[u8]]) -> String {
+        let to_bytes = |value: u32| {
+            if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            }
+        };
+
+        let mut buf = vec![];
+        buf.extend_from_slice(&to_bytes(PCAP_MAGIC));
+        // the major version 2 and minor version 4 in the file byte order.
+        buf.extend_from_slice(&to_bytes(if big_endian {
+            0x0002_0004
+        } else {
+            0x0004_0002
+        }));
+        buf.extend_from_slice(&to_bytes(0));
+        buf.extend_from_slice(&to_bytes(0));
+        buf.extend_from_slice(&to_bytes(PCAP_SNAPSHOT_LEN));
+        buf.extend_from_slice(&to_bytes(link_type));
+
+        for packet in packets {
+            buf.extend_from_slice(&to_bytes(1));
+            buf.extend_from_slice(&to_bytes(0));
+            buf.extend_from_slice(&to_bytes(packet.len() as u32));
+            buf.extend_from_slice(&to_bytes(packet.len() as u32));
+            buf.extend_from_slice(packet);
+        }
+
+        let path = env::temp_dir().join(name);
+        fs::write(&path, buf).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    #[capsule::test]
+    fn read_little_endian_pcap() {
+        let path = write_pcap(
+            "capsule_le.pcap",
+            false,
+            DLT_EN10MB,
+            &[&IPV4_TCP_PACKET, &IPV4_UDP_PACKET],
+        );
+
+        let packets = PcapReader::open(&path)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(2, packets.len());
+        assert_eq!(IPV4_TCP_PACKET.len(), packets[0].mbuf().data_len());
+        assert_eq!(IPV4_UDP_PACKET.len(), packets[1].mbuf().data_len());
+    }
+
+    #[capsule::test]
+    fn read_big_endian_pcap() {
+        let path = write_pcap("capsule_be.pcap", true, DLT_EN10MB, &[&IPV4_UDP_PACKET]);
+
+        let mut reader = PcapReader::open(&path).unwrap();
+        let ethernet = reader.next().unwrap().unwrap();
+        assert_eq!(IPV4_UDP_PACKET.len(), ethernet.mbuf().data_len());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn invalid_link_type() {
+        let path = write_pcap("capsule_link.pcap", false, 101, &[]);
+        assert!(PcapReader::open(&path).is_err());
+    }
+
+    #[test]
+    fn invalid_magic() {
+        let path = env::temp_dir().join("capsule_magic.pcap");
+        fs::write(&path, [0u8; 24]).unwrap();
+        assert!(PcapReader::open(path.to_str().unwrap()).is_err());
+    }
+
+    #[capsule::test]
+    fn write_and_read_pcap() {
+        let path = env::temp_dir().join("capsule_write.pcap");
+        let path = path.to_str().unwrap();
+
+        let packets = [&IPV4_TCP_PACKET[..], &IPV4_UDP_PACKET[..]]
+            .iter()
+            .map(|bytes| {
+                Mbuf::from_bytes(bytes)
+                    .unwrap()
+                    .parse::<Ethernet>()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        let mut writer = PcapWriter::create(path).unwrap();
+        writer.write_packets(&packets).unwrap();
+        writer.write_packet(&packets[0]).unwrap();
+        drop(writer);
+
+        let read = PcapReader::open(path)
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(3, read.len());
+        assert_eq!(IPV4_TCP_PACKET.len(), read[2].mbuf().data_len());
+
+        let bytes = fs::read(path).unwrap();
+        assert_eq!(PCAP_MAGIC.to_ne_bytes(), bytes[..4]);
+        assert_eq!(
+            &IPV4_UDP_PACKET[..],
+            &bytes[24 + 16 + IPV4_TCP_PACKET.len() + 16..][..IPV4_UDP_PACKET.len()]
+        );
+    }
+}
@@ -0,0 +1,370 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{Mbuf, DLT_EN10MB, PCAP_SNAPSHOT_LEN};
+use crate::packets::{Ethernet, Packet};
+use crate::{error, info};
+use anyhow::Result;
+use bitflags::bitflags;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use thiserror::Error;
+
+/// The block type of the Section Header Block.
+const SHB_TYPE: u32 = 0x0a0d_0d0a;
+
+/// The byte-order magic of the Section Header Block.
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+/// The block type of the Interface Description Block.
+const IDB_TYPE: u32 = 0x0000_0001;
+
+/// The block type of the Enhanced Packet Block.
+const EPB_TYPE: u32 = 0x0000_0006;
+
+/// The option code marking the end of the options.
+const OPT_ENDOFOPT: u16 = 0;
+
+/// The option code of a UTF-8 comment.
+const OPT_COMMENT: u16 = 1;
+
+/// The option code of the interface timestamp resolution.
+const IF_TSRESOL: u16 = 9;
+
+/// The option code of the Enhanced Packet Block flags.
+const EPB_FLAGS: u16 = 2;
+
+/// The timestamp resolution of the interfaces, as a negative power of 10.
+const TSRESOL_NANOS: u8 = 9;
+
+/// PCAP-ng file errors.
+#[derive(Debug, Error)]
+pub(crate) enum PcapNgError {
+    /// The packet refers to an interface that's not added to the file.
+    #[error("Unknown PCAP-ng interface {0:?}.")]
+    UnknownInterface(InterfaceId),
+}
+
+bitflags! {
+    /// The flags of an Enhanced Packet Block, for the direction and the
+    /// reception type of the packet.
+    pub struct EpbFlags: u32 {
+        /// The packet is received on the interface.
+        const INBOUND = 0b01;
+        /// The packet is sent from the interface.
+        const OUTBOUND = 0b10;
+        /// The packet is received as unicast.
+        const UNICAST = 1 << 2;
+        /// The packet is received as multicast.
+        const MULTICAST = 2 << 2;
+        /// The packet is received as broadcast.
+        const BROADCAST = 3 << 2;
+        /// The packet is received in promiscuous mode.
+        const PROMISCUOUS = 4 << 2;
+    }
+}
+
+/// The identifier of an interface in a PCAP-ng file.
+///
+/// Interfaces are numbered in the order they are added, starting from 0
+/// for the Ethernet interface every file starts with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InterfaceId(u32);
+
+impl InterfaceId {
+    /// The Ethernet interface added when the file is created.
+    pub const DEFAULT: Self = InterfaceId(0);
+}
+
+/// Appends an option to the body of a block, padded to 32 bits.
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_ne_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_ne_bytes());
+    body.extend_from_slice(value);
+    pad(body);
+}
+
+/// Pads the body of a block to 32 bits.
+fn pad(body: &mut Vec<u8>) {
+    body.resize((body.len() + 3) & !3, 0);
+}
+
+/// A writer of PCAP-ng files, with nanosecond timestamps.
+///
+/// The file is written in the native byte order, as a single section. The
+/// interfaces record their timestamps in nanoseconds, so hardware
+/// timestamps can be saved without losing precision. The writes are
+/// buffered, and flushed when the writer is dropped.
+///
+/// # Example
+///
+/// ```
+/// let mut writer = PcapNgWriter::create("out.pcapng")?;
+/// writer.write_packet(&ethernet, ts_ns)?;
+/// writer.flush_and_close()?;
+/// ```
+pub struct PcapNgWriter {
+    path: String,
+    writer: BufWriter<File>,
+    snap_lens: Vec<u32>,
+}
+
+impl PcapNgWriter {
+    /// Creates a PCAP-ng file, truncating it if it exists, and writes the
+    /// section header and the description of an Ethernet interface.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or written to.
+    pub fn create(path: &str) -> Result<Self> {
+        let mut writer = PcapNgWriter {
+            path: path.to_owned(),
+            writer: BufWriter::new(File::create(path)?),
+            snap_lens: vec![],
+        };
+
+        let mut body = vec![];
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_ne_bytes());
+        body.extend_from_slice(&1u16.to_ne_bytes());
+        body.extend_from_slice(&0u16.to_ne_bytes());
+        // the section length is not known upfront.
+        body.extend_from_slice(&(-1i64).to_ne_bytes());
+        writer.write_block(SHB_TYPE, &body)?;
+
+        writer.add_interface(DLT_EN10MB as u16, PCAP_SNAPSHOT_LEN)?;
+
+        info!(path, "created PCAP-ng file.");
+        Ok(writer)
+    }
+
+    /// Writes a block with the body, which must be padded to 32 bits.
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> Result<()> {
+        let total_len = (body.len() + 12) as u32;
+        self.writer.write_all(&block_type.to_ne_bytes())?;
+        self.writer.write_all(&total_len.to_ne_bytes())?;
+        self.writer.write_all(body)?;
+        self.writer.write_all(&total_len.to_ne_bytes())?;
+        Ok(())
+    }
+
+    /// Adds an interface to the file. A snapshot length of 0 means the
+    /// packets are not truncated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written to.
+    pub fn add_interface(&mut self, link_type: u16, snap_len: u32) -> Result<InterfaceId> {
+        let mut body = vec![];
+        body.extend_from_slice(&link_type.to_ne_bytes());
+        body.extend_from_slice(&0u16.to_ne_bytes());
+        body.extend_from_slice(&snap_len.to_ne_bytes());
+        push_option(&mut body, IF_TSRESOL, &[TSRESOL_NANOS]);
+        push_option(&mut body, OPT_ENDOFOPT, &[]);
+        self.write_block(IDB_TYPE, &body)?;
+
+        self.snap_lens.push(snap_len);
+        Ok(InterfaceId(self.snap_lens.len() as u32 - 1))
+    }
+
+    /// Writes the Enhanced Packet Block of a message buffer.
+    pub(crate) fn write_mbuf(
+        &mut self,
+        interface: InterfaceId,
+        mbuf: &Mbuf,
+        ts_ns: u64,
+        flags: Option<EpbFlags>,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        let snap_len = *self
+            .snap_lens
+            .get(interface.0 as usize)
+            .ok_or(PcapNgError::UnknownInterface(interface))?;
+
+        let orig_len = mbuf.pkt_len() as u32;
+        let captured_len = if snap_len == 0 {
+            orig_len
+        } else {
+            orig_len.min(snap_len)
+        };
+
+        let mut body = Vec::with_capacity(captured_len as usize + 32);
+        body.extend_from_slice(&interface.0.to_ne_bytes());
+        body.extend_from_slice(&((ts_ns >> 32) as u32).to_ne_bytes());
+        body.extend_from_slice(&(ts_ns as u32).to_ne_bytes());
+        body.extend_from_slice(&captured_len.to_ne_bytes());
+        body.extend_from_slice(&orig_len.to_ne_bytes());
+
+        let mut remaining = captured_len as usize;
+        for segment in mbuf.segments() {
+            let len = segment.len().min(remaining);
+            body.extend_from_slice(&segment[..len]);
+            remaining -= len;
+        }
+        pad(&mut body);
+
+        if flags.is_some() || comment.is_some() {
+            if let Some(flags) = flags {
+                push_option(&mut body, EPB_FLAGS, &flags.bits().to_ne_bytes());
+            }
+            if let Some(comment) = comment {
+                push_option(&mut body, OPT_COMMENT, comment.as_bytes());
+            }
+            push_option(&mut body, OPT_ENDOFOPT, &[]);
+        }
+
+        self.write_block(EPB_TYPE, &body)
+    }
+
+    /// Writes a packet received on the default Ethernet interface, with the
+    /// timestamp in nanoseconds since the Unix epoch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written to.
+    pub fn write_packet(&mut self, pkt: &Ethernet, ts_ns: u64) -> Result<()> {
+        self.write_mbuf(InterfaceId::DEFAULT, pkt.mbuf(), ts_ns, None, None)
+    }
+
+    /// Writes a packet on the interface, with the flags and the comment as
+    /// options of the packet block.
+    ///
+    /// # Errors
+    ///
+    /// Returns `PcapNgError::UnknownInterface` if the interface is not added
+    /// to the file, or an error if the file cannot be written to.
+    pub fn write_packet_with(
+        &mut self,
+        interface: InterfaceId,
+        pkt: &Ethernet,
+        ts_ns: u64,
+        flags: Option<EpbFlags>,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        self.write_mbuf(interface, pkt.mbuf(), ts_ns, flags, comment)
+    }
+
+    /// Flushes the buffered blocks and closes the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written to.
+    pub fn flush_and_close(mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_all()?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PcapNgWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PcapNgWriter")
+            .field("path", &self.path)
+            .field("interfaces", &self.snap_lens.len())
+            .finish()
+    }
+}
+
+impl Drop for PcapNgWriter {
+    fn drop(&mut self) {
+        if let Err(err) = self.writer.flush() {
+            error!(message = "failed to flush PCAP-ng file.", path = ?self.path, ?err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+    use std::convert::TryInto;
+    use std::env;
+    use std::fs;
+
+    fn read_u32(buf: &[u8], offset: usize) -> u32 {
+        u32::from_ne_bytes(buf[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[capsule::test]
+    fn write_pcapng() {
+        let path = env::temp_dir().join("capsule_write.pcapng");
+        let path = path.to_str().unwrap();
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET)
+            .unwrap()
+            .parse::<Ethernet>()
+            .unwrap();
+
+        let mut writer = PcapNgWriter::create(path).unwrap();
+        writer.write_packet(&packet, 0x1_2345_6789).unwrap();
+        writer.flush_and_close().unwrap();
+
+        let buf = fs::read(path).unwrap();
+        assert_eq!(SHB_TYPE, read_u32(&buf, 0));
+        assert_eq!(28, read_u32(&buf, 4));
+        assert_eq!(BYTE_ORDER_MAGIC, read_u32(&buf, 8));
+
+        assert_eq!(IDB_TYPE, read_u32(&buf, 28));
+        assert_eq!(32, read_u32(&buf, 32));
+
+        // the 52 bytes packet needs no padding.
+        let epb = &buf[60..];
+        assert_eq!(EPB_TYPE, read_u32(epb, 0));
+        assert_eq!(32 + 52, read_u32(epb, 4));
+        assert_eq!(0, read_u32(epb, 8));
+        assert_eq!(1, read_u32(epb, 12));
+        assert_eq!(0x2345_6789, read_u32(epb, 16));
+        assert_eq!(52, read_u32(epb, 20));
+        assert_eq!(52, read_u32(epb, 24));
+        assert_eq!(&IPV4_UDP_PACKET[..], &epb[28..80]);
+        assert_eq!(84, epb.len());
+    }
+
+    #[capsule::test]
+    fn write_pcapng_with_options() {
+        let path = env::temp_dir().join("capsule_options.pcapng");
+        let path = path.to_str().unwrap();
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET)
+            .unwrap()
+            .parse::<Ethernet>()
+            .unwrap();
+
+        let mut writer = PcapNgWriter::create(path).unwrap();
+        let interface = writer.add_interface(DLT_EN10MB as u16, 20).unwrap();
+        assert_eq!(InterfaceId(1), interface);
+        assert!(writer
+            .write_packet_with(InterfaceId(2), &packet, 0, None, None)
+            .is_err());
+
+        writer
+            .write_packet_with(interface, &packet, 0, Some(EpbFlags::OUTBOUND), Some("hi"))
+            .unwrap();
+        writer.flush_and_close().unwrap();
+
+        let buf = fs::read(path).unwrap();
+        let epb = &buf[28 + 32 + 32..];
+        assert_eq!(EPB_TYPE, read_u32(epb, 0));
+        // truncated to the snapshot length, then the flags, the comment
+        // and the end of options.
+        assert_eq!(32 + 20 + 8 + 8 + 4, read_u32(epb, 4));
+        assert_eq!(1, read_u32(epb, 8));
+        assert_eq!(20, read_u32(epb, 20));
+        assert_eq!(52, read_u32(epb, 24));
+        assert_eq!(EpbFlags::OUTBOUND.bits(), read_u32(epb, 52));
+        assert_eq!(&b"hi"[..], &epb[60..62]);
+    }
+}
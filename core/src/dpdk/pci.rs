@@ -0,0 +1,309 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::DpdkError;
+use crate::ffi::{self, AsStr, ToResult};
+use crate::{debug, ensure};
+use anyhow::Result;
+use std::ffi::CString;
+use std::fmt;
+use std::os::raw;
+use std::ptr;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The name of the PCI bus.
+const PCI_BUS: &str = "pci";
+
+/// PCI device address, in the domain:bus:device.function format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PciAddr {
+    /// The PCI domain.
+    pub domain: u16,
+    /// The bus number.
+    pub bus: u8,
+    /// The device number, between 0 and 31.
+    pub device: u8,
+    /// The function number, between 0 and 7.
+    pub function: u8,
+}
+
+impl fmt::Display for PciAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:02x}:{:02x}.{:x}",
+            self.domain, self.bus, self.device, self.function
+        )
+    }
+}
+
+impl From<ffi::rte_pci_addr> for PciAddr {
+    fn from(addr: ffi::rte_pci_addr) -> Self {
+        PciAddr {
+            domain: addr.domain as u16,
+            bus: addr.bus,
+            device: addr.devid,
+            function: addr.function,
+        }
+    }
+}
+
+/// Error returned when parsing a malformed PCI address.
+#[derive(Debug, Error)]
+#[error("Failed to parse '{0}' as PCI address.")]
+pub struct PciAddrParseError(String);
+
+impl FromStr for PciAddr {
+    type Err = PciAddrParseError;
+
+    /// Parses the address from either the `0000:00:1f.6` format, or the
+    /// short `00:1f.6` format without the domain. All the fields are hex.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || PciAddrParseError(s.to_owned());
+
+        let mut parts = s.rsplitn(2, '.');
+        let function = parts.next().ok_or_else(err)?;
+        let rest = parts.next().ok_or_else(err)?;
+
+        let parts = rest.split(':').collect::<Vec<_>>();
+        let (domain, bus, device) = match parts.as_slice() {
+            [domain, bus, device] => (*domain, *bus, *device),
+            [bus, device] => ("0", *bus, *device),
+            _ => return Err(err()),
+        };
+
+        let field = |part: &str, len| {
+            if part.is_empty() || part.len() > len {
+                Err(err())
+            } else {
+                u16::from_str_radix(part, 16).map_err(|_| err())
+            }
+        };
+
+        let addr = PciAddr {
+            domain: field(domain, 4)?,
+            bus: field(bus, 2)? as u8,
+            device: field(device, 2)? as u8,
+            function: field(function, 1)? as u8,
+        };
+
+        if addr.device > 0x1f || addr.function > 7 {
+            return Err(err());
+        }
+
+        Ok(addr)
+    }
+}
+
+/// Information about a DPDK-visible PCI device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PciDeviceInfo {
+    /// The address of the device.
+    pub addr: PciAddr,
+    /// The vendor identifier.
+    pub vendor_id: u16,
+    /// The device identifier.
+    pub device_id: u16,
+    /// The class code, made of the class, the subclass and the
+    /// programming interface.
+    pub class_code: u32,
+    /// The name of the driver the device is probed with, if any.
+    pub driver_name: Option<String>,
+}
+
+impl PciDeviceInfo {
+    fn from_raw(dev: &ffi::rte_device) -> Self {
+        let mut addr = ffi::rte_pci_addr::default();
+        let mut id = ffi::rte_pci_id::default();
+        unsafe {
+            ffi::_rte_pci_device_info(dev, &mut addr, &mut id);
+        }
+
+        let driver_name = if dev.driver.is_null() {
+            None
+        } else {
+            unsafe { Some((*dev.driver).name.as_str().to_owned()) }
+        };
+
+        PciDeviceInfo {
+            addr: addr.into(),
+            vendor_id: id.vendor_id,
+            device_id: id.device_id,
+            class_code: id.class_id,
+            driver_name,
+        }
+    }
+}
+
+/// Error indicating failed PCI device operations.
+#[derive(Debug, Error)]
+pub(crate) enum PciError {
+    /// The PCI bus is not registered with the EAL.
+    #[error("The PCI bus is not available.")]
+    NoBus,
+
+    /// The device is probed by a different driver than requested.
+    #[error("PCI device {0} is driven by '{1}' instead of '{2}'.")]
+    DriverMismatch(PciAddr, String, String),
+
+    /// The device is not found on the bus after probing.
+    #[error("PCI device {0} not found.")]
+    NotFound(PciAddr),
+}
+
+/// A device comparison function that matches every device.
+unsafe extern "C" fn match_any(
+    _dev: *const ffi::rte_device,
+    _data: *const raw::c_void,
+) -> raw::c_int {
+    0
+}
+
+/// Discovery and probing of the NICs on the PCI bus.
+///
+/// # Example
+///
+/// ```
+/// for info in PciDevice::enumerate()? {
+///     println!("{} {:04x}:{:04x}", info.addr, info.vendor_id, info.device_id);
+/// }
+///
+/// PciDevice::bind_driver(&"0000:00:1f.6".parse()?, "net_e1000_em")?;
+/// ```
+#[derive(Debug)]
+pub struct PciDevice;
+
+impl PciDevice {
+    /// Returns the PCI bus.
+    fn bus() -> Result<&'static ffi::rte_bus> {
+        let name = CString::new(PCI_BUS).unwrap();
+        let bus =
+            unsafe { ffi::rte_bus_find_by_name(name.as_ptr()).into_result(|_| PciError::NoBus)? };
+        Ok(unsafe { &*bus.as_ptr() })
+    }
+
+    /// Lists the devices on the PCI bus.
+    ///
+    /// The bus is scanned again first, so the list includes devices that
+    /// appeared after the EAL is initialized.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DpdkError` if the bus scan fails.
+    pub fn enumerate() -> Result<Vec<PciDeviceInfo>> {
+        let bus = PciDevice::bus()?;
+
+        if let Some(scan) = bus.scan {
+            unsafe {
+                scan().into_result(DpdkError::from_errno)?;
+            }
+        }
+
+        let find_device = bus.find_device.ok_or(PciError::NoBus)?;
+        let mut devices = vec![];
+        let mut dev = unsafe { find_device(ptr::null(), Some(match_any), ptr::null()) };
+
+        while !dev.is_null() {
+            devices.push(PciDeviceInfo::from_raw(unsafe { &*dev }));
+            dev = unsafe { find_device(dev, Some(match_any), ptr::null()) };
+        }
+
+        Ok(devices)
+    }
+
+    /// Probes the device at the address and checks that it's driven by
+    /// the named driver.
+    ///
+    /// The EAL picks the driver by matching the device identifiers against
+    /// the drivers registered for the PCI bus, so the device must already
+    /// be bound to a kernel module that the driver works with, for example
+    /// `vfio-pci` or `igb_uio`. If another driver claims the device, the
+    /// device is detached again.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DpdkError` if the probe fails, or `PciError::DriverMismatch`
+    /// if the device is driven by another driver.
+    pub fn bind_driver(addr: &PciAddr, driver: &str) -> Result<()> {
+        let bus = CString::new(PCI_BUS).unwrap();
+        let name = CString::new(addr.to_string()).unwrap();
+        let args = CString::new("").unwrap();
+
+        // a device that is already probed is fine, as long as the driver
+        // is the requested one.
+        let res = unsafe { ffi::rte_eal_hotplug_add(bus.as_ptr(), name.as_ptr(), args.as_ptr()) };
+        if res != -libc::EEXIST {
+            res.into_result(DpdkError::from_errno)?;
+        }
+
+        let info = PciDevice::enumerate()?
+            .into_iter()
+            .find(|info| info.addr == *addr)
+            .ok_or(PciError::NotFound(*addr))?;
+        let actual = info.driver_name.unwrap_or_default();
+
+        if actual != driver {
+            unsafe {
+                ffi::rte_eal_hotplug_remove(bus.as_ptr(), name.as_ptr());
+            }
+        }
+
+        ensure!(
+            actual == driver,
+            PciError::DriverMismatch(*addr, actual, driver.to_owned())
+        );
+
+        debug!(%addr, driver, "probed PCI device.");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pci_addr() {
+        let addr = "0000:00:1f.6".parse::<PciAddr>().unwrap();
+        assert_eq!(
+            PciAddr {
+                domain: 0,
+                bus: 0,
+                device: 0x1f,
+                function: 6,
+            },
+            addr
+        );
+        assert_eq!("0000:00:1f.6", addr.to_string());
+
+        let addr = "03:00.1".parse::<PciAddr>().unwrap();
+        assert_eq!("0000:03:00.1", addr.to_string());
+    }
+
+    #[test]
+    fn parse_bad_pci_addr() {
+        assert!("".parse::<PciAddr>().is_err());
+        assert!("0000:00:1f".parse::<PciAddr>().is_err());
+        assert!("0000:00:20.0".parse::<PciAddr>().is_err());
+        assert!("0000:00:1f.8".parse::<PciAddr>().is_err());
+        assert!("00000:00:1f.6".parse::<PciAddr>().is_err());
+        assert!("0000:000:1f.6".parse::<PciAddr>().is_err());
+        assert!("0000:00:1g.6".parse::<PciAddr>().is_err());
+    }
+}
@@ -0,0 +1,286 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, Mbuf, MempoolError, PcapWriter, SocketId, MEMPOOL};
+use crate::ffi::{self, AsStr, ToCString, ToResult};
+use crate::packets::{Ethernet, Packet};
+use crate::{debug, info};
+use anyhow::Result;
+use std::fmt;
+use std::os::raw;
+use std::ptr::{self, NonNull};
+use std::sync::Arc;
+
+/// Captures both the received and the transmitted packets. The flags are
+/// an anonymous enum in `rte_pdump.h`.
+const PDUMP_FLAG_RXTX: u32 = 3;
+
+/// A ring the captured packets are copied to.
+///
+/// `Ring<T>` boxes its items, so it cannot receive the raw mbufs that
+/// `rte_pdump` enqueues. The capture ring stores the mbuf pointers as is,
+/// and is always multi-producer and multi-consumer as `rte_pdump`
+/// requires.
+pub struct CaptureRing {
+    raw: NonNull<ffi::rte_ring>,
+}
+
+impl CaptureRing {
+    /// Creates a new capture ring.
+    ///
+    /// `name` must be unique and `capacity` must be a power of 2.
+    ///
+    /// # Errors
+    ///
+    /// If the name is already used, or `capacity` is invalid, or the
+    /// allocation fails, `DpdkError` is returned.
+    pub fn new(name: &str, capacity: usize, socket_id: SocketId) -> Result<Self> {
+        let raw = unsafe {
+            ffi::rte_ring_create(
+                name.into_cstring().as_ptr(),
+                capacity as raw::c_uint,
+                socket_id.raw(),
+                0,
+            )
+            .into_result(|_| DpdkError::new())?
+        };
+
+        debug!("created capture ring {}.", name);
+        Ok(CaptureRing { raw })
+    }
+
+    /// Dequeues up to `max` captured packets.
+    pub fn dequeue_burst(&self, max: usize) -> Vec<Mbuf> {
+        let mut ptrs = Vec::with_capacity(max);
+
+        unsafe {
+            let count = ffi::_rte_ring_dequeue_burst(
+                self.raw.as_ptr(),
+                ptrs.as_mut_ptr(),
+                max as raw::c_uint,
+                ptr::null_mut(),
+            ) as usize;
+            ptrs.set_len(count);
+
+            ptrs.into_iter()
+                .map(|ptr| Mbuf::from_ptr(ptr as *mut ffi::rte_mbuf))
+                .collect()
+        }
+    }
+}
+
+impl fmt::Debug for CaptureRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = unsafe { self.raw.as_ref() };
+        f.debug_struct(raw.name[..].as_str())
+            .field("capacity", &raw.capacity)
+            .finish()
+    }
+}
+
+impl Drop for CaptureRing {
+    fn drop(&mut self) {
+        // frees the captured packets still in the ring.
+        while !self.dequeue_burst(32).is_empty() {}
+
+        unsafe {
+            ffi::rte_ring_free(self.raw.as_ptr());
+        }
+    }
+}
+
+unsafe impl Send for CaptureRing {}
+unsafe impl Sync for CaptureRing {}
+
+/// Live packet capture through `rte_pdump`.
+///
+/// The primary process must call `PdumpCapture::init` to serve capture
+/// requests. Captures are then started from a secondary process, and the
+/// primary copies the packets of the port queue into the capture ring.
+#[derive(Debug)]
+pub struct PdumpCapture;
+
+impl PdumpCapture {
+    /// Initializes the packet capture framework in the primary process.
+    ///
+    /// # Errors
+    ///
+    /// If the initialization fails, `DpdkError` is returned.
+    pub fn init() -> Result<()> {
+        unsafe {
+            ffi::rte_pdump_init().into_result(DpdkError::from_errno)?;
+        }
+        Ok(())
+    }
+
+    /// Uninitializes the packet capture framework.
+    pub fn uninit() {
+        unsafe {
+            ffi::rte_pdump_uninit();
+        }
+    }
+
+    /// Starts capturing the received and transmitted packets of a port
+    /// queue into the ring. `u16::MAX` captures all the queues of the
+    /// port. The copies are allocated from the `Mempool` assigned to the
+    /// current executing thread.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MempoolError::NotFound` if invoked from a thread not
+    /// managed by the `Runtime`. If the capture cannot be enabled,
+    /// `DpdkError` is returned.
+    pub fn start(port_id: u16, queue_id: u16, ring: Arc<CaptureRing>) -> Result<PdumpHandle> {
+        let mempool = NonNull::new(MEMPOOL.with(|tls| tls.get()))
+            .ok_or_else(|| MempoolError::NotFound(SocketId::current()))?;
+
+        unsafe {
+            ffi::rte_pdump_enable(
+                port_id,
+                queue_id,
+                PDUMP_FLAG_RXTX,
+                ring.raw.as_ptr(),
+                mempool.as_ptr(),
+                ptr::null_mut(),
+            )
+            .into_result(DpdkError::from_errno)?;
+        }
+
+        info!(port_id, queue_id, "started packet capture.");
+        Ok(PdumpHandle {
+            port_id,
+            queue_id,
+            ring,
+            stopped: false,
+        })
+    }
+
+    /// Drains the captured packets from the ring and writes them to a
+    /// PCAP file, until the ring is empty or `max_packets` are written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be created or written to.
+    pub fn to_pcap_file(path: &str, ring: Arc<CaptureRing>, max_packets: usize) -> Result<()> {
+        let mut writer = PcapWriter::create(path)?;
+
+        let mut written = 0;
+        while written < max_packets {
+            let mbufs = ring.dequeue_burst((max_packets - written).min(32));
+            if mbufs.is_empty() {
+                break;
+            }
+
+            for mbuf in mbufs.iter() {
+                writer.write_mbuf(mbuf)?;
+            }
+            written += mbufs.len();
+        }
+
+        writer.flush()?;
+        info!(path, written, "wrote captured packets.");
+        Ok(())
+    }
+
+    /// Drains the captured packets from the ring as Ethernet frames.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a packet is not a valid Ethernet frame.
+    pub fn drain(ring: &CaptureRing, max: usize) -> Result<Vec<Ethernet>> {
+        ring.dequeue_burst(max)
+            .into_iter()
+            .map(|mbuf| mbuf.parse::<Ethernet>())
+            .collect()
+    }
+}
+
+/// A running packet capture. The capture is stopped when dropped.
+pub struct PdumpHandle {
+    port_id: u16,
+    queue_id: u16,
+    ring: Arc<CaptureRing>,
+    stopped: bool,
+}
+
+impl PdumpHandle {
+    /// Returns the ring the packets are captured to.
+    pub fn ring(&self) -> &Arc<CaptureRing> {
+        &self.ring
+    }
+
+    /// Stops the capture.
+    ///
+    /// # Errors
+    ///
+    /// If the capture cannot be disabled, `DpdkError` is returned.
+    pub fn stop(mut self) -> Result<()> {
+        self.disable()
+    }
+
+    fn disable(&mut self) -> Result<()> {
+        if !self.stopped {
+            self.stopped = true;
+            unsafe {
+                ffi::rte_pdump_disable(self.port_id, self.queue_id, PDUMP_FLAG_RXTX)
+                    .into_result(DpdkError::from_errno)?;
+            }
+            info!(
+                port_id = self.port_id,
+                queue_id = self.queue_id,
+                "stopped packet capture."
+            );
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PdumpHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PdumpHandle")
+            .field("port_id", &self.port_id)
+            .field("queue_id", &self.queue_id)
+            .field("ring", &self.ring)
+            .finish()
+    }
+}
+
+impl Drop for PdumpHandle {
+    fn drop(&mut self) {
+        let _ = self.disable();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn capture_ring_dequeue() {
+        let ring = CaptureRing::new("capture_test", 8, SocketId::ANY).unwrap();
+        let mbuf = Mbuf::new().unwrap();
+        let ptrs = [mbuf.into_ptr() as *mut raw::c_void];
+
+        unsafe {
+            ffi::_rte_ring_enqueue_burst(ring.raw.as_ptr(), ptrs.as_ptr(), 1, ptr::null_mut());
+        }
+
+        assert_eq!(1, ring.dequeue_burst(8).len());
+        assert!(ring.dequeue_burst(8).is_empty());
+    }
+}
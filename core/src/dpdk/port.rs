@@ -16,7 +16,11 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
-use super::{CoreId, Kni, KniBuilder, KniTxQueue, Mbuf, Mempool, MempoolMap, SocketId};
+use super::{
+    CoreId, DeviceInfo, FlowRule, InstalledFlowRule, Kni, KniBuilder, KniTxQueue, MacFilter, Mbuf,
+    Mempool, MempoolMap, MulticastFilter, RssConfig, RxOffloadFlags, SharedPacket, SocketId,
+    SymmetricRssKey, TxOffloadFlags,
+};
 use crate::dpdk::DpdkError;
 use crate::ffi::{self, AsStr, ToCString, ToResult};
 #[cfg(feature = "metrics")]
@@ -30,8 +34,18 @@ use std::collections::HashMap;
 use std::fmt;
 use std::os::raw;
 use std::ptr;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+/// The default maximum number of packets received in one burst.
+const DEFAULT_RX_BURST: u16 = 32;
+
+/// The default Ethernet MTU.
+pub(crate) const ETHER_MTU: usize = 1500;
+
+/// The Ethernet header and CRC overhead on top of the MTU.
+pub(crate) const ETHER_OVERHEAD: usize = 14 + 4;
+
 const DEFAULT_RSS_HF: u64 =
     (ffi::ETH_RSS_IP | ffi::ETH_RSS_TCP | ffi::ETH_RSS_UDP | ffi::ETH_RSS_SCTP) as u64;
 
@@ -61,6 +75,22 @@ impl PortId {
     pub(crate) fn raw(&self) -> u16 {
         self.0
     }
+
+    /// Returns whether the port is started.
+    pub(crate) fn is_started(self) -> bool {
+        unsafe {
+            let dev = ptr::addr_of!(ffi::rte_eth_devices)
+                .cast::<ffi::rte_eth_dev>()
+                .add(self.0 as usize);
+            (*(*dev).data).dev_started() == 1
+        }
+    }
+
+    /// Returns an error if the port is not started.
+    fn ensure_started(self) -> Result<()> {
+        ensure!(self.is_started(), PortError::NotStarted(self.0));
+        Ok(())
+    }
 }
 
 impl fmt::Debug for PortId {
@@ -112,6 +142,7 @@ pub struct PortQueue {
     port_id: PortId,
     rxq: RxQueueIndex,
     txq: TxQueueIndex,
+    rx_burst: u16,
     kni: Option<KniTxQueue>,
     #[cfg(feature = "metrics")]
     received: Option<Counter>,
@@ -123,40 +154,37 @@ pub struct PortQueue {
 
 impl PortQueue {
     #[cfg(not(feature = "metrics"))]
-    fn new(port: PortId, rxq: RxQueueIndex, txq: TxQueueIndex) -> Self {
+    fn new(port: PortId, rxq: RxQueueIndex, txq: TxQueueIndex, rx_burst: u16) -> Self {
         PortQueue {
             port_id: port,
             rxq,
             txq,
+            rx_burst,
             kni: None,
         }
     }
 
     #[cfg(feature = "metrics")]
-    fn new(port: PortId, rxq: RxQueueIndex, txq: TxQueueIndex) -> Self {
+    fn new(port: PortId, rxq: RxQueueIndex, txq: TxQueueIndex, rx_burst: u16) -> Self {
         PortQueue {
             port_id: port,
             rxq,
             txq,
+            rx_burst,
             kni: None,
             received: None,
             transmitted: None,
             dropped: None,
         }
     }
-    /// Receives a burst of packets from the receive queue, up to a maximum
-    /// of 32 packets.
+
+    /// Receives a burst of packets from the receive queue, up to the
+    /// maximum burst size configured for the port.
     pub(crate) fn receive(&self) -> Vec<Mbuf> {
-        const RX_BURST_MAX: usize = 32;
-        let mut ptrs = Vec::with_capacity(RX_BURST_MAX);
+        let mut ptrs = Vec::with_capacity(self.rx_burst as usize);
 
         let len = unsafe {
-            ffi::_rte_eth_rx_burst(
-                self.port_id.0,
-                self.rxq.0,
-                ptrs.as_mut_ptr(),
-                RX_BURST_MAX as u16,
-            )
+            ffi::_rte_eth_rx_burst(self.port_id.0, self.rxq.0, ptrs.as_mut_ptr(), self.rx_burst)
         };
 
         #[cfg(feature = "metrics")]
@@ -204,6 +232,63 @@ impl PortQueue {
         }
     }
 
+    /// Sends the shared packets to the transmit queue without copying
+    /// them. The clones still held elsewhere are not affected.
+    pub fn transmit_shared(&self, packets: Vec<SharedPacket>) {
+        self.transmit(
+            packets
+                .into_iter()
+                .map(SharedPacket::into_shared_mbuf)
+                .collect(),
+        );
+    }
+
+    /// Sends the packets to the transmit queue, retrying until either all
+    /// the packets are sent or the timeout expires. Returns the number of
+    /// packets sent.
+    ///
+    /// Unlike `transmit`, which gives up as soon as the queue stops making
+    /// progress, `drain` keeps retrying while the queue is full. Use it for
+    /// flushing out the remaining packets, for example on shutdown. The
+    /// packets not sent by the deadline are freed.
+    pub fn drain(&self, packets: Vec<Mbuf>, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        let mut ptrs = packets.into_iter().map(Mbuf::into_ptr).collect::<Vec<_>>();
+        let mut total = 0;
+
+        while total < ptrs.len() {
+            // the burst size is a `u16`, larger batches are sent in chunks.
+            let to_send = (ptrs.len() - total).min(u16::MAX as usize) as u16;
+            let sent = unsafe {
+                ffi::_rte_eth_tx_burst(
+                    self.port_id.0,
+                    self.txq.0,
+                    ptrs[total..].as_mut_ptr(),
+                    to_send,
+                )
+            };
+
+            if sent > 0 {
+                #[cfg(feature = "metrics")]
+                self.transmitted.as_ref().unwrap().record(sent as u64);
+
+                total += sent as usize;
+            }
+
+            if total < ptrs.len() && Instant::now() >= deadline {
+                let unsent = ptrs.split_off(total);
+
+                #[cfg(feature = "metrics")]
+                self.dropped.as_ref().unwrap().record(unsent.len() as u64);
+
+                super::mbuf_free_bulk(unsent);
+                break;
+            }
+        }
+
+        total
+    }
+
     /// Returns a handle to send packets to the associated KNI interface.
     pub fn kni(&self) -> Option<&KniTxQueue> {
         self.kni.as_ref()
@@ -255,6 +340,194 @@ impl PortQueue {
     pub fn mac_addr(&self) -> MacAddr {
         super::eth_macaddr_get(self.port_id.0)
     }
+
+    /// Returns the receive offload capabilities of the port.
+    pub fn rx_offload_capa(&self) -> RxOffloadFlags {
+        RxOffloadFlags::from_bits_truncate(super::eth_dev_info_get(self.port_id.0).rx_offload_capa)
+    }
+
+    /// Returns the transmit offload capabilities of the port.
+    pub fn tx_offload_capa(&self) -> TxOffloadFlags {
+        TxOffloadFlags::from_bits_truncate(super::eth_dev_info_get(self.port_id.0).tx_offload_capa)
+    }
+
+    /// Returns the receive side scaling configuration of the port.
+    ///
+    /// # Errors
+    ///
+    /// If the device does not support RSS, `DpdkError` is returned.
+    pub fn rss_conf(&self) -> Result<RssConfig> {
+        super::rss_hash_conf_get(self.port_id)
+    }
+
+    /// Updates the receive side scaling configuration of the port. The
+    /// change applies to all the queues of the port.
+    ///
+    /// # Errors
+    ///
+    /// If the device does not support RSS, or the hash key size does not
+    /// match the device's, or any of the hash functions is not supported,
+    /// `DpdkError` is returned.
+    pub fn set_rss_conf(&self, conf: &RssConfig) -> Result<()> {
+        super::rss_hash_update(self.port_id, conf)
+    }
+
+    /// Checks whether the flow rule is supported by the port without
+    /// installing it.
+    ///
+    /// # Errors
+    ///
+    /// If the device does not support the rule's pattern or actions,
+    /// `FlowError` is returned.
+    pub fn validate_flow_rule(&self, rule: &FlowRule) -> Result<()> {
+        rule.validate(self.port_id)
+    }
+
+    /// Installs the flow rule on the port. The rule is removed when the
+    /// returned handle is dropped.
+    ///
+    /// # Errors
+    ///
+    /// If the device does not support the rule's pattern or actions,
+    /// `FlowError` is returned.
+    pub fn install_flow_rule(&self, rule: &FlowRule) -> Result<InstalledFlowRule> {
+        rule.install(self.port_id)
+    }
+
+    /// Enables promiscuous mode, so the port receives all packets
+    /// regardless of the destination MAC address.
+    ///
+    /// # Errors
+    ///
+    /// If the port is not started, `PortError::NotStarted` is returned. If
+    /// the device does not support the mode, `DpdkError` is returned.
+    pub fn enable_promiscuous(&self) -> Result<()> {
+        self.port_id.ensure_started()?;
+        unsafe {
+            ffi::rte_eth_promiscuous_enable(self.port_id.0).into_result(DpdkError::from_errno)?;
+        }
+        Ok(())
+    }
+
+    /// Disables promiscuous mode.
+    ///
+    /// # Errors
+    ///
+    /// If the port is not started, `PortError::NotStarted` is returned. If
+    /// the device does not support the mode, `DpdkError` is returned.
+    pub fn disable_promiscuous(&self) -> Result<()> {
+        self.port_id.ensure_started()?;
+        unsafe {
+            ffi::rte_eth_promiscuous_disable(self.port_id.0).into_result(DpdkError::from_errno)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether promiscuous mode is enabled.
+    pub fn is_promiscuous(&self) -> bool {
+        unsafe { ffi::rte_eth_promiscuous_get(self.port_id.0) == 1 }
+    }
+
+    /// Enables all multicast mode, so the port receives all multicast
+    /// packets regardless of the joined groups.
+    ///
+    /// # Errors
+    ///
+    /// If the port is not started, `PortError::NotStarted` is returned. If
+    /// the device does not support the mode, `DpdkError` is returned.
+    pub fn enable_allmulticast(&self) -> Result<()> {
+        self.port_id.ensure_started()?;
+        unsafe {
+            ffi::rte_eth_allmulticast_enable(self.port_id.0).into_result(DpdkError::from_errno)?;
+        }
+        Ok(())
+    }
+
+    /// Disables all multicast mode.
+    ///
+    /// # Errors
+    ///
+    /// If the port is not started, `PortError::NotStarted` is returned. If
+    /// the device does not support the mode, `DpdkError` is returned.
+    pub fn disable_allmulticast(&self) -> Result<()> {
+        self.port_id.ensure_started()?;
+        unsafe {
+            ffi::rte_eth_allmulticast_disable(self.port_id.0).into_result(DpdkError::from_errno)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether all multicast mode is enabled.
+    pub fn is_allmulticast(&self) -> bool {
+        unsafe { ffi::rte_eth_allmulticast_get(self.port_id.0) == 1 }
+    }
+
+    /// Returns the MTU of the port.
+    ///
+    /// # Errors
+    ///
+    /// If the MTU can't be retrieved, `DpdkError` is returned.
+    pub fn mtu(&self) -> Result<u16> {
+        let mut mtu = 0;
+        unsafe {
+            ffi::rte_eth_dev_get_mtu(self.port_id.0, &mut mtu)
+                .into_result(DpdkError::from_errno)?;
+        }
+        Ok(mtu)
+    }
+
+    /// Sets the MTU of the port. The value is checked against the limits
+    /// of the Ethernet device.
+    ///
+    /// MTUs larger than `1500` also need jumbo frames enabled when the port
+    /// is configured, see `PortConfig::enable_jumbo_frames`. Some devices
+    /// only allow changing the MTU while the port is stopped.
+    ///
+    /// # Errors
+    ///
+    /// If the MTU is outside of the supported range, `PortError::InvalidMtu`
+    /// is returned. If the device fails to set the MTU, `DpdkError` is
+    /// returned.
+    pub fn set_mtu(&self, mtu: u16) -> Result<()> {
+        let info = DeviceInfo::query(self.port_id.0)?;
+        let min = info.min_mtu();
+        let max = info.max_mtu();
+        ensure!(
+            mtu >= min && mtu <= max,
+            PortError::InvalidMtu(mtu as usize, min, max)
+        );
+
+        unsafe {
+            ffi::rte_eth_dev_set_mtu(self.port_id.0, mtu).into_result(DpdkError::from_errno)?;
+        }
+
+        debug!(port = ?self.port_id, mtu, "set MTU.");
+        Ok(())
+    }
+
+    /// Returns the largest frame the port can receive, including the
+    /// Ethernet header and CRC, as reported by the device.
+    ///
+    /// # Errors
+    ///
+    /// If the device info can't be retrieved, `DpdkError` is returned.
+    pub fn max_frame_size(&self) -> Result<u32> {
+        Ok(DeviceInfo::query(self.port_id.0)?.max_rx_pktlen())
+    }
+
+    /// Returns the unicast MAC address filter of the port.
+    ///
+    /// # Errors
+    ///
+    /// If the device info can't be retrieved, `DpdkError` is returned.
+    pub fn mac_filter(&self) -> Result<MacFilter> {
+        MacFilter::new(self.port_id)
+    }
+
+    /// Returns an empty multicast filter for the port.
+    pub fn multicast_filter(&self) -> MulticastFilter {
+        MulticastFilter::new(self.port_id)
+    }
 }
 
 /// Error indicating failed to initialize the port.
@@ -276,6 +549,30 @@ pub(crate) enum PortError {
     /// assigned to the port.
     #[error("Insufficient number of TX queues '{0}'.")]
     InsufficientTxQueues(usize),
+
+    /// The receive burst size is 0 or exceeds the receive queue capacity.
+    #[error("Invalid RX burst size '{0}'.")]
+    InvalidRxBurst(usize),
+
+    /// The MTU is outside of the range supported by the device.
+    #[error("MTU {0} is not within the supported range of {1} to {2}.")]
+    InvalidMtu(usize, u16, u16),
+
+    /// The MTU needs jumbo frames, which the device doesn't support.
+    #[error("MTU {0} requires jumbo frames, which are not supported.")]
+    JumboFramesNotSupported(usize),
+
+    /// The receive offloads are not supported by the device.
+    #[error("RX offloads {0:?} are not supported.")]
+    UnsupportedRxOffloads(RxOffloadFlags),
+
+    /// The transmit offloads are not supported by the device.
+    #[error("TX offloads {0:?} are not supported.")]
+    UnsupportedTxOffloads(TxOffloadFlags),
+
+    /// The operation requires the port to be started.
+    #[error("Port {0} is not started.")]
+    NotStarted(u16),
 }
 
 /// An Ethernet device port.
@@ -307,6 +604,16 @@ impl Port {
         super::eth_macaddr_get(self.id.0)
     }
 
+    /// Returns the receive offload capabilities of the port.
+    pub(crate) fn rx_offload_capa(&self) -> RxOffloadFlags {
+        RxOffloadFlags::from_bits_truncate(self.dev_info.rx_offload_capa)
+    }
+
+    /// Returns the transmit offload capabilities of the port.
+    pub(crate) fn tx_offload_capa(&self) -> TxOffloadFlags {
+        TxOffloadFlags::from_bits_truncate(self.dev_info.tx_offload_capa)
+    }
+
     /// Returns the available port queues.
     pub(crate) fn queues(&self) -> &HashMap<CoreId, PortQueue> {
         &self.queues
@@ -343,8 +650,8 @@ impl Port {
     }
 
     #[cfg(feature = "metrics")]
-    pub(crate) fn stats(&self) -> super::PortStats {
-        super::PortStats::build(self)
+    pub(crate) fn stats(&self) -> super::PortStatsCollector {
+        super::PortStatsCollector::build(self)
     }
 }
 
@@ -356,8 +663,8 @@ impl fmt::Debug for Port {
             .field("port", &self.id.0)
             .field("mac", &format_args!("\"{}\"", self.mac_addr()))
             .field("driver", &info.driver_name.as_str())
-            .field("rx_offload", &format_args!("{:#x}", info.rx_offload_capa))
-            .field("tx_offload", &format_args!("{:#x}", info.tx_offload_capa))
+            .field("rx_offload", &self.rx_offload_capa())
+            .field("tx_offload", &self.tx_offload_capa())
             .field("max_rxq", &info.max_rx_queues)
             .field("max_txq", &info.max_tx_queues)
             .field("socket", &self.id.socket_id().map_or(-1, |s| s.0))
@@ -385,6 +692,11 @@ pub(crate) struct PortBuilder<'a> {
     mempools: MempoolMap<'a>,
     rxd: u16,
     txd: u16,
+    rx_burst: u16,
+    mtu: Option<u16>,
+    rx_offloads: RxOffloadFlags,
+    tx_offloads: TxOffloadFlags,
+    rss_key: Option<Vec<u8>>,
 }
 
 impl<'a> PortBuilder<'a> {
@@ -421,6 +733,11 @@ impl<'a> PortBuilder<'a> {
             mempools: Default::default(),
             rxd: 0,
             txd: 0,
+            rx_burst: DEFAULT_RX_BURST,
+            mtu: None,
+            rx_offloads: RxOffloadFlags::empty(),
+            tx_offloads: TxOffloadFlags::empty(),
+            rss_key: None,
         })
     }
 
@@ -490,6 +807,90 @@ impl<'a> PortBuilder<'a> {
         Ok(self)
     }
 
+    /// Sets the maximum number of packets to receive in one burst.
+    ///
+    /// # Errors
+    ///
+    /// If the burst size is 0 or exceeds the receive queue capacity,
+    /// `PortError::InvalidRxBurst` is returned.
+    pub(crate) fn rx_burst(&mut self, rx_burst: usize) -> Result<&mut Self> {
+        ensure!(
+            rx_burst > 0 && rx_burst <= self.rxd as usize,
+            PortError::InvalidRxBurst(rx_burst)
+        );
+
+        self.rx_burst = rx_burst as u16;
+        Ok(self)
+    }
+
+    /// Sets the maximum transmission unit of the port.
+    ///
+    /// If not set, the device's default MTU is used.
+    ///
+    /// # Errors
+    ///
+    /// If the MTU is outside of the range supported by the device,
+    /// `PortError::InvalidMtu` is returned.
+    pub(crate) fn mtu(&mut self, mtu: Option<usize>) -> Result<&mut Self> {
+        if let Some(mtu) = mtu {
+            let min = self.dev_info.min_mtu;
+            let max = self.dev_info.max_mtu;
+            ensure!(
+                mtu >= min as usize && mtu <= max as usize,
+                PortError::InvalidMtu(mtu, min, max)
+            );
+            self.mtu = Some(mtu as u16);
+        }
+
+        Ok(self)
+    }
+
+    /// Sets the receive offloads to enable on the port.
+    ///
+    /// # Errors
+    ///
+    /// If any of the offloads is not supported by the device,
+    /// `PortError::UnsupportedRxOffloads` is returned.
+    pub(crate) fn rx_offloads(&mut self, offloads: RxOffloadFlags) -> Result<&mut Self> {
+        let capa = RxOffloadFlags::from_bits_truncate(self.dev_info.rx_offload_capa);
+        ensure!(
+            capa.contains(offloads),
+            PortError::UnsupportedRxOffloads(offloads - capa)
+        );
+
+        self.rx_offloads = offloads;
+        Ok(self)
+    }
+
+    /// Sets the transmit offloads to enable on the port.
+    ///
+    /// # Errors
+    ///
+    /// If any of the offloads is not supported by the device,
+    /// `PortError::UnsupportedTxOffloads` is returned.
+    pub(crate) fn tx_offloads(&mut self, offloads: TxOffloadFlags) -> Result<&mut Self> {
+        let capa = TxOffloadFlags::from_bits_truncate(self.dev_info.tx_offload_capa);
+        ensure!(
+            capa.contains(offloads),
+            PortError::UnsupportedTxOffloads(offloads - capa)
+        );
+
+        self.tx_offloads = offloads;
+        Ok(self)
+    }
+
+    /// Sets whether receive side scaling uses a symmetric hash key, so
+    /// both directions of a flow are received on the same queue.
+    pub(crate) fn symmetric_rss(&mut self, enabled: bool) -> &mut Self {
+        self.rss_key = if enabled {
+            let len = self.dev_info.hash_key_size as usize;
+            Some(SymmetricRssKey::generate_len(self.port_id.0 as u32, len))
+        } else {
+            None
+        };
+        self
+    }
+
     /// Sets the available mempools.
     pub(crate) fn mempools(&'a mut self, mempools: &'a mut [Mempool]) -> &'a mut Self {
         self.mempools = MempoolMap::new(mempools);
@@ -512,20 +913,63 @@ impl<'a> PortBuilder<'a> {
             conf.rxmode.mq_mode = ffi::rte_eth_rx_mq_mode::ETH_MQ_RX_RSS;
             conf.rx_adv_conf.rss_conf.rss_hf =
                 DEFAULT_RSS_HF & self.dev_info.flow_type_rss_offloads;
+
+            // the key only needs to outlive the device configuration.
+            if let Some(key) = self.rss_key.as_mut() {
+                conf.rx_adv_conf.rss_conf.rss_key = key.as_mut_ptr();
+                conf.rx_adv_conf.rss_conf.rss_key_len = key.len() as u8;
+                debug!("turned on symmetric receive side scaling.");
+            }
         }
 
         // turns on optimization for fast release of mbufs.
-        if self.dev_info.tx_offload_capa & ffi::DEV_TX_OFFLOAD_MBUF_FAST_FREE as u64 > 0 {
-            conf.txmode.offloads |= ffi::DEV_TX_OFFLOAD_MBUF_FAST_FREE as u64;
+        let tx_capa = TxOffloadFlags::from_bits_truncate(self.dev_info.tx_offload_capa);
+        if tx_capa.contains(TxOffloadFlags::MBUF_FAST_FREE) {
+            conf.txmode.offloads |= TxOffloadFlags::MBUF_FAST_FREE.bits();
             debug!("turned on optimization for fast release of mbufs.");
         }
 
+        // turns on the requested receive offloads.
+        if !self.rx_offloads.is_empty() {
+            conf.rxmode.offloads |= self.rx_offloads.bits();
+            debug!(message = "turned on rx offloads.", offloads = ?self.rx_offloads);
+        }
+
+        // turns on the requested transmit offloads.
+        if !self.tx_offloads.is_empty() {
+            conf.txmode.offloads |= self.tx_offloads.bits();
+            debug!(message = "turned on tx offloads.", offloads = ?self.tx_offloads);
+        }
+
+        // turns on jumbo frame support if the MTU is larger than the
+        // standard Ethernet MTU.
+        if let Some(mtu) = self.mtu {
+            if mtu as usize > ETHER_MTU {
+                ensure!(
+                    RxOffloadFlags::from_bits_truncate(self.dev_info.rx_offload_capa)
+                        .contains(RxOffloadFlags::JUMBO_FRAME),
+                    PortError::JumboFramesNotSupported(mtu as usize)
+                );
+                conf.rxmode.offloads |= RxOffloadFlags::JUMBO_FRAME.bits();
+                conf.rxmode.max_rx_pkt_len = (mtu as usize + ETHER_OVERHEAD) as u32;
+                debug!("turned on jumbo frame support.");
+            }
+        }
+
         // must configure the device first before everything else.
         unsafe {
             ffi::rte_eth_dev_configure(self.port_id.0, len, len, &conf)
                 .into_result(DpdkError::from_errno)?;
         }
 
+        // sets the MTU after the device is configured.
+        if let Some(mtu) = self.mtu {
+            unsafe {
+                ffi::rte_eth_dev_set_mtu(self.port_id.0, mtu).into_result(DpdkError::from_errno)?;
+            }
+            debug!("set {} MTU to {}.", self.name, mtu);
+        }
+
         // if the port is virtual, we will allocate it to the socket of
         // the first assigned core.
         let socket_id = self
@@ -608,7 +1052,7 @@ impl<'a> PortBuilder<'a> {
                 )?;
             }
 
-            let mut q = PortQueue::new(self.port_id, rxq, txq);
+            let mut q = PortQueue::new(self.port_id, rxq, txq, self.rx_burst);
 
             if let Some(kni) = &kni {
                 q.set_kni(kni.txq());
@@ -637,6 +1081,9 @@ impl<'a> PortBuilder<'a> {
             }
         }
 
+        #[cfg(feature = "telemetry")]
+        crate::telemetry::register_port_stats();
+
         info!("initialized port {}.", self.name);
 
         Ok(Port {
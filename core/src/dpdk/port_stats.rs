@@ -0,0 +1,273 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::DpdkError;
+use crate::ffi::{self, AsStr, ToResult};
+use anyhow::Result;
+use std::fmt;
+use std::os::raw;
+use std::ptr;
+
+/// A snapshot of the basic statistics of an Ethernet device.
+///
+/// Two snapshots taken some time apart can be compared to compute the
+/// throughput of the port.
+///
+/// # Example
+///
+/// ```
+/// let prev = PortStats::query(port_id)?;
+/// thread::sleep(Duration::from_secs(1));
+/// let rates = PortStats::query(port_id)?
+///     .delta(&prev)
+///     .rate_per_sec(1_000_000_000);
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct PortStats {
+    raw: ffi::rte_eth_stats,
+}
+
+impl PortStats {
+    /// Retrieves the current statistics of a port.
+    ///
+    /// # Errors
+    ///
+    /// If the port id is invalid, `DpdkError` is returned.
+    pub fn query(port_id: u16) -> Result<Self> {
+        let mut raw = ffi::rte_eth_stats::default();
+        unsafe {
+            ffi::rte_eth_stats_get(port_id, &mut raw).into_result(DpdkError::from_errno)?;
+        }
+        Ok(PortStats { raw })
+    }
+
+    /// Resets the statistics of a port.
+    ///
+    /// # Errors
+    ///
+    /// If the port id is invalid or the device does not support resetting,
+    /// `DpdkError` is returned.
+    pub fn reset(port_id: u16) -> Result<()> {
+        unsafe {
+            ffi::rte_eth_stats_reset(port_id)
+                .into_result(DpdkError::from_errno)
+                .map(|_| ())
+        }
+    }
+
+    /// Retrieves the extended statistics of a port. The available counters
+    /// are driver specific.
+    ///
+    /// # Errors
+    ///
+    /// If the port id is invalid, `DpdkError` is returned.
+    pub fn xstats(port_id: u16) -> Result<Vec<(String, u64)>> {
+        let len = unsafe {
+            ffi::rte_eth_xstats_get_names(port_id, ptr::null_mut(), 0)
+                .into_result(DpdkError::from_errno)?
+        };
+
+        let mut names = vec![ffi::rte_eth_xstat_name { name: [0; 64] }; len as usize];
+        let mut values = vec![ffi::rte_eth_xstat::default(); len as usize];
+
+        unsafe {
+            ffi::rte_eth_xstats_get_names(port_id, names.as_mut_ptr(), len as raw::c_uint)
+                .into_result(DpdkError::from_errno)?;
+            let len = ffi::rte_eth_xstats_get(port_id, values.as_mut_ptr(), len as raw::c_uint)
+                .into_result(DpdkError::from_errno)?;
+            // the number of counters could have grown in between calls.
+            values.truncate(len as usize);
+        }
+
+        let xstats = values
+            .iter()
+            .filter_map(|xstat| {
+                names
+                    .get(xstat.id as usize)
+                    .map(|name| (name.name[..].as_str().to_owned(), xstat.value))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(xstats)
+    }
+
+    /// Returns the number of successfully received packets.
+    pub fn rx_packets(&self) -> u64 {
+        self.raw.ipackets
+    }
+
+    /// Returns the number of successfully transmitted packets.
+    pub fn tx_packets(&self) -> u64 {
+        self.raw.opackets
+    }
+
+    /// Returns the number of successfully received bytes.
+    pub fn rx_bytes(&self) -> u64 {
+        self.raw.ibytes
+    }
+
+    /// Returns the number of successfully transmitted bytes.
+    pub fn tx_bytes(&self) -> u64 {
+        self.raw.obytes
+    }
+
+    /// Returns the number of received packets dropped by the hardware
+    /// because the receive queues are full.
+    pub fn rx_missed(&self) -> u64 {
+        self.raw.imissed
+    }
+
+    /// Returns the number of erroneous received packets.
+    pub fn rx_errors(&self) -> u64 {
+        self.raw.ierrors
+    }
+
+    /// Returns the number of failed transmitted packets.
+    pub fn tx_errors(&self) -> u64 {
+        self.raw.oerrors
+    }
+
+    /// Returns the number of receive mbuf allocation failures.
+    pub fn rx_nombuf(&self) -> u64 {
+        self.raw.rx_nombuf
+    }
+
+    /// Returns the per-counter differences between this and a previous
+    /// snapshot.
+    ///
+    /// A counter that wrapped around in between the snapshots still yields
+    /// the correct difference as long as it wrapped at most once.
+    pub fn delta(&self, prev: &PortStats) -> PortStatsDelta {
+        PortStatsDelta {
+            rx_packets: self.rx_packets().wrapping_sub(prev.rx_packets()),
+            tx_packets: self.tx_packets().wrapping_sub(prev.tx_packets()),
+            rx_bytes: self.rx_bytes().wrapping_sub(prev.rx_bytes()),
+            tx_bytes: self.tx_bytes().wrapping_sub(prev.tx_bytes()),
+            rx_missed: self.rx_missed().wrapping_sub(prev.rx_missed()),
+            rx_errors: self.rx_errors().wrapping_sub(prev.rx_errors()),
+            tx_errors: self.tx_errors().wrapping_sub(prev.tx_errors()),
+            rx_nombuf: self.rx_nombuf().wrapping_sub(prev.rx_nombuf()),
+        }
+    }
+}
+
+impl fmt::Debug for PortStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PortStats")
+            .field("rx_packets", &self.rx_packets())
+            .field("tx_packets", &self.tx_packets())
+            .field("rx_bytes", &self.rx_bytes())
+            .field("tx_bytes", &self.tx_bytes())
+            .field("rx_missed", &self.rx_missed())
+            .field("rx_errors", &self.rx_errors())
+            .field("tx_errors", &self.tx_errors())
+            .field("rx_nombuf", &self.rx_nombuf())
+            .finish()
+    }
+}
+
+/// The differences between two `PortStats` snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PortStatsDelta {
+    /// Number of packets received.
+    pub rx_packets: u64,
+    /// Number of packets transmitted.
+    pub tx_packets: u64,
+    /// Number of bytes received.
+    pub rx_bytes: u64,
+    /// Number of bytes transmitted.
+    pub tx_bytes: u64,
+    /// Number of received packets dropped by the hardware.
+    pub rx_missed: u64,
+    /// Number of erroneous received packets.
+    pub rx_errors: u64,
+    /// Number of failed transmitted packets.
+    pub tx_errors: u64,
+    /// Number of receive mbuf allocation failures.
+    pub rx_nombuf: u64,
+}
+
+impl PortStatsDelta {
+    /// Returns the per second rates given the time elapsed in between the
+    /// two snapshots.
+    pub fn rate_per_sec(&self, elapsed_nanos: u64) -> PortRates {
+        if elapsed_nanos == 0 {
+            return PortRates::default();
+        }
+
+        let secs = elapsed_nanos as f64 / 1_000_000_000.0;
+        PortRates {
+            rx_pps: self.rx_packets as f64 / secs,
+            tx_pps: self.tx_packets as f64 / secs,
+            rx_bytes_per_sec: self.rx_bytes as f64 / secs,
+            tx_bytes_per_sec: self.tx_bytes as f64 / secs,
+        }
+    }
+}
+
+/// The throughput of a port.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PortRates {
+    /// Packets received per second.
+    pub rx_pps: f64,
+    /// Packets transmitted per second.
+    pub tx_pps: f64,
+    /// Bytes received per second.
+    pub rx_bytes_per_sec: f64,
+    /// Bytes transmitted per second.
+    pub tx_bytes_per_sec: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(ipackets: u64, ibytes: u64) -> PortStats {
+        PortStats {
+            raw: ffi::rte_eth_stats {
+                ipackets,
+                ibytes,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn stats_delta() {
+        let delta = snapshot(150, 9000).delta(&snapshot(100, 6000));
+        assert_eq!(50, delta.rx_packets);
+        assert_eq!(3000, delta.rx_bytes);
+        assert_eq!(0, delta.tx_packets);
+
+        // the counter wrapped around.
+        let delta = snapshot(10, 0).delta(&snapshot(u64::max_value() - 9, 0));
+        assert_eq!(20, delta.rx_packets);
+    }
+
+    #[test]
+    fn delta_rate_per_sec() {
+        let delta = snapshot(1000, 64000).delta(&snapshot(0, 0));
+
+        let rates = delta.rate_per_sec(500_000_000);
+        assert_eq!(2000.0, rates.rx_pps);
+        assert_eq!(128_000.0, rates.rx_bytes_per_sec);
+        assert_eq!(0.0, rates.tx_pps);
+
+        assert_eq!(PortRates::default(), delta.rate_per_sec(0));
+    }
+}
@@ -0,0 +1,377 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, SocketId};
+use crate::ensure;
+use crate::ffi::{self, ToResult};
+use anyhow::Result;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// RCU errors.
+#[derive(Debug, Error)]
+pub(crate) enum RcuError {
+    /// The domain must be able to track at least one reader.
+    #[error("RCU domain requires at least one thread.")]
+    NoThreads,
+
+    /// The thread id is not below the domain's `max_threads`.
+    #[error("Thread id {0} is out of range for a domain of {1} threads.")]
+    InvalidThread(u32, u32),
+
+    /// The thread id already has a live reader.
+    #[error("Thread id {0} is already registered.")]
+    AlreadyRegistered(u32),
+}
+
+/// A quiescent state based RCU domain.
+///
+/// Reader threads register with the domain and periodically report a
+/// quiescent state, a point where they hold no references to shared
+/// data, typically once per polling loop iteration. Writers wait on the
+/// domain until every registered reader has gone through a quiescent
+/// state before reclaiming replaced data.
+///
+/// The QSBR variable is allocated from huge page memory, which is the
+/// equivalent of `rte_rcu_qsbr_alloc` in the DPDK documentation; DPDK
+/// itself leaves the allocation to the application.
+pub struct RcuDomain {
+    raw: NonNull<ffi::rte_rcu_qsbr>,
+    max_threads: u32,
+    // whether each thread id has a live reader.
+    registered: Box<[AtomicBool]>,
+}
+
+impl RcuDomain {
+    /// Creates a new domain for up to `max_threads` reader threads.
+    ///
+    /// Reader thread ids range from `0` to `max_threads - 1`. Lcore ids
+    /// can be used directly as long as `max_threads` is larger than the
+    /// highest lcore id.
+    ///
+    /// # Errors
+    ///
+    /// If `max_threads` is 0 or the memory allocation failed, an error is
+    /// returned.
+    pub fn new(max_threads: u32) -> Result<Self> {
+        ensure!(max_threads > 0, RcuError::NoThreads);
+
+        let size = unsafe { ffi::rte_rcu_qsbr_get_memsize(max_threads) };
+        let raw = unsafe {
+            ffi::rte_zmalloc_socket(
+                ptr::null(),
+                size,
+                ffi::RTE_CACHE_LINE_SIZE,
+                SocketId::ANY.raw(),
+            )
+            .into_result(|_| DpdkError::new())?
+            .cast::<ffi::rte_rcu_qsbr>()
+        };
+
+        unsafe {
+            if let Err(err) =
+                ffi::rte_rcu_qsbr_init(raw.as_ptr(), max_threads).into_result(DpdkError::from_errno)
+            {
+                ffi::rte_free(raw.as_ptr() as *mut _);
+                return Err(err);
+            }
+        }
+
+        Ok(RcuDomain {
+            raw,
+            max_threads,
+            registered: (0..max_threads).map(|_| AtomicBool::new(false)).collect(),
+        })
+    }
+
+    /// Returns the maximum number of reader threads.
+    pub fn max_threads(&self) -> u32 {
+        self.max_threads
+    }
+
+    /// Registers a reader thread and marks it online.
+    ///
+    /// From this point on, writers wait for the thread to report a
+    /// quiescent state before reclaiming replaced data. The returned
+    /// reader is bound to the calling thread. Dropping it marks the
+    /// thread offline and unregisters it.
+    ///
+    /// # Errors
+    ///
+    /// If the thread id is out of range or already has a live reader, an
+    /// error is returned.
+    pub fn register_thread(&self, lcore_id: u32) -> Result<RcuReader<'_>> {
+        ensure!(
+            lcore_id < self.max_threads,
+            RcuError::InvalidThread(lcore_id, self.max_threads)
+        );
+        ensure!(
+            self.registered[lcore_id as usize]
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok(),
+            RcuError::AlreadyRegistered(lcore_id)
+        );
+
+        unsafe {
+            if let Err(err) = ffi::rte_rcu_qsbr_thread_register(self.raw.as_ptr(), lcore_id)
+                .into_result(DpdkError::from_errno)
+            {
+                self.registered[lcore_id as usize].store(false, Ordering::Release);
+                return Err(err);
+            }
+            ffi::_rte_rcu_qsbr_thread_online(self.raw.as_ptr(), lcore_id);
+        }
+
+        Ok(RcuReader {
+            domain: self,
+            thread_id: lcore_id,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Blocks until every registered reader thread has reported a
+    /// quiescent state.
+    ///
+    /// The calling thread must not be a registered reader itself, or it
+    /// waits on itself forever.
+    pub fn synchronize(&self) {
+        unsafe {
+            ffi::rte_rcu_qsbr_synchronize(self.raw.as_ptr(), ffi::RTE_QSBR_THRID_INVALID);
+        }
+    }
+}
+
+impl fmt::Debug for RcuDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RcuDomain")
+            .field("raw", &self.raw)
+            .field("max_threads", &self.max_threads)
+            .finish()
+    }
+}
+
+impl Drop for RcuDomain {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rte_free(self.raw.as_ptr() as *mut _);
+        }
+    }
+}
+
+// the QSBR variable is designed to be shared across threads.
+unsafe impl Send for RcuDomain {}
+unsafe impl Sync for RcuDomain {}
+
+/// A reader thread registered with an `RcuDomain`.
+///
+/// The reader is required to access an `RcuWriter`'s value, and it can't
+/// be sent to another thread. The thread must report a quiescent state
+/// regularly, otherwise writers are blocked.
+pub struct RcuReader<'a> {
+    domain: &'a RcuDomain,
+    thread_id: u32,
+    // the reader state is per thread.
+    _phantom: PhantomData<*const ()>,
+}
+
+impl RcuReader<'_> {
+    /// Returns the thread id of the reader.
+    pub fn thread_id(&self) -> u32 {
+        self.thread_id
+    }
+
+    /// Reports a quiescent state for the reader thread.
+    ///
+    /// The values read before the call may be reclaimed afterwards.
+    /// `RcuWriter::read` borrows the reader mutably for the duration of
+    /// the closure and doesn't let references escape it, so none are left
+    /// at this point.
+    #[inline]
+    pub fn quiescent_state(&self) {
+        unsafe {
+            ffi::_rte_rcu_qsbr_quiescent(self.domain.raw.as_ptr(), self.thread_id);
+        }
+    }
+}
+
+impl fmt::Debug for RcuReader<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RcuReader")
+            .field("thread_id", &self.thread_id)
+            .finish()
+    }
+}
+
+impl Drop for RcuReader<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::_rte_rcu_qsbr_thread_offline(self.domain.raw.as_ptr(), self.thread_id);
+            let _ = ffi::rte_rcu_qsbr_thread_unregister(self.domain.raw.as_ptr(), self.thread_id);
+        }
+        self.domain.registered[self.thread_id as usize].store(false, Ordering::Release);
+    }
+}
+
+/// A shared value that is replaced without locking the readers.
+///
+/// Readers access the current value through `read` with their
+/// `RcuReader`, writers replace it with `update`. The replaced value is
+/// only dropped once every reader registered with the domain has
+/// reported a quiescent state, so a reader never observes a freed value.
+///
+/// # Example
+///
+/// ```
+/// let routes = Arc::new(RcuWriter::new(domain.clone(), RouteTable::new()));
+///
+/// // on the data plane cores
+/// let mut reader = domain.register_thread(lcore_id)?;
+/// loop {
+///     let next_hop = routes.read(&mut reader, |table| table.lookup(dst));
+///     ...
+///     reader.quiescent_state();
+/// }
+///
+/// // on the control plane
+/// routes.update(new_table)?;
+/// ```
+pub struct RcuWriter<T> {
+    ptr: AtomicPtr<T>,
+    domain: Arc<RcuDomain>,
+}
+
+impl<T> RcuWriter<T> {
+    /// Creates a new shared value protected by the domain.
+    pub fn new(domain: Arc<RcuDomain>, value: T) -> Self {
+        RcuWriter {
+            ptr: AtomicPtr::new(Box::into_raw(Box::new(value))),
+            domain,
+        }
+    }
+
+    /// Returns the domain protecting the value.
+    pub fn domain(&self) -> &Arc<RcuDomain> {
+        &self.domain
+    }
+
+    /// Calls `f` with a reference to the current value.
+    ///
+    /// The reader is borrowed mutably so `f` can't report a quiescent
+    /// state while it still holds the reference.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the reader is not registered with the writer's domain.
+    #[inline]
+    pub fn read<R, F: FnOnce(&T) -> R>(&self, reader: &mut RcuReader<'_>, f: F) -> R {
+        assert!(
+            ptr::eq(reader.domain, &*self.domain),
+            "reader is from another domain."
+        );
+        // the reader is online, so the value is not reclaimed before its
+        // next quiescent state.
+        let ptr = self.ptr.load(Ordering::Acquire);
+        f(unsafe { &*ptr })
+    }
+
+    /// Replaces the current value and reclaims the old one.
+    ///
+    /// The pointer is swapped atomically. The call then blocks until every
+    /// registered reader has reported a quiescent state, before dropping
+    /// the old value. Must not be called from a registered reader thread.
+    ///
+    /// # Errors
+    ///
+    /// Currently always succeeds. The result is kept so the reclamation
+    /// strategy can change without breaking callers.
+    pub fn update(&self, new_val: T) -> Result<()> {
+        let new = Box::into_raw(Box::new(new_val));
+        let old = self.ptr.swap(new, Ordering::AcqRel);
+        self.domain.synchronize();
+        unsafe {
+            drop(Box::from_raw(old));
+        }
+        Ok(())
+    }
+}
+
+impl<T> fmt::Debug for RcuWriter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RcuWriter")
+            .field("domain", &self.domain)
+            .finish()
+    }
+}
+
+impl<T> Drop for RcuWriter<T> {
+    fn drop(&mut self) {
+        // no readers can be left once the writer is dropped.
+        unsafe {
+            drop(Box::from_raw(*self.ptr.get_mut()));
+        }
+    }
+}
+
+unsafe impl<T: Send + Sync> Send for RcuWriter<T> {}
+unsafe impl<T: Send + Sync> Sync for RcuWriter<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn register_and_report_quiescent_state() -> Result<()> {
+        let domain = RcuDomain::new(4)?;
+        let reader = domain.register_thread(1)?;
+        reader.quiescent_state();
+        assert!(domain.register_thread(1).is_err());
+
+        drop(reader);
+        assert!(domain.register_thread(1).is_ok());
+        assert!(domain.register_thread(4).is_err());
+        Ok(())
+    }
+
+    #[capsule::test]
+    fn update_replaces_value() -> Result<()> {
+        let domain = Arc::new(RcuDomain::new(2)?);
+        let writer = Arc::new(RcuWriter::new(domain.clone(), vec![1u32, 2, 3]));
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let reader_writer = writer.clone();
+        let reader_domain = domain.clone();
+        let handle = std::thread::spawn(move || {
+            let mut reader = reader_domain.register_thread(1).unwrap();
+            let sum = reader_writer.read(&mut reader, |v| v.iter().sum::<u32>());
+            tx.send(sum).unwrap();
+            reader.quiescent_state();
+        });
+
+        assert_eq!(6, rx.recv().unwrap());
+        writer.update(vec![4, 5])?;
+        handle.join().unwrap();
+
+        let mut reader = domain.register_thread(0)?;
+        assert_eq!(9, writer.read(&mut reader, |v| v.iter().sum::<u32>()));
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::{DpdkError, Mbuf, SocketId};
+use crate::ffi::{self, ToCString, ToResult};
+use crate::info;
+use crate::packets::{Ethernet, Packet};
+use anyhow::Result;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::ptr::{self, NonNull};
+
+/// The number of packets drained from the buffer at once.
+const DRAIN_BURST: usize = 32;
+
+/// A buffer that puts packets back in the order of their sequence numbers.
+///
+/// The sequence number of a packet is set on its metadata with
+/// `set_seqn`, usually when the packet is received, before it is handed
+/// to the cores that process packets in parallel. The buffer then
+/// releases the packets in sequence and waits for the missing ones,
+/// up to the size of the buffer.
+///
+/// # Example
+///
+/// ```
+/// packet.mbuf_mut().meta_mut().set_seqn(seqn);
+/// ...
+/// reorder.insert(packet)?;
+/// reorder.drain(&mut ordered, 32);
+/// ```
+pub struct ReorderBuffer {
+    raw: NonNull<ffi::rte_reorder_buffer>,
+    name: String,
+    // the in order packets drained by `drain_up_to` that are past the
+    // requested sequence number.
+    pending: RefCell<VecDeque<Mbuf>>,
+}
+
+impl ReorderBuffer {
+    /// Creates a new reorder buffer that holds up to `size` packets. The
+    /// size must be a power of 2.
+    ///
+    /// # Errors
+    ///
+    /// If the size is not a power of 2, or a buffer with the same name
+    /// already exists, `DpdkError` is returned.
+    pub fn new(name: &str, socket_id: SocketId, size: u32) -> Result<Self> {
+        let raw = unsafe {
+            ffi::rte_reorder_create(name.into_cstring().as_ptr(), socket_id.raw() as u32, size)
+                .into_result(|_| DpdkError::new())?
+        };
+
+        info!("created {}.", name);
+        Ok(ReorderBuffer {
+            raw,
+            name: name.to_owned(),
+            pending: RefCell::new(VecDeque::new()),
+        })
+    }
+
+    /// Returns the name of the buffer.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Inserts a packet into the buffer at its sequence number.
+    ///
+    /// # Errors
+    ///
+    /// If the sequence number is before the ones already drained, or too
+    /// far ahead for the buffer to hold, `DpdkError` is returned and the
+    /// packet is dropped.
+    pub fn insert(&self, pkt: Ethernet) -> Result<()> {
+        let mbuf = pkt.reset().into_ptr();
+        unsafe {
+            if let Err(err) =
+                ffi::rte_reorder_insert(self.raw.as_ptr(), mbuf).into_result(DpdkError::from_errno)
+            {
+                let _ = Mbuf::from_ptr(mbuf);
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drains up to `max` packets that are in order and appends them to
+    /// `out`. Returns the number of packets drained.
+    pub fn drain(&self, out: &mut Vec<Ethernet>, max: u32) -> u32 {
+        let max = max as usize;
+        let mut mbufs = {
+            let mut pending = self.pending.borrow_mut();
+            let len = pending.len().min(max);
+            pending.drain(..len).collect::<Vec<_>>()
+        };
+
+        if mbufs.len() < max {
+            mbufs.extend(self.drain_raw(max - mbufs.len()));
+        }
+
+        let count = mbufs.len() as u32;
+        out.extend(
+            mbufs
+                .into_iter()
+                .filter_map(|mbuf| mbuf.parse::<Ethernet>().ok()),
+        );
+        count
+    }
+
+    /// Drains the packets that are in order with a sequence number before
+    /// `seqn`, and appends them to `out`. Returns the number of packets
+    /// drained.
+    ///
+    /// Unlike `drain`, the packets held for a missing sequence number
+    /// before `seqn` are not released.
+    pub fn drain_up_to(&self, seqn: u32, out: &mut Vec<Ethernet>) -> u32 {
+        let mut pending = self.pending.borrow_mut();
+        loop {
+            let mbufs = self.drain_raw(DRAIN_BURST);
+            let len = mbufs.len();
+            pending.extend(mbufs);
+            if len < DRAIN_BURST {
+                break;
+            }
+        }
+
+        let mut count = 0;
+        while pending
+            .front()
+            .map_or(false, |mbuf| seqn_before(mbuf.meta().seqn(), seqn))
+        {
+            let mbuf = pending.pop_front().unwrap();
+            if let Ok(pkt) = mbuf.parse::<Ethernet>() {
+                out.push(pkt);
+            }
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Frees all the packets held in the buffer and resets the buffer to
+    /// its initial state.
+    pub fn free_buffers(&mut self) {
+        self.pending.borrow_mut().clear();
+        unsafe {
+            ffi::rte_reorder_reset(self.raw.as_ptr());
+        }
+    }
+
+    /// Drains up to `max` in order packets from the buffer.
+    fn drain_raw(&self, max: usize) -> Vec<Mbuf> {
+        let mut ptrs = vec![ptr::null_mut(); max];
+        let count =
+            unsafe { ffi::rte_reorder_drain(self.raw.as_ptr(), ptrs.as_mut_ptr(), max as u32) }
+                as usize;
+
+        ptrs.into_iter()
+            .take(count)
+            .map(|ptr| unsafe { Mbuf::from_ptr(ptr) })
+            .collect()
+    }
+}
+
+/// Returns whether `a` comes before `b`, accounting for the sequence
+/// numbers wrapping around.
+fn seqn_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+impl fmt::Debug for ReorderBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReorderBuffer")
+            .field("name", &self.name)
+            .field("pending", &self.pending.borrow().len())
+            .finish()
+    }
+}
+
+impl Drop for ReorderBuffer {
+    fn drop(&mut self) {
+        // frees the packets still held too.
+        unsafe {
+            ffi::rte_reorder_free(self.raw.as_ptr());
+        }
+    }
+}
+
+unsafe impl Send for ReorderBuffer {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+
+    fn new_packet(seqn: u32) -> Ethernet {
+        let mut mbuf = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        mbuf.meta_mut().set_seqn(seqn);
+        mbuf.parse::<Ethernet>().unwrap()
+    }
+
+    #[test]
+    fn seqn_wraps_around() {
+        assert!(seqn_before(1, 2));
+        assert!(!seqn_before(2, 2));
+        assert!(seqn_before(u32::MAX, 0));
+    }
+
+    #[capsule::test]
+    fn reorder_packets() {
+        let reorder = ReorderBuffer::new("reorder0", SocketId::ANY, 16).unwrap();
+
+        reorder.insert(new_packet(0)).unwrap();
+        reorder.insert(new_packet(2)).unwrap();
+        reorder.insert(new_packet(1)).unwrap();
+        reorder.insert(new_packet(4)).unwrap();
+
+        // 3 is missing, so 4 is held back.
+        let mut out = vec![];
+        assert_eq!(3, reorder.drain(&mut out, 32));
+        let seqns = out
+            .iter()
+            .map(|pkt| pkt.mbuf().meta().seqn())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![0, 1, 2], seqns);
+
+        reorder.insert(new_packet(3)).unwrap();
+        let mut out = vec![];
+        assert_eq!(1, reorder.drain_up_to(4, &mut out));
+        assert_eq!(3, out[0].mbuf().meta().seqn());
+        assert_eq!(1, reorder.drain(&mut out, 32));
+        assert_eq!(4, out[1].mbuf().meta().seqn());
+    }
+
+    #[capsule::test]
+    fn invalid_size() {
+        assert!(ReorderBuffer::new("reorder1", SocketId::ANY, 10).is_err());
+    }
+}
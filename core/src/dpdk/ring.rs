@@ -0,0 +1,258 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{Allocate, NeedsSocket, SocketId};
+use crate::dpdk::DpdkError;
+use crate::ffi::{self, AsStr, ToCString, ToResult};
+use crate::{debug, ensure};
+use anyhow::Result;
+use bitflags::bitflags;
+use std::fmt;
+use std::marker::PhantomData;
+use std::os::raw;
+use std::ptr::{self, NonNull};
+use thiserror::Error;
+
+bitflags! {
+    /// Flags used when creating a `Ring`.
+    pub struct RingFlags: u32 {
+        /// Only one thread enqueues to the ring.
+        const SINGLE_PRODUCER = ffi::RING_F_SP_ENQ;
+        /// Only one thread dequeues from the ring.
+        const SINGLE_CONSUMER = ffi::RING_F_SC_DEQ;
+        /// The ring holds exactly the requested number of items. Otherwise
+        /// the capacity must be a power of 2.
+        const EXACT_SIZE = ffi::RING_F_EXACT_SZ;
+    }
+}
+
+/// Error indicating the ring is full.
+#[derive(Debug, Error)]
+pub(crate) enum RingError {
+    #[error("Ring is full.")]
+    Full,
+}
+
+/// A fixed-size, lockless FIFO queue for passing items between cores.
+///
+/// Items are boxed and the pointers are stored in the `rte_ring`. The ring
+/// is `Sync` and can be shared across cores in an `Arc`. If the ring is
+/// created with `SINGLE_PRODUCER` or `SINGLE_CONSUMER`, only one core
+/// should enqueue or dequeue respectively.
+///
+/// # Example
+///
+/// ```
+/// let ring = Arc::new(Ring::<Mbuf>::new("ring0", 1024, SocketId::ANY, RingFlags::empty())?);
+/// ring.enqueue(Mbuf::new()?)?;
+/// ```
+pub struct Ring<T: Send> {
+    raw: NonNull<ffi::rte_ring>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Send> Ring<T> {
+    /// Creates a new `Ring`.
+    ///
+    /// `name` must be unique. Unless `EXACT_SIZE` is set, `capacity` must
+    /// be a power of 2 and the ring can hold `capacity - 1` items.
+    ///
+    /// # Errors
+    ///
+    /// If the name is already used, or `capacity` is invalid, or the
+    /// allocation fails, `DpdkError` is returned.
+    pub fn new(name: &str, capacity: usize, socket_id: SocketId, flags: RingFlags) -> Result<Self> {
+        let raw = unsafe {
+            ffi::rte_ring_create(
+                name.into_cstring().as_ptr(),
+                capacity as raw::c_uint,
+                socket_id.raw(),
+                flags.bits() as raw::c_uint,
+            )
+            .into_result(|_| DpdkError::new())?
+        };
+
+        #[cfg(feature = "telemetry")]
+        crate::telemetry::register_ring_info();
+
+        debug!("created ring {}.", name);
+        Ok(Ring {
+            raw,
+            _phantom: PhantomData,
+        })
+    }
+
+    #[inline]
+    fn raw_mut(&self) -> *mut ffi::rte_ring {
+        self.raw.as_ptr()
+    }
+
+    /// Enqueues one item.
+    ///
+    /// # Errors
+    ///
+    /// If the ring is full, `RingError::Full` is returned and the item is
+    /// dropped.
+    pub fn enqueue(&self, item: T) -> Result<()> {
+        let mut items = vec![item];
+        ensure!(self.enqueue_burst(&mut items) == 1, RingError::Full);
+        Ok(())
+    }
+
+    /// Dequeues one item. Returns `None` if the ring is empty.
+    pub fn dequeue(&self) -> Option<T> {
+        let mut buf = Vec::with_capacity(1);
+        self.dequeue_burst(&mut buf, 1);
+        buf.pop()
+    }
+
+    /// Enqueues as many items as the ring has room for, from the front of
+    /// `items`. Returns the number of items enqueued. The enqueued items
+    /// are removed from `items`.
+    pub fn enqueue_burst(&self, items: &mut Vec<T>) -> usize {
+        let ptrs = items
+            .drain(..)
+            .map(|item| Box::into_raw(Box::new(item)) as *mut raw::c_void)
+            .collect::<Vec<_>>();
+
+        let count = unsafe {
+            ffi::_rte_ring_enqueue_burst(
+                self.raw_mut(),
+                ptrs.as_ptr(),
+                ptrs.len() as raw::c_uint,
+                ptr::null_mut(),
+            ) as usize
+        };
+
+        // hands ownership of the items not enqueued back to the caller.
+        items.extend(
+            ptrs[count..]
+                .iter()
+                .map(|&ptr| unsafe { *Box::from_raw(ptr as *mut T) }),
+        );
+
+        count
+    }
+
+    /// Dequeues up to `max` items and appends them to `buf`. Returns the
+    /// number of items dequeued.
+    pub fn dequeue_burst(&self, buf: &mut Vec<T>, max: usize) -> usize {
+        let mut ptrs = Vec::with_capacity(max);
+
+        unsafe {
+            let count = ffi::_rte_ring_dequeue_burst(
+                self.raw_mut(),
+                ptrs.as_mut_ptr(),
+                max as raw::c_uint,
+                ptr::null_mut(),
+            ) as usize;
+            ptrs.set_len(count);
+        }
+
+        buf.extend(
+            ptrs.iter()
+                .map(|&ptr| unsafe { *Box::from_raw(ptr as *mut T) }),
+        );
+
+        ptrs.len()
+    }
+}
+
+impl<T: Send> NeedsSocket for Ring<T> {
+    fn socket_id(&self) -> SocketId {
+        let raw = unsafe { self.raw.as_ref() };
+        unsafe { SocketId((*raw.memzone).socket_id) }
+    }
+}
+
+impl<T: Send> Allocate for Ring<T> {
+    /// The name, the capacity and the flags of the ring.
+    type Params = (String, usize, RingFlags);
+
+    fn allocate(socket_id: SocketId, (name, capacity, flags): Self::Params) -> Result<Self> {
+        Ring::new(&name, capacity, socket_id, flags)
+    }
+}
+
+impl<T: Send> fmt::Debug for Ring<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw = unsafe { self.raw.as_ref() };
+        f.debug_struct(raw.name[..].as_str())
+            .field("capacity", &raw.capacity)
+            .field("flags", &RingFlags::from_bits_truncate(raw.flags as u32))
+            .finish()
+    }
+}
+
+impl<T: Send> Drop for Ring<T> {
+    fn drop(&mut self) {
+        // drops the items still in the ring before freeing it.
+        let mut buf = Vec::with_capacity(32);
+        while self.dequeue_burst(&mut buf, 32) > 0 {
+            buf.clear();
+        }
+
+        unsafe {
+            ffi::rte_ring_free(self.raw_mut());
+        }
+    }
+}
+
+// the items are moved across threads through the ring, hence `T: Send`.
+// the enqueue and dequeue operations are thread-safe unless the ring is
+// created with the single producer or consumer flags.
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[capsule::test]
+    fn enqueue_and_dequeue() {
+        let ring = Ring::<u32>::new("test_ring0", 4, SocketId::ANY, RingFlags::empty()).unwrap();
+
+        assert!(ring.enqueue(1).is_ok());
+        let mut items = vec![2, 3, 4];
+        assert_eq!(2, ring.enqueue_burst(&mut items));
+        assert_eq!(vec![4], items);
+        assert!(ring.enqueue(5).is_err());
+
+        assert_eq!(Some(1), ring.dequeue());
+        let mut buf = vec![];
+        assert_eq!(2, ring.dequeue_burst(&mut buf, 8));
+        assert_eq!(vec![2, 3], buf);
+        assert_eq!(None, ring.dequeue());
+    }
+
+    #[capsule::test]
+    fn share_ring_across_threads() {
+        let ring = Arc::new(
+            Ring::<String>::new("test_ring1", 8, SocketId::ANY, RingFlags::EXACT_SIZE).unwrap(),
+        );
+
+        let producer = ring.clone();
+        thread::spawn(move || producer.enqueue("hello".to_owned()).unwrap())
+            .join()
+            .unwrap();
+
+        assert_eq!(Some("hello".to_owned()), ring.dequeue());
+    }
+}
@@ -0,0 +1,529 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::PortId;
+use crate::dpdk::DpdkError;
+use crate::ffi::{self, ToResult};
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::v6::{Ipv6, Ipv6Packet};
+use crate::packets::ip::ProtocolNumbers;
+use crate::packets::{EtherTypes, Ethernet, Packet, Tcp4, Tcp6, Udp4, Udp6};
+use anyhow::Result;
+use bitflags::bitflags;
+use std::fmt;
+use std::net::SocketAddrV4;
+
+bitflags! {
+    /// The packet types used for computing the receive side scaling hash.
+    ///
+    /// The Ethernet device uses only the flow types it supports. See
+    /// `rte_eth_dev_info::flow_type_rss_offloads`.
+    pub struct RssHashFunc: u64 {
+        /// IPv4 packets.
+        const IPV4 = ffi::ETH_RSS_IPV4 as u64;
+        /// Fragmented IPv4 packets.
+        const FRAG_IPV4 = ffi::ETH_RSS_FRAG_IPV4 as u64;
+        /// Non-fragmented IPv4 TCP packets.
+        const IPV4_TCP = ffi::ETH_RSS_NONFRAG_IPV4_TCP as u64;
+        /// Non-fragmented IPv4 UDP packets.
+        const IPV4_UDP = ffi::ETH_RSS_NONFRAG_IPV4_UDP as u64;
+        /// Non-fragmented IPv4 SCTP packets.
+        const IPV4_SCTP = ffi::ETH_RSS_NONFRAG_IPV4_SCTP as u64;
+        /// Non-fragmented IPv4 packets of other protocols.
+        const IPV4_OTHER = ffi::ETH_RSS_NONFRAG_IPV4_OTHER as u64;
+        /// IPv6 packets.
+        const IPV6 = ffi::ETH_RSS_IPV6 as u64;
+        /// Fragmented IPv6 packets.
+        const FRAG_IPV6 = ffi::ETH_RSS_FRAG_IPV6 as u64;
+        /// Non-fragmented IPv6 TCP packets.
+        const IPV6_TCP = ffi::ETH_RSS_NONFRAG_IPV6_TCP as u64;
+        /// Non-fragmented IPv6 UDP packets.
+        const IPV6_UDP = ffi::ETH_RSS_NONFRAG_IPV6_UDP as u64;
+        /// Non-fragmented IPv6 SCTP packets.
+        const IPV6_SCTP = ffi::ETH_RSS_NONFRAG_IPV6_SCTP as u64;
+        /// Non-fragmented IPv6 packets of other protocols.
+        const IPV6_OTHER = ffi::ETH_RSS_NONFRAG_IPV6_OTHER as u64;
+        /// IPv6 packets with extension headers.
+        const IPV6_EX = ffi::ETH_RSS_IPV6_EX as u64;
+        /// IPv6 TCP packets with extension headers.
+        const IPV6_TCP_EX = ffi::ETH_RSS_IPV6_TCP_EX as u64;
+        /// IPv6 UDP packets with extension headers.
+        const IPV6_UDP_EX = ffi::ETH_RSS_IPV6_UDP_EX as u64;
+        /// Layer 2 payload.
+        const L2_PAYLOAD = ffi::ETH_RSS_L2_PAYLOAD as u64;
+        /// Port numbers.
+        const PORT = ffi::ETH_RSS_PORT as u64;
+        /// VXLAN tunneled packets.
+        const VXLAN = ffi::ETH_RSS_VXLAN as u64;
+        /// Geneve tunneled packets.
+        const GENEVE = ffi::ETH_RSS_GENEVE as u64;
+        /// NVGRE tunneled packets.
+        const NVGRE = ffi::ETH_RSS_NVGRE as u64;
+
+        /// All IPv4 and IPv6 packets.
+        const IP = Self::IPV4.bits
+            | Self::FRAG_IPV4.bits
+            | Self::IPV4_OTHER.bits
+            | Self::IPV6.bits
+            | Self::FRAG_IPV6.bits
+            | Self::IPV6_OTHER.bits
+            | Self::IPV6_EX.bits;
+        /// All TCP packets.
+        const TCP = Self::IPV4_TCP.bits | Self::IPV6_TCP.bits | Self::IPV6_TCP_EX.bits;
+        /// All UDP packets.
+        const UDP = Self::IPV4_UDP.bits | Self::IPV6_UDP.bits | Self::IPV6_UDP_EX.bits;
+        /// All SCTP packets.
+        const SCTP = Self::IPV4_SCTP.bits | Self::IPV6_SCTP.bits;
+    }
+}
+
+/// Receive side scaling configuration of an Ethernet device.
+///
+/// # Example
+///
+/// ```
+/// let conf = RssConfig::new(RssHashFunc::IP | RssHashFunc::TCP).hash_key(&key);
+/// q.set_rss_conf(&conf)?;
+/// ```
+#[derive(Clone, PartialEq)]
+pub struct RssConfig {
+    hash_key: Option<Vec<u8>>,
+    hash_functions: RssHashFunc,
+}
+
+impl RssConfig {
+    /// Creates a new configuration that hashes the packet types in
+    /// `hash_functions` with the device's current hash key.
+    pub fn new(hash_functions: RssHashFunc) -> Self {
+        RssConfig {
+            hash_key: None,
+            hash_functions,
+        }
+    }
+
+    /// Sets the hash key. The key length must match the hash key size of
+    /// the Ethernet device, typically 40 or 52 bytes.
+    pub fn hash_key(mut self, key: &[u8]) -> Self {
+        self.hash_key = Some(key.to_vec());
+        self
+    }
+
+    /// Returns the hash key, if set.
+    pub fn key(&self) -> Option<&[u8]> {
+        self.hash_key.as_deref()
+    }
+
+    /// Returns the packet types used for computing the hash. If empty,
+    /// receive side scaling is disabled.
+    pub fn hash_functions(&self) -> RssHashFunc {
+        self.hash_functions
+    }
+}
+
+impl fmt::Debug for RssConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("rss_conf");
+        if let Some(key) = &self.hash_key {
+            d.field("hash_key", &format_args!("{:02x?}", key));
+        }
+        d.field("hash_functions", &self.hash_functions).finish()
+    }
+}
+
+/// The seed pattern used when the seed given does not produce one.
+const DEFAULT_SYMMETRIC_PATTERN: u16 = 0x6d5a;
+
+/// Generator of symmetric receive side scaling hash keys.
+///
+/// With the default Toeplitz hash function, the two directions of a flow
+/// usually hash to different values, and land on different receive
+/// queues. A key that repeats with a period of 16 bits produces the same
+/// hash for a flow and for its reverse, because swapping the source and
+/// destination addresses or ports shifts the input by a multiple of 16
+/// bits.
+#[derive(Debug)]
+pub struct SymmetricRssKey;
+
+impl SymmetricRssKey {
+    /// Generates a 40-byte symmetric key from a seed.
+    pub fn generate(seed: u32) -> [u8; 40] {
+        let mut key = [0; 40];
+        key.copy_from_slice(&SymmetricRssKey::generate_len(seed, 40));
+        key
+    }
+
+    /// Generates a symmetric key of any length from a seed.
+    pub(crate) fn generate_len(seed: u32, len: usize) -> Vec<u8> {
+        // folds the seed into the 16-bit pattern the key repeats.
+        let pattern = match (seed ^ (seed >> 16)) as u16 {
+            0 => DEFAULT_SYMMETRIC_PATTERN,
+            pattern => pattern,
+        };
+
+        pattern
+            .to_be_bytes()
+            .iter()
+            .cycle()
+            .take(len)
+            .copied()
+            .collect()
+    }
+
+    /// Checks that the key hashes a sample of IPv4 TCP and UDP flows to
+    /// the same value as their reverse flows.
+    pub fn verify(key: &[u8; 40]) -> bool {
+        const SAMPLES: [([u8; 4], [u8; 4], u16, u16); 4] = [
+            ([66, 9, 149, 187], [161, 142, 100, 80], 2794, 1766),
+            ([199, 92, 111, 2], [65, 69, 140, 83], 14230, 4739),
+            ([24, 19, 198, 95], [12, 22, 207, 184], 12898, 38024),
+            ([10, 0, 0, 1], [192, 168, 1, 254], 65535, 1),
+        ];
+
+        SAMPLES.iter().all(|&(src, dst, src_port, dst_port)| {
+            toeplitz_hash(key, &ipv4_tuple(src, dst, src_port, dst_port))
+                == toeplitz_hash(key, &ipv4_tuple(dst, src, dst_port, src_port))
+        })
+    }
+}
+
+/// The number of entries in the redirection table of `RssSim`.
+const RETA_SIZE: u32 = 128;
+
+/// Simulates the receive side scaling of an Ethernet device in software.
+///
+/// The packets are hashed with the Toeplitz hash, and the hash indexes a
+/// 128 entry redirection table with the queues assigned round-robin, which
+/// is the default of most devices. TCP and UDP packets are hashed on the
+/// addresses and ports, and other IP packets only on the addresses. It
+/// lets tests of multi-queue pipelines predict which queue a packet is
+/// received on without the hardware.
+///
+/// # Example
+///
+/// ```
+/// let rss = RssSim::new(key, 4);
+/// let queue = rss.classify_ipv4_tcp("10.0.0.1:1234".parse()?, "10.0.0.2:80".parse()?);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RssSim {
+    key: [u8; 40],
+    num_queues: u16,
+}
+
+impl RssSim {
+    /// Creates a simulator of `num_queues` receive queues.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_queues` is 0.
+    pub fn new(key: [u8; 40], num_queues: u16) -> Self {
+        assert!(num_queues > 0, "RssSim needs at least one queue.");
+        RssSim { key, num_queues }
+    }
+
+    /// Returns the number of queues.
+    pub fn num_queues(&self) -> u16 {
+        self.num_queues
+    }
+
+    /// Returns the queue of the hash.
+    #[inline]
+    fn queue(&self, hash: u32) -> u16 {
+        ((hash % RETA_SIZE) % self.num_queues as u32) as u16
+    }
+
+    /// Returns the queue of an IPv4 TCP flow.
+    pub fn classify_ipv4_tcp(&self, src: SocketAddrV4, dst: SocketAddrV4) -> u16 {
+        let input = ipv4_tuple(src.ip().octets(), dst.ip().octets(), src.port(), dst.port());
+        self.queue(toeplitz_hash(&self.key, &input))
+    }
+
+    /// Returns the queue of an IPv4 UDP flow.
+    ///
+    /// The hash is the same as for TCP. The devices differ only in whether
+    /// the ports are included, which depends on the hash functions enabled.
+    pub fn classify_ipv4_udp(&self, src: SocketAddrV4, dst: SocketAddrV4) -> u16 {
+        self.classify_ipv4_tcp(src, dst)
+    }
+
+    /// Returns the queue of the packet. Packets that are not IP are
+    /// received on queue 0.
+    pub fn classify_packet(&self, pkt: &Ethernet) -> u16 {
+        let hash = match pkt.ether_type() {
+            EtherTypes::Ipv4 => match pkt.peek::<Ipv4>() {
+                Ok(ipv4) => {
+                    let (src, dst) = (ipv4.src().octets(), ipv4.dst().octets());
+                    let ports = match ipv4.protocol() {
+                        ProtocolNumbers::Tcp => ipv4
+                            .peek::<Tcp4>()
+                            .ok()
+                            .map(|tcp| (tcp.src_port(), tcp.dst_port())),
+                        ProtocolNumbers::Udp => ipv4
+                            .peek::<Udp4>()
+                            .ok()
+                            .map(|udp| (udp.src_port(), udp.dst_port())),
+                        _ => None,
+                    };
+                    match ports {
+                        Some((src_port, dst_port)) => {
+                            toeplitz_hash(&self.key, &ipv4_tuple(src, dst, src_port, dst_port))
+                        }
+                        None => toeplitz_hash(&self.key, &ipv4_tuple(src, dst, 0, 0)[..8]),
+                    }
+                }
+                Err(_) => return 0,
+            },
+            EtherTypes::Ipv6 => match pkt.peek::<Ipv6>() {
+                Ok(ipv6) => {
+                    let mut input = Vec::with_capacity(36);
+                    input.extend_from_slice(&ipv6.src().octets());
+                    input.extend_from_slice(&ipv6.dst().octets());
+                    let ports = match ipv6.next_header() {
+                        ProtocolNumbers::Tcp => ipv6
+                            .peek::<Tcp6>()
+                            .ok()
+                            .map(|tcp| (tcp.src_port(), tcp.dst_port())),
+                        ProtocolNumbers::Udp => ipv6
+                            .peek::<Udp6>()
+                            .ok()
+                            .map(|udp| (udp.src_port(), udp.dst_port())),
+                        _ => None,
+                    };
+                    if let Some((src_port, dst_port)) = ports {
+                        input.extend_from_slice(&src_port.to_be_bytes());
+                        input.extend_from_slice(&dst_port.to_be_bytes());
+                    }
+                    toeplitz_hash(&self.key, &input)
+                }
+                Err(_) => return 0,
+            },
+            _ => return 0,
+        };
+
+        self.queue(hash)
+    }
+}
+
+/// Returns the Toeplitz hash input of an IPv4 flow with ports.
+fn ipv4_tuple(src: [u8; 4], dst: [u8; 4], src_port: u16, dst_port: u16) -> [u8; 12] {
+    let mut input = [0; 12];
+    input[..4].copy_from_slice(&src);
+    input[4..8].copy_from_slice(&dst);
+    input[8..10].copy_from_slice(&src_port.to_be_bytes());
+    input[10..].copy_from_slice(&dst_port.to_be_bytes());
+    input
+}
+
+/// Computes the Toeplitz hash of the input with the key, as the
+/// Ethernet devices do for receive side scaling.
+///
+/// For every bit set in the input, the 32 bits of the key starting at
+/// the same bit offset are XOR-ed into the hash.
+pub(crate) fn toeplitz_hash(key: &[u8], input: &[u8]) -> u32 {
+    let key_bit = |offset: usize| {
+        key.get(offset / 8)
+            .map_or(0, |byte| (byte >> (7 - offset % 8)) as u32 & 1)
+    };
+
+    // the 32-bit window of the key aligned with the current input bit.
+    let mut window = (0..32).fold(0u32, |window, offset| window << 1 | key_bit(offset));
+    let mut hash = 0;
+
+    for (idx, byte) in input.iter().enumerate() {
+        for bit in 0..8 {
+            if byte & (0x80 >> bit) != 0 {
+                hash ^= window;
+            }
+            window = window << 1 | key_bit(idx * 8 + bit + 32);
+        }
+    }
+
+    hash
+}
+
+/// Returns the receive side scaling configuration of a port.
+pub(crate) fn rss_hash_conf_get(port_id: PortId) -> Result<RssConfig> {
+    let mut dev_info = ffi::rte_eth_dev_info::default();
+    unsafe {
+        ffi::rte_eth_dev_info_get(port_id.raw(), &mut dev_info);
+    }
+
+    let mut key = vec![0u8; dev_info.hash_key_size as usize];
+    let mut conf = ffi::rte_eth_rss_conf {
+        rss_key: key.as_mut_ptr(),
+        rss_key_len: key.len() as u8,
+        ..Default::default()
+    };
+
+    unsafe {
+        ffi::rte_eth_dev_rss_hash_conf_get(port_id.raw(), &mut conf)
+            .into_result(DpdkError::from_errno)?;
+    }
+
+    Ok(RssConfig {
+        hash_key: Some(key),
+        hash_functions: RssHashFunc::from_bits_truncate(conf.rss_hf),
+    })
+}
+
+/// Updates the receive side scaling configuration of a port.
+///
+/// If the configuration does not have a hash key, the device keeps
+/// using its current key.
+pub(crate) fn rss_hash_update(port_id: PortId, rss_conf: &RssConfig) -> Result<()> {
+    let mut key = rss_conf.hash_key.clone().unwrap_or_default();
+    let mut conf = ffi::rte_eth_rss_conf {
+        rss_key: if key.is_empty() {
+            std::ptr::null_mut()
+        } else {
+            key.as_mut_ptr()
+        },
+        rss_key_len: key.len() as u8,
+        rss_hf: rss_conf.hash_functions.bits(),
+    };
+
+    unsafe {
+        ffi::rte_eth_dev_rss_hash_update(port_id.raw(), &mut conf)
+            .into_result(DpdkError::from_errno)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::{ARP4_PACKET, IPV4_UDP_PACKET};
+    use crate::Mbuf;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn rss_hash_func_groups() {
+        assert!(RssHashFunc::TCP.contains(RssHashFunc::IPV4_TCP));
+        assert!(RssHashFunc::TCP.contains(RssHashFunc::IPV6_TCP_EX));
+        assert!(!RssHashFunc::IP.intersects(RssHashFunc::UDP));
+    }
+
+    #[test]
+    fn rss_config_builder() {
+        let key = [0x6d, 0x5a];
+        let conf = RssConfig::new(RssHashFunc::IP).hash_key(&key);
+
+        assert_eq!(Some(&key[..]), conf.key());
+        assert_eq!(RssHashFunc::IP, conf.hash_functions());
+        assert_eq!(None, RssConfig::new(RssHashFunc::IP).key());
+    }
+    /// The default hash key of the Microsoft RSS verification suite.
+    const MS_KEY: [u8; 40] = [
+        0x6d, 0x5a, 0x56, 0xda, 0x25, 0x5b, 0x0e, 0xc2, 0x41, 0x67, 0x25, 0x3d, 0x43, 0xa3, 0x8f,
+        0xb0, 0xd0, 0xca, 0x2b, 0xcb, 0xae, 0x7b, 0x30, 0xb4, 0x77, 0xcb, 0x2d, 0xa3, 0x80, 0x30,
+        0xf2, 0x0c, 0x6a, 0x42, 0xb7, 0x3b, 0xbe, 0xac, 0x01, 0xfa,
+    ];
+
+    #[test]
+    fn toeplitz_verification_suite() {
+        let input = ipv4_tuple([66, 9, 149, 187], [161, 142, 100, 80], 2794, 1766);
+        assert_eq!(0x51cc_c178, toeplitz_hash(&MS_KEY, &input));
+
+        let input = ipv4_tuple([199, 92, 111, 2], [65, 69, 140, 83], 14230, 4739);
+        assert_eq!(0xc626_b0ea, toeplitz_hash(&MS_KEY, &input));
+
+        // the address only hash uses the first 8 bytes.
+        assert_eq!(0xd718_262a, toeplitz_hash(&MS_KEY, &input[..8]));
+    }
+
+    #[test]
+    fn rss_sim_uniform_distribution() {
+        const FLOWS: usize = 10_000;
+        const QUEUES: u16 = 8;
+        // the critical value of the chi-squared distribution with 7 degrees
+        // of freedom at p = 0.05.
+        const CHI_SQUARED_CRITICAL: f64 = 14.07;
+
+        let rss = RssSim::new(MS_KEY, QUEUES);
+        let dst = SocketAddrV4::new(Ipv4Addr::new(192, 168, 0, 1), 80);
+        let mut counts = [0usize; QUEUES as usize];
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+
+        for _ in 0..FLOWS {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            let src = SocketAddrV4::new(
+                Ipv4Addr::new(10, (x >> 8) as u8, (x >> 16) as u8, (x >> 24) as u8),
+                (x >> 32) as u16,
+            );
+            counts[rss.classify_ipv4_tcp(src, dst) as usize] += 1;
+        }
+
+        let expected = FLOWS as f64 / QUEUES as f64;
+        let chi_squared: f64 = counts
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(
+            chi_squared < CHI_SQUARED_CRITICAL,
+            "chi-squared {}",
+            chi_squared
+        );
+    }
+
+    #[capsule::test]
+    fn rss_sim_classify_packet() {
+        let rss = RssSim::new(MS_KEY, 4);
+
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let ipv4 = ethernet.peek::<Ipv4>().unwrap();
+        let udp = ipv4.peek::<Udp4>().unwrap();
+        let src = SocketAddrV4::new(ipv4.src(), udp.src_port());
+        let dst = SocketAddrV4::new(ipv4.dst(), udp.dst_port());
+        assert_eq!(
+            rss.classify_ipv4_udp(src, dst),
+            rss.classify_packet(&ethernet)
+        );
+
+        let packet = Mbuf::from_bytes(&ARP4_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        assert_eq!(0, rss.classify_packet(&ethernet));
+    }
+
+    #[test]
+    fn symmetric_key_hashes_both_directions() {
+        for &seed in &[0, 1, 0xdead_beef, u32::MAX] {
+            let key = SymmetricRssKey::generate(seed);
+            assert!(SymmetricRssKey::verify(&key));
+
+            let a = ipv4_tuple([10, 1, 2, 3], [172, 16, 0, 9], 443, 51234);
+            let b = ipv4_tuple([172, 16, 0, 9], [10, 1, 2, 3], 51234, 443);
+            assert_eq!(toeplitz_hash(&key, &a), toeplitz_hash(&key, &b));
+        }
+    }
+
+    #[test]
+    fn default_key_is_not_symmetric() {
+        assert!(!SymmetricRssKey::verify(&MS_KEY));
+    }
+
+    #[test]
+    fn generate_key_of_any_length() {
+        let key = SymmetricRssKey::generate_len(0x1234_5678, 52);
+        assert_eq!(52, key.len());
+        assert_eq!(&key[..40], &SymmetricRssKey::generate(0x1234_5678)[..]);
+    }
+}
@@ -0,0 +1,530 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::{DpdkError, Mbuf, SocketId};
+use crate::ffi::{self, ToCString, ToResult};
+use crate::packets::{Ethernet, Packet};
+use crate::{ensure, info};
+use anyhow::Result;
+use std::fmt;
+use std::ptr::NonNull;
+use thiserror::Error;
+
+/// The maximum number of traffic classes per pipe, including the best
+/// effort class.
+const MAX_TRAFFIC_CLASSES: usize = ffi::RTE_SCHED_TRAFFIC_CLASSES_PER_PIPE as usize;
+
+/// The index of the best effort traffic class.
+const BE_TRAFFIC_CLASS: usize = ffi::RTE_SCHED_TRAFFIC_CLASS_BE as usize;
+
+/// The number of queues of the best effort traffic class.
+const BE_QUEUES: usize = ffi::RTE_SCHED_BE_QUEUES_PER_PIPE as usize;
+
+/// The token bucket size of a subport in bytes.
+const SUBPORT_TB_SIZE: u32 = 1_000_000;
+
+/// The enforcement period of the traffic class rates of a subport in
+/// milliseconds.
+const SUBPORT_TC_PERIOD: u32 = 10;
+
+/// Scheduler errors.
+#[derive(Debug, Error)]
+pub(crate) enum SchedError {
+    /// The number of traffic classes is not within the supported range.
+    #[error("Number of traffic classes {0} is not within 1..=13.")]
+    InvalidTrafficClasses(usize),
+
+    /// The number of per traffic class rates is not the number of traffic
+    /// classes.
+    #[error("Expected {1} traffic class rates, got {0}.")]
+    InvalidTcRates(usize, usize),
+
+    /// The subport id is not valid.
+    #[error("Subport {0} is not found.")]
+    InvalidSubport(u32),
+
+    /// The pipe id is not valid.
+    #[error("Pipe {0} is not found.")]
+    InvalidPipe(u32),
+
+    /// The traffic class or the queue is not valid.
+    #[error("Traffic class {0} queue {1} is not found.")]
+    InvalidQueue(u32, u32),
+
+    /// Some packets were dropped because their queues are full.
+    #[error("{0} packets dropped by full queues.")]
+    Dropped(usize),
+}
+
+/// The rates of a pipe, applied to the flows mapped to the pipe.
+///
+/// All rates are in bytes per second.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PipeProfile {
+    /// The rate of the token bucket of the pipe.
+    pub rate: u32,
+    /// The size of the token bucket of the pipe in bytes.
+    pub bucket_size: u32,
+    /// The rate of each traffic class. The length must be the number of
+    /// traffic classes of the scheduler, and each rate must not exceed
+    /// the pipe rate.
+    pub tc_rates: Vec<u32>,
+    /// The enforcement period of the traffic class rates in milliseconds.
+    pub tc_period: u32,
+    /// The weights of the best effort queues.
+    pub wrr_weights: [u8; BE_QUEUES],
+}
+
+impl PipeProfile {
+    /// Creates a new profile that allows `rate` for the pipe and for each
+    /// of the `traffic_classes`.
+    pub fn new(rate: u32, traffic_classes: usize) -> Self {
+        PipeProfile {
+            rate,
+            bucket_size: 1_000_000,
+            tc_rates: vec![rate; traffic_classes],
+            tc_period: 40,
+            wrr_weights: [1; BE_QUEUES],
+        }
+    }
+
+    fn to_raw(&self, traffic_classes: usize) -> Result<ffi::rte_sched_pipe_params> {
+        ensure!(
+            self.tc_rates.len() == traffic_classes,
+            SchedError::InvalidTcRates(self.tc_rates.len(), traffic_classes)
+        );
+
+        let mut raw = ffi::rte_sched_pipe_params {
+            tb_rate: self.rate,
+            tb_size: self.bucket_size,
+            tc_period: self.tc_period,
+            tc_ov_weight: 1,
+            wrr_weights: self.wrr_weights,
+            ..Default::default()
+        };
+        for (tc, &rate) in self.tc_rates.iter().enumerate() {
+            raw.tc_rate[tc_index(tc, traffic_classes)] = rate;
+        }
+
+        Ok(raw)
+    }
+}
+
+/// The configuration of a `Scheduler`.
+///
+/// The hierarchy is a port with `subports`, each with `pipes_per_subport`
+/// pipes. Each pipe has `traffic_classes` classes served in strict
+/// priority order, 0 being the highest. The last class is the best effort
+/// class, which has 4 queues served in weighted round robin. Every other
+/// class has a single queue. All rates are in bytes per second.
+#[derive(Clone, Debug)]
+pub struct SchedConfig {
+    /// The name of the scheduler.
+    pub name: String,
+    /// The socket to allocate the scheduler on. `SocketId::ANY` allocates
+    /// it on the socket of the current core.
+    pub socket_id: SocketId,
+    /// The rate of the output port.
+    pub rate: u32,
+    /// The maximum frame size of the output port.
+    pub mtu: u32,
+    /// The framing overhead per packet in bytes.
+    pub frame_overhead: u32,
+    /// The number of subports.
+    pub subports: u32,
+    /// The number of pipes per subport.
+    pub pipes_per_subport: u32,
+    /// The number of traffic classes per pipe, between 1 and 13.
+    pub traffic_classes: usize,
+    /// The rate of each traffic class of each subport.
+    pub tc_rates: Vec<u32>,
+    /// The size of each queue in packets. Must be a power of 2.
+    pub queue_size: u16,
+    /// The maximum number of pipe profiles per subport.
+    pub max_pipe_profiles: u32,
+    /// The profile all the pipes start with.
+    pub default_profile: PipeProfile,
+}
+
+impl Default for SchedConfig {
+    fn default() -> Self {
+        // 10 Gbps.
+        let rate = 1_250_000_000;
+        SchedConfig {
+            name: "sched".to_owned(),
+            socket_id: SocketId::ANY,
+            rate,
+            mtu: 1522,
+            frame_overhead: ffi::RTE_SCHED_FRAME_OVERHEAD_DEFAULT,
+            subports: 1,
+            pipes_per_subport: 64,
+            traffic_classes: 1,
+            tc_rates: vec![rate],
+            queue_size: 64,
+            max_pipe_profiles: 16,
+            default_profile: PipeProfile::new(rate, 1),
+        }
+    }
+}
+
+/// The position of a packet in the scheduler hierarchy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SchedClass {
+    /// The subport id.
+    pub subport: u32,
+    /// The pipe id within the subport.
+    pub pipe: u32,
+    /// The traffic class of the pipe.
+    pub traffic_class: u32,
+    /// The queue within the best effort class. Must be 0 for the other
+    /// classes.
+    pub queue: u32,
+}
+
+/// A hierarchical QoS scheduler backed by `rte_sched`.
+///
+/// Packets are classified with `classify` before they are enqueued;
+/// unclassified packets go to the first queue of the first pipe. A
+/// scheduler is meant to be run by a single core.
+pub struct Scheduler {
+    raw: NonNull<ffi::rte_sched_port>,
+    name: String,
+    subports: u32,
+    pipes_per_subport: u32,
+    traffic_classes: usize,
+    // the pipe profiles of each subport, indexed by profile id.
+    profiles: Vec<Vec<PipeProfile>>,
+}
+
+impl Scheduler {
+    /// Creates a new scheduler with all the pipes on the default profile.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of traffic classes or rates is not
+    /// valid. If the scheduler cannot be configured, `DpdkError` is
+    /// returned.
+    pub fn new(config: SchedConfig) -> Result<Self> {
+        let tcs = config.traffic_classes;
+        ensure!(
+            (1..=MAX_TRAFFIC_CLASSES).contains(&tcs),
+            SchedError::InvalidTrafficClasses(tcs)
+        );
+        ensure!(
+            config.tc_rates.len() == tcs,
+            SchedError::InvalidTcRates(config.tc_rates.len(), tcs)
+        );
+        let mut profile = config.default_profile.to_raw(tcs)?;
+
+        // `rte_sched` does not accept any socket.
+        let socket_id = if config.socket_id == SocketId::ANY {
+            SocketId::current()
+        } else {
+            config.socket_id
+        };
+
+        let name = config.name.clone().into_cstring();
+        let mut params = ffi::rte_sched_port_params {
+            name: name.as_ptr(),
+            socket: socket_id.raw(),
+            rate: config.rate,
+            mtu: config.mtu,
+            frame_overhead: config.frame_overhead,
+            n_subports_per_port: config.subports,
+            n_pipes_per_subport: config.pipes_per_subport,
+        };
+
+        let raw =
+            unsafe { ffi::rte_sched_port_config(&mut params).into_result(|_| DpdkError::new())? };
+
+        // from here on, drop frees the scheduler.
+        let sched = Scheduler {
+            raw,
+            name: config.name,
+            subports: config.subports,
+            pipes_per_subport: config.pipes_per_subport,
+            traffic_classes: tcs,
+            profiles: vec![vec![config.default_profile.clone()]; config.subports as usize],
+        };
+
+        let mut subport = ffi::rte_sched_subport_params {
+            tb_rate: config.rate,
+            tb_size: SUBPORT_TB_SIZE,
+            tc_period: SUBPORT_TC_PERIOD,
+            n_pipes_per_subport_enabled: config.pipes_per_subport,
+            pipe_profiles: &mut profile,
+            n_pipe_profiles: 1,
+            n_max_pipe_profiles: config.max_pipe_profiles,
+            ..Default::default()
+        };
+        for (tc, &rate) in config.tc_rates.iter().enumerate() {
+            let index = tc_index(tc, tcs);
+            subport.tc_rate[index] = rate;
+            subport.qsize[index] = config.queue_size;
+        }
+        for queue in 1..BE_QUEUES {
+            subport.qsize[BE_TRAFFIC_CLASS + queue] = config.queue_size;
+        }
+
+        unsafe {
+            for subport_id in 0..config.subports {
+                ffi::rte_sched_subport_config(sched.raw.as_ptr(), subport_id, &mut subport)
+                    .into_result(DpdkError::from_errno)?;
+
+                for pipe_id in 0..config.pipes_per_subport {
+                    ffi::rte_sched_pipe_config(sched.raw.as_ptr(), subport_id, pipe_id, 0)
+                        .into_result(DpdkError::from_errno)?;
+                }
+            }
+        }
+
+        info!("created {}.", sched.name);
+        Ok(sched)
+    }
+
+    /// Returns the name of the scheduler.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Sets the position of the packet in the hierarchy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the subport, the pipe, the traffic class or the
+    /// queue is not valid.
+    pub fn classify(&self, pkt: &mut Ethernet, class: SchedClass) -> Result<()> {
+        ensure!(
+            class.subport < self.subports,
+            SchedError::InvalidSubport(class.subport)
+        );
+        ensure!(
+            class.pipe < self.pipes_per_subport,
+            SchedError::InvalidPipe(class.pipe)
+        );
+
+        let tc = class.traffic_class as usize;
+        let max_queue = if tc + 1 == self.traffic_classes {
+            BE_QUEUES as u32
+        } else {
+            1
+        };
+        ensure!(
+            tc < self.traffic_classes && class.queue < max_queue,
+            SchedError::InvalidQueue(class.traffic_class, class.queue)
+        );
+
+        unsafe {
+            ffi::rte_sched_port_pkt_write(
+                self.raw.as_ptr(),
+                pkt.mbuf_mut().raw_mut(),
+                class.subport,
+                class.pipe,
+                tc_index(tc, self.traffic_classes) as u32,
+                class.queue,
+                ffi::rte_color::RTE_COLOR_GREEN,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Enqueues the packets to the queues they are classified to.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SchedError::Dropped` if some packets are dropped because
+    /// their queues are full. The dropped packets are freed.
+    pub fn enqueue(&self, pkts: Vec<Ethernet>) -> Result<()> {
+        let len = pkts.len();
+        let mut mbufs = pkts
+            .into_iter()
+            .map(|pkt| pkt.reset().into_ptr())
+            .collect::<Vec<_>>();
+
+        let enqueued = unsafe {
+            ffi::rte_sched_port_enqueue(self.raw.as_ptr(), mbufs.as_mut_ptr(), len as u32)
+        } as usize;
+
+        ensure!(enqueued == len, SchedError::Dropped(len - enqueued));
+        Ok(())
+    }
+
+    /// Dequeues up to `max` packets that are allowed by the rates, and
+    /// appends them to `out`. Returns the number of packets dequeued.
+    pub fn dequeue(&self, max: u32, out: &mut Vec<Ethernet>) -> u32 {
+        let mut mbufs = Vec::with_capacity(max as usize);
+
+        unsafe {
+            let count = ffi::rte_sched_port_dequeue(self.raw.as_ptr(), mbufs.as_mut_ptr(), max);
+            mbufs.set_len(count as usize);
+        }
+
+        let count = mbufs.len() as u32;
+        out.extend(
+            mbufs
+                .into_iter()
+                .filter_map(|ptr| unsafe { Mbuf::from_ptr(ptr) }.parse::<Ethernet>().ok()),
+        );
+        count
+    }
+
+    /// Changes the rates of a pipe.
+    ///
+    /// A profile is added to the subport the first time it is used, and
+    /// reused by the pipes set to an identical profile afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the subport or the pipe is not valid, or if the
+    /// number of traffic class rates is not the number of traffic classes.
+    /// If the subport has reached its maximum number of profiles, or the
+    /// rates are not valid, `DpdkError` is returned.
+    pub fn set_pipe_profile(
+        &mut self,
+        subport_id: u32,
+        pipe_id: u32,
+        profile: PipeProfile,
+    ) -> Result<()> {
+        ensure!(
+            subport_id < self.subports,
+            SchedError::InvalidSubport(subport_id)
+        );
+        ensure!(
+            pipe_id < self.pipes_per_subport,
+            SchedError::InvalidPipe(pipe_id)
+        );
+
+        let profiles = &mut self.profiles[subport_id as usize];
+        let profile_id = match profiles.iter().position(|p| *p == profile) {
+            Some(id) => id as u32,
+            None => {
+                let mut raw = profile.to_raw(self.traffic_classes)?;
+                let mut id = 0;
+                unsafe {
+                    ffi::rte_sched_subport_pipe_profile_add(
+                        self.raw.as_ptr(),
+                        subport_id,
+                        &mut raw,
+                        &mut id,
+                    )
+                    .into_result(DpdkError::from_errno)?;
+                }
+                profiles.push(profile);
+                id
+            }
+        };
+
+        unsafe {
+            ffi::rte_sched_pipe_config(self.raw.as_ptr(), subport_id, pipe_id, profile_id as i32)
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the `rte_sched` index of the traffic class. The last class is
+/// mapped to the best effort class.
+fn tc_index(tc: usize, traffic_classes: usize) -> usize {
+    if tc + 1 == traffic_classes {
+        BE_TRAFFIC_CLASS
+    } else {
+        tc
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("name", &self.name)
+            .field("subports", &self.subports)
+            .field("pipes_per_subport", &self.pipes_per_subport)
+            .field("traffic_classes", &self.traffic_classes)
+            .finish()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rte_sched_port_free(self.raw.as_ptr());
+        }
+    }
+}
+
+unsafe impl Send for Scheduler {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+
+    #[test]
+    fn invalid_traffic_classes() {
+        let config = SchedConfig {
+            traffic_classes: 0,
+            ..Default::default()
+        };
+        assert!(Scheduler::new(config).is_err());
+
+        let config = SchedConfig {
+            traffic_classes: 2,
+            ..Default::default()
+        };
+        assert!(Scheduler::new(config).is_err());
+    }
+
+    #[capsule::test]
+    fn enqueue_and_dequeue() {
+        let sched = Scheduler::new(SchedConfig::default()).unwrap();
+
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let mut ethernet = packet.parse::<Ethernet>().unwrap();
+        let class = SchedClass {
+            pipe: 1,
+            queue: 2,
+            ..Default::default()
+        };
+        assert!(sched.classify(&mut ethernet, class).is_ok());
+        assert!(sched.enqueue(vec![ethernet]).is_ok());
+
+        // the grinders may need a few rounds to pick up the queue.
+        let mut out = vec![];
+        for _ in 0..100 {
+            if sched.dequeue(32, &mut out) > 0 {
+                break;
+            }
+        }
+        assert_eq!(1, out.len());
+    }
+
+    #[capsule::test]
+    fn set_pipe_profile() {
+        let mut sched = Scheduler::new(SchedConfig::default()).unwrap();
+        let profile = PipeProfile::new(1_000_000, 1);
+
+        assert!(sched.set_pipe_profile(0, 0, profile.clone()).is_ok());
+        assert!(sched.set_pipe_profile(0, 1, profile.clone()).is_ok());
+        assert_eq!(2, sched.profiles[0].len());
+
+        assert!(sched.set_pipe_profile(1, 0, profile.clone()).is_err());
+        assert!(sched
+            .set_pipe_profile(0, 0, PipeProfile::new(1_000_000, 2))
+            .is_err());
+    }
+}
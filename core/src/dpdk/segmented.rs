@@ -0,0 +1,220 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{DpdkError, Mbuf};
+use crate::packets::{Ethernet, Packet};
+use crate::{ensure, ffi};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::iter;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use std::slice;
+
+/// A packet made of a chain of message buffers.
+///
+/// The segments are linked through the `next` pointers of the buffers.
+/// The first segment holds the total length and the number of segments
+/// of the chain.
+pub struct SegmentedPacket {
+    mbuf: Mbuf,
+}
+
+impl SegmentedPacket {
+    /// Creates a new segmented packet with the buffer as the first
+    /// segment. The buffer may already be a chain.
+    pub fn new(mbuf: Mbuf) -> Self {
+        SegmentedPacket { mbuf }
+    }
+
+    /// Returns the number of segments.
+    pub fn nb_segs(&self) -> usize {
+        self.mbuf.nb_segs()
+    }
+
+    /// Returns the length of the packet across all the segments.
+    pub fn pkt_len(&self) -> usize {
+        self.mbuf.pkt_len()
+    }
+
+    /// Returns an iterator over the data of each segment.
+    pub fn segments(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.mbuf.segments()
+    }
+
+    /// Returns an iterator over the mutable data of each segment.
+    pub fn segments_mut(&mut self) -> impl Iterator<Item = &mut [u8]> + '_ {
+        let first = NonNull::from(self.mbuf.raw_mut());
+        SegmentsMut {
+            next: Some(first),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Chains a buffer to the end of the packet. The buffer may be a
+    /// chain itself. The total length and the number of segments are
+    /// updated.
+    ///
+    /// # Errors
+    ///
+    /// If the chain would exceed the maximum number of segments,
+    /// `DpdkError` is returned and the buffer is freed.
+    pub fn append_segment(&mut self, new_seg: Mbuf) -> Result<()> {
+        let tail = new_seg.into_ptr();
+
+        unsafe {
+            let res = ffi::_rte_pktmbuf_chain(self.mbuf.raw_mut(), tail);
+            if res < 0 {
+                let _ = Mbuf::from_ptr(tail);
+                return Err(DpdkError::from_code(res).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Coalesces the segments into a single buffer and parses it as an
+    /// Ethernet frame.
+    ///
+    /// The data is moved into the first segment if it has enough tailroom.
+    /// Otherwise, the packet is copied into a new buffer allocated from the
+    /// same `Mempool`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the copy fails, or if the data is too large to
+    /// fit in a single buffer, or if the packet is not a valid Ethernet
+    /// frame.
+    pub fn coalesce(self) -> Result<Ethernet> {
+        let mut mbuf = self.mbuf;
+
+        if mbuf.nb_segs() > 1 {
+            let res = unsafe { ffi::_rte_pktmbuf_linearize(mbuf.raw_mut()) };
+            if res < 0 {
+                mbuf = mbuf.try_clone()?;
+            }
+        }
+
+        ensure!(
+            mbuf.nb_segs() == 1,
+            anyhow!(
+                "packet of {} bytes does not fit in one segment.",
+                mbuf.pkt_len()
+            )
+        );
+
+        mbuf.parse::<Ethernet>()
+    }
+
+    /// Returns the first segment of the packet.
+    pub fn into_mbuf(self) -> Mbuf {
+        self.mbuf
+    }
+}
+
+impl From<Mbuf> for SegmentedPacket {
+    fn from(mbuf: Mbuf) -> Self {
+        SegmentedPacket::new(mbuf)
+    }
+}
+
+impl fmt::Debug for SegmentedPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SegmentedPacket")
+            .field("nb_segs", &self.nb_segs())
+            .field("pkt_len", &self.pkt_len())
+            .finish()
+    }
+}
+
+/// Mutable iterator over the segments of a packet.
+struct SegmentsMut<'a> {
+    next: Option<NonNull<ffi::rte_mbuf>>,
+    _phantom: PhantomData<&'a mut ffi::rte_mbuf>,
+}
+
+impl<'a> Iterator for SegmentsMut<'a> {
+    type Item = &'a mut [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next.map(|seg| unsafe {
+            let seg = seg.as_ptr();
+            self.next = NonNull::new((*seg).next);
+            // each segment has its own data buffer, so the slices do not
+            // overlap.
+            let data = ((*seg).buf_addr as *mut u8).offset((*seg).data_off as isize);
+            slice::from_raw_parts_mut(data, (*seg).data_len as usize)
+        })
+    }
+}
+
+impl iter::FusedIterator for SegmentsMut<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+
+    #[capsule::test]
+    fn append_and_iterate_segments() {
+        let (head, tail) = IPV4_UDP_PACKET.split_at(20);
+        let mut packet = SegmentedPacket::new(Mbuf::from_bytes(head).unwrap());
+        packet
+            .append_segment(Mbuf::from_bytes(tail).unwrap())
+            .unwrap();
+
+        assert_eq!(2, packet.nb_segs());
+        assert_eq!(IPV4_UDP_PACKET.len(), packet.pkt_len());
+
+        let segments = packet.segments().collect::<Vec<_>>();
+        assert_eq!(head, segments[0]);
+        assert_eq!(tail, segments[1]);
+    }
+
+    #[capsule::test]
+    fn mutate_segments() {
+        let mut packet = SegmentedPacket::new(Mbuf::from_bytes(&[1, 2]).unwrap());
+        packet
+            .append_segment(Mbuf::from_bytes(&[3, 4]).unwrap())
+            .unwrap();
+
+        for segment in packet.segments_mut() {
+            segment[0] = 0xff;
+        }
+
+        let segments = packet.segments().collect::<Vec<_>>();
+        assert_eq!(&[0xff, 2], segments[0]);
+        assert_eq!(&[0xff, 4], segments[1]);
+    }
+
+    #[capsule::test]
+    fn coalesce_segments() {
+        let (head, tail) = IPV4_UDP_PACKET.split_at(20);
+        let mut packet = SegmentedPacket::new(Mbuf::from_bytes(head).unwrap());
+        packet
+            .append_segment(Mbuf::from_bytes(tail).unwrap())
+            .unwrap();
+
+        let ethernet = packet.coalesce().unwrap();
+        assert_eq!(1, ethernet.mbuf().nb_segs());
+        assert_eq!(
+            &IPV4_UDP_PACKET[..],
+            ethernet.mbuf().segments().next().unwrap()
+        );
+    }
+}
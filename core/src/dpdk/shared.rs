@@ -0,0 +1,248 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::Mbuf;
+use crate::packets::{Ethernet, Packet};
+use crate::{ensure, ffi};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::ops::Deref;
+
+/// Zero-copy cloner for fanning out a packet to multiple destinations.
+///
+/// Instead of copying the data, the cloner bumps the reference counter
+/// of the underlying message buffer. Every clone frees the buffer when
+/// dropped or transmitted, and DPDK only returns the buffer to its
+/// `Mempool` once the last reference is gone.
+#[derive(Debug)]
+pub struct PacketCloner;
+
+impl PacketCloner {
+    /// Turns the packet into `n` shared packets pointing to the same
+    /// message buffer.
+    ///
+    /// The packet is consumed. Its own reference is handed to the first
+    /// clone, so the reference counter is increased by `n - 1`. If `n` is
+    /// 0, the packet is dropped and no clone is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reference counter would overflow.
+    pub fn clone_to_n(packet: Ethernet, n: u16) -> Result<Vec<SharedPacket>> {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+
+        ensure!(
+            n - 1 <= i16::MAX as u16,
+            anyhow!("too many references to the packet.")
+        );
+
+        let ptr = packet.reset().into_ptr();
+        let delta = (n - 1) as i16;
+        let refcnt = unsafe { ffi::_rte_mbuf_refcnt_update(ptr, delta) };
+        if refcnt < n {
+            // the counter wrapped around, undoes the update. the packet
+            // is freed on the way out, same as if it was dropped.
+            unsafe {
+                ffi::_rte_mbuf_refcnt_update(ptr, -delta);
+                drop(Mbuf::from_ptr(ptr));
+            }
+            return Err(anyhow!("too many references to the packet."));
+        }
+
+        // the parse will succeed for all the clones because it already
+        // succeeded for the original. but if it fails, the clones dropped
+        // will release their references, so nothing is leaked.
+        (0..n)
+            .map(|_| unsafe { Mbuf::from_ptr(ptr) }.parse::<Ethernet>())
+            .map(|packet| packet.map(|packet| SharedPacket { packet }))
+            .collect()
+    }
+}
+
+/// An Ethernet packet whose message buffer may be referenced by other
+/// packets.
+///
+/// Because the data is shared, only read access is allowed. The header
+/// and the payload can be inspected through `Deref`, but they cannot be
+/// mutated. Use `try_into_exclusive` to get the packet back once all the
+/// other references are gone.
+pub struct SharedPacket {
+    packet: Ethernet,
+}
+
+impl SharedPacket {
+    /// Returns the number of packets referencing the message buffer,
+    /// including this one.
+    pub fn refcnt(&self) -> u16 {
+        unsafe { ffi::_rte_mbuf_refcnt_read(self.packet.mbuf().raw()) }
+    }
+
+    /// Returns whether this is the only reference to the message buffer.
+    pub fn is_exclusive(&self) -> bool {
+        self.refcnt() == 1
+    }
+
+    /// Returns the exclusive packet if this is the only reference to the
+    /// message buffer. Otherwise the shared packet is given back.
+    pub fn try_into_exclusive(self) -> Result<Ethernet, SharedPacket> {
+        if self.is_exclusive() {
+            Ok(self.packet)
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Returns a message buffer with the packet that can be modified.
+    ///
+    /// If this is the only reference, the buffer is returned as is.
+    /// Otherwise the whole packet, with all its segments, is copied into a
+    /// new buffer from the same mempool and this reference is released,
+    /// leaving the other clones untouched. Use `PortQueue::transmit_shared`
+    /// to send the packet without the copy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is copied and the mempool is
+    /// exhausted.
+    pub fn into_mbuf(self) -> Result<Mbuf> {
+        if self.is_exclusive() {
+            return Ok(self.packet.reset());
+        }
+
+        self.packet.mbuf().try_clone()
+    }
+
+    /// Returns the message buffer still shared with the other clones.
+    ///
+    /// The buffer must only be handed to a transmit queue.
+    pub(crate) fn into_shared_mbuf(self) -> Mbuf {
+        self.packet.reset()
+    }
+}
+
+impl Deref for SharedPacket {
+    type Target = Ethernet;
+
+    fn deref(&self) -> &Self::Target {
+        &self.packet
+    }
+}
+
+impl fmt::Debug for SharedPacket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedPacket")
+            .field("refcnt", &self.refcnt())
+            .field("packet", &self.packet)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpdk::SegmentedPacket;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+
+    #[capsule::test]
+    fn clone_to_n_shares_buffer() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET)
+            .unwrap()
+            .parse::<Ethernet>()
+            .unwrap();
+        let src = packet.src();
+
+        let clones = PacketCloner::clone_to_n(packet, 3).unwrap();
+        assert_eq!(3, clones.len());
+
+        let addr = clones[0].mbuf().raw() as *const ffi::rte_mbuf;
+        for clone in clones.iter() {
+            assert_eq!(3, clone.refcnt());
+            assert_eq!(src, clone.src());
+            assert_eq!(addr, clone.mbuf().raw() as *const ffi::rte_mbuf);
+        }
+    }
+
+    #[capsule::test]
+    fn drop_clones_until_exclusive() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET)
+            .unwrap()
+            .parse::<Ethernet>()
+            .unwrap();
+
+        let mut clones = PacketCloner::clone_to_n(packet, 2).unwrap();
+        let last = clones.pop().unwrap();
+        let last = last.try_into_exclusive().unwrap_err();
+        assert_eq!(2, last.refcnt());
+
+        drop(clones);
+        assert_eq!(1, last.refcnt());
+        let mut packet = last.try_into_exclusive().unwrap();
+        packet.swap_addresses();
+    }
+
+    #[capsule::test]
+    fn into_mbuf_copies_shared_buffer() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET)
+            .unwrap()
+            .parse::<Ethernet>()
+            .unwrap();
+
+        let mut clones = PacketCloner::clone_to_n(packet, 2).unwrap();
+        let shared = clones.pop().unwrap();
+        let addr = shared.mbuf().raw() as *const ffi::rte_mbuf;
+
+        let mbuf = shared.into_mbuf().unwrap();
+        assert_ne!(addr, mbuf.raw() as *const ffi::rte_mbuf);
+        assert_eq!(IPV4_UDP_PACKET.len(), mbuf.data_len());
+        assert!(clones[0].is_exclusive());
+
+        let last = clones.pop().unwrap();
+        let mbuf = last.into_mbuf().unwrap();
+        assert_eq!(addr, mbuf.raw() as *const ffi::rte_mbuf);
+    }
+
+    #[capsule::test]
+    fn into_mbuf_copies_all_segments() {
+        let (head, tail) = IPV4_UDP_PACKET.split_at(20);
+        let mut segmented = SegmentedPacket::new(Mbuf::from_bytes(head).unwrap());
+        segmented
+            .append_segment(Mbuf::from_bytes(tail).unwrap())
+            .unwrap();
+        let packet = segmented.into_mbuf().parse::<Ethernet>().unwrap();
+
+        let mut clones = PacketCloner::clone_to_n(packet, 2).unwrap();
+        let mbuf = clones.pop().unwrap().into_mbuf().unwrap();
+        assert_eq!(IPV4_UDP_PACKET.len(), mbuf.pkt_len());
+        assert_eq!(
+            IPV4_UDP_PACKET.to_vec(),
+            mbuf.segments().flatten().copied().collect::<Vec<_>>()
+        );
+    }
+
+    #[capsule::test]
+    fn clone_to_zero() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET)
+            .unwrap()
+            .parse::<Ethernet>()
+            .unwrap();
+
+        assert!(PacketCloner::clone_to_n(packet, 0).unwrap().is_empty());
+    }
+}
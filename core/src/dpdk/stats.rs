@@ -16,23 +16,22 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
-use super::{Mempool, Port, PortId};
-use crate::dpdk::DpdkError;
-use crate::ffi::{self, AsStr, ToResult};
+use super::{Mempool, Port, PortId, PortStats};
+use crate::ffi::{self, AsStr};
 use crate::metrics::{labels, Key, Measurement};
 use anyhow::Result;
 use std::ptr::NonNull;
 
 /// Port stats collector.
-pub(crate) struct PortStats {
+pub(crate) struct PortStatsCollector {
     id: PortId,
     name: String,
 }
 
-impl PortStats {
+impl PortStatsCollector {
     /// Builds a collector from the port.
     pub(crate) fn build(port: &Port) -> Self {
-        PortStats {
+        PortStatsCollector {
             id: port.id(),
             name: port.name().to_owned(),
         }
@@ -59,19 +58,16 @@ impl PortStats {
 
     /// Collects the port stats tracked by DPDK.
     pub(crate) fn collect(&self) -> Result<Vec<(Key, Measurement)>> {
-        let mut stats = ffi::rte_eth_stats::default();
-        unsafe {
-            ffi::rte_eth_stats_get(self.id.raw(), &mut stats).into_result(DpdkError::from_errno)?;
-        }
+        let stats = PortStats::query(self.id.raw())?;
 
         let mut values = Vec::new();
 
-        values.push(self.new_counter("octets", stats.ibytes, "rx"));
-        values.push(self.new_counter("octets", stats.obytes, "tx"));
-        values.push(self.new_counter("dropped", stats.imissed, "rx"));
-        values.push(self.new_counter("errors", stats.ierrors, "rx"));
-        values.push(self.new_counter("errors", stats.oerrors, "tx"));
-        values.push(self.new_counter("no_mbuf", stats.rx_nombuf, "rx"));
+        values.push(self.new_counter("octets", stats.rx_bytes(), "rx"));
+        values.push(self.new_counter("octets", stats.tx_bytes(), "tx"));
+        values.push(self.new_counter("dropped", stats.rx_missed(), "rx"));
+        values.push(self.new_counter("errors", stats.rx_errors(), "rx"));
+        values.push(self.new_counter("errors", stats.tx_errors(), "tx"));
+        values.push(self.new_counter("no_mbuf", stats.rx_nombuf(), "rx"));
 
         Ok(values)
     }
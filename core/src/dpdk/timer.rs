@@ -0,0 +1,222 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::{CoreId, DpdkError};
+use crate::error;
+use crate::ffi::{self, ToResult};
+use anyhow::Result;
+use std::fmt;
+use std::marker::PhantomData;
+use std::os::raw;
+use std::panic::{self, AssertUnwindSafe};
+use thiserror::Error;
+
+type TimerFn = Box<dyn Fn() + Send + 'static>;
+
+/// Error indicating the timer operation failed.
+#[derive(Debug, Error)]
+pub(crate) enum TimerError {
+    /// The timer callback is running on another lcore.
+    #[error("Timer is busy.")]
+    Busy,
+}
+
+/// A timer that runs a callback on an EAL lcore.
+///
+/// The callback runs on the lcore's thread when `TimerManager::manage` is
+/// called on that lcore and the timer has expired. Because the runtime only
+/// gives the master core to the EAL, the timer lcore is either the master
+/// core or a worker launched with `LcoreManager`.
+///
+/// The timer is stopped when dropped. The handle cannot be sent to another
+/// thread.
+///
+/// # Example
+///
+/// ```
+/// let hz = TimerManager::hz();
+/// let _timer = Timer::new_periodic(hz, CoreId::current(), || println!("tick"))?;
+/// loop {
+///     TimerManager::manage()?;
+/// }
+/// ```
+pub struct Timer {
+    // the `rte_timer` is linked into the per lcore timer list and must not
+    // move, hence it's boxed.
+    raw: Box<ffi::rte_timer>,
+    cb: Box<TimerFn>,
+    _phantom: PhantomData<*const ()>,
+}
+
+impl Timer {
+    /// Creates a timer that runs `cb` every `interval_cycles` on the lcore.
+    ///
+    /// # Errors
+    ///
+    /// If the lcore is not enabled in the EAL, `DpdkError` is returned.
+    pub fn new_periodic<F>(interval_cycles: u64, lcore: CoreId, cb: F) -> Result<Self>
+    where
+        F: Fn() + Send + 'static,
+    {
+        Timer::reset(
+            interval_cycles,
+            ffi::rte_timer_type::PERIODICAL,
+            lcore,
+            Box::new(cb),
+        )
+    }
+
+    /// Creates a timer that runs `cb` once after `delay_cycles` on the lcore.
+    ///
+    /// # Errors
+    ///
+    /// If the lcore is not enabled in the EAL, `DpdkError` is returned.
+    pub fn new_oneshot<F>(delay_cycles: u64, lcore: CoreId, cb: F) -> Result<Self>
+    where
+        F: Fn() + Send + 'static,
+    {
+        Timer::reset(
+            delay_cycles,
+            ffi::rte_timer_type::SINGLE,
+            lcore,
+            Box::new(cb),
+        )
+    }
+
+    fn reset(
+        ticks: u64,
+        type_: ffi::rte_timer_type::Type,
+        lcore: CoreId,
+        cb: TimerFn,
+    ) -> Result<Self> {
+        let mut timer = Timer {
+            raw: Box::new(ffi::rte_timer::default()),
+            cb: Box::new(cb),
+            _phantom: PhantomData,
+        };
+
+        unsafe {
+            ffi::rte_timer_init(timer.raw_mut());
+            ffi::rte_timer_reset(
+                timer.raw_mut(),
+                ticks,
+                type_,
+                lcore.raw() as raw::c_uint,
+                Some(timer_main),
+                &*timer.cb as *const TimerFn as *mut raw::c_void,
+            )
+            .into_result(|_| DpdkError::new())?;
+        }
+
+        Ok(timer)
+    }
+
+    #[inline]
+    fn raw_mut(&mut self) -> *mut ffi::rte_timer {
+        &mut *self.raw
+    }
+
+    /// Returns whether the timer is still scheduled to run.
+    pub fn is_pending(&mut self) -> bool {
+        unsafe { ffi::rte_timer_pending(self.raw_mut()) == 1 }
+    }
+
+    /// Stops the timer. The callback will not be called again.
+    ///
+    /// # Errors
+    ///
+    /// If the callback is running on another lcore, `TimerError::Busy` is
+    /// returned.
+    pub fn stop(&mut self) -> Result<()> {
+        unsafe {
+            ffi::rte_timer_stop(self.raw_mut())
+                .into_result(|_| TimerError::Busy)
+                .map(|_| ())
+        }
+    }
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("timer")
+            .field("expire", &self.raw.expire)
+            .field("period", &self.raw.period)
+            .finish()
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        // waits for the callback to finish if it's running on another
+        // lcore, before the callback is freed.
+        unsafe {
+            ffi::rte_timer_stop_sync(self.raw_mut());
+        }
+    }
+}
+
+/// The callback registered with all the timers.
+unsafe extern "C" fn timer_main(_timer: *mut ffi::rte_timer, arg: *mut raw::c_void) {
+    let cb = &*(arg as *const TimerFn);
+
+    // must not unwind across the FFI boundary.
+    if panic::catch_unwind(AssertUnwindSafe(cb)).is_err() {
+        error!("timer callback panicked.");
+    }
+}
+
+/// Processes the timers of the current lcore.
+#[derive(Debug)]
+pub struct TimerManager;
+
+impl TimerManager {
+    /// Runs the callbacks of the expired timers on the current lcore. This
+    /// should be called regularly in the lcore's main loop.
+    ///
+    /// # Errors
+    ///
+    /// If the current thread is not an EAL lcore, `DpdkError` is returned.
+    pub fn manage() -> Result<()> {
+        unsafe {
+            ffi::rte_timer_manage()
+                .into_result(DpdkError::from_errno)
+                .map(|_| ())
+        }
+    }
+
+    /// Returns the number of timer cycles in one second.
+    pub fn hz() -> u64 {
+        unsafe { ffi::_rte_get_timer_hz() }
+    }
+}
+
+/// Initializes the timer subsystem.
+pub(crate) fn timer_init() -> Result<()> {
+    unsafe {
+        ffi::rte_timer_subsystem_init()
+            .into_result(DpdkError::from_errno)
+            .map(|_| ())
+    }
+}
+
+/// Frees the timer subsystem.
+pub(crate) fn timer_close() {
+    unsafe {
+        ffi::rte_timer_subsystem_finalize();
+    }
+}
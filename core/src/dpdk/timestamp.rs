@@ -0,0 +1,158 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::DpdkError;
+use crate::ffi::{self, ToResult};
+use crate::packets::{Ethernet, Packet};
+use crate::{debug, info};
+use anyhow::Result;
+
+/// Reads the receive timestamps of packets.
+///
+/// There are two kinds of timestamps. IEEE 1588 timestamps are latched
+/// by the device for PTP packets once timesync is enabled on the port.
+/// The receive timestamp offload stamps every packet in device specific
+/// units, and is enabled through `PortConfig::enable_rx_offloads` with
+/// `RxOffloadFlags::TIMESTAMP`.
+///
+/// # Example
+///
+/// ```
+/// PacketTimestamp::enable_rx_timestamping(0)?;
+/// if let Some(ns) = PacketTimestamp::read_hw(&ethernet) {
+///     println!("received at {}ns", ns);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PacketTimestamp;
+
+impl PacketTimestamp {
+    /// Returns the IEEE 1588 receive timestamp of the packet in
+    /// nanoseconds. Returns `None` if the device did not latch a timestamp
+    /// for the packet.
+    pub fn read_hw(pkt: &Ethernet) -> Option<u64> {
+        let raw = pkt.mbuf().raw();
+        if raw.ol_flags & ffi::PKT_RX_IEEE1588_TMST as u64 == 0 {
+            return None;
+        }
+
+        let mut ts = ffi::timespec::default();
+        unsafe {
+            ffi::rte_eth_timesync_read_rx_timestamp(raw.port, &mut ts, raw.timesync as u32)
+                .into_result(DpdkError::from_errno)
+                .ok()?;
+        }
+        Some(ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64)
+    }
+
+    /// Returns the timestamp set by the receive timestamp offload, in
+    /// device specific units, usually cycles of the device clock. Returns
+    /// `None` if the device did not timestamp the packet.
+    pub fn read_cycles(pkt: &Ethernet) -> Option<u64> {
+        pkt.mbuf().meta().timestamp()
+    }
+
+    /// Enables IEEE 1588 timestamping of the received packets on a port.
+    ///
+    /// # Errors
+    ///
+    /// If the device does not support timesync, `DpdkError` is returned.
+    pub fn enable_rx_timestamping(port_id: u16) -> Result<()> {
+        unsafe {
+            ffi::rte_eth_timesync_enable(port_id).into_result(DpdkError::from_errno)?;
+        }
+
+        info!(port_id, "enabled rx timestamping.");
+        Ok(())
+    }
+
+    /// Disables IEEE 1588 timestamping on a port.
+    ///
+    /// # Errors
+    ///
+    /// If the device does not support timesync, `DpdkError` is returned.
+    pub fn disable_rx_timestamping(port_id: u16) -> Result<()> {
+        unsafe {
+            ffi::rte_eth_timesync_disable(port_id).into_result(DpdkError::from_errno)?;
+        }
+
+        debug!(port_id, "disabled rx timestamping.");
+        Ok(())
+    }
+}
+
+/// Measures time with the CPU time stamp counter.
+///
+/// # Example
+///
+/// ```
+/// let start = CycleTimer::now();
+/// pipeline.run_once();
+/// let ns = CycleTimer::elapsed_ns(start, CycleTimer::now());
+/// ```
+#[derive(Debug)]
+pub struct CycleTimer;
+
+impl CycleTimer {
+    /// Returns the current value of the time stamp counter.
+    #[inline]
+    pub fn now() -> u64 {
+        super::tsc_cycles()
+    }
+
+    /// Returns the number of time stamp counter cycles per second.
+    #[inline]
+    pub fn hz() -> u64 {
+        unsafe { ffi::rte_get_tsc_hz() }
+    }
+
+    /// Returns the nanoseconds elapsed between two counter values. Returns
+    /// `0` if `end` is before `start`.
+    #[inline]
+    pub fn elapsed_ns(start: u64, end: u64) -> u64 {
+        let cycles = end.saturating_sub(start) as u128;
+        (cycles * 1_000_000_000 / CycleTimer::hz() as u128) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpdk::Mbuf;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+    use std::thread;
+    use std::time::Duration;
+
+    #[capsule::test]
+    fn read_timestamps_not_set() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+
+        assert_eq!(None, PacketTimestamp::read_hw(&ethernet));
+        assert_eq!(None, PacketTimestamp::read_cycles(&ethernet));
+    }
+
+    #[capsule::test]
+    fn cycle_timer_elapsed() {
+        let start = CycleTimer::now();
+        thread::sleep(Duration::from_millis(10));
+        let end = CycleTimer::now();
+
+        assert!(CycleTimer::elapsed_ns(start, end) >= 10_000_000);
+        assert_eq!(0, CycleTimer::elapsed_ns(end, start));
+    }
+}
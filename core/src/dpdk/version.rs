@@ -0,0 +1,136 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use crate::ffi::{self, AsStr};
+use crate::warn;
+use std::fmt;
+
+/// A DPDK release version, in `YY.MM.patch` form.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Version {
+    /// The release year.
+    pub major: u32,
+    /// The release month.
+    pub minor: u32,
+    /// The maintenance release number.
+    pub patch: u32,
+}
+
+impl Version {
+    /// Parses a version string like `DPDK 19.11.6` or `19.11.0-rc1`. The
+    /// prefix and the suffix are ignored.
+    fn parse(s: &str) -> Option<Version> {
+        let digits = s
+            .trim_start_matches(|c: char| !c.is_ascii_digit())
+            .split(|c: char| c != '.' && !c.is_ascii_digit())
+            .next()?;
+
+        let mut parts = digits.split('.').map(|part| part.parse::<u32>());
+        let major = parts.next()?.ok()?;
+        let minor = parts.next()?.ok()?;
+        let patch = match parts.next() {
+            Some(part) => part.ok()?,
+            None => 0,
+        };
+
+        Some(Version {
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Reports the version of DPDK capsule is built against and running with.
+///
+/// The two can differ when `libdpdk` is linked dynamically.
+///
+/// # Example
+///
+/// ```
+/// if DpdkVersion::at_least(20, 5) {
+///     // uses an API added in 20.05.
+/// }
+/// ```
+#[derive(Debug)]
+pub struct DpdkVersion;
+
+impl DpdkVersion {
+    /// Returns the version of the `libdpdk` loaded at runtime.
+    pub fn runtime() -> Version {
+        let version = unsafe { ffi::_rte_version() };
+        Version::parse(version.as_str()).unwrap_or_else(|| {
+            warn!("cannot parse dpdk version '{}'.", version.as_str());
+            DpdkVersion::compile_time()
+        })
+    }
+
+    /// Returns the version of the DPDK headers the bindings are generated
+    /// from.
+    pub fn compile_time() -> Version {
+        Version {
+            major: ffi::RTE_VER_YEAR,
+            minor: ffi::RTE_VER_MONTH,
+            patch: ffi::RTE_VER_MINOR,
+        }
+    }
+
+    /// Returns whether the runtime version is at least `major.minor`.
+    pub fn at_least(major: u32, minor: u32) -> bool {
+        let version = DpdkVersion::runtime();
+        (version.major, version.minor) >= (major, minor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_version_strings() {
+        let expected = Version {
+            major: 19,
+            minor: 11,
+            patch: 6,
+        };
+        assert_eq!(Some(expected), Version::parse("DPDK 19.11.6"));
+        assert_eq!(Some(expected), Version::parse("19.11.6-rc1"));
+        assert_eq!(Some(19), Version::parse("DPDK 19.11").map(|v| v.major));
+        assert_eq!(None, Version::parse("DPDK"));
+        assert_eq!("19.11.6", expected.to_string());
+    }
+
+    #[capsule::test]
+    fn runtime_not_older_than_compile_time() {
+        let compile_time = DpdkVersion::compile_time();
+        let runtime = DpdkVersion::runtime();
+
+        assert!(compile_time.major >= 19);
+        assert!(runtime >= compile_time);
+        assert!(DpdkVersion::at_least(
+            compile_time.major,
+            compile_time.minor
+        ));
+        assert!(!DpdkVersion::at_least(runtime.major + 1, 0));
+    }
+}
@@ -0,0 +1,299 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::{debug, ensure};
+use anyhow::{Context, Result};
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::raw;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use thiserror::Error;
+
+/// The VFIO API version the kernel is expected to report.
+const VFIO_API_VERSION: raw::c_int = 0;
+
+// the VFIO ioctl request codes, `_IO(VFIO_TYPE, VFIO_BASE + n)`, from
+// `linux/vfio.h`.
+const VFIO_TYPE: raw::c_ulong = b';' as raw::c_ulong;
+const VFIO_BASE: raw::c_ulong = 100;
+const VFIO_GET_API_VERSION: raw::c_ulong = VFIO_TYPE << 8 | VFIO_BASE;
+const VFIO_CHECK_EXTENSION: raw::c_ulong = VFIO_TYPE << 8 | (VFIO_BASE + 1);
+const VFIO_SET_IOMMU: raw::c_ulong = VFIO_TYPE << 8 | (VFIO_BASE + 2);
+const VFIO_GROUP_GET_STATUS: raw::c_ulong = VFIO_TYPE << 8 | (VFIO_BASE + 3);
+const VFIO_GROUP_SET_CONTAINER: raw::c_ulong = VFIO_TYPE << 8 | (VFIO_BASE + 4);
+const VFIO_GROUP_GET_DEVICE_FD: raw::c_ulong = VFIO_TYPE << 8 | (VFIO_BASE + 6);
+
+/// The group is viable, all the devices in the group are bound to VFIO
+/// drivers or not bound at all.
+const VFIO_GROUP_FLAGS_VIABLE: u32 = 1 << 0;
+
+/// The argument of the `VFIO_GROUP_GET_STATUS` ioctl.
+#[repr(C)]
+#[derive(Default)]
+struct VfioGroupStatus {
+    argsz: u32,
+    flags: u32,
+}
+
+/// Error indicating failed VFIO operations.
+#[derive(Debug, Error)]
+pub(crate) enum VfioError {
+    /// The kernel reports an unknown VFIO API version.
+    #[error("Unsupported VFIO API version {0}.")]
+    UnsupportedApi(raw::c_int),
+
+    /// The IOMMU type is not supported by the kernel.
+    #[error("IOMMU type {0:?} is not supported.")]
+    UnsupportedIommu(VfioIommuType),
+
+    /// Not all the devices in the group are bound to VFIO drivers.
+    #[error("VFIO group {0} is not viable.")]
+    NotViable(u32),
+}
+
+/// IOMMU models a container can be set to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum VfioIommuType {
+    /// The x86 IOMMU model.
+    Type1 = 1,
+    /// The PowerPC IOMMU model.
+    SpaprTceV2 = 7,
+    /// No IOMMU, devices get no DMA isolation.
+    NoIommu = 8,
+}
+
+/// Issues an ioctl that takes a scalar or pointer argument.
+fn ioctl(fd: RawFd, request: raw::c_ulong, arg: raw::c_ulong) -> io::Result<raw::c_int> {
+    // the request is declared as different integer types by different
+    // libcs, hence the inferred cast.
+    match unsafe { libc::ioctl(fd, request as _, arg) } {
+        -1 => Err(io::Error::last_os_error()),
+        res => Ok(res),
+    }
+}
+
+/// Opens a VFIO character device for read and write.
+fn open(path: &str) -> Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("failed to open '{}'.", path))
+}
+
+/// A VFIO container, which holds the IOMMU context shared by the groups
+/// added to it.
+///
+/// # Example
+///
+/// ```
+/// let container = VfioContainer::open()?;
+/// let group = VfioGroup::open(42)?;
+/// container.add_group(&group)?;
+/// container.enable_iommu(VfioIommuType::Type1)?;
+/// let device = group.get_device("0000:00:1f.6")?;
+/// ```
+pub struct VfioContainer {
+    file: File,
+}
+
+impl VfioContainer {
+    /// Opens a new container from `/dev/vfio/vfio`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device cannot be opened, or if the kernel
+    /// VFIO API version is not supported.
+    pub fn open() -> Result<Self> {
+        let file = open("/dev/vfio/vfio")?;
+        let version = ioctl(file.as_raw_fd(), VFIO_GET_API_VERSION, 0)?;
+        ensure!(
+            version == VFIO_API_VERSION,
+            VfioError::UnsupportedApi(version)
+        );
+
+        Ok(VfioContainer { file })
+    }
+
+    /// Adds a group to the container.
+    ///
+    /// # Errors
+    ///
+    /// Returns `VfioError::NotViable` if not all the devices in the group
+    /// are bound to VFIO drivers, or an IO error if the ioctl fails.
+    pub fn add_group(&self, group: &VfioGroup) -> Result<()> {
+        ensure!(group.is_viable()?, VfioError::NotViable(group.group_id));
+
+        let fd = self.file.as_raw_fd();
+        ioctl(
+            group.file.as_raw_fd(),
+            VFIO_GROUP_SET_CONTAINER,
+            &fd as *const RawFd as raw::c_ulong,
+        )?;
+
+        debug!(group_id = group.group_id, "added group to VFIO container.");
+        Ok(())
+    }
+
+    /// Sets the IOMMU model of the container. At least one group must be
+    /// added to the container first.
+    ///
+    /// # Errors
+    ///
+    /// Returns `VfioError::UnsupportedIommu` if the kernel does not support
+    /// the IOMMU model, or an IO error if the ioctl fails.
+    pub fn enable_iommu(&self, type_: VfioIommuType) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+        let supported = ioctl(fd, VFIO_CHECK_EXTENSION, type_ as raw::c_ulong)?;
+        ensure!(supported > 0, VfioError::UnsupportedIommu(type_));
+
+        ioctl(fd, VFIO_SET_IOMMU, type_ as raw::c_ulong)?;
+
+        debug!(?type_, "enabled VFIO IOMMU.");
+        Ok(())
+    }
+}
+
+impl fmt::Debug for VfioContainer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VfioContainer")
+            .field("fd", &self.file.as_raw_fd())
+            .finish()
+    }
+}
+
+/// A VFIO group, the smallest set of devices the IOMMU can isolate.
+pub struct VfioGroup {
+    group_id: u32,
+    file: File,
+}
+
+impl VfioGroup {
+    /// Opens the group from `/dev/vfio/{group_id}`.
+    ///
+    /// The group id of a device is the name of the link at
+    /// `/sys/bus/pci/devices/{pci_addr}/iommu_group`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device cannot be opened.
+    pub fn open(group_id: u32) -> Result<Self> {
+        let file = open(&format!("/dev/vfio/{}", group_id))?;
+        Ok(VfioGroup { group_id, file })
+    }
+
+    /// Returns the group id.
+    pub fn group_id(&self) -> u32 {
+        self.group_id
+    }
+
+    /// Returns whether all the devices in the group are bound to VFIO
+    /// drivers, or not bound at all.
+    ///
+    /// # Errors
+    ///
+    /// Returns an IO error if the ioctl fails.
+    pub fn is_viable(&self) -> Result<bool> {
+        let mut status = VfioGroupStatus {
+            argsz: std::mem::size_of::<VfioGroupStatus>() as u32,
+            ..Default::default()
+        };
+        ioctl(
+            self.file.as_raw_fd(),
+            VFIO_GROUP_GET_STATUS,
+            &mut status as *mut VfioGroupStatus as raw::c_ulong,
+        )?;
+        Ok(status.flags & VFIO_GROUP_FLAGS_VIABLE != 0)
+    }
+
+    /// Returns the device at the PCI address. The group must be added to a
+    /// container with the IOMMU enabled first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an IO error if the device is not in the group.
+    pub fn get_device(&self, pci_addr: &str) -> Result<VfioDevice> {
+        let name = CString::new(pci_addr)?;
+        let fd = ioctl(
+            self.file.as_raw_fd(),
+            VFIO_GROUP_GET_DEVICE_FD,
+            name.as_ptr() as raw::c_ulong,
+        )
+        .with_context(|| format!("failed to get VFIO device '{}'.", pci_addr))?;
+
+        Ok(VfioDevice {
+            pci_addr: pci_addr.to_owned(),
+            file: unsafe { File::from_raw_fd(fd) },
+        })
+    }
+}
+
+impl fmt::Debug for VfioGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VfioGroup")
+            .field("group_id", &self.group_id)
+            .field("fd", &self.file.as_raw_fd())
+            .finish()
+    }
+}
+
+/// A device in a VFIO group. The file descriptor is closed when the
+/// device goes out of scope.
+pub struct VfioDevice {
+    pci_addr: String,
+    file: File,
+}
+
+impl VfioDevice {
+    /// Returns the PCI address of the device.
+    pub fn pci_addr(&self) -> &str {
+        &self.pci_addr
+    }
+}
+
+impl AsRawFd for VfioDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl fmt::Debug for VfioDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VfioDevice")
+            .field("pci_addr", &self.pci_addr)
+            .field("fd", &self.file.as_raw_fd())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vfio_ioctl_codes() {
+        // the values of the macros in `linux/vfio.h`.
+        assert_eq!(0x3b64, VFIO_GET_API_VERSION);
+        assert_eq!(0x3b66, VFIO_SET_IOMMU);
+        assert_eq!(0x3b68, VFIO_GROUP_SET_CONTAINER);
+        assert_eq!(0x3b6a, VFIO_GROUP_GET_DEVICE_FD);
+    }
+}
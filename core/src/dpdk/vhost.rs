@@ -0,0 +1,390 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::{DpdkError, Mbuf, MempoolError, SocketId, MEMPOOL};
+use crate::ffi::{self, AsStr, ToCString, ToResult};
+use crate::packets::{Ethernet, Packet};
+use crate::{debug, error, info, warn};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::fmt;
+use std::os::raw;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::NonNull;
+use std::sync::RwLock;
+
+/// The maximum number of packets received from a virtqueue in one burst.
+const VHOST_RX_BURST: usize = 32;
+
+/// The maximum length of a vhost-user socket path.
+const VHOST_PATH_MAX: usize = 4096;
+
+type DeviceFn = Box<dyn Fn(i32) + Send + Sync + 'static>;
+type VringFn = Box<dyn Fn(i32, u16, bool) + Send + Sync + 'static>;
+
+/// The closures registered for a vhost-user socket.
+#[derive(Default)]
+struct VhostCallbacks {
+    max_queues: u32,
+    new_device: Option<DeviceFn>,
+    destroy_device: Option<DeviceFn>,
+    vring_state_changed: Option<VringFn>,
+}
+
+/// The device events only carry the vhost device id, so the closures are
+/// kept in a global registry keyed by the socket path of the device.
+static BACKENDS: Lazy<RwLock<HashMap<String, VhostCallbacks>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Invokes `f` with the callbacks registered for the socket of the device.
+fn with_callbacks<F: FnOnce(&VhostCallbacks)>(vid: raw::c_int, f: F) {
+    let path = match VhostUserSession::ifname(vid) {
+        Ok(path) => path,
+        Err(err) => {
+            warn!(message = "unknown vhost device.", vid, ?err);
+            return;
+        }
+    };
+
+    if let Some(callbacks) = BACKENDS.read().unwrap().get(&path) {
+        // must not unwind across the FFI boundary.
+        if panic::catch_unwind(AssertUnwindSafe(|| f(callbacks))).is_err() {
+            error!("vhost device callback panicked.");
+        }
+    }
+}
+
+unsafe extern "C" fn new_device(vid: raw::c_int) -> raw::c_int {
+    info!(vid, "new vhost device.");
+    with_callbacks(vid, |callbacks| {
+        if let Some(cb) = &callbacks.new_device {
+            cb(vid);
+        }
+    });
+    0
+}
+
+unsafe extern "C" fn destroy_device(vid: raw::c_int) {
+    info!(vid, "destroyed vhost device.");
+    with_callbacks(vid, |callbacks| {
+        if let Some(cb) = &callbacks.destroy_device {
+            cb(vid);
+        }
+    });
+}
+
+unsafe extern "C" fn vring_state_changed(
+    vid: raw::c_int,
+    queue_id: u16,
+    enable: raw::c_int,
+) -> raw::c_int {
+    debug!(vid, queue_id, enable, "vhost vring state changed.");
+    with_callbacks(vid, |callbacks| {
+        // each queue pair has two vrings.
+        if queue_id as u32 >= callbacks.max_queues * 2 {
+            warn!(vid, queue_id, "ignored vring beyond the max queues.");
+        } else if let Some(cb) = &callbacks.vring_state_changed {
+            cb(vid, queue_id, enable != 0);
+        }
+    });
+    0
+}
+
+/// A vhost-user backend, the DPDK side of the virtio protocol that VMs
+/// and containers use to exchange packets with the application.
+///
+/// The backend listens on a Unix socket for the guests to connect. Each
+/// connected guest is a vhost device with an id, passed to the event
+/// closures. The packets are exchanged with the device through
+/// `VhostUserSession`. The backend is unregistered when dropped.
+///
+/// # Example
+///
+/// ```
+/// let mut backend = VhostUserBackend::new("/tmp/vhost0.sock", 1)?;
+/// backend.on_new_device(|vid| println!("vm {} connected.", vid));
+/// backend.start()?;
+/// ```
+pub struct VhostUserBackend {
+    path: String,
+    // the driver keeps a pointer to the ops until it's unregistered.
+    _ops: Box<ffi::vhost_device_ops>,
+}
+
+impl VhostUserBackend {
+    /// Registers a new vhost-user backend listening on `socket_path`,
+    /// with up to `max_queues` queue pairs per device. The events of the
+    /// vrings beyond the max queues are ignored.
+    ///
+    /// # Errors
+    ///
+    /// If the path is already registered, `DpdkError` is returned.
+    pub fn new(socket_path: &str, max_queues: u32) -> Result<VhostUserBackend> {
+        let path = socket_path.into_cstring();
+        unsafe {
+            ffi::rte_vhost_driver_register(path.as_ptr(), 0).into_result(DpdkError::from_errno)?;
+        }
+
+        let ops = Box::new(ffi::vhost_device_ops {
+            new_device: Some(new_device),
+            destroy_device: Some(destroy_device),
+            vring_state_changed: Some(vring_state_changed),
+            ..Default::default()
+        });
+
+        let res = unsafe {
+            ffi::rte_vhost_driver_callback_register(path.as_ptr(), &*ops)
+                .into_result(DpdkError::from_errno)
+        };
+
+        if let Err(err) = res {
+            unsafe {
+                ffi::rte_vhost_driver_unregister(path.as_ptr());
+            }
+            return Err(err);
+        }
+
+        BACKENDS.write().unwrap().insert(
+            socket_path.to_owned(),
+            VhostCallbacks {
+                max_queues,
+                ..Default::default()
+            },
+        );
+
+        debug!("registered vhost-user backend {}.", socket_path);
+        Ok(VhostUserBackend {
+            path: socket_path.to_owned(),
+            _ops: ops,
+        })
+    }
+
+    /// Returns the socket path of the backend.
+    pub fn socket_path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// Updates the callbacks registered for the backend.
+    fn update<F: FnOnce(&mut VhostCallbacks)>(&mut self, f: F) -> &mut Self {
+        if let Some(callbacks) = BACKENDS.write().unwrap().get_mut(&self.path) {
+            f(callbacks);
+        }
+        self
+    }
+
+    /// Sets the closure invoked when a guest device is ready for packets.
+    pub fn on_new_device<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(i32) + Send + Sync + 'static,
+    {
+        self.update(|callbacks| callbacks.new_device = Some(Box::new(f)))
+    }
+
+    /// Sets the closure invoked when a guest device is removed. The device
+    /// must not be used once the closure returns.
+    pub fn on_destroy_device<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(i32) + Send + Sync + 'static,
+    {
+        self.update(|callbacks| callbacks.destroy_device = Some(Box::new(f)))
+    }
+
+    /// Sets the closure invoked when a vring of a guest device is enabled
+    /// or disabled.
+    pub fn on_vring_state_changed<F>(&mut self, f: F) -> &mut Self
+    where
+        F: Fn(i32, u16, bool) + Send + Sync + 'static,
+    {
+        self.update(|callbacks| callbacks.vring_state_changed = Some(Box::new(f)))
+    }
+
+    /// Starts listening for the guests to connect.
+    ///
+    /// # Errors
+    ///
+    /// If the socket cannot be created, `DpdkError` is returned.
+    pub fn start(&self) -> Result<()> {
+        unsafe {
+            ffi::rte_vhost_driver_start(self.path.clone().into_cstring().as_ptr())
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        info!("started vhost-user backend {}.", self.path);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for VhostUserBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VhostUserBackend")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl Drop for VhostUserBackend {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rte_vhost_driver_unregister(self.path.clone().into_cstring().as_ptr());
+        }
+
+        BACKENDS.write().unwrap().remove(&self.path);
+        debug!("unregistered vhost-user backend {}.", self.path);
+    }
+}
+
+/// Exchanges packets with a connected vhost device.
+///
+/// `queue_id` is the index of the queue pair. Each queue pair has two
+/// virtqueues. The packets the guest transmits are received from one, and
+/// the packets for the guest are transmitted to the other.
+///
+/// # Example
+///
+/// ```
+/// let mut packets = vec![];
+/// VhostUserSession::virtqueue_rx(vid, 0, &mut packets);
+/// VhostUserSession::virtqueue_tx(other_vid, 0, packets);
+/// ```
+#[derive(Debug)]
+pub struct VhostUserSession;
+
+impl VhostUserSession {
+    /// Returns the socket path of the vhost device.
+    ///
+    /// # Errors
+    ///
+    /// If the device does not exist, `DpdkError` is returned.
+    pub fn ifname(vid: i32) -> Result<String> {
+        let mut buf = vec![0 as raw::c_char; VHOST_PATH_MAX];
+        unsafe {
+            ffi::rte_vhost_get_ifname(vid, buf.as_mut_ptr(), buf.len() as ffi::size_t)
+                .into_result(DpdkError::from_errno)?;
+        }
+        Ok(buf[..].as_str().to_owned())
+    }
+
+    /// Receives the packets the guest transmitted on the queue pair and
+    /// appends them to `burst`. Returns the number of packets received.
+    /// The packets are allocated from the `Mempool` assigned to the current
+    /// executing thread. Packets that are not valid Ethernet frames are
+    /// dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MempoolError::NotFound` if invoked from a thread not
+    /// managed by the `Runtime`.
+    pub fn virtqueue_rx(vid: i32, queue_id: u32, burst: &mut Vec<Ethernet>) -> Result<usize> {
+        let mempool = NonNull::new(MEMPOOL.with(|tls| tls.get()))
+            .ok_or_else(|| MempoolError::NotFound(SocketId::current()))?;
+
+        let mut ptrs = Vec::with_capacity(VHOST_RX_BURST);
+        let len = unsafe {
+            ffi::rte_vhost_dequeue_burst(
+                vid,
+                (queue_id * 2 + 1) as u16,
+                mempool.as_ptr(),
+                ptrs.as_mut_ptr(),
+                VHOST_RX_BURST as u16,
+            )
+        };
+
+        let before = burst.len();
+        unsafe {
+            ptrs.set_len(len as usize);
+            burst.extend(
+                ptrs.into_iter()
+                    .filter_map(|ptr| Mbuf::from_ptr(ptr).parse::<Ethernet>().ok()),
+            );
+        }
+
+        Ok(burst.len() - before)
+    }
+
+    /// Transmits the packets to the guest on the queue pair. Returns the
+    /// number of packets transmitted. The packets are copied into the
+    /// guest memory, so all the packets are freed afterwards, including the
+    /// ones the guest had no room for.
+    pub fn virtqueue_tx(vid: i32, queue_id: u32, pkts: Vec<Ethernet>) -> usize {
+        let mut mbufs = pkts.into_iter().map(Packet::reset).collect::<Vec<_>>();
+        let mut ptrs = mbufs
+            .iter_mut()
+            .map(|mbuf| mbuf.raw_mut() as *mut ffi::rte_mbuf)
+            .collect::<Vec<_>>();
+
+        unsafe {
+            ffi::rte_vhost_enqueue_burst(
+                vid,
+                (queue_id * 2) as u16,
+                ptrs.as_mut_ptr(),
+                ptrs.len() as u16,
+            ) as usize
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[capsule::test]
+    fn register_and_unregister_backend() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+
+        let mut backend = VhostUserBackend::new("/tmp/capsule_vhost_test0.sock", 1).unwrap();
+        backend.on_new_device(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        assert!(VhostUserBackend::new("/tmp/capsule_vhost_test0.sock", 1).is_err());
+        assert!(BACKENDS
+            .read()
+            .unwrap()
+            .get("/tmp/capsule_vhost_test0.sock")
+            .unwrap()
+            .new_device
+            .is_some());
+
+        drop(backend);
+        assert!(BACKENDS
+            .read()
+            .unwrap()
+            .get("/tmp/capsule_vhost_test0.sock")
+            .is_none());
+        assert_eq!(0, count.load(Ordering::Relaxed));
+    }
+
+    #[capsule::test]
+    fn exchange_with_unknown_device() {
+        assert!(VhostUserSession::ifname(1000).is_err());
+
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        assert_eq!(0, VhostUserSession::virtqueue_tx(1000, 0, vec![ethernet]));
+
+        let mut burst = vec![];
+        assert_eq!(
+            0,
+            VhostUserSession::virtqueue_rx(1000, 0, &mut burst).unwrap()
+        );
+        assert!(burst.is_empty());
+    }
+}
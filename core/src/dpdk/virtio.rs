@@ -0,0 +1,300 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::{DeviceInfo, DpdkError, RxOffloadFlags, TxOffloadFlags};
+use crate::ffi::{self, ToResult};
+use crate::net::MacAddr;
+use crate::{ensure, info};
+use anyhow::Result;
+use bitflags::bitflags;
+use thiserror::Error;
+
+/// The names of the virtio PMDs, for virtio PCI devices and for
+/// virtio-user ports.
+const VIRTIO_DRIVERS: [&str; 2] = ["net_virtio", "net_virtio_user"];
+
+bitflags! {
+    /// The virtio-net feature bits, as defined in the virtio specification.
+    pub struct VirtioFeatures: u64 {
+        /// The device handles packets with partial checksum.
+        const CSUM = 1 << 0;
+        /// The driver handles packets with partial checksum.
+        const GUEST_CSUM = 1 << 1;
+        /// The driver can receive TSOv4.
+        const GUEST_TSO4 = 1 << 7;
+        /// The driver can receive TSOv6.
+        const GUEST_TSO6 = 1 << 8;
+        /// The device can receive TSOv4.
+        const HOST_TSO4 = 1 << 11;
+        /// The device can receive TSOv6.
+        const HOST_TSO6 = 1 << 12;
+        /// The device can receive UFO.
+        const HOST_UFO = 1 << 14;
+        /// The driver can merge receive buffers.
+        const MRG_RXBUF = 1 << 15;
+    }
+}
+
+/// Error indicating the virtio device cannot be configured.
+#[derive(Debug, Error)]
+pub(crate) enum VirtioError {
+    /// The port is not a virtio device.
+    #[error("Port {0} is not a virtio device.")]
+    NotVirtio(u16),
+
+    /// The device doesn't support the offload.
+    #[error("Virtio device doesn't support {0}.")]
+    Unsupported(&'static str),
+
+    /// The queue size is out of the device's descriptor limits.
+    #[error("Queue size {0} is not supported, the device adjusted it to {1}.")]
+    InvalidQueueSize(u16, u16),
+}
+
+/// The virtio specific configuration of a port.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct VirtioConfig {
+    /// The number of descriptors of each receive and transmit queue.
+    pub queue_size: u16,
+    /// Offloads TCP and UDP checksums in both directions.
+    pub csum_offload: bool,
+    /// Offloads the segmentation of transmitted TCP over IPv4 packets.
+    pub tso4: bool,
+    /// Offloads the fragmentation of transmitted UDP packets.
+    pub ufo: bool,
+    /// Receives packets larger than a buffer across merged buffers.
+    pub mrg_rxbuf: bool,
+}
+
+impl VirtioConfig {
+    /// Returns the receive offloads of the configuration.
+    pub fn rx_offloads(&self) -> RxOffloadFlags {
+        let mut offloads = RxOffloadFlags::empty();
+        if self.csum_offload {
+            offloads |= RxOffloadFlags::TCP_CKSUM | RxOffloadFlags::UDP_CKSUM;
+        }
+        if self.mrg_rxbuf {
+            offloads |= RxOffloadFlags::SCATTER | RxOffloadFlags::JUMBO_FRAME;
+        }
+        offloads
+    }
+
+    /// Returns the transmit offloads of the configuration.
+    pub fn tx_offloads(&self) -> TxOffloadFlags {
+        let mut offloads = TxOffloadFlags::empty();
+        if self.csum_offload {
+            offloads |= TxOffloadFlags::TCP_CKSUM | TxOffloadFlags::UDP_CKSUM;
+        }
+        if self.tso4 {
+            offloads |= TxOffloadFlags::TCP_TSO;
+        }
+        if self.ufo {
+            offloads |= TxOffloadFlags::UDP_TSO;
+        }
+        if self.mrg_rxbuf {
+            offloads |= TxOffloadFlags::MULTI_SEGS;
+        }
+        offloads
+    }
+}
+
+/// Manages the virtio ports, the paravirtual NICs of QEMU/KVM VMs and of
+/// containers attached to a vhost-user backend.
+///
+/// The virtio PMD negotiates the features with the host when the port is
+/// probed, and exposes them as the offload capabilities of the port.
+/// `configure` checks the requested offloads against them.
+///
+/// `configure` is for ports not managed by the runtime. For ports in the
+/// runtime configuration, enable `VirtioConfig::rx_offloads` and
+/// `VirtioConfig::tx_offloads` in `PortConfig` instead, and set `rxd` and
+/// `txd` to the queue size.
+///
+/// # Example
+///
+/// ```
+/// let config = VirtioConfig {
+///     queue_size: 256,
+///     csum_offload: true,
+///     ..Default::default()
+/// };
+/// VirtioDevice::configure(port_id, config)?;
+/// VirtioDevice::set_mac(port_id, "02:00:00:00:00:01".parse()?)?;
+/// ```
+#[derive(Debug)]
+pub struct VirtioDevice;
+
+impl VirtioDevice {
+    /// Returns the device info if the port is a virtio device.
+    fn query(port_id: u16) -> Result<DeviceInfo> {
+        let info = DeviceInfo::query(port_id)?;
+        ensure!(
+            VIRTIO_DRIVERS.contains(&info.driver_name()),
+            VirtioError::NotVirtio(port_id)
+        );
+        Ok(info)
+    }
+
+    /// Returns whether the port is a virtio device.
+    pub fn is_virtio(port_id: u16) -> bool {
+        VirtioDevice::query(port_id).is_ok()
+    }
+
+    /// Configures the port with one receive and one transmit queue and the
+    /// offloads of the configuration.
+    ///
+    /// # Errors
+    ///
+    /// Returns `VirtioError::NotVirtio` if the port is not a virtio device,
+    /// `VirtioError::Unsupported` if the device didn't negotiate a feature
+    /// the configuration requires, or `VirtioError::InvalidQueueSize` if
+    /// the queue size is out of the device's limits. If the port is
+    /// started, `DpdkError` is returned.
+    pub fn configure(port_id: u16, config: VirtioConfig) -> Result<()> {
+        let info = VirtioDevice::query(port_id)?;
+        let rx_capa = info.rx_offload_capa();
+        let tx_capa = info.tx_offload_capa();
+
+        let required = [
+            (
+                config.csum_offload,
+                tx_capa.contains(TxOffloadFlags::TCP_CKSUM),
+                "checksum offload",
+            ),
+            (
+                config.tso4,
+                tx_capa.contains(TxOffloadFlags::TCP_TSO),
+                "TSOv4",
+            ),
+            (config.ufo, tx_capa.contains(TxOffloadFlags::UDP_TSO), "UFO"),
+            (
+                config.mrg_rxbuf,
+                rx_capa.contains(RxOffloadFlags::SCATTER),
+                "mergeable receive buffers",
+            ),
+        ];
+        for &(requested, supported, name) in required.iter() {
+            ensure!(!requested || supported, VirtioError::Unsupported(name));
+        }
+
+        let mut conf = ffi::rte_eth_conf::default();
+        conf.rxmode.offloads = (config.rx_offloads() & rx_capa).bits();
+        conf.txmode.offloads = (config.tx_offloads() & tx_capa).bits();
+
+        unsafe {
+            ffi::rte_eth_dev_configure(port_id, 1, 1, &conf).into_result(DpdkError::from_errno)?;
+        }
+
+        let mut rxd = config.queue_size;
+        let mut txd = config.queue_size;
+        unsafe {
+            ffi::rte_eth_dev_adjust_nb_rx_tx_desc(port_id, &mut rxd, &mut txd)
+                .into_result(DpdkError::from_errno)?;
+        }
+        ensure!(
+            rxd == config.queue_size && txd == config.queue_size,
+            VirtioError::InvalidQueueSize(config.queue_size, rxd.min(txd))
+        );
+
+        info!(port_id, ?config, "configured virtio device.");
+        Ok(())
+    }
+
+    /// Returns the negotiated features that are visible through the
+    /// offload capabilities of the port. Features without a matching
+    /// offload, like mergeable receive buffers, are not reported.
+    ///
+    /// # Errors
+    ///
+    /// Returns `VirtioError::NotVirtio` if the port is not a virtio device.
+    pub fn get_features(port_id: u16) -> Result<VirtioFeatures> {
+        let info = VirtioDevice::query(port_id)?;
+        let rx_capa = info.rx_offload_capa();
+        let tx_capa = info.tx_offload_capa();
+
+        let mut features = VirtioFeatures::empty();
+        features.set(
+            VirtioFeatures::GUEST_CSUM,
+            rx_capa.contains(RxOffloadFlags::TCP_CKSUM),
+        );
+        features.set(
+            VirtioFeatures::GUEST_TSO4 | VirtioFeatures::GUEST_TSO6,
+            rx_capa.contains(RxOffloadFlags::TCP_LRO),
+        );
+        features.set(
+            VirtioFeatures::CSUM,
+            tx_capa.contains(TxOffloadFlags::TCP_CKSUM),
+        );
+        features.set(
+            VirtioFeatures::HOST_TSO4 | VirtioFeatures::HOST_TSO6,
+            tx_capa.contains(TxOffloadFlags::TCP_TSO),
+        );
+        features.set(
+            VirtioFeatures::HOST_UFO,
+            tx_capa.contains(TxOffloadFlags::UDP_TSO),
+        );
+        Ok(features)
+    }
+
+    /// Sets the MAC address of the port.
+    ///
+    /// # Errors
+    ///
+    /// Returns `VirtioError::NotVirtio` if the port is not a virtio device.
+    /// If the device doesn't allow changing the address, `DpdkError` is
+    /// returned.
+    pub fn set_mac(port_id: u16, mac: MacAddr) -> Result<()> {
+        VirtioDevice::query(port_id)?;
+
+        let mut addr: ffi::rte_ether_addr = mac.into();
+        unsafe {
+            ffi::rte_eth_dev_default_mac_addr_set(port_id, &mut addr)
+                .into_result(DpdkError::from_errno)?;
+        }
+
+        info!(port_id, %mac, "set virtio MAC address.");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virtio_config_offloads() {
+        let config = VirtioConfig {
+            queue_size: 256,
+            csum_offload: true,
+            tso4: true,
+            ..Default::default()
+        };
+
+        assert!(config.rx_offloads().contains(RxOffloadFlags::TCP_CKSUM));
+        assert!(!config.rx_offloads().contains(RxOffloadFlags::SCATTER));
+        assert!(config
+            .tx_offloads()
+            .contains(TxOffloadFlags::TCP_CKSUM | TxOffloadFlags::TCP_TSO));
+        assert!(!config.tx_offloads().contains(TxOffloadFlags::UDP_TSO));
+    }
+
+    #[capsule::test]
+    fn not_virtio_device() {
+        assert!(!VirtioDevice::is_virtio(u16::MAX));
+        assert!(VirtioDevice::get_features(u16::MAX).is_err());
+    }
+}
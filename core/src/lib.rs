@@ -85,6 +85,12 @@
 //! - `default`: Enables metrics by default.
 //! - `metrics`: Enables automatic [`metrics`] collection.
 //! - `pcap-dump`: Enables capturing port traffic to `pcap` files.
+//! - `telemetry`: Enables the [`telemetry`] socket and its built-in
+//!   commands.
+//! - `compress-sw`: Enables the software deflate fallback for
+//!   `CompressSession`.
+//! - `hash-multi-writer`: Makes `HashTable` safe for concurrent reads and
+//!   writes from multiple cores.
 //! - `testils`: Enables utilities for unit testing and benchmarking.
 //! - `full`: Enables all features.
 //!
@@ -110,6 +116,7 @@
 //! [README]: https://github.com/capsule-rs/capsule/blob/master/README.md
 //! [sandbox repo]: https://github.com/capsule-rs/sandbox
 //! [`metrics`]: crate::metrics
+//! [`telemetry`]: crate::telemetry
 //! [kni]: https://github.com/capsule-rs/capsule/tree/master/examples/kni
 //! [nat64]: https://github.com/capsule-rs/capsule/tree/master/examples/nat64
 //! [ping4d]: https://github.com/capsule-rs/capsule/tree/master/examples/ping4d
@@ -123,7 +130,7 @@ extern crate self as capsule;
 
 pub mod batch;
 pub mod config;
-mod dpdk;
+pub mod dpdk;
 mod ffi;
 mod macros;
 #[cfg(feature = "metrics")]
@@ -135,6 +142,9 @@ pub mod packets;
 #[cfg_attr(docsrs, doc(cfg(feature = "pcap-dump")))]
 mod pcap;
 mod runtime;
+#[cfg(feature = "telemetry")]
+#[cfg_attr(docsrs, doc(cfg(feature = "telemetry")))]
+pub mod telemetry;
 #[cfg(any(test, feature = "testils"))]
 #[cfg_attr(docsrs, doc(cfg(feature = "testils")))]
 pub mod testils;
@@ -0,0 +1,296 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::dpdk::{self, PortQueue};
+use crate::ffi;
+use crate::net::MacAddr;
+use crate::packets::arp::{Arp4, OperationCode, OperationCodes};
+use crate::packets::{Ethernet, Packet};
+use crate::Mbuf;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+/// The maximum number of packets held while waiting on resolution. The
+/// oldest packet is dropped when the hold buffer is full.
+const MAX_PENDING: usize = 64;
+
+/// The interval before an unanswered ARP request is sent again.
+const REQUEST_RETRY: Duration = Duration::from_secs(1);
+
+/// A cache of IPv4 neighbors resolved with the Address Resolution
+/// Protocol, as defined in RFC 826.
+///
+/// Each entry expires at a TSC cycle count. Expired entries are not
+/// returned by lookups, and are removed with `evict_expired`, for example
+/// from a periodic `Timer`. Packets to neighbors not resolved yet can be
+/// held in the cache, and are released with the destination set once the
+/// reply is received.
+///
+/// # Example
+///
+/// ```
+/// let mut arp = ArpCache::new(local_ip, Duration::from_secs(300));
+/// arp.send_gratuitous(local_ip, q.mac_addr(), &q)?;
+///
+/// match arp.resolve(next_hop, &q) {
+///     Some(mac) => ethernet.set_dst(mac),
+///     None => arp.hold(next_hop, ethernet),
+/// }
+///
+/// // when an ARP reply is received.
+/// let released = arp.process_reply(&arp4);
+/// ```
+#[derive(Debug)]
+pub struct ArpCache {
+    local_ip: Ipv4Addr,
+    ttl_cycles: u64,
+    retry_cycles: u64,
+    entries: HashMap<Ipv4Addr, (MacAddr, u64)>,
+    requested: HashMap<Ipv4Addr, u64>,
+    pending_queue: Vec<(Ipv4Addr, Ethernet)>,
+}
+
+impl ArpCache {
+    /// Creates a new empty cache. `local_ip` is the sender address of the
+    /// ARP requests, and neighbors learned from replies are kept for `ttl`.
+    pub fn new(local_ip: Ipv4Addr, ttl: Duration) -> Self {
+        let hz = unsafe { ffi::rte_get_tsc_hz() } as f64;
+        ArpCache {
+            local_ip,
+            ttl_cycles: (ttl.as_secs_f64() * hz) as u64,
+            retry_cycles: (REQUEST_RETRY.as_secs_f64() * hz) as u64,
+            entries: HashMap::new(),
+            requested: HashMap::new(),
+            pending_queue: vec![],
+        }
+    }
+
+    /// Returns the number of entries, including the expired ones not
+    /// evicted yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the cache has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of packets held waiting on resolution.
+    pub fn pending_len(&self) -> usize {
+        self.pending_queue.len()
+    }
+
+    /// Adds or refreshes a neighbor that expires `ttl_cycles` from now.
+    pub fn insert(&mut self, ip: Ipv4Addr, mac: MacAddr, ttl_cycles: u64) {
+        let expires_at = dpdk::tsc_cycles() + ttl_cycles;
+        self.entries.insert(ip, (mac, expires_at));
+        self.requested.remove(&ip);
+    }
+
+    /// Returns the MAC address of the neighbor, if it has not expired.
+    pub fn lookup(&self, ip: &Ipv4Addr) -> Option<MacAddr> {
+        let now = dpdk::tsc_cycles();
+        self.entries
+            .get(ip)
+            .filter(|(_, expires_at)| *expires_at > now)
+            .map(|(mac, _)| *mac)
+    }
+
+    /// Removes the neighbors expired at `now` in TSC cycles. Returns the
+    /// number of neighbors removed.
+    pub fn evict_expired(&mut self, now: u64) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, (_, expires_at)| *expires_at > now);
+        before - self.entries.len()
+    }
+
+    /// Announces the address with a gratuitous ARP request, where both the
+    /// sender and the target are the same address.
+    ///
+    /// # Errors
+    ///
+    /// If the packet cannot be allocated, `DpdkError` is returned.
+    pub fn send_gratuitous(&self, ip: Ipv4Addr, mac: MacAddr, queue: &PortQueue) -> Result<()> {
+        let packet = build_arp(OperationCodes::Request, mac, ip, ip)?;
+        queue.transmit(vec![packet]);
+        Ok(())
+    }
+
+    /// Returns the MAC address of the neighbor if it's cached. Otherwise,
+    /// broadcasts an ARP request for it, unless one is sent recently, and
+    /// returns `None`.
+    pub fn resolve(&mut self, ip: Ipv4Addr, queue: &PortQueue) -> Option<MacAddr> {
+        if let Some(mac) = self.lookup(&ip) {
+            return Some(mac);
+        }
+
+        let now = dpdk::tsc_cycles();
+        let due = self
+            .requested
+            .get(&ip)
+            .map_or(true, |sent_at| now - sent_at >= self.retry_cycles);
+
+        if due {
+            if let Ok(packet) =
+                build_arp(OperationCodes::Request, queue.mac_addr(), self.local_ip, ip)
+            {
+                queue.transmit(vec![packet]);
+                self.requested.insert(ip, now);
+            }
+        }
+
+        None
+    }
+
+    /// Holds the packet until the neighbor is resolved.
+    pub fn hold(&mut self, ip: Ipv4Addr, packet: Ethernet) {
+        if self.pending_queue.len() >= MAX_PENDING {
+            let _ = self.pending_queue.remove(0);
+        }
+        self.pending_queue.push((ip, packet));
+    }
+
+    /// Learns the sender of an ARP reply, or of a gratuitous ARP. Returns
+    /// the packets held for the sender, with the destination set to its
+    /// MAC address.
+    pub fn process_reply(&mut self, arp: &Arp4) -> Vec<Ethernet> {
+        let ip = arp.sender_protocol_addr();
+        let mac = arp.sender_hardware_addr();
+
+        let is_reply = arp.operation_code() == OperationCodes::Reply;
+        let is_gratuitous = ip == arp.target_protocol_addr();
+        if !(is_reply || is_gratuitous) || ip.is_unspecified() {
+            return vec![];
+        }
+
+        self.insert(ip, mac, self.ttl_cycles);
+
+        let (released, pending) = self
+            .pending_queue
+            .drain(..)
+            .partition::<Vec<_>, _>(|(pending_ip, _)| *pending_ip == ip);
+        self.pending_queue = pending;
+
+        released
+            .into_iter()
+            .map(|(_, mut packet)| {
+                packet.set_dst(mac);
+                packet
+            })
+            .collect()
+    }
+}
+
+/// Builds a broadcast ARP packet.
+fn build_arp(
+    code: OperationCode,
+    src_mac: MacAddr,
+    src_ip: Ipv4Addr,
+    target_ip: Ipv4Addr,
+) -> Result<Mbuf> {
+    let mut ethernet = Mbuf::new()?.push::<Ethernet>()?;
+    ethernet.set_src(src_mac);
+    ethernet.set_dst(MacAddr::BROADCAST);
+
+    let mut arp = ethernet.push::<Arp4>()?;
+    arp.set_operation_code(code);
+    arp.set_sender_hardware_addr(src_mac);
+    arp.set_sender_protocol_addr(src_ip);
+    arp.set_target_hardware_addr(MacAddr::UNSPECIFIED);
+    arp.set_target_protocol_addr(target_ip);
+
+    Ok(arp.reset())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_ip() -> Ipv4Addr {
+        Ipv4Addr::new(10, 0, 0, 1)
+    }
+
+    fn neighbor_ip() -> Ipv4Addr {
+        Ipv4Addr::new(10, 0, 0, 2)
+    }
+
+    fn neighbor_mac() -> MacAddr {
+        MacAddr::new(0x02, 0, 0, 0, 0, 2)
+    }
+
+    #[capsule::test]
+    fn insert_lookup_and_evict() {
+        let mut cache = ArpCache::new(local_ip(), Duration::from_secs(60));
+        cache.insert(neighbor_ip(), neighbor_mac(), 1_000_000_000);
+        cache.insert(local_ip(), MacAddr::BROADCAST, 0);
+
+        assert_eq!(Some(neighbor_mac()), cache.lookup(&neighbor_ip()));
+        assert_eq!(None, cache.lookup(&local_ip()));
+        assert_eq!(2, cache.len());
+
+        assert_eq!(1, cache.evict_expired(dpdk::tsc_cycles()));
+        assert_eq!(1, cache.len());
+        assert_eq!(1, cache.evict_expired(u64::MAX));
+        assert!(cache.is_empty());
+    }
+
+    #[capsule::test]
+    fn build_gratuitous_arp() {
+        let mac = MacAddr::new(0x02, 0, 0, 0, 0, 1);
+        let packet = build_arp(OperationCodes::Request, mac, local_ip(), local_ip()).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        assert_eq!(MacAddr::BROADCAST, ethernet.dst());
+
+        let arp = ethernet.parse::<Arp4>().unwrap();
+        assert_eq!(OperationCodes::Request, arp.operation_code());
+        assert_eq!(mac, arp.sender_hardware_addr());
+        assert_eq!(local_ip(), arp.sender_protocol_addr());
+        assert_eq!(local_ip(), arp.target_protocol_addr());
+    }
+
+    #[capsule::test]
+    fn release_held_packets_on_reply() {
+        let mut cache = ArpCache::new(local_ip(), Duration::from_secs(60));
+        let held = Mbuf::new().unwrap().push::<Ethernet>().unwrap();
+        cache.hold(neighbor_ip(), held);
+        let other = Mbuf::new().unwrap().push::<Ethernet>().unwrap();
+        cache.hold(Ipv4Addr::new(10, 0, 0, 3), other);
+
+        let packet = build_arp(
+            OperationCodes::Request,
+            neighbor_mac(),
+            neighbor_ip(),
+            local_ip(),
+        )
+        .unwrap();
+        let request = packet.parse::<Ethernet>().unwrap().parse::<Arp4>().unwrap();
+        assert!(cache.process_reply(&request).is_empty());
+
+        let mut reply = request.reply(neighbor_mac()).unwrap();
+        reply.set_sender_protocol_addr(neighbor_ip());
+        let released = cache.process_reply(&reply);
+
+        assert_eq!(1, released.len());
+        assert_eq!(neighbor_mac(), released[0].dst());
+        assert_eq!(1, cache.pending_len());
+        assert_eq!(Some(neighbor_mac()), cache.lookup(&neighbor_ip()));
+    }
+}
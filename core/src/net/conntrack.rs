@@ -0,0 +1,463 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::dpdk;
+use crate::ensure;
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::{Flow, ProtocolNumbers};
+use crate::packets::{Packet, Tcp4, Udp4};
+use anyhow::Result;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// The key identifying a tracked connection.
+///
+/// A connection is keyed by the flow of the packet that opened it. Packets
+/// in the reply direction match the reverse of the key.
+pub type FlowKey = Flow;
+
+/// Error indicating the connection table operation failed.
+#[derive(Debug, Error)]
+pub enum ConnTrackError {
+    /// Error returned when the connection is already tracked.
+    #[error("Connection {0:?} is already tracked.")]
+    AlreadyTracked(FlowKey),
+
+    /// Error returned when the connection is not tracked.
+    #[error("Connection {0:?} is not tracked.")]
+    NotTracked(FlowKey),
+}
+
+/// TCP connection states.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TcpState {
+    /// The originator sent a SYN.
+    SynSent,
+    /// The responder replied with a SYN-ACK.
+    SynReceived,
+    /// The originator acknowledged the SYN-ACK.
+    Established,
+    /// One side sent a FIN.
+    FinWait,
+    /// Both sides sent a FIN.
+    TimeWait,
+    /// Either side sent a RST.
+    Closed,
+}
+
+/// UDP connection states.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UdpState {
+    /// Packets were seen for the flow.
+    Established,
+    /// The flow was marked as idle.
+    TimedOut,
+}
+
+/// The state of a tracked connection.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConnState {
+    /// A TCP connection.
+    Tcp(TcpState),
+    /// A UDP pseudo-connection.
+    Udp(UdpState),
+}
+
+/// The verdict for a packet processed by the tracker.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConnAction {
+    /// The packet belongs to a tracked connection.
+    Allow,
+    /// The packet does not belong to a tracked connection and does not
+    /// open a new one.
+    Deny,
+    /// The packet opened a new connection.
+    New,
+}
+
+/// A tracked connection.
+#[derive(Clone, Copy, Debug)]
+struct ConnEntry {
+    state: ConnState,
+    /// Whether the originator sent the first FIN.
+    originator_fin: bool,
+    last_seen: u64,
+}
+
+impl ConnEntry {
+    fn new(state: ConnState) -> Self {
+        ConnEntry {
+            state,
+            originator_fin: false,
+            last_seen: dpdk::tsc_cycles(),
+        }
+    }
+}
+
+/// A connection tracking table for TCP and UDP flows.
+///
+/// The table follows the TCP handshake and teardown of each connection
+/// and treats UDP flows as pseudo-connections. It is meant as the building
+/// block of a stateful firewall, where only packets opening or belonging
+/// to a tracked connection are let through.
+///
+/// # Example
+///
+/// ```
+/// let mut tracker = ConnTracker::new();
+///
+/// match tracker.process_packet(&ipv4) {
+///     ConnAction::Allow | ConnAction::New => forward(ipv4),
+///     ConnAction::Deny => drop(ipv4),
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ConnTracker {
+    conns: HashMap<FlowKey, ConnEntry>,
+}
+
+impl ConnTracker {
+    /// Creates a new empty table.
+    pub fn new() -> Self {
+        ConnTracker::default()
+    }
+
+    /// Returns the number of tracked connections.
+    pub fn len(&self) -> usize {
+        self.conns.len()
+    }
+
+    /// Returns whether the table has no tracked connections.
+    pub fn is_empty(&self) -> bool {
+        self.conns.is_empty()
+    }
+
+    /// Returns the key the connection is stored under, and whether the
+    /// flow is in the originator direction.
+    fn find(&self, flow: &FlowKey) -> Option<(FlowKey, bool)> {
+        if self.conns.contains_key(flow) {
+            Some((*flow, true))
+        } else {
+            let reverse = flow.reverse();
+            if self.conns.contains_key(&reverse) {
+                Some((reverse, false))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Starts tracking a connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnTrackError::AlreadyTracked` if the connection is
+    /// already tracked in either direction.
+    pub fn insert(&mut self, key: FlowKey, state: ConnState) -> Result<()> {
+        ensure!(
+            self.find(&key).is_none(),
+            ConnTrackError::AlreadyTracked(key)
+        );
+        self.conns.insert(key, ConnEntry::new(state));
+        Ok(())
+    }
+
+    /// Returns the state of the connection. The key can be in either
+    /// direction.
+    pub fn lookup(&self, key: &FlowKey) -> Option<&ConnState> {
+        self.find(key)
+            .and_then(|(key, _)| self.conns.get(&key))
+            .map(|entry| &entry.state)
+    }
+
+    /// Sets the state of the connection. The key can be in either
+    /// direction.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnTrackError::NotTracked` if the connection is not
+    /// tracked.
+    pub fn update(&mut self, key: &FlowKey, state: ConnState) -> Result<()> {
+        let (found, _) = self
+            .find(key)
+            .ok_or_else(|| ConnTrackError::NotTracked(*key))?;
+        let entry = self.conns.get_mut(&found).unwrap();
+        entry.state = state;
+        entry.last_seen = dpdk::tsc_cycles();
+        Ok(())
+    }
+
+    /// Stops tracking the connection. The key can be in either direction.
+    pub fn remove(&mut self, key: &FlowKey) {
+        if let Some((found, _)) = self.find(key) {
+            self.conns.remove(&found);
+        }
+    }
+
+    /// Tracks the packet and returns the verdict.
+    ///
+    /// A TCP SYN without ACK opens a new connection. Any other TCP packet
+    /// must belong to a tracked connection, and advances its state through
+    /// the handshake and teardown. Any UDP packet opens a new connection
+    /// unless one is already tracked. Packets of other protocols, or that
+    /// fail to parse, are denied.
+    pub fn process_packet(&mut self, ipv4: &Ipv4) -> ConnAction {
+        match ipv4.protocol() {
+            ProtocolNumbers::Tcp => match ipv4.peek::<Tcp4>() {
+                Ok(tcp) => self.process_tcp(tcp.flow(), tcp.syn(), tcp.ack(), tcp.fin(), tcp.rst()),
+                Err(_) => ConnAction::Deny,
+            },
+            ProtocolNumbers::Udp => match ipv4.peek::<Udp4>() {
+                Ok(udp) => self.process_udp(udp.flow()),
+                Err(_) => ConnAction::Deny,
+            },
+            _ => ConnAction::Deny,
+        }
+    }
+
+    fn process_tcp(
+        &mut self,
+        flow: Flow,
+        syn: bool,
+        ack: bool,
+        fin: bool,
+        rst: bool,
+    ) -> ConnAction {
+        let opening = syn && !ack;
+
+        let (key, originator) = match self.find(&flow) {
+            Some(found) => found,
+            None if opening => {
+                self.conns
+                    .insert(flow, ConnEntry::new(ConnState::Tcp(TcpState::SynSent)));
+                return ConnAction::New;
+            }
+            None => return ConnAction::Deny,
+        };
+
+        let entry = self.conns.get_mut(&key).unwrap();
+        let state = match entry.state {
+            ConnState::Tcp(state) => state,
+            // the flow was tracked as UDP by `insert`, which is not a
+            // connection a TCP packet can belong to.
+            ConnState::Udp(_) => return ConnAction::Deny,
+        };
+
+        let next = if rst {
+            TcpState::Closed
+        } else {
+            match state {
+                TcpState::SynSent if !originator && syn && ack => TcpState::SynReceived,
+                TcpState::SynReceived if originator && ack && !syn => TcpState::Established,
+                TcpState::Established if fin => {
+                    entry.originator_fin = originator;
+                    TcpState::FinWait
+                }
+                TcpState::FinWait if fin && originator != entry.originator_fin => {
+                    TcpState::TimeWait
+                }
+                // a new connection reusing the flow of a closed one.
+                TcpState::Closed if originator && opening => {
+                    entry.state = ConnState::Tcp(TcpState::SynSent);
+                    entry.originator_fin = false;
+                    entry.last_seen = dpdk::tsc_cycles();
+                    return ConnAction::New;
+                }
+                TcpState::Closed => return ConnAction::Deny,
+                state => state,
+            }
+        };
+
+        entry.state = ConnState::Tcp(next);
+        entry.last_seen = dpdk::tsc_cycles();
+        ConnAction::Allow
+    }
+
+    fn process_udp(&mut self, flow: Flow) -> ConnAction {
+        let key = match self.find(&flow) {
+            Some((key, _)) => key,
+            None => {
+                self.conns
+                    .insert(flow, ConnEntry::new(ConnState::Udp(UdpState::Established)));
+                return ConnAction::New;
+            }
+        };
+
+        let entry = self.conns.get_mut(&key).unwrap();
+        let action = match entry.state {
+            ConnState::Udp(UdpState::Established) => ConnAction::Allow,
+            ConnState::Udp(UdpState::TimedOut) => ConnAction::New,
+            ConnState::Tcp(_) => return ConnAction::Deny,
+        };
+
+        entry.state = ConnState::Udp(UdpState::Established);
+        entry.last_seen = dpdk::tsc_cycles();
+        action
+    }
+
+    /// Removes the connections last seen before `cutoff_cycles`. Returns
+    /// the number of connections removed.
+    ///
+    /// The cutoff is a TSC timestamp, for example the current cycles minus
+    /// the idle timeout.
+    pub fn evict_older_than(&mut self, cutoff_cycles: u64) -> usize {
+        let before = self.conns.len();
+        self.conns
+            .retain(|_, entry| entry.last_seen >= cutoff_cycles);
+        before - self.conns.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::Ethernet;
+    use crate::testils::byte_arrays::{IPV4_TCP_PACKET, IPV4_UDP_PACKET};
+    use crate::Mbuf;
+
+    /// Returns a TCP segment with the flags, in the reply direction if
+    /// `reply` is set.
+    fn tcp_segment(reply: bool, syn: bool, ack: bool, fin: bool) -> Ipv4 {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let ipv4 = packet.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap();
+        let mut tcp = ipv4.parse::<Tcp4>().unwrap();
+
+        if reply {
+            let src_port = tcp.src_port();
+            let dst_port = tcp.dst_port();
+            tcp.set_src_port(dst_port);
+            tcp.set_dst_port(src_port);
+            let src = tcp.envelope().src();
+            let dst = tcp.envelope().dst();
+            tcp.envelope_mut().set_src(dst);
+            tcp.envelope_mut().set_dst(src);
+        }
+
+        tcp.unset_syn();
+        if syn {
+            tcp.set_syn();
+        }
+        if ack {
+            tcp.set_ack();
+        }
+        if fin {
+            tcp.set_fin();
+        }
+
+        tcp.deparse()
+    }
+
+    fn tcp_state(tracker: &ConnTracker) -> TcpState {
+        match tracker.conns.values().next().unwrap().state {
+            ConnState::Tcp(state) => state,
+            state => panic!("not a TCP connection: {:?}", state),
+        }
+    }
+
+    #[capsule::test]
+    fn track_tcp_handshake_and_teardown() {
+        let mut tracker = ConnTracker::new();
+
+        let syn = tcp_segment(false, true, false, false);
+        assert_eq!(ConnAction::New, tracker.process_packet(&syn));
+        assert_eq!(TcpState::SynSent, tcp_state(&tracker));
+
+        let syn_ack = tcp_segment(true, true, true, false);
+        assert_eq!(ConnAction::Allow, tracker.process_packet(&syn_ack));
+        assert_eq!(TcpState::SynReceived, tcp_state(&tracker));
+
+        let ack = tcp_segment(false, false, true, false);
+        assert_eq!(ConnAction::Allow, tracker.process_packet(&ack));
+        assert_eq!(TcpState::Established, tcp_state(&tracker));
+
+        let fin = tcp_segment(true, false, true, true);
+        assert_eq!(ConnAction::Allow, tracker.process_packet(&fin));
+        assert_eq!(TcpState::FinWait, tcp_state(&tracker));
+
+        // a retransmitted FIN from the same side does not advance.
+        assert_eq!(ConnAction::Allow, tracker.process_packet(&fin));
+        assert_eq!(TcpState::FinWait, tcp_state(&tracker));
+
+        let fin = tcp_segment(false, false, true, true);
+        assert_eq!(ConnAction::Allow, tracker.process_packet(&fin));
+        assert_eq!(TcpState::TimeWait, tcp_state(&tracker));
+
+        assert_eq!(1, tracker.len());
+    }
+
+    #[capsule::test]
+    fn deny_untracked_tcp_segment() {
+        let mut tracker = ConnTracker::new();
+
+        let ack = tcp_segment(false, false, true, false);
+        assert_eq!(ConnAction::Deny, tracker.process_packet(&ack));
+        assert!(tracker.is_empty());
+    }
+
+    #[capsule::test]
+    fn track_udp_flow() {
+        let mut tracker = ConnTracker::new();
+
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ipv4 = packet.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap();
+        let flow = ipv4.peek::<Udp4>().unwrap().flow();
+
+        assert_eq!(ConnAction::New, tracker.process_packet(&ipv4));
+        assert_eq!(ConnAction::Allow, tracker.process_packet(&ipv4));
+        assert_eq!(
+            Some(&ConnState::Udp(UdpState::Established)),
+            tracker.lookup(&flow.reverse())
+        );
+
+        tracker
+            .update(&flow, ConnState::Udp(UdpState::TimedOut))
+            .unwrap();
+        assert_eq!(ConnAction::New, tracker.process_packet(&ipv4));
+    }
+
+    #[capsule::test]
+    fn insert_update_and_remove() {
+        let mut tracker = ConnTracker::new();
+        let flow = Flow::default();
+        let state = ConnState::Tcp(TcpState::Established);
+
+        assert!(tracker.update(&flow, state).is_err());
+        assert!(tracker.insert(flow, state).is_ok());
+        assert!(tracker.insert(flow.reverse(), state).is_err());
+
+        let closed = ConnState::Tcp(TcpState::Closed);
+        assert!(tracker.update(&flow, closed).is_ok());
+        assert_eq!(Some(&closed), tracker.lookup(&flow));
+
+        tracker.remove(&flow);
+        assert_eq!(None, tracker.lookup(&flow));
+    }
+
+    #[capsule::test]
+    fn evict_idle_connections() {
+        let mut tracker = ConnTracker::new();
+
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ipv4 = packet.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap();
+        let _ = tracker.process_packet(&ipv4);
+
+        assert_eq!(0, tracker.evict_older_than(0));
+        assert_eq!(1, tracker.evict_older_than(u64::max_value()));
+        assert!(tracker.is_empty());
+    }
+}
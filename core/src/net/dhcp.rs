@@ -0,0 +1,477 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use super::mac::xorshift;
+use super::udp::{build_datagram, parse_datagram};
+use crate::dpdk::PortQueue;
+use crate::net::{Cidr, Ipv4Cidr, MacAddr};
+use crate::packets::Packet;
+use crate::{debug, info};
+use anyhow::Result;
+use std::convert::TryInto;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+const CLIENT_PORT: u16 = 68;
+const SERVER_PORT: u16 = 67;
+
+/// The number of times a message is sent before giving up.
+const ATTEMPTS: usize = 4;
+
+/// How long to wait for the reply to each attempt.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The length of the fixed BOOTP part of the message.
+const BOOTP_LEN: usize = 236;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+const HTYPE_ETHERNET: u8 = 1;
+const FLAG_BROADCAST: u16 = 0x8000;
+
+// message types.
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+const DHCPREQUEST: u8 = 3;
+const DHCPACK: u8 = 5;
+const DHCPNAK: u8 = 6;
+
+// option codes.
+const OPT_PAD: u8 = 0;
+const OPT_SUBNET_MASK: u8 = 1;
+const OPT_ROUTER: u8 = 3;
+const OPT_DNS: u8 = 6;
+const OPT_HOSTNAME: u8 = 12;
+const OPT_REQUESTED_IP: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MSG_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_PARAM_LIST: u8 = 55;
+const OPT_END: u8 = 255;
+
+/// Error indicating the address cannot be acquired.
+#[derive(Debug, Error)]
+pub enum DhcpError {
+    /// Error returned when no server replied.
+    #[error("No reply from DHCP server.")]
+    Timeout,
+
+    /// Error returned when the server declined the request.
+    #[error("DHCP server {0} declined the request.")]
+    Declined(Ipv4Addr),
+}
+
+/// The fields of a server reply the client uses.
+#[derive(Debug)]
+struct Reply {
+    msg_type: u8,
+    xid: u32,
+    yiaddr: Ipv4Addr,
+    server_id: Option<Ipv4Addr>,
+    subnet_mask: Option<Ipv4Addr>,
+    router: Option<Ipv4Addr>,
+    dns_servers: Vec<Ipv4Addr>,
+    lease_time: Option<u32>,
+}
+
+/// The parameters of a client message.
+struct Request<'a> {
+    msg_type: u8,
+    xid: u32,
+    mac: MacAddr,
+    ciaddr: Ipv4Addr,
+    hostname: &'a str,
+    requested_ip: Option<Ipv4Addr>,
+    server_id: Option<Ipv4Addr>,
+}
+
+impl Request<'_> {
+    /// Encodes the message, as defined in RFC 2131.
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![0; BOOTP_LEN];
+        bytes[0] = BOOTREQUEST;
+        bytes[1] = HTYPE_ETHERNET;
+        bytes[2] = 6;
+        bytes[4..8].copy_from_slice(&self.xid.to_be_bytes());
+        // a client without an address can't receive unicast replies.
+        if self.ciaddr.is_unspecified() {
+            bytes[10..12].copy_from_slice(&FLAG_BROADCAST.to_be_bytes());
+        }
+        bytes[12..16].copy_from_slice(&self.ciaddr.octets());
+        bytes[28..34].copy_from_slice(&self.mac.octets());
+
+        bytes.extend_from_slice(&MAGIC_COOKIE);
+        bytes.extend_from_slice(&[OPT_MSG_TYPE, 1, self.msg_type]);
+        if let Some(ip) = self.requested_ip {
+            bytes.extend_from_slice(&[OPT_REQUESTED_IP, 4]);
+            bytes.extend_from_slice(&ip.octets());
+        }
+        if let Some(ip) = self.server_id {
+            bytes.extend_from_slice(&[OPT_SERVER_ID, 4]);
+            bytes.extend_from_slice(&ip.octets());
+        }
+        if !self.hostname.is_empty() {
+            let hostname = &self.hostname.as_bytes()[..self.hostname.len().min(255)];
+            bytes.extend_from_slice(&[OPT_HOSTNAME, hostname.len() as u8]);
+            bytes.extend_from_slice(hostname);
+        }
+        bytes.extend_from_slice(&[
+            OPT_PARAM_LIST,
+            4,
+            OPT_SUBNET_MASK,
+            OPT_ROUTER,
+            OPT_DNS,
+            OPT_LEASE_TIME,
+        ]);
+        bytes.push(OPT_END);
+        bytes
+    }
+}
+
+/// Returns the IPv4 addresses in the option value.
+fn addrs(value: &[u8]) -> Vec<Ipv4Addr> {
+    value
+        .chunks_exact(4)
+        .map(|octets| Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+        .collect()
+}
+
+/// Decodes a server reply for the client. Returns `None` if the message
+/// is malformed or not a reply to the client.
+fn decode(bytes: &[u8], mac: MacAddr) -> Option<Reply> {
+    if bytes.len() < BOOTP_LEN + MAGIC_COOKIE.len()
+        || bytes[0] != BOOTREPLY
+        || bytes[28..34] != mac.octets()
+        || bytes[BOOTP_LEN..BOOTP_LEN + 4] != MAGIC_COOKIE
+    {
+        return None;
+    }
+
+    let mut reply = Reply {
+        xid: u32::from_be_bytes(bytes[4..8].try_into().ok()?),
+        yiaddr: addrs(&bytes[16..20])[0],
+        msg_type: 0,
+        server_id: None,
+        subnet_mask: None,
+        router: None,
+        dns_servers: vec![],
+        lease_time: None,
+    };
+
+    let mut options = &bytes[BOOTP_LEN + 4..];
+    while let Some((&code, rest)) = options.split_first() {
+        match code {
+            OPT_PAD => {
+                options = rest;
+                continue;
+            }
+            OPT_END => break,
+            _ => (),
+        }
+
+        let (&len, rest) = rest.split_first()?;
+        if rest.len() < len as usize {
+            return None;
+        }
+        let (value, rest) = rest.split_at(len as usize);
+        options = rest;
+
+        match code {
+            OPT_MSG_TYPE if len == 1 => reply.msg_type = value[0],
+            OPT_SERVER_ID => reply.server_id = addrs(value).first().copied(),
+            OPT_SUBNET_MASK => reply.subnet_mask = addrs(value).first().copied(),
+            OPT_ROUTER => reply.router = addrs(value).first().copied(),
+            OPT_DNS => reply.dns_servers = addrs(value),
+            OPT_LEASE_TIME if len == 4 => {
+                reply.lease_time = Some(u32::from_be_bytes(value.try_into().ok()?))
+            }
+            _ => (),
+        }
+    }
+
+    if reply.msg_type == 0 {
+        None
+    } else {
+        Some(reply)
+    }
+}
+
+/// An address leased from a DHCP server.
+#[derive(Clone, Debug)]
+pub struct DhcpLease {
+    /// The leased address.
+    pub ip: Ipv4Addr,
+    /// The subnet the address is on.
+    pub subnet: Ipv4Cidr,
+    /// The default gateway, or unspecified if the server didn't offer one.
+    pub gateway: Ipv4Addr,
+    /// The DNS servers.
+    pub dns_servers: Vec<Ipv4Addr>,
+    /// The duration of the lease in seconds.
+    pub lease_time_secs: u32,
+    mac: MacAddr,
+    hostname: String,
+    server_id: Ipv4Addr,
+    server_mac: MacAddr,
+}
+
+impl DhcpLease {
+    fn new(reply: Reply, mac: MacAddr, hostname: &str, server_mac: MacAddr) -> Result<Self> {
+        let mask = reply.subnet_mask.unwrap_or(Ipv4Addr::BROADCAST);
+        let subnet = Ipv4Cidr::new(reply.yiaddr, u32::from(mask).count_ones() as usize)?;
+
+        Ok(DhcpLease {
+            ip: reply.yiaddr,
+            subnet,
+            gateway: reply.router.unwrap_or(Ipv4Addr::UNSPECIFIED),
+            dns_servers: reply.dns_servers,
+            lease_time_secs: reply.lease_time.unwrap_or(0),
+            mac,
+            hostname: hostname.to_owned(),
+            server_id: reply.server_id.unwrap_or(Ipv4Addr::UNSPECIFIED),
+            server_mac,
+        })
+    }
+
+    /// Renews the lease with the server that granted it. Returns the
+    /// renewed lease.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DhcpError::Timeout` if the server doesn't reply, or
+    /// `DhcpError::Declined` if the server doesn't renew the lease.
+    pub fn renew(&self, queue: &PortQueue) -> Result<DhcpLease> {
+        let request = Request {
+            msg_type: DHCPREQUEST,
+            xid: xorshift() as u32,
+            mac: self.mac,
+            ciaddr: self.ip,
+            hostname: &self.hostname,
+            requested_ip: None,
+            server_id: None,
+        };
+        let local = SocketAddrV4::new(self.ip, CLIENT_PORT);
+        let server = SocketAddrV4::new(self.server_id, SERVER_PORT);
+
+        let (reply, server_mac) = exchange(queue, &request, local, server, self.server_mac)?;
+        let lease = DhcpLease::new(reply, self.mac, &self.hostname, server_mac)?;
+        info!(ip = ?lease.ip, lease_time_secs = lease.lease_time_secs, "renewed DHCP lease.");
+        Ok(lease)
+    }
+}
+
+/// Sends the message and waits for the reply, retrying on timeout. The
+/// reply is either a `DHCPOFFER` to a `DHCPDISCOVER`, or a `DHCPACK` to a
+/// `DHCPREQUEST`. Returns the reply and the server's MAC address.
+fn exchange(
+    queue: &PortQueue,
+    request: &Request<'_>,
+    local: SocketAddrV4,
+    server: SocketAddrV4,
+    server_mac: MacAddr,
+) -> Result<(Reply, MacAddr)> {
+    let expected = if request.msg_type == DHCPDISCOVER {
+        DHCPOFFER
+    } else {
+        DHCPACK
+    };
+    let bytes = request.encode();
+    let any = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, CLIENT_PORT);
+
+    for attempt in 0..ATTEMPTS {
+        debug!(
+            attempt,
+            msg_type = request.msg_type,
+            "sending DHCP message."
+        );
+        let udp = build_datagram(request.mac, server_mac, local, server, &bytes)?;
+        queue.transmit(vec![udp.reset()]);
+
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        while Instant::now() < deadline {
+            for mbuf in queue.receive() {
+                // replies are broadcast until the client has an address.
+                let udp = match parse_datagram(mbuf, request.mac, any, true) {
+                    Some(udp) => udp,
+                    None => continue,
+                };
+
+                let payload = udp
+                    .mbuf()
+                    .read_data_slice::<u8>(udp.payload_offset(), udp.payload_len())?;
+                let reply = match decode(unsafe { payload.as_ref() }, request.mac) {
+                    Some(reply) if reply.xid == request.xid => reply,
+                    _ => continue,
+                };
+
+                if reply.msg_type == DHCPNAK {
+                    let server_id = reply.server_id.unwrap_or_else(|| *server.ip());
+                    return Err(DhcpError::Declined(server_id).into());
+                } else if reply.msg_type == expected {
+                    return Ok((reply, udp.envelope().envelope().src()));
+                }
+            }
+        }
+    }
+
+    Err(DhcpError::Timeout.into())
+}
+
+/// A minimal DHCP client, as defined in RFC 2131.
+///
+/// The client acquires an address with the discover, offer, request and
+/// acknowledge exchange. It takes the first offer received. Each message
+/// is sent up to 4 times, 2 seconds apart. The client polls the receive
+/// queue and discards any other packet received during the exchange, so
+/// it should run before the pipelines are started on the queue.
+///
+/// # Example
+///
+/// ```
+/// let lease = Dhcpv4Client::discover(q.mac_addr(), "nf0", &q)?;
+/// info!(ip = ?lease.ip, gateway = ?lease.gateway);
+///
+/// // before half the lease time has passed.
+/// let lease = lease.renew(&q)?;
+/// ```
+#[derive(Debug)]
+pub struct Dhcpv4Client;
+
+impl Dhcpv4Client {
+    /// Acquires an address for the MAC address.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DhcpError::Timeout` if no server replies, or
+    /// `DhcpError::Declined` if the server declines the request.
+    pub fn discover(mac: MacAddr, hostname: &str, queue: &PortQueue) -> Result<DhcpLease> {
+        let local = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, CLIENT_PORT);
+        let broadcast = SocketAddrV4::new(Ipv4Addr::BROADCAST, SERVER_PORT);
+
+        let mut request = Request {
+            msg_type: DHCPDISCOVER,
+            xid: xorshift() as u32,
+            mac,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            hostname,
+            requested_ip: None,
+            server_id: None,
+        };
+        let (offer, _) = exchange(queue, &request, local, broadcast, MacAddr::BROADCAST)?;
+        debug!(ip = ?offer.yiaddr, server = ?offer.server_id, "received DHCP offer.");
+
+        // the request is broadcast so other servers know their offers
+        // are declined.
+        request.msg_type = DHCPREQUEST;
+        request.requested_ip = Some(offer.yiaddr);
+        request.server_id = offer.server_id;
+        let (ack, server_mac) = exchange(queue, &request, local, broadcast, MacAddr::BROADCAST)?;
+
+        let lease = DhcpLease::new(ack, mac, hostname, server_mac)?;
+        info!(ip = ?lease.ip, lease_time_secs = lease.lease_time_secs, "acquired DHCP lease.");
+        Ok(lease)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_mac() -> MacAddr {
+        MacAddr::new(0x02, 0, 0, 0, 0, 1)
+    }
+
+    /// Encodes a server reply.
+    fn reply(msg_type: u8, xid: u32, options: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; BOOTP_LEN];
+        bytes[0] = BOOTREPLY;
+        bytes[4..8].copy_from_slice(&xid.to_be_bytes());
+        bytes[16..20].copy_from_slice(&[10, 0, 0, 100]);
+        bytes[28..34].copy_from_slice(&client_mac().octets());
+        bytes.extend_from_slice(&MAGIC_COOKIE);
+        bytes.extend_from_slice(&[OPT_MSG_TYPE, 1, msg_type]);
+        bytes.extend_from_slice(options);
+        bytes.push(OPT_END);
+        bytes
+    }
+
+    #[test]
+    fn encode_request() {
+        let request = Request {
+            msg_type: DHCPREQUEST,
+            xid: 0x1234_5678,
+            mac: client_mac(),
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            hostname: "nf0",
+            requested_ip: Some(Ipv4Addr::new(10, 0, 0, 100)),
+            server_id: Some(Ipv4Addr::new(10, 0, 0, 1)),
+        };
+        let bytes = request.encode();
+
+        assert_eq!(BOOTREQUEST, bytes[0]);
+        assert_eq!([0x12u8, 0x34, 0x56, 0x78], bytes[4..8]);
+        assert_eq!(FLAG_BROADCAST.to_be_bytes(), bytes[10..12]);
+        assert_eq!(client_mac().octets(), bytes[28..34]);
+        assert_eq!(MAGIC_COOKIE, bytes[236..240]);
+        assert_eq!([OPT_MSG_TYPE, 1, DHCPREQUEST], bytes[240..243]);
+        assert_eq!([OPT_REQUESTED_IP, 4, 10, 0, 0, 100], bytes[243..249]);
+        assert_eq!([OPT_SERVER_ID, 4, 10, 0, 0, 1], bytes[249..255]);
+        assert_eq!([OPT_HOSTNAME, 3, b'n', b'f', b'0'], bytes[255..260]);
+        assert_eq!(Some(&OPT_END), bytes.last());
+    }
+
+    #[test]
+    fn decode_ack() {
+        let options = [
+            &[OPT_SERVER_ID, 4, 10, 0, 0, 1][..],
+            &[OPT_PAD],
+            &[OPT_SUBNET_MASK, 4, 255, 255, 255, 0],
+            &[OPT_ROUTER, 4, 10, 0, 0, 1],
+            &[OPT_DNS, 8, 8, 8, 8, 8, 8, 8, 4, 4],
+            &[OPT_LEASE_TIME, 4, 0, 0, 0x0e, 0x10],
+        ]
+        .concat();
+        let bytes = reply(DHCPACK, 42, &options);
+        let reply = decode(&bytes, client_mac()).unwrap();
+        assert_eq!(DHCPACK, reply.msg_type);
+        assert_eq!(42, reply.xid);
+        assert_eq!(Some(Ipv4Addr::new(10, 0, 0, 1)), reply.server_id);
+
+        let lease = DhcpLease::new(reply, client_mac(), "nf0", MacAddr::BROADCAST).unwrap();
+        assert_eq!(Ipv4Addr::new(10, 0, 0, 100), lease.ip);
+        assert_eq!(24, lease.subnet.length());
+        assert_eq!(Ipv4Addr::new(10, 0, 0, 1), lease.gateway);
+        assert_eq!(
+            vec![Ipv4Addr::new(8, 8, 8, 8), Ipv4Addr::new(8, 8, 4, 4)],
+            lease.dns_servers
+        );
+        assert_eq!(3600, lease.lease_time_secs);
+    }
+
+    #[test]
+    fn decode_not_for_client() {
+        let bytes = reply(DHCPOFFER, 42, &[]);
+        assert!(decode(&bytes, MacAddr::new(0x02, 0, 0, 0, 0, 2)).is_none());
+
+        // truncated option.
+        let mut bytes = reply(DHCPOFFER, 42, &[OPT_DNS, 8, 8, 8]);
+        bytes.pop();
+        assert!(decode(&bytes, client_mac()).is_none());
+    }
+}
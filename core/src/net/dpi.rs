@@ -0,0 +1,482 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::packets::{Ethernet, Packet};
+use crate::{ensure, info};
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use thiserror::Error;
+
+/// The state of the automaton the matching starts from.
+const ROOT: usize = 0;
+
+/// Error indicating the patterns cannot be compiled.
+#[derive(Debug, Error)]
+pub enum DpiError {
+    /// Error returned when a pattern has no bytes.
+    #[error("Pattern {0} is empty.")]
+    EmptyPattern(usize),
+
+    /// Error returned when a pattern has a malformed escape sequence.
+    #[error("Malformed escape sequence in pattern '{0}'.")]
+    BadEscape(String),
+
+    /// Error returned when a Snort rule has a malformed `content` option.
+    #[error("Malformed content on line {0}.")]
+    BadContent(usize),
+}
+
+/// A signature found in a packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DpiMatch {
+    /// The index of the pattern in the list the context is created from.
+    pub pattern_id: u32,
+    /// The offset of the first byte of the match in the scanned bytes.
+    pub offset: usize,
+    /// The length of the match.
+    pub length: usize,
+}
+
+/// A state of the Aho-Corasick automaton.
+struct State {
+    /// The next state for each byte, with the failure transitions already
+    /// followed.
+    next: Box<[u32; 256]>,
+    /// The patterns that end at this state, including the ones inherited
+    /// through the failure link.
+    outputs: Vec<u32>,
+}
+
+impl State {
+    fn new() -> Self {
+        State {
+            next: Box::new([ROOT as u32; 256]),
+            outputs: vec![],
+        }
+    }
+}
+
+/// Parses a pattern, either as the exact bytes or with `\xNN` escaped
+/// bytes. `\\` is a literal backslash.
+fn parse_pattern(pattern: &str) -> Result<Vec<u8>, DpiError> {
+    let err = || DpiError::BadEscape(pattern.to_owned());
+    let mut bytes = Vec::with_capacity(pattern.len());
+    let mut rest = pattern.as_bytes();
+
+    while let Some((&b, tail)) = rest.split_first() {
+        if b != b'\\' {
+            bytes.push(b);
+            rest = tail;
+            continue;
+        }
+
+        match tail {
+            [b'\\', tail @ ..] => {
+                bytes.push(b'\\');
+                rest = tail;
+            }
+            [b'x', hi, lo, tail @ ..] => {
+                let hex = std::str::from_utf8(&[*hi, *lo])
+                    .map_err(|_| err())?
+                    .to_owned();
+                bytes.push(u8::from_str_radix(&hex, 16).map_err(|_| err())?);
+                rest = tail;
+            }
+            _ => return Err(err()),
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Parses the value of a Snort `content` option, where bytes between `|`
+/// are space separated hex, and `\"`, `\;` and `\\` are escaped.
+fn parse_snort_content(content: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut chars = content.chars();
+    let mut in_hex = false;
+    let mut hex = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '|' => {
+                if in_hex {
+                    let digits = hex.split_whitespace().collect::<String>();
+                    if digits.len() % 2 != 0 {
+                        return None;
+                    }
+                    for i in (0..digits.len()).step_by(2) {
+                        bytes.push(u8::from_str_radix(digits.get(i..i + 2)?, 16).ok()?);
+                    }
+                    hex.clear();
+                }
+                in_hex = !in_hex;
+            }
+            _ if in_hex => hex.push(c),
+            '\\' => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(chars.next()?.encode_utf8(&mut buf).as_bytes());
+            }
+            _ => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+
+    if in_hex {
+        None
+    } else {
+        Some(bytes)
+    }
+}
+
+/// Returns the values of the `content` options of a Snort rule. Negated
+/// contents are skipped, since they match packets without the bytes.
+fn snort_contents(rule: &str, line: usize) -> Result<Vec<Vec<u8>>, DpiError> {
+    let mut contents = vec![];
+    let mut rest = rule;
+
+    while let Some(start) = rest.find("content:") {
+        rest = rest[start + "content:".len()..].trim_start();
+
+        let negated = rest.starts_with('!');
+        if negated {
+            rest = rest[1..].trim_start();
+        }
+
+        ensure_quote(rest, line)?;
+        rest = &rest[1..];
+
+        // finds the closing quote that is not escaped.
+        let mut end = None;
+        let mut escaped = false;
+        for (idx, c) in rest.char_indices() {
+            match c {
+                '\\' if !escaped => escaped = true,
+                '"' if !escaped => {
+                    end = Some(idx);
+                    break;
+                }
+                _ => escaped = false,
+            }
+        }
+
+        let end = end.ok_or(DpiError::BadContent(line))?;
+        let content = parse_snort_content(&rest[..end]).ok_or(DpiError::BadContent(line))?;
+        rest = &rest[end + 1..];
+
+        if !negated {
+            contents.push(content);
+        }
+    }
+
+    Ok(contents)
+}
+
+fn ensure_quote(s: &str, line: usize) -> Result<(), DpiError> {
+    if s.starts_with('"') {
+        Ok(())
+    } else {
+        Err(DpiError::BadContent(line))
+    }
+}
+
+/// A deep packet inspection context that finds signatures in payloads.
+///
+/// The patterns are compiled into an Aho-Corasick automaton, so all the
+/// patterns are matched in a single pass over the bytes, regardless of
+/// the number of patterns. Overlapping matches are all reported.
+///
+/// # Example
+///
+/// ```
+/// let dpi = DpiContext::load_snort_rules_file("local.rules")?;
+///
+/// for m in dpi.classify(&ethernet) {
+///     println!("pattern {} at {}", m.pattern_id, m.offset);
+/// }
+/// ```
+pub struct DpiContext {
+    patterns: Vec<Vec<u8>>,
+    states: Vec<State>,
+}
+
+impl DpiContext {
+    /// Creates a new context from the patterns. A pattern is matched as
+    /// the exact bytes, where `\xNN` is a hex escaped byte and `\\` is a
+    /// backslash.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DpiError::EmptyPattern` if a pattern is empty, or
+    /// `DpiError::BadEscape` if a pattern has a malformed escape sequence.
+    pub fn new(patterns: &[&str]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| parse_pattern(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        DpiContext::from_bytes(patterns)
+    }
+
+    /// Creates a new context from patterns of raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DpiError::EmptyPattern` if a pattern is empty.
+    pub fn from_bytes(patterns: Vec<Vec<u8>>) -> Result<Self> {
+        for (idx, pattern) in patterns.iter().enumerate() {
+            ensure!(!pattern.is_empty(), DpiError::EmptyPattern(idx));
+        }
+
+        let mut context = DpiContext {
+            patterns,
+            states: vec![State::new()],
+        };
+        context.build();
+        Ok(context)
+    }
+
+    /// Creates a new context from the `content` options of a file of
+    /// Snort rules. The id of a pattern is the index of the content in the
+    /// file. Only the content bytes are matched, the other options of the
+    /// rules, such as `offset` or `nocase`, are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or
+    /// `DpiError::BadContent` if a `content` option is malformed.
+    pub fn load_snort_rules_file(path: &str) -> Result<Self> {
+        let rules =
+            fs::read_to_string(path).with_context(|| format!("failed to read '{}'.", path))?;
+
+        let mut patterns = vec![];
+        for (idx, line) in rules.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            patterns.extend(snort_contents(line, idx + 1)?);
+        }
+
+        info!(path, patterns = patterns.len(), "loaded Snort rules.");
+        DpiContext::from_bytes(patterns)
+    }
+
+    /// Builds the automaton from the patterns.
+    fn build(&mut self) {
+        // the trie of the patterns. 0 in `next` means no transition, since
+        // the root cannot be the child of another state.
+        for (id, pattern) in self.patterns.iter().enumerate() {
+            let mut state = ROOT;
+            for &b in pattern.iter() {
+                state = match self.states[state].next[b as usize] as usize {
+                    ROOT => {
+                        self.states.push(State::new());
+                        let child = self.states.len() - 1;
+                        self.states[state].next[b as usize] = child as u32;
+                        child
+                    }
+                    child => child,
+                };
+            }
+            self.states[state].outputs.push(id as u32);
+        }
+
+        // a breadth first walk computes the failure links, and fills in the
+        // missing transitions with the transitions of the failure state.
+        let mut fail = vec![ROOT; self.states.len()];
+        let mut queue = VecDeque::new();
+        for b in 0..256 {
+            let child = self.states[ROOT].next[b] as usize;
+            if child != ROOT {
+                queue.push_back(child);
+            }
+        }
+
+        while let Some(state) = queue.pop_front() {
+            let inherited = self.states[fail[state]].outputs.clone();
+            self.states[state].outputs.extend(inherited);
+
+            for b in 0..256 {
+                let child = self.states[state].next[b] as usize;
+                let fallback = self.states[fail[state]].next[b];
+                if child == ROOT {
+                    self.states[state].next[b] = fallback;
+                } else {
+                    fail[child] = fallback as usize;
+                    queue.push_back(child);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of patterns.
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Returns whether the context has no patterns.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns the bytes of the pattern.
+    pub fn pattern(&self, pattern_id: u32) -> Option<&[u8]> {
+        self.patterns.get(pattern_id as usize).map(Vec::as_slice)
+    }
+
+    /// Returns all the matches in the bytes, ordered by where they end.
+    pub fn scan(&self, data: &[u8]) -> Vec<DpiMatch> {
+        let mut matches = vec![];
+        let mut state = ROOT;
+
+        for (idx, &b) in data.iter().enumerate() {
+            state = self.states[state].next[b as usize] as usize;
+            for &pattern_id in self.states[state].outputs.iter() {
+                let length = self.patterns[pattern_id as usize].len();
+                matches.push(DpiMatch {
+                    pattern_id,
+                    offset: idx + 1 - length,
+                    length,
+                });
+            }
+        }
+
+        matches
+    }
+
+    /// Returns all the matches in the payload of the Ethernet frame. The
+    /// offsets are relative to the start of the payload.
+    pub fn classify(&self, pkt: &Ethernet) -> Vec<DpiMatch> {
+        let len = pkt.payload_len();
+        if len == 0 {
+            return vec![];
+        }
+
+        match pkt.mbuf().read_data_slice::<u8>(pkt.payload_offset(), len) {
+            Ok(data) => self.scan(unsafe { data.as_ref() }),
+            Err(_) => vec![],
+        }
+    }
+}
+
+impl fmt::Debug for DpiContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DpiContext")
+            .field("patterns", &self.patterns.len())
+            .field("states", &self.states.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+    use crate::Mbuf;
+    use std::env;
+
+    #[test]
+    fn parse_patterns() {
+        assert_eq!(b"abc".to_vec(), parse_pattern("abc").unwrap());
+        assert_eq!(
+            vec![0xde, 0xad, b'!', b'\\'],
+            parse_pattern("\\xde\\xAD!\\\\").unwrap()
+        );
+        assert!(parse_pattern("\\xz1").is_err());
+        assert!(parse_pattern("\\x1").is_err());
+        assert!(parse_pattern("\\n").is_err());
+    }
+
+    #[test]
+    fn find_overlapping_matches() {
+        let dpi = DpiContext::new(&["he", "she", "his", "hers"]).unwrap();
+        let matches = dpi.scan(b"ushers");
+
+        assert_eq!(
+            vec![
+                DpiMatch {
+                    pattern_id: 1,
+                    offset: 1,
+                    length: 3
+                },
+                DpiMatch {
+                    pattern_id: 0,
+                    offset: 2,
+                    length: 2
+                },
+                DpiMatch {
+                    pattern_id: 3,
+                    offset: 2,
+                    length: 4
+                },
+            ],
+            matches
+        );
+        assert!(dpi.scan(b"nothing").is_empty());
+    }
+
+    #[test]
+    fn reject_empty_pattern() {
+        assert!(DpiContext::new(&["abc", ""]).is_err());
+        assert!(DpiContext::new(&[]).unwrap().scan(b"abc").is_empty());
+    }
+
+    #[test]
+    fn load_snort_rules() {
+        let path = env::temp_dir().join("capsule_dpi.rules");
+        fs::write(
+            &path,
+            concat!(
+                "# comment with content:\"ignored\"\n",
+                "alert tcp any any -> any 80 (msg:\"get\"; content:\"GET /\"; ",
+                "content:!\"skip\"; sid:1;)\n",
+                "alert udp any any -> any any (content: \"|de ad|be\\\"ef\"; sid:2;)\n",
+            ),
+        )
+        .unwrap();
+
+        let dpi = DpiContext::load_snort_rules_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(2, dpi.len());
+        assert_eq!(Some(&b"GET /"[..]), dpi.pattern(0));
+        assert_eq!(Some(&b"\xde\xadbe\"ef"[..]), dpi.pattern(1));
+
+        assert!(snort_contents("content:\"|de a|\";", 1).is_err());
+        assert!(snort_contents("content:\"open", 1).is_err());
+    }
+
+    #[capsule::test]
+    fn classify_packet() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+
+        // the payload of the frame starts with the IPv4 header.
+        let dpi = DpiContext::new(&["\\x45\\x00"]).unwrap();
+        assert_eq!(
+            vec![DpiMatch {
+                pattern_id: 0,
+                offset: 0,
+                length: 2
+            }],
+            dpi.classify(&ethernet)
+        );
+    }
+}
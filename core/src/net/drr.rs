@@ -0,0 +1,246 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::ensure;
+use crate::packets::{Ethernet, Packet};
+use anyhow::Result;
+use std::collections::{HashMap, VecDeque};
+use thiserror::Error;
+
+/// Error indicating the scheduler cannot be created or the packet cannot
+/// be enqueued.
+#[derive(Debug, Error)]
+pub enum DrrError {
+    /// Error returned when the packet belongs to a new flow, but the
+    /// scheduler already has the maximum number of flows queued.
+    #[error("Cannot queue more than {0} flows.")]
+    TooManyFlows(usize),
+
+    /// Error returned when the quantum is 0, because flows would never
+    /// get credit to send.
+    #[error("Quantum must be at least 1 byte.")]
+    ZeroQuantum,
+}
+
+/// The queue and the deficit of a flow.
+#[derive(Debug, Default)]
+struct FlowQueue {
+    packets: VecDeque<Ethernet>,
+    deficit: u32,
+}
+
+/// A Deficit Round Robin scheduler for per-flow fairness, as described in
+/// "Efficient Fair Queueing using Deficit Round Robin" by Shreedhar and
+/// Varghese.
+///
+/// Each flow with queued packets gets `quantum_bytes` of credit every
+/// round, and sends packets while the credit covers them. Flows share the
+/// bandwidth equally regardless of their packet sizes. The quantum should
+/// be at least the largest packet size, so every flow sends at least one
+/// packet per round.
+///
+/// # Example
+///
+/// ```
+/// let mut drr = DrrScheduler::new(1514, 1024)?;
+/// for packet in packets {
+///     let flow_id = packet_flow_id(&packet);
+///     drr.enqueue(flow_id, packet)?;
+/// }
+///
+/// while let Some((flow_id, packet)) = drr.dequeue() {
+///     q.transmit(vec![packet.reset()]);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct DrrScheduler {
+    quantum: u32,
+    max_flows: usize,
+    flows: HashMap<u32, FlowQueue>,
+    active: VecDeque<u32>,
+    // whether the flow at the front of `active` got its quantum for the
+    // current round.
+    visiting: bool,
+    total: usize,
+}
+
+impl DrrScheduler {
+    /// Creates a new scheduler that holds packets of up to `max_flows`
+    /// flows at a time.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DrrError::ZeroQuantum` if `quantum_bytes` is 0.
+    pub fn new(quantum_bytes: u32, max_flows: usize) -> Result<Self> {
+        ensure!(quantum_bytes > 0, DrrError::ZeroQuantum);
+
+        Ok(DrrScheduler {
+            quantum: quantum_bytes,
+            max_flows,
+            flows: HashMap::new(),
+            active: VecDeque::new(),
+            visiting: false,
+            total: 0,
+        })
+    }
+
+    /// Queues the packet at the end of the flow's queue.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DrrError::TooManyFlows` if the flow has no packets queued,
+    /// and there are already `max_flows` flows with packets queued.
+    pub fn enqueue(&mut self, flow_id: u32, pkt: Ethernet) -> Result<()> {
+        if !self.flows.contains_key(&flow_id) {
+            ensure!(
+                self.flows.len() < self.max_flows,
+                DrrError::TooManyFlows(self.max_flows)
+            );
+            self.active.push_back(flow_id);
+        }
+
+        self.flows
+            .entry(flow_id)
+            .or_default()
+            .packets
+            .push_back(pkt);
+        self.total += 1;
+        Ok(())
+    }
+
+    /// Returns the next packet to send and its flow, or `None` if no packet
+    /// is queued.
+    pub fn dequeue(&mut self) -> Option<(u32, Ethernet)> {
+        loop {
+            let flow_id = *self.active.front()?;
+            let flow = self.flows.get_mut(&flow_id)?;
+
+            if !self.visiting {
+                flow.deficit = flow.deficit.saturating_add(self.quantum);
+                self.visiting = true;
+            }
+
+            let len = flow.packets.front()?.mbuf().pkt_len() as u32;
+            if len > flow.deficit {
+                // the credit is used up for this round, the flow keeps the
+                // rest for the next round.
+                self.active.rotate_left(1);
+                self.visiting = false;
+                continue;
+            }
+
+            flow.deficit -= len;
+            let pkt = flow.packets.pop_front()?;
+            self.total -= 1;
+
+            if flow.packets.is_empty() {
+                // an idle flow does not accumulate credit.
+                self.flows.remove(&flow_id);
+                let _ = self.active.pop_front();
+                self.visiting = false;
+            }
+
+            return Some((flow_id, pkt));
+        }
+    }
+
+    /// Returns the number of packets queued for the flow.
+    pub fn flow_queue_depth(&self, flow_id: u32) -> usize {
+        self.flows
+            .get(&flow_id)
+            .map_or(0, |flow| flow.packets.len())
+    }
+
+    /// Returns the number of packets queued for all the flows.
+    pub fn total_queued(&self) -> usize {
+        self.total
+    }
+
+    /// Returns the number of flows with packets queued.
+    pub fn active_flows(&self) -> usize {
+        self.active.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Mbuf;
+
+    fn new_packet(len: usize) -> Ethernet {
+        Mbuf::from_bytes(&vec![0; len])
+            .unwrap()
+            .parse::<Ethernet>()
+            .unwrap()
+    }
+
+    #[test]
+    fn reject_zero_quantum() {
+        assert!(DrrScheduler::new(0, 2).is_err());
+    }
+
+    #[capsule::test]
+    fn enqueue_and_dequeue() {
+        let mut drr = DrrScheduler::new(1500, 2).unwrap();
+        drr.enqueue(1, new_packet(64)).unwrap();
+        drr.enqueue(1, new_packet(64)).unwrap();
+        drr.enqueue(2, new_packet(64)).unwrap();
+        assert!(drr.enqueue(3, new_packet(64)).is_err());
+
+        assert_eq!(2, drr.flow_queue_depth(1));
+        assert_eq!(1, drr.flow_queue_depth(2));
+        assert_eq!(0, drr.flow_queue_depth(3));
+        assert_eq!(3, drr.total_queued());
+
+        let mut order = vec![];
+        while let Some((flow_id, _)) = drr.dequeue() {
+            order.push(flow_id);
+        }
+
+        assert_eq!(vec![1, 1, 2], order);
+        assert_eq!(0, drr.total_queued());
+        assert_eq!(0, drr.active_flows());
+        assert!(drr.enqueue(3, new_packet(64)).is_ok());
+    }
+
+    #[capsule::test]
+    fn equal_bandwidth_for_equal_quantum() {
+        let mut drr = DrrScheduler::new(600, 2).unwrap();
+        // the same backlog in bytes, with different packet sizes.
+        for _ in 0..60 {
+            drr.enqueue(1, new_packet(100)).unwrap();
+        }
+        for _ in 0..20 {
+            drr.enqueue(2, new_packet(300)).unwrap();
+        }
+
+        // drains half of the backlog, while both flows still have packets.
+        let mut bytes = [0; 2];
+        let mut sent = 0;
+        while sent < 6000 {
+            let (flow_id, packet) = drr.dequeue().unwrap();
+            let len = packet.mbuf().pkt_len();
+            bytes[flow_id as usize - 1] += len;
+            sent += len;
+        }
+
+        assert_eq!(bytes[0], bytes[1]);
+        assert!(drr.flow_queue_depth(1) > 0);
+        assert!(drr.flow_queue_depth(2) > 0);
+    }
+}
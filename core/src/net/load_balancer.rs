@@ -0,0 +1,597 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::FlowKey;
+use crate::batch::{PacketRx, PacketTx};
+use crate::dpdk::{CoreId, LcoreHandle, LcoreManager};
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::v6::{Ipv6, Ipv6Packet};
+use crate::packets::ip::{Flow, ProtocolNumbers};
+use crate::packets::{EtherTypes, Ethernet, Packet, Tcp4, Tcp6, Udp4, Udp6};
+use crate::Mbuf;
+use crate::{debug, ensure};
+use anyhow::Result;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Error indicating the backend cannot be added.
+#[derive(Debug, Error)]
+pub enum LbError {
+    /// Error returned when a backend with the same id is already added.
+    #[error("Backend {0} already exists.")]
+    DuplicateBackend(u32),
+
+    /// Error returned when the backend weight is zero.
+    #[error("Backend {0} has a zero weight.")]
+    ZeroWeight(u32),
+}
+
+/// Per-backend counters.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BackendStats {
+    /// The weight of the backend.
+    pub weight: u32,
+    /// The number of times the backend was selected.
+    pub selected: u64,
+}
+
+/// A strategy for distributing flows across a pool of backends.
+pub trait LbStrategy {
+    /// Adds a backend with a relative weight.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LbError::DuplicateBackend` if the id is already added, or
+    /// `LbError::ZeroWeight` if the weight is zero.
+    fn add_backend(&mut self, id: u32, weight: u32) -> Result<()>;
+
+    /// Removes a backend. Does nothing if the backend is not added.
+    fn remove_backend(&mut self, id: u32);
+
+    /// Selects the backend for the flow. Returns `None` if the pool is
+    /// empty.
+    fn select_backend(&mut self, flow_key: &FlowKey) -> Option<u32>;
+
+    /// Returns the counters of each backend.
+    fn stats(&self) -> HashMap<u32, BackendStats>;
+}
+
+/// A pool of backends ordered by id.
+#[derive(Debug, Default)]
+struct BackendPool {
+    backends: Vec<(u32, BackendStats)>,
+}
+
+impl BackendPool {
+    fn add(&mut self, id: u32, weight: u32) -> Result<usize> {
+        ensure!(weight > 0, LbError::ZeroWeight(id));
+
+        match self.backends.binary_search_by_key(&id, |&(id, _)| id) {
+            Ok(_) => Err(LbError::DuplicateBackend(id).into()),
+            Err(idx) => {
+                let stats = BackendStats {
+                    weight,
+                    selected: 0,
+                };
+                self.backends.insert(idx, (id, stats));
+                Ok(idx)
+            }
+        }
+    }
+
+    fn remove(&mut self, id: u32) -> Option<usize> {
+        let idx = self
+            .backends
+            .binary_search_by_key(&id, |&(id, _)| id)
+            .ok()?;
+        self.backends.remove(idx);
+        Some(idx)
+    }
+
+    fn select(&mut self, idx: usize) -> u32 {
+        let (id, stats) = &mut self.backends[idx];
+        stats.selected += 1;
+        *id
+    }
+
+    fn stats(&self) -> HashMap<u32, BackendStats> {
+        self.backends.iter().copied().collect()
+    }
+}
+
+/// Hashes the flow with a seed.
+fn flow_hash(flow_key: &FlowKey, seed: u32) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    flow_key.hash(&mut hasher);
+    seed.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A consistent hashing load balancer using the Rendezvous, or highest
+/// random weight, algorithm.
+///
+/// Each flow is scored against every backend and goes to the backend with
+/// the highest score. When a backend is removed, only the flows on that
+/// backend move; when a backend is added, only the flows it now wins move.
+/// The weight scales the share of flows a backend receives.
+///
+/// # Example
+///
+/// ```
+/// let mut lb = LoadBalancer::new();
+/// lb.add_backend(1, 1)?;
+/// lb.add_backend(2, 2)?;
+///
+/// let backend = lb.select_backend(&tcp.flow());
+/// ```
+#[derive(Debug, Default)]
+pub struct LoadBalancer {
+    pool: BackendPool,
+}
+
+impl LoadBalancer {
+    /// Creates a new load balancer with no backends.
+    pub fn new() -> Self {
+        LoadBalancer::default()
+    }
+
+    /// Returns the score of the flow for the backend.
+    fn score(flow_key: &FlowKey, id: u32, weight: u32) -> f64 {
+        // maps the hash to a uniform number in the open interval (0, 1).
+        let hash = flow_hash(flow_key, id) >> 11;
+        let uniform = (hash as f64 + 0.5) / (1u64 << 53) as f64;
+        f64::from(weight) / -uniform.ln()
+    }
+}
+
+impl LbStrategy for LoadBalancer {
+    fn add_backend(&mut self, id: u32, weight: u32) -> Result<()> {
+        self.pool.add(id, weight).map(|_| ())
+    }
+
+    fn remove_backend(&mut self, id: u32) {
+        self.pool.remove(id);
+    }
+
+    fn select_backend(&mut self, flow_key: &FlowKey) -> Option<u32> {
+        let idx = self
+            .pool
+            .backends
+            .iter()
+            .map(|&(id, stats)| LoadBalancer::score(flow_key, id, stats.weight))
+            .enumerate()
+            .fold(
+                None,
+                |best: Option<(usize, f64)>, (idx, score)| match best {
+                    Some((_, best_score)) if best_score >= score => best,
+                    _ => Some((idx, score)),
+                },
+            )
+            .map(|(idx, _)| idx)?;
+
+        Some(self.pool.select(idx))
+    }
+
+    fn stats(&self) -> HashMap<u32, BackendStats> {
+        self.pool.stats()
+    }
+}
+
+/// A weighted round robin load balancer.
+///
+/// Backends are selected in turn regardless of the flow, so packets of the
+/// same flow may go to different backends. The selections are spread
+/// smoothly, a backend with weight 3 and one with weight 1 are selected in
+/// the order `a, a, b, a` rather than `a, a, a, b`.
+#[derive(Debug, Default)]
+pub struct RoundRobinLb {
+    pool: BackendPool,
+    current: Vec<i64>,
+}
+
+impl RoundRobinLb {
+    /// Creates a new load balancer with no backends.
+    pub fn new() -> Self {
+        RoundRobinLb::default()
+    }
+}
+
+impl LbStrategy for RoundRobinLb {
+    fn add_backend(&mut self, id: u32, weight: u32) -> Result<()> {
+        let idx = self.pool.add(id, weight)?;
+        self.current.insert(idx, 0);
+        Ok(())
+    }
+
+    fn remove_backend(&mut self, id: u32) {
+        if let Some(idx) = self.pool.remove(id) {
+            self.current.remove(idx);
+        }
+    }
+
+    fn select_backend(&mut self, _flow_key: &FlowKey) -> Option<u32> {
+        let mut total = 0;
+        let mut best = None;
+
+        for (idx, &(_, stats)) in self.pool.backends.iter().enumerate() {
+            let weight = i64::from(stats.weight);
+            total += weight;
+            self.current[idx] += weight;
+
+            match best {
+                Some(best_idx) if self.current[best_idx] >= self.current[idx] => (),
+                _ => best = Some(idx),
+            }
+        }
+
+        let idx = best?;
+        self.current[idx] -= total;
+        Some(self.pool.select(idx))
+    }
+
+    fn stats(&self) -> HashMap<u32, BackendStats> {
+        self.pool.stats()
+    }
+}
+
+/// A power of two choices load balancer.
+///
+/// The flow is hashed to two candidate backends, and the one with fewer
+/// selections relative to its weight is chosen. This keeps the load even
+/// without scanning the whole pool, but like `RoundRobinLb`, packets of
+/// the same flow may go to different backends.
+#[derive(Debug, Default)]
+pub struct PowerOfTwoChoicesLb {
+    pool: BackendPool,
+}
+
+impl PowerOfTwoChoicesLb {
+    /// Creates a new load balancer with no backends.
+    pub fn new() -> Self {
+        PowerOfTwoChoicesLb::default()
+    }
+}
+
+impl LbStrategy for PowerOfTwoChoicesLb {
+    fn add_backend(&mut self, id: u32, weight: u32) -> Result<()> {
+        self.pool.add(id, weight).map(|_| ())
+    }
+
+    fn remove_backend(&mut self, id: u32) {
+        self.pool.remove(id);
+    }
+
+    fn select_backend(&mut self, flow_key: &FlowKey) -> Option<u32> {
+        let len = self.pool.backends.len() as u64;
+        if len == 0 {
+            return None;
+        }
+
+        let first = (flow_hash(flow_key, 0) % len) as usize;
+        let mut second = (flow_hash(flow_key, 1) % len) as usize;
+        if second == first {
+            second = (first + 1) % len as usize;
+        }
+
+        // compares `selected / weight` without the division.
+        let load = |idx: usize| {
+            let (_, stats) = self.pool.backends[idx];
+            (u128::from(stats.selected), u128::from(stats.weight))
+        };
+        let (a_selected, a_weight) = load(first);
+        let (b_selected, b_weight) = load(second);
+
+        let idx = if a_selected * b_weight <= b_selected * a_weight {
+            first
+        } else {
+            second
+        };
+
+        Some(self.pool.select(idx))
+    }
+
+    fn stats(&self) -> HashMap<u32, BackendStats> {
+        self.pool.stats()
+    }
+}
+
+/// Returns the 5-tuple flow of a TCP or UDP packet.
+fn packet_flow(ethernet: &Ethernet) -> Option<Flow> {
+    match ethernet.ether_type() {
+        EtherTypes::Ipv4 => {
+            let ipv4 = ethernet.peek::<Ipv4>().ok()?;
+            match ipv4.protocol() {
+                ProtocolNumbers::Tcp => ipv4.peek::<Tcp4>().ok().map(|tcp| tcp.flow()),
+                ProtocolNumbers::Udp => ipv4.peek::<Udp4>().ok().map(|udp| udp.flow()),
+                _ => None,
+            }
+        }
+        EtherTypes::Ipv6 => {
+            let ipv6 = ethernet.peek::<Ipv6>().ok()?;
+            match ipv6.next_header() {
+                ProtocolNumbers::Tcp => ipv6.peek::<Tcp6>().ok().map(|tcp| tcp.flow()),
+                ProtocolNumbers::Udp => ipv6.peek::<Udp6>().ok().map(|udp| udp.flow()),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// A receive loop that distributes packets across backend transmit queues.
+///
+/// The backend id selected by the strategy is the index of the transmit
+/// queue in `backends`. Packets that are not TCP or UDP, or whose
+/// selected backend has no queue, are dropped.
+///
+/// # Example
+///
+/// ```
+/// let mut lb = LoadBalancer::new();
+/// lb.add_backend(0, 1)?;
+/// lb.add_backend(1, 1)?;
+///
+/// let pipeline = LbPipeline::new(rx, vec![tx0, tx1], lb);
+/// let handle = pipeline.start(core_id)?;
+/// ```
+#[allow(missing_debug_implementations)]
+pub struct LbPipeline<Rx: PacketRx, Tx: PacketTx, S: LbStrategy> {
+    rx: Rx,
+    backends: Vec<Tx>,
+    lb: S,
+}
+
+impl<Rx: PacketRx, Tx: PacketTx, S: LbStrategy> LbPipeline<Rx, Tx, S> {
+    /// Creates a new pipeline.
+    pub fn new(rx: Rx, backends: Vec<Tx>, lb: S) -> Self {
+        LbPipeline { rx, backends, lb }
+    }
+
+    /// Returns the load balancing strategy.
+    pub fn strategy(&self) -> &S {
+        &self.lb
+    }
+
+    /// Receives one burst of packets and transmits each to its backend.
+    /// Returns the number of packets transmitted.
+    pub fn run_once(&mut self) -> usize {
+        let mut queued = self
+            .backends
+            .iter()
+            .map(|_| Vec::new())
+            .collect::<Vec<Vec<Mbuf>>>();
+
+        for mbuf in self.rx.receive() {
+            let ethernet = match mbuf.parse::<Ethernet>() {
+                Ok(ethernet) => ethernet,
+                Err(_) => continue,
+            };
+
+            let backend = packet_flow(&ethernet)
+                .and_then(|flow| self.lb.select_backend(&flow))
+                .and_then(|id| queued.get_mut(id as usize));
+
+            if let Some(queue) = backend {
+                queue.push(ethernet.reset());
+            }
+        }
+
+        let mut sent = 0;
+        for (tx, packets) in self.backends.iter_mut().zip(queued) {
+            if !packets.is_empty() {
+                sent += packets.len();
+                tx.transmit(packets);
+            }
+        }
+
+        sent
+    }
+}
+
+impl<Rx, Tx, S> LbPipeline<Rx, Tx, S>
+where
+    Rx: PacketRx + Send + 'static,
+    Tx: PacketTx + Send + 'static,
+    S: LbStrategy + Send + 'static,
+{
+    /// Runs the pipeline in a loop on a worker lcore.
+    ///
+    /// # Errors
+    ///
+    /// If the lcore cannot be launched, `DpdkError` is returned.
+    pub fn start(self, core_id: CoreId) -> Result<LbPipelineHandle> {
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+        let mut pipeline = self;
+
+        let handle = LcoreManager::launch(core_id, move || {
+            while flag.load(Ordering::Relaxed) {
+                pipeline.run_once();
+            }
+        })?;
+
+        debug!(?core_id, "started load balancer pipeline.");
+        Ok(LbPipelineHandle {
+            running,
+            handle: Some(handle),
+        })
+    }
+}
+
+/// A handle to a running `LbPipeline`. The pipeline is stopped when the
+/// handle is dropped.
+#[derive(Debug)]
+pub struct LbPipelineHandle {
+    running: Arc<AtomicBool>,
+    handle: Option<LcoreHandle>,
+}
+
+impl LbPipelineHandle {
+    /// Stops the pipeline and waits for the lcore to finish.
+    ///
+    /// # Errors
+    ///
+    /// If the pipeline panicked, `LcoreError::Panicked` is returned.
+    pub fn stop(mut self) -> Result<()> {
+        self.running.store(false, Ordering::Relaxed);
+        match self.handle.take() {
+            Some(handle) => handle.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for LbPipelineHandle {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+            debug!("stopped load balancer pipeline.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::{IPV4_TCP_PACKET, IPV4_UDP_PACKET};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::mpsc;
+
+    fn flow(src_port: u16) -> Flow {
+        Flow::new(
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            src_port,
+            80,
+            ProtocolNumbers::Tcp,
+        )
+    }
+
+    #[test]
+    fn reject_invalid_backend() {
+        let mut lb = LoadBalancer::new();
+        assert!(lb.add_backend(1, 0).is_err());
+        assert!(lb.add_backend(1, 1).is_ok());
+        assert!(lb.add_backend(1, 1).is_err());
+        assert_eq!(None, LoadBalancer::new().select_backend(&flow(1)));
+    }
+
+    #[test]
+    fn rendezvous_is_stable_under_removal() {
+        let mut lb = LoadBalancer::new();
+        for id in 0..4 {
+            lb.add_backend(id, 1).unwrap();
+        }
+
+        let before = (0..1000)
+            .map(|port| lb.select_backend(&flow(port)).unwrap())
+            .collect::<Vec<_>>();
+
+        // every backend gets a share of the flows.
+        let stats = lb.stats();
+        assert!(stats.values().all(|stats| stats.selected > 100));
+
+        lb.remove_backend(2);
+        for (port, &backend) in before.iter().enumerate() {
+            let after = lb.select_backend(&flow(port as u16)).unwrap();
+            if backend != 2 {
+                assert_eq!(backend, after);
+            } else {
+                assert_ne!(2, after);
+            }
+        }
+    }
+
+    #[test]
+    fn rendezvous_respects_weight() {
+        let mut lb = LoadBalancer::new();
+        lb.add_backend(1, 1).unwrap();
+        lb.add_backend(2, 3).unwrap();
+
+        for port in 0..1000 {
+            lb.select_backend(&flow(port));
+        }
+
+        let stats = lb.stats();
+        assert!(stats[&2].selected > stats[&1].selected * 2);
+    }
+
+    #[test]
+    fn weighted_round_robin() {
+        let mut lb = RoundRobinLb::new();
+        lb.add_backend(1, 3).unwrap();
+        lb.add_backend(2, 1).unwrap();
+
+        let selected = (0..4)
+            .map(|_| lb.select_backend(&flow(1)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 1, 2, 1], selected);
+
+        lb.remove_backend(1);
+        assert_eq!(Some(2), lb.select_backend(&flow(1)));
+    }
+
+    #[test]
+    fn power_of_two_choices_balances_load() {
+        let mut lb = PowerOfTwoChoicesLb::new();
+        for id in 0..2 {
+            lb.add_backend(id, 1).unwrap();
+        }
+
+        // with two backends, both are always the candidates.
+        for _ in 0..100 {
+            lb.select_backend(&flow(1));
+        }
+
+        let stats = lb.stats();
+        assert_eq!(50, stats[&0].selected);
+        assert_eq!(50, stats[&1].selected);
+    }
+
+    #[capsule::test]
+    fn pipeline_distributes_packets() {
+        let (mut tx, rx) = mpsc::channel();
+        let (tx0, rx0) = mpsc::channel();
+        let (tx1, rx1) = mpsc::channel();
+
+        let mut lb = LoadBalancer::new();
+        lb.add_backend(0, 1).unwrap();
+        lb.add_backend(1, 1).unwrap();
+
+        tx.transmit(vec![
+            Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap(),
+            Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap(),
+            Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap(),
+            Mbuf::from_bytes(&[0; 14]).unwrap(),
+        ]);
+
+        let mut pipeline = LbPipeline::new(rx, vec![tx0, tx1], lb);
+        assert_eq!(3, pipeline.run_once());
+        assert_eq!(3, rx0.try_iter().count() + rx1.try_iter().count());
+
+        // packets of the same flow go to the same backend.
+        let stats = pipeline.strategy().stats();
+        assert_eq!(3, stats[&0].selected + stats[&1].selected);
+        assert!(stats.values().any(|stats| stats.selected >= 2));
+    }
+}
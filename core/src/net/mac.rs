@@ -16,13 +16,16 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
+use crate::dpdk::{self, CoreId};
+use crate::ffi;
+use std::cell::Cell;
 use std::convert::From;
 use std::fmt;
 use std::str::FromStr;
 use thiserror::Error;
 
 /// Ethernet MAC address.
-#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[repr(C, packed)]
 pub struct MacAddr([u8; 6]);
 
@@ -30,24 +33,85 @@ impl MacAddr {
     /// A MAC address representing an unspecified address: 00:00:00:00:00:00.
     pub const UNSPECIFIED: Self = MacAddr([0, 0, 0, 0, 0, 0]);
 
+    /// A MAC address representing the broadcast address: FF:FF:FF:FF:FF:FF.
+    pub const BROADCAST: Self = MacAddr([0xff; 6]);
+
     /// Creates a MAC address from 6 octets.
     #[allow(clippy::many_single_char_names)]
-    pub fn new(a: u8, b: u8, c: u8, d: u8, e: u8, f: u8) -> Self {
+    pub const fn new(a: u8, b: u8, c: u8, d: u8, e: u8, f: u8) -> Self {
         MacAddr([a, b, c, d, e, f])
     }
 
+    /// Returns the broadcast address.
+    pub fn broadcast() -> Self {
+        MacAddr::BROADCAST
+    }
+
+    /// Generates a random locally administered unicast address.
+    ///
+    /// The generator is a per-thread XorShift seeded from the current core
+    /// id and the TSC cycle counter. It is not cryptographically secure.
+    pub fn random() -> Self {
+        let mut bytes = [0; 6];
+        bytes.copy_from_slice(&xorshift().to_be_bytes()[2..]);
+        // clears the multicast bit and sets the locally administered bit.
+        bytes[0] = (bytes[0] & 0xfe) | 0x02;
+        MacAddr(bytes)
+    }
+
     /// Returns the six bytes the MAC address consists of.
     #[allow(clippy::trivially_copy_pass_by_ref)]
     pub fn octets(&self) -> [u8; 6] {
         self.0
     }
+
+    /// Returns whether the address is the broadcast address.
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn is_broadcast(&self) -> bool {
+        *self == MacAddr::BROADCAST
+    }
+
+    /// Returns whether the address is a group address. The broadcast
+    /// address is also a multicast address.
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0x01 != 0
+    }
+
+    /// Returns whether the address is locally administered, as opposed to
+    /// universally administered by the manufacturer.
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn is_locally_administered(&self) -> bool {
+        self.0[0] & 0x02 != 0
+    }
+}
+
+thread_local! {
+    static XORSHIFT_STATE: Cell<u64> = Cell::new(0);
+}
+
+/// Returns the next value of the thread's XorShift generator.
+pub(super) fn xorshift() -> u64 {
+    XORSHIFT_STATE.with(|state| {
+        let mut x = state.get();
+        if x == 0 {
+            // lazily seeds the generator, a zero seed gets stuck at zero.
+            x = (dpdk::tsc_cycles() ^ ((CoreId::current().raw() as u64) << 48)) | 1;
+        }
+
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    })
 }
 
 impl fmt::Display for MacAddr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
             self.0[0], self.0[1], self.0[2], self.0[3], self.0[4], self.0[5]
         )
     }
@@ -59,6 +123,20 @@ impl From<[u8; 6]> for MacAddr {
     }
 }
 
+impl From<ffi::rte_ether_addr> for MacAddr {
+    fn from(addr: ffi::rte_ether_addr) -> MacAddr {
+        MacAddr(addr.addr_bytes)
+    }
+}
+
+impl From<MacAddr> for ffi::rte_ether_addr {
+    fn from(mac: MacAddr) -> ffi::rte_ether_addr {
+        ffi::rte_ether_addr {
+            addr_bytes: mac.octets(),
+        }
+    }
+}
+
 /// Error returned when parsing a malformed MAC address.
 #[derive(Debug, Error)]
 #[error("Failed to parse '{0}' as MAC address.")]
@@ -67,20 +145,34 @@ pub struct MacParseError(String);
 impl FromStr for MacAddr {
     type Err = MacParseError;
 
+    /// Parses the address from either the colon or hyphen separated
+    /// format, or 12 hex digits without any separator.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let u8s = s
-            .split(|c| c == ':' || c == '-')
-            .map(|s| u8::from_str_radix(s, 16))
-            .filter_map(Result::ok)
-            .collect::<Vec<_>>();
-
-        if u8s.len() == 6 {
-            let mut octets = [0; 6];
-            octets.copy_from_slice(u8s.as_slice());
-            Ok(octets.into())
+        let err = || MacParseError(s.to_owned());
+
+        let parts = if s.contains(':') {
+            s.split(':').collect::<Vec<_>>()
+        } else if s.contains('-') {
+            s.split('-').collect::<Vec<_>>()
+        } else if s.len() == 12 && s.is_ascii() {
+            (0..6).map(|i| &s[i * 2..i * 2 + 2]).collect::<Vec<_>>()
         } else {
-            Err(MacParseError(s.to_owned()))
+            return Err(err());
+        };
+
+        if parts.len() != 6 {
+            return Err(err());
+        }
+
+        let mut octets = [0; 6];
+        for (octet, part) in octets.iter_mut().zip(parts) {
+            if part.is_empty() || part.len() > 2 {
+                return Err(err());
+            }
+            *octet = u8::from_str_radix(part, 16).map_err(|_| err())?;
         }
+
+        Ok(octets.into())
     }
 }
 
@@ -95,11 +187,11 @@ mod tests {
             MacAddr::new(0, 0, 0, 0, 0, 0).to_string()
         );
         assert_eq!(
-            "ff:ff:ff:ff:ff:ff",
+            "FF:FF:FF:FF:FF:FF",
             MacAddr::new(255, 255, 255, 255, 255, 255).to_string()
         );
         assert_eq!(
-            "12:34:56:ab:cd:ef",
+            "12:34:56:AB:CD:EF",
             MacAddr::new(0x12, 0x34, 0x56, 0xAB, 0xCD, 0xEF).to_string()
         );
     }
@@ -119,4 +211,49 @@ mod tests {
             "12:34:56:ab:cd:ef".parse().unwrap()
         );
     }
+
+    #[test]
+    fn string_to_mac_addr_formats() {
+        let mac = MacAddr::new(0x12, 0x34, 0x56, 0xAB, 0xCD, 0xEF);
+        assert_eq!(mac, "12-34-56-AB-CD-EF".parse().unwrap());
+        assert_eq!(mac, "123456abcdef".parse().unwrap());
+
+        assert!("12:34:56:ab:cd".parse::<MacAddr>().is_err());
+        assert!("12:34:56:ab:cd:ef:01".parse::<MacAddr>().is_err());
+        assert!("12:34:56-ab-cd-ef".parse::<MacAddr>().is_err());
+        assert!("12:34:56:ab:cd:zz".parse::<MacAddr>().is_err());
+        assert!("123456abcd".parse::<MacAddr>().is_err());
+    }
+
+    #[test]
+    fn mac_addr_ordering() {
+        assert!(MacAddr::new(0, 0, 0, 0, 0, 1) < MacAddr::new(0, 0, 0, 0, 1, 0));
+        assert!(MacAddr::UNSPECIFIED < MacAddr::broadcast());
+    }
+
+    #[test]
+    fn mac_addr_kinds() {
+        assert!(MacAddr::broadcast().is_broadcast());
+        assert!(MacAddr::broadcast().is_multicast());
+        assert!(MacAddr::new(0x01, 0x00, 0x5e, 0, 0, 1).is_multicast());
+        assert!(!MacAddr::new(0x00, 0x00, 0x5e, 0, 0, 1).is_multicast());
+        assert!(MacAddr::new(0x02, 0, 0, 0, 0, 1).is_locally_administered());
+        assert!(!MacAddr::UNSPECIFIED.is_locally_administered());
+    }
+
+    #[test]
+    fn rte_ether_addr_conversion() {
+        let mac = MacAddr::new(0x12, 0x34, 0x56, 0xAB, 0xCD, 0xEF);
+        let addr: ffi::rte_ether_addr = mac.into();
+        assert_eq!(mac.octets(), addr.addr_bytes);
+        assert_eq!(mac, addr.into());
+    }
+
+    #[capsule::test]
+    fn random_mac_addr() {
+        let mac = MacAddr::random();
+        assert!(mac.is_locally_administered());
+        assert!(!mac.is_multicast());
+        assert_ne!(mac, MacAddr::random());
+    }
 }
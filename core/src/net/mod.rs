@@ -18,8 +18,37 @@
 
 //! Common network utilities.
 
+mod arp;
 mod cidr;
+mod conntrack;
+mod dhcp;
+mod dpi;
+mod drr;
+mod load_balancer;
 mod mac;
+mod nat;
+mod ndp;
+mod retransmit;
+mod router;
+mod tcp;
+mod udp;
 
+pub use self::arp::ArpCache;
 pub use self::cidr::{Cidr, CidrError, Ipv4Cidr, Ipv6Cidr};
+pub use self::conntrack::{
+    ConnAction, ConnState, ConnTrackError, ConnTracker, FlowKey, TcpState, UdpState,
+};
+pub use self::dhcp::{DhcpError, DhcpLease, Dhcpv4Client};
+pub use self::dpi::{DpiContext, DpiError, DpiMatch};
+pub use self::drr::{DrrError, DrrScheduler};
+pub use self::load_balancer::{
+    BackendStats, LbError, LbPipeline, LbPipelineHandle, LbStrategy, LoadBalancer,
+    PowerOfTwoChoicesLb, RoundRobinLb,
+};
 pub use self::mac::{MacAddr, MacParseError};
+pub use self::nat::{NatError, NatTable};
+pub use self::ndp::{NdpHandler, NeighborCache};
+pub use self::retransmit::RetransmitQueue;
+pub use self::router::{ForwardDecision, RouteEntry, Router, RouterError};
+pub use self::tcp::{TcpError, TcpListener, TcpStack, TcpStream};
+pub use self::udp::{UdpSocket, UdpSocketError};
@@ -0,0 +1,346 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::dpdk;
+use crate::ensure;
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::{Flow, ProtocolNumber, ProtocolNumbers};
+use crate::packets::{Packet, Tcp4, Udp4};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::ops::Range;
+use thiserror::Error;
+
+/// Error indicating the table cannot be created or the packet cannot be
+/// translated.
+#[derive(Debug, Error)]
+pub enum NatError {
+    /// Error returned when all the ephemeral ports are in use.
+    #[error("No ephemeral port available.")]
+    PortsExhausted,
+
+    /// Error returned when an inbound packet does not match any mapping.
+    #[error("No mapping found for {0:?}.")]
+    NoMapping(Flow),
+
+    /// Error returned when the transport protocol is not TCP or UDP.
+    #[error("Protocol {0} is not supported.")]
+    UnsupportedProtocol(ProtocolNumber),
+
+    /// Error returned when the ephemeral port range is empty.
+    #[error("Port range {0:?} is empty.")]
+    EmptyPortRange(Range<u16>),
+}
+
+/// A translated flow.
+#[derive(Clone, Copy, Debug)]
+struct NatEntry {
+    external_port: u16,
+    last_seen: u64,
+}
+
+/// A source network address and port translation table for TCP and UDP
+/// flows.
+///
+/// Outbound packets from the private network have their source address
+/// rewritten to the external address and their source port rewritten to
+/// an ephemeral port. Inbound replies to the external address and port
+/// are translated back. Only replies from the original destination are
+/// accepted.
+///
+/// # Example
+///
+/// ```
+/// let mut nat = NatTable::new(Ipv4Addr::new(203, 0, 113, 1));
+/// let ipv4 = nat.translate_outbound(ipv4)?;
+/// ```
+#[derive(Debug)]
+pub struct NatTable {
+    external_ip: Ipv4Addr,
+    ports: Range<u16>,
+    next_port: u16,
+    outbound: HashMap<Flow, NatEntry>,
+    inbound: HashMap<(ProtocolNumber, u16), Flow>,
+}
+
+impl NatTable {
+    /// Creates a new table that translates to `external_ip` and the
+    /// ephemeral ports `49152..65535`.
+    pub fn new(external_ip: Ipv4Addr) -> Self {
+        NatTable::new_unchecked(external_ip, 49152..u16::max_value())
+    }
+
+    /// Creates a new table that translates to `external_ip` and a range of
+    /// ephemeral ports.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NatError::EmptyPortRange` if the range has no ports.
+    pub fn with_ports(external_ip: Ipv4Addr, ports: Range<u16>) -> Result<Self> {
+        ensure!(ports.start < ports.end, NatError::EmptyPortRange(ports));
+        Ok(NatTable::new_unchecked(external_ip, ports))
+    }
+
+    fn new_unchecked(external_ip: Ipv4Addr, ports: Range<u16>) -> Self {
+        NatTable {
+            external_ip,
+            next_port: ports.start,
+            ports,
+            outbound: HashMap::new(),
+            inbound: HashMap::new(),
+        }
+    }
+
+    /// Returns the number of translated flows.
+    pub fn len(&self) -> usize {
+        self.outbound.len()
+    }
+
+    /// Returns whether the table has no translated flows.
+    pub fn is_empty(&self) -> bool {
+        self.outbound.is_empty()
+    }
+
+    /// Allocates the next unused ephemeral port for the protocol.
+    fn alloc_port(&mut self, protocol: ProtocolNumber) -> Result<u16> {
+        let len = self.ports.end - self.ports.start;
+        for _ in 0..len {
+            let port = self.next_port;
+            self.next_port = if port + 1 >= self.ports.end {
+                self.ports.start
+            } else {
+                port + 1
+            };
+
+            if !self.inbound.contains_key(&(protocol, port)) {
+                return Ok(port);
+            }
+        }
+
+        Err(NatError::PortsExhausted.into())
+    }
+
+    /// Returns the external port for the outbound flow, allocating a new
+    /// one if the flow is not translated yet.
+    fn outbound_port(&mut self, flow: Flow) -> Result<u16> {
+        let now = dpdk::tsc_cycles();
+
+        if let Some(entry) = self.outbound.get_mut(&flow) {
+            entry.last_seen = now;
+            return Ok(entry.external_port);
+        }
+
+        let external_port = self.alloc_port(flow.protocol())?;
+        self.outbound.insert(
+            flow,
+            NatEntry {
+                external_port,
+                last_seen: now,
+            },
+        );
+        self.inbound.insert((flow.protocol(), external_port), flow);
+        Ok(external_port)
+    }
+
+    /// Returns the original flow for the inbound flow.
+    fn inbound_flow(&mut self, flow: Flow) -> Result<Flow> {
+        let original = self
+            .inbound
+            .get(&(flow.protocol(), flow.dst_port()))
+            .copied()
+            .filter(|original| {
+                original.dst_ip() == flow.src_ip() && original.dst_port() == flow.src_port()
+            })
+            .ok_or_else(|| NatError::NoMapping(flow))?;
+
+        if let Some(entry) = self.outbound.get_mut(&original) {
+            entry.last_seen = dpdk::tsc_cycles();
+        }
+
+        Ok(original)
+    }
+
+    /// Translates an outbound packet's source address and port. The
+    /// checksums are recomputed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NatError::UnsupportedProtocol` if the packet is not TCP or
+    /// UDP. Returns `NatError::PortsExhausted` if the flow is new and no
+    /// ephemeral port is available.
+    pub fn translate_outbound(&mut self, ipv4: Ipv4) -> Result<Ipv4> {
+        let src = IpAddr::V4(self.external_ip);
+
+        match ipv4.protocol() {
+            ProtocolNumbers::Tcp => {
+                let mut tcp = ipv4.parse::<Tcp4>()?;
+                let port = self.outbound_port(tcp.flow())?;
+                tcp.set_src_ip(src)?;
+                tcp.set_src_port(port);
+                tcp.reconcile_all();
+                Ok(tcp.deparse())
+            }
+            ProtocolNumbers::Udp => {
+                let mut udp = ipv4.parse::<Udp4>()?;
+                let port = self.outbound_port(udp.flow())?;
+                udp.set_src_ip(src)?;
+                udp.set_src_port(port);
+                udp.reconcile_all();
+                Ok(udp.deparse())
+            }
+            protocol => Err(NatError::UnsupportedProtocol(protocol).into()),
+        }
+    }
+
+    /// Translates an inbound packet's destination address and port back
+    /// to the original source of the flow. The checksums are recomputed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NatError::UnsupportedProtocol` if the packet is not TCP or
+    /// UDP. Returns `NatError::NoMapping` if the packet does not belong to
+    /// any translated flow.
+    pub fn translate_inbound(&mut self, ipv4: Ipv4) -> Result<Ipv4> {
+        match ipv4.protocol() {
+            ProtocolNumbers::Tcp => {
+                let mut tcp = ipv4.parse::<Tcp4>()?;
+                let original = self.inbound_flow(tcp.flow())?;
+                tcp.set_dst_ip(original.src_ip())?;
+                tcp.set_dst_port(original.src_port());
+                tcp.reconcile_all();
+                Ok(tcp.deparse())
+            }
+            ProtocolNumbers::Udp => {
+                let mut udp = ipv4.parse::<Udp4>()?;
+                let original = self.inbound_flow(udp.flow())?;
+                udp.set_dst_ip(original.src_ip())?;
+                udp.set_dst_port(original.src_port());
+                udp.reconcile_all();
+                Ok(udp.deparse())
+            }
+            protocol => Err(NatError::UnsupportedProtocol(protocol).into()),
+        }
+    }
+
+    /// Removes the flows not seen for more than `timeout_cycles`. Returns
+    /// the number of flows removed.
+    pub fn evict_stale(&mut self, timeout_cycles: u64) -> usize {
+        let now = dpdk::tsc_cycles();
+        let inbound = &mut self.inbound;
+        let before = self.outbound.len();
+
+        self.outbound.retain(|flow, entry| {
+            let stale = now.saturating_sub(entry.last_seen) > timeout_cycles;
+            if stale {
+                inbound.remove(&(flow.protocol(), entry.external_port));
+            }
+            !stale
+        });
+
+        before - self.outbound.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::Ethernet;
+    use crate::testils::byte_arrays::{IPV4_TCP_PACKET, IPV4_UDP_PACKET};
+    use crate::Mbuf;
+
+    const EXTERNAL_IP: Ipv4Addr = Ipv4Addr::new(203, 0, 113, 1);
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn reject_empty_port_range() {
+        assert!(NatTable::with_ports(EXTERNAL_IP, 1000..1000).is_err());
+        assert!(NatTable::with_ports(EXTERNAL_IP, 2000..1000).is_err());
+        assert!(NatTable::with_ports(EXTERNAL_IP, 1000..1001).is_ok());
+    }
+
+    #[capsule::test]
+    fn translate_tcp_flow() {
+        let mut nat = NatTable::new(EXTERNAL_IP);
+
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let ipv4 = packet.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap();
+        let original = ipv4.peek::<Tcp4>().unwrap().flow();
+
+        let ipv4 = nat.translate_outbound(ipv4).unwrap();
+        let mut tcp = ipv4.parse::<Tcp4>().unwrap();
+        assert_eq!(EXTERNAL_IP, tcp.envelope().src());
+        assert_eq!(49152, tcp.src_port());
+        assert!(tcp.validate_checksum());
+        assert_eq!(1, nat.len());
+
+        // turns the packet into a reply from the remote end.
+        let src_port = tcp.src_port();
+        let dst_port = tcp.dst_port();
+        tcp.set_src_port(dst_port);
+        tcp.set_dst_port(src_port);
+        let src = tcp.envelope().src();
+        let dst = tcp.envelope().dst();
+        tcp.envelope_mut().set_src(dst);
+        tcp.envelope_mut().set_dst(src);
+
+        let ipv4 = nat.translate_inbound(tcp.deparse()).unwrap();
+        let tcp = ipv4.parse::<Tcp4>().unwrap();
+        assert_eq!(original.src_ip(), IpAddr::V4(tcp.envelope().dst()));
+        assert_eq!(original.src_port(), tcp.dst_port());
+        assert!(tcp.validate_checksum());
+    }
+
+    #[capsule::test]
+    fn reuse_mapping_for_same_flow() {
+        let mut nat = NatTable::new(EXTERNAL_IP);
+
+        for _ in 0..2 {
+            let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+            let ipv4 = packet.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap();
+            let ipv4 = nat.translate_outbound(ipv4).unwrap();
+            let udp = ipv4.parse::<Udp4>().unwrap();
+            assert_eq!(49152, udp.src_port());
+        }
+
+        assert_eq!(1, nat.len());
+    }
+
+    #[capsule::test]
+    fn reject_unknown_inbound_flow() {
+        let mut nat = NatTable::new(EXTERNAL_IP);
+
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ipv4 = packet.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap();
+        assert!(nat.translate_inbound(ipv4).is_err());
+    }
+
+    #[capsule::test]
+    fn evict_stale_flows() {
+        let mut nat = NatTable::new(EXTERNAL_IP);
+
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ipv4 = packet.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap();
+        let _ = nat.translate_outbound(ipv4).unwrap();
+
+        assert_eq!(0, nat.evict_stale(u64::max_value()));
+        assert_eq!(1, nat.evict_stale(0));
+        assert!(nat.is_empty());
+        assert!(nat.inbound.is_empty());
+    }
+}
@@ -0,0 +1,358 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use crate::dpdk::{self, PortQueue};
+use crate::ffi;
+use crate::net::MacAddr;
+use crate::packets::icmp::v6::ndp::{
+    LinkLayerAddress, NdpOptionTypes, NdpPacket, NeighborAdvertisement, NeighborSolicitation,
+};
+use crate::packets::ip::v6::Ipv6;
+use crate::packets::{Ethernet, Packet};
+use crate::Mbuf;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
+use std::time::Duration;
+
+/// The hop limit of NDP messages. Messages received with any other hop
+/// limit did not originate on the link and are discarded.
+const NDP_HOP_LIMIT: u8 = 255;
+
+/// Returns the solicited-node multicast address of the address, as defined
+/// in RFC 4291.
+fn solicited_node(ip: Ipv6Addr) -> Ipv6Addr {
+    let o = ip.octets();
+    Ipv6Addr::new(
+        0xff02,
+        0,
+        0,
+        0,
+        0,
+        1,
+        0xff00 | o[13] as u16,
+        (o[14] as u16) << 8 | o[15] as u16,
+    )
+}
+
+/// Returns the MAC address an IPv6 multicast address maps to, as defined
+/// in RFC 2464.
+fn multicast_mac(ip: Ipv6Addr) -> MacAddr {
+    let o = ip.octets();
+    MacAddr::new(0x33, 0x33, o[12], o[13], o[14], o[15])
+}
+
+/// A cache of resolved IPv6 neighbors.
+///
+/// Each entry expires at a TSC cycle count. Expired entries are not
+/// returned by lookups, and are removed with `evict_stale`.
+#[derive(Debug, Default)]
+pub struct NeighborCache {
+    entries: HashMap<Ipv6Addr, (MacAddr, u64)>,
+}
+
+impl NeighborCache {
+    /// Creates a new empty cache.
+    pub fn new() -> Self {
+        NeighborCache::default()
+    }
+
+    /// Returns the number of entries, including the expired ones not
+    /// evicted yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the cache has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds or refreshes a neighbor.
+    pub fn insert(&mut self, ip: Ipv6Addr, mac: MacAddr, expires_at_cycles: u64) {
+        self.entries.insert(ip, (mac, expires_at_cycles));
+    }
+
+    /// Returns the MAC address of the neighbor, if it has not expired at
+    /// `now_cycles`.
+    pub fn get(&self, ip: Ipv6Addr, now_cycles: u64) -> Option<MacAddr> {
+        self.entries
+            .get(&ip)
+            .filter(|(_, expires_at)| *expires_at > now_cycles)
+            .map(|(mac, _)| *mac)
+    }
+
+    /// Removes a neighbor.
+    pub fn remove(&mut self, ip: Ipv6Addr) -> Option<MacAddr> {
+        self.entries.remove(&ip).map(|(mac, _)| mac)
+    }
+
+    /// Removes the neighbors expired at `now_cycles`. Returns the number of
+    /// neighbors removed.
+    pub fn evict_stale(&mut self, now_cycles: u64) -> usize {
+        let before = self.entries.len();
+        self.entries
+            .retain(|_, (_, expires_at)| *expires_at > now_cycles);
+        before - self.entries.len()
+    }
+}
+
+/// Resolves IPv6 neighbors with the Neighbor Discovery Protocol, as
+/// defined in RFC 4861.
+///
+/// The handler sends neighbor solicitations, and learns the link-layer
+/// addresses from the neighbor advertisements received. A learned neighbor
+/// is considered reachable for the configured reachable time. There's no
+/// retransmission of unanswered solicitations, nor neighbor unreachability
+/// detection.
+///
+/// # Example
+///
+/// ```
+/// let mut ndp = NdpHandler::new(Duration::from_secs(30));
+/// ndp.send_neighbor_solicitation(local_ip, q.mac_addr(), gateway, &q)?;
+///
+/// // when an advertisement is received.
+/// ndp.process_neighbor_advertisement(ipv6);
+///
+/// if let Some(mac) = ndp.resolve(gateway) {
+///     ethernet.set_dst(mac);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct NdpHandler {
+    cache: NeighborCache,
+    reachable_cycles: u64,
+}
+
+impl NdpHandler {
+    /// Creates a new handler. Neighbors are reachable for `reachable_time`
+    /// after their advertisement is received.
+    pub fn new(reachable_time: Duration) -> Self {
+        let hz = unsafe { ffi::rte_get_tsc_hz() };
+        NdpHandler {
+            cache: NeighborCache::new(),
+            reachable_cycles: (reachable_time.as_secs_f64() * hz as f64) as u64,
+        }
+    }
+
+    /// Returns the neighbor cache.
+    pub fn cache(&self) -> &NeighborCache {
+        &self.cache
+    }
+
+    /// Returns a mutable reference to the neighbor cache.
+    pub fn cache_mut(&mut self) -> &mut NeighborCache {
+        &mut self.cache
+    }
+
+    /// Sends a neighbor solicitation for the target to its solicited-node
+    /// multicast address.
+    ///
+    /// # Errors
+    ///
+    /// If the packet cannot be allocated, `DpdkError` is returned.
+    pub fn send_neighbor_solicitation(
+        &self,
+        src_ip: Ipv6Addr,
+        src_mac: MacAddr,
+        target_ip: Ipv6Addr,
+        queue: &PortQueue,
+    ) -> Result<()> {
+        let packet = build_neighbor_solicitation(src_ip, src_mac, target_ip)?;
+        queue.transmit(vec![packet]);
+        Ok(())
+    }
+
+    /// Learns the neighbor from the advertisement. Returns the target
+    /// address and its link-layer address, or `None` if the packet is not
+    /// a valid advertisement with a target link-layer address option.
+    pub fn process_neighbor_advertisement(&mut self, ipv6: Ipv6) -> Option<(Ipv6Addr, MacAddr)> {
+        if ipv6.hop_limit() != NDP_HOP_LIMIT {
+            return None;
+        }
+
+        let advert = ipv6.parse::<NeighborAdvertisement<Ipv6>>().ok()?;
+        let target = advert.target();
+        let mac = target_link_layer_addr(&advert)?;
+
+        let expires_at = dpdk::tsc_cycles() + self.reachable_cycles;
+        self.cache.insert(target, mac, expires_at);
+        Some((target, mac))
+    }
+
+    /// Returns the MAC address of the neighbor, if it's in the cache and
+    /// still reachable.
+    pub fn resolve(&self, ip: Ipv6Addr) -> Option<MacAddr> {
+        self.cache.get(ip, dpdk::tsc_cycles())
+    }
+
+    /// Removes the neighbors no longer reachable at `now_cycles`. Returns
+    /// the number of neighbors removed.
+    pub fn evict_stale(&mut self, now_cycles: u64) -> usize {
+        self.cache.evict_stale(now_cycles)
+    }
+}
+
+/// Builds a neighbor solicitation with the source link-layer address
+/// option.
+fn build_neighbor_solicitation(
+    src_ip: Ipv6Addr,
+    src_mac: MacAddr,
+    target_ip: Ipv6Addr,
+) -> Result<Mbuf> {
+    let dst_ip = solicited_node(target_ip);
+
+    let mut ethernet = Mbuf::new()?.push::<Ethernet>()?;
+    ethernet.set_src(src_mac);
+    ethernet.set_dst(multicast_mac(dst_ip));
+
+    let mut ipv6 = ethernet.push::<Ipv6>()?;
+    ipv6.set_src(src_ip);
+    ipv6.set_dst(dst_ip);
+    ipv6.set_hop_limit(NDP_HOP_LIMIT);
+
+    let mut solicit = ipv6.push::<NeighborSolicitation<Ipv6>>()?;
+    solicit.set_target(target_ip);
+
+    let mut options = solicit.options_mut();
+    let mut lla = options.append::<LinkLayerAddress<'_>>()?;
+    lla.set_option_type_source();
+    lla.set_addr(src_mac);
+
+    solicit.reconcile_all();
+    Ok(solicit.reset())
+}
+
+/// Returns the address in the target link-layer address option.
+fn target_link_layer_addr(advert: &NeighborAdvertisement<Ipv6>) -> Option<MacAddr> {
+    let mut iter = advert.options_iter();
+    while let Ok(Some(mut option)) = iter.next() {
+        if option.option_type() == NdpOptionTypes::TargetLinkLayerAddress {
+            return option
+                .downcast::<LinkLayerAddress<'_>>()
+                .ok()
+                .map(|lla| lla.addr());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_ip() -> Ipv6Addr {
+        "fe80::1".parse().unwrap()
+    }
+
+    fn target_ip() -> Ipv6Addr {
+        "fe80::2:ab:cdef".parse().unwrap()
+    }
+
+    fn target_mac() -> MacAddr {
+        MacAddr::new(0x02, 0, 0, 0, 0, 2)
+    }
+
+    fn new_advertisement(hop_limit: u8) -> Ipv6 {
+        let ethernet = Mbuf::new().unwrap().push::<Ethernet>().unwrap();
+        let mut ipv6 = ethernet.push::<Ipv6>().unwrap();
+        ipv6.set_src(target_ip());
+        ipv6.set_dst(local_ip());
+        ipv6.set_hop_limit(hop_limit);
+
+        let mut advert = ipv6.push::<NeighborAdvertisement<Ipv6>>().unwrap();
+        advert.set_solicited();
+        advert.set_target(target_ip());
+        let mut options = advert.options_mut();
+        let mut lla = options.append::<LinkLayerAddress<'_>>().unwrap();
+        lla.set_option_type_target();
+        lla.set_addr(target_mac());
+        advert.reconcile_all();
+        advert.deparse()
+    }
+
+    #[test]
+    fn solicited_node_address() {
+        assert_eq!(
+            "ff02::1:ffab:cdef".parse::<Ipv6Addr>().unwrap(),
+            solicited_node(target_ip())
+        );
+        assert_eq!(
+            MacAddr::new(0x33, 0x33, 0xff, 0xab, 0xcd, 0xef),
+            multicast_mac(solicited_node(target_ip()))
+        );
+    }
+
+    #[test]
+    fn evict_stale_neighbors() {
+        let mut cache = NeighborCache::new();
+        cache.insert(local_ip(), target_mac(), 100);
+        cache.insert(target_ip(), target_mac(), 200);
+
+        assert_eq!(Some(target_mac()), cache.get(local_ip(), 50));
+        assert_eq!(None, cache.get(local_ip(), 150));
+        assert_eq!(1, cache.evict_stale(150));
+        assert_eq!(1, cache.len());
+        assert_eq!(Some(target_mac()), cache.get(target_ip(), 150));
+    }
+
+    #[capsule::test]
+    fn build_solicitation() {
+        let src_mac = MacAddr::new(0x02, 0, 0, 0, 0, 1);
+        let packet = build_neighbor_solicitation(local_ip(), src_mac, target_ip()).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        assert_eq!(
+            MacAddr::new(0x33, 0x33, 0xff, 0xab, 0xcd, 0xef),
+            ethernet.dst()
+        );
+
+        let ipv6 = ethernet.parse::<Ipv6>().unwrap();
+        assert_eq!(NDP_HOP_LIMIT, ipv6.hop_limit());
+        assert_eq!(solicited_node(target_ip()), ipv6.dst());
+
+        let solicit = ipv6.parse::<NeighborSolicitation<Ipv6>>().unwrap();
+        assert_eq!(target_ip(), solicit.target());
+        let mut iter = solicit.options_iter();
+        let option = iter.next().unwrap().unwrap();
+        assert_eq!(NdpOptionTypes::SourceLinkLayerAddress, option.option_type());
+    }
+
+    #[capsule::test]
+    fn learn_from_advertisement() {
+        let mut ndp = NdpHandler::new(Duration::from_secs(30));
+        assert_eq!(None, ndp.resolve(target_ip()));
+
+        assert_eq!(
+            Some((target_ip(), target_mac())),
+            ndp.process_neighbor_advertisement(new_advertisement(255))
+        );
+        assert_eq!(Some(target_mac()), ndp.resolve(target_ip()));
+        assert_eq!(0, ndp.evict_stale(dpdk::tsc_cycles()));
+        assert_eq!(1, ndp.evict_stale(u64::MAX));
+    }
+
+    #[capsule::test]
+    fn ignore_off_link_advertisement() {
+        let mut ndp = NdpHandler::new(Duration::from_secs(30));
+        assert_eq!(
+            None,
+            ndp.process_neighbor_advertisement(new_advertisement(64))
+        );
+        assert!(ndp.cache().is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use super::tcp::seq_lt;
+use crate::dpdk;
+use std::collections::VecDeque;
+
+/// A segment sent but not acknowledged yet.
+#[derive(Debug)]
+struct Pending {
+    seq: u32,
+    data: Vec<u8>,
+    sent_at: u64,
+    retransmitted: bool,
+}
+
+impl Pending {
+    /// Returns the sequence number following the segment.
+    fn end(&self) -> u32 {
+        self.seq.wrapping_add(self.data.len() as u32)
+    }
+}
+
+/// A queue of sent segments waiting for acknowledgement.
+///
+/// The queue keeps the segments in sequence order, frees them as they
+/// are cumulatively acknowledged, and hands back the ones whose
+/// retransmission timeout expired. It also estimates the round-trip time
+/// following RFC 6298. Per Karn's algorithm, a segment that was
+/// retransmitted never contributes an RTT sample, since its ack can't be
+/// matched to a particular transmission.
+///
+/// Time is measured in TSC cycles. The queue doesn't run any timers, the
+/// caller polls `retransmit_expired` and is responsible for backing off
+/// the timeout on repeated retransmissions.
+///
+/// # Example
+///
+/// ```
+/// let mut queue = RetransmitQueue::new();
+/// queue.push(snd_nxt, data.clone(), dpdk::tsc_cycles());
+///
+/// // on an incoming segment
+/// queue.acknowledge(tcp.ack_no());
+///
+/// // on every poll
+/// for (seq, data) in queue.retransmit_expired(dpdk::tsc_cycles(), rto) {
+///     send_segment(seq, &data);
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct RetransmitQueue {
+    segments: VecDeque<Pending>,
+    srtt: Option<u64>,
+    rttvar: u64,
+}
+
+impl RetransmitQueue {
+    /// Creates a new empty queue.
+    pub fn new() -> Self {
+        RetransmitQueue::default()
+    }
+
+    /// Returns the number of unacknowledged segments.
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Returns whether all the segments are acknowledged.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Adds a segment sent at `sent_at_cycles` to the queue.
+    ///
+    /// Segments are expected to be pushed in sequence order.
+    pub fn push(&mut self, seq: u32, data: Vec<u8>, sent_at_cycles: u64) {
+        self.segments.push_back(Pending {
+            seq,
+            data,
+            sent_at: sent_at_cycles,
+            retransmitted: false,
+        });
+    }
+
+    /// Frees the segments covered by the cumulative `ack_num`, and
+    /// returns how many were freed.
+    ///
+    /// A segment only partially covered by the ack stays in the queue.
+    pub fn acknowledge(&mut self, ack_num: u32) -> usize {
+        self.acknowledge_at(ack_num, dpdk::tsc_cycles())
+    }
+
+    /// Same as `acknowledge`, with the ack received at `now_cycles`.
+    pub fn acknowledge_at(&mut self, ack_num: u32, now_cycles: u64) -> usize {
+        let mut freed = 0;
+        let mut sample = None;
+
+        while let Some(segment) = self.segments.front() {
+            if seq_lt(ack_num, segment.end()) {
+                break;
+            }
+            // Karn's algorithm, an ack covering a retransmitted segment
+            // is ambiguous, so no sample is taken.
+            sample = if segment.retransmitted {
+                None
+            } else {
+                Some(now_cycles.saturating_sub(segment.sent_at))
+            };
+            self.segments.pop_front();
+            freed += 1;
+        }
+
+        if let Some(rtt) = sample {
+            self.update_rtt(rtt);
+        }
+
+        freed
+    }
+
+    /// Updates the smoothed RTT and its variation with a new sample.
+    fn update_rtt(&mut self, rtt: u64) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            Some(srtt) => {
+                let delta = if srtt > rtt { srtt - rtt } else { rtt - srtt };
+                // beta = 1/4, alpha = 1/8.
+                self.rttvar = self.rttvar - self.rttvar / 4 + delta / 4;
+                self.srtt = Some(srtt - srtt / 8 + rtt / 8);
+            }
+        }
+    }
+
+    /// Returns the segments sent more than `rto_cycles` before
+    /// `now_cycles`.
+    ///
+    /// The returned segments are marked as retransmitted and their send
+    /// time is reset to `now_cycles`, so they expire again after another
+    /// `rto_cycles` if still unacknowledged.
+    pub fn retransmit_expired(&mut self, now_cycles: u64, rto_cycles: u64) -> Vec<(u32, Vec<u8>)> {
+        self.segments
+            .iter_mut()
+            .filter(|segment| now_cycles.saturating_sub(segment.sent_at) >= rto_cycles)
+            .map(|segment| {
+                segment.retransmitted = true;
+                segment.sent_at = now_cycles;
+                (segment.seq, segment.data.clone())
+            })
+            .collect()
+    }
+
+    /// Returns the smoothed round-trip time in cycles, or 0 if no sample
+    /// was taken yet.
+    pub fn rtt_estimate(&self) -> u64 {
+        self.srtt.unwrap_or(0)
+    }
+
+    /// Returns the retransmission timeout in cycles computed from the RTT
+    /// estimate, or `None` if no sample was taken yet.
+    ///
+    /// The minimum of 1 second recommended by RFC 6298 is left to the
+    /// caller.
+    pub fn rto_estimate(&self) -> Option<u64> {
+        self.srtt.map(|srtt| srtt + (4 * self.rttvar).max(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acknowledge_frees_covered_segments() {
+        let mut queue = RetransmitQueue::new();
+        queue.push(1000, vec![0; 100], 0);
+        queue.push(1100, vec![0; 100], 0);
+        queue.push(1200, vec![0; 100], 0);
+
+        // partially acks the second segment.
+        assert_eq!(1, queue.acknowledge_at(1150, 10));
+        assert_eq!(2, queue.len());
+
+        // duplicate ack.
+        assert_eq!(0, queue.acknowledge_at(1150, 20));
+
+        assert_eq!(2, queue.acknowledge_at(1300, 30));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn sequence_wraparound() {
+        let mut queue = RetransmitQueue::new();
+        queue.push(u32::MAX - 49, vec![0; 100], 0);
+
+        assert_eq!(0, queue.acknowledge_at(u32::MAX, 10));
+        assert_eq!(1, queue.acknowledge_at(50, 10));
+    }
+
+    #[test]
+    fn retransmit_expired_segments() {
+        let mut queue = RetransmitQueue::new();
+        queue.push(1, vec![1; 10], 0);
+        queue.push(11, vec![2; 10], 500);
+
+        let expired = queue.retransmit_expired(1000, 1000);
+        assert_eq!(vec![(1, vec![1; 10])], expired);
+
+        // the resent segment restarts its timeout.
+        let expired = queue.retransmit_expired(1500, 1000);
+        assert_eq!(vec![(11, vec![2; 10])], expired);
+        assert!(queue.retransmit_expired(1900, 1000).is_empty());
+    }
+
+    #[test]
+    fn karn_skips_retransmitted_samples() {
+        let mut queue = RetransmitQueue::new();
+        queue.push(1, vec![0; 10], 0);
+        let _ = queue.retransmit_expired(1000, 1000);
+        queue.acknowledge_at(11, 1100);
+        assert_eq!(0, queue.rtt_estimate());
+        assert_eq!(None, queue.rto_estimate());
+
+        queue.push(11, vec![0; 10], 2000);
+        queue.acknowledge_at(21, 2800);
+        assert_eq!(800, queue.rtt_estimate());
+        assert_eq!(Some(800 + 4 * 400), queue.rto_estimate());
+    }
+
+    #[test]
+    fn rtt_ewma() {
+        let mut queue = RetransmitQueue::new();
+        queue.push(1, vec![0; 10], 0);
+        queue.acknowledge_at(11, 800);
+
+        queue.push(11, vec![0; 10], 1000);
+        queue.acknowledge_at(21, 2600);
+
+        // srtt = 7/8 * 800 + 1/8 * 1600, rttvar = 3/4 * 400 + 1/4 * 800.
+        assert_eq!(900, queue.rtt_estimate());
+        assert_eq!(Some(900 + 4 * 500), queue.rto_estimate());
+    }
+}
@@ -0,0 +1,368 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use crate::dpdk::{self, LpmTable, SocketId};
+use crate::net::{Ipv4Cidr, MacAddr};
+use crate::packets::icmp::v4::TimeExceeded;
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::{Ethernet, Packet};
+use crate::Mbuf;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use thiserror::Error;
+
+/// The number of bytes of the original datagram quoted after its IP
+/// header in an ICMP error, as defined in RFC 792.
+const ICMP_QUOTE_LEN: usize = 8;
+
+/// The TTL of the ICMP errors the router sends.
+const ICMP_TTL: u8 = 64;
+
+/// Error indicating the route cannot be changed.
+#[derive(Debug, Error)]
+pub enum RouterError {
+    /// Error returned when the route to delete does not exist.
+    #[error("No route to {0:?}.")]
+    NoRoute(Ipv4Cidr),
+}
+
+/// Where packets matching a route are sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct NextHop {
+    out_port: u16,
+    gateway: Option<Ipv4Addr>,
+    src_mac: MacAddr,
+}
+
+/// The result of a route lookup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RouteEntry {
+    /// The port the packet is sent out of.
+    pub out_port: u16,
+    /// The gateway the packet is sent to, or `None` if the destination is
+    /// directly connected.
+    pub gateway: Option<Ipv4Addr>,
+    /// The MAC address of the next hop, or `None` if it's not resolved.
+    pub next_hop_mac: Option<MacAddr>,
+}
+
+/// What to do with a packet after routing.
+#[derive(Debug)]
+pub enum ForwardDecision {
+    /// Sends the packet out of the port.
+    Send(Ethernet, u16),
+    /// Sends the ICMP error generated for the packet back out of the port
+    /// it was received on. The original packet is dropped.
+    Reply(Ethernet, u16),
+    /// Drops the packet, because there's no route to the destination or
+    /// the next hop is not resolved.
+    Drop,
+}
+
+/// An IPv4 router that forwards packets with an LPM table.
+///
+/// Routes point to an output port and an optional gateway. The MAC
+/// addresses of the gateways and of the directly connected hosts are
+/// looked up in a neighbor table, which is populated with `add_neighbor`,
+/// for example from ARP replies. Packets to unresolved next hops are
+/// dropped.
+///
+/// # Example
+///
+/// ```
+/// let mut router = Router::new("router0", 1024, SocketId::current(), local_ip)?;
+/// router.add_route("0.0.0.0/0".parse()?, Some(gateway), 0)?;
+/// router.add_neighbor(gateway, gateway_mac);
+///
+/// match router.resolve_and_forward(ipv4) {
+///     ForwardDecision::Send(ethernet, port_id) => ...,
+///     ForwardDecision::Reply(ethernet, port_id) => ...,
+///     ForwardDecision::Drop => ...,
+/// }
+/// ```
+pub struct Router {
+    lpm: LpmTable,
+    local_ip: Ipv4Addr,
+    // the LPM table stores indices into the next hops.
+    next_hops: Vec<NextHop>,
+    routes: HashMap<Ipv4Cidr, u32>,
+    neighbors: HashMap<Ipv4Addr, MacAddr>,
+}
+
+impl Router {
+    /// Creates a new router that holds up to `max_routes` routes. ICMP
+    /// errors are sent from `local_ip`.
+    ///
+    /// # Errors
+    ///
+    /// If the LPM table cannot be created, `DpdkError` is returned.
+    pub fn new(
+        name: &str,
+        max_routes: u32,
+        socket_id: SocketId,
+        local_ip: Ipv4Addr,
+    ) -> Result<Self> {
+        Ok(Router {
+            lpm: LpmTable::new(name, max_routes, socket_id)?,
+            local_ip,
+            next_hops: vec![],
+            routes: HashMap::new(),
+            neighbors: HashMap::new(),
+        })
+    }
+
+    /// Adds a route. If there's already a route for the prefix, it's
+    /// replaced.
+    ///
+    /// # Errors
+    ///
+    /// If the LPM table is full, `DpdkError` is returned.
+    pub fn add_route(
+        &mut self,
+        prefix: Ipv4Cidr,
+        gateway: Option<Ipv4Addr>,
+        out_port: u16,
+    ) -> Result<()> {
+        let next_hop = NextHop {
+            out_port,
+            gateway,
+            src_mac: dpdk::eth_macaddr_get(out_port),
+        };
+
+        // routes through the same gateway share the next hop.
+        let index = match self.next_hops.iter().position(|nh| *nh == next_hop) {
+            Some(index) => index,
+            None => {
+                self.next_hops.push(next_hop);
+                self.next_hops.len() - 1
+            }
+        } as u32;
+
+        self.lpm.add_route(prefix, index)?;
+        self.routes.insert(prefix, index);
+        Ok(())
+    }
+
+    /// Deletes a route.
+    ///
+    /// # Errors
+    ///
+    /// Returns `RouterError::NoRoute` if there's no route for the prefix.
+    pub fn delete_route(&mut self, prefix: Ipv4Cidr) -> Result<()> {
+        if self.routes.remove(&prefix).is_none() {
+            return Err(RouterError::NoRoute(prefix).into());
+        }
+        self.lpm.delete_route(prefix)
+    }
+
+    /// Returns the number of routes.
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    /// Returns whether the router has no routes.
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Sets the MAC address of a neighbor.
+    pub fn add_neighbor(&mut self, ip: Ipv4Addr, mac: MacAddr) {
+        self.neighbors.insert(ip, mac);
+    }
+
+    /// Removes a neighbor. Returns its MAC address if it was known.
+    pub fn remove_neighbor(&mut self, ip: Ipv4Addr) -> Option<MacAddr> {
+        self.neighbors.remove(&ip)
+    }
+
+    /// Returns the route of the longest prefix matching the destination.
+    pub fn route_lookup(&self, dst: Ipv4Addr) -> Option<RouteEntry> {
+        self.lookup_next_hop(dst).map(|next_hop| RouteEntry {
+            out_port: next_hop.out_port,
+            gateway: next_hop.gateway,
+            next_hop_mac: self
+                .neighbors
+                .get(&next_hop.gateway.unwrap_or(dst))
+                .copied(),
+        })
+    }
+
+    fn lookup_next_hop(&self, dst: Ipv4Addr) -> Option<&NextHop> {
+        self.lpm
+            .lookup(dst)
+            .and_then(|index| self.next_hops.get(index as usize))
+    }
+
+    /// Routes the packet.
+    ///
+    /// The TTL is decremented, and the Ethernet addresses are rewritten for
+    /// the next hop. If the TTL expires, an ICMP time exceeded error is
+    /// returned for the sender instead.
+    pub fn resolve_and_forward(&self, mut ipv4: Ipv4) -> ForwardDecision {
+        let dst = ipv4.dst();
+        let next_hop = match self.lookup_next_hop(dst) {
+            Some(next_hop) => *next_hop,
+            None => return ForwardDecision::Drop,
+        };
+
+        if ipv4.ttl() <= 1 {
+            let in_port = ipv4.mbuf().meta().port();
+            return match self.time_exceeded(&ipv4) {
+                Ok(reply) => ForwardDecision::Reply(reply, in_port),
+                Err(_) => ForwardDecision::Drop,
+            };
+        }
+
+        let dst_mac = match self.neighbors.get(&next_hop.gateway.unwrap_or(dst)) {
+            Some(mac) => *mac,
+            None => return ForwardDecision::Drop,
+        };
+
+        ipv4.set_ttl(ipv4.ttl() - 1);
+        ipv4.reconcile();
+
+        let mut ethernet = ipv4.deparse();
+        ethernet.set_src(next_hop.src_mac);
+        ethernet.set_dst(dst_mac);
+        ForwardDecision::Send(ethernet, next_hop.out_port)
+    }
+
+    /// Creates an ICMP time exceeded error for the packet, quoting its IP
+    /// header and the first 8 bytes of its payload.
+    fn time_exceeded(&self, ipv4: &Ipv4) -> Result<Ethernet> {
+        let quote_len = (ipv4.header_len() + ICMP_QUOTE_LEN).min(ipv4.len());
+        let quote = ipv4
+            .mbuf()
+            .read_data_slice::<u8>(ipv4.offset(), quote_len)?;
+        let mbuf = Mbuf::from_bytes(unsafe { quote.as_ref() })?;
+
+        let mut ethernet = mbuf.push::<Ethernet>()?;
+        ethernet.set_src(ipv4.envelope().dst());
+        ethernet.set_dst(ipv4.envelope().src());
+
+        let mut reply = ethernet.push::<Ipv4>()?;
+        reply.set_src(self.local_ip);
+        reply.set_dst(ipv4.src());
+        reply.set_ttl(ICMP_TTL);
+
+        let mut exceeded = reply.push::<TimeExceeded>()?;
+        exceeded.reconcile_all();
+        Ok(exceeded.deparse().deparse())
+    }
+}
+
+impl std::fmt::Debug for Router {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Router")
+            .field("lpm", &self.lpm)
+            .field("local_ip", &self.local_ip)
+            .field("routes", &self.routes.len())
+            .field("neighbors", &self.neighbors.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::Cidr;
+    use crate::packets::icmp::v4::{Icmpv4, Icmpv4Types};
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+
+    fn new_router() -> Router {
+        Router::new("router0", 16, SocketId::ANY, Ipv4Addr::new(10, 0, 0, 1)).unwrap()
+    }
+
+    fn new_packet() -> Ipv4 {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        packet.parse::<Ethernet>().unwrap().parse::<Ipv4>().unwrap()
+    }
+
+    #[capsule::test]
+    fn add_and_delete_route() {
+        let mut router = new_router();
+        let gateway = Ipv4Addr::new(10, 0, 0, 254);
+        router
+            .add_route("0.0.0.0/0".parse().unwrap(), Some(gateway), 0)
+            .unwrap();
+        assert_eq!(1, router.len());
+
+        let entry = router.route_lookup(Ipv4Addr::new(8, 8, 8, 8)).unwrap();
+        assert_eq!(Some(gateway), entry.gateway);
+        assert_eq!(None, entry.next_hop_mac);
+
+        let mac = MacAddr::new(0x02, 0, 0, 0, 0, 1);
+        router.add_neighbor(gateway, mac);
+        let entry = router.route_lookup(Ipv4Addr::new(8, 8, 8, 8)).unwrap();
+        assert_eq!(Some(mac), entry.next_hop_mac);
+
+        router.delete_route("0.0.0.0/0".parse().unwrap()).unwrap();
+        assert!(router.route_lookup(Ipv4Addr::new(8, 8, 8, 8)).is_none());
+        assert!(router.delete_route("0.0.0.0/0".parse().unwrap()).is_err());
+    }
+
+    #[capsule::test]
+    fn forward_decrements_ttl() {
+        let mut router = new_router();
+        let ipv4 = new_packet();
+        let dst = ipv4.dst();
+        let ttl = ipv4.ttl();
+        let mac = MacAddr::new(0x02, 0, 0, 0, 0, 1);
+
+        router
+            .add_route(Ipv4Cidr::new(dst, 32).unwrap(), None, 1)
+            .unwrap();
+        assert!(matches!(
+            router.resolve_and_forward(new_packet()),
+            ForwardDecision::Drop
+        ));
+
+        router.add_neighbor(dst, mac);
+        match router.resolve_and_forward(ipv4) {
+            ForwardDecision::Send(ethernet, port_id) => {
+                assert_eq!(1, port_id);
+                assert_eq!(mac, ethernet.dst());
+                let ipv4 = ethernet.parse::<Ipv4>().unwrap();
+                assert_eq!(ttl - 1, ipv4.ttl());
+            }
+            _ => panic!("packet not forwarded."),
+        }
+    }
+
+    #[capsule::test]
+    fn expired_ttl_replies_time_exceeded() {
+        let mut router = new_router();
+        let mut ipv4 = new_packet();
+        let src = ipv4.src();
+        ipv4.set_ttl(1);
+
+        router
+            .add_route("0.0.0.0/0".parse().unwrap(), None, 0)
+            .unwrap();
+        match router.resolve_and_forward(ipv4) {
+            ForwardDecision::Reply(ethernet, _) => {
+                let reply = ethernet.parse::<Ipv4>().unwrap();
+                assert_eq!(src, reply.dst());
+                assert_eq!(Ipv4Addr::new(10, 0, 0, 1), reply.src());
+                let icmp = reply.parse::<Icmpv4>().unwrap();
+                assert_eq!(Icmpv4Types::TimeExceeded, icmp.msg_type());
+            }
+            _ => panic!("no time exceeded reply."),
+        }
+    }
+}
@@ -0,0 +1,1161 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use crate::dpdk::{self, CoreId, PortQueue, Timer, TimerManager};
+use crate::net::{ConnState, ConnTracker, MacAddr, TcpState};
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::Flow;
+use crate::packets::{Ethernet, Packet, Tcp4};
+use crate::{debug, ensure, ffi, warn, Mbuf};
+use anyhow::Result;
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::net::SocketAddrV4;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// The maximum segment size sent.
+const MSS: usize = 1460;
+
+/// The receive window advertised.
+const WINDOW: u16 = 65535;
+
+/// The number of retransmissions before the connection is reset.
+const MAX_RETRIES: u32 = 5;
+
+/// Error indicating the TCP operation failed.
+#[derive(Debug, Error)]
+pub enum TcpError {
+    /// Error returned when there's already a listener on the address.
+    #[error("Address {0} is already in use.")]
+    AddrInUse(SocketAddrV4),
+
+    /// Error returned when no data is received on the stream.
+    #[error("No data received.")]
+    WouldBlock,
+
+    /// Error returned when sending on a stream already closed.
+    #[error("Stream is closed.")]
+    Closed,
+
+    /// Error returned when the connection is reset.
+    #[error("Connection reset.")]
+    Reset,
+
+    /// Error returned when the stack is not created on an EAL lcore.
+    #[error("Current thread is not an EAL lcore.")]
+    NotLcore,
+}
+
+/// Returns whether the sequence number `a` is before `b`, accounting for
+/// the wraparound.
+#[inline]
+pub(super) fn seq_lt(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+/// Returns the initial sequence number of a connection, as described in
+/// [IETF RFC 6528].
+///
+/// The ISN is a clock ticking every 4 microseconds, offset by a keyed
+/// hash of the 4-tuple. The hash keeps the sequence numbers of different
+/// connections unpredictable, and the clock keeps the ones of successive
+/// connections on the same 4-tuple from overlapping.
+///
+/// [IETF RFC 6528]: https://tools.ietf.org/html/rfc6528
+fn initial_seq_no(
+    secret: &RandomState,
+    local: SocketAddrV4,
+    remote: SocketAddrV4,
+    cycles: u64,
+    hz: u64,
+) -> u32 {
+    let mut hasher = secret.build_hasher();
+    local.hash(&mut hasher);
+    remote.hash(&mut hasher);
+    let clock = u128::from(cycles) * 250_000 / u128::from(hz.max(1));
+    (clock as u32).wrapping_add(hasher.finish() as u32)
+}
+
+/// A segment sent but not acknowledged yet.
+#[derive(Debug)]
+struct Segment {
+    seq: u32,
+    syn: bool,
+    fin: bool,
+    data: Vec<u8>,
+}
+
+impl Segment {
+    /// Returns the sequence space the segment takes.
+    fn seq_len(&self) -> u32 {
+        self.data.len() as u32 + self.syn as u32 + self.fin as u32
+    }
+}
+
+/// The control block of a connection.
+struct Tcb {
+    local: SocketAddrV4,
+    remote: SocketAddrV4,
+    remote_mac: MacAddr,
+    snd_una: u32,
+    snd_nxt: u32,
+    rcv_nxt: u32,
+    received: VecDeque<u8>,
+    unacked: VecDeque<Segment>,
+    fin_sent: bool,
+    fin_received: bool,
+    reset: bool,
+    // whether the stream handle is dropped, or was never handed out.
+    released: bool,
+    retries: u32,
+    timer: Option<Timer>,
+    // set by the timer callback, checked in `on_timeouts`.
+    expired: Arc<AtomicBool>,
+}
+
+impl Tcb {
+    /// Returns whether both sides closed and all data is acknowledged.
+    fn is_finished(&self) -> bool {
+        self.reset || (self.fin_sent && self.fin_received && self.unacked.is_empty())
+    }
+
+    /// Stops the retransmission timer, including an expiry not handled
+    /// yet.
+    fn stop_timer(&mut self) {
+        self.timer = None;
+        self.expired.store(false, Ordering::Relaxed);
+    }
+}
+
+/// The protocol state shared by the stack and its sockets.
+struct TcpCore {
+    mac_addr: MacAddr,
+    // the lcore running the retransmission timers.
+    lcore: CoreId,
+    rto_cycles: u64,
+    // the SipHash key of the initial sequence numbers, random per stack.
+    isn_secret: RandomState,
+    tsc_hz: u64,
+    tracker: ConnTracker,
+    listeners: HashMap<SocketAddrV4, VecDeque<Flow>>,
+    conns: HashMap<Flow, Tcb>,
+    outbox: Vec<Mbuf>,
+}
+
+impl TcpCore {
+    fn new(mac_addr: MacAddr, lcore: CoreId, rto_cycles: u64) -> Self {
+        TcpCore {
+            mac_addr,
+            lcore,
+            rto_cycles,
+            isn_secret: RandomState::new(),
+            tsc_hz: unsafe { ffi::rte_get_tsc_hz() },
+            tracker: ConnTracker::new(),
+            listeners: HashMap::new(),
+            conns: HashMap::new(),
+            outbox: vec![],
+        }
+    }
+
+    /// Builds a segment of the connection and queues it for transmission.
+    fn send_segment(&mut self, key: &Flow, seq: u32, flags: Flags, data: &[u8]) -> Result<()> {
+        let tcb = &self.conns[key];
+        let ack_no = if flags.ack { tcb.rcv_nxt } else { 0 };
+        let mbuf = build_segment(
+            self.mac_addr,
+            tcb.remote_mac,
+            tcb.local,
+            tcb.remote,
+            seq,
+            ack_no,
+            flags,
+            data,
+        )?;
+        self.outbox.push(mbuf);
+        Ok(())
+    }
+
+    /// Sends a segment that takes sequence space, and keeps it for
+    /// retransmission.
+    fn send_reliable(&mut self, key: &Flow, syn: bool, fin: bool, data: &[u8]) -> Result<()> {
+        let flags = Flags {
+            syn,
+            fin,
+            ack: true,
+            psh: !data.is_empty(),
+            rst: false,
+        };
+        let seq = self.conns[key].snd_nxt;
+        self.send_segment(key, seq, flags, data)?;
+
+        let segment = Segment {
+            seq,
+            syn,
+            fin,
+            data: data.to_vec(),
+        };
+        let tcb = self.conns.get_mut(key).unwrap();
+        tcb.snd_nxt = tcb.snd_nxt.wrapping_add(segment.seq_len());
+        tcb.unacked.push_back(segment);
+
+        if tcb.timer.is_none() {
+            self.arm_timer(key)?;
+        }
+        Ok(())
+    }
+
+    /// Starts the retransmission timer of the connection, backing off
+    /// exponentially with the number of retries.
+    fn arm_timer(&mut self, key: &Flow) -> Result<()> {
+        let tcb = self.conns.get_mut(key).unwrap();
+        tcb.expired.store(false, Ordering::Relaxed);
+        let expired = tcb.expired.clone();
+        let delay = self.rto_cycles << tcb.retries.min(MAX_RETRIES);
+
+        let timer = Timer::new_oneshot(delay, self.lcore, move || {
+            expired.store(true, Ordering::Relaxed)
+        })?;
+        tcb.timer = Some(timer);
+        Ok(())
+    }
+
+    /// Resets the connection.
+    fn reset(&mut self, key: &Flow) -> Result<()> {
+        let seq = self.conns[key].snd_nxt;
+        let result = self.send_segment(key, seq, Flags::RST, &[]);
+        self.abort(key);
+        result
+    }
+
+    /// Marks the connection as reset without notifying the remote.
+    fn abort(&mut self, key: &Flow) {
+        let tcb = self.conns.get_mut(key).unwrap();
+        tcb.reset = true;
+        tcb.stop_timer();
+        tcb.unacked.clear();
+        let _ = self.tracker.update(key, ConnState::Tcp(TcpState::Closed));
+        self.cleanup(key);
+    }
+
+    /// Removes the connection if it's finished and there's no stream
+    /// handle left.
+    fn cleanup(&mut self, key: &Flow) {
+        let done = self
+            .conns
+            .get(key)
+            .map(|tcb| tcb.released && tcb.is_finished())
+            .unwrap_or(false);
+
+        if done {
+            let tcb = self.conns.remove(key).unwrap();
+            self.tracker.remove(key);
+            for backlog in self.listeners.values_mut() {
+                backlog.retain(|flow| flow != key);
+            }
+            debug!(local = ?tcb.local, remote = ?tcb.remote, "connection closed.");
+        }
+    }
+
+    /// Processes a received segment.
+    fn on_segment(&mut self, remote_mac: MacAddr, tcp: Tcp4) -> Result<()> {
+        let key = tcp.flow();
+
+        if self.conns.contains_key(&key) {
+            let result = self.on_conn_segment(&key, &tcp);
+            self.cleanup(&key);
+            return result;
+        }
+
+        let local = SocketAddrV4::new(tcp.envelope().dst(), tcp.dst_port());
+        let remote = SocketAddrV4::new(tcp.envelope().src(), tcp.src_port());
+
+        if tcp.syn() && !tcp.ack() && self.listeners.contains_key(&local) {
+            let isn = initial_seq_no(
+                &self.isn_secret,
+                local,
+                remote,
+                dpdk::tsc_cycles(),
+                self.tsc_hz,
+            );
+            let tcb = Tcb {
+                local,
+                remote,
+                remote_mac,
+                snd_una: isn,
+                snd_nxt: isn,
+                rcv_nxt: tcp.seq_no().wrapping_add(1),
+                received: VecDeque::new(),
+                unacked: VecDeque::new(),
+                fin_sent: false,
+                fin_received: false,
+                reset: false,
+                released: true,
+                retries: 0,
+                timer: None,
+                expired: Arc::new(AtomicBool::new(false)),
+            };
+            self.conns.insert(key, tcb);
+            let _ = self
+                .tracker
+                .insert(key, ConnState::Tcp(TcpState::SynReceived));
+            if let Err(err) = self.send_reliable(&key, true, false, &[]) {
+                // forgets the connection, the remote retries the SYN.
+                self.conns.remove(&key);
+                self.tracker.remove(&key);
+                return Err(err);
+            }
+        } else if !tcp.rst() {
+            // no connection and no listener, refuses the segment.
+            let (seq, ack_no, flags) = if tcp.ack() {
+                (tcp.ack_no(), 0, Flags::RST)
+            } else {
+                let len = tcp.payload_len() as u32 + tcp.syn() as u32 + tcp.fin() as u32;
+                (0, tcp.seq_no().wrapping_add(len), Flags::RST_ACK)
+            };
+            let mbuf = build_segment(
+                self.mac_addr,
+                remote_mac,
+                local,
+                remote,
+                seq,
+                ack_no,
+                flags,
+                &[],
+            )?;
+            self.outbox.push(mbuf);
+        }
+
+        Ok(())
+    }
+
+    /// Processes a segment of a known connection.
+    fn on_conn_segment(&mut self, key: &Flow, tcp: &Tcp4) -> Result<()> {
+        if tcp.rst() {
+            self.abort(key);
+            return Ok(());
+        }
+
+        let tcb = self.conns.get_mut(key).unwrap();
+        let mut need_ack = false;
+
+        if tcp.ack() {
+            let ack_no = tcp.ack_no();
+            if seq_lt(tcb.snd_una, ack_no) && !seq_lt(tcb.snd_nxt, ack_no) {
+                tcb.snd_una = ack_no;
+                while let Some(segment) = tcb.unacked.front() {
+                    let end = segment.seq.wrapping_add(segment.seq_len());
+                    if seq_lt(ack_no, end) {
+                        break;
+                    }
+                    tcb.unacked.pop_front();
+                }
+                tcb.retries = 0;
+                tcb.stop_timer();
+            }
+        }
+
+        let state = match self.tracker.lookup(key) {
+            Some(ConnState::Tcp(state)) => *state,
+            _ => TcpState::Closed,
+        };
+
+        if state == TcpState::SynReceived && tcb.unacked.iter().all(|s| !s.syn) {
+            let _ = self
+                .tracker
+                .update(key, ConnState::Tcp(TcpState::Established));
+            if let Some(backlog) = self.listeners.get_mut(&tcb.local) {
+                backlog.push_back(*key);
+            }
+        }
+
+        let mut seq = tcp.seq_no();
+        let len = tcp.payload_len();
+        if len > 0 {
+            if seq == tcb.rcv_nxt && !tcb.fin_received {
+                if let Ok(data) = tcp.mbuf().read_data_slice::<u8>(tcp.payload_offset(), len) {
+                    tcb.received.extend(unsafe { data.as_ref() });
+                    tcb.rcv_nxt = tcb.rcv_nxt.wrapping_add(len as u32);
+                }
+            }
+            // out of order segments are dropped and acked with the next
+            // expected sequence number.
+            need_ack = true;
+        }
+        seq = seq.wrapping_add(len as u32);
+
+        if tcp.fin() && seq == tcb.rcv_nxt && !tcb.fin_received {
+            tcb.fin_received = true;
+            tcb.rcv_nxt = tcb.rcv_nxt.wrapping_add(1);
+            need_ack = true;
+            let state = if tcb.fin_sent {
+                TcpState::TimeWait
+            } else {
+                TcpState::FinWait
+            };
+            let _ = self.tracker.update(key, ConnState::Tcp(state));
+        }
+
+        if !self.conns[key].unacked.is_empty() && self.conns[key].timer.is_none() {
+            self.arm_timer(key)?;
+        }
+
+        if need_ack {
+            let seq = self.conns[key].snd_nxt;
+            self.send_segment(key, seq, Flags::ACK, &[])?;
+        }
+
+        Ok(())
+    }
+
+    /// Retransmits the unacknowledged segments of the connections whose
+    /// timer expired.
+    fn on_timeouts(&mut self) -> Result<()> {
+        let expired = self
+            .conns
+            .iter()
+            .filter(|(_, tcb)| tcb.expired.swap(false, Ordering::Relaxed))
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+
+        for key in expired {
+            let tcb = self.conns.get_mut(&key).unwrap();
+            tcb.timer = None;
+            tcb.retries += 1;
+
+            if tcb.retries > MAX_RETRIES {
+                debug!(remote = ?tcb.remote, "retransmission timed out.");
+                self.reset(&key)?;
+                continue;
+            }
+
+            let segments = tcb
+                .unacked
+                .iter()
+                .map(|s| {
+                    let flags = Flags {
+                        syn: s.syn,
+                        fin: s.fin,
+                        ack: true,
+                        psh: !s.data.is_empty(),
+                        rst: false,
+                    };
+                    (s.seq, flags, s.data.clone())
+                })
+                .collect::<Vec<_>>();
+
+            // rearms first, so a failed retransmission is retried.
+            self.arm_timer(&key)?;
+            for (seq, flags, data) in segments {
+                self.send_segment(&key, seq, flags, &data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends a FIN on the connection.
+    fn close(&mut self, key: &Flow) -> Result<()> {
+        let tcb = &self.conns[key];
+        if tcb.fin_sent || tcb.reset {
+            return Ok(());
+        }
+
+        self.send_reliable(key, false, true, &[])?;
+        let tcb = self.conns.get_mut(key).unwrap();
+        tcb.fin_sent = true;
+        let state = if tcb.fin_received {
+            TcpState::TimeWait
+        } else {
+            TcpState::FinWait
+        };
+        let _ = self.tracker.update(key, ConnState::Tcp(state));
+        Ok(())
+    }
+}
+
+/// The TCP flags of a segment.
+#[derive(Clone, Copy, Debug)]
+struct Flags {
+    syn: bool,
+    ack: bool,
+    fin: bool,
+    rst: bool,
+    psh: bool,
+}
+
+impl Flags {
+    const ACK: Flags = Flags {
+        syn: false,
+        ack: true,
+        fin: false,
+        rst: false,
+        psh: false,
+    };
+
+    const RST: Flags = Flags {
+        syn: false,
+        ack: false,
+        fin: false,
+        rst: true,
+        psh: false,
+    };
+
+    const RST_ACK: Flags = Flags {
+        syn: false,
+        ack: true,
+        fin: false,
+        rst: true,
+        psh: false,
+    };
+}
+
+/// Builds a TCP segment.
+#[allow(clippy::too_many_arguments)]
+fn build_segment(
+    src_mac: MacAddr,
+    dst_mac: MacAddr,
+    local: SocketAddrV4,
+    remote: SocketAddrV4,
+    seq: u32,
+    ack_no: u32,
+    flags: Flags,
+    data: &[u8],
+) -> Result<Mbuf> {
+    let mut ethernet = Mbuf::new()?.push::<Ethernet>()?;
+    ethernet.set_src(src_mac);
+    ethernet.set_dst(dst_mac);
+
+    let mut ipv4 = ethernet.push::<Ipv4>()?;
+    ipv4.set_src(*local.ip());
+    ipv4.set_dst(*remote.ip());
+
+    let mut tcp = ipv4.push::<Tcp4>()?;
+    tcp.set_src_port(local.port());
+    tcp.set_dst_port(remote.port());
+    tcp.set_seq_no(seq);
+    tcp.set_ack_no(ack_no);
+    tcp.set_window(WINDOW);
+    if flags.syn {
+        tcp.set_syn();
+    }
+    if flags.ack {
+        tcp.set_ack();
+    }
+    if flags.fin {
+        tcp.set_fin();
+    }
+    if flags.rst {
+        tcp.set_rst();
+    }
+    if flags.psh {
+        tcp.set_psh();
+    }
+
+    if !data.is_empty() {
+        let offset = tcp.payload_offset();
+        tcp.mbuf_mut().extend(offset, data.len())?;
+        tcp.mbuf_mut().write_data_slice(offset, data)?;
+    }
+    tcp.reconcile_all();
+    Ok(tcp.reset())
+}
+
+/// A minimal userspace TCP stack on a port queue.
+///
+/// The stack only accepts connections. It handles the handshake, in order
+/// data transfer and the teardown, and retransmits unacknowledged segments
+/// with exponential backoff. It does not reassemble out of order
+/// segments, honor the remote's receive window, do congestion control or
+/// linger in `TIME-WAIT`. It's meant for research and testing, not
+/// production traffic.
+///
+/// The stack is driven by `poll`, which receives and processes a burst of
+/// segments, runs the retransmission timers through `TimerManager`, and
+/// sends out the queued segments. The timers run on the lcore that created
+/// the stack, so the stack must be created and polled on an EAL lcore,
+/// either the master lcore or a worker launched with `LcoreManager`. The
+/// pipeline cores of the runtime are not EAL lcores. `poll` should be
+/// called regularly. The sockets are not thread-safe.
+///
+/// # Example
+///
+/// ```
+/// let stack = TcpStack::new(q)?;
+/// let listener = stack.listen("10.0.0.1:80".parse()?)?;
+///
+/// loop {
+///     stack.poll()?;
+///     if let Some(mut stream) = listener.accept() {
+///         stream.send(b"hello")?;
+///     }
+/// }
+/// ```
+pub struct TcpStack {
+    queue: PortQueue,
+    core: Rc<RefCell<TcpCore>>,
+}
+
+impl TcpStack {
+    /// Creates a stack on the port queue. The retransmission timeout
+    /// starts at one second.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TcpError::NotLcore` if the current thread is not an EAL
+    /// lcore.
+    pub fn new(queue: PortQueue) -> Result<Self> {
+        let lcore = unsafe { ffi::_rte_lcore_id() };
+        ensure!(lcore < ffi::RTE_MAX_LCORE, TcpError::NotLcore);
+
+        let core = TcpCore::new(
+            queue.mac_addr(),
+            CoreId::new(lcore as usize),
+            TimerManager::hz(),
+        );
+
+        Ok(TcpStack {
+            queue,
+            core: Rc::new(RefCell::new(core)),
+        })
+    }
+
+    /// Listens for connections on the address.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TcpError::AddrInUse` if there's already a listener on the
+    /// address.
+    pub fn listen(&self, addr: SocketAddrV4) -> Result<TcpListener> {
+        TcpListener::bind(&self.core, addr)
+    }
+
+    /// Returns the number of connections, including the ones in the
+    /// handshake and the teardown.
+    pub fn connections(&self) -> usize {
+        self.core.borrow().conns.len()
+    }
+
+    /// Processes the received segments and the expired timers, and sends
+    /// out the queued segments.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a segment cannot be built, usually because the
+    /// mempool is exhausted, or if a retransmission timer cannot be
+    /// started. The segments built so far are still sent.
+    pub fn poll(&self) -> Result<()> {
+        let mut core = self.core.borrow_mut();
+        let mut result = Ok(());
+
+        for mbuf in self.queue.receive() {
+            if let Ok(ethernet) = mbuf.parse::<Ethernet>() {
+                if ethernet.dst() != core.mac_addr {
+                    continue;
+                }
+                let remote_mac = ethernet.src();
+                if let Ok(tcp) = ethernet.parse::<Ipv4>().and_then(|ip| ip.parse::<Tcp4>()) {
+                    result = result.and(core.on_segment(remote_mac, tcp));
+                }
+            }
+        }
+
+        result = result.and(TimerManager::manage()).and(core.on_timeouts());
+
+        let outbox = std::mem::take(&mut core.outbox);
+        if !outbox.is_empty() {
+            self.queue.transmit(outbox);
+        }
+        result
+    }
+}
+
+impl std::fmt::Debug for TcpStack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let core = self.core.borrow();
+        f.debug_struct("TcpStack")
+            .field("mac_addr", &core.mac_addr)
+            .field("listeners", &core.listeners.len())
+            .field("connections", &core.conns.len())
+            .finish()
+    }
+}
+
+/// A socket listening for connections.
+///
+/// Dropping the listener closes the connections not accepted yet.
+pub struct TcpListener {
+    core: Rc<RefCell<TcpCore>>,
+    local: SocketAddrV4,
+}
+
+impl TcpListener {
+    fn bind(core: &Rc<RefCell<TcpCore>>, local: SocketAddrV4) -> Result<Self> {
+        let mut inner = core.borrow_mut();
+        ensure!(
+            !inner.listeners.contains_key(&local),
+            TcpError::AddrInUse(local)
+        );
+        inner.listeners.insert(local, VecDeque::new());
+
+        Ok(TcpListener {
+            core: core.clone(),
+            local,
+        })
+    }
+
+    /// Returns the local address the listener is bound to.
+    pub fn local_addr(&self) -> SocketAddrV4 {
+        self.local
+    }
+
+    /// Returns the next established connection, or `None` if there's
+    /// none.
+    pub fn accept(&self) -> Option<TcpStream> {
+        let mut core = self.core.borrow_mut();
+        let key = core.listeners.get_mut(&self.local)?.pop_front()?;
+        core.conns.get_mut(&key)?.released = false;
+
+        Some(TcpStream {
+            core: self.core.clone(),
+            key,
+        })
+    }
+}
+
+impl std::fmt::Debug for TcpListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TcpListener")
+            .field("local", &self.local)
+            .finish()
+    }
+}
+
+impl Drop for TcpListener {
+    fn drop(&mut self) {
+        let mut core = self.core.borrow_mut();
+        if let Some(backlog) = core.listeners.remove(&self.local) {
+            for key in backlog {
+                if let Err(err) = core.close(&key) {
+                    warn!(?err, "failed to close connection.");
+                }
+            }
+        }
+    }
+}
+
+/// A connection accepted by a listener.
+///
+/// Dropping the stream closes the connection.
+pub struct TcpStream {
+    core: Rc<RefCell<TcpCore>>,
+    key: Flow,
+}
+
+impl TcpStream {
+    /// Returns the local address of the connection.
+    pub fn local_addr(&self) -> SocketAddrV4 {
+        self.core.borrow().conns[&self.key].local
+    }
+
+    /// Returns the remote address of the connection.
+    pub fn peer_addr(&self) -> SocketAddrV4 {
+        self.core.borrow().conns[&self.key].remote
+    }
+
+    /// Queues the data for transmission in segments of up to 1460 bytes.
+    /// Returns the number of bytes queued, which is less than the length
+    /// of the data if the mempool ran out of buffers midway.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TcpError::Closed` if the stream is closed, or
+    /// `TcpError::Reset` if the connection is reset. Returns an error if
+    /// not even the first segment can be built.
+    pub fn send(&mut self, data: &[u8]) -> Result<usize> {
+        let mut core = self.core.borrow_mut();
+        let tcb = &core.conns[&self.key];
+        ensure!(!tcb.reset, TcpError::Reset);
+        ensure!(!tcb.fin_sent, TcpError::Closed);
+
+        let mut queued = 0;
+        for chunk in data.chunks(MSS) {
+            if let Err(err) = core.send_reliable(&self.key, false, false, chunk) {
+                if queued == 0 {
+                    return Err(err);
+                }
+                break;
+            }
+            queued += chunk.len();
+        }
+        Ok(queued)
+    }
+
+    /// Reads the received data into `buf`. Returns the number of bytes
+    /// read, or 0 if the remote closed the connection and all the data
+    /// is read.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TcpError::WouldBlock` if there's no data to read, or
+    /// `TcpError::Reset` if the connection is reset.
+    pub fn recv(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut core = self.core.borrow_mut();
+        let tcb = core.conns.get_mut(&self.key).unwrap();
+
+        if !tcb.received.is_empty() {
+            let len = buf.len().min(tcb.received.len());
+            for (dst, src) in buf.iter_mut().zip(tcb.received.drain(..len)) {
+                *dst = src;
+            }
+            Ok(len)
+        } else if tcb.reset {
+            Err(TcpError::Reset.into())
+        } else if tcb.fin_received {
+            Ok(0)
+        } else {
+            Err(TcpError::WouldBlock.into())
+        }
+    }
+
+    /// Closes the sending side of the connection. The stream can still
+    /// receive until the remote closes its side.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the FIN cannot be built. The stream stays open
+    /// and the close can be retried.
+    pub fn close(&mut self) -> Result<()> {
+        self.core.borrow_mut().close(&self.key)
+    }
+}
+
+impl std::fmt::Debug for TcpStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let core = self.core.borrow();
+        let tcb = &core.conns[&self.key];
+        f.debug_struct("TcpStream")
+            .field("local", &tcb.local)
+            .field("remote", &tcb.remote)
+            .field("state", &core.tracker.lookup(&self.key))
+            .finish()
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        let mut core = self.core.borrow_mut();
+        if let Err(err) = core.close(&self.key) {
+            warn!(?err, "failed to close connection.");
+        }
+        if let Some(tcb) = core.conns.get_mut(&self.key) {
+            tcb.released = true;
+        }
+        core.cleanup(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn server() -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 80)
+    }
+
+    fn client() -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 40000)
+    }
+
+    fn server_mac() -> MacAddr {
+        MacAddr::new(0x02, 0, 0, 0, 0, 1)
+    }
+
+    fn client_mac() -> MacAddr {
+        MacAddr::new(0x02, 0, 0, 0, 0, 2)
+    }
+
+    fn flags(syn: bool, ack: bool, fin: bool) -> Flags {
+        Flags {
+            syn,
+            ack,
+            fin,
+            rst: false,
+            psh: false,
+        }
+    }
+
+    const RTO: u64 = 1_000_000;
+
+    fn new_core() -> Rc<RefCell<TcpCore>> {
+        // the test threads are not EAL lcores, so the timers are armed on
+        // the master lcore, which doesn't manage them during the tests.
+        let lcore = CoreId::new(unsafe { ffi::rte_get_master_lcore() } as usize);
+        Rc::new(RefCell::new(TcpCore::new(server_mac(), lcore, RTO)))
+    }
+
+    /// Expires the retransmission timer of the connection, the same as
+    /// the timer callback does when the lcore manages its timers.
+    fn expire(core: &Rc<RefCell<TcpCore>>, key: &Flow) {
+        let mut core = core.borrow_mut();
+        let tcb = core.conns.get_mut(key).unwrap();
+        assert!(tcb.timer.as_mut().unwrap().is_pending());
+        tcb.expired.store(true, Ordering::Relaxed);
+    }
+
+    /// Sends a segment from the client to the server.
+    fn deliver(
+        core: &Rc<RefCell<TcpCore>>,
+        to: SocketAddrV4,
+        seq: u32,
+        ack_no: u32,
+        flags: Flags,
+        data: &[u8],
+    ) {
+        let mbuf = build_segment(
+            client_mac(),
+            server_mac(),
+            client(),
+            to,
+            seq,
+            ack_no,
+            flags,
+            data,
+        )
+        .unwrap();
+        let tcp = mbuf
+            .parse::<Ethernet>()
+            .unwrap()
+            .parse::<Ipv4>()
+            .unwrap()
+            .parse::<Tcp4>()
+            .unwrap();
+        core.borrow_mut().on_segment(client_mac(), tcp).unwrap();
+    }
+
+    /// Returns the segments sent by the server.
+    fn sent(core: &Rc<RefCell<TcpCore>>) -> Vec<Tcp4> {
+        std::mem::take(&mut core.borrow_mut().outbox)
+            .into_iter()
+            .map(|mbuf| {
+                mbuf.parse::<Ethernet>()
+                    .unwrap()
+                    .parse::<Ipv4>()
+                    .unwrap()
+                    .parse::<Tcp4>()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    /// Completes the handshake. Returns the server's next sequence number.
+    fn handshake(core: &Rc<RefCell<TcpCore>>) -> u32 {
+        deliver(core, server(), 100, 0, flags(true, false, false), &[]);
+        let syn_ack = sent(core).pop().unwrap();
+        assert!(syn_ack.syn_ack());
+        assert_eq!(101, syn_ack.ack_no());
+        assert!(syn_ack.validate_checksum());
+
+        let isn = syn_ack.seq_no();
+        deliver(
+            core,
+            server(),
+            101,
+            isn.wrapping_add(1),
+            flags(false, true, false),
+            &[],
+        );
+        isn.wrapping_add(1)
+    }
+
+    #[capsule::test]
+    fn accept_connection() {
+        let core = new_core();
+        let listener = TcpListener::bind(&core, server()).unwrap();
+        assert!(TcpListener::bind(&core, server()).is_err());
+        assert!(listener.accept().is_none());
+
+        handshake(&core);
+        let stream = listener.accept().unwrap();
+        assert_eq!(client(), stream.peer_addr());
+        assert_eq!(
+            Some(&ConnState::Tcp(TcpState::Established)),
+            core.borrow().tracker.lookup(&stream.key)
+        );
+    }
+
+    #[capsule::test]
+    fn send_and_recv() {
+        let core = new_core();
+        let listener = TcpListener::bind(&core, server()).unwrap();
+        let seq = handshake(&core);
+        let mut stream = listener.accept().unwrap();
+
+        let mut buf = [0; 16];
+        assert!(stream.recv(&mut buf).is_err());
+
+        deliver(
+            &core,
+            server(),
+            101,
+            seq,
+            flags(false, true, false),
+            b"hello",
+        );
+        assert_eq!(5, stream.recv(&mut buf).unwrap());
+        assert_eq!(b"hello", &buf[..5]);
+        assert_eq!(106, sent(&core).pop().unwrap().ack_no());
+
+        assert_eq!(5, stream.send(b"world").unwrap());
+        let segment = sent(&core).pop().unwrap();
+        assert_eq!(seq, segment.seq_no());
+        assert_eq!(5, segment.payload_len());
+        assert_eq!(1, core.borrow().conns[&stream.key].unacked.len());
+
+        deliver(
+            &core,
+            server(),
+            106,
+            seq.wrapping_add(5),
+            flags(false, true, false),
+            &[],
+        );
+        assert!(core.borrow().conns[&stream.key].unacked.is_empty());
+    }
+
+    #[capsule::test]
+    fn close_connection() {
+        let core = new_core();
+        let listener = TcpListener::bind(&core, server()).unwrap();
+        let seq = handshake(&core);
+        let mut stream = listener.accept().unwrap();
+
+        deliver(&core, server(), 101, seq, flags(false, true, true), &[]);
+        let mut buf = [0; 16];
+        assert_eq!(0, stream.recv(&mut buf).unwrap());
+        assert_eq!(102, sent(&core).pop().unwrap().ack_no());
+
+        drop(stream);
+        let fin = sent(&core).pop().unwrap();
+        assert!(fin.fin());
+        assert_eq!(1, core.borrow().conns.len());
+
+        deliver(
+            &core,
+            server(),
+            102,
+            seq.wrapping_add(1),
+            flags(false, true, false),
+            &[],
+        );
+        assert!(core.borrow().conns.is_empty());
+        assert!(core.borrow().tracker.is_empty());
+    }
+
+    #[capsule::test]
+    fn retransmit_unacked_segment() {
+        let core = new_core();
+        let listener = TcpListener::bind(&core, server()).unwrap();
+        let seq = handshake(&core);
+        let mut stream = listener.accept().unwrap();
+
+        assert_eq!(5, stream.send(b"hello").unwrap());
+        assert_eq!(1, sent(&core).len());
+
+        // the timer hasn't expired yet.
+        core.borrow_mut().on_timeouts().unwrap();
+        assert!(sent(&core).is_empty());
+
+        expire(&core, &stream.key);
+        core.borrow_mut().on_timeouts().unwrap();
+        let segment = sent(&core).pop().unwrap();
+        assert_eq!(seq, segment.seq_no());
+        assert_eq!(5, segment.payload_len());
+
+        // rearmed for the next retry.
+        assert_eq!(1, core.borrow().conns[&stream.key].retries);
+        assert!(core.borrow().conns[&stream.key].timer.is_some());
+
+        deliver(
+            &core,
+            server(),
+            101,
+            seq.wrapping_add(5),
+            flags(false, true, false),
+            &[],
+        );
+        assert!(core.borrow().conns[&stream.key].timer.is_none());
+        assert_eq!(0, core.borrow().conns[&stream.key].retries);
+    }
+
+    #[capsule::test]
+    fn reset_after_max_retries() {
+        let core = new_core();
+        let listener = TcpListener::bind(&core, server()).unwrap();
+        handshake(&core);
+        let mut stream = listener.accept().unwrap();
+        stream.send(b"hello").unwrap();
+        sent(&core);
+
+        for _ in 0..MAX_RETRIES {
+            expire(&core, &stream.key);
+            core.borrow_mut().on_timeouts().unwrap();
+            assert!(!sent(&core).pop().unwrap().rst());
+        }
+
+        expire(&core, &stream.key);
+        core.borrow_mut().on_timeouts().unwrap();
+        assert!(sent(&core).pop().unwrap().rst());
+        assert!(stream.recv(&mut [0; 16]).is_err());
+    }
+
+    #[test]
+    fn isn_from_keyed_hash_and_clock() {
+        let secret = RandomState::new();
+        let hz = 1_000_000_000;
+        let isn = initial_seq_no(&secret, server(), client(), 0, hz);
+
+        // the clock ticks every 4 microseconds.
+        assert_eq!(
+            isn.wrapping_add(1),
+            initial_seq_no(&secret, server(), client(), 4_000, hz)
+        );
+
+        let other = SocketAddrV4::new(*client().ip(), client().port() + 1);
+        assert_ne!(isn, initial_seq_no(&secret, server(), other, 0, hz));
+        assert_ne!(
+            isn,
+            initial_seq_no(&RandomState::new(), server(), client(), 0, hz)
+        );
+    }
+
+    #[capsule::test]
+    fn refuse_without_listener() {
+        let core = new_core();
+        deliver(&core, server(), 100, 0, flags(true, false, false), &[]);
+
+        let rst = sent(&core).pop().unwrap();
+        assert!(rst.rst());
+        assert_eq!(101, rst.ack_no());
+        assert!(core.borrow().conns.is_empty());
+    }
+}
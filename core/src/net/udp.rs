@@ -0,0 +1,298 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+use crate::dpdk::PortQueue;
+use crate::net::MacAddr;
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::{Ethernet, Packet, Udp4};
+use crate::Mbuf;
+use anyhow::Result;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use thiserror::Error;
+
+/// Error indicating the datagram cannot be sent or received.
+#[derive(Debug, Error)]
+pub enum UdpSocketError {
+    /// Error returned when no datagram is received for the socket.
+    #[error("No datagram received.")]
+    WouldBlock,
+
+    /// Error returned when sending to a broadcast address without
+    /// enabling broadcast on the socket.
+    #[error("Broadcast is not enabled on the socket.")]
+    BroadcastDisabled,
+}
+
+/// A UDP socket that sends and receives datagrams directly on a port
+/// queue, bypassing the kernel.
+///
+/// The socket is non-blocking. `recv_from` polls the receive queue once
+/// and returns `UdpSocketError::WouldBlock` if there's no datagram for
+/// the socket. Packets received that are not for the socket are freed,
+/// so the queue should not be shared with other consumers.
+///
+/// There's no address resolution. All datagrams are sent to the MAC
+/// address set with `set_next_hop`, for example the default gateway.
+///
+/// # Example
+///
+/// ```
+/// let socket = UdpSocket::bind(q, "10.0.0.1:5353".parse()?)?;
+/// socket.set_next_hop(gateway_mac);
+///
+/// socket.send_to(b"ping", "10.0.0.2:5353".parse()?)?;
+/// let (len, remote) = socket.recv_from(&mut buf)?;
+/// ```
+pub struct UdpSocket {
+    queue: PortQueue,
+    local: SocketAddrV4,
+    mac_addr: MacAddr,
+    next_hop: Cell<MacAddr>,
+    broadcast: Cell<bool>,
+    // datagrams for the socket received in the same burst as the one
+    // returned by `recv_from`.
+    pending: RefCell<VecDeque<Udp4>>,
+}
+
+impl UdpSocket {
+    /// Creates a socket bound to the local address on the port queue.
+    ///
+    /// If the local IP is unspecified, the socket receives datagrams sent
+    /// to any address on the port.
+    pub fn bind(queue: PortQueue, local: SocketAddrV4) -> Result<Self> {
+        let mac_addr = queue.mac_addr();
+        Ok(UdpSocket {
+            queue,
+            local,
+            mac_addr,
+            next_hop: Cell::new(MacAddr::BROADCAST),
+            broadcast: Cell::new(false),
+            pending: RefCell::new(VecDeque::new()),
+        })
+    }
+
+    /// Returns the local address the socket is bound to.
+    pub fn local_addr(&self) -> SocketAddrV4 {
+        self.local
+    }
+
+    /// Sets the MAC address datagrams are sent to.
+    pub fn set_next_hop(&self, mac: MacAddr) {
+        self.next_hop.set(mac);
+    }
+
+    /// Sets whether the socket can send and receive broadcast datagrams.
+    ///
+    /// Enabling broadcast puts the port in promiscuous mode, so it also
+    /// receives packets not sent to its MAC address. Because the mode is
+    /// per port, disabling it affects all the queues of the port.
+    pub fn set_broadcast(&self, broadcast: bool) -> Result<()> {
+        if broadcast {
+            self.queue.enable_promiscuous()?;
+        } else {
+            self.queue.disable_promiscuous()?;
+        }
+        self.broadcast.set(broadcast);
+        Ok(())
+    }
+
+    /// Returns whether broadcast is enabled on the socket.
+    pub fn broadcast(&self) -> bool {
+        self.broadcast.get()
+    }
+
+    /// Sends a datagram to the remote address. Returns the number of
+    /// bytes sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UdpSocketError::BroadcastDisabled` if the remote address
+    /// is the broadcast address and broadcast is not enabled. If the
+    /// datagram doesn't fit in an mbuf, `BufferError` is returned.
+    pub fn send_to(&self, buf: &[u8], remote: SocketAddrV4) -> Result<usize> {
+        let dst_mac = if remote.ip().is_broadcast() {
+            if !self.broadcast.get() {
+                return Err(UdpSocketError::BroadcastDisabled.into());
+            }
+            MacAddr::BROADCAST
+        } else {
+            self.next_hop.get()
+        };
+
+        let udp = build_datagram(self.mac_addr, dst_mac, self.local, remote, buf)?;
+        self.queue.transmit(vec![udp.reset()]);
+        Ok(buf.len())
+    }
+
+    /// Receives a datagram. Returns the number of bytes copied into `buf`
+    /// and the address of the sender. If the datagram is larger than
+    /// `buf`, the excess bytes are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UdpSocketError::WouldBlock` if no datagram is received.
+    pub fn recv_from(&self, buf: &mut [u8]) -> Result<(usize, SocketAddrV4)> {
+        let mut pending = self.pending.borrow_mut();
+
+        if pending.is_empty() {
+            let broadcast = self.broadcast.get();
+            pending.extend(
+                self.queue
+                    .receive()
+                    .into_iter()
+                    .filter_map(|mbuf| parse_datagram(mbuf, self.mac_addr, self.local, broadcast)),
+            );
+        }
+
+        let udp = pending.pop_front().ok_or(UdpSocketError::WouldBlock)?;
+        let remote = SocketAddrV4::new(udp.envelope().src(), udp.src_port());
+        let len = udp.payload_len().min(buf.len());
+        let payload = udp
+            .mbuf()
+            .read_data_slice::<u8>(udp.payload_offset(), len)?;
+        buf[..len].copy_from_slice(unsafe { payload.as_ref() });
+        Ok((len, remote))
+    }
+}
+
+impl std::fmt::Debug for UdpSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UdpSocket")
+            .field("local", &self.local)
+            .field("mac_addr", &self.mac_addr)
+            .field("next_hop", &self.next_hop.get())
+            .field("broadcast", &self.broadcast.get())
+            .finish()
+    }
+}
+
+/// Builds the datagram with the payload.
+pub(super) fn build_datagram(
+    src_mac: MacAddr,
+    dst_mac: MacAddr,
+    local: SocketAddrV4,
+    remote: SocketAddrV4,
+    payload: &[u8],
+) -> Result<Udp4> {
+    let mut ethernet = Mbuf::new()?.push::<Ethernet>()?;
+    ethernet.set_src(src_mac);
+    ethernet.set_dst(dst_mac);
+
+    let mut ipv4 = ethernet.push::<Ipv4>()?;
+    ipv4.set_src(*local.ip());
+    ipv4.set_dst(*remote.ip());
+
+    let mut udp = ipv4.push::<Udp4>()?;
+    udp.set_src_port(local.port());
+    udp.set_dst_port(remote.port());
+
+    if !payload.is_empty() {
+        let offset = udp.payload_offset();
+        udp.mbuf_mut().extend(offset, payload.len())?;
+        udp.mbuf_mut().write_data_slice(offset, payload)?;
+    }
+    udp.reconcile_all();
+    Ok(udp)
+}
+
+/// Parses the packet as a datagram for the socket. Returns `None` and
+/// frees the packet if it's not for the socket.
+pub(super) fn parse_datagram(
+    mbuf: Mbuf,
+    mac_addr: MacAddr,
+    local: SocketAddrV4,
+    broadcast: bool,
+) -> Option<Udp4> {
+    let ethernet = mbuf.parse::<Ethernet>().ok()?;
+    if ethernet.dst() != mac_addr && !(broadcast && ethernet.dst().is_broadcast()) {
+        return None;
+    }
+
+    let udp = ethernet.parse::<Ipv4>().ok()?.parse::<Udp4>().ok()?;
+    let dst = udp.envelope().dst();
+    let addressed = local.ip().is_unspecified()
+        || dst == *local.ip()
+        || (broadcast && dst == Ipv4Addr::BROADCAST);
+
+    if addressed && udp.dst_port() == local.port() {
+        Some(udp)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::ip::ProtocolNumbers;
+
+    fn local() -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 5353)
+    }
+
+    fn remote() -> SocketAddrV4 {
+        SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 5354)
+    }
+
+    fn local_mac() -> MacAddr {
+        MacAddr::new(0x02, 0, 0, 0, 0, 1)
+    }
+
+    fn remote_mac() -> MacAddr {
+        MacAddr::new(0x02, 0, 0, 0, 0, 2)
+    }
+
+    #[capsule::test]
+    fn build_and_parse_datagram() {
+        let udp = build_datagram(remote_mac(), local_mac(), remote(), local(), b"hello").unwrap();
+        assert_eq!(ProtocolNumbers::Udp, udp.envelope().protocol());
+        assert_eq!(5, udp.payload_len());
+        assert!(udp.validate_checksum());
+
+        let udp = parse_datagram(udp.reset(), local_mac(), local(), false).unwrap();
+        assert_eq!(remote().port(), udp.src_port());
+        assert_eq!(*remote().ip(), udp.envelope().src());
+    }
+
+    #[capsule::test]
+    fn build_zero_length_datagram() {
+        let udp = build_datagram(remote_mac(), local_mac(), remote(), local(), b"").unwrap();
+        assert_eq!(0, udp.payload_len());
+        assert_eq!(8, udp.length());
+        assert!(udp.validate_checksum());
+
+        let udp = parse_datagram(udp.reset(), local_mac(), local(), false).unwrap();
+        assert_eq!(0, udp.payload_len());
+    }
+
+    #[capsule::test]
+    fn parse_datagram_not_for_socket() {
+        // wrong destination port.
+        let other = SocketAddrV4::new(*local().ip(), 53);
+        let udp = build_datagram(remote_mac(), local_mac(), remote(), other, b"hello").unwrap();
+        assert!(parse_datagram(udp.reset(), local_mac(), local(), false).is_none());
+
+        // broadcast only accepted when enabled.
+        let bcast = SocketAddrV4::new(Ipv4Addr::BROADCAST, local().port());
+        let udp = build_datagram(remote_mac(), MacAddr::BROADCAST, remote(), bcast, b"").unwrap();
+        assert!(parse_datagram(udp.reset(), local_mac(), local(), false).is_none());
+        let udp = build_datagram(remote_mac(), MacAddr::BROADCAST, remote(), bcast, b"").unwrap();
+        assert!(parse_datagram(udp.reset(), local_mac(), local(), true).is_some());
+    }
+}
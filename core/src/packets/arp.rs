@@ -21,7 +21,7 @@
 use crate::net::MacAddr;
 use crate::packets::types::u16be;
 use crate::packets::{EtherTypes, Ethernet, Internal, Packet};
-use crate::{ensure, SizeOf};
+use crate::{ensure, Mbuf, SizeOf};
 use anyhow::{anyhow, Result};
 use std::fmt;
 use std::net::Ipv4Addr;
@@ -580,6 +580,39 @@ impl ProtocolAddr for Ipv4Addr {
 /// A type alias for an IPv4 ARP packet.
 pub type Arp4 = Arp<MacAddr, Ipv4Addr>;
 
+impl Arp4 {
+    /// Builds a new reply to this ARP request, answering that the target
+    /// protocol address is at `mac_addr`.
+    ///
+    /// The reply is written to a newly allocated `Mbuf`. The request is
+    /// not modified. The Ethernet frame of the reply is addressed to the
+    /// sender of the request.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the packet is not an ARP request. Returns an
+    /// error if the allocation of the new `Mbuf` fails.
+    pub fn reply(&self, mac_addr: MacAddr) -> Result<Arp4> {
+        ensure!(
+            self.operation_code() == OperationCodes::Request,
+            anyhow!("not an ARP request.")
+        );
+
+        let mut ethernet = Mbuf::new()?.push::<Ethernet>()?;
+        ethernet.set_src(mac_addr);
+        ethernet.set_dst(self.sender_hardware_addr());
+
+        let mut reply = ethernet.push::<Arp4>()?;
+        reply.set_operation_code(OperationCodes::Reply);
+        reply.set_sender_hardware_addr(mac_addr);
+        reply.set_sender_protocol_addr(self.target_protocol_addr());
+        reply.set_target_hardware_addr(self.sender_hardware_addr());
+        reply.set_target_protocol_addr(self.sender_protocol_addr());
+
+        Ok(reply)
+    }
+}
+
 /// ARP header.
 #[allow(missing_debug_implementations)]
 #[derive(Copy, SizeOf)]
@@ -632,7 +665,6 @@ impl<H: HardwareAddr, P: ProtocolAddr> Default for ArpHeader<H, P> {
 mod tests {
     use super::*;
     use crate::testils::byte_arrays::ARP4_PACKET;
-    use crate::Mbuf;
 
     #[test]
     fn size_of_arp_header() {
@@ -685,4 +717,30 @@ mod tests {
         // make sure the ether type is fixed
         assert_eq!(EtherTypes::Arp, arp4.envelope().ether_type());
     }
+
+    #[capsule::test]
+    fn reply_to_arp_request() {
+        let packet = Mbuf::from_bytes(&ARP4_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let request = ethernet.parse::<Arp4>().unwrap();
+
+        let mac_addr = MacAddr::new(0, 0, 0, 0, 0, 2);
+        let reply = request.reply(mac_addr).unwrap();
+
+        assert_eq!(OperationCodes::Reply, reply.operation_code());
+        assert_eq!(mac_addr, reply.sender_hardware_addr());
+        assert_eq!("139.133.233.2", reply.sender_protocol_addr().to_string());
+        assert_eq!(
+            "00:00:00:00:00:01",
+            reply.target_hardware_addr().to_string()
+        );
+        assert_eq!("139.133.217.110", reply.target_protocol_addr().to_string());
+
+        assert_eq!(mac_addr, reply.envelope().src());
+        assert_eq!(request.sender_hardware_addr(), reply.envelope().dst());
+        assert_eq!(EtherTypes::Arp, reply.envelope().ether_type());
+
+        // can't reply to a reply
+        assert!(reply.reply(mac_addr).is_err());
+    }
 }
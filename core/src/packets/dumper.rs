@@ -0,0 +1,311 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::v6::{Ipv6, Ipv6Packet};
+use crate::packets::ip::{IpPacket, ProtocolNumbers};
+use crate::packets::{EtherTypes, Ethernet, Packet, Tcp, TcpFlags, Udp};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// The maximum number of payload bytes printed for an unknown layer.
+const RAW_BYTES_MAX: usize = 32;
+
+/// The TCP flags and their names, in the order tcpdump prints them.
+const TCP_FLAG_NAMES: [(TcpFlags, &str); 9] = [
+    (TcpFlags::SYN, "SYN"),
+    (TcpFlags::FIN, "FIN"),
+    (TcpFlags::RST, "RST"),
+    (TcpFlags::PSH, "PSH"),
+    (TcpFlags::ACK, "ACK"),
+    (TcpFlags::URG, "URG"),
+    (TcpFlags::ECE, "ECE"),
+    (TcpFlags::CWR, "CWR"),
+    (TcpFlags::NS, "NS"),
+];
+
+/// Returns the bytes of the packet's payload.
+fn payload<T: Packet>(packet: &T) -> &[u8] {
+    packet
+        .mbuf()
+        .read_data_slice::<u8>(packet.payload_offset(), packet.payload_len())
+        .map(|data| unsafe { &*data.as_ptr() })
+        // the payload is empty, which is not a readable offset.
+        .unwrap_or(&[])
+}
+
+/// Human-readable packet dumps for debugging.
+///
+/// # Example
+///
+/// ```
+/// let mut stdout = std::io::stdout();
+/// PacketDumper::dump(&ethernet, &mut stdout)?;
+/// println!("{}", PacketDumper::dump_hex(&ethernet));
+/// ```
+#[derive(Debug)]
+pub struct PacketDumper;
+
+impl PacketDumper {
+    /// Writes a one-line summary of the packet headers, from Ethernet down
+    /// to the transport layer, in the style of `tcpdump -v`.
+    ///
+    /// For example,
+    ///
+    /// ```
+    /// Ethernet src=aa:bb:cc:dd:ee:ff dst=11:22:33:44:55:66 type=IPv4 | IPv4 src=1.2.3.4 dst=5.6.7.8 proto=TCP ttl=64 len=100 | TCP sport=12345 dport=80 seq=1 ack=0 flags=SYN
+    /// ```
+    ///
+    /// The first bytes of an unrecognized layer are printed as hex.
+    pub fn dump<W: Write>(packet: &Ethernet, writer: &mut W) -> io::Result<()> {
+        let mut line = String::new();
+        PacketDumper::format_ethernet(packet, &mut line);
+        writeln!(writer, "{}", line)
+    }
+
+    /// Returns the packet bytes in the style of `xxd`, 16 bytes per line
+    /// with the offset, the hex bytes in groups of two, and the ASCII
+    /// characters side by side.
+    ///
+    /// ```
+    /// 00000000: 0000 0000 0001 0000 0000 0002 0800 4500  ..............E.
+    /// ```
+    pub fn dump_hex(packet: &Ethernet) -> String {
+        let data = packet
+            .mbuf()
+            .read_data_slice::<u8>(packet.offset(), packet.len())
+            .map(|data| unsafe { &*data.as_ptr() })
+            .unwrap_or(&[]);
+
+        let mut out = String::new();
+        for (idx, chunk) in data.chunks(16).enumerate() {
+            let _ = write!(out, "{:08x}:", idx * 16);
+
+            for col in 0..16 {
+                if col % 2 == 0 {
+                    out.push(' ');
+                }
+                match chunk.get(col) {
+                    Some(byte) => {
+                        let _ = write!(out, "{:02x}", byte);
+                    }
+                    None => out.push_str("  "),
+                }
+            }
+
+            out.push_str("  ");
+            out.extend(chunk.iter().map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            }));
+            out.push('\n');
+        }
+
+        out
+    }
+
+    fn format_raw(data: &[u8], out: &mut String) {
+        if data.is_empty() {
+            return;
+        }
+
+        out.push_str(" | raw=");
+        for byte in data.iter().take(RAW_BYTES_MAX) {
+            let _ = write!(out, "{:02x}", byte);
+        }
+        if data.len() > RAW_BYTES_MAX {
+            let _ = write!(out, "... ({} bytes)", data.len());
+        }
+    }
+
+    fn format_ethernet(ethernet: &Ethernet, out: &mut String) {
+        let _ = write!(
+            out,
+            "Ethernet src={} dst={} type={}",
+            ethernet.src(),
+            ethernet.dst(),
+            ethernet.ether_type()
+        );
+        if let Some(vlan_id) = ethernet.outer_vlan_id() {
+            let _ = write!(out, " outer_vlan={}", vlan_id);
+        }
+        if let Some(vlan_id) = ethernet.vlan_id() {
+            let _ = write!(out, " vlan={}", vlan_id);
+        }
+
+        match ethernet.ether_type() {
+            EtherTypes::Ipv4 => match ethernet.peek::<Ipv4>() {
+                Ok(ipv4) => PacketDumper::format_ipv4(&ipv4, out),
+                Err(_) => PacketDumper::format_raw(payload(ethernet), out),
+            },
+            EtherTypes::Ipv6 => match ethernet.peek::<Ipv6>() {
+                Ok(ipv6) => PacketDumper::format_ipv6(&ipv6, out),
+                Err(_) => PacketDumper::format_raw(payload(ethernet), out),
+            },
+            _ => PacketDumper::format_raw(payload(ethernet), out),
+        }
+    }
+
+    fn format_ipv4(ipv4: &Ipv4, out: &mut String) {
+        let _ = write!(
+            out,
+            " | IPv4 src={} dst={} proto={} ttl={} len={}",
+            ipv4.src(),
+            ipv4.dst(),
+            ipv4.protocol(),
+            ipv4.ttl(),
+            ipv4.total_length()
+        );
+        PacketDumper::format_transport(ipv4, out);
+    }
+
+    fn format_ipv6(ipv6: &Ipv6, out: &mut String) {
+        let _ = write!(
+            out,
+            " | IPv6 src={} dst={} next={} hlim={} len={}",
+            ipv6.src(),
+            ipv6.dst(),
+            ipv6.next_header(),
+            ipv6.hop_limit(),
+            ipv6.payload_length()
+        );
+        PacketDumper::format_transport(ipv6, out);
+    }
+
+    fn format_transport<E: IpPacket>(ip: &E, out: &mut String) {
+        match ip.next_protocol() {
+            ProtocolNumbers::Tcp => match ip.peek::<Tcp<E>>() {
+                Ok(tcp) => {
+                    let flags = TCP_FLAG_NAMES
+                        .iter()
+                        .filter(|(flag, _)| tcp.flags().contains(*flag))
+                        .map(|(_, name)| *name)
+                        .collect::<Vec<_>>();
+                    let _ = write!(
+                        out,
+                        " | TCP sport={} dport={} seq={} ack={} flags={}",
+                        tcp.src_port(),
+                        tcp.dst_port(),
+                        tcp.seq_no(),
+                        tcp.ack_no(),
+                        if flags.is_empty() {
+                            "none".to_string()
+                        } else {
+                            flags.join(",")
+                        }
+                    );
+                    PacketDumper::format_raw(payload(&*tcp), out);
+                }
+                Err(_) => PacketDumper::format_raw(payload(ip), out),
+            },
+            ProtocolNumbers::Udp => match ip.peek::<Udp<E>>() {
+                Ok(udp) => {
+                    let _ = write!(
+                        out,
+                        " | UDP sport={} dport={} len={}",
+                        udp.src_port(),
+                        udp.dst_port(),
+                        udp.length()
+                    );
+                    PacketDumper::format_raw(payload(&*udp), out);
+                }
+                Err(_) => PacketDumper::format_raw(payload(ip), out),
+            },
+            _ => PacketDumper::format_raw(payload(ip), out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::{IPV4_TCP_PACKET, IPV4_UDP_PACKET, IPV6_TCP_PACKET};
+    use crate::Mbuf;
+
+    #[capsule::test]
+    fn dump_tcp_packet() {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+
+        let mut out = vec![];
+        PacketDumper::dump(&ethernet, &mut out).unwrap();
+
+        assert_eq!(
+            "Ethernet src=00:00:00:00:00:02 dst=00:00:00:00:00:01 type=IPv4 \
+             | IPv4 src=139.133.217.110 dst=139.133.233.2 proto=TCP ttl=255 len=44 \
+             | TCP sport=36869 dport=23 seq=1913975060 ack=0 flags=SYN\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[capsule::test]
+    fn dump_ipv6_and_udp_packets() {
+        let packet = Mbuf::from_bytes(&IPV6_TCP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let mut out = vec![];
+        PacketDumper::dump(&ethernet, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("| IPv6 src="));
+        assert!(out.contains("| TCP sport="));
+
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let mut out = vec![];
+        PacketDumper::dump(&ethernet, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("| UDP sport="));
+        assert!(out.contains("| raw="));
+    }
+
+    #[capsule::test]
+    fn dump_unknown_ether_type() {
+        let mut bytes = IPV4_TCP_PACKET;
+        bytes[12] = 0x88;
+        bytes[13] = 0xb5;
+        let packet = Mbuf::from_bytes(&bytes).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+
+        let mut out = vec![];
+        PacketDumper::dump(&ethernet, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("type=0x88b5 | raw=4500002c08b84000ff069997"));
+        assert!(out.ends_with("... (44 bytes)\n"));
+    }
+
+    #[capsule::test]
+    fn dump_hex_packet() {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+
+        let hex = PacketDumper::dump_hex(&ethernet);
+        let lines = hex.lines().collect::<Vec<_>>();
+
+        assert_eq!(4, lines.len());
+        assert_eq!(
+            "00000000: 0000 0000 0001 0000 0000 0002 0800 4500  ..............E.",
+            lines[0]
+        );
+        assert_eq!(
+            "00000030: 2238 a92c 0000 0204 05b4                 \"8.,......",
+            lines[3]
+        );
+    }
+}
@@ -21,7 +21,7 @@ use crate::net::MacAddr;
 use crate::packets::types::u16be;
 use crate::packets::{Internal, Packet};
 use crate::{ensure, Mbuf, SizeOf};
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::fmt;
 use std::ptr::NonNull;
 
@@ -31,6 +31,13 @@ const ETH_HEADER_SIZE: usize = 14;
 const VLAN_802_1Q: u16 = 0x8100;
 const VLAN_802_1AD: u16 = 0x88a8;
 
+// Offset of the VLAN tags from the start of the Ethernet header.
+const VLAN_TAG_OFFSET: usize = 12;
+
+// Reserved VLAN identifiers.
+const VLAN_ID_NONE: u16 = 0x000;
+const VLAN_ID_RESERVED: u16 = 0xfff;
+
 /// Ethernet II frame.
 ///
 /// This is an implementation of the Ethernet II frame specified in IEEE
@@ -203,6 +210,123 @@ impl Ethernet {
         self.vlan_marker() == VLAN_802_1AD
     }
 
+    /// Returns the VLAN identifier of the frame.
+    ///
+    /// For double tagged frames, it's the identifier of the inner C-TAG.
+    /// Returns `None` if the frame is not VLAN tagged.
+    #[inline]
+    pub fn vlan_id(&self) -> Option<u16> {
+        self.inner_vlan_tag().map(|tag| tag.identifier())
+    }
+
+    /// Returns the VLAN priority code point of the frame.
+    ///
+    /// For double tagged frames, it's the priority of the inner C-TAG.
+    /// Returns `None` if the frame is not VLAN tagged.
+    #[inline]
+    pub fn vlan_priority(&self) -> Option<u8> {
+        self.inner_vlan_tag().map(|tag| tag.priority())
+    }
+
+    /// Returns the VLAN identifier of the outer S-TAG. Returns `None` if
+    /// the frame is not double tagged.
+    #[inline]
+    pub fn outer_vlan_id(&self) -> Option<u16> {
+        if self.is_qinq() {
+            Some(unsafe { self.header().chunk.qinq.stag.identifier() })
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn inner_vlan_tag(&self) -> Option<VlanTag> {
+        let header = self.header();
+        unsafe {
+            match self.vlan_marker() {
+                VLAN_802_1Q => Some(header.chunk.dot1q.tag),
+                VLAN_802_1AD => Some(header.chunk.qinq.ctag),
+                _ => None,
+            }
+        }
+    }
+
+    /// Inserts a VLAN tag into an untagged frame, making it a Dot1q frame.
+    ///
+    /// `vlan_id` is the 12-bit VLAN identifier and `priority` is the 3-bit
+    /// priority code point.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is already VLAN tagged. Returns an
+    /// error if `vlan_id` is one of the reserved values `0` and `4095`, or
+    /// is out of range, or if `priority` is out of range. Returns an error
+    /// if the buffer does not have enough free space.
+    #[inline]
+    pub fn push_vlan(&mut self, vlan_id: u16, priority: u8) -> Result<()> {
+        ensure!(
+            !self.is_dot1q() && !self.is_qinq(),
+            anyhow!("frame is already VLAN tagged.")
+        );
+        self.insert_vlan_tag(VlanTag::new(VLAN_802_1Q, vlan_id, priority)?)
+    }
+
+    /// Removes the VLAN tag from a Dot1q frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is not Dot1q tagged. For a double
+    /// tagged frame, the outer tag must be removed first with
+    /// `pop_outer_vlan`.
+    #[inline]
+    pub fn pop_vlan(&mut self) -> Result<()> {
+        ensure!(self.is_dot1q(), anyhow!("frame is not Dot1q tagged."));
+        self.remove_vlan_tag()
+    }
+
+    /// Inserts an outer S-TAG into a Dot1q frame, making it a QinQ frame.
+    /// The existing tag becomes the inner C-TAG.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is not Dot1q tagged. Returns an error
+    /// if `vlan_id` is one of the reserved values `0` and `4095`, or is
+    /// out of range, or if `priority` is out of range. Returns an error if
+    /// the buffer does not have enough free space.
+    #[inline]
+    pub fn push_outer_vlan(&mut self, vlan_id: u16, priority: u8) -> Result<()> {
+        ensure!(self.is_dot1q(), anyhow!("frame is not Dot1q tagged."));
+        self.insert_vlan_tag(VlanTag::new(VLAN_802_1AD, vlan_id, priority)?)
+    }
+
+    /// Removes the outer S-TAG from a QinQ frame, leaving a Dot1q frame
+    /// tagged with the inner C-TAG.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is not QinQ tagged.
+    #[inline]
+    pub fn pop_outer_vlan(&mut self) -> Result<()> {
+        ensure!(self.is_qinq(), anyhow!("frame is not QinQ tagged."));
+        self.remove_vlan_tag()
+    }
+
+    /// Inserts the tag right after the source MAC address.
+    #[inline]
+    fn insert_vlan_tag(&mut self, tag: VlanTag) -> Result<()> {
+        let offset = self.offset + VLAN_TAG_OFFSET;
+        self.mbuf_mut().extend(offset, VlanTag::size_of())?;
+        let _ = self.mbuf_mut().write_data(offset, &tag)?;
+        Ok(())
+    }
+
+    /// Removes the tag right after the source MAC address.
+    #[inline]
+    fn remove_vlan_tag(&mut self) -> Result<()> {
+        let offset = self.offset + VLAN_TAG_OFFSET;
+        self.mbuf_mut().shrink(offset, VlanTag::size_of())
+    }
+
     /// Swaps the source MAC address with the destination MAC address.
     #[inline]
     pub fn swap_addresses(&mut self) {
@@ -354,6 +478,19 @@ pub mod EtherTypes {
     pub const Ipv4: EtherType = EtherType(0x0800);
     /// Internet Protocol version 6.
     pub const Ipv6: EtherType = EtherType(0x86DD);
+    /// IEEE 802.1Q VLAN tagged frame.
+    pub const Vlan: EtherType = EtherType(super::VLAN_802_1Q);
+    /// IEEE 802.1ad QinQ double tagged frame.
+    pub const Qinq: EtherType = EtherType(super::VLAN_802_1AD);
+    /// Transparent Ethernet bridging, used by tunnels carrying Ethernet
+    /// frames.
+    pub const Teb: EtherType = EtherType(0x6558);
+    /// Multiprotocol label switching unicast.
+    pub const Mpls: EtherType = EtherType(0x8847);
+    /// IEEE 802.3 slow protocols, such as LACP and link OAM.
+    pub const SlowProtocols: EtherType = EtherType(0x8809);
+    /// IEEE 802.1ag connectivity fault management.
+    pub const Cfm: EtherType = EtherType(0x8902);
 }
 
 impl fmt::Display for EtherType {
@@ -365,6 +502,12 @@ impl fmt::Display for EtherType {
                 EtherTypes::Arp => "ARP".to_string(),
                 EtherTypes::Ipv4 => "IPv4".to_string(),
                 EtherTypes::Ipv6 => "IPv6".to_string(),
+                EtherTypes::Vlan => "802.1Q".to_string(),
+                EtherTypes::Qinq => "802.1ad".to_string(),
+                EtherTypes::Teb => "TEB".to_string(),
+                EtherTypes::Mpls => "MPLS".to_string(),
+                EtherTypes::SlowProtocols => "Slow Protocols".to_string(),
+                EtherTypes::Cfm => "CFM".to_string(),
                 _ => {
                     let t = self.0;
                     format!("0x{:04x}", t)
@@ -384,6 +527,28 @@ struct VlanTag {
 
 #[allow(clippy::trivially_copy_pass_by_ref)]
 impl VlanTag {
+    /// Creates a new VLAN tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `vlan_id` is reserved or out of range, or if
+    /// `priority` is out of range.
+    fn new(tpid: u16, vlan_id: u16, priority: u8) -> Result<VlanTag> {
+        ensure!(
+            vlan_id != VLAN_ID_NONE && vlan_id < VLAN_ID_RESERVED,
+            anyhow!("invalid VLAN identifier {}.", vlan_id)
+        );
+        ensure!(
+            priority < 8,
+            anyhow!("invalid VLAN priority code point {}.", priority)
+        );
+
+        Ok(VlanTag {
+            tpid: tpid.into(),
+            tci: ((u16::from(priority) << 13) | vlan_id).into(),
+        })
+    }
+
     /// Returns the tag protocol identifier, either 802.1q (Dot1q) or 802.1ad (QinQ).
     #[allow(dead_code)]
     #[inline]
@@ -392,7 +557,6 @@ impl VlanTag {
     }
 
     /// Returns the priority code point.
-    #[allow(dead_code)]
     #[inline]
     fn priority(&self) -> u8 {
         let tci: u16 = self.tci.into();
@@ -408,7 +572,6 @@ impl VlanTag {
     }
 
     /// Returns the VLAN identifier.
-    #[allow(dead_code)]
     #[inline]
     fn identifier(&self) -> u16 {
         (self.tci & u16be::from(0x0fff)).into()
@@ -490,6 +653,12 @@ mod tests {
         assert_eq!("ARP", EtherTypes::Arp.to_string());
         assert_eq!("IPv4", EtherTypes::Ipv4.to_string());
         assert_eq!("IPv6", EtherTypes::Ipv6.to_string());
+        assert_eq!("802.1Q", EtherTypes::Vlan.to_string());
+        assert_eq!("802.1ad", EtherTypes::Qinq.to_string());
+        assert_eq!("TEB", EtherTypes::Teb.to_string());
+        assert_eq!("MPLS", EtherTypes::Mpls.to_string());
+        assert_eq!("Slow Protocols", EtherTypes::SlowProtocols.to_string());
+        assert_eq!("CFM", EtherTypes::Cfm.to_string());
         assert_eq!("0x0000", EtherType::new(0).to_string());
     }
 
@@ -564,4 +733,66 @@ mod tests {
         let overflow = ethernet.mbuf().read_data_slice::<u8>(14, 8).unwrap();
         assert_eq!(&data, unsafe { overflow.as_ref() });
     }
+
+    #[capsule::test]
+    fn push_and_pop_vlan() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let mut ethernet = packet.parse::<Ethernet>().unwrap();
+        assert_eq!(None, ethernet.vlan_id());
+
+        ethernet.push_vlan(100, 5).unwrap();
+        assert!(ethernet.is_dot1q());
+        assert_eq!(Some(100), ethernet.vlan_id());
+        assert_eq!(Some(5), ethernet.vlan_priority());
+        assert_eq!(EtherTypes::Ipv4, ethernet.ether_type());
+        assert_eq!(18, ethernet.header_len());
+        assert_eq!(IPV4_UDP_PACKET.len() + 4, ethernet.len());
+
+        // can't tag twice with dot1q
+        assert!(ethernet.push_vlan(200, 0).is_err());
+
+        ethernet.pop_vlan().unwrap();
+        assert!(!ethernet.is_dot1q());
+        assert_eq!(None, ethernet.vlan_id());
+        assert_eq!(EtherTypes::Ipv4, ethernet.ether_type());
+
+        let data = ethernet.mbuf().read_data_slice::<u8>(0, ethernet.len());
+        assert_eq!(&IPV4_UDP_PACKET[..], unsafe { data.unwrap().as_ref() });
+
+        // can't pop an untagged frame
+        assert!(ethernet.pop_vlan().is_err());
+    }
+
+    #[capsule::test]
+    fn push_and_pop_outer_vlan() {
+        let packet = Mbuf::from_bytes(&VLAN_DOT1Q_PACKET).unwrap();
+        let mut ethernet = packet.parse::<Ethernet>().unwrap();
+        let inner_id = ethernet.vlan_id();
+
+        ethernet.push_outer_vlan(300, 0).unwrap();
+        assert!(ethernet.is_qinq());
+        assert_eq!(Some(300), ethernet.outer_vlan_id());
+        assert_eq!(inner_id, ethernet.vlan_id());
+        assert_eq!(EtherTypes::Arp, ethernet.ether_type());
+        assert_eq!(22, ethernet.header_len());
+
+        // must pop the outer tag first
+        assert!(ethernet.pop_vlan().is_err());
+
+        ethernet.pop_outer_vlan().unwrap();
+        assert!(ethernet.is_dot1q());
+        assert_eq!(None, ethernet.outer_vlan_id());
+        assert_eq!(inner_id, ethernet.vlan_id());
+    }
+
+    #[capsule::test]
+    fn push_reserved_vlan_id() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let mut ethernet = packet.parse::<Ethernet>().unwrap();
+
+        assert!(ethernet.push_vlan(0, 0).is_err());
+        assert!(ethernet.push_vlan(4095, 0).is_err());
+        assert!(ethernet.push_vlan(100, 8).is_err());
+        assert!(!ethernet.is_dot1q());
+    }
 }
@@ -0,0 +1,660 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::v6::Ipv6;
+use crate::packets::ip::IpPacket;
+use crate::packets::types::{u16be, u32be};
+use crate::packets::{EtherType, EtherTypes, Ethernet, Internal, Packet, Udp};
+use crate::{ensure, SizeOf};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::net::SocketAddrV4;
+use std::ptr::NonNull;
+
+/// The IANA assigned UDP destination port for Geneve.
+pub const GENEVE_PORT: u16 = 6081;
+
+// Geneve header bitmasks.
+const VERSION: u8 = 0b1100_0000;
+const OPT_LEN: u8 = 0b0011_1111;
+const OAM: u8 = 0b1000_0000;
+const CRITICAL: u8 = 0b0100_0000;
+
+// Geneve option bitmasks.
+const OPTION_CRITICAL: u8 = 0b1000_0000;
+const OPTION_LEN: u8 = 0b0001_1111;
+
+/// A Geneve variable length option.
+///
+/// The `length` is the length of `data` in 4-byte multiples. The high bit
+/// of `type_` is the critical bit, indicating the receiver must drop the
+/// packet if it does not recognize the option.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GeneveOption {
+    /// The namespace of the option type.
+    pub class: u16,
+    /// The option type within the class.
+    pub type_: u8,
+    /// The length of the option data in 4-byte multiples.
+    pub length: u8,
+    /// The option data.
+    pub data: Vec<u8>,
+}
+
+impl GeneveOption {
+    /// Creates a new option.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the length of data is not a multiple of 4, or
+    /// is longer than 124 bytes.
+    pub fn new(class: u16, type_: u8, data: Vec<u8>) -> Result<Self> {
+        ensure!(
+            data.len() % 4 == 0 && data.len() <= OPTION_LEN as usize * 4,
+            anyhow!("invalid Geneve option data length {}.", data.len())
+        );
+
+        Ok(GeneveOption {
+            class,
+            type_,
+            length: (data.len() / 4) as u8,
+            data,
+        })
+    }
+
+    /// Returns whether the critical bit is set.
+    #[inline]
+    pub fn is_critical(&self) -> bool {
+        self.type_ & OPTION_CRITICAL != 0
+    }
+
+    /// Returns the length of the option including the option header.
+    #[inline]
+    fn wire_len(&self) -> usize {
+        GeneveOptionHeader::size_of() + self.length as usize * 4
+    }
+}
+
+/// Parses the options from the raw option bytes.
+fn parse_options(mut data: &[u8]) -> Result<Vec<GeneveOption>> {
+    let mut options = vec![];
+
+    while !data.is_empty() {
+        ensure!(
+            data.len() >= GeneveOptionHeader::size_of(),
+            anyhow!("truncated Geneve option.")
+        );
+
+        let class = u16::from_be_bytes([data[0], data[1]]);
+        let type_ = data[2];
+        let length = data[3] & OPTION_LEN;
+        let end = GeneveOptionHeader::size_of() + length as usize * 4;
+        ensure!(data.len() >= end, anyhow!("truncated Geneve option."));
+
+        options.push(GeneveOption {
+            class,
+            type_,
+            length,
+            data: data[GeneveOptionHeader::size_of()..end].to_vec(),
+        });
+        data = &data[end..];
+    }
+
+    Ok(options)
+}
+
+/// The fields of a Geneve header, detached from the packet.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GeneveHeader {
+    vni: u32,
+    protocol_type: EtherType,
+    oam: bool,
+    options: Vec<GeneveOption>,
+}
+
+impl GeneveHeader {
+    /// Returns the virtual network identifier.
+    #[inline]
+    pub fn vni(&self) -> u32 {
+        self.vni
+    }
+
+    /// Returns the protocol type of the payload.
+    #[inline]
+    pub fn protocol_type(&self) -> EtherType {
+        self.protocol_type
+    }
+
+    /// Returns whether the packet is an OAM control packet.
+    #[inline]
+    pub fn oam(&self) -> bool {
+        self.oam
+    }
+
+    /// Returns the variable length options.
+    #[inline]
+    pub fn options(&self) -> &[GeneveOption] {
+        &self.options
+    }
+}
+
+/// Generic Network Virtualization Encapsulation based on [IETF RFC 8926].
+///
+/// ```
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |Ver|  Opt Len  |O|C|    Rsvd.  |          Protocol Type        |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |        Virtual Network Identifier (VNI)       |    Reserved   |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                                                               |
+/// ~                    Variable-Length Options                    ~
+/// |                                                               |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// - *Version (Ver)*: (2 bits)
+///   The current version number is 0.
+///
+/// - *Option Length (Opt Len)*: (6 bits)
+///   The length of the option fields, expressed in 4-byte multiples,
+///   not including the 8-byte fixed tunnel header.
+///
+/// - *O (1 bit)*:
+///   Control packet. This packet contains a control message.
+///
+/// - *C (1 bit)*:
+///   Critical options present. One or more options has the critical
+///   bit set.
+///
+/// - *Protocol Type*: (16 bits)
+///   The type of protocol data unit appearing after the Geneve header,
+///   using the Ethernet type values.
+///
+/// - *Virtual Network Identifier (VNI)*: (24 bits)
+///   An identifier for a unique element of a virtual network.
+///
+/// - *Variable-Length Options*: each option is a 4-byte option header
+///   followed by zero or more 4-byte multiples of option data.
+///
+/// ```
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |          Option Class         |      Type     |R|R|R| Length  |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                 Variable-Length Option Data                   |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// [IETF RFC 8926]: https://tools.ietf.org/html/rfc8926
+pub struct Geneve<E: IpPacket> {
+    envelope: Udp<E>,
+    header: NonNull<GeneveBaseHeader>,
+    offset: usize,
+}
+
+impl<E: IpPacket> Geneve<E> {
+    #[inline]
+    fn header(&self) -> &GeneveBaseHeader {
+        unsafe { self.header.as_ref() }
+    }
+
+    #[inline]
+    fn header_mut(&mut self) -> &mut GeneveBaseHeader {
+        unsafe { self.header.as_mut() }
+    }
+
+    /// Returns the version.
+    #[inline]
+    pub fn version(&self) -> u8 {
+        (self.header().ver_opt_len & VERSION) >> 6
+    }
+
+    /// Returns the length of the options in bytes.
+    #[inline]
+    pub fn options_len(&self) -> usize {
+        (self.header().ver_opt_len & OPT_LEN) as usize * 4
+    }
+
+    /// Returns whether the packet is an OAM control packet.
+    #[inline]
+    pub fn oam(&self) -> bool {
+        self.header().flags & OAM != 0
+    }
+
+    /// Sets whether the packet is an OAM control packet.
+    #[inline]
+    pub fn set_oam(&mut self, oam: bool) {
+        if oam {
+            self.header_mut().flags |= OAM;
+        } else {
+            self.header_mut().flags &= !OAM;
+        }
+    }
+
+    /// Returns whether any option has the critical bit set.
+    #[inline]
+    pub fn critical(&self) -> bool {
+        self.header().flags & CRITICAL != 0
+    }
+
+    /// Returns the protocol type of the payload.
+    #[inline]
+    pub fn protocol_type(&self) -> EtherType {
+        EtherType::new(self.header().protocol_type.into())
+    }
+
+    /// Sets the protocol type of the payload.
+    #[inline]
+    pub fn set_protocol_type(&mut self, protocol_type: EtherType) {
+        self.header_mut().protocol_type = protocol_type.0.into();
+    }
+
+    /// Returns the virtual network identifier.
+    #[inline]
+    pub fn vni(&self) -> u32 {
+        u32::from(self.header().vni_reserved) >> 8
+    }
+
+    /// Sets the virtual network identifier. Only the lower 24 bits are
+    /// used.
+    #[inline]
+    pub fn set_vni(&mut self, vni: u32) {
+        self.header_mut().vni_reserved = ((vni & 0x00ff_ffff) << 8).into();
+    }
+
+    /// Reads and parses the options from the buffer.
+    fn read_options(&self) -> Result<Vec<GeneveOption>> {
+        let len = self.options_len();
+        if len == 0 {
+            return Ok(vec![]);
+        }
+
+        let data = self
+            .mbuf()
+            .read_data_slice::<u8>(self.offset + GeneveBaseHeader::size_of(), len)?;
+        parse_options(unsafe { data.as_ref() })
+    }
+
+    /// Returns the variable length options.
+    pub fn options(&self) -> Vec<GeneveOption> {
+        // the options are validated when the packet is parsed.
+        self.read_options().unwrap_or_default()
+    }
+
+    /// Appends an option after the existing options. The critical flag is
+    /// set if the option is critical.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the option length does not match its data, or
+    /// the options would exceed the 252 bytes the option length can
+    /// express, or if the buffer does not have enough free space.
+    pub fn push_option(&mut self, option: &GeneveOption) -> Result<()> {
+        ensure!(
+            option.length <= OPTION_LEN && option.data.len() == option.length as usize * 4,
+            anyhow!("invalid Geneve option length {}.", option.length)
+        );
+
+        let len = option.wire_len();
+        let options_len = self.options_len() + len;
+        ensure!(
+            options_len <= OPT_LEN as usize * 4,
+            anyhow!("Geneve options too long.")
+        );
+
+        let offset = self.offset + GeneveBaseHeader::size_of() + self.options_len();
+        let header = GeneveOptionHeader {
+            class: option.class.into(),
+            type_: option.type_,
+            length: option.length,
+        };
+
+        let mbuf = self.mbuf_mut();
+        mbuf.extend(offset, len)?;
+        mbuf.write_data(offset, &header)?;
+        if !option.data.is_empty() {
+            mbuf.write_data_slice(offset + GeneveOptionHeader::size_of(), &option.data)?;
+        }
+
+        let ver_opt_len = self.header().ver_opt_len;
+        self.header_mut().ver_opt_len = (ver_opt_len & VERSION) | (options_len / 4) as u8;
+        if option.is_critical() {
+            self.header_mut().flags |= CRITICAL;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the tunnel headers and returns the inner Ethernet frame and
+    /// the Geneve header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload is not an Ethernet frame.
+    pub fn decapsulate(self) -> Result<(Ethernet, GeneveHeader)> {
+        ensure!(
+            self.protocol_type() == EtherTypes::Teb,
+            anyhow!("not an Ethernet payload.")
+        );
+
+        let header = GeneveHeader {
+            vni: self.vni(),
+            protocol_type: self.protocol_type(),
+            oam: self.oam(),
+            options: self.options(),
+        };
+
+        let len = self.payload_offset();
+        let mut mbuf = self.reset();
+        mbuf.shrink(0, len)?;
+        Ok((mbuf.parse::<Ethernet>()?, header))
+    }
+}
+
+impl Geneve<Ipv4> {
+    /// Encapsulates the Ethernet frame in a Geneve tunnel between
+    /// `outer_src` and `outer_dst`, with the options. The outer Ethernet
+    /// addresses are not set.
+    ///
+    /// The destination port should be `GENEVE_PORT` for the packet to be
+    /// recognized by the receiver.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the options are too long, or if the buffer does
+    /// not have enough free space.
+    pub fn encapsulate(
+        inner: Ethernet,
+        vni: u32,
+        outer_src: SocketAddrV4,
+        outer_dst: SocketAddrV4,
+        options: &[GeneveOption],
+    ) -> Result<Self> {
+        let mut ipv4 = inner.reset().push::<Ethernet>()?.push::<Ipv4>()?;
+        ipv4.set_src(*outer_src.ip());
+        ipv4.set_dst(*outer_dst.ip());
+
+        let mut udp = ipv4.push::<Udp<Ipv4>>()?;
+        udp.set_src_port(outer_src.port());
+
+        let mut geneve = udp.push::<Geneve<Ipv4>>()?;
+        geneve.envelope_mut().set_dst_port(outer_dst.port());
+        geneve.set_vni(vni);
+        for option in options {
+            geneve.push_option(option)?;
+        }
+        geneve.reconcile_all();
+
+        Ok(geneve)
+    }
+}
+
+impl<E: IpPacket> fmt::Debug for Geneve<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("geneve")
+            .field("version", &self.version())
+            .field("oam", &self.oam())
+            .field("critical", &self.critical())
+            .field("protocol_type", &format!("{}", self.protocol_type()))
+            .field("vni", &self.vni())
+            .field("options", &self.options())
+            .field("$offset", &self.offset())
+            .field("$len", &self.len())
+            .field("$header_len", &self.header_len())
+            .finish()
+    }
+}
+
+impl<E: IpPacket> Packet for Geneve<E> {
+    /// The preceding packet type for a Geneve packet must be an UDP packet.
+    type Envelope = Udp<E>;
+
+    #[inline]
+    fn envelope(&self) -> &Self::Envelope {
+        &self.envelope
+    }
+
+    #[inline]
+    fn envelope_mut(&mut self) -> &mut Self::Envelope {
+        &mut self.envelope
+    }
+
+    #[inline]
+    fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the length of the packet header.
+    ///
+    /// The length of the Geneve header depends on the options.
+    #[inline]
+    fn header_len(&self) -> usize {
+        GeneveBaseHeader::size_of() + self.options_len()
+    }
+
+    #[inline]
+    unsafe fn clone(&self, internal: Internal) -> Self {
+        Geneve::<E> {
+            envelope: self.envelope.clone(internal),
+            header: self.header,
+            offset: self.offset,
+        }
+    }
+
+    /// Parses the UDP packet's payload as a Geneve packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the UDP destination port is not
+    /// [`GENEVE_PORT`], or the version is not `0`. Returns an error if the
+    /// payload does not have sufficient data for the Geneve header and the
+    /// options, or the options are malformed.
+    ///
+    /// [`GENEVE_PORT`]: GENEVE_PORT
+    #[inline]
+    fn try_parse(envelope: Self::Envelope, _internal: Internal) -> Result<Self> {
+        ensure!(
+            envelope.dst_port() == GENEVE_PORT,
+            anyhow!("not a Geneve packet.")
+        );
+
+        let mbuf = envelope.mbuf();
+        let offset = envelope.payload_offset();
+        let header = mbuf.read_data(offset)?;
+
+        let packet = Geneve {
+            envelope,
+            header,
+            offset,
+        };
+
+        ensure!(
+            packet.version() == 0,
+            anyhow!("invalid Geneve version {}.", packet.version())
+        );
+
+        let _ = packet.read_options()?;
+
+        Ok(packet)
+    }
+
+    /// Prepends a Geneve packet to the beginning of the UDP packet's
+    /// payload.
+    ///
+    /// The UDP destination port is set to [`GENEVE_PORT`] and the protocol
+    /// type is set to [`EtherTypes::Teb`]. The Geneve header has no
+    /// options.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer does not have enough free space.
+    ///
+    /// [`GENEVE_PORT`]: GENEVE_PORT
+    /// [`EtherTypes::Teb`]: crate::packets::EtherTypes::Teb
+    #[inline]
+    fn try_push(mut envelope: Self::Envelope, _internal: Internal) -> Result<Self> {
+        let offset = envelope.payload_offset();
+        let mbuf = envelope.mbuf_mut();
+
+        mbuf.extend(offset, GeneveBaseHeader::size_of())?;
+        let header = mbuf.write_data(offset, &GeneveBaseHeader::default())?;
+
+        envelope.set_dst_port(GENEVE_PORT);
+
+        Ok(Geneve {
+            envelope,
+            header,
+            offset,
+        })
+    }
+
+    #[inline]
+    fn deparse(self) -> Self::Envelope {
+        self.envelope
+    }
+}
+
+/// A type alias for an IPv4 Geneve packet.
+pub type Geneve4 = Geneve<Ipv4>;
+
+/// A type alias for an IPv6 Geneve packet.
+pub type Geneve6 = Geneve<Ipv6>;
+
+/// Geneve fixed header.
+#[derive(Clone, Copy, Debug, SizeOf)]
+#[repr(C)]
+struct GeneveBaseHeader {
+    ver_opt_len: u8,
+    flags: u8,
+    protocol_type: u16be,
+    vni_reserved: u32be,
+}
+
+impl Default for GeneveBaseHeader {
+    fn default() -> Self {
+        GeneveBaseHeader {
+            ver_opt_len: 0,
+            flags: 0,
+            protocol_type: EtherTypes::Teb.0.into(),
+            vni_reserved: u32be::default(),
+        }
+    }
+}
+
+/// Geneve option header.
+#[derive(Clone, Copy, Debug, Default, SizeOf)]
+#[repr(C)]
+struct GeneveOptionHeader {
+    class: u16be,
+    type_: u8,
+    length: u8,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_TCP_PACKET;
+    use crate::Mbuf;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn size_of_geneve_header() {
+        assert_eq!(8, GeneveBaseHeader::size_of());
+        assert_eq!(4, GeneveOptionHeader::size_of());
+    }
+
+    #[test]
+    fn invalid_option_length() {
+        assert!(GeneveOption::new(0x0102, 0x80, vec![0; 3]).is_err());
+        assert!(GeneveOption::new(0x0102, 0x80, vec![0; 128]).is_err());
+        assert_eq!(
+            2,
+            GeneveOption::new(0x0102, 0x80, vec![0; 8]).unwrap().length
+        );
+    }
+
+    #[capsule::test]
+    fn push_geneve_packet() {
+        let packet = Mbuf::new().unwrap();
+        let ethernet = packet.push::<Ethernet>().unwrap();
+        let ipv4 = ethernet.push::<Ipv4>().unwrap();
+        let udp = ipv4.push::<Udp<Ipv4>>().unwrap();
+        let mut geneve = udp.push::<Geneve4>().unwrap();
+
+        assert_eq!(8, geneve.len());
+        assert_eq!(GENEVE_PORT, geneve.envelope().dst_port());
+        assert_eq!(EtherTypes::Teb, geneve.protocol_type());
+        assert_eq!(0, geneve.version());
+
+        geneve.set_vni(0x12_3456);
+        assert_eq!(0x12_3456, geneve.vni());
+
+        let option = GeneveOption::new(0x0102, 0x01, vec![1, 2, 3, 4]).unwrap();
+        geneve.push_option(&option).unwrap();
+        assert_eq!(16, geneve.header_len());
+        assert_eq!(vec![option], geneve.options());
+        assert!(!geneve.critical());
+    }
+
+    #[capsule::test]
+    fn encapsulate_and_decapsulate() {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let inner = packet.parse::<Ethernet>().unwrap();
+        let inner_dst = inner.dst();
+
+        let options = vec![
+            GeneveOption::new(0x0102, 0x81, vec![0xaa; 4]).unwrap(),
+            GeneveOption::new(0xffff, 0x02, vec![]).unwrap(),
+        ];
+        let src = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 50000);
+        let dst = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), GENEVE_PORT);
+        let geneve = Geneve4::encapsulate(inner, 100, src, dst, &options).unwrap();
+
+        assert_eq!(100, geneve.vni());
+        assert!(geneve.critical());
+        assert_eq!(50000, geneve.envelope().src_port());
+        assert_eq!(*dst.ip(), geneve.envelope().envelope().dst());
+
+        let mbuf = geneve.reset();
+        assert_eq!(
+            IPV4_TCP_PACKET.len() + 14 + 20 + 8 + 8 + 12,
+            mbuf.data_len()
+        );
+
+        let geneve = mbuf
+            .parse::<Ethernet>()
+            .unwrap()
+            .parse::<Ipv4>()
+            .unwrap()
+            .parse::<Udp<Ipv4>>()
+            .unwrap()
+            .parse::<Geneve4>()
+            .unwrap();
+        let (inner, header) = geneve.decapsulate().unwrap();
+
+        assert_eq!(100, header.vni());
+        assert_eq!(EtherTypes::Teb, header.protocol_type());
+        assert_eq!(&options[..], header.options());
+        assert_eq!(inner_dst, inner.dst());
+        assert_eq!(IPV4_TCP_PACKET.len(), inner.mbuf().data_len());
+    }
+}
@@ -0,0 +1,585 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::v6::Ipv6;
+use crate::packets::ip::{IpPacket, ProtocolNumbers};
+use crate::packets::types::{u16be, u32be};
+use crate::packets::{checksum, EtherType, EtherTypes, Ethernet, Internal, Packet};
+use crate::{ensure, SizeOf};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::net::Ipv4Addr;
+use std::ptr::NonNull;
+
+// GRE flag bitmasks.
+const CHECKSUM_PRESENT: u16 = 0b1000_0000_0000_0000;
+const KEY_PRESENT: u16 = 0b0010_0000_0000_0000;
+const SEQUENCE_PRESENT: u16 = 0b0001_0000_0000_0000;
+const VERSION: u16 = 0b0000_0000_0000_0111;
+
+/// Generic Routing Encapsulation based on [IETF RFC 2784] and the key and
+/// sequence number extensions in [IETF RFC 2890].
+///
+/// ```
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |C| |K|S| Reserved0       | Ver |         Protocol Type         |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |      Checksum (optional)      |       Reserved1 (Optional)    |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                         Key (optional)                        |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                 Sequence Number (Optional)                    |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// - *Checksum Present*: (bit 0)
+///   If the Checksum Present bit is set to one, then the Checksum and
+///   the Reserved1 fields are present and the Checksum field contains
+///   valid information.
+///
+/// - *Key Present*: (bit 2)
+///   If the Key Present bit is set to 1, then it indicates that the
+///   Key field is present in the GRE header.
+///
+/// - *Sequence Number Present*: (bit 3)
+///   If the Sequence Number Present bit is set to 1, then it indicates
+///   that the Sequence Number field is present.
+///
+/// - *Version Number*: (bits 13-15)
+///   The Version Number field MUST contain the value zero.
+///
+/// - *Protocol Type*: (2 octets)
+///   The Protocol Type field contains the protocol type of the payload
+///   packet. These Protocol Types are defined as "ETHER TYPES".
+///
+/// - *Checksum*: (2 octets)
+///   The Checksum field contains the IP (one's complement) checksum sum
+///   of the all the 16 bit words in the GRE header and the payload
+///   packet.
+///
+/// - *Key*: (4 octets)
+///   The Key field contains a four octet number which was inserted by
+///   the encapsulator. It may be used by the receiver to authenticate
+///   the source of the packet, or to identify an individual traffic
+///   flow within a tunnel.
+///
+/// - *Sequence Number*: (4 octets)
+///   The Sequence Number field contains an unsigned 32 bit integer
+///   which is inserted by the encapsulator. It may be used by the
+///   receiver to establish the order in which packets have been
+///   transmitted from the encapsulator to the receiver.
+///
+/// [IETF RFC 2784]: https://tools.ietf.org/html/rfc2784
+/// [IETF RFC 2890]: https://tools.ietf.org/html/rfc2890
+pub struct Gre<E: IpPacket> {
+    envelope: E,
+    header: NonNull<GreHeader>,
+    offset: usize,
+}
+
+impl<E: IpPacket> Gre<E> {
+    #[inline]
+    fn header(&self) -> &GreHeader {
+        unsafe { self.header.as_ref() }
+    }
+
+    #[inline]
+    fn header_mut(&mut self) -> &mut GreHeader {
+        unsafe { self.header.as_mut() }
+    }
+
+    #[inline]
+    fn flags(&self) -> u16 {
+        self.header().flags_version.into()
+    }
+
+    #[inline]
+    fn set_flags(&mut self, flags: u16) {
+        self.header_mut().flags_version = flags.into();
+    }
+
+    /// Returns a flag indicating whether the checksum field is present.
+    #[inline]
+    pub fn checksum_present(&self) -> bool {
+        self.flags() & CHECKSUM_PRESENT != 0
+    }
+
+    /// Returns a flag indicating whether the key field is present.
+    #[inline]
+    pub fn key_present(&self) -> bool {
+        self.flags() & KEY_PRESENT != 0
+    }
+
+    /// Returns a flag indicating whether the sequence number field is
+    /// present.
+    #[inline]
+    pub fn sequence_present(&self) -> bool {
+        self.flags() & SEQUENCE_PRESENT != 0
+    }
+
+    /// Returns the version number.
+    #[inline]
+    pub fn version(&self) -> u16 {
+        self.flags() & VERSION
+    }
+
+    /// Returns the protocol type of the payload.
+    #[inline]
+    pub fn protocol_type(&self) -> EtherType {
+        EtherType::new(self.header().protocol_type.into())
+    }
+
+    /// Sets the protocol type of the payload.
+    #[inline]
+    pub fn set_protocol_type(&mut self, protocol_type: EtherType) {
+        self.header_mut().protocol_type = protocol_type.0.into();
+    }
+
+    /// Returns the offset of the checksum field.
+    #[inline]
+    fn checksum_offset(&self) -> usize {
+        self.offset + GreHeader::size_of()
+    }
+
+    /// Returns the offset of the key field.
+    #[inline]
+    fn key_offset(&self) -> usize {
+        if self.checksum_present() {
+            self.checksum_offset() + GreOption::size_of()
+        } else {
+            self.checksum_offset()
+        }
+    }
+
+    /// Returns the offset of the sequence number field.
+    #[inline]
+    fn sequence_offset(&self) -> usize {
+        if self.key_present() {
+            self.key_offset() + GreOption::size_of()
+        } else {
+            self.key_offset()
+        }
+    }
+
+    #[inline]
+    fn option(&self, offset: usize) -> u32 {
+        // the header length is validated when the packet is parsed. the
+        // option exists if the flag is set.
+        let option = self.mbuf().read_data::<GreOption>(offset).unwrap();
+        unsafe { option.as_ref().value.into() }
+    }
+
+    #[inline]
+    fn set_option(&mut self, offset: usize, value: u32) {
+        let mut option = self.mbuf().read_data::<GreOption>(offset).unwrap();
+        unsafe { option.as_mut().value = value.into() }
+    }
+
+    /// Adds or removes an optional field and updates the flag accordingly.
+    fn resize_option(&mut self, flag: u16, offset: usize, value: Option<u32>) -> Result<()> {
+        let present = self.flags() & flag != 0;
+        match value {
+            Some(value) => {
+                if !present {
+                    self.mbuf_mut().extend(offset, GreOption::size_of())?;
+                    self.set_flags(self.flags() | flag);
+                }
+                self.set_option(offset, value);
+            }
+            None => {
+                if present {
+                    self.mbuf_mut().shrink(offset, GreOption::size_of())?;
+                    self.set_flags(self.flags() & !flag);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the checksum if present.
+    #[inline]
+    pub fn checksum(&self) -> Option<u16> {
+        if self.checksum_present() {
+            Some((self.option(self.checksum_offset()) >> 16) as u16)
+        } else {
+            None
+        }
+    }
+
+    /// Turns the checksum on or off. When on, the checksum is computed
+    /// when the packet is reconciled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer does not have enough free space.
+    #[inline]
+    pub fn set_checksum_present(&mut self, present: bool) -> Result<()> {
+        let offset = self.checksum_offset();
+        self.resize_option(
+            CHECKSUM_PRESENT,
+            offset,
+            if present { Some(0) } else { None },
+        )
+    }
+
+    /// Returns the key if present.
+    #[inline]
+    pub fn key(&self) -> Option<u32> {
+        if self.key_present() {
+            Some(self.option(self.key_offset()))
+        } else {
+            None
+        }
+    }
+
+    /// Sets the key. Use `None` to remove the key field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer does not have enough free space.
+    #[inline]
+    pub fn set_key(&mut self, key: Option<u32>) -> Result<()> {
+        let offset = self.key_offset();
+        self.resize_option(KEY_PRESENT, offset, key)
+    }
+
+    /// Returns the sequence number if present.
+    #[inline]
+    pub fn sequence_number(&self) -> Option<u32> {
+        if self.sequence_present() {
+            Some(self.option(self.sequence_offset()))
+        } else {
+            None
+        }
+    }
+
+    /// Sets the sequence number. Use `None` to remove the sequence number
+    /// field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer does not have enough free space.
+    #[inline]
+    pub fn set_sequence_number(&mut self, seq_no: Option<u32>) -> Result<()> {
+        let offset = self.sequence_offset();
+        self.resize_option(SEQUENCE_PRESENT, offset, seq_no)
+    }
+
+    /// Recomputes the checksum over the GRE header and the payload if the
+    /// checksum is present.
+    #[inline]
+    pub fn compute_checksum(&mut self) {
+        if self.checksum_present() {
+            let offset = self.checksum_offset();
+            self.set_option(offset, 0);
+
+            if let Ok(data) = self.mbuf().read_data_slice(self.offset, self.len()) {
+                let data = unsafe { data.as_ref() };
+                let checksum = checksum::compute(0, data);
+                self.set_option(offset, (checksum as u32) << 16);
+            } else {
+                // we are reading till the end of buffer, should never run out
+                unreachable!()
+            }
+        }
+    }
+
+    /// Removes the tunnel headers and returns the inner Ethernet frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload is not an Ethernet frame.
+    pub fn decapsulate(self) -> Result<Ethernet> {
+        ensure!(
+            self.protocol_type() == EtherTypes::Teb,
+            anyhow!("not an Ethernet payload.")
+        );
+
+        let len = self.payload_offset();
+        let mut mbuf = self.reset();
+        mbuf.shrink(0, len)?;
+        mbuf.parse::<Ethernet>()
+    }
+}
+
+impl Gre<Ipv4> {
+    /// Encapsulates the Ethernet frame in a GRE tunnel between `src` and
+    /// `dst`. The outer Ethernet addresses are not set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer does not have enough free space.
+    pub fn encapsulate(
+        inner: Ethernet,
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        key: Option<u32>,
+    ) -> Result<Self> {
+        let mut ipv4 = inner.reset().push::<Ethernet>()?.push::<Ipv4>()?;
+        ipv4.set_src(src);
+        ipv4.set_dst(dst);
+
+        let mut gre = ipv4.push::<Gre<Ipv4>>()?;
+        gre.set_protocol_type(EtherTypes::Teb);
+        gre.set_key(key)?;
+        gre.reconcile_all();
+
+        Ok(gre)
+    }
+}
+
+impl<E: IpPacket> fmt::Debug for Gre<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("gre");
+        d.field("protocol_type", &format!("{}", self.protocol_type()))
+            .field("version", &self.version());
+        if let Some(checksum) = self.checksum() {
+            d.field("checksum", &format!("0x{:04x}", checksum));
+        }
+        if let Some(key) = self.key() {
+            d.field("key", &key);
+        }
+        if let Some(seq_no) = self.sequence_number() {
+            d.field("sequence_number", &seq_no);
+        }
+        d.field("$offset", &self.offset())
+            .field("$len", &self.len())
+            .field("$header_len", &self.header_len())
+            .finish()
+    }
+}
+
+impl<E: IpPacket> Packet for Gre<E> {
+    /// The preceding packet type for a GRE packet can be either an [IPv4]
+    /// packet, an [IPv6] packet, or any IPv6 extension packets.
+    ///
+    /// [IPv4]: crate::packets::ip::v4::Ipv4
+    /// [IPv6]: crate::packets::ip::v6::Ipv6
+    type Envelope = E;
+
+    #[inline]
+    fn envelope(&self) -> &Self::Envelope {
+        &self.envelope
+    }
+
+    #[inline]
+    fn envelope_mut(&mut self) -> &mut Self::Envelope {
+        &mut self.envelope
+    }
+
+    #[inline]
+    fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the length of the packet header.
+    ///
+    /// The length of the GRE header depends on the optional fields.
+    #[inline]
+    fn header_len(&self) -> usize {
+        if self.sequence_present() {
+            self.sequence_offset() + GreOption::size_of() - self.offset
+        } else {
+            self.sequence_offset() - self.offset
+        }
+    }
+
+    #[inline]
+    unsafe fn clone(&self, internal: Internal) -> Self {
+        Gre::<E> {
+            envelope: self.envelope.clone(internal),
+            header: self.header,
+            offset: self.offset,
+        }
+    }
+
+    /// Parses the envelope's payload as a GRE packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the envelope's next protocol is not set to
+    /// [`ProtocolNumbers::Gre`]. Returns an error if the version is not
+    /// `0`, or the payload does not have sufficient data for the GRE
+    /// header and the optional fields.
+    ///
+    /// [`ProtocolNumbers::Gre`]: crate::packets::ip::ProtocolNumbers::Gre
+    #[inline]
+    fn try_parse(envelope: Self::Envelope, _internal: Internal) -> Result<Self> {
+        ensure!(
+            envelope.next_protocol() == ProtocolNumbers::Gre,
+            anyhow!("not a GRE packet.")
+        );
+
+        let mbuf = envelope.mbuf();
+        let offset = envelope.payload_offset();
+        let header = mbuf.read_data(offset)?;
+
+        let packet = Gre {
+            envelope,
+            header,
+            offset,
+        };
+
+        ensure!(
+            packet.version() == 0,
+            anyhow!("invalid GRE version {}.", packet.version())
+        );
+        let _ = packet
+            .mbuf()
+            .read_data_slice::<u8>(offset, packet.header_len())?;
+
+        Ok(packet)
+    }
+
+    /// Prepends a GRE packet to the beginning of the envelope's payload.
+    ///
+    /// The envelope's next protocol is set to [`ProtocolNumbers::Gre`].
+    /// The GRE header has no optional fields.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer does not have enough free space.
+    ///
+    /// [`ProtocolNumbers::Gre`]: crate::packets::ip::ProtocolNumbers::Gre
+    #[inline]
+    fn try_push(mut envelope: Self::Envelope, _internal: Internal) -> Result<Self> {
+        let offset = envelope.payload_offset();
+        let mbuf = envelope.mbuf_mut();
+
+        mbuf.extend(offset, GreHeader::size_of())?;
+        let header = mbuf.write_data(offset, &GreHeader::default())?;
+
+        envelope.set_next_protocol(ProtocolNumbers::Gre);
+
+        Ok(Gre {
+            envelope,
+            header,
+            offset,
+        })
+    }
+
+    #[inline]
+    fn deparse(self) -> Self::Envelope {
+        self.envelope
+    }
+
+    /// Reconciles the derivable header fields against the changes made to
+    /// the packet.
+    ///
+    /// * [`checksum`] is computed over the GRE header and the payload if
+    ///   present.
+    ///
+    /// [`checksum`]: Gre::checksum
+    #[inline]
+    fn reconcile(&mut self) {
+        self.compute_checksum();
+    }
+}
+
+/// A type alias for an IPv4 GRE packet.
+pub type Gre4 = Gre<Ipv4>;
+
+/// A type alias for an IPv6 GRE packet.
+pub type Gre6 = Gre<Ipv6>;
+
+/// GRE header.
+#[derive(Clone, Copy, Debug, Default, SizeOf)]
+#[repr(C)]
+struct GreHeader {
+    flags_version: u16be,
+    protocol_type: u16be,
+}
+
+/// GRE optional field.
+#[derive(Clone, Copy, Debug, Default, SizeOf)]
+#[repr(C)]
+struct GreOption {
+    value: u32be,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+    use crate::Mbuf;
+
+    #[test]
+    fn size_of_gre_header() {
+        assert_eq!(4, GreHeader::size_of());
+    }
+
+    #[capsule::test]
+    fn push_gre_packet() {
+        let packet = Mbuf::new().unwrap();
+        let ethernet = packet.push::<Ethernet>().unwrap();
+        let ipv4 = ethernet.push::<Ipv4>().unwrap();
+        let mut gre = ipv4.push::<Gre4>().unwrap();
+
+        assert_eq!(4, gre.len());
+        assert_eq!(ProtocolNumbers::Gre, gre.envelope().protocol());
+        assert_eq!(None, gre.key());
+
+        gre.set_key(Some(0x1234)).unwrap();
+        gre.set_sequence_number(Some(7)).unwrap();
+        gre.set_checksum_present(true).unwrap();
+        gre.reconcile_all();
+
+        assert_eq!(16, gre.header_len());
+        assert_eq!(Some(0x1234), gre.key());
+        assert_eq!(Some(7), gre.sequence_number());
+        assert!(gre.checksum().is_some());
+
+        gre.set_key(None).unwrap();
+        assert_eq!(12, gre.header_len());
+        assert_eq!(None, gre.key());
+        assert_eq!(Some(7), gre.sequence_number());
+    }
+
+    #[capsule::test]
+    fn encapsulate_and_decapsulate() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let inner = packet.parse::<Ethernet>().unwrap();
+        let inner_src = inner.src();
+
+        let src = Ipv4Addr::new(10, 0, 0, 1);
+        let dst = Ipv4Addr::new(10, 0, 0, 2);
+        let gre = Gre4::encapsulate(inner, src, dst, Some(42)).unwrap();
+
+        assert_eq!(src, gre.envelope().src());
+        assert_eq!(EtherTypes::Teb, gre.protocol_type());
+        assert_eq!(Some(42), gre.key());
+
+        let mbuf = gre.reset();
+        assert_eq!(IPV4_UDP_PACKET.len() + 14 + 20 + 8, mbuf.data_len());
+
+        let gre = mbuf
+            .parse::<Ethernet>()
+            .unwrap()
+            .parse::<Ipv4>()
+            .unwrap()
+            .parse::<Gre4>()
+            .unwrap();
+        let inner = gre.decapsulate().unwrap();
+
+        assert_eq!(inner_src, inner.src());
+        assert_eq!(IPV4_UDP_PACKET.len(), inner.mbuf().data_len());
+    }
+}
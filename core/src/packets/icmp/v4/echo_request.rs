@@ -16,10 +16,13 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
-use crate::packets::icmp::v4::{Icmpv4, Icmpv4Message, Icmpv4Packet, Icmpv4Type, Icmpv4Types};
+use crate::packets::icmp::v4::{
+    EchoReply, Icmpv4, Icmpv4Message, Icmpv4Packet, Icmpv4Type, Icmpv4Types,
+};
+use crate::packets::ip::v4::Ipv4;
 use crate::packets::types::u16be;
-use crate::packets::{Internal, Packet};
-use crate::SizeOf;
+use crate::packets::{Ethernet, Internal, Packet};
+use crate::{Mbuf, SizeOf};
 use anyhow::Result;
 use std::fmt;
 use std::ptr::NonNull;
@@ -127,6 +130,39 @@ impl EchoRequest {
         self.icmp_mut().mbuf_mut().write_data_slice(offset, data)?;
         Ok(())
     }
+
+    /// Builds a new echo reply to this request.
+    ///
+    /// The reply is written to a newly allocated `Mbuf`. The request is not
+    /// modified. The Ethernet and IPv4 source and destination addresses are
+    /// swapped, and the identifier, sequence number and data are copied
+    /// from the request. Both the ICMPv4 and IPv4 checksums are computed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the allocation of the new `Mbuf` fails.
+    pub fn reply(&self) -> Result<EchoReply> {
+        let ipv4 = self.envelope();
+        let ethernet = ipv4.envelope();
+
+        let mut reply = Mbuf::new()?.push::<Ethernet>()?;
+        reply.set_src(ethernet.dst());
+        reply.set_dst(ethernet.src());
+
+        let mut reply = reply.push::<Ipv4>()?;
+        reply.set_src(ipv4.dst());
+        reply.set_dst(ipv4.src());
+
+        let mut reply = reply.push::<EchoReply>()?;
+        reply.set_identifier(self.identifier());
+        reply.set_seq_no(self.seq_no());
+        if self.data_len() > 0 {
+            reply.set_data(self.data())?;
+        }
+
+        reply.reconcile_all();
+        Ok(reply)
+    }
 }
 
 impl fmt::Debug for EchoRequest {
@@ -220,9 +256,8 @@ struct EchoRequestBody {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::packets::ip::v4::Ipv4;
-    use crate::packets::Ethernet;
-    use crate::Mbuf;
+    use crate::net::MacAddr;
+    use std::net::Ipv4Addr;
 
     #[test]
     fn size_of_echo_request_body() {
@@ -254,4 +289,37 @@ mod tests {
         echo.reconcile_all();
         assert!(echo.checksum() != 0);
     }
+
+    #[capsule::test]
+    fn reply_to_echo_request() {
+        let packet = Mbuf::new().unwrap();
+        let mut ethernet = packet.push::<Ethernet>().unwrap();
+        ethernet.set_src(MacAddr::new(0, 0, 0, 0, 0, 1));
+        ethernet.set_dst(MacAddr::new(0, 0, 0, 0, 0, 2));
+        let mut ipv4 = ethernet.push::<Ipv4>().unwrap();
+        ipv4.set_src(Ipv4Addr::new(10, 0, 0, 1));
+        ipv4.set_dst(Ipv4Addr::new(10, 0, 0, 2));
+        let mut request = ipv4.push::<EchoRequest>().unwrap();
+        request.set_identifier(42);
+        request.set_seq_no(7);
+        assert!(request.set_data(&[1, 2, 3, 4]).is_ok());
+        request.reconcile_all();
+
+        let reply = request.reply().unwrap();
+
+        assert_eq!(Icmpv4Types::EchoReply, reply.msg_type());
+        assert_eq!(42, reply.identifier());
+        assert_eq!(7, reply.seq_no());
+        assert_eq!(&[1, 2, 3, 4], reply.data());
+        assert!(reply.checksum() != 0);
+
+        let ipv4 = reply.envelope();
+        assert_eq!(Ipv4Addr::new(10, 0, 0, 2), ipv4.src());
+        assert_eq!(Ipv4Addr::new(10, 0, 0, 1), ipv4.dst());
+        assert!(ipv4.validate_checksum());
+
+        let ethernet = ipv4.envelope();
+        assert_eq!(MacAddr::new(0, 0, 0, 0, 0, 2), ethernet.src());
+        assert_eq!(MacAddr::new(0, 0, 0, 0, 0, 1), ethernet.dst());
+    }
 }
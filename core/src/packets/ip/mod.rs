@@ -61,6 +61,9 @@ pub mod ProtocolNumbers {
     /// User Datagram Protocol.
     pub const Udp: ProtocolNumber = ProtocolNumber(0x11);
 
+    /// Generic Routing Encapsulation.
+    pub const Gre: ProtocolNumber = ProtocolNumber(0x2F);
+
     /// Routing Header for IPv6.
     pub const Ipv6Route: ProtocolNumber = ProtocolNumber(0x2B);
 
@@ -82,6 +85,7 @@ impl fmt::Display for ProtocolNumber {
             match *self {
                 ProtocolNumbers::Tcp => "TCP".to_string(),
                 ProtocolNumbers::Udp => "UDP".to_string(),
+                ProtocolNumbers::Gre => "GRE".to_string(),
                 ProtocolNumbers::Ipv6Route => "IPv6 Route".to_string(),
                 ProtocolNumbers::Ipv6Frag => "IPv6 Frag".to_string(),
                 ProtocolNumbers::Icmpv6 => "ICMPv6".to_string(),
@@ -272,6 +276,7 @@ mod tests {
     fn protocol_number_to_string() {
         assert_eq!("TCP", ProtocolNumbers::Tcp.to_string());
         assert_eq!("UDP", ProtocolNumbers::Udp.to_string());
+        assert_eq!("GRE", ProtocolNumbers::Gre.to_string());
         assert_eq!("IPv6 Route", ProtocolNumbers::Ipv6Route.to_string());
         assert_eq!("ICMPv6", ProtocolNumbers::Icmpv6.to_string());
         assert_eq!("0x00", ProtocolNumber::new(0).to_string());
@@ -175,7 +175,7 @@ impl Ipv4 {
     #[allow(dead_code)]
     #[inline]
     fn set_ihl(&mut self, ihl: u8) {
-        self.header_mut().version_ihl = (self.header().version_ihl & 0x0f) | (ihl & 0x0f);
+        self.header_mut().version_ihl = (self.header().version_ihl & 0xf0) | (ihl & 0x0f);
     }
 
     /// Returns the differentiated services codepoint.
@@ -318,8 +318,25 @@ impl Ipv4 {
         self.header_mut().checksum = checksum.into();
     }
 
+    /// Returns whether the header checksum is valid.
+    ///
+    /// The checksum covers the entire header, including the options.
+    #[inline]
+    pub fn validate_checksum(&self) -> bool {
+        if let Ok(data) = self.mbuf().read_data_slice(self.offset, self.header_len()) {
+            let data = unsafe { data.as_ref() };
+            checksum::compute(0, data) == 0
+        } else {
+            // we are reading the entire header, should never run out
+            unreachable!()
+        }
+    }
+
+    /// Recomputes the header checksum and writes it back to the header.
+    ///
+    /// The checksum is also recomputed when the packet is reconciled.
     #[inline]
-    fn compute_checksum(&mut self) {
+    pub fn compute_checksum(&mut self) {
         self.set_checksum(0);
 
         if let Ok(data) = self.mbuf().read_data_slice(self.offset, self.header_len()) {
@@ -332,6 +349,26 @@ impl Ipv4 {
         }
     }
 
+    /// Returns the options as raw bytes.
+    ///
+    /// The slice is empty if the header does not have any options.
+    #[inline]
+    pub fn options(&self) -> &[u8] {
+        let offset = self.offset + Ipv4Header::size_of();
+        let len = self.header_len() - Ipv4Header::size_of();
+
+        if len > 0 {
+            if let Ok(data) = self.mbuf().read_data_slice(offset, len) {
+                unsafe { &*data.as_ptr() }
+            } else {
+                // length is checked when the packet is parsed
+                unreachable!()
+            }
+        } else {
+            &[]
+        }
+    }
+
     /// Returns the source address.
     #[inline]
     pub fn src(&self) -> Ipv4Addr {
@@ -399,9 +436,10 @@ impl Packet for Ipv4 {
         self.offset
     }
 
+    /// Returns the length of the header, including the options.
     #[inline]
     fn header_len(&self) -> usize {
-        Ipv4Header::size_of()
+        self.ihl() as usize * 4
     }
 
     #[inline]
@@ -419,7 +457,8 @@ impl Packet for Ipv4 {
     ///
     /// Returns an error if [`ether_type`] is not set to [`EtherTypes::Ipv4`].
     /// Returns an error if the payload does not have sufficient data for the
-    /// IPv4 header.
+    /// IPv4 header, including the options. Returns an error if the internet
+    /// header length is less than the minimum of 5.
     ///
     /// [`ether_type`]: Ethernet::ether_type
     /// [`EtherTypes::Ipv4`]: EtherTypes::Ipv4
@@ -434,11 +473,21 @@ impl Packet for Ipv4 {
         let offset = envelope.payload_offset();
         let header = mbuf.read_data(offset)?;
 
-        Ok(Ipv4 {
+        let packet = Ipv4 {
             envelope,
             header,
             offset,
-        })
+        };
+
+        ensure!(
+            packet.ihl() >= 5,
+            anyhow!("invalid internet header length {}.", packet.ihl())
+        );
+        let _ = packet
+            .mbuf()
+            .read_data_slice::<u8>(offset, packet.header_len())?;
+
+        Ok(packet)
     }
 
     /// Prepends an IPv4 packet to the beginning of the Ethernet's payload.
@@ -715,6 +764,58 @@ mod tests {
         assert_eq!(1000, ipv4.len());
     }
 
+    #[capsule::test]
+    fn parse_ipv4_options() {
+        let mut bytes = IPV4_UDP_PACKET.to_vec();
+        // bumps the header length to 6 words and adds 4 bytes of options
+        bytes[14] = 0x46;
+        bytes.splice(34..34, [1u8, 1, 1, 0].iter().cloned());
+
+        let packet = Mbuf::from_bytes(&bytes).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let ipv4 = ethernet.parse::<Ipv4>().unwrap();
+
+        assert_eq!(6, ipv4.ihl());
+        assert_eq!(24, ipv4.header_len());
+        assert_eq!([1, 1, 1, 0], ipv4.options());
+        assert_eq!(38, ipv4.payload_offset());
+    }
+
+    #[capsule::test]
+    fn parse_ipv4_no_options() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let ipv4 = ethernet.parse::<Ipv4>().unwrap();
+
+        assert!(ipv4.options().is_empty());
+    }
+
+    #[capsule::test]
+    fn parse_ipv4_bad_ihl() {
+        let mut bytes = IPV4_UDP_PACKET.to_vec();
+        bytes[14] = 0x44;
+
+        let packet = Mbuf::from_bytes(&bytes).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+
+        assert!(ethernet.parse::<Ipv4>().is_err());
+    }
+
+    #[capsule::test]
+    fn validate_checksum() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let mut ipv4 = ethernet.parse::<Ipv4>().unwrap();
+
+        assert!(ipv4.validate_checksum());
+
+        ipv4.set_ttl(ipv4.ttl() - 1);
+        assert!(!ipv4.validate_checksum());
+
+        ipv4.compute_checksum();
+        assert!(ipv4.validate_checksum());
+    }
+
     #[capsule::test]
     fn compute_checksum() {
         let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
@@ -147,6 +147,21 @@ impl Ipv6 {
             (self.header().version_to_flow_label & !ECN) | (u32be::from(u32::from(ecn) << 20) & ECN)
     }
 
+    /// Returns the traffic class, which is the combination of the
+    /// differentiated services codepoint and the explicit congestion
+    /// notification codepoint.
+    #[inline]
+    pub fn traffic_class(&self) -> u8 {
+        (self.dscp() << 2) | self.ecn()
+    }
+
+    /// Sets the traffic class.
+    #[inline]
+    pub fn set_traffic_class(&mut self, traffic_class: u8) {
+        self.set_dscp(traffic_class >> 2);
+        self.set_ecn(traffic_class & 0b11);
+    }
+
     /// Returns the flow label.
     #[inline]
     pub fn flow_label(&self) -> u32 {
@@ -484,6 +499,7 @@ mod tests {
         assert_eq!(6, ipv6.version());
         assert_eq!(0, ipv6.dscp());
         assert_eq!(0, ipv6.ecn());
+        assert_eq!(0, ipv6.traffic_class());
         assert_eq!(0, ipv6.flow_label());
         assert_eq!(24, ipv6.payload_len());
         assert_eq!(ProtocolNumbers::Tcp, ipv6.next_header());
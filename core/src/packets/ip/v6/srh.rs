@@ -175,6 +175,12 @@ impl<E: Ipv6Packet> SegmentRouting<E> {
         self.header_mut().last_entry = last_entry;
     }
 
+    /// Returns the flags.
+    #[inline]
+    pub fn flags(&self) -> u8 {
+        self.header().flags
+    }
+
     /// Returns the tag that marks a packet as part of a class or group of
     /// packets.
     #[inline]
@@ -231,6 +237,65 @@ impl<E: Ipv6Packet> SegmentRouting<E> {
             Err(anyhow!("segment list length must be greater than 0."))
         }
     }
+
+    /// Advances the packet to the next segment.
+    ///
+    /// Decrements the segments left and copies the next segment into the
+    /// destination address of the IPv6 header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are no segments left, or the segments
+    /// left is past the end of the segment list.
+    pub fn advance(&mut self) -> Result<()> {
+        let segments_left = self.segments_left();
+        ensure!(segments_left > 0, anyhow!("no segments left."));
+        ensure!(
+            segments_left as usize <= self.segments().len(),
+            anyhow!("segments left {} exceeds the segment list.", segments_left)
+        );
+
+        let segments_left = segments_left - 1;
+        let next = self.segments()[segments_left as usize];
+        self.set_segments_left(segments_left);
+        self.envelope_mut().set_dst(IpAddr::V6(next))
+    }
+
+    /// Inserts a segment routing header with the segment list after the
+    /// envelope.
+    ///
+    /// The segment list is in the same encoding as [`set_segments`], the
+    /// first element is the final destination and the last element is the
+    /// first segment to visit. Segments left is set to the index of the
+    /// last element, and the envelope's destination is set to that
+    /// element. The envelope's [`next_header`] and payload length are
+    /// updated.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the segment list is empty or longer than 128
+    /// segments. Returns an error if the buffer does not have enough free
+    /// space.
+    ///
+    /// [`set_segments`]: SegmentRouting::set_segments
+    /// [`next_header`]: Ipv6Packet::next_header
+    pub fn insert(envelope: E, segments: &[Ipv6Addr]) -> Result<Self> {
+        ensure!(
+            !segments.is_empty() && segments.len() <= 128,
+            anyhow!("segment list length must be between 1 and 128.")
+        );
+
+        let mut srh = envelope.push::<SegmentRouting<E>>()?;
+        srh.set_segments(segments)?;
+
+        let last_entry = srh.last_entry();
+        srh.set_segments_left(last_entry);
+        srh.envelope_mut()
+            .set_dst(IpAddr::V6(segments[last_entry as usize]))?;
+        srh.reconcile_all();
+
+        Ok(srh)
+    }
 }
 
 impl<E: Ipv6Packet> fmt::Debug for SegmentRouting<E> {
@@ -696,4 +761,38 @@ mod tests {
         let tcp = ipv6.parse::<Tcp6>().unwrap();
         assert_eq!(3464, tcp.src_port());
     }
+
+    #[capsule::test]
+    fn insert_and_advance_segments() {
+        let segments = [
+            "2001:db8:85a3::8a2e:370:7333".parse().unwrap(),
+            "2001:db8:85a3::8a2e:370:7334".parse().unwrap(),
+            "2001:db8:85a3::8a2e:370:7335".parse().unwrap(),
+        ];
+
+        let packet = Mbuf::from_bytes(&IPV6_TCP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let ipv6 = ethernet.parse::<Ipv6>().unwrap();
+        let mut srh = SegmentRouting::insert(ipv6, &segments).unwrap();
+
+        assert_eq!(2, srh.segments_left());
+        assert_eq!(2, srh.last_entry());
+        assert_eq!(0, srh.flags());
+        assert_eq!(&segments[..], srh.segments());
+        assert_eq!(ProtocolNumbers::Tcp, srh.next_header());
+        assert_eq!(ProtocolNumbers::Ipv6Route, srh.envelope().next_header());
+        assert_eq!(segments[2], srh.envelope().dst());
+        assert_eq!(srh.len(), srh.envelope().payload_length() as usize);
+
+        srh.advance().unwrap();
+        assert_eq!(1, srh.segments_left());
+        assert_eq!(segments[1], srh.envelope().dst());
+
+        srh.advance().unwrap();
+        assert_eq!(0, srh.segments_left());
+        assert_eq!(segments[0], srh.envelope().dst());
+
+        assert!(srh.advance().is_err());
+        assert!(SegmentRouting::insert(srh.deparse(), &[]).is_err());
+    }
 }
@@ -20,16 +20,32 @@
 
 pub mod arp;
 pub mod checksum;
+mod dumper;
 mod ethernet;
+mod geneve;
+mod gre;
 pub mod icmp;
 pub mod ip;
+mod mpls;
+mod oam;
 mod tcp;
+mod tso;
+mod tunnel;
 pub mod types;
 mod udp;
+mod vxlan;
 
+pub use self::dumper::*;
 pub use self::ethernet::*;
+pub use self::geneve::*;
+pub use self::gre::*;
+pub use self::mpls::*;
+pub use self::oam::*;
 pub use self::tcp::*;
+pub use self::tso::*;
+pub use self::tunnel::*;
 pub use self::udp::*;
+pub use self::vxlan::*;
 
 use crate::Mbuf;
 use anyhow::{Context, Result};
@@ -0,0 +1,312 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::packets::types::u32be;
+use crate::packets::{EtherTypes, Ethernet, Packet};
+use crate::{ensure, SizeOf};
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+// MPLS label stack entry bitmasks.
+const LABEL: u32 = 0xffff_f000;
+const TRAFFIC_CLASS: u32 = 0x0000_0e00;
+const BOTTOM_OF_STACK: u32 = 0x0000_0100;
+const TTL: u32 = 0x0000_00ff;
+
+/// The largest 20-bit label value.
+const MAX_LABEL: u32 = 0x000f_ffff;
+
+/// MPLS label stack entry based on [IETF RFC 3032].
+///
+/// ```
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                Label                  | TC  |S|       TTL     |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// - *Label*: 20-bit label value.
+///
+/// - *TC*: 3-bit traffic class, used for QoS.
+///
+/// - *S*: Bottom of stack, set for the last entry of the stack.
+///
+/// - *TTL*: 8-bit time-to-live.
+///
+/// [IETF RFC 3032]: https://tools.ietf.org/html/rfc3032
+#[derive(Clone, Copy, Default, PartialEq, SizeOf)]
+#[repr(C, packed)]
+pub struct MplsEntry {
+    value: u32be,
+}
+
+impl MplsEntry {
+    /// Creates a new label stack entry.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `label` is larger than 20 bits or
+    /// `traffic_class` is larger than 3 bits.
+    pub fn new(label: u32, traffic_class: u8, bottom_of_stack: bool, ttl: u8) -> Result<Self> {
+        ensure!(
+            label <= MAX_LABEL,
+            anyhow!("label {} is out of range.", label)
+        );
+        ensure!(
+            traffic_class <= 7,
+            anyhow!("traffic class {} is out of range.", traffic_class)
+        );
+
+        let mut value = (label << 12) | ((traffic_class as u32) << 9) | ttl as u32;
+        if bottom_of_stack {
+            value |= BOTTOM_OF_STACK;
+        }
+
+        Ok(MplsEntry {
+            value: value.into(),
+        })
+    }
+
+    #[inline]
+    fn raw(&self) -> u32 {
+        self.value.into()
+    }
+
+    /// Returns the 20-bit label value.
+    #[inline]
+    pub fn label(&self) -> u32 {
+        (self.raw() & LABEL) >> 12
+    }
+
+    /// Returns the 3-bit traffic class.
+    #[inline]
+    pub fn traffic_class(&self) -> u8 {
+        ((self.raw() & TRAFFIC_CLASS) >> 9) as u8
+    }
+
+    /// Returns whether the entry is the last of the stack.
+    #[inline]
+    pub fn bottom_of_stack(&self) -> bool {
+        self.raw() & BOTTOM_OF_STACK != 0
+    }
+
+    /// Returns the time-to-live.
+    #[inline]
+    pub fn ttl(&self) -> u8 {
+        (self.raw() & TTL) as u8
+    }
+
+    /// Sets the time-to-live.
+    #[inline]
+    pub fn set_ttl(&mut self, ttl: u8) {
+        self.value = ((self.raw() & !TTL) | ttl as u32).into();
+    }
+}
+
+impl fmt::Debug for MplsEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("mpls")
+            .field("label", &self.label())
+            .field("traffic_class", &self.traffic_class())
+            .field("bottom_of_stack", &self.bottom_of_stack())
+            .field("ttl", &self.ttl())
+            .finish()
+    }
+}
+
+/// The MPLS label stack of an Ethernet frame.
+///
+/// The stack is a snapshot, changes to the frame afterwards are not
+/// reflected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MplsHeader {
+    entries: Vec<MplsEntry>,
+}
+
+impl MplsHeader {
+    /// Parses the label stack following the Ethernet header.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is not MPLS, or the stack is not
+    /// terminated with a bottom of stack entry.
+    pub fn parse(ethernet: &Ethernet) -> Result<Self> {
+        ensure!(
+            ethernet.ether_type() == EtherTypes::Mpls,
+            anyhow!("not an MPLS frame.")
+        );
+
+        let mut entries = vec![];
+        let mut offset = ethernet.payload_offset();
+        loop {
+            let entry = unsafe { *ethernet.mbuf().read_data::<MplsEntry>(offset)?.as_ptr() };
+            entries.push(entry);
+            offset += MplsEntry::size_of();
+
+            if entry.bottom_of_stack() {
+                break;
+            }
+        }
+
+        Ok(MplsHeader { entries })
+    }
+
+    /// Returns the entries, from the top of the stack.
+    pub fn entries(&self) -> &[MplsEntry] {
+        &self.entries
+    }
+
+    /// Returns the top entry of the stack.
+    pub fn top(&self) -> MplsEntry {
+        self.entries[0]
+    }
+
+    /// Returns the number of entries in the stack.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the stack has no entries. A parsed stack always
+    /// has at least one entry.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the length of the stack in bytes.
+    pub fn header_len(&self) -> usize {
+        self.entries.len() * MplsEntry::size_of()
+    }
+}
+
+/// Pushes a new label onto the MPLS label stack of the frame. If the frame
+/// is not MPLS yet, the new entry becomes the bottom of the stack and the
+/// ether type is changed to MPLS.
+///
+/// # Errors
+///
+/// Returns an error if `label` is larger than 20 bits or `tc` is larger
+/// than 3 bits. Returns an error if the buffer does not have enough free
+/// space.
+pub fn push_label(ethernet: &mut Ethernet, label: u32, tc: u8, ttl: u8) -> Result<()> {
+    let bottom_of_stack = ethernet.ether_type() != EtherTypes::Mpls;
+    let entry = MplsEntry::new(label, tc, bottom_of_stack, ttl)?;
+
+    let offset = ethernet.payload_offset();
+    ethernet.mbuf_mut().extend(offset, MplsEntry::size_of())?;
+    let _ = ethernet.mbuf_mut().write_data(offset, &entry)?;
+
+    if bottom_of_stack {
+        ethernet.set_ether_type(EtherTypes::Mpls);
+    }
+
+    Ok(())
+}
+
+/// Pops the top label off the MPLS label stack of the frame and returns
+/// it. If the label is the bottom of the stack, the ether type is set to
+/// the payload's IP version.
+///
+/// # Errors
+///
+/// Returns an error if the frame is not MPLS. Returns an error if the
+/// label is the bottom of the stack and the payload is not IPv4 or IPv6,
+/// the frame is unchanged in that case.
+pub fn pop_label(ethernet: &mut Ethernet) -> Result<MplsEntry> {
+    ensure!(
+        ethernet.ether_type() == EtherTypes::Mpls,
+        anyhow!("not an MPLS frame.")
+    );
+
+    let offset = ethernet.payload_offset();
+    let entry = unsafe { *ethernet.mbuf().read_data::<MplsEntry>(offset)?.as_ptr() };
+
+    let ether_type = if entry.bottom_of_stack() {
+        let payload = offset + MplsEntry::size_of();
+        let version = unsafe { *ethernet.mbuf().read_data::<u8>(payload)?.as_ptr() } >> 4;
+        match version {
+            4 => Some(EtherTypes::Ipv4),
+            6 => Some(EtherTypes::Ipv6),
+            _ => return Err(anyhow!("cannot infer the protocol of the MPLS payload.")),
+        }
+    } else {
+        None
+    };
+
+    ethernet.mbuf_mut().shrink(offset, MplsEntry::size_of())?;
+    if let Some(ether_type) = ether_type {
+        ethernet.set_ether_type(ether_type);
+    }
+
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::ip::v4::Ipv4;
+    use crate::packets::Udp4;
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+    use crate::Mbuf;
+
+    #[test]
+    fn size_of_mpls_entry() {
+        assert_eq!(4, MplsEntry::size_of());
+    }
+
+    #[test]
+    fn mpls_entry_fields() {
+        let entry = MplsEntry::new(0xabcde, 5, true, 64).unwrap();
+        assert_eq!(0xabcde, entry.label());
+        assert_eq!(5, entry.traffic_class());
+        assert!(entry.bottom_of_stack());
+        assert_eq!(64, entry.ttl());
+
+        assert!(MplsEntry::new(MAX_LABEL + 1, 0, false, 64).is_err());
+        assert!(MplsEntry::new(16, 8, false, 64).is_err());
+    }
+
+    #[capsule::test]
+    fn push_and_pop_labels() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let mut ethernet = packet.parse::<Ethernet>().unwrap();
+        let len = ethernet.len();
+
+        push_label(&mut ethernet, 100, 0, 64).unwrap();
+        push_label(&mut ethernet, 200, 1, 63).unwrap();
+        assert_eq!(EtherTypes::Mpls, ethernet.ether_type());
+        assert_eq!(len + 8, ethernet.len());
+
+        let stack = MplsHeader::parse(&ethernet).unwrap();
+        assert_eq!(2, stack.len());
+        assert_eq!(200, stack.top().label());
+        assert!(!stack.top().bottom_of_stack());
+        assert_eq!(100, stack.entries()[1].label());
+        assert!(stack.entries()[1].bottom_of_stack());
+
+        assert_eq!(200, pop_label(&mut ethernet).unwrap().label());
+        assert_eq!(EtherTypes::Mpls, ethernet.ether_type());
+        assert_eq!(100, pop_label(&mut ethernet).unwrap().label());
+        assert_eq!(EtherTypes::Ipv4, ethernet.ether_type());
+        assert_eq!(len, ethernet.len());
+        assert!(pop_label(&mut ethernet).is_err());
+
+        let ipv4 = ethernet.parse::<Ipv4>().unwrap();
+        assert!(ipv4.parse::<Udp4>().is_ok());
+    }
+}
@@ -0,0 +1,395 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::net::MacAddr;
+use crate::packets::{EtherType, EtherTypes, Ethernet, Packet};
+use crate::{ensure, Mbuf};
+use anyhow::{anyhow, Result};
+
+/// The destination address of slow protocols frames.
+pub const SLOW_PROTOCOLS_MAC: MacAddr = MacAddr::new(0x01, 0x80, 0xc2, 0x00, 0x00, 0x02);
+
+/// The slow protocols subtype of OAM.
+const OAM_SUBTYPE: u8 = 0x03;
+
+/// The length of the OAMPDU header, the subtype, the flags and the code.
+const OAM_HEADER_LEN: usize = 4;
+
+/// The minimum length of a frame without the FCS.
+const MIN_FRAME_LEN: usize = 60;
+
+/// The CFM opcode of a loopback reply.
+const CFM_LBR: u8 = 2;
+
+/// The CFM opcode of a loopback message.
+const CFM_LBM: u8 = 3;
+
+/// The offset of the first TLV after the CFM loopback header.
+const CFM_LB_TLV_OFFSET: u8 = 4;
+
+/// The CFM end TLV type.
+const CFM_END_TLV: u8 = 0;
+
+/// The codes of the link OAM protocol data units, as defined in IEEE
+/// 802.3ah clause 57.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OamPduType {
+    /// Communicates the local and remote OAM information.
+    InformationPdu,
+    /// Alerts the remote peer of link events.
+    EventNotification,
+    /// Requests MIB variables from the remote peer.
+    VariableRequest,
+    /// Returns the requested MIB variables.
+    VariableResponse,
+    /// Enables or disables the remote loopback.
+    LoopbackControl,
+    /// Carries organization specific extensions.
+    OrganizationSpecific,
+}
+
+impl OamPduType {
+    /// Returns the code of the OAMPDU.
+    pub fn code(self) -> u8 {
+        match self {
+            OamPduType::InformationPdu => 0x00,
+            OamPduType::EventNotification => 0x01,
+            OamPduType::VariableRequest => 0x02,
+            OamPduType::VariableResponse => 0x03,
+            OamPduType::LoopbackControl => 0x04,
+            OamPduType::OrganizationSpecific => 0xfe,
+        }
+    }
+
+    /// Returns the type of the OAMPDU code, or `None` if the code is
+    /// reserved.
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x00 => Some(OamPduType::InformationPdu),
+            0x01 => Some(OamPduType::EventNotification),
+            0x02 => Some(OamPduType::VariableRequest),
+            0x03 => Some(OamPduType::VariableResponse),
+            0x04 => Some(OamPduType::LoopbackControl),
+            0xfe => Some(OamPduType::OrganizationSpecific),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the payload bytes of the Ethernet frame.
+fn payload(pkt: &Ethernet) -> Result<&[u8]> {
+    let len = pkt.payload_len();
+    if len == 0 {
+        return Ok(&[]);
+    }
+
+    let data = pkt
+        .mbuf()
+        .read_data_slice::<u8>(pkt.payload_offset(), len)?;
+    Ok(unsafe { &*data.as_ptr() })
+}
+
+/// Builds a frame with the payload, padded to the minimum frame length.
+fn build_frame(
+    src: MacAddr,
+    dst: MacAddr,
+    ether_type: EtherType,
+    payload: &[u8],
+) -> Result<Ethernet> {
+    let mut ethernet = Mbuf::new()?.push::<Ethernet>()?;
+    ethernet.set_src(src);
+    ethernet.set_dst(dst);
+    ethernet.set_ether_type(ether_type);
+
+    let offset = ethernet.payload_offset();
+    let len = payload.len().max(MIN_FRAME_LEN - offset);
+    ethernet.mbuf_mut().extend(offset, len)?;
+    ethernet.mbuf_mut().write_data_slice(offset, payload)?;
+    Ok(ethernet)
+}
+
+/// A link OAM protocol data unit, as defined in IEEE 802.3ah clause 57.
+///
+/// OAMPDUs are slow protocols frames, sent to `SLOW_PROTOCOLS_MAC` with
+/// the ether type 0x8809, and never forwarded by bridges.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OamPdu {
+    frame_type: OamPduType,
+    flags: u16,
+    data: Vec<u8>,
+}
+
+impl OamPdu {
+    /// Parses the OAMPDU carried in the Ethernet frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is not a slow protocols frame, if the
+    /// subtype is not OAM, or if the code is reserved.
+    pub fn parse(pkt: &Ethernet) -> Result<OamPdu> {
+        ensure!(
+            pkt.ether_type() == EtherTypes::SlowProtocols,
+            anyhow!("not a slow protocols frame.")
+        );
+
+        let payload = payload(pkt)?;
+        ensure!(
+            payload.len() >= OAM_HEADER_LEN && payload[0] == OAM_SUBTYPE,
+            anyhow!("not an OAMPDU.")
+        );
+
+        let frame_type = OamPduType::from_code(payload[3])
+            .ok_or_else(|| anyhow!("reserved OAMPDU code 0x{:02x}.", payload[3]))?;
+
+        Ok(OamPdu {
+            frame_type,
+            flags: u16::from_be_bytes([payload[1], payload[2]]),
+            data: payload[OAM_HEADER_LEN..].to_vec(),
+        })
+    }
+
+    /// Returns the type of the OAMPDU.
+    pub fn frame_type(&self) -> OamPduType {
+        self.frame_type
+    }
+
+    /// Returns the flags, indicating the link fault conditions and the
+    /// discovery state.
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// Returns the data following the code, including the padding.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Builder of link OAMPDUs.
+///
+/// # Example
+///
+/// ```
+/// let pdu = OamPduBuilder::new(OamPduType::InformationPdu)
+///     .flags(0x0050)
+///     .src(q.mac_addr())
+///     .data(&tlvs)
+///     .build()?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct OamPduBuilder {
+    pdu_type: OamPduType,
+    flags: u16,
+    src: MacAddr,
+    data: Vec<u8>,
+}
+
+impl OamPduBuilder {
+    /// Creates a new builder for the type of OAMPDU.
+    pub fn new(pdu_type: OamPduType) -> Self {
+        OamPduBuilder {
+            pdu_type,
+            flags: 0,
+            src: MacAddr::UNSPECIFIED,
+            data: vec![],
+        }
+    }
+
+    /// Sets the flags.
+    pub fn flags(&mut self, flags: u16) -> &mut Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Sets the source MAC address, the address of the sending port.
+    pub fn src(&mut self, src: MacAddr) -> &mut Self {
+        self.src = src;
+        self
+    }
+
+    /// Sets the data following the code, for example the TLVs of an
+    /// information OAMPDU.
+    pub fn data(&mut self, data: &[u8]) -> &mut Self {
+        self.data = data.to_vec();
+        self
+    }
+
+    /// Builds the OAMPDU in a new `Mbuf`. The frame is padded to the
+    /// minimum Ethernet frame length.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the allocation of the `Mbuf` fails, or if the
+    /// data does not fit in it.
+    pub fn build(&self) -> Result<Ethernet> {
+        let mut payload = Vec::with_capacity(OAM_HEADER_LEN + self.data.len());
+        payload.push(OAM_SUBTYPE);
+        payload.extend_from_slice(&self.flags.to_be_bytes());
+        payload.push(self.pdu_type.code());
+        payload.extend_from_slice(&self.data);
+
+        build_frame(
+            self.src,
+            SLOW_PROTOCOLS_MAC,
+            EtherTypes::SlowProtocols,
+            &payload,
+        )
+    }
+}
+
+/// Ethernet loopback, as defined in IEEE 802.1ag connectivity fault
+/// management.
+///
+/// Link OAM has no sequence numbers in its loopback mode, so the loopback
+/// messages and replies are CFM frames, with the ether type 0x8902 and
+/// the transaction id matching replies to messages. The frames are sent
+/// at the maintenance domain level 0.
+#[derive(Debug)]
+pub struct OamLoopback;
+
+impl OamLoopback {
+    /// The multicast address of the level 0 CFM frames.
+    pub const CFM_MULTICAST_MAC: MacAddr = MacAddr::new(0x01, 0x80, 0xc2, 0x00, 0x00, 0x30);
+
+    /// Returns the payload of a loopback message or reply.
+    fn payload(opcode: u8, seq: u32) -> [u8; 9] {
+        let seq = seq.to_be_bytes();
+        [
+            0,
+            opcode,
+            0,
+            CFM_LB_TLV_OFFSET,
+            seq[0],
+            seq[1],
+            seq[2],
+            seq[3],
+            CFM_END_TLV,
+        ]
+    }
+
+    /// Builds a loopback message with the transaction id `seq`, addressed
+    /// to `CFM_MULTICAST_MAC`. Set the source, and the destination for a
+    /// unicast loopback, before sending it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the allocation of the `Mbuf` fails.
+    pub fn request(seq: u32) -> Result<Ethernet> {
+        build_frame(
+            MacAddr::UNSPECIFIED,
+            OamLoopback::CFM_MULTICAST_MAC,
+            EtherTypes::Cfm,
+            &OamLoopback::payload(CFM_LBM, seq),
+        )
+    }
+
+    /// Builds the reply to a loopback message, sent from `mac_addr` back to
+    /// the sender of the message.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is not a loopback message, or if the
+    /// allocation of the `Mbuf` fails.
+    pub fn reply(pkt: &Ethernet, mac_addr: MacAddr) -> Result<Ethernet> {
+        let seq = OamLoopback::transaction_id(pkt, CFM_LBM)
+            .ok_or_else(|| anyhow!("not a CFM loopback message."))?;
+        build_frame(
+            mac_addr,
+            pkt.src(),
+            EtherTypes::Cfm,
+            &OamLoopback::payload(CFM_LBR, seq),
+        )
+    }
+
+    /// Returns the transaction id of the CFM loopback frame with the opcode.
+    fn transaction_id(pkt: &Ethernet, opcode: u8) -> Option<u32> {
+        if pkt.ether_type() != EtherTypes::Cfm {
+            return None;
+        }
+
+        match payload(pkt).ok()? {
+            [_, op, _, _, a, b, c, d, ..] if *op == opcode => {
+                Some(u32::from_be_bytes([*a, *b, *c, *d]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns whether the frame is a loopback reply.
+    pub fn is_loopback_reply(pkt: &Ethernet) -> bool {
+        OamLoopback::reply_seq(pkt).is_some()
+    }
+
+    /// Returns the transaction id of a loopback reply.
+    pub fn reply_seq(pkt: &Ethernet) -> Option<u32> {
+        OamLoopback::transaction_id(pkt, CFM_LBR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[capsule::test]
+    fn build_and_parse_oam_pdu() {
+        let src = MacAddr::new(0x02, 0, 0, 0, 0, 1);
+        let ethernet = OamPduBuilder::new(OamPduType::EventNotification)
+            .flags(0x0052)
+            .src(src)
+            .data(&[1, 2, 3])
+            .build()
+            .unwrap();
+
+        assert_eq!(EtherTypes::SlowProtocols, ethernet.ether_type());
+        assert_eq!(SLOW_PROTOCOLS_MAC, ethernet.dst());
+        assert_eq!(MIN_FRAME_LEN, ethernet.mbuf().data_len());
+
+        let pdu = OamPdu::parse(&ethernet).unwrap();
+        assert_eq!(OamPduType::EventNotification, pdu.frame_type());
+        assert_eq!(0x0052, pdu.flags());
+        assert_eq!(&[1, 2, 3], &pdu.data()[..3]);
+    }
+
+    #[capsule::test]
+    fn parse_non_oam_frame() {
+        let ethernet = OamLoopback::request(1).unwrap();
+        assert!(OamPdu::parse(&ethernet).is_err());
+    }
+
+    #[test]
+    fn oam_pdu_codes() {
+        for &code in [0x00, 0x01, 0x02, 0x03, 0x04, 0xfe].iter() {
+            assert_eq!(code, OamPduType::from_code(code).unwrap().code());
+        }
+        assert_eq!(None, OamPduType::from_code(0x05));
+    }
+
+    #[capsule::test]
+    fn loopback_request_and_reply() {
+        let request = OamLoopback::request(0x1234_5678).unwrap();
+        assert_eq!(EtherTypes::Cfm, request.ether_type());
+        assert!(!OamLoopback::is_loopback_reply(&request));
+
+        let mac = MacAddr::new(0x02, 0, 0, 0, 0, 2);
+        let reply = OamLoopback::reply(&request, mac).unwrap();
+        assert!(OamLoopback::is_loopback_reply(&reply));
+        assert_eq!(Some(0x1234_5678), OamLoopback::reply_seq(&reply));
+        assert_eq!(mac, reply.src());
+        assert!(OamLoopback::reply(&reply, mac).is_err());
+    }
+}
@@ -23,6 +23,7 @@ use crate::packets::types::{u16be, u32be};
 use crate::packets::{checksum, Internal, Packet};
 use crate::{ensure, SizeOf};
 use anyhow::{anyhow, Result};
+use bitflags::bitflags;
 use std::fmt;
 use std::net::IpAddr;
 use std::ptr::NonNull;
@@ -37,6 +38,31 @@ const RST: u8 = 0b0000_0100;
 const SYN: u8 = 0b0000_0010;
 const FIN: u8 = 0b0000_0001;
 
+bitflags! {
+    /// TCP control flags, including the nonce sum bit.
+    #[derive(Default)]
+    pub struct TcpFlags: u16 {
+        /// No more data from sender.
+        const FIN = FIN as u16;
+        /// Synchronize sequence numbers.
+        const SYN = SYN as u16;
+        /// Reset the connection.
+        const RST = RST as u16;
+        /// Push function.
+        const PSH = PSH as u16;
+        /// Acknowledgment field significant.
+        const ACK = ACK as u16;
+        /// Urgent pointer field significant.
+        const URG = URG as u16;
+        /// ECN echo.
+        const ECE = ECE as u16;
+        /// Congestion window reduced.
+        const CWR = CWR as u16;
+        /// ECN nonce concealment protection.
+        const NS = 0b1_0000_0000;
+    }
+}
+
 /// Transmission Control Protocol packet based on [IETF RFC 793].
 ///
 /// ```
@@ -189,13 +215,48 @@ impl<E: IpPacket> Tcp<E> {
         (self.header().offset_to_ns & 0xf0) >> 4
     }
 
-    // TODO: support tcp header options.
     #[allow(dead_code)]
     #[inline]
     fn set_data_offset(&mut self, data_offset: u8) {
         self.header_mut().offset_to_ns = (self.header().offset_to_ns & 0x0f) | (data_offset << 4);
     }
 
+    /// Returns the options as raw bytes.
+    ///
+    /// The slice is empty if the header does not have any options.
+    #[inline]
+    pub fn options(&self) -> &[u8] {
+        let offset = self.offset + TcpHeader::size_of();
+        let len = self.header_len() - TcpHeader::size_of();
+
+        if len > 0 {
+            if let Ok(data) = self.mbuf().read_data_slice(offset, len) {
+                unsafe { &*data.as_ptr() }
+            } else {
+                // length is checked when the packet is parsed
+                unreachable!()
+            }
+        } else {
+            &[]
+        }
+    }
+
+    /// Returns all the control flags.
+    #[inline]
+    pub fn flags(&self) -> TcpFlags {
+        let ns = u16::from(self.header().offset_to_ns & 0x01) << 8;
+        TcpFlags::from_bits_truncate(ns | u16::from(self.header().flags))
+    }
+
+    /// Sets all the control flags.
+    #[inline]
+    pub fn set_flags(&mut self, flags: TcpFlags) {
+        let bits = flags.bits();
+        self.header_mut().offset_to_ns =
+            (self.header().offset_to_ns & !0x01) | ((bits >> 8) as u8 & 0x01);
+        self.header_mut().flags = bits as u8;
+    }
+
     /// Returns the nonce sum bit.
     #[inline]
     pub fn ns(&self) -> bool {
@@ -458,8 +519,30 @@ impl<E: IpPacket> Tcp<E> {
         Ok(())
     }
 
+    /// Returns whether the checksum is valid.
+    ///
+    /// The checksum covers the pseudo-header, the TCP header and the data.
+    #[inline]
+    pub fn validate_checksum(&self) -> bool {
+        if let Ok(data) = self.mbuf().read_data_slice(self.offset, self.len()) {
+            let data = unsafe { data.as_ref() };
+            let pseudo_header_sum = self
+                .envelope()
+                .pseudo_header(data.len() as u16, ProtocolNumbers::Tcp)
+                .sum();
+            checksum::compute(pseudo_header_sum, data) == 0
+        } else {
+            // we are reading till the end of buffer, should never run out
+            unreachable!()
+        }
+    }
+
+    /// Recomputes the checksum over the pseudo-header, the TCP header and
+    /// the data.
+    ///
+    /// The checksum is also recomputed when the packet is reconciled.
     #[inline]
-    fn compute_checksum(&mut self) {
+    pub fn compute_checksum(&mut self) {
         self.set_checksum(0);
 
         if let Ok(data) = self.mbuf().read_data_slice(self.offset, self.len()) {
@@ -527,9 +610,10 @@ impl<E: IpPacket> Packet for Tcp<E> {
         self.offset
     }
 
+    /// Returns the length of the header, including the options.
     #[inline]
     fn header_len(&self) -> usize {
-        TcpHeader::size_of()
+        self.data_offset() as usize * 4
     }
 
     #[inline]
@@ -549,7 +633,8 @@ impl<E: IpPacket> Packet for Tcp<E> {
     /// not set to [`ProtocolNumbers::Tcp`]. If the envelope is IPv6 or an
     /// extension header, returns an error if [`next_header`] is not set to
     /// `ProtocolNumbers::Tcp`. Returns an error if the payload does not
-    /// have sufficient data for the TCP header.
+    /// have sufficient data for the TCP header, including the options.
+    /// Returns an error if the data offset is less than the minimum of 5.
     ///
     /// [`Ipv4::protocol`]: crate::packets::ip::v4::Ipv4::protocol
     /// [`ProtocolNumbers::Tcp`]: crate::packets::ip::ProtocolNumbers::Tcp
@@ -565,11 +650,21 @@ impl<E: IpPacket> Packet for Tcp<E> {
         let offset = envelope.payload_offset();
         let header = mbuf.read_data(offset)?;
 
-        Ok(Tcp {
+        let packet = Tcp {
             envelope,
             header,
             offset,
-        })
+        };
+
+        ensure!(
+            packet.data_offset() >= 5,
+            anyhow!("invalid data offset {}.", packet.data_offset())
+        );
+        let _ = packet
+            .mbuf()
+            .read_data_slice::<u8>(offset, packet.header_len())?;
+
+        Ok(packet)
     }
 
     /// Prepends a TCP packet to the beginning of the envelope's payload.
@@ -701,6 +796,55 @@ mod tests {
         assert!(!tcp.fin());
     }
 
+    #[capsule::test]
+    fn parse_tcp_options() {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let ipv4 = ethernet.parse::<Ipv4>().unwrap();
+        let tcp = ipv4.parse::<Tcp4>().unwrap();
+
+        assert_eq!(24, tcp.header_len());
+        // maximum segment size of 1460
+        assert_eq!([0x02, 0x04, 0x05, 0xb4], tcp.options());
+    }
+
+    #[capsule::test]
+    fn get_and_set_tcp_flags() {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let ipv4 = ethernet.parse::<Ipv4>().unwrap();
+        let mut tcp = ipv4.parse::<Tcp4>().unwrap();
+
+        assert_eq!(TcpFlags::SYN, tcp.flags());
+
+        tcp.set_flags(TcpFlags::SYN | TcpFlags::ACK | TcpFlags::NS);
+        assert!(tcp.syn());
+        assert!(tcp.ack());
+        assert!(tcp.ns());
+        assert!(!tcp.fin());
+        assert_eq!(6, tcp.data_offset());
+
+        tcp.set_flags(TcpFlags::empty());
+        assert!(!tcp.syn());
+        assert!(!tcp.ns());
+    }
+
+    #[capsule::test]
+    fn validate_tcp_checksum() {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let ipv4 = ethernet.parse::<Ipv4>().unwrap();
+        let mut tcp = ipv4.parse::<Tcp4>().unwrap();
+
+        assert!(tcp.validate_checksum());
+
+        tcp.set_dst_port(80);
+        assert!(!tcp.validate_checksum());
+
+        tcp.compute_checksum();
+        assert!(tcp.validate_checksum());
+    }
+
     #[capsule::test]
     fn parse_non_tcp_packet() {
         let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
@@ -0,0 +1,294 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::v6::Ipv6;
+use crate::packets::ip::IpPacket;
+use crate::packets::{EtherTypes, Ethernet, Packet, Tcp};
+use crate::{ensure, Mbuf};
+use anyhow::{anyhow, Result};
+
+/// The offsets of a TCP packet needed to segment it.
+struct Layout {
+    header_len: usize,
+    payload_len: usize,
+}
+
+impl Layout {
+    fn of(ethernet: &Ethernet) -> Result<Self> {
+        match ethernet.ether_type() {
+            EtherTypes::Ipv4 => {
+                let ipv4 = ethernet.peek::<Ipv4>()?;
+                let tcp = ipv4.peek::<Tcp<Ipv4>>()?;
+                Ok(Layout {
+                    header_len: tcp.payload_offset(),
+                    payload_len: (ipv4.total_length() as usize)
+                        .saturating_sub(ipv4.header_len() + tcp.header_len()),
+                })
+            }
+            EtherTypes::Ipv6 => {
+                let ipv6 = ethernet.peek::<Ipv6>()?;
+                let tcp = ipv6.peek::<Tcp<Ipv6>>()?;
+                Ok(Layout {
+                    header_len: tcp.payload_offset(),
+                    payload_len: (ipv6.payload_length() as usize).saturating_sub(tcp.header_len()),
+                })
+            }
+            _ => Err(anyhow!("not an IP packet.")),
+        }
+    }
+}
+
+/// Updates the TCP header of a segment and reconciles the headers.
+fn fix_segment<E: IpPacket>(tcp: &mut Tcp<E>, seq_no: u32, first: bool, last: bool) {
+    tcp.set_seq_no(seq_no);
+
+    // congestion window reduced is only signaled once.
+    if !first {
+        tcp.unset_cwr();
+    }
+
+    // push and finish only apply to the end of the data.
+    if !last {
+        tcp.unset_psh();
+        tcp.unset_fin();
+    }
+
+    tcp.reconcile_all();
+}
+
+/// A software TCP segmentation offload, for devices without TSO support.
+///
+/// A TCP packet with a payload larger than the maximum segment size is
+/// split into segments of at most that size. Each segment gets a copy of
+/// the Ethernet, IP and TCP headers, including the options, with the
+/// sequence number, lengths and checksums updated. Unlike [`GsoContext`],
+/// the payload is copied, so the original packet can be a chain of any
+/// shape as long as the headers are in the first segment.
+///
+/// Only TCP directly following the IPv4 or IPv6 header is supported.
+///
+/// # Example
+///
+/// ```
+/// if TcpSegmenter::is_needed(&ethernet, 1460) {
+///     let segments = TcpSegmenter::segment(ethernet, 1460)?;
+/// }
+/// ```
+///
+/// [`GsoContext`]: crate::dpdk::GsoContext
+#[derive(Debug)]
+pub struct TcpSegmenter;
+
+impl TcpSegmenter {
+    /// Returns whether the packet is a TCP packet with a payload larger
+    /// than `mss`.
+    pub fn is_needed(pkt: &Ethernet, mss: u16) -> bool {
+        Layout::of(pkt)
+            .map(|layout| layout.payload_len > mss as usize)
+            .unwrap_or(false)
+    }
+
+    /// Segments the packet. If the payload is not larger than `mss`, the
+    /// packet is returned as the only segment. The segments are allocated
+    /// from the `Mempool` assigned to the current executing thread.
+    ///
+    /// For IPv4, each segment after the first gets the next identification
+    /// value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the packet is not a TCP packet, if the headers
+    /// are not in the first segment, or if `mss` is `0`. Returns
+    /// `MempoolError::Exhausted` if the segments can't be allocated.
+    pub fn segment(pkt: Ethernet, mss: u16) -> Result<Vec<Ethernet>> {
+        ensure!(mss > 0, anyhow!("MSS must be greater than 0."));
+
+        let layout = Layout::of(&pkt)?;
+        if layout.payload_len <= mss as usize {
+            return Ok(vec![pkt]);
+        }
+
+        let header = unsafe {
+            pkt.mbuf()
+                .read_data_slice::<u8>(0, layout.header_len)?
+                .as_ref()
+        }
+        .to_vec();
+
+        let payload = pkt
+            .mbuf()
+            .segments()
+            .flat_map(|seg| seg.iter().copied())
+            .skip(layout.header_len)
+            .take(layout.payload_len)
+            .collect::<Vec<_>>();
+
+        let chunks = payload.chunks(mss as usize);
+        let last = chunks.len() - 1;
+
+        chunks
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut mbuf = Mbuf::from_bytes(&header)?;
+                mbuf.extend(layout.header_len, chunk.len())?;
+                mbuf.write_data_slice(layout.header_len, chunk)?;
+
+                let ethernet = mbuf.parse::<Ethernet>()?;
+                let offset = (i * mss as usize) as u32;
+
+                let ethernet = if ethernet.ether_type() == EtherTypes::Ipv4 {
+                    let mut tcp = ethernet.parse::<Ipv4>()?.parse::<Tcp<Ipv4>>()?;
+                    let id = tcp.envelope().identification();
+                    tcp.envelope_mut()
+                        .set_identification(id.wrapping_add(i as u16));
+                    let seq_no = tcp.seq_no().wrapping_add(offset);
+                    fix_segment(&mut tcp, seq_no, i == 0, i == last);
+                    tcp.deparse().deparse()
+                } else {
+                    let mut tcp = ethernet.parse::<Ipv6>()?.parse::<Tcp<Ipv6>>()?;
+                    let seq_no = tcp.seq_no().wrapping_add(offset);
+                    fix_segment(&mut tcp, seq_no, i == 0, i == last);
+                    tcp.deparse().deparse()
+                };
+
+                Ok(ethernet)
+            })
+            .collect::<Result<Vec<_>>>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpdk::SegmentedPacket;
+    use crate::packets::Tcp4;
+    use crate::testils::byte_arrays::IPV4_TCP_PACKET;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const SEGMENT_LEN: usize = 1000;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    /// Builds a TCP packet with the payload spread across segments.
+    fn large_tcp4_packet(len: usize) -> Ethernet {
+        let ethernet = Mbuf::new().unwrap().push::<Ethernet>().unwrap();
+        let mut ipv4 = ethernet.push::<Ipv4>().unwrap();
+        ipv4.set_src(Ipv4Addr::new(10, 0, 0, 1));
+        ipv4.set_dst(Ipv4Addr::new(10, 0, 0, 2));
+        ipv4.set_identification(100);
+        let mut tcp = ipv4.push::<Tcp4>().unwrap();
+        tcp.set_seq_no(u32::MAX - 10);
+        tcp.set_ack();
+        tcp.set_psh();
+
+        let data = payload(len);
+        let payload_offset = tcp.payload_offset();
+        tcp.mbuf_mut().extend(payload_offset, SEGMENT_LEN).unwrap();
+        tcp.mbuf_mut()
+            .write_data_slice(payload_offset, &data[..SEGMENT_LEN])
+            .unwrap();
+
+        let ip_offset = tcp.envelope().offset();
+        let mut packet = SegmentedPacket::new(tcp.reset());
+        for chunk in data[SEGMENT_LEN..].chunks(SEGMENT_LEN) {
+            packet
+                .append_segment(Mbuf::from_bytes(chunk).unwrap())
+                .unwrap();
+        }
+
+        // reconcile only sees the first segment, sets the length directly.
+        let mut mbuf = packet.into_mbuf();
+        let total_length = (20 + 20 + len) as u16;
+        mbuf.write_data_slice(ip_offset + 2, &total_length.to_be_bytes())
+            .unwrap();
+        mbuf.parse::<Ethernet>().unwrap()
+    }
+
+    #[capsule::test]
+    fn round_trip_large_tcp4_payload() {
+        let ethernet = large_tcp4_packet(9000);
+        assert!(TcpSegmenter::is_needed(&ethernet, 1460));
+
+        let segments = TcpSegmenter::segment(ethernet, 1460).unwrap();
+        assert_eq!(7, segments.len());
+
+        let mut data = vec![];
+        for (i, segment) in segments.into_iter().enumerate() {
+            let ipv4 = segment.parse::<Ipv4>().unwrap();
+            assert_eq!(100 + i as u16, ipv4.identification());
+            assert!(ipv4.validate_checksum());
+
+            let tcp = ipv4.parse::<Tcp4>().unwrap();
+            assert_eq!((u32::MAX - 10).wrapping_add(i as u32 * 1460), tcp.seq_no());
+            assert_eq!(i == 6, tcp.psh());
+            assert!(tcp.ack());
+            assert!(tcp.validate_checksum());
+            assert!(tcp.payload_len() <= 1460);
+
+            let offset = tcp.payload_offset();
+            let len = tcp.payload_len();
+            data.extend_from_slice(unsafe {
+                tcp.mbuf()
+                    .read_data_slice::<u8>(offset, len)
+                    .unwrap()
+                    .as_ref()
+            });
+        }
+
+        assert_eq!(payload(9000), data);
+    }
+
+    #[capsule::test]
+    fn segment_tcp6_packet() {
+        let ethernet = Mbuf::new().unwrap().push::<Ethernet>().unwrap();
+        let mut ipv6 = ethernet.push::<Ipv6>().unwrap();
+        ipv6.set_src(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1));
+        ipv6.set_dst(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 2));
+        let mut tcp = ipv6.push::<Tcp<Ipv6>>().unwrap();
+        tcp.set_seq_no(1);
+
+        let payload_offset = tcp.payload_offset();
+        tcp.mbuf_mut().extend(payload_offset, 1500).unwrap();
+        tcp.mbuf_mut()
+            .write_data_slice(payload_offset, &payload(1500))
+            .unwrap();
+        tcp.reconcile_all();
+
+        let segments = TcpSegmenter::segment(tcp.deparse().deparse(), 500).unwrap();
+        assert_eq!(3, segments.len());
+
+        for (i, segment) in segments.into_iter().enumerate() {
+            let ipv6 = segment.parse::<Ipv6>().unwrap();
+            assert_eq!(20 + 500, ipv6.payload_length());
+            let tcp = ipv6.parse::<Tcp<Ipv6>>().unwrap();
+            assert_eq!(1 + i as u32 * 500, tcp.seq_no());
+            assert!(tcp.validate_checksum());
+        }
+    }
+
+    #[capsule::test]
+    fn segment_small_packet() {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        assert!(!TcpSegmenter::is_needed(&ethernet, 1460));
+        assert_eq!(1, TcpSegmenter::segment(ethernet, 1460).unwrap().len());
+    }
+}
@@ -0,0 +1,275 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::v6::Ipv6;
+use crate::packets::ip::{IpPacket, ProtocolNumbers};
+use crate::packets::{
+    EtherTypes, Ethernet, Geneve, GeneveOption, Gre, Packet, Udp, Vxlan, GENEVE_PORT, VXLAN_PORT,
+};
+use anyhow::{anyhow, Result};
+use std::net::{Ipv4Addr, SocketAddrV4};
+
+/// The tunnel protocols supported by `TunnelEndpoint`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TunnelType {
+    /// Virtual eXtensible Local Area Network.
+    Vxlan,
+    /// Generic Routing Encapsulation.
+    Gre,
+    /// Generic Network Virtualization Encapsulation.
+    Geneve,
+}
+
+/// Parameters of a VXLAN tunnel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VxlanParams {
+    /// The VXLAN network identifier.
+    pub vni: u32,
+    /// The outer source address.
+    pub outer_src: SocketAddrV4,
+    /// The outer destination address, usually on `VXLAN_PORT`.
+    pub outer_dst: SocketAddrV4,
+}
+
+/// Parameters of a GRE tunnel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GreParams {
+    /// The outer source address.
+    pub src: Ipv4Addr,
+    /// The outer destination address.
+    pub dst: Ipv4Addr,
+    /// The optional key identifying the flow within the tunnel.
+    pub key: Option<u32>,
+}
+
+/// Parameters of a Geneve tunnel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneveParams {
+    /// The virtual network identifier.
+    pub vni: u32,
+    /// The outer source address.
+    pub outer_src: SocketAddrV4,
+    /// The outer destination address, usually on `GENEVE_PORT`.
+    pub outer_dst: SocketAddrV4,
+    /// The variable length options.
+    pub options: Vec<GeneveOption>,
+}
+
+/// Parameters of a tunnel to encapsulate a frame in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TunnelParams {
+    /// A VXLAN tunnel.
+    Vxlan(VxlanParams),
+    /// A GRE tunnel.
+    Gre(GreParams),
+    /// A Geneve tunnel.
+    Geneve(GeneveParams),
+}
+
+impl TunnelParams {
+    /// Returns the type of the tunnel.
+    pub fn tunnel_type(&self) -> TunnelType {
+        match self {
+            TunnelParams::Vxlan(_) => TunnelType::Vxlan,
+            TunnelParams::Gre(_) => TunnelType::Gre,
+            TunnelParams::Geneve(_) => TunnelType::Geneve,
+        }
+    }
+}
+
+/// Identifies the tunnel a frame is received from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TunnelId {
+    /// The type of the tunnel.
+    pub tunnel_type: TunnelType,
+    /// The network identifier of VXLAN and Geneve, or the key of GRE. GRE
+    /// packets without a key have an id of 0.
+    pub vni: u32,
+}
+
+/// A tunnel endpoint that handles VXLAN, GRE and Geneve interchangeably.
+///
+/// Only tunnels over IPv4 can be encapsulated. Tunnels over either IPv4
+/// or IPv6 can be detected and decapsulated.
+///
+/// # Example
+///
+/// ```
+/// let (inner, id) = TunnelEndpoint::decapsulate(outer)?;
+/// let params = concentrator.params_for(id);
+/// let outer = TunnelEndpoint::encapsulate(inner, &params)?;
+/// ```
+#[derive(Debug)]
+pub struct TunnelEndpoint;
+
+impl TunnelEndpoint {
+    /// Encapsulates the Ethernet frame in the tunnel. The outer Ethernet
+    /// addresses are not set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer does not have enough free space, or
+    /// if the Geneve options are too long.
+    pub fn encapsulate(inner: Ethernet, params: &TunnelParams) -> Result<Ethernet> {
+        let outer = match params {
+            TunnelParams::Vxlan(p) => {
+                Vxlan::<Ipv4>::encapsulate(inner, p.vni, p.outer_src, p.outer_dst)?
+                    .deparse()
+                    .deparse()
+                    .deparse()
+            }
+            TunnelParams::Gre(p) => Gre::<Ipv4>::encapsulate(inner, p.src, p.dst, p.key)?
+                .deparse()
+                .deparse(),
+            TunnelParams::Geneve(p) => {
+                Geneve::<Ipv4>::encapsulate(inner, p.vni, p.outer_src, p.outer_dst, &p.options)?
+                    .deparse()
+                    .deparse()
+                    .deparse()
+            }
+        };
+
+        Ok(outer)
+    }
+
+    /// Removes the tunnel headers and returns the inner Ethernet frame and
+    /// the id of the tunnel.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frame is not a tunnel packet, or if the
+    /// payload is not an Ethernet frame.
+    pub fn decapsulate(outer: Ethernet) -> Result<(Ethernet, TunnelId)> {
+        let tunnel_type =
+            TunnelEndpoint::detect_type(&outer).ok_or_else(|| anyhow!("not a tunnel packet."))?;
+
+        match outer.ether_type() {
+            EtherTypes::Ipv4 => decapsulate_ip(outer.parse::<Ipv4>()?, tunnel_type),
+            _ => decapsulate_ip(outer.parse::<Ipv6>()?, tunnel_type),
+        }
+    }
+
+    /// Sniffs the type of the tunnel from the outer IP protocol, or the
+    /// outer UDP destination port.
+    pub fn detect_type(packet: &Ethernet) -> Option<TunnelType> {
+        match packet.ether_type() {
+            EtherTypes::Ipv4 => detect_ip(&*packet.peek::<Ipv4>().ok()?),
+            EtherTypes::Ipv6 => detect_ip(&*packet.peek::<Ipv6>().ok()?),
+            _ => None,
+        }
+    }
+}
+
+fn detect_ip<E: IpPacket>(ip: &E) -> Option<TunnelType> {
+    match ip.next_protocol() {
+        ProtocolNumbers::Gre => Some(TunnelType::Gre),
+        ProtocolNumbers::Udp => match ip.peek::<Udp<E>>().ok()?.dst_port() {
+            VXLAN_PORT => Some(TunnelType::Vxlan),
+            GENEVE_PORT => Some(TunnelType::Geneve),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn decapsulate_ip<E: IpPacket>(ip: E, tunnel_type: TunnelType) -> Result<(Ethernet, TunnelId)> {
+    let (inner, vni) = match tunnel_type {
+        TunnelType::Vxlan => ip.parse::<Udp<E>>()?.parse::<Vxlan<E>>()?.decapsulate()?,
+        TunnelType::Gre => {
+            let gre = ip.parse::<Gre<E>>()?;
+            let key = gre.key().unwrap_or_default();
+            (gre.decapsulate()?, key)
+        }
+        TunnelType::Geneve => {
+            let (inner, header) = ip.parse::<Udp<E>>()?.parse::<Geneve<E>>()?.decapsulate()?;
+            (inner, header.vni())
+        }
+    };
+
+    Ok((inner, TunnelId { tunnel_type, vni }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::{IPV4_TCP_PACKET, IPV4_UDP_PACKET};
+    use crate::Mbuf;
+
+    fn roundtrip(params: TunnelParams, vni: u32) {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let inner = packet.parse::<Ethernet>().unwrap();
+
+        let outer = TunnelEndpoint::encapsulate(inner, &params).unwrap();
+        assert_eq!(
+            Some(params.tunnel_type()),
+            TunnelEndpoint::detect_type(&outer)
+        );
+
+        let (inner, id) = TunnelEndpoint::decapsulate(outer).unwrap();
+        assert_eq!(params.tunnel_type(), id.tunnel_type);
+        assert_eq!(vni, id.vni);
+        assert_eq!(IPV4_TCP_PACKET.len(), inner.len() + inner.payload_len());
+    }
+
+    #[capsule::test]
+    fn vxlan_roundtrip() {
+        roundtrip(
+            TunnelParams::Vxlan(VxlanParams {
+                vni: 0x12_3456,
+                outer_src: SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 49152),
+                outer_dst: SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), VXLAN_PORT),
+            }),
+            0x12_3456,
+        );
+    }
+
+    #[capsule::test]
+    fn gre_roundtrip() {
+        roundtrip(
+            TunnelParams::Gre(GreParams {
+                src: Ipv4Addr::new(10, 0, 0, 1),
+                dst: Ipv4Addr::new(10, 0, 0, 2),
+                key: Some(42),
+            }),
+            42,
+        );
+    }
+
+    #[capsule::test]
+    fn geneve_roundtrip() {
+        roundtrip(
+            TunnelParams::Geneve(GeneveParams {
+                vni: 7,
+                outer_src: SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 49152),
+                outer_dst: SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), GENEVE_PORT),
+                options: vec![GeneveOption::new(0x0102, 0x80, vec![1, 2, 3, 4]).unwrap()],
+            }),
+            7,
+        );
+    }
+
+    #[capsule::test]
+    fn detect_non_tunnel_packet() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+
+        assert_eq!(None, TunnelEndpoint::detect_type(&ethernet));
+        assert!(TunnelEndpoint::decapsulate(ethernet).is_err());
+    }
+}
@@ -200,8 +200,35 @@ impl<E: IpPacket> Udp<E> {
         Ok(())
     }
 
+    /// Returns whether the checksum is valid.
+    ///
+    /// A checksum of `0` means the transmitter generated no checksum and
+    /// is always valid.
     #[inline]
-    fn compute_checksum(&mut self) {
+    pub fn validate_checksum(&self) -> bool {
+        if self.checksum() == 0 {
+            return true;
+        }
+
+        if let Ok(data) = self.mbuf().read_data_slice(self.offset, self.len()) {
+            let data = unsafe { data.as_ref() };
+            let pseudo_header_sum = self
+                .envelope()
+                .pseudo_header(data.len() as u16, ProtocolNumbers::Udp)
+                .sum();
+            checksum::compute(pseudo_header_sum, data) == 0
+        } else {
+            // we are reading till the end of buffer, should never run out
+            unreachable!()
+        }
+    }
+
+    /// Recomputes the checksum over the pseudo-header, the UDP header and
+    /// the data.
+    ///
+    /// The checksum is also recomputed when the packet is reconciled.
+    #[inline]
+    pub fn compute_checksum(&mut self) {
         self.no_checksum();
 
         if let Ok(data) = self.mbuf().read_data_slice(self.offset, self.len()) {
@@ -443,6 +470,23 @@ mod tests {
         assert!(udp.set_src_ip(Ipv6Addr::UNSPECIFIED.into()).is_err());
     }
 
+    #[capsule::test]
+    fn validate_udp_checksum() {
+        let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
+        let ethernet = packet.parse::<Ethernet>().unwrap();
+        let ipv4 = ethernet.parse::<Ipv4>().unwrap();
+        let mut udp = ipv4.parse::<Udp4>().unwrap();
+
+        udp.compute_checksum();
+        assert!(udp.validate_checksum());
+
+        udp.set_dst_port(80);
+        assert!(!udp.validate_checksum());
+
+        udp.no_checksum();
+        assert!(udp.validate_checksum());
+    }
+
     #[capsule::test]
     fn compute_checksum() {
         let packet = Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap();
@@ -0,0 +1,343 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+use crate::packets::ip::v4::Ipv4;
+use crate::packets::ip::v6::Ipv6;
+use crate::packets::ip::IpPacket;
+use crate::packets::types::u32be;
+use crate::packets::{Ethernet, Internal, Packet, Udp};
+use crate::{ensure, SizeOf};
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::net::SocketAddrV4;
+use std::ptr::NonNull;
+
+/// The IANA assigned UDP destination port for VXLAN.
+pub const VXLAN_PORT: u16 = 4789;
+
+// The flag indicating the VXLAN network identifier is valid.
+const VNI_VALID: u8 = 0b0000_1000;
+
+/// Virtual eXtensible Local Area Network based on [IETF RFC 7348].
+///
+/// ```
+///  0                   1                   2                   3
+///  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |R|R|R|R|I|R|R|R|            Reserved                           |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// |                VXLAN Network Identifier (VNI) |   Reserved    |
+/// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+/// ```
+///
+/// - *Flags*: (8 bits)
+///   The I flag MUST be set to 1 for a valid VXLAN Network ID (VNI).
+///   The other 7 bits (designated "R") are reserved fields and MUST be
+///   set to zero on transmission and ignored on receipt.
+///
+/// - *VXLAN Network Identifier (VNI)*: (24 bits)
+///   A value used to designate the individual VXLAN overlay network on
+///   which the communicating VMs are situated.
+///
+/// - *Reserved fields*: (24 bits and 8 bits)
+///   MUST be set to zero on transmission and ignored on receipt.
+///
+/// The payload of a VXLAN packet is the inner Ethernet frame.
+///
+/// [IETF RFC 7348]: https://tools.ietf.org/html/rfc7348
+pub struct Vxlan<E: IpPacket> {
+    envelope: Udp<E>,
+    header: NonNull<VxlanHeader>,
+    offset: usize,
+}
+
+impl<E: IpPacket> Vxlan<E> {
+    #[inline]
+    fn header(&self) -> &VxlanHeader {
+        unsafe { self.header.as_ref() }
+    }
+
+    #[inline]
+    fn header_mut(&mut self) -> &mut VxlanHeader {
+        unsafe { self.header.as_mut() }
+    }
+
+    /// Returns the flags.
+    #[inline]
+    pub fn flags(&self) -> u8 {
+        self.header().flags
+    }
+
+    /// Returns the VXLAN network identifier.
+    #[inline]
+    pub fn vni(&self) -> u32 {
+        u32::from(self.header().vni_reserved) >> 8
+    }
+
+    /// Sets the VXLAN network identifier. Only the lower 24 bits are used.
+    #[inline]
+    pub fn set_vni(&mut self, vni: u32) {
+        self.header_mut().vni_reserved = ((vni & 0x00ff_ffff) << 8).into();
+    }
+
+    /// Removes the tunnel headers and returns the inner Ethernet frame and
+    /// the VXLAN network identifier.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload is not an Ethernet frame.
+    pub fn decapsulate(self) -> Result<(Ethernet, u32)> {
+        let vni = self.vni();
+        let len = self.payload_offset();
+        let mut mbuf = self.reset();
+        mbuf.shrink(0, len)?;
+        Ok((mbuf.parse::<Ethernet>()?, vni))
+    }
+}
+
+impl Vxlan<Ipv4> {
+    /// Encapsulates the Ethernet frame in a VXLAN tunnel between
+    /// `outer_src` and `outer_dst`. The outer Ethernet addresses are not
+    /// set.
+    ///
+    /// The destination port should be `VXLAN_PORT` for the packet to be
+    /// recognized by the receiver.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer does not have enough free space.
+    pub fn encapsulate(
+        inner: Ethernet,
+        vni: u32,
+        outer_src: SocketAddrV4,
+        outer_dst: SocketAddrV4,
+    ) -> Result<Self> {
+        let mut ipv4 = inner.reset().push::<Ethernet>()?.push::<Ipv4>()?;
+        ipv4.set_src(*outer_src.ip());
+        ipv4.set_dst(*outer_dst.ip());
+
+        let mut udp = ipv4.push::<Udp<Ipv4>>()?;
+        udp.set_src_port(outer_src.port());
+
+        let mut vxlan = udp.push::<Vxlan<Ipv4>>()?;
+        vxlan.envelope_mut().set_dst_port(outer_dst.port());
+        vxlan.set_vni(vni);
+        vxlan.reconcile_all();
+
+        Ok(vxlan)
+    }
+}
+
+impl<E: IpPacket> fmt::Debug for Vxlan<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("vxlan")
+            .field("flags", &format!("0x{:02x}", self.flags()))
+            .field("vni", &self.vni())
+            .field("$offset", &self.offset())
+            .field("$len", &self.len())
+            .field("$header_len", &self.header_len())
+            .finish()
+    }
+}
+
+impl<E: IpPacket> Packet for Vxlan<E> {
+    /// The preceding packet type for a VXLAN packet must be an UDP packet.
+    type Envelope = Udp<E>;
+
+    #[inline]
+    fn envelope(&self) -> &Self::Envelope {
+        &self.envelope
+    }
+
+    #[inline]
+    fn envelope_mut(&mut self) -> &mut Self::Envelope {
+        &mut self.envelope
+    }
+
+    #[inline]
+    fn offset(&self) -> usize {
+        self.offset
+    }
+
+    #[inline]
+    fn header_len(&self) -> usize {
+        VxlanHeader::size_of()
+    }
+
+    #[inline]
+    unsafe fn clone(&self, internal: Internal) -> Self {
+        Vxlan::<E> {
+            envelope: self.envelope.clone(internal),
+            header: self.header,
+            offset: self.offset,
+        }
+    }
+
+    /// Parses the UDP packet's payload as a VXLAN packet.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the UDP destination port is not [`VXLAN_PORT`],
+    /// or the I flag is not set. Returns an error if the payload does not
+    /// have sufficient data for the VXLAN header.
+    ///
+    /// [`VXLAN_PORT`]: VXLAN_PORT
+    #[inline]
+    fn try_parse(envelope: Self::Envelope, _internal: Internal) -> Result<Self> {
+        ensure!(
+            envelope.dst_port() == VXLAN_PORT,
+            anyhow!("not a VXLAN packet.")
+        );
+
+        let mbuf = envelope.mbuf();
+        let offset = envelope.payload_offset();
+        let header = mbuf.read_data(offset)?;
+
+        let packet = Vxlan {
+            envelope,
+            header,
+            offset,
+        };
+
+        ensure!(
+            packet.flags() & VNI_VALID != 0,
+            anyhow!("invalid VXLAN flags 0x{:02x}.", packet.flags())
+        );
+
+        Ok(packet)
+    }
+
+    /// Prepends a VXLAN packet to the beginning of the UDP packet's payload.
+    ///
+    /// The UDP destination port is set to [`VXLAN_PORT`] and the I flag is
+    /// set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the buffer does not have enough free space.
+    ///
+    /// [`VXLAN_PORT`]: VXLAN_PORT
+    #[inline]
+    fn try_push(mut envelope: Self::Envelope, _internal: Internal) -> Result<Self> {
+        let offset = envelope.payload_offset();
+        let mbuf = envelope.mbuf_mut();
+
+        mbuf.extend(offset, VxlanHeader::size_of())?;
+        let header = mbuf.write_data(offset, &VxlanHeader::default())?;
+
+        envelope.set_dst_port(VXLAN_PORT);
+
+        Ok(Vxlan {
+            envelope,
+            header,
+            offset,
+        })
+    }
+
+    #[inline]
+    fn deparse(self) -> Self::Envelope {
+        self.envelope
+    }
+}
+
+/// A type alias for an IPv4 VXLAN packet.
+pub type Vxlan4 = Vxlan<Ipv4>;
+
+/// A type alias for an IPv6 VXLAN packet.
+pub type Vxlan6 = Vxlan<Ipv6>;
+
+/// VXLAN header.
+#[derive(Clone, Copy, Debug, SizeOf)]
+#[repr(C)]
+struct VxlanHeader {
+    flags: u8,
+    reserved: [u8; 3],
+    vni_reserved: u32be,
+}
+
+impl Default for VxlanHeader {
+    fn default() -> Self {
+        VxlanHeader {
+            flags: VNI_VALID,
+            reserved: [0; 3],
+            vni_reserved: u32be::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testils::byte_arrays::IPV4_TCP_PACKET;
+    use crate::Mbuf;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn size_of_vxlan_header() {
+        assert_eq!(8, VxlanHeader::size_of());
+    }
+
+    #[capsule::test]
+    fn push_vxlan_packet() {
+        let packet = Mbuf::new().unwrap();
+        let ethernet = packet.push::<Ethernet>().unwrap();
+        let ipv4 = ethernet.push::<Ipv4>().unwrap();
+        let udp = ipv4.push::<Udp<Ipv4>>().unwrap();
+        let mut vxlan = udp.push::<Vxlan4>().unwrap();
+
+        assert_eq!(8, vxlan.len());
+        assert_eq!(VXLAN_PORT, vxlan.envelope().dst_port());
+        assert_eq!(VNI_VALID, vxlan.flags());
+
+        vxlan.set_vni(0x12_3456);
+        assert_eq!(0x12_3456, vxlan.vni());
+    }
+
+    #[capsule::test]
+    fn encapsulate_and_decapsulate() {
+        let packet = Mbuf::from_bytes(&IPV4_TCP_PACKET).unwrap();
+        let inner = packet.parse::<Ethernet>().unwrap();
+        let inner_dst = inner.dst();
+
+        let src = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 50000);
+        let dst = SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), VXLAN_PORT);
+        let vxlan = Vxlan4::encapsulate(inner, 100, src, dst).unwrap();
+
+        assert_eq!(100, vxlan.vni());
+        assert_eq!(50000, vxlan.envelope().src_port());
+        assert_eq!(*dst.ip(), vxlan.envelope().envelope().dst());
+
+        let mbuf = vxlan.reset();
+        assert_eq!(IPV4_TCP_PACKET.len() + 14 + 20 + 8 + 8, mbuf.data_len());
+
+        let vxlan = mbuf
+            .parse::<Ethernet>()
+            .unwrap()
+            .parse::<Ipv4>()
+            .unwrap()
+            .parse::<Udp<Ipv4>>()
+            .unwrap()
+            .parse::<Vxlan4>()
+            .unwrap();
+        let (inner, vni) = vxlan.decapsulate().unwrap();
+
+        assert_eq!(100, vni);
+        assert_eq!(inner_dst, inner.dst());
+        assert_eq!(IPV4_TCP_PACKET.len(), inner.mbuf().data_len());
+    }
+}
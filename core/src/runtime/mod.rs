@@ -82,6 +82,9 @@ impl Runtime {
         info!("initializing EAL...");
         dpdk::eal_init(config.to_eal_args())?;
 
+        info!("initializing timer subsystem...");
+        dpdk::timer_init()?;
+
         #[cfg(feature = "metrics")]
         {
             info!("initializing metrics subsystem...");
@@ -120,6 +123,11 @@ impl Runtime {
                 .cores(&conf.cores)?
                 .mempools(&mut mempools)
                 .rx_tx_queue_capacity(conf.rxd, conf.txd)?
+                .rx_burst(conf.rx_burst)?
+                .mtu(conf.mtu)?
+                .rx_offloads(conf.rx_offloads)?
+                .tx_offloads(conf.tx_offloads)?
+                .symmetric_rss(conf.symmetric_rss)
                 .finish(conf.promiscuous, conf.multicast, conf.kni)?;
 
             debug!(?port);
@@ -624,6 +632,9 @@ impl Drop for Runtime {
             dpdk::kni_close();
         }
 
+        debug!("freeing timer subsystem.");
+        dpdk::timer_close();
+
         debug!("freeing EAL.");
         dpdk::eal_cleanup().unwrap();
     }
@@ -0,0 +1,622 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+
+//! Runtime introspection over a Unix socket, modeled after DPDK's
+//! telemetry library.
+//!
+//! Applications register commands with [`Telemetry::register_command`].
+//! A client connected to the socket started by [`Telemetry::serve`] sends
+//! a command and its optional parameters separated by a comma, for example
+//! `/ethdev/stats,0`, and receives the command's output as JSON, for
+//! example `{"/ethdev/stats": {"ipackets": 10, ...}}`. On connect, the
+//! server first sends an information message with the version and the
+//! process id.
+//!
+//! The command callback API of DPDK's telemetry library was added in DPDK
+//! 20.05, and is not available in the DPDK version Capsule builds against.
+//! The registry and the socket server are implemented in Rust instead,
+//! following the same request and response format. Unlike DPDK, the
+//! socket is a stream socket, and each read is treated as one request.
+//!
+//! # Built-in Commands
+//!
+//! * `/`, lists all the registered commands.
+//! * `/help`, returns the help text of the command in the parameters.
+//! * `/info`, returns the version and the process id.
+//! * `/ethdev/stats`, returns the stats of the port id in the parameters.
+//!   Registered when the first port is initialized.
+//! * `/ring/info`, returns the size and the usage of the ring named in the
+//!   parameters. Registered when the first ring is created.
+//! * `/mempool/info`, returns the size and the usage of the mempool named
+//!   in the parameters. Registered when the first mempool is created.
+
+use crate::dpdk::PortStats;
+use crate::ffi::{self, AsStr};
+use crate::{debug, ensure, warn};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::fmt::{self, Write as _};
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use std::{fs, process};
+use thiserror::Error;
+
+/// The maximum length of a command name, matching DPDK.
+const MAX_CMD_LEN: usize = 56;
+
+/// The maximum length of a command help text, matching DPDK.
+const MAX_HELP_LEN: usize = 128;
+
+/// The maximum length of a request.
+const MAX_REQUEST_LEN: usize = 1024;
+
+/// How often the server checks whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Telemetry errors.
+#[derive(Debug, Error)]
+pub enum TelemetryError {
+    /// The command name is not valid.
+    #[error("Invalid telemetry command '{0}'.")]
+    InvalidCommand(String),
+
+    /// A command with the same name is already registered.
+    #[error("Telemetry command '{0}' is already registered.")]
+    DuplicateCommand(String),
+}
+
+/// A value returned by a telemetry command.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TelemetryData {
+    /// A signed integer.
+    Int(i64),
+    /// A string.
+    Str(String),
+    /// A list of values.
+    Array(Vec<TelemetryData>),
+    /// A list of named values, in order.
+    Dict(Vec<(String, TelemetryData)>),
+}
+
+impl TelemetryData {
+    /// Returns the value formatted as JSON.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    fn write_json(&self, out: &mut String) {
+        match self {
+            TelemetryData::Int(value) => {
+                let _ = write!(out, "{}", value);
+            }
+            TelemetryData::Str(value) => write_json_str(value, out),
+            TelemetryData::Array(values) => {
+                out.push('[');
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        out.push_str(", ");
+                    }
+                    value.write_json(out);
+                }
+                out.push(']');
+            }
+            TelemetryData::Dict(entries) => {
+                out.push('{');
+                for (idx, (key, value)) in entries.iter().enumerate() {
+                    if idx > 0 {
+                        out.push_str(", ");
+                    }
+                    write_json_str(key, out);
+                    out.push_str(": ");
+                    value.write_json(out);
+                }
+                out.push('}');
+            }
+        }
+    }
+}
+
+/// Writes a JSON string literal with the special characters escaped.
+fn write_json_str(value: &str, out: &mut String) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl From<i64> for TelemetryData {
+    fn from(value: i64) -> Self {
+        TelemetryData::Int(value)
+    }
+}
+
+impl From<u64> for TelemetryData {
+    /// Values larger than `i64::MAX` are saturated.
+    fn from(value: u64) -> Self {
+        TelemetryData::Int(value.min(i64::max_value() as u64) as i64)
+    }
+}
+
+impl From<&str> for TelemetryData {
+    fn from(value: &str) -> Self {
+        TelemetryData::Str(value.to_owned())
+    }
+}
+
+impl From<String> for TelemetryData {
+    fn from(value: String) -> Self {
+        TelemetryData::Str(value)
+    }
+}
+
+impl fmt::Display for TelemetryData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_json())
+    }
+}
+
+type Callback = Arc<dyn Fn(&str) -> TelemetryData + Send + Sync>;
+
+/// A registered command.
+struct Command {
+    help: String,
+    callback: Callback,
+}
+
+static COMMANDS: Lazy<RwLock<BTreeMap<String, Command>>> =
+    Lazy::new(|| RwLock::new(BTreeMap::new()));
+
+/// Returns whether the command name is `/` followed by alphanumeric
+/// characters, `_` or `/`.
+fn is_valid_command(name: &str) -> bool {
+    name.starts_with('/')
+        && name.len() <= MAX_CMD_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '/')
+}
+
+/// The information message, also the output of `/info`.
+fn info() -> TelemetryData {
+    TelemetryData::Dict(vec![
+        (
+            "version".to_owned(),
+            format!("capsule {}", env!("CARGO_PKG_VERSION")).into(),
+        ),
+        ("pid".to_owned(), i64::from(process::id()).into()),
+        ("max_output_len".to_owned(), 16384i64.into()),
+    ])
+}
+
+/// Runtime introspection commands.
+///
+/// # Example
+///
+/// ```
+/// Telemetry::register_command("/app/flows", "Returns the number of flows.", |_| {
+///     TelemetryData::Int(FLOWS.load(Ordering::Relaxed))
+/// })?;
+///
+/// let server = Telemetry::serve("/var/run/capsule/telemetry")?;
+/// ```
+#[derive(Debug)]
+pub struct Telemetry;
+
+impl Telemetry {
+    /// Registers a command. The callback is invoked with the parameters
+    /// following the command name, or an empty string if there are none.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TelemetryError::InvalidCommand` if the name does not start
+    /// with `/`, is longer than 56 characters, or has characters other
+    /// than alphanumerics, `_` and `/`, or if the help text is longer than
+    /// 128 characters. Returns `TelemetryError::DuplicateCommand` if the
+    /// command is already registered.
+    pub fn register_command<F>(name: &str, help: &str, callback: F) -> Result<()>
+    where
+        F: Fn(&str) -> TelemetryData + Send + Sync + 'static,
+    {
+        ensure!(
+            is_valid_command(name) && help.len() <= MAX_HELP_LEN,
+            TelemetryError::InvalidCommand(name.to_owned())
+        );
+
+        let mut commands = COMMANDS.write().unwrap();
+        ensure!(
+            !commands.contains_key(name),
+            TelemetryError::DuplicateCommand(name.to_owned())
+        );
+
+        commands.insert(
+            name.to_owned(),
+            Command {
+                help: help.to_owned(),
+                callback: Arc::new(callback),
+            },
+        );
+
+        debug!("registered telemetry command {}.", name);
+        Ok(())
+    }
+
+    /// Runs the request and returns the response as JSON.
+    ///
+    /// The request is the command name, optionally followed by a comma and
+    /// the parameters. The output of an unknown command is `null`.
+    pub fn handle_request(request: &str) -> String {
+        let request = request.trim();
+        let (name, params) = match request.find(',') {
+            Some(idx) => (&request[..idx], &request[idx + 1..]),
+            None => (request, ""),
+        };
+
+        let output = match name {
+            "/" => Some(TelemetryData::Array(
+                COMMANDS
+                    .read()
+                    .unwrap()
+                    .keys()
+                    .map(|name| name.as_str().into())
+                    .chain(vec!["/".into(), "/help".into(), "/info".into()])
+                    .collect(),
+            )),
+            "/help" => COMMANDS.read().unwrap().get(params).map(|command| {
+                TelemetryData::Dict(vec![(params.to_owned(), command.help.as_str().into())])
+            }),
+            "/info" => Some(info()),
+            _ => {
+                // clones the callback so the lock is not held while it runs.
+                let callback = COMMANDS
+                    .read()
+                    .unwrap()
+                    .get(name)
+                    .map(|command| command.callback.clone());
+                callback.map(|callback| callback(params))
+            }
+        };
+
+        let mut out = String::from("{");
+        write_json_str(name, &mut out);
+        out.push_str(": ");
+        match output {
+            Some(output) => output.write_json(&mut out),
+            None => out.push_str("null"),
+        }
+        out.push('}');
+        out
+    }
+
+    /// Starts serving the registered commands on a Unix socket at `path`.
+    /// The socket file is removed when the server is stopped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket cannot be bound.
+    pub fn serve<P: AsRef<Path>>(path: P) -> Result<TelemetryServer> {
+        let path = path.as_ref().to_path_buf();
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = running.clone();
+
+        let handle = thread::Builder::new()
+            .name("telemetry".to_owned())
+            .spawn(move || {
+                while flag.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            let flag = flag.clone();
+                            thread::spawn(move || {
+                                if let Err(err) = serve_client(stream, &flag) {
+                                    warn!(message = "telemetry client error.", ?err);
+                                }
+                            });
+                        }
+                        Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(POLL_INTERVAL);
+                        }
+                        Err(err) => {
+                            warn!(message = "telemetry accept error.", ?err);
+                            thread::sleep(POLL_INTERVAL);
+                        }
+                    }
+                }
+            })?;
+
+        debug!(?path, "started telemetry server.");
+        Ok(TelemetryServer {
+            path,
+            running,
+            handle: Some(handle),
+        })
+    }
+}
+
+/// Sends the information message, then answers the requests until the
+/// client disconnects or the server stops.
+fn serve_client(mut stream: UnixStream, running: &AtomicBool) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL * 10))?;
+    stream.write_all(info().to_json().as_bytes())?;
+
+    let mut buf = [0u8; MAX_REQUEST_LEN];
+    while running.load(Ordering::Relaxed) {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => {
+                let request = String::from_utf8_lossy(&buf[..len]);
+                let response = Telemetry::handle_request(&request);
+                stream.write_all(response.as_bytes())?;
+            }
+            Err(ref err)
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+/// A handle to a running telemetry server. The server is stopped when the
+/// handle is dropped.
+#[derive(Debug)]
+pub struct TelemetryServer {
+    path: PathBuf,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TelemetryServer {
+    /// Returns the path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stops the server and waits for it to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+            let _ = fs::remove_file(&self.path);
+            debug!(path = ?self.path, "stopped telemetry server.");
+        }
+    }
+}
+
+impl Drop for TelemetryServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Registers a built-in command, ignoring it if already registered.
+fn register_builtin<F>(name: &str, help: &str, callback: F)
+where
+    F: Fn(&str) -> TelemetryData + Send + Sync + 'static,
+{
+    if !COMMANDS.read().unwrap().contains_key(name) {
+        let _ = Telemetry::register_command(name, help, callback);
+    }
+}
+
+/// Returns an error message as the command output.
+fn error_output(message: String) -> TelemetryData {
+    TelemetryData::Dict(vec![("error".to_owned(), message.into())])
+}
+
+/// Registers the `/ethdev/stats` command.
+pub(crate) fn register_port_stats() {
+    register_builtin(
+        "/ethdev/stats",
+        "Returns the stats of a port. Parameters: int port_id",
+        |params| {
+            let stats = params
+                .parse::<u16>()
+                .map_err(anyhow::Error::from)
+                .and_then(PortStats::query);
+
+            match stats {
+                Ok(stats) => TelemetryData::Dict(vec![
+                    ("ipackets".to_owned(), stats.rx_packets().into()),
+                    ("opackets".to_owned(), stats.tx_packets().into()),
+                    ("ibytes".to_owned(), stats.rx_bytes().into()),
+                    ("obytes".to_owned(), stats.tx_bytes().into()),
+                    ("imissed".to_owned(), stats.rx_missed().into()),
+                    ("ierrors".to_owned(), stats.rx_errors().into()),
+                    ("oerrors".to_owned(), stats.tx_errors().into()),
+                    ("rx_nombuf".to_owned(), stats.rx_nombuf().into()),
+                ]),
+                Err(err) => error_output(err.to_string()),
+            }
+        },
+    );
+}
+
+/// Registers the `/ring/info` command.
+pub(crate) fn register_ring_info() {
+    register_builtin(
+        "/ring/info",
+        "Returns the size and usage of a ring. Parameters: string name",
+        |params| {
+            let raw = CString::new(params)
+                .ok()
+                .map(|name| unsafe { ffi::rte_ring_lookup(name.as_ptr()) })
+                .filter(|raw| !raw.is_null());
+
+            match raw {
+                Some(raw) => {
+                    let ring = unsafe { &*raw };
+                    let count = ring.prod.tail.wrapping_sub(ring.cons.tail) & ring.mask;
+                    let count = count.min(ring.capacity);
+                    TelemetryData::Dict(vec![
+                        ("name".to_owned(), ring.name[..].as_str().into()),
+                        ("capacity".to_owned(), i64::from(ring.capacity).into()),
+                        ("count".to_owned(), i64::from(count).into()),
+                        ("free".to_owned(), i64::from(ring.capacity - count).into()),
+                    ])
+                }
+                None => error_output(format!("ring '{}' not found.", params)),
+            }
+        },
+    );
+}
+
+/// Registers the `/mempool/info` command.
+pub(crate) fn register_mempool_info() {
+    register_builtin(
+        "/mempool/info",
+        "Returns the size and usage of a mempool. Parameters: string name",
+        |params| {
+            let raw = CString::new(params)
+                .ok()
+                .map(|name| unsafe { ffi::rte_mempool_lookup(name.as_ptr()) })
+                .filter(|raw| !raw.is_null());
+
+            match raw {
+                Some(raw) => unsafe {
+                    TelemetryData::Dict(vec![
+                        ("name".to_owned(), (&(*raw).name)[..].as_str().into()),
+                        ("size".to_owned(), i64::from((*raw).size).into()),
+                        (
+                            "avail".to_owned(),
+                            i64::from(ffi::rte_mempool_avail_count(raw)).into(),
+                        ),
+                        (
+                            "in_use".to_owned(),
+                            i64::from(ffi::rte_mempool_in_use_count(raw)).into(),
+                        ),
+                    ])
+                },
+                None => error_output(format!("mempool '{}' not found.", params)),
+            }
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_telemetry_data() {
+        let data = TelemetryData::Dict(vec![
+            ("int".to_owned(), TelemetryData::Int(-1)),
+            ("str".to_owned(), "a \"b\"\n".into()),
+            (
+                "array".to_owned(),
+                TelemetryData::Array(vec![1i64.into(), 2i64.into()]),
+            ),
+        ]);
+
+        assert_eq!(
+            r#"{"int": -1, "str": "a \"b\"\n", "array": [1, 2]}"#,
+            data.to_json()
+        );
+    }
+
+    #[test]
+    fn register_and_run_command() {
+        Telemetry::register_command("/test/echo", "Echoes the parameters.", |params| {
+            params.into()
+        })
+        .unwrap();
+
+        assert!(Telemetry::register_command("/test/echo", "", |_| 0i64.into()).is_err());
+        assert!(Telemetry::register_command("no/slash", "", |_| 0i64.into()).is_err());
+        assert!(Telemetry::register_command("/a b", "", |_| 0i64.into()).is_err());
+
+        assert_eq!(
+            r#"{"/test/echo": "hello, world"}"#,
+            Telemetry::handle_request("/test/echo,hello, world\n")
+        );
+        assert_eq!(
+            r#"{"/help": {"/test/echo": "Echoes the parameters."}}"#,
+            Telemetry::handle_request("/help,/test/echo")
+        );
+        assert!(Telemetry::handle_request("/").contains("\"/test/echo\""));
+        assert_eq!(r#"{"/none": null}"#, Telemetry::handle_request("/none"));
+    }
+
+    #[test]
+    fn serve_over_unix_socket() {
+        Telemetry::register_command("/test/sum", "Sums the parameters.", |params| {
+            TelemetryData::Int(
+                params
+                    .split(',')
+                    .filter_map(|v| v.parse::<i64>().ok())
+                    .sum(),
+            )
+        })
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!("capsule-telemetry-{}", process::id()));
+        let _ = fs::remove_file(&path);
+        let server = Telemetry::serve(&path).unwrap();
+
+        let client = {
+            let path = path.clone();
+            thread::spawn(move || {
+                let mut stream = UnixStream::connect(path).unwrap();
+                let mut buf = [0u8; 1024];
+
+                let len = stream.read(&mut buf).unwrap();
+                let info = String::from_utf8_lossy(&buf[..len]).into_owned();
+
+                stream.write_all(b"/test/sum,1,2,3").unwrap();
+                let len = stream.read(&mut buf).unwrap();
+                let response = String::from_utf8_lossy(&buf[..len]).into_owned();
+
+                (info, response)
+            })
+        };
+
+        let (info, response) = client.join().unwrap();
+        assert!(info.contains("\"pid\""));
+        assert_eq!(r#"{"/test/sum": 6}"#, response);
+
+        server.stop();
+        assert!(!path.exists());
+    }
+}
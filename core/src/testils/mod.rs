@@ -22,9 +22,11 @@ pub mod byte_arrays;
 pub mod criterion;
 mod packet;
 pub mod proptest;
+mod queue;
 mod rvg;
 
 pub use self::packet::*;
+pub use self::queue::*;
 pub use self::rvg::*;
 
 use crate::dpdk::{self, Mempool, SocketId, MEMPOOL};
@@ -43,6 +45,7 @@ pub fn cargo_test_init() {
             "--iova-mode=va".to_owned(),
         ])
         .unwrap();
+        dpdk::timer_init().unwrap();
         let _ = metrics::init();
     });
 }
@@ -0,0 +1,171 @@
+/*
+* Copyright 2019 Comcast Cable Communications Management, LLC
+*
+* Licensed under the Apache License, Version 2.0 (the "License");
+* you may not use this file except in compliance with the License.
+* You may obtain a copy of the License at
+*
+* http://www.apache.org/licenses/LICENSE-2.0
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific language governing permissions and
+* limitations under the License.
+*
+* SPDX-License-Identifier: Apache-2.0
+*/
+//! Software queues for testing pipelines without Ethernet devices.
+
+use crate::batch::{PacketRx, PacketTx};
+use crate::Mbuf;
+use crossbeam_channel::{self, Receiver, Sender, TrySendError};
+use std::iter;
+
+/// The maximum number of packets received in one burst, the same as the
+/// default of the port queues.
+const RX_BURST: usize = 32;
+
+/// The transmit end of a software queue.
+///
+/// Packets transmitted when the queue is full are dropped, the same as a
+/// full transmit queue of an Ethernet device.
+#[derive(Clone, Debug)]
+pub struct SoftwareTxQueue {
+    sender: Sender<Mbuf>,
+}
+
+impl SoftwareTxQueue {
+    /// Transmits the packets to the connected receive queue.
+    pub fn transmit(&self, packets: Vec<Mbuf>) {
+        for packet in packets {
+            if let Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) =
+                self.sender.try_send(packet)
+            {
+                // the packet is freed when dropped.
+                break;
+            }
+        }
+    }
+}
+
+impl PacketTx for SoftwareTxQueue {
+    fn transmit(&mut self, packets: Vec<Mbuf>) {
+        SoftwareTxQueue::transmit(self, packets)
+    }
+}
+
+/// The receive end of a software queue.
+#[derive(Clone, Debug)]
+pub struct SoftwareRxQueue {
+    receiver: Receiver<Mbuf>,
+}
+
+impl SoftwareRxQueue {
+    /// Receives a burst of up to 32 packets from the queue. Returns an
+    /// empty burst if the queue is empty.
+    pub fn receive(&self) -> Vec<Mbuf> {
+        iter::from_fn(|| self.receiver.try_recv().ok())
+            .take(RX_BURST)
+            .collect()
+    }
+
+    /// Returns the number of packets in the queue.
+    pub fn len(&self) -> usize {
+        self.receiver.len()
+    }
+
+    /// Returns whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
+}
+
+impl PacketRx for SoftwareRxQueue {
+    fn receive(&mut self) -> Vec<Mbuf> {
+        SoftwareRxQueue::receive(self)
+    }
+}
+
+/// Creates software queues that stand in for the queues of a port.
+///
+/// A pair of queues is connected, what's transmitted to one end is
+/// received from the other. A test feeds packets through the transmit end
+/// of a pair into a pipeline polling the receive end, and collects what
+/// the pipeline sends to another pair.
+///
+/// # Example
+///
+/// ```
+/// let (mut input, rx) = MockPort::new_pair(64);
+/// let (tx, output) = MockPort::new_pair(64);
+///
+/// input.transmit(vec![Mbuf::from_bytes(&IPV4_UDP_PACKET)?]);
+/// let mut pipeline = Poll::new(rx).map(|packet| packet.parse::<Ethernet>()).send(tx);
+/// pipeline.run_once();
+///
+/// assert_eq!(1, output.len());
+/// ```
+#[derive(Debug)]
+pub struct MockPort;
+
+impl MockPort {
+    /// Creates a connected pair of queues holding up to `capacity` packets.
+    pub fn new_pair(capacity: usize) -> (SoftwareTxQueue, SoftwareRxQueue) {
+        let (sender, receiver) = crossbeam_channel::bounded(capacity);
+        (SoftwareTxQueue { sender }, SoftwareRxQueue { receiver })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::{Batch, Pipeline, Poll};
+    use crate::packets::{Ethernet, Packet};
+    use crate::testils::byte_arrays::IPV4_UDP_PACKET;
+
+    // the queues themselves don't need EAL, but the mbufs moving through
+    // them are allocated from a mempool, so the tests still run as
+    // `capsule::test`.
+
+    #[capsule::test]
+    fn receive_what_is_transmitted() {
+        let (tx, rx) = MockPort::new_pair(64);
+        assert!(rx.is_empty());
+
+        let packets = (0..40)
+            .map(|_| Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap())
+            .collect();
+        tx.transmit(packets);
+
+        assert_eq!(40, rx.len());
+        assert_eq!(RX_BURST, rx.receive().len());
+        assert_eq!(8, rx.receive().len());
+        assert!(rx.receive().is_empty());
+    }
+
+    #[capsule::test]
+    fn drop_when_full() {
+        let (tx, rx) = MockPort::new_pair(2);
+        let packets = (0..4)
+            .map(|_| Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap())
+            .collect();
+        tx.transmit(packets);
+
+        assert_eq!(2, rx.len());
+    }
+
+    #[capsule::test]
+    fn run_pipeline() {
+        let (input, rx) = MockPort::new_pair(64);
+        let (tx, output) = MockPort::new_pair(64);
+
+        input.transmit(vec![Mbuf::from_bytes(&IPV4_UDP_PACKET).unwrap()]);
+        let mut pipeline = Poll::new(rx)
+            .map(|packet| packet.parse::<Ethernet>())
+            .send(tx);
+        pipeline.run_once();
+
+        assert_eq!(1, output.len());
+    }
+}
@@ -21,10 +21,32 @@
 
 // all the necessary DPDK functions, types and constants are defined
 // in the following header files.
+#include <rte_acl.h>
+#include <rte_compressdev.h>
+#include <rte_cryptodev.h>
 #include <rte_eal.h>
 #include <rte_errno.h>
+#include <rte_eth_bond.h>
 #include <rte_ethdev.h>
+#include <rte_eventdev.h>
+#include <rte_gro.h>
+#include <rte_gso.h>
+#include <rte_hash.h>
+#include <rte_ip_frag.h>
+#include <rte_ipsec.h>
 #include <rte_kni.h>
+#include <rte_lpm.h>
+#include <rte_lpm6.h>
+#include <rte_malloc.h>
+#include <rte_meter.h>
+#include <rte_pdump.h>
+#include <rte_rcu_qsbr.h>
+#include <rte_reorder.h>
+#include <rte_ring.h>
+#include <rte_sched.h>
+#include <rte_timer.h>
+#include <rte_version.h>
+#include <rte_vhost.h>
 
 // libnuma functions and types
 #include <numa.h>
@@ -87,3 +109,222 @@ uint16_t _rte_eth_tx_burst(
     uint16_t queue_id,
     struct rte_mbuf **tx_pkts,
     uint16_t nb_pkts);
+
+/**
+ * Enqueue several objects on a ring. This function is safe for multiple
+ * producers unless the ring is created with the single-producer flag.
+ */
+unsigned int _rte_ring_enqueue_burst(
+    struct rte_ring *r,
+    void *const *obj_table,
+    unsigned int n,
+    unsigned int *free_space);
+
+/**
+ * Dequeue several objects from a ring. This function is safe for multiple
+ * consumers unless the ring is created with the single-consumer flag.
+ */
+unsigned int _rte_ring_dequeue_burst(
+    struct rte_ring *r,
+    void **obj_table,
+    unsigned int n,
+    unsigned int *available);
+
+/**
+ * Get the number of cycles in one second for the default timer.
+ */
+uint64_t _rte_get_timer_hz(void);
+
+/**
+ * Return the number of TSC cycles since boot.
+ */
+uint64_t _rte_get_tsc_cycles(void);
+
+/**
+ * Return the lcore id of the calling thread, or LCORE_ID_ANY if the
+ * thread is not an EAL thread.
+ */
+unsigned _rte_lcore_id(void);
+
+/**
+ * Dump the statistics of all the malloc heaps to stdout.
+ */
+void _rte_malloc_dump_stats(const char *type);
+
+/**
+ * Lookup an IP into the LPM table.
+ */
+int _rte_lpm_lookup(
+    struct rte_lpm *lpm,
+    uint32_t ip,
+    uint32_t *next_hop);
+
+/**
+ * Lookup multiple IP addresses in an LPM table.
+ */
+int _rte_lpm_lookup_bulk(
+    const struct rte_lpm *lpm,
+    const uint32_t *ips,
+    uint32_t *next_hops,
+    unsigned int n);
+
+/**
+ * Calculate the Jenkins hash of a key. Can be used as the `hash_func`
+ * of `rte_hash_parameters`.
+ */
+uint32_t _rte_jhash(const void *key, uint32_t length, uint32_t initval);
+
+/**
+ * Calculate the CRC32 hash of a key, using the SSE4.2 instructions if
+ * available. Can be used as the `hash_func` of `rte_hash_parameters`.
+ */
+uint32_t _rte_hash_crc(const void *data, uint32_t data_len, uint32_t init_val);
+
+/**
+ * Allocate a crypto operation from a mempool with default parameters set.
+ */
+struct rte_crypto_op *_rte_crypto_op_alloc(
+    struct rte_mempool *mempool,
+    enum rte_crypto_op_type type);
+
+/**
+ * Free a crypto operation back into its original mempool.
+ */
+void _rte_crypto_op_free(struct rte_crypto_op *op);
+
+/**
+ * Attach a symmetric session to a crypto operation.
+ */
+int _rte_crypto_op_attach_sym_session(
+    struct rte_crypto_op *op,
+    struct rte_cryptodev_sym_session *sess);
+
+/**
+ * Enqueue a burst of operations for processing on a crypto device.
+ */
+uint16_t _rte_cryptodev_enqueue_burst(
+    uint8_t dev_id,
+    uint16_t qp_id,
+    struct rte_crypto_op **ops,
+    uint16_t nb_ops);
+
+/**
+ * Dequeue a burst of processed operations from a queue pair of a crypto
+ * device.
+ */
+uint16_t _rte_cryptodev_dequeue_burst(
+    uint8_t dev_id,
+    uint16_t qp_id,
+    struct rte_crypto_op **ops,
+    uint16_t nb_ops);
+
+/**
+ * Enqueue a burst of events on an event port of an event device.
+ */
+uint16_t _rte_event_enqueue_burst(
+    uint8_t dev_id,
+    uint8_t port_id,
+    const struct rte_event ev[],
+    uint16_t nb_events);
+
+/**
+ * Dequeue a burst of events from an event port of an event device,
+ * waiting up to the timeout if no event is available.
+ */
+uint16_t _rte_event_dequeue_burst(
+    uint8_t dev_id,
+    uint8_t port_id,
+    struct rte_event ev[],
+    uint16_t nb_events,
+    uint64_t timeout_ticks);
+
+/**
+ * Color the packet with the single-rate three-color marker in color
+ * blind mode, and update the token buckets.
+ */
+enum rte_color _rte_meter_srtcm_color_blind_check(
+    struct rte_meter_srtcm *m,
+    struct rte_meter_srtcm_profile *p,
+    uint64_t time,
+    uint32_t pkt_len);
+
+/**
+ * Color the packet with the two-rate three-color marker in color blind
+ * mode, and update the token buckets.
+ */
+enum rte_color _rte_meter_trtcm_color_blind_check(
+    struct rte_meter_trtcm *m,
+    struct rte_meter_trtcm_profile *p,
+    uint64_t time,
+    uint32_t pkt_len);
+
+/**
+ * Chain an mbuf to another, thereby creating a segmented packet.
+ */
+int _rte_pktmbuf_chain(struct rte_mbuf *head, struct rte_mbuf *tail);
+
+/**
+ * Linearize the data of a segmented packet into the first segment.
+ */
+int _rte_pktmbuf_linearize(struct rte_mbuf *mbuf);
+
+/**
+ * Remove len bytes of data at the end of the mbuf.
+ */
+int _rte_pktmbuf_trim(struct rte_mbuf *m, uint16_t len);
+
+/**
+ * Reads the reference counter of an mbuf.
+ */
+uint16_t _rte_mbuf_refcnt_read(const struct rte_mbuf *m);
+
+/**
+ * Adds a value to the reference counter of an mbuf.
+ */
+uint16_t _rte_mbuf_refcnt_update(struct rte_mbuf *m, int16_t value);
+
+/**
+ * Reads the address and the identifiers of a device on the PCI bus.
+ */
+void _rte_pci_device_info(
+    const struct rte_device *dev,
+    struct rte_pci_addr *addr,
+    struct rte_pci_id *id);
+
+/**
+ * Marks a registered reader thread as online.
+ */
+void _rte_rcu_qsbr_thread_online(struct rte_rcu_qsbr *v, unsigned int thread_id);
+
+/**
+ * Marks a registered reader thread as offline.
+ */
+void _rte_rcu_qsbr_thread_offline(struct rte_rcu_qsbr *v, unsigned int thread_id);
+
+/**
+ * Reports a quiescent state of a reader thread.
+ */
+void _rte_rcu_qsbr_quiescent(struct rte_rcu_qsbr *v, unsigned int thread_id);
+
+/**
+ * Function returning version string.
+ */
+const char *_rte_version(void);
+
+/**
+ * Prepare crypto operations for a burst of packets of a lookaside IPsec
+ * session.
+ */
+uint16_t _rte_ipsec_pkt_crypto_prepare(
+    const struct rte_ipsec_session *ss,
+    struct rte_mbuf *mb[],
+    struct rte_crypto_op *cop[],
+    uint16_t num);
+
+/**
+ * Finalize the processing of a burst of packets of an IPsec session.
+ */
+uint16_t _rte_ipsec_pkt_process(
+    const struct rte_ipsec_session *ss,
+    struct rte_mbuf *mb[],
+    uint16_t num);
@@ -412,6 +412,100 @@ pub const RTE_MEMZONE_NAMESIZE: u32 = 32;
 pub const RTE_TAILQ_RING_NAME: &'static [u8; 9usize] = b"RTE_RING\0";
 pub const RTE_RING_MZ_PREFIX: &'static [u8; 4usize] = b"RG_\0";
 pub const RTE_RING_SZ_MASK: u32 = 2147483647;
+pub const RING_F_SP_ENQ: u32 = 1;
+pub const RING_F_SC_DEQ: u32 = 2;
+pub const RING_F_EXACT_SZ: u32 = 4;
+pub const RTE_LPM_NAMESIZE: u32 = 32;
+pub const RTE_LPM_MAX_DEPTH: u32 = 32;
+pub const RTE_LPM_LOOKUP_SUCCESS: u32 = 16777216;
+pub const RTE_LPM6_MAX_DEPTH: u32 = 128;
+pub const RTE_LPM6_IPV6_ADDR_SIZE: u32 = 16;
+pub const RTE_LPM6_NAMESIZE: u32 = 32;
+pub const RTE_HASH_ENTRIES_MAX: u32 = 1073741824;
+pub const RTE_GSO_SEG_SIZE_MIN: u32 = 256;
+pub const RTE_GSO_FLAG_IPID_FIXED: u32 = 1;
+pub const RTE_CRYPTODEV_NAME_MAX_LEN: u32 = 64;
+pub const RTE_CRYPTODEV_FF_SYMMETRIC_CRYPTO: u32 = 1;
+pub const RTE_CRYPTODEV_FF_ASYMMETRIC_CRYPTO: u32 = 2;
+pub const RTE_CRYPTODEV_FF_HW_ACCELERATED: u32 = 8;
+pub const RTE_SCHED_TYPE_ORDERED: u32 = 0;
+pub const RTE_SCHED_TYPE_ATOMIC: u32 = 1;
+pub const RTE_SCHED_TYPE_PARALLEL: u32 = 2;
+pub const RTE_EVENT_TYPE_ETHDEV: u32 = 0;
+pub const RTE_EVENT_TYPE_CPU: u32 = 3;
+pub const RTE_EVENT_OP_NEW: u32 = 0;
+pub const RTE_EVENT_OP_FORWARD: u32 = 1;
+pub const RTE_EVENT_OP_RELEASE: u32 = 2;
+pub const RTE_EVENT_DEV_PRIORITY_HIGHEST: u32 = 0;
+pub const RTE_EVENT_DEV_PRIORITY_NORMAL: u32 = 128;
+pub const RTE_EVENT_DEV_PRIORITY_LOWEST: u32 = 255;
+pub const RTE_EVENT_DEV_CAP_QUEUE_ALL_TYPES: u32 = 4;
+pub const RTE_EVENT_QUEUE_CFG_ALL_TYPES: u32 = 1;
+pub const IP_FRAG_DEATH_ROW_LEN: u32 = 32;
+pub const IP_FRAG_DEATH_ROW_MBUF_LEN: u32 = 160;
+pub const RTE_GRO_MAX_BURST_ITEM_NUM: u32 = 128;
+pub const RTE_GRO_TYPE_MAX_NUM: u32 = 64;
+pub const RTE_GRO_TYPE_SUPPORT_NUM: u32 = 4;
+pub const RTE_GRO_TCP_IPV4_INDEX: u32 = 0;
+pub const RTE_GRO_TCP_IPV4: u32 = 1;
+pub const RTE_GRO_IPV4_VXLAN_TCP_IPV4_INDEX: u32 = 1;
+pub const RTE_GRO_IPV4_VXLAN_TCP_IPV4: u32 = 2;
+pub const RTE_GRO_UDP_IPV4_INDEX: u32 = 2;
+pub const RTE_GRO_UDP_IPV4: u32 = 4;
+pub const RTE_GRO_IPV4_VXLAN_UDP_IPV4_INDEX: u32 = 3;
+pub const RTE_GRO_IPV4_VXLAN_UDP_IPV4: u32 = 8;
+pub const RTE_COMP_LEVEL_PMD_DEFAULT: i32 = -1;
+pub const RTE_COMP_LEVEL_NONE: u32 = 0;
+pub const RTE_COMP_LEVEL_MIN: u32 = 1;
+pub const RTE_COMP_LEVEL_MAX: u32 = 9;
+pub const RTE_IPSEC_SAFLAG_SQN_ATOM: u32 = 1;
+pub const RTE_IPSEC_SATP_IPV_MASK: u32 = 1;
+pub const RTE_IPSEC_SATP_IPV4: u32 = 0;
+pub const RTE_IPSEC_SATP_IPV6: u32 = 1;
+pub const RTE_IPSEC_SATP_PROTO_MASK: u32 = 2;
+pub const RTE_IPSEC_SATP_PROTO_AH: u32 = 0;
+pub const RTE_IPSEC_SATP_PROTO_ESP: u32 = 2;
+pub const RTE_IPSEC_SATP_DIR_MASK: u32 = 4;
+pub const RTE_IPSEC_SATP_DIR_IB: u32 = 0;
+pub const RTE_IPSEC_SATP_DIR_OB: u32 = 4;
+pub const RTE_IPSEC_SATP_MODE_MASK: u32 = 24;
+pub const RTE_IPSEC_SATP_MODE_TRANS: u32 = 0;
+pub const RTE_IPSEC_SATP_MODE_TUNLV4: u32 = 8;
+pub const RTE_IPSEC_SATP_MODE_TUNLV6: u32 = 16;
+pub const RTE_IPSEC_SATP_SQN_MASK: u32 = 32;
+pub const RTE_IPSEC_SATP_SQN_RAW: u32 = 0;
+pub const RTE_IPSEC_SATP_SQN_ATOM: u32 = 32;
+pub const RTE_IPSEC_SATP_ESN_MASK: u32 = 64;
+pub const RTE_IPSEC_SATP_ESN_ENABLE: u32 = 0;
+pub const RTE_IPSEC_SATP_ESN_DISABLE: u32 = 64;
+pub const RTE_SCHED_QUEUES_PER_PIPE: u32 = 16;
+pub const RTE_SCHED_BE_QUEUES_PER_PIPE: u32 = 4;
+pub const RTE_SCHED_TRAFFIC_CLASSES_PER_PIPE: u32 = 13;
+pub const RTE_SCHED_TRAFFIC_CLASS_BE: u32 = 12;
+pub const RTE_SCHED_FRAME_OVERHEAD_DEFAULT: u32 = 24;
+pub const RTE_VHOST_USER_CLIENT: u32 = 1;
+pub const RTE_VHOST_USER_NO_RECONNECT: u32 = 2;
+pub const RTE_VHOST_USER_DEQUEUE_ZERO_COPY: u32 = 4;
+pub const RTE_VHOST_USER_IOMMU_SUPPORT: u32 = 8;
+pub const RTE_VHOST_USER_POSTCOPY_SUPPORT: u32 = 16;
+pub const RTE_ACL_MAX_CATEGORIES: u32 = 16;
+pub const RTE_ACL_RESULTS_MULTIPLIER: u32 = 4;
+pub const RTE_ACL_MAX_LEVELS: u32 = 64;
+pub const RTE_ACL_MAX_FIELDS: u32 = 64;
+pub const RTE_ACL_NAMESIZE: u32 = 32;
+pub const RTE_ACL_MAX_PRIORITY: u32 = 536870911;
+pub const RTE_ACL_MIN_PRIORITY: u32 = 0;
+pub const RTE_ACL_FIELD_TYPE_MASK: u32 = 0;
+pub const RTE_ACL_FIELD_TYPE_RANGE: u32 = 1;
+pub const RTE_ACL_FIELD_TYPE_BITMASK: u32 = 2;
+pub const RTE_HASH_NAMESIZE: u32 = 32;
+pub const RTE_HASH_LOOKUP_BULK_MAX: u32 = 64;
+pub const RTE_HASH_EXTRA_FLAGS_TRANS_MEM_SUPPORT: u32 = 1;
+pub const RTE_HASH_EXTRA_FLAGS_MULTI_WRITER_ADD: u32 = 2;
+pub const RTE_HASH_EXTRA_FLAGS_RW_CONCURRENCY: u32 = 4;
+pub const RTE_HASH_EXTRA_FLAGS_EXT_TABLE: u32 = 8;
+pub const RTE_HASH_EXTRA_FLAGS_NO_FREE_ON_DEL: u32 = 16;
+pub const RTE_HASH_EXTRA_FLAGS_RW_CONCURRENCY_LF: u32 = 32;
 pub const RTE_MEMPOOL_HEADER_COOKIE1: i64 = -4982197544707871147;
 pub const RTE_MEMPOOL_HEADER_COOKIE2: i64 = -941548164385788331;
 pub const RTE_MEMPOOL_TRAILER_COOKIE: i64 = -5921418378119291987;
@@ -21439,6 +21533,60 @@ extern "C" {
         user_cb: *const rte_eth_rxtx_callback,
     ) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn rte_eth_bond_create(
+        name: *const ::std::os::raw::c_char,
+        mode: u8,
+        socket_id: u8,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_free(name: *const ::std::os::raw::c_char) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_slave_add(bonded_port_id: u16, slave_port_id: u16)
+        -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_slave_remove(
+        bonded_port_id: u16,
+        slave_port_id: u16,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_mode_set(bonded_port_id: u16, mode: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_mode_get(bonded_port_id: u16) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_primary_set(bonded_port_id: u16, slave_port_id: u16)
+        -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_primary_get(bonded_port_id: u16) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_slaves_get(
+        bonded_port_id: u16,
+        slaves: *mut u16,
+        len: u16,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_active_slaves_get(
+        bonded_port_id: u16,
+        slaves: *mut u16,
+        len: u16,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_xmit_policy_set(bonded_port_id: u16, policy: u8)
+        -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_eth_bond_xmit_policy_get(bonded_port_id: u16) -> ::std::os::raw::c_int;
+}
 extern "C" {
     pub fn rte_eth_rx_queue_info_get(
         port_id: u16,
@@ -26122,6 +26270,1936 @@ extern "C" {
         nb_pkts: u16,
     ) -> u16;
 }
+extern "C" {
+    #[doc = " Enqueue several objects on a ring. This function is safe for multiple"]
+    #[doc = " producers unless the ring is created with the single-producer flag."]
+    pub fn _rte_ring_enqueue_burst(
+        r: *mut rte_ring,
+        obj_table: *const *mut ::std::os::raw::c_void,
+        n: ::std::os::raw::c_uint,
+        free_space: *mut ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    #[doc = " Dequeue several objects from a ring. This function is safe for multiple"]
+    #[doc = " consumers unless the ring is created with the single-consumer flag."]
+    pub fn _rte_ring_dequeue_burst(
+        r: *mut rte_ring,
+        obj_table: *mut *mut ::std::os::raw::c_void,
+        n: ::std::os::raw::c_uint,
+        available: *mut ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    #[doc = " Get the number of cycles in one second for the default timer."]
+    pub fn _rte_get_timer_hz() -> u64;
+}
+extern "C" {
+    #[doc = " Return the number of TSC cycles since boot."]
+    pub fn _rte_get_tsc_cycles() -> u64;
+}
+extern "C" {
+    #[doc = " Return the lcore id of the calling thread, or LCORE_ID_ANY if the"]
+    #[doc = " thread is not an EAL thread."]
+    pub fn _rte_lcore_id() -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    #[doc = " Dump the statistics of all the malloc heaps to stdout."]
+    pub fn _rte_malloc_dump_stats(type_: *const ::std::os::raw::c_char);
+}
+extern "C" {
+    #[doc = " Lookup an IP into the LPM table."]
+    pub fn _rte_lpm_lookup(lpm: *mut rte_lpm, ip: u32, next_hop: *mut u32)
+        -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = " Calculate the Jenkins hash of a key. Can be used as the `hash_func`"]
+    #[doc = " of `rte_hash_parameters`."]
+    pub fn _rte_jhash(key: *const ::std::os::raw::c_void, length: u32, initval: u32) -> u32;
+}
+extern "C" {
+    #[doc = " Calculate the CRC32 hash of a key, using the SSE4.2 instructions if"]
+    #[doc = " available. Can be used as the `hash_func` of `rte_hash_parameters`."]
+    pub fn _rte_hash_crc(data: *const ::std::os::raw::c_void, data_len: u32, init_val: u32)
+        -> u32;
+}
+extern "C" {
+    #[doc = " Lookup multiple IP addresses in an LPM table."]
+    pub fn _rte_lpm_lookup_bulk(
+        lpm: *const rte_lpm,
+        ips: *const u32,
+        next_hops: *mut u32,
+        n: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = " Allocate a crypto operation from a mempool with default parameters set."]
+    pub fn _rte_crypto_op_alloc(
+        mempool: *mut rte_mempool,
+        type_: rte_crypto_op_type::Type,
+    ) -> *mut rte_crypto_op;
+}
+extern "C" {
+    #[doc = " Free a crypto operation back into its original mempool."]
+    pub fn _rte_crypto_op_free(op: *mut rte_crypto_op);
+}
+extern "C" {
+    #[doc = " Attach a symmetric session to a crypto operation."]
+    pub fn _rte_crypto_op_attach_sym_session(
+        op: *mut rte_crypto_op,
+        sess: *mut rte_cryptodev_sym_session,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = " Enqueue a burst of operations for processing on a crypto device."]
+    pub fn _rte_cryptodev_enqueue_burst(
+        dev_id: u8,
+        qp_id: u16,
+        ops: *mut *mut rte_crypto_op,
+        nb_ops: u16,
+    ) -> u16;
+}
+extern "C" {
+    #[doc = " Dequeue a burst of processed operations from a queue pair of a crypto"]
+    #[doc = " device."]
+    pub fn _rte_cryptodev_dequeue_burst(
+        dev_id: u8,
+        qp_id: u16,
+        ops: *mut *mut rte_crypto_op,
+        nb_ops: u16,
+    ) -> u16;
+}
+extern "C" {
+    #[doc = " Enqueue a burst of events on an event port of an event device."]
+    pub fn _rte_event_enqueue_burst(
+        dev_id: u8,
+        port_id: u8,
+        ev: *const rte_event,
+        nb_events: u16,
+    ) -> u16;
+}
+extern "C" {
+    #[doc = " Dequeue a burst of events from an event port of an event device,"]
+    #[doc = " waiting up to the timeout if no event is available."]
+    pub fn _rte_event_dequeue_burst(
+        dev_id: u8,
+        port_id: u8,
+        ev: *mut rte_event,
+        nb_events: u16,
+        timeout_ticks: u64,
+    ) -> u16;
+}
+extern "C" {
+    #[doc = " Color the packet with the single-rate three-color marker in color"]
+    #[doc = " blind mode, and update the token buckets."]
+    pub fn _rte_meter_srtcm_color_blind_check(
+        m: *mut rte_meter_srtcm,
+        p: *mut rte_meter_srtcm_profile,
+        time: u64,
+        pkt_len: u32,
+    ) -> rte_color::Type;
+}
+extern "C" {
+    #[doc = " Color the packet with the two-rate three-color marker in color blind"]
+    #[doc = " mode, and update the token buckets."]
+    pub fn _rte_meter_trtcm_color_blind_check(
+        m: *mut rte_meter_trtcm,
+        p: *mut rte_meter_trtcm_profile,
+        time: u64,
+        pkt_len: u32,
+    ) -> rte_color::Type;
+}
+extern "C" {
+    #[doc = " Chain an mbuf to another, thereby creating a segmented packet."]
+    pub fn _rte_pktmbuf_chain(head: *mut rte_mbuf, tail: *mut rte_mbuf) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = " Linearize the data of a segmented packet into the first segment."]
+    pub fn _rte_pktmbuf_linearize(mbuf: *mut rte_mbuf) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = " Remove len bytes of data at the end of the mbuf."]
+    pub fn _rte_pktmbuf_trim(m: *mut rte_mbuf, len: u16) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = " Reads the reference counter of an mbuf."]
+    pub fn _rte_mbuf_refcnt_read(m: *const rte_mbuf) -> u16;
+}
+extern "C" {
+    #[doc = " Adds a value to the reference counter of an mbuf."]
+    pub fn _rte_mbuf_refcnt_update(m: *mut rte_mbuf, value: i16) -> u16;
+}
+extern "C" {
+    #[doc = " Reads the address and the identifiers of a device on the PCI bus."]
+    pub fn _rte_pci_device_info(
+        dev: *const rte_device,
+        addr: *mut rte_pci_addr,
+        id: *mut rte_pci_id,
+    );
+}
+extern "C" {
+    #[doc = " Marks a registered reader thread as online."]
+    pub fn _rte_rcu_qsbr_thread_online(v: *mut rte_rcu_qsbr, thread_id: ::std::os::raw::c_uint);
+}
+extern "C" {
+    #[doc = " Marks a registered reader thread as offline."]
+    pub fn _rte_rcu_qsbr_thread_offline(v: *mut rte_rcu_qsbr, thread_id: ::std::os::raw::c_uint);
+}
+extern "C" {
+    #[doc = " Reports a quiescent state of a reader thread."]
+    pub fn _rte_rcu_qsbr_quiescent(v: *mut rte_rcu_qsbr, thread_id: ::std::os::raw::c_uint);
+}
+extern "C" {
+    #[doc = " Function returning version string."]
+    pub fn _rte_version() -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    #[doc = " Prepare crypto operations for a burst of packets of a lookaside IPsec"]
+    #[doc = " session."]
+    pub fn _rte_ipsec_pkt_crypto_prepare(
+        ss: *const rte_ipsec_session,
+        mb: *mut *mut rte_mbuf,
+        cop: *mut *mut rte_crypto_op,
+        num: u16,
+    ) -> u16;
+}
+extern "C" {
+    #[doc = " Finalize the processing of a burst of packets of an IPsec session."]
+    pub fn _rte_ipsec_pkt_process(
+        ss: *const rte_ipsec_session,
+        mb: *mut *mut rte_mbuf,
+        num: u16,
+    ) -> u16;
+}
+pub mod rte_timer_type {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const SINGLE: Type = 0;
+    pub const PERIODICAL: Type = 1;
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_timer_status {
+    pub __bindgen_anon_1: rte_timer_status__bindgen_ty_1,
+    pub u32_: u32,
+    _bindgen_union_align: u32,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_timer_status__bindgen_ty_1 {
+    pub state: u16,
+    pub owner: i16,
+}
+impl Default for rte_timer_status {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+impl ::std::fmt::Debug for rte_timer_status {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        write!(f, "rte_timer_status {{ union }}")
+    }
+}
+pub type rte_timer_cb_t = ::std::option::Option<
+    unsafe extern "C" fn(arg1: *mut rte_timer, arg2: *mut ::std::os::raw::c_void),
+>;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_timer {
+    pub expire: u64,
+    pub sl_next: [*mut rte_timer; 10usize],
+    pub status: rte_timer_status,
+    pub period: u64,
+    pub f: rte_timer_cb_t,
+    pub arg: *mut ::std::os::raw::c_void,
+}
+impl Default for rte_timer {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+extern "C" {
+    pub fn rte_timer_subsystem_init() -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_timer_subsystem_finalize();
+}
+extern "C" {
+    pub fn rte_timer_init(tim: *mut rte_timer);
+}
+extern "C" {
+    pub fn rte_timer_reset(
+        tim: *mut rte_timer,
+        ticks: u64,
+        type_: rte_timer_type::Type,
+        tim_lcore: ::std::os::raw::c_uint,
+        fct: rte_timer_cb_t,
+        arg: *mut ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_timer_stop(tim: *mut rte_timer) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_timer_stop_sync(tim: *mut rte_timer);
+}
+extern "C" {
+    pub fn rte_timer_pending(tim: *mut rte_timer) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_timer_manage() -> ::std::os::raw::c_int;
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_malloc_socket_stats {
+    pub heap_totalsz_bytes: size_t,
+    pub heap_freesz_bytes: size_t,
+    pub greatest_free_size: size_t,
+    pub free_count: ::std::os::raw::c_uint,
+    pub alloc_count: ::std::os::raw::c_uint,
+    pub heap_allocsz_bytes: size_t,
+}
+extern "C" {
+    pub fn rte_malloc_socket(
+        type_: *const ::std::os::raw::c_char,
+        size: size_t,
+        align: ::std::os::raw::c_uint,
+        socket: ::std::os::raw::c_int,
+    ) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn rte_zmalloc_socket(
+        type_: *const ::std::os::raw::c_char,
+        size: size_t,
+        align: ::std::os::raw::c_uint,
+        socket: ::std::os::raw::c_int,
+    ) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn rte_free(ptr: *mut ::std::os::raw::c_void);
+}
+pub const RTE_QSBR_THRID_INVALID: u32 = 4294967295;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_rcu_qsbr {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn rte_rcu_qsbr_get_memsize(max_threads: u32) -> size_t;
+}
+extern "C" {
+    pub fn rte_rcu_qsbr_init(v: *mut rte_rcu_qsbr, max_threads: u32) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_rcu_qsbr_thread_register(
+        v: *mut rte_rcu_qsbr,
+        thread_id: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_rcu_qsbr_thread_unregister(
+        v: *mut rte_rcu_qsbr,
+        thread_id: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_rcu_qsbr_synchronize(v: *mut rte_rcu_qsbr, thread_id: ::std::os::raw::c_uint);
+}
+extern "C" {
+    pub fn rte_malloc_get_socket_stats(
+        socket: ::std::os::raw::c_int,
+        socket_stats: *mut rte_malloc_socket_stats,
+    ) -> ::std::os::raw::c_int;
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_lpm_config {
+    pub max_rules: u32,
+    pub number_tbl8s: u32,
+    pub flags: ::std::os::raw::c_int,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_lpm {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn rte_lpm_create(
+        name: *const ::std::os::raw::c_char,
+        socket_id: ::std::os::raw::c_int,
+        config: *const rte_lpm_config,
+    ) -> *mut rte_lpm;
+}
+extern "C" {
+    pub fn rte_lpm_free(lpm: *mut rte_lpm);
+}
+extern "C" {
+    pub fn rte_lpm_add(lpm: *mut rte_lpm, ip: u32, depth: u8, next_hop: u32)
+        -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_lpm_delete(lpm: *mut rte_lpm, ip: u32, depth: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_lpm_delete_all(lpm: *mut rte_lpm);
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_lpm6_config {
+    pub max_rules: u32,
+    pub number_tbl8s: u32,
+    pub flags: ::std::os::raw::c_int,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_lpm6 {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn rte_lpm6_create(
+        name: *const ::std::os::raw::c_char,
+        socket_id: ::std::os::raw::c_int,
+        config: *const rte_lpm6_config,
+    ) -> *mut rte_lpm6;
+}
+extern "C" {
+    pub fn rte_lpm6_free(lpm: *mut rte_lpm6);
+}
+extern "C" {
+    pub fn rte_lpm6_add(
+        lpm: *mut rte_lpm6,
+        ip: *mut u8,
+        depth: u8,
+        next_hop: u32,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_lpm6_delete(lpm: *mut rte_lpm6, ip: *mut u8, depth: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_lpm6_delete_all(lpm: *mut rte_lpm6);
+}
+extern "C" {
+    pub fn rte_lpm6_lookup(
+        lpm: *const rte_lpm6,
+        ip: *mut u8,
+        next_hop: *mut u32,
+    ) -> ::std::os::raw::c_int;
+}
+pub type rte_hash_function = ::std::option::Option<
+    unsafe extern "C" fn(key: *const ::std::os::raw::c_void, key_len: u32, init_val: u32) -> u32,
+>;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_hash_parameters {
+    pub name: *const ::std::os::raw::c_char,
+    pub entries: u32,
+    pub reserved: u32,
+    pub key_len: u32,
+    pub hash_func: rte_hash_function,
+    pub hash_func_init_val: u32,
+    pub socket_id: ::std::os::raw::c_int,
+    pub extra_flag: u8,
+}
+impl Default for rte_hash_parameters {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_hash {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn rte_hash_create(params: *const rte_hash_parameters) -> *mut rte_hash;
+}
+extern "C" {
+    pub fn rte_hash_free(h: *mut rte_hash);
+}
+extern "C" {
+    pub fn rte_hash_reset(h: *mut rte_hash);
+}
+extern "C" {
+    pub fn rte_hash_count(h: *const rte_hash) -> i32;
+}
+extern "C" {
+    pub fn rte_hash_add_key_data(
+        h: *const rte_hash,
+        key: *const ::std::os::raw::c_void,
+        data: *mut ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_hash_lookup_data(
+        h: *const rte_hash,
+        key: *const ::std::os::raw::c_void,
+        data: *mut *mut ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_hash_del_key(h: *const rte_hash, key: *const ::std::os::raw::c_void) -> i32;
+}
+extern "C" {
+    pub fn rte_hash_iterate(
+        h: *const rte_hash,
+        key: *mut *const ::std::os::raw::c_void,
+        data: *mut *mut ::std::os::raw::c_void,
+        next: *mut u32,
+    ) -> i32;
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_acl_field_types {
+    pub u8_: u8,
+    pub u16_: u16,
+    pub u32_: u32,
+    pub u64_: u64,
+    _bindgen_union_align: u64,
+}
+impl Default for rte_acl_field_types {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_acl_field {
+    pub value: rte_acl_field_types,
+    pub mask_range: rte_acl_field_types,
+}
+impl Default for rte_acl_field {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_acl_field_def {
+    pub type_: u8,
+    pub size: u8,
+    pub field_index: u8,
+    pub input_index: u8,
+    pub offset: u32,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_acl_config {
+    pub num_categories: u32,
+    pub num_fields: u32,
+    pub defs: [rte_acl_field_def; 64usize],
+    pub max_size: size_t,
+}
+impl Default for rte_acl_config {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_acl_rule_data {
+    pub category_mask: u32,
+    pub priority: i32,
+    pub userdata: u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_acl_param {
+    pub name: *const ::std::os::raw::c_char,
+    pub socket_id: ::std::os::raw::c_int,
+    pub rule_size: u32,
+    pub max_rule_num: u32,
+}
+impl Default for rte_acl_param {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_acl_ctx {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_acl_rule {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn rte_acl_create(param: *const rte_acl_param) -> *mut rte_acl_ctx;
+}
+extern "C" {
+    pub fn rte_acl_free(ctx: *mut rte_acl_ctx);
+}
+extern "C" {
+    pub fn rte_acl_add_rules(
+        ctx: *mut rte_acl_ctx,
+        rules: *const rte_acl_rule,
+        num: u32,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_acl_build(
+        ctx: *mut rte_acl_ctx,
+        cfg: *const rte_acl_config,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_acl_classify(
+        ctx: *const rte_acl_ctx,
+        data: *mut *const u8,
+        results: *mut u32,
+        num: u32,
+        categories: u32,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_acl_dump(ctx: *const rte_acl_ctx);
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_gso_ctx {
+    pub direct_pool: *mut rte_mempool,
+    pub indirect_pool: *mut rte_mempool,
+    pub flag: u64,
+    pub gso_types: u32,
+    pub gso_size: u16,
+}
+impl Default for rte_gso_ctx {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+extern "C" {
+    pub fn rte_gso_segment(
+        pkt: *mut rte_mbuf,
+        ctx: *const rte_gso_ctx,
+        pkts_out: *mut *mut rte_mbuf,
+        nb_pkts_out: u16,
+    ) -> ::std::os::raw::c_int;
+}
+pub mod rte_crypto_op_type {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_CRYPTO_OP_TYPE_UNDEFINED: Type = 0;
+    pub const RTE_CRYPTO_OP_TYPE_SYMMETRIC: Type = 1;
+    pub const RTE_CRYPTO_OP_TYPE_ASYMMETRIC: Type = 2;
+}
+pub mod rte_crypto_op_status {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_CRYPTO_OP_STATUS_SUCCESS: Type = 0;
+    pub const RTE_CRYPTO_OP_STATUS_NOT_PROCESSED: Type = 1;
+    pub const RTE_CRYPTO_OP_STATUS_AUTH_FAILED: Type = 2;
+    pub const RTE_CRYPTO_OP_STATUS_INVALID_SESSION: Type = 3;
+    pub const RTE_CRYPTO_OP_STATUS_INVALID_ARGS: Type = 4;
+    pub const RTE_CRYPTO_OP_STATUS_ERROR: Type = 5;
+}
+pub mod rte_crypto_sym_xform_type {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_CRYPTO_SYM_XFORM_NOT_SPECIFIED: Type = 0;
+    pub const RTE_CRYPTO_SYM_XFORM_AUTH: Type = 1;
+    pub const RTE_CRYPTO_SYM_XFORM_CIPHER: Type = 2;
+    pub const RTE_CRYPTO_SYM_XFORM_AEAD: Type = 3;
+}
+pub mod rte_crypto_aead_algorithm {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_CRYPTO_AEAD_AES_CCM: Type = 1;
+    pub const RTE_CRYPTO_AEAD_AES_GCM: Type = 2;
+    pub const RTE_CRYPTO_AEAD_LIST_END: Type = 3;
+}
+pub mod rte_crypto_aead_operation {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_CRYPTO_AEAD_OP_ENCRYPT: Type = 0;
+    pub const RTE_CRYPTO_AEAD_OP_DECRYPT: Type = 1;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_crypto_param_range {
+    pub min: u16,
+    pub max: u16,
+    pub increment: u16,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_crypto_aead_xform__bindgen_ty_1 {
+    pub data: *const u8,
+    pub length: u16,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_crypto_aead_xform__bindgen_ty_2 {
+    pub offset: u16,
+    pub length: u16,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_crypto_aead_xform {
+    pub op: rte_crypto_aead_operation::Type,
+    pub algo: rte_crypto_aead_algorithm::Type,
+    pub key: rte_crypto_aead_xform__bindgen_ty_1,
+    pub iv: rte_crypto_aead_xform__bindgen_ty_2,
+    pub digest_length: u16,
+    pub aad_length: u16,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_crypto_sym_xform__bindgen_ty_1 {
+    pub aead: rte_crypto_aead_xform,
+    _bindgen_union_align: [u64; 4usize],
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_crypto_sym_xform {
+    pub next: *mut rte_crypto_sym_xform,
+    pub type_: rte_crypto_sym_xform_type::Type,
+    pub __bindgen_anon_1: rte_crypto_sym_xform__bindgen_ty_1,
+}
+impl Default for rte_crypto_sym_xform {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_crypto_op__bindgen_ty_1__bindgen_ty_1 {
+    pub type_: u8,
+    pub status: u8,
+    pub sess_type: u8,
+    pub reserved: [u8; 3usize],
+    pub private_data_offset: u16,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_crypto_op__bindgen_ty_1 {
+    pub raw: u64,
+    pub __bindgen_anon_1: rte_crypto_op__bindgen_ty_1__bindgen_ty_1,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_crypto_op {
+    pub __bindgen_anon_1: rte_crypto_op__bindgen_ty_1,
+    pub mempool: *mut rte_mempool,
+    pub phys_addr: rte_iova_t,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_crypto_sym_op_data {
+    pub offset: u32,
+    pub length: u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_crypto_sym_op_buf {
+    pub data: *mut u8,
+    pub phys_addr: rte_iova_t,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_crypto_sym_op__bindgen_ty_2__bindgen_ty_1 {
+    pub data: rte_crypto_sym_op_data,
+    pub digest: rte_crypto_sym_op_buf,
+    pub aad: rte_crypto_sym_op_buf,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_crypto_sym_op__bindgen_ty_2 {
+    pub aead: rte_crypto_sym_op__bindgen_ty_2__bindgen_ty_1,
+    _bindgen_union_align: [u64; 5usize],
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_crypto_sym_op__bindgen_ty_1 {
+    pub session: *mut rte_cryptodev_sym_session,
+    pub sec_session: *mut ::std::os::raw::c_void,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_crypto_sym_op {
+    pub m_src: *mut rte_mbuf,
+    pub m_dst: *mut rte_mbuf,
+    pub __bindgen_anon_1: rte_crypto_sym_op__bindgen_ty_1,
+    pub __bindgen_anon_2: rte_crypto_sym_op__bindgen_ty_2,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_cryptodev_sym_session {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_cryptodev_sym_algo_capability {
+    pub algo: ::std::os::raw::c_uint,
+    pub block_size: u16,
+    pub key_size: rte_crypto_param_range,
+    pub digest_size: rte_crypto_param_range,
+    pub aad_size: rte_crypto_param_range,
+    pub iv_size: rte_crypto_param_range,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_cryptodev_symmetric_capability__bindgen_ty_1 {
+    pub auth: rte_cryptodev_sym_algo_capability,
+    pub cipher: rte_cryptodev_sym_algo_capability,
+    pub aead: rte_cryptodev_sym_algo_capability,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_cryptodev_symmetric_capability {
+    pub xform_type: rte_crypto_sym_xform_type::Type,
+    pub __bindgen_anon_1: rte_cryptodev_symmetric_capability__bindgen_ty_1,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_cryptodev_capabilities__bindgen_ty_1 {
+    pub sym: rte_cryptodev_symmetric_capability,
+    _bindgen_union_align: [u32; 11usize],
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_cryptodev_capabilities {
+    pub op: rte_crypto_op_type::Type,
+    pub __bindgen_anon_1: rte_cryptodev_capabilities__bindgen_ty_1,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_cryptodev_info__bindgen_ty_1 {
+    pub max_nb_sessions: ::std::os::raw::c_uint,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_cryptodev_info {
+    pub driver_name: *const ::std::os::raw::c_char,
+    pub driver_id: u8,
+    pub device: *mut rte_device,
+    pub feature_flags: u64,
+    pub capabilities: *const rte_cryptodev_capabilities,
+    pub max_nb_queue_pairs: ::std::os::raw::c_uint,
+    pub min_mbuf_headroom_req: u16,
+    pub min_mbuf_tailroom_req: u16,
+    pub sym: rte_cryptodev_info__bindgen_ty_1,
+}
+impl Default for rte_cryptodev_info {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_cryptodev_config {
+    pub socket_id: ::std::os::raw::c_int,
+    pub nb_queue_pairs: u16,
+    pub ff_disable: u64,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_cryptodev_qp_conf {
+    pub nb_descriptors: u32,
+    pub mp_session: *mut rte_mempool,
+    pub mp_session_private: *mut rte_mempool,
+}
+impl Default for rte_cryptodev_qp_conf {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+extern "C" {
+    pub fn rte_cryptodev_count() -> u8;
+}
+extern "C" {
+    pub fn rte_cryptodev_socket_id(dev_id: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_cryptodev_info_get(dev_id: u8, dev_info: *mut rte_cryptodev_info);
+}
+extern "C" {
+    pub fn rte_cryptodev_configure(
+        dev_id: u8,
+        config: *mut rte_cryptodev_config,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_cryptodev_queue_pair_setup(
+        dev_id: u8,
+        queue_pair_id: u16,
+        qp_conf: *const rte_cryptodev_qp_conf,
+        socket_id: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_cryptodev_start(dev_id: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_cryptodev_stop(dev_id: u8);
+}
+extern "C" {
+    pub fn rte_cryptodev_close(dev_id: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_cryptodev_sym_session_pool_create(
+        name: *const ::std::os::raw::c_char,
+        nb_elts: u32,
+        elt_size: u32,
+        cache_size: u32,
+        priv_size: u16,
+        socket_id: ::std::os::raw::c_int,
+    ) -> *mut rte_mempool;
+}
+extern "C" {
+    pub fn rte_cryptodev_sym_session_create(
+        mempool: *mut rte_mempool,
+    ) -> *mut rte_cryptodev_sym_session;
+}
+extern "C" {
+    pub fn rte_cryptodev_sym_session_init(
+        dev_id: u8,
+        sess: *mut rte_cryptodev_sym_session,
+        xforms: *mut rte_crypto_sym_xform,
+        mempool: *mut rte_mempool,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_cryptodev_sym_session_clear(
+        dev_id: u8,
+        sess: *mut rte_cryptodev_sym_session,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_cryptodev_sym_session_free(
+        sess: *mut rte_cryptodev_sym_session,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_cryptodev_sym_get_private_session_size(dev_id: u8) -> ::std::os::raw::c_uint;
+}
+extern "C" {
+    pub fn rte_crypto_op_pool_create(
+        name: *const ::std::os::raw::c_char,
+        type_: rte_crypto_op_type::Type,
+        nb_elts: ::std::os::raw::c_uint,
+        cache_size: ::std::os::raw::c_uint,
+        priv_size: u16,
+        socket_id: ::std::os::raw::c_int,
+    ) -> *mut rte_mempool;
+}
+extern "C" {
+    pub static mut rte_crypto_cipher_algorithm_strings: [*const ::std::os::raw::c_char; 0usize];
+}
+extern "C" {
+    pub static mut rte_crypto_auth_algorithm_strings: [*const ::std::os::raw::c_char; 0usize];
+}
+extern "C" {
+    pub static mut rte_crypto_aead_algorithm_strings: [*const ::std::os::raw::c_char; 0usize];
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_event__bindgen_ty_1__bindgen_ty_1 {
+    pub _bitfield_1: [u8; 5usize],
+    pub queue_id: u8,
+    pub priority: u8,
+    pub impl_opaque: u8,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_event__bindgen_ty_1 {
+    pub event: u64,
+    pub __bindgen_anon_1: rte_event__bindgen_ty_1__bindgen_ty_1,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_event__bindgen_ty_2 {
+    pub u64_: u64,
+    pub event_ptr: *mut ::std::os::raw::c_void,
+    pub mbuf: *mut rte_mbuf,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_event {
+    pub __bindgen_anon_1: rte_event__bindgen_ty_1,
+    pub __bindgen_anon_2: rte_event__bindgen_ty_2,
+}
+impl Default for rte_event {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_event_dev_info {
+    pub driver_name: *const ::std::os::raw::c_char,
+    pub dev: *mut rte_device,
+    pub min_dequeue_timeout_ns: u32,
+    pub max_dequeue_timeout_ns: u32,
+    pub dequeue_timeout_ns: u32,
+    pub max_event_queues: u8,
+    pub max_event_queue_flows: u32,
+    pub max_event_queue_priority_levels: u8,
+    pub max_event_priority_levels: u8,
+    pub max_event_ports: u8,
+    pub max_event_port_dequeue_depth: u8,
+    pub max_event_port_enqueue_depth: u32,
+    pub max_num_events: i32,
+    pub event_dev_cap: u32,
+}
+impl Default for rte_event_dev_info {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_event_dev_config {
+    pub dequeue_timeout_ns: u32,
+    pub nb_events_limit: i32,
+    pub nb_event_queues: u8,
+    pub nb_event_ports: u8,
+    pub nb_event_queue_flows: u32,
+    pub nb_event_port_dequeue_depth: u32,
+    pub nb_event_port_enqueue_depth: u32,
+    pub event_dev_cfg: u32,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_event_queue_conf {
+    pub nb_atomic_flows: u32,
+    pub nb_atomic_order_sequences: u32,
+    pub event_queue_cfg: u32,
+    pub schedule_type: u8,
+    pub priority: u8,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_event_port_conf {
+    pub new_event_threshold: i32,
+    pub dequeue_depth: u16,
+    pub enqueue_depth: u16,
+    pub disable_implicit_release: u8,
+}
+extern "C" {
+    pub fn rte_event_dev_count() -> u8;
+}
+extern "C" {
+    pub fn rte_event_dev_socket_id(dev_id: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_event_dev_info_get(
+        dev_id: u8,
+        dev_info: *mut rte_event_dev_info,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_event_dev_configure(
+        dev_id: u8,
+        dev_conf: *const rte_event_dev_config,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_event_queue_default_conf_get(
+        dev_id: u8,
+        queue_id: u8,
+        queue_conf: *mut rte_event_queue_conf,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_event_queue_setup(
+        dev_id: u8,
+        queue_id: u8,
+        queue_conf: *const rte_event_queue_conf,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_event_port_default_conf_get(
+        dev_id: u8,
+        port_id: u8,
+        port_conf: *mut rte_event_port_conf,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_event_port_setup(
+        dev_id: u8,
+        port_id: u8,
+        port_conf: *const rte_event_port_conf,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_event_port_link(
+        dev_id: u8,
+        port_id: u8,
+        queues: *const u8,
+        priorities: *const u8,
+        nb_links: u16,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_event_port_unlink(
+        dev_id: u8,
+        port_id: u8,
+        queues: *mut u8,
+        nb_unlinks: u16,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_event_dev_start(dev_id: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_event_dev_stop(dev_id: u8);
+}
+extern "C" {
+    pub fn rte_event_dev_close(dev_id: u8) -> ::std::os::raw::c_int;
+}
+pub mod rte_color {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_COLOR_GREEN: Type = 0;
+    pub const RTE_COLOR_YELLOW: Type = 1;
+    pub const RTE_COLOR_RED: Type = 2;
+    pub const RTE_COLORS: Type = 3;
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_meter_srtcm_params {
+    pub cir: u64,
+    pub cbs: u64,
+    pub ebs: u64,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_meter_srtcm_profile {
+    pub cbs: u64,
+    pub ebs: u64,
+    pub cir_period: u64,
+    pub cir_bytes_per_period: u64,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_meter_srtcm {
+    pub time: u64,
+    pub tc: u64,
+    pub te: u64,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_meter_trtcm_params {
+    pub cir: u64,
+    pub pir: u64,
+    pub cbs: u64,
+    pub pbs: u64,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_meter_trtcm_profile {
+    pub cbs: u64,
+    pub pbs: u64,
+    pub cir_period: u64,
+    pub cir_bytes_per_period: u64,
+    pub pir_period: u64,
+    pub pir_bytes_per_period: u64,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_meter_trtcm {
+    pub time_tc: u64,
+    pub time_tp: u64,
+    pub tc: u64,
+    pub tp: u64,
+}
+extern "C" {
+    pub fn rte_meter_srtcm_profile_config(
+        p: *mut rte_meter_srtcm_profile,
+        params: *mut rte_meter_srtcm_params,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_meter_srtcm_config(
+        m: *mut rte_meter_srtcm,
+        p: *mut rte_meter_srtcm_profile,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_meter_trtcm_profile_config(
+        p: *mut rte_meter_trtcm_profile,
+        params: *mut rte_meter_trtcm_params,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_meter_trtcm_config(
+        m: *mut rte_meter_trtcm,
+        p: *mut rte_meter_trtcm_profile,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_pdump_init() -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_pdump_uninit() -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_pdump_enable(
+        port: u16,
+        queue: u16,
+        flags: u32,
+        ring: *mut rte_ring,
+        mp: *mut rte_mempool,
+        filter: *mut ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_pdump_disable(port: u16, queue: u16, flags: u32) -> ::std::os::raw::c_int;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_ip_frag_tbl {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_ip_frag_death_row {
+    pub cnt: u32,
+    pub row: [*mut rte_mbuf; 160usize],
+}
+impl Default for rte_ip_frag_death_row {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+extern "C" {
+    pub fn rte_ip_frag_table_create(
+        bucket_num: u32,
+        bucket_entries: u32,
+        max_entries: u32,
+        max_cycles: u64,
+        socket_id: ::std::os::raw::c_int,
+    ) -> *mut rte_ip_frag_tbl;
+}
+extern "C" {
+    pub fn rte_ip_frag_table_destroy(tbl: *mut rte_ip_frag_tbl);
+}
+extern "C" {
+    pub fn rte_ipv4_fragment_packet(
+        pkt_in: *mut rte_mbuf,
+        pkts_out: *mut *mut rte_mbuf,
+        nb_pkts_out: u16,
+        mtu_size: u16,
+        pool_direct: *mut rte_mempool,
+        pool_indirect: *mut rte_mempool,
+    ) -> i32;
+}
+extern "C" {
+    pub fn rte_ipv4_frag_reassemble_packet(
+        tbl: *mut rte_ip_frag_tbl,
+        dr: *mut rte_ip_frag_death_row,
+        mb: *mut rte_mbuf,
+        tms: u64,
+        ip_hdr: *mut rte_ipv4_hdr,
+    ) -> *mut rte_mbuf;
+}
+#[repr(C, packed)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct ipv6_extension_fragment {
+    pub next_header: u8,
+    pub reserved: u8,
+    pub frag_data: u16,
+    pub id: u32,
+}
+extern "C" {
+    pub fn rte_ipv6_fragment_packet(
+        pkt_in: *mut rte_mbuf,
+        pkts_out: *mut *mut rte_mbuf,
+        nb_pkts_out: u16,
+        mtu_size: u16,
+        pool_direct: *mut rte_mempool,
+        pool_indirect: *mut rte_mempool,
+    ) -> i32;
+}
+extern "C" {
+    pub fn rte_ipv6_frag_reassemble_packet(
+        tbl: *mut rte_ip_frag_tbl,
+        dr: *mut rte_ip_frag_death_row,
+        mb: *mut rte_mbuf,
+        tms: u64,
+        ip_hdr: *mut rte_ipv6_hdr,
+        frag_hdr: *mut ipv6_extension_fragment,
+    ) -> *mut rte_mbuf;
+}
+extern "C" {
+    pub fn rte_ip_frag_free_death_row(dr: *mut rte_ip_frag_death_row, prefetch: u32);
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct rte_gro_param {
+    pub gro_types: u64,
+    pub max_flow_num: u16,
+    pub max_item_per_flow: u16,
+    pub socket_id: u16,
+}
+extern "C" {
+    pub fn rte_gro_ctx_create(param: *const rte_gro_param) -> *mut ::std::os::raw::c_void;
+}
+extern "C" {
+    pub fn rte_gro_ctx_destroy(ctx: *mut ::std::os::raw::c_void);
+}
+extern "C" {
+    pub fn rte_gro_reassemble_burst(
+        pkts: *mut *mut rte_mbuf,
+        nb_pkts: u16,
+        param: *const rte_gro_param,
+    ) -> u16;
+}
+extern "C" {
+    pub fn rte_gro_reassemble(
+        pkts: *mut *mut rte_mbuf,
+        nb_pkts: u16,
+        ctx: *mut ::std::os::raw::c_void,
+    ) -> u16;
+}
+extern "C" {
+    pub fn rte_gro_timeout_flush(
+        ctx: *mut ::std::os::raw::c_void,
+        timeout_cycles: u64,
+        gro_types: u64,
+        out: *mut *mut rte_mbuf,
+        max_nb_out: u16,
+    ) -> u16;
+}
+extern "C" {
+    pub fn rte_gro_get_pkt_count(ctx: *mut ::std::os::raw::c_void) -> u64;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct vhost_device_ops {
+    pub new_device:
+        ::std::option::Option<unsafe extern "C" fn(vid: ::std::os::raw::c_int) -> ::std::os::raw::c_int>,
+    pub destroy_device: ::std::option::Option<unsafe extern "C" fn(vid: ::std::os::raw::c_int)>,
+    pub vring_state_changed: ::std::option::Option<
+        unsafe extern "C" fn(
+            vid: ::std::os::raw::c_int,
+            queue_id: u16,
+            enable: ::std::os::raw::c_int,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub features_changed: ::std::option::Option<
+        unsafe extern "C" fn(vid: ::std::os::raw::c_int, features: u64) -> ::std::os::raw::c_int,
+    >,
+    pub new_connection:
+        ::std::option::Option<unsafe extern "C" fn(vid: ::std::os::raw::c_int) -> ::std::os::raw::c_int>,
+    pub destroy_connection: ::std::option::Option<unsafe extern "C" fn(vid: ::std::os::raw::c_int)>,
+    pub reserved: [*mut ::std::os::raw::c_void; 2usize],
+}
+impl Default for vhost_device_ops {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+extern "C" {
+    pub fn rte_vhost_driver_register(
+        path: *const ::std::os::raw::c_char,
+        flags: u64,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_vhost_driver_unregister(path: *const ::std::os::raw::c_char) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_vhost_driver_callback_register(
+        path: *const ::std::os::raw::c_char,
+        ops: *const vhost_device_ops,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_vhost_driver_start(path: *const ::std::os::raw::c_char) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_vhost_get_ifname(
+        vid: ::std::os::raw::c_int,
+        buf: *mut ::std::os::raw::c_char,
+        len: size_t,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_vhost_get_vring_num(vid: ::std::os::raw::c_int) -> u16;
+}
+extern "C" {
+    pub fn rte_vhost_enqueue_burst(
+        vid: ::std::os::raw::c_int,
+        queue_id: u16,
+        pkts: *mut *mut rte_mbuf,
+        count: u16,
+    ) -> u16;
+}
+extern "C" {
+    pub fn rte_vhost_dequeue_burst(
+        vid: ::std::os::raw::c_int,
+        queue_id: u16,
+        mbuf_pool: *mut rte_mempool,
+        pkts: *mut *mut rte_mbuf,
+        count: u16,
+    ) -> u16;
+}
+pub mod rte_comp_algorithm {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_COMP_ALGO_UNSPECIFIED: Type = 0;
+    pub const RTE_COMP_ALGO_NULL: Type = 1;
+    pub const RTE_COMP_ALGO_DEFLATE: Type = 2;
+    pub const RTE_COMP_ALGO_LZS: Type = 3;
+    pub const RTE_COMP_ALGO_LIST_END: Type = 4;
+}
+pub mod rte_comp_hash_algorithm {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_COMP_HASH_ALGO_NONE: Type = 0;
+    pub const RTE_COMP_HASH_ALGO_SHA1: Type = 1;
+    pub const RTE_COMP_HASH_ALGO_SHA2_256: Type = 2;
+    pub const RTE_COMP_HASH_ALGO_LIST_END: Type = 3;
+}
+pub mod rte_comp_checksum_type {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_COMP_CHECKSUM_NONE: Type = 0;
+    pub const RTE_COMP_CHECKSUM_CRC32: Type = 1;
+    pub const RTE_COMP_CHECKSUM_ADLER32: Type = 2;
+    pub const RTE_COMP_CHECKSUM_CRC32_ADLER32: Type = 3;
+}
+pub mod rte_comp_huffman {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_COMP_HUFFMAN_DEFAULT: Type = 0;
+    pub const RTE_COMP_HUFFMAN_FIXED: Type = 1;
+    pub const RTE_COMP_HUFFMAN_DYNAMIC: Type = 2;
+}
+pub mod rte_comp_flush_flag {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_COMP_FLUSH_NONE: Type = 0;
+    pub const RTE_COMP_FLUSH_SYNC: Type = 1;
+    pub const RTE_COMP_FLUSH_FULL: Type = 2;
+    pub const RTE_COMP_FLUSH_FINAL: Type = 3;
+}
+pub mod rte_comp_xform_type {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_COMP_COMPRESS: Type = 0;
+    pub const RTE_COMP_DECOMPRESS: Type = 1;
+}
+pub mod rte_comp_op_type {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_COMP_OP_STATELESS: Type = 0;
+    pub const RTE_COMP_OP_STATEFUL: Type = 1;
+}
+pub mod rte_comp_op_status {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_COMP_OP_STATUS_SUCCESS: Type = 0;
+    pub const RTE_COMP_OP_STATUS_NOT_PROCESSED: Type = 1;
+    pub const RTE_COMP_OP_STATUS_INVALID_ARGS: Type = 2;
+    pub const RTE_COMP_OP_STATUS_ERROR: Type = 3;
+    pub const RTE_COMP_OP_STATUS_INVALID_STATE: Type = 4;
+    pub const RTE_COMP_OP_STATUS_OUT_OF_SPACE_TERMINATED: Type = 5;
+    pub const RTE_COMP_OP_STATUS_OUT_OF_SPACE_RECOVERABLE: Type = 6;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_comp_deflate_params {
+    pub huffman: rte_comp_huffman::Type,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_comp_compress_xform__bindgen_ty_1 {
+    pub deflate: rte_comp_deflate_params,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_comp_compress_xform {
+    pub algo: rte_comp_algorithm::Type,
+    pub __bindgen_anon_1: rte_comp_compress_xform__bindgen_ty_1,
+    pub level: ::std::os::raw::c_int,
+    pub window_size: u8,
+    pub chksum: rte_comp_checksum_type::Type,
+    pub hash_algo: rte_comp_hash_algorithm::Type,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_comp_decompress_xform {
+    pub algo: rte_comp_algorithm::Type,
+    pub chksum: rte_comp_checksum_type::Type,
+    pub window_size: u8,
+    pub hash_algo: rte_comp_hash_algorithm::Type,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_comp_xform__bindgen_ty_1 {
+    pub compress: rte_comp_compress_xform,
+    pub decompress: rte_comp_decompress_xform,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_comp_xform {
+    pub type_: rte_comp_xform_type::Type,
+    pub __bindgen_anon_1: rte_comp_xform__bindgen_ty_1,
+}
+impl Default for rte_comp_xform {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_compressdev_config {
+    pub socket_id: ::std::os::raw::c_int,
+    pub nb_queue_pairs: u16,
+    pub max_nb_priv_xforms: u16,
+    pub max_nb_streams: u16,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_comp_op__bindgen_ty_1 {
+    pub private_xform: *mut ::std::os::raw::c_void,
+    pub stream: *mut ::std::os::raw::c_void,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_comp_op__bindgen_ty_2 {
+    pub offset: u32,
+    pub length: u32,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_comp_op__bindgen_ty_3 {
+    pub offset: u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_comp_op__bindgen_ty_4 {
+    pub digest: *mut u8,
+    pub iova_addr: rte_iova_t,
+}
+#[repr(C)]
+#[repr(align(64))]
+#[derive(Copy, Clone)]
+pub struct rte_comp_op {
+    pub op_type: rte_comp_op_type::Type,
+    pub __bindgen_anon_1: rte_comp_op__bindgen_ty_1,
+    pub mempool: *mut rte_mempool,
+    pub iova_addr: rte_iova_t,
+    pub m_src: *mut rte_mbuf,
+    pub m_dst: *mut rte_mbuf,
+    pub src: rte_comp_op__bindgen_ty_2,
+    pub dst: rte_comp_op__bindgen_ty_3,
+    pub hash: rte_comp_op__bindgen_ty_4,
+    pub flush_flag: rte_comp_flush_flag::Type,
+    pub input_chksum: u64,
+    pub output_chksum: u64,
+    pub consumed: u32,
+    pub produced: u32,
+    pub debug_status: u64,
+    pub status: u8,
+}
+extern "C" {
+    pub fn rte_comp_op_pool_create(
+        name: *const ::std::os::raw::c_char,
+        nb_elts: ::std::os::raw::c_uint,
+        cache_size: ::std::os::raw::c_uint,
+        user_size: u16,
+        socket_id: ::std::os::raw::c_int,
+    ) -> *mut rte_mempool;
+}
+extern "C" {
+    pub fn rte_comp_op_alloc(mempool: *mut rte_mempool) -> *mut rte_comp_op;
+}
+extern "C" {
+    pub fn rte_comp_op_free(op: *mut rte_comp_op);
+}
+extern "C" {
+    pub fn rte_compressdev_count() -> u8;
+}
+extern "C" {
+    pub fn rte_compressdev_socket_id(dev_id: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_compressdev_configure(
+        dev_id: u8,
+        config: *mut rte_compressdev_config,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_compressdev_start(dev_id: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_compressdev_stop(dev_id: u8);
+}
+extern "C" {
+    pub fn rte_compressdev_close(dev_id: u8) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_compressdev_queue_pair_setup(
+        dev_id: u8,
+        queue_pair_id: u16,
+        max_inflight_ops: u32,
+        socket_id: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_compressdev_dequeue_burst(
+        dev_id: u8,
+        qp_id: u16,
+        ops: *mut *mut rte_comp_op,
+        nb_ops: u16,
+    ) -> u16;
+}
+extern "C" {
+    pub fn rte_compressdev_enqueue_burst(
+        dev_id: u8,
+        qp_id: u16,
+        ops: *mut *mut rte_comp_op,
+        nb_ops: u16,
+    ) -> u16;
+}
+extern "C" {
+    pub fn rte_compressdev_private_xform_create(
+        dev_id: u8,
+        xform: *const rte_comp_xform,
+        private_xform: *mut *mut ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_compressdev_private_xform_free(
+        dev_id: u8,
+        private_xform: *mut ::std::os::raw::c_void,
+    ) -> ::std::os::raw::c_int;
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct in_addr {
+    pub s_addr: u32,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union in6_addr__bindgen_ty_1 {
+    pub __u6_addr8: [u8; 16usize],
+    pub __u6_addr16: [u16; 8usize],
+    pub __u6_addr32: [u32; 4usize],
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct in6_addr {
+    pub __in6_u: in6_addr__bindgen_ty_1,
+}
+pub mod rte_security_session_action_type {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_SECURITY_ACTION_TYPE_NONE: Type = 0;
+    pub const RTE_SECURITY_ACTION_TYPE_INLINE_CRYPTO: Type = 1;
+    pub const RTE_SECURITY_ACTION_TYPE_INLINE_PROTOCOL: Type = 2;
+    pub const RTE_SECURITY_ACTION_TYPE_LOOKASIDE_PROTOCOL: Type = 3;
+}
+pub mod rte_security_ipsec_sa_direction {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_SECURITY_IPSEC_SA_DIR_EGRESS: Type = 0;
+    pub const RTE_SECURITY_IPSEC_SA_DIR_INGRESS: Type = 1;
+}
+pub mod rte_security_ipsec_sa_protocol {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_SECURITY_IPSEC_SA_PROTO_AH: Type = 1;
+    pub const RTE_SECURITY_IPSEC_SA_PROTO_ESP: Type = 2;
+}
+pub mod rte_security_ipsec_sa_mode {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_SECURITY_IPSEC_SA_MODE_TRANSPORT: Type = 1;
+    pub const RTE_SECURITY_IPSEC_SA_MODE_TUNNEL: Type = 2;
+}
+pub mod rte_security_ipsec_tunnel_type {
+    pub type Type = ::std::os::raw::c_uint;
+    pub const RTE_SECURITY_IPSEC_TUNNEL_IPV4: Type = 1;
+    pub const RTE_SECURITY_IPSEC_TUNNEL_IPV6: Type = 2;
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_security_ipsec_sa_options {
+    pub _bitfield_align_1: [u32; 0],
+    pub _bitfield_1: __BindgenBitfieldUnit<[u8; 4usize]>,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_security_ipv4_tunnel_param {
+    pub src_ip: in_addr,
+    pub dst_ip: in_addr,
+    pub dscp: u8,
+    pub df: u8,
+    pub ttl: u8,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_security_ipv6_tunnel_param {
+    pub src_addr: in6_addr,
+    pub dst_addr: in6_addr,
+    pub dscp: u8,
+    pub flabel: u32,
+    pub hlimit: u8,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_security_ipsec_tunnel_param__bindgen_ty_1 {
+    pub ipv4: rte_security_ipv4_tunnel_param,
+    pub ipv6: rte_security_ipv6_tunnel_param,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_security_ipsec_tunnel_param {
+    pub type_: rte_security_ipsec_tunnel_type::Type,
+    pub __bindgen_anon_1: rte_security_ipsec_tunnel_param__bindgen_ty_1,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_security_ipsec_xform {
+    pub spi: u32,
+    pub salt: u32,
+    pub options: rte_security_ipsec_sa_options,
+    pub direction: rte_security_ipsec_sa_direction::Type,
+    pub proto: rte_security_ipsec_sa_protocol::Type,
+    pub mode: rte_security_ipsec_sa_mode::Type,
+    pub tunnel: rte_security_ipsec_tunnel_param,
+    pub esn_soft_limit: u64,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_security_session {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_security_ctx {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_ipsec_sa {
+    _unused: [u8; 0],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_ipsec_sa_prm__bindgen_ty_1__bindgen_ty_1 {
+    pub hdr_len: u8,
+    pub hdr_l3_off: u8,
+    pub next_proto: u8,
+    pub hdr: *const ::std::os::raw::c_void,
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_ipsec_sa_prm__bindgen_ty_1__bindgen_ty_2 {
+    pub proto: u8,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_ipsec_sa_prm__bindgen_ty_1 {
+    pub tun: rte_ipsec_sa_prm__bindgen_ty_1__bindgen_ty_1,
+    pub trs: rte_ipsec_sa_prm__bindgen_ty_1__bindgen_ty_2,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct rte_ipsec_sa_prm {
+    pub userdata: u64,
+    pub flags: u64,
+    pub ipsec_xform: rte_security_ipsec_xform,
+    pub crypto_xform: *mut rte_crypto_sym_xform,
+    pub __bindgen_anon_1: rte_ipsec_sa_prm__bindgen_ty_1,
+    pub replay_win_sz: u32,
+}
+impl Default for rte_ipsec_sa_prm {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_ipsec_sa_pkt_func {
+    pub prepare: ::std::option::Option<
+        unsafe extern "C" fn(
+            ss: *const rte_ipsec_session,
+            mb: *mut *mut rte_mbuf,
+            cop: *mut *mut rte_crypto_op,
+            num: u16,
+        ) -> u16,
+    >,
+    pub process: ::std::option::Option<
+        unsafe extern "C" fn(
+            ss: *const rte_ipsec_session,
+            mb: *mut *mut rte_mbuf,
+            num: u16,
+        ) -> u16,
+    >,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_ipsec_session__bindgen_ty_1__bindgen_ty_1 {
+    pub ses: *mut rte_cryptodev_sym_session,
+    pub dev_id: u8,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_ipsec_session__bindgen_ty_1__bindgen_ty_2 {
+    pub ses: *mut rte_security_session,
+    pub ctx: *mut rte_security_ctx,
+    pub ol_flags: u32,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union rte_ipsec_session__bindgen_ty_1 {
+    pub crypto: rte_ipsec_session__bindgen_ty_1__bindgen_ty_1,
+    pub security: rte_ipsec_session__bindgen_ty_1__bindgen_ty_2,
+}
+#[repr(C)]
+#[repr(align(64))]
+#[derive(Copy, Clone)]
+pub struct rte_ipsec_session {
+    pub sa: *mut rte_ipsec_sa,
+    pub type_: rte_security_session_action_type::Type,
+    pub __bindgen_anon_1: rte_ipsec_session__bindgen_ty_1,
+    pub pkt_func: rte_ipsec_sa_pkt_func,
+}
+impl Default for rte_ipsec_session {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+extern "C" {
+    pub fn rte_ipsec_sa_type(sa: *const rte_ipsec_sa) -> u64;
+}
+extern "C" {
+    pub fn rte_ipsec_sa_size(prm: *const rte_ipsec_sa_prm) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_ipsec_sa_init(
+        sa: *mut rte_ipsec_sa,
+        prm: *const rte_ipsec_sa_prm,
+        size: u32,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_ipsec_sa_fini(sa: *mut rte_ipsec_sa);
+}
+extern "C" {
+    pub fn rte_ipsec_session_prepare(ss: *mut rte_ipsec_session) -> ::std::os::raw::c_int;
+}
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct rte_sched_pipe_params {
+    pub tb_rate: u32,
+    pub tb_size: u32,
+    pub tc_rate: [u32; 13usize],
+    pub tc_period: u32,
+    pub tc_ov_weight: u8,
+    pub wrr_weights: [u8; 4usize],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_sched_subport_params {
+    pub tb_rate: u32,
+    pub tb_size: u32,
+    pub tc_rate: [u32; 13usize],
+    pub tc_period: u32,
+    pub n_pipes_per_subport_enabled: u32,
+    pub qsize: [u16; 16usize],
+    pub pipe_profiles: *mut rte_sched_pipe_params,
+    pub n_pipe_profiles: u32,
+    pub n_max_pipe_profiles: u32,
+}
+impl Default for rte_sched_subport_params {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct rte_sched_port_params {
+    pub name: *const ::std::os::raw::c_char,
+    pub socket: ::std::os::raw::c_int,
+    pub rate: u32,
+    pub mtu: u32,
+    pub frame_overhead: u32,
+    pub n_subports_per_port: u32,
+    pub n_pipes_per_subport: u32,
+}
+impl Default for rte_sched_port_params {
+    fn default() -> Self {
+        unsafe { ::std::mem::zeroed() }
+    }
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_sched_port {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn rte_sched_port_config(params: *mut rte_sched_port_params) -> *mut rte_sched_port;
+}
+extern "C" {
+    pub fn rte_sched_port_free(port: *mut rte_sched_port);
+}
+extern "C" {
+    pub fn rte_sched_subport_config(
+        port: *mut rte_sched_port,
+        subport_id: u32,
+        params: *mut rte_sched_subport_params,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_sched_subport_pipe_profile_add(
+        port: *mut rte_sched_port,
+        subport_id: u32,
+        params: *mut rte_sched_pipe_params,
+        pipe_profile_id: *mut u32,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_sched_pipe_config(
+        port: *mut rte_sched_port,
+        subport_id: u32,
+        pipe_id: u32,
+        pipe_profile: i32,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_sched_port_pkt_write(
+        port: *mut rte_sched_port,
+        pkt: *mut rte_mbuf,
+        subport: u32,
+        pipe: u32,
+        traffic_class: u32,
+        queue: u32,
+        color: rte_color::Type,
+    );
+}
+extern "C" {
+    pub fn rte_sched_port_enqueue(
+        port: *mut rte_sched_port,
+        pkts: *mut *mut rte_mbuf,
+        n_pkts: u32,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_sched_port_dequeue(
+        port: *mut rte_sched_port,
+        pkts: *mut *mut rte_mbuf,
+        n_pkts: u32,
+    ) -> ::std::os::raw::c_int;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct rte_reorder_buffer {
+    _unused: [u8; 0],
+}
+extern "C" {
+    pub fn rte_reorder_create(
+        name: *const ::std::os::raw::c_char,
+        socket_id: ::std::os::raw::c_uint,
+        size: ::std::os::raw::c_uint,
+    ) -> *mut rte_reorder_buffer;
+}
+extern "C" {
+    pub fn rte_reorder_reset(b: *mut rte_reorder_buffer);
+}
+extern "C" {
+    pub fn rte_reorder_free(b: *mut rte_reorder_buffer);
+}
+extern "C" {
+    pub fn rte_reorder_insert(b: *mut rte_reorder_buffer, mbuf: *mut rte_mbuf)
+        -> ::std::os::raw::c_int;
+}
+extern "C" {
+    pub fn rte_reorder_drain(
+        b: *mut rte_reorder_buffer,
+        mbufs: *mut *mut rte_mbuf,
+        max_mbufs: ::std::os::raw::c_uint,
+    ) -> ::std::os::raw::c_uint;
+}
 pub type __builtin_va_list = [__va_list_tag; 1usize];
 #[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq)]
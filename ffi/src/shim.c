@@ -16,10 +16,25 @@
 * SPDX-License-Identifier: Apache-2.0
 */
 
+#include <rte_bus_pci.h>
+#include <rte_crypto.h>
+#include <rte_cryptodev.h>
+#include <rte_cycles.h>
 #include <rte_errno.h>
 #include <rte_ethdev.h>
+#include <rte_eventdev.h>
+#include <rte_hash_crc.h>
+#include <rte_ipsec.h>
+#include <rte_jhash.h>
+#include <rte_lcore.h>
+#include <rte_lpm.h>
+#include <rte_malloc.h>
 #include <rte_mbuf.h>
 #include <rte_mempool.h>
+#include <rte_meter.h>
+#include <rte_rcu_qsbr.h>
+#include <rte_ring.h>
+#include <rte_version.h>
 
 int _rte_errno(void) {
     return rte_errno;
@@ -62,3 +77,183 @@ uint16_t _rte_eth_tx_burst(
     uint16_t nb_pkts) {
     return rte_eth_tx_burst(port_id, queue_id, tx_pkts, nb_pkts);
 }
+
+unsigned int _rte_ring_enqueue_burst(
+    struct rte_ring *r,
+    void *const *obj_table,
+    unsigned int n,
+    unsigned int *free_space) {
+    return rte_ring_enqueue_burst(r, obj_table, n, free_space);
+}
+
+unsigned int _rte_ring_dequeue_burst(
+    struct rte_ring *r,
+    void **obj_table,
+    unsigned int n,
+    unsigned int *available) {
+    return rte_ring_dequeue_burst(r, obj_table, n, available);
+}
+
+uint64_t _rte_get_timer_hz(void) {
+    return rte_get_timer_hz();
+}
+
+uint64_t _rte_get_tsc_cycles(void) {
+    return rte_get_tsc_cycles();
+}
+
+unsigned _rte_lcore_id(void) {
+    return rte_lcore_id();
+}
+
+void _rte_malloc_dump_stats(const char *type) {
+    rte_malloc_dump_stats(stdout, type);
+}
+
+int _rte_lpm_lookup(
+    struct rte_lpm *lpm,
+    uint32_t ip,
+    uint32_t *next_hop) {
+    return rte_lpm_lookup(lpm, ip, next_hop);
+}
+
+int _rte_lpm_lookup_bulk(
+    const struct rte_lpm *lpm,
+    const uint32_t *ips,
+    uint32_t *next_hops,
+    unsigned int n) {
+    return rte_lpm_lookup_bulk(lpm, ips, next_hops, n);
+}
+
+uint32_t _rte_jhash(const void *key, uint32_t length, uint32_t initval) {
+    return rte_jhash(key, length, initval);
+}
+
+uint32_t _rte_hash_crc(const void *data, uint32_t data_len, uint32_t init_val) {
+    return rte_hash_crc(data, data_len, init_val);
+}
+
+struct rte_crypto_op *_rte_crypto_op_alloc(
+    struct rte_mempool *mempool,
+    enum rte_crypto_op_type type) {
+    return rte_crypto_op_alloc(mempool, type);
+}
+
+void _rte_crypto_op_free(struct rte_crypto_op *op) {
+    rte_crypto_op_free(op);
+}
+
+int _rte_crypto_op_attach_sym_session(
+    struct rte_crypto_op *op,
+    struct rte_cryptodev_sym_session *sess) {
+    return rte_crypto_op_attach_sym_session(op, sess);
+}
+
+uint16_t _rte_cryptodev_enqueue_burst(
+    uint8_t dev_id,
+    uint16_t qp_id,
+    struct rte_crypto_op **ops,
+    uint16_t nb_ops) {
+    return rte_cryptodev_enqueue_burst(dev_id, qp_id, ops, nb_ops);
+}
+
+uint16_t _rte_cryptodev_dequeue_burst(
+    uint8_t dev_id,
+    uint16_t qp_id,
+    struct rte_crypto_op **ops,
+    uint16_t nb_ops) {
+    return rte_cryptodev_dequeue_burst(dev_id, qp_id, ops, nb_ops);
+}
+
+uint16_t _rte_event_enqueue_burst(
+    uint8_t dev_id,
+    uint8_t port_id,
+    const struct rte_event ev[],
+    uint16_t nb_events) {
+    return rte_event_enqueue_burst(dev_id, port_id, ev, nb_events);
+}
+
+uint16_t _rte_event_dequeue_burst(
+    uint8_t dev_id,
+    uint8_t port_id,
+    struct rte_event ev[],
+    uint16_t nb_events,
+    uint64_t timeout_ticks) {
+    return rte_event_dequeue_burst(dev_id, port_id, ev, nb_events, timeout_ticks);
+}
+
+enum rte_color _rte_meter_srtcm_color_blind_check(
+    struct rte_meter_srtcm *m,
+    struct rte_meter_srtcm_profile *p,
+    uint64_t time,
+    uint32_t pkt_len) {
+    return rte_meter_srtcm_color_blind_check(m, p, time, pkt_len);
+}
+
+enum rte_color _rte_meter_trtcm_color_blind_check(
+    struct rte_meter_trtcm *m,
+    struct rte_meter_trtcm_profile *p,
+    uint64_t time,
+    uint32_t pkt_len) {
+    return rte_meter_trtcm_color_blind_check(m, p, time, pkt_len);
+}
+
+int _rte_pktmbuf_chain(struct rte_mbuf *head, struct rte_mbuf *tail) {
+    return rte_pktmbuf_chain(head, tail);
+}
+
+int _rte_pktmbuf_linearize(struct rte_mbuf *mbuf) {
+    return rte_pktmbuf_linearize(mbuf);
+}
+
+int _rte_pktmbuf_trim(struct rte_mbuf *m, uint16_t len) {
+    return rte_pktmbuf_trim(m, len);
+}
+
+uint16_t _rte_mbuf_refcnt_read(const struct rte_mbuf *m) {
+    return rte_mbuf_refcnt_read(m);
+}
+
+uint16_t _rte_mbuf_refcnt_update(struct rte_mbuf *m, int16_t value) {
+    return rte_mbuf_refcnt_update(m, value);
+}
+
+void _rte_pci_device_info(
+    const struct rte_device *dev,
+    struct rte_pci_addr *addr,
+    struct rte_pci_id *id) {
+    const struct rte_pci_device *pdev = container_of(dev, const struct rte_pci_device, device);
+    *addr = pdev->addr;
+    *id = pdev->id;
+}
+
+void _rte_rcu_qsbr_thread_online(struct rte_rcu_qsbr *v, unsigned int thread_id) {
+    rte_rcu_qsbr_thread_online(v, thread_id);
+}
+
+void _rte_rcu_qsbr_thread_offline(struct rte_rcu_qsbr *v, unsigned int thread_id) {
+    rte_rcu_qsbr_thread_offline(v, thread_id);
+}
+
+void _rte_rcu_qsbr_quiescent(struct rte_rcu_qsbr *v, unsigned int thread_id) {
+    rte_rcu_qsbr_quiescent(v, thread_id);
+}
+
+const char *_rte_version(void) {
+    return rte_version();
+}
+
+uint16_t _rte_ipsec_pkt_crypto_prepare(
+    const struct rte_ipsec_session *ss,
+    struct rte_mbuf *mb[],
+    struct rte_crypto_op *cop[],
+    uint16_t num) {
+    return rte_ipsec_pkt_crypto_prepare(ss, mb, cop, num);
+}
+
+uint16_t _rte_ipsec_pkt_process(
+    const struct rte_ipsec_session *ss,
+    struct rte_mbuf *mb[],
+    uint16_t num) {
+    return rte_ipsec_pkt_process(ss, mb, num);
+}